@@ -11,7 +11,7 @@ pub use crate::compositor::CompositingReason;
 pub use crate::compositor::IOCompositor;
 pub use crate::compositor::ShutdownState;
 pub use crate::compositor_thread::CompositorProxy;
-use embedder_traits::Cursor;
+use embedder_traits::{Cursor, DownloadId};
 use gfx_traits::Epoch;
 use ipc_channel::ipc::IpcSender;
 use keyboard_types::KeyboardEvent;
@@ -24,7 +24,7 @@ use script_traits::{
 use script_traits::{
     CompositorEvent, ConstellationControlMsg, LayoutControlMsg, MediaSessionActionType,
 };
-use servo_url::ServoUrl;
+use servo_url::{ImmutableOrigin, ServoUrl};
 use std::collections::HashMap;
 use std::fmt;
 use std::time::Duration;
@@ -110,6 +110,12 @@ pub enum ConstellationMsg {
     ExitFullScreen(TopLevelBrowsingContextId),
     /// Media session action.
     MediaSessionAction(MediaSessionActionType),
+    /// Stop a download.
+    CancelDownload(DownloadId),
+    /// Continue a download that was stopped.
+    ResumeDownload(DownloadId),
+    /// Clear the data every store holds for an origin.
+    ClearOriginData(ImmutableOrigin),
 }
 
 impl fmt::Debug for ConstellationMsg {
@@ -141,6 +147,9 @@ impl fmt::Debug for ConstellationMsg {
             DisableProfiler => "DisableProfiler",
             ExitFullScreen(..) => "ExitFullScreen",
             MediaSessionAction(..) => "MediaSessionAction",
+            CancelDownload(..) => "CancelDownload",
+            ResumeDownload(..) => "ResumeDownload",
+            ClearOriginData(..) => "ClearOriginData",
         };
         write!(formatter, "ConstellationMsg::{}", variant)
     }
@@ -4,7 +4,7 @@
 
 //! Abstract windowing methods. The concrete implementations of these can be found in `platform/`.
 
-use embedder_traits::EventLoopWaker;
+use embedder_traits::{DownloadId, EventLoopWaker};
 use euclid::Scale;
 #[cfg(feature = "gl")]
 use gleam::gl;
@@ -13,7 +13,7 @@ use msg::constellation_msg::{PipelineId, TopLevelBrowsingContextId, TraversalDir
 use script_traits::{MediaSessionActionType, MouseButton, TouchEventType, TouchId, WheelDelta};
 use servo_geometry::DeviceIndependentPixel;
 use servo_media::player::context::{GlApi, GlContext, NativeDisplay};
-use servo_url::ServoUrl;
+use servo_url::{ImmutableOrigin, ServoUrl};
 use std::fmt::{Debug, Error, Formatter};
 #[cfg(feature = "gl")]
 use std::rc::Rc;
@@ -105,6 +105,12 @@ pub enum WindowEvent {
     /// Sent when the user triggers a media action through the UA exposed media UI
     /// (play, pause, seek, etc.).
     MediaSessionAction(MediaSessionActionType),
+    /// Stop a download, keeping what was saved so far.
+    CancelDownload(DownloadId),
+    /// Continue a download that was cancelled or failed.
+    ResumeDownload(DownloadId),
+    /// Clear the data every store holds for an origin.
+    ClearOriginData(ImmutableOrigin),
 }
 
 impl Debug for WindowEvent {
@@ -136,6 +142,9 @@ impl Debug for WindowEvent {
             WindowEvent::ToggleSamplingProfiler(..) => write!(f, "ToggleSamplingProfiler"),
             WindowEvent::ExitFullScreen(..) => write!(f, "ExitFullScreen"),
             WindowEvent::MediaSessionAction(..) => write!(f, "MediaSessionAction"),
+            WindowEvent::CancelDownload(..) => write!(f, "CancelDownload"),
+            WindowEvent::ResumeDownload(..) => write!(f, "ResumeDownload"),
+            WindowEvent::ClearOriginData(..) => write!(f, "ClearOriginData"),
         }
     }
 }
@@ -168,6 +168,10 @@ mod gen {
                         enabled: bool,
                     }
                 },
+                caches: {
+                    enabled: bool,
+                    quota_mb: i64,
+                },
                 canvas_text: {
                     #[serde(rename = "dom.canvas-text.enabled")]
                     enabled: bool,
@@ -193,6 +197,9 @@ mod gen {
                 gamepad: {
                     enabled: bool,
                 },
+                indexeddb: {
+                    enabled: bool,
+                },
                 microdata: {
                     testing: {
                         enabled: bool,
@@ -216,6 +223,10 @@ mod gen {
                         allowed_in_nonsecure_contexts: bool,
                     }
                 },
+                quota: {
+                    global_limit_mb: i64,
+                    origin_limit_mb: i64,
+                },
                 serviceworker: {
                     enabled: bool,
                     timeout_seconds: i64,
@@ -464,10 +475,36 @@ mod gen {
                 http_cache: {
                     #[serde(rename = "network.http-cache.disabled")]
                     disabled: bool,
+                    disk: {
+                        #[serde(rename = "network.http-cache.disk.enabled")]
+                        enabled: bool,
+                        #[serde(rename = "network.http-cache.disk.max-size")]
+                        max_size: i64,
+                    },
                 },
                 mime: {
                     sniff: bool,
-                }
+                },
+                proxy: {
+                    http: String,
+                    https: String,
+                    #[serde(rename = "network.proxy.no-proxy")]
+                    no_proxy: String,
+                },
+                websocket: {
+                    permessage_deflate: {
+                        #[serde(rename = "network.websocket.permessage-deflate.enabled")]
+                        enabled: bool,
+                        #[serde(
+                            rename = "network.websocket.permessage-deflate.request-server-no-context-takeover"
+                        )]
+                        request_server_no_context_takeover: bool,
+                        #[serde(
+                            rename = "network.websocket.permessage-deflate.accept-client-no-context-takeover"
+                        )]
+                        accept_client_no_context_takeover: bool,
+                    },
+                },
             },
             session_history: {
                 #[serde(rename = "session-history.max-length")]
@@ -100,6 +100,7 @@ use crate::session_history::{
     JointSessionHistory, NeedsToReload, SessionHistoryChange, SessionHistoryDiff,
 };
 use crate::timer_scheduler::TimerScheduler;
+use crate::web_locks::WebLockManager;
 use background_hang_monitor::HangMonitorRegister;
 use backtrace::Backtrace;
 use bluetooth_traits::BluetoothRequest;
@@ -110,7 +111,7 @@ use compositing::compositor_thread::CompositorProxy;
 use compositing::compositor_thread::Msg as ToCompositorMsg;
 use compositing::{ConstellationMsg as FromCompositorMsg, SendableFrameTree};
 use crossbeam_channel::{after, never, unbounded, Receiver, Sender};
-use devtools_traits::{ChromeToDevtoolsControlMsg, DevtoolsControlMsg};
+use devtools_traits::{ChromeToDevtoolsControlMsg, DevtoolsControlMsg, WorkerId};
 use embedder_traits::{Cursor, EmbedderMsg, EmbedderProxy, EventLoopWaker};
 use embedder_traits::{MediaSessionEvent, MediaSessionPlaybackState};
 use euclid::{default::Size2D as UntypedSize2D, Size2D};
@@ -126,23 +127,28 @@ use log::{Level, LevelFilter, Log, Metadata, Record};
 use media::{GLPlayerThreads, WindowGLContext};
 use msg::constellation_msg::{BackgroundHangMonitorRegister, HangMonitorAlert, SamplerControlMsg};
 use msg::constellation_msg::{
-    BrowsingContextGroupId, BrowsingContextId, HistoryStateId, PipelineId,
-    TopLevelBrowsingContextId,
+    BroadcastChannelRouterId, MessagePortId, MessagePortRouterId, PipelineNamespace,
+    PipelineNamespaceId, PipelineNamespaceRequest, TraversalDirection,
 };
 use msg::constellation_msg::{
-    MessagePortId, MessagePortRouterId, PipelineNamespace, PipelineNamespaceId,
-    PipelineNamespaceRequest, TraversalDirection,
+    BrowsingContextGroupId, BrowsingContextId, HistoryStateId, PipelineId,
+    TopLevelBrowsingContextId,
 };
+use net_traits::cache_storage_thread::CacheStorageThreadMsg;
+use net_traits::indexeddb_thread::IndexedDBThreadMsg;
 use net_traits::pub_domains::reg_host;
+use net_traits::quota_thread::QuotaThreadMsg;
 use net_traits::request::RequestBuilder;
 use net_traits::storage_thread::{StorageThreadMsg, StorageType};
-use net_traits::{self, FetchResponseMsg, IpcSend, ResourceThreads};
+use net_traits::{self, CrossOriginOpenerPolicy, FetchMetadata, FetchResponseMsg};
+use net_traits::{IpcSend, ResourceThreads};
 use profile_traits::mem;
 use profile_traits::time;
 use script_traits::CompositorEvent::{MouseButtonEvent, MouseMoveEvent};
 use script_traits::{webdriver_msg, LogEntry, ScriptToConstellationChan, ServiceWorkerMsg};
 use script_traits::{
-    AnimationState, AnimationTickType, AuxiliaryBrowsingContextLoadInfo, CompositorEvent,
+    AnimationState, AnimationTickType, AuxiliaryBrowsingContextLoadInfo, BroadcastMsg,
+    CompositorEvent,
 };
 use script_traits::{ConstellationControlMsg, DiscardBrowsingContext};
 use script_traits::{DocumentActivity, DocumentState, LayoutControlMsg, LoadData, LoadOrigin};
@@ -154,6 +160,8 @@ use script_traits::{LayoutMsg as FromLayoutMsg, ScriptMsg as FromScriptMsg, Scri
 use script_traits::{MediaSessionActionType, MouseEventType};
 use script_traits::{MessagePortMsg, PortMessageTask, StructuredSerializedData};
 use script_traits::{SWManagerMsg, ScopeThings, UpdatePipelineIdReason, WebDriverCommandMsg};
+use script_traits::{ServiceWorkerClientInfo, ServiceWorkerLifecycleState};
+use script_traits::{SharedWorkerKey, SharedWorkerMsg};
 use serde::{Deserialize, Serialize};
 use servo_config::{opts, pref};
 use servo_rand::{random, Rng, ServoRng, SliceRandom};
@@ -201,6 +209,21 @@ enum TransferState {
     EntangledRemoved,
 }
 
+/// A shared worker tracked by the constellation.
+struct SharedWorkerInfo {
+    /// The id of the worker, once it started.
+    worker_id: Option<WorkerId>,
+    /// The sender of connections to the worker, once it started.
+    sender: Option<IpcSender<SharedWorkerMsg>>,
+    /// The connections made while the worker was starting.
+    pending_connections: Vec<StructuredSerializedData>,
+    /// The senders of error events to the clients that connected before the worker
+    /// loaded its script, `None` once it did.
+    error_senders: Option<Vec<IpcSender<()>>>,
+    /// The pipelines that connected to the worker, which lives as long as one of them does.
+    clients: HashSet<PipelineId>,
+}
+
 #[derive(Debug)]
 /// Info related to a message-port tracked by the constellation.
 struct MessagePortInfo {
@@ -398,6 +421,18 @@ pub struct Constellation<Message, LTF, STF> {
     /// A map of router-id to ipc-sender, to route messages to ports.
     message_port_routers: HashMap<MessagePortRouterId, IpcSender<MessagePortMsg>>,
 
+    /// A map of broadcast routers to their IPC sender.
+    broadcast_routers: HashMap<BroadcastChannelRouterId, IpcSender<BroadcastMsg>>,
+
+    /// A map of origin to a map of channel-name to a list of relevant routers.
+    broadcast_channels: HashMap<ImmutableOrigin, HashMap<String, Vec<BroadcastChannelRouterId>>>,
+
+    /// The shared workers, shared by every same-origin global connecting with the same URL and name.
+    shared_workers: HashMap<SharedWorkerKey, SharedWorkerInfo>,
+
+    /// The lock managers of the Web Locks API.
+    web_locks: WebLockManager,
+
     /// The set of all the pipelines in the browser.  (See the `pipeline` module
     /// for more details.)
     pipelines: HashMap<PipelineId, Pipeline>,
@@ -718,6 +753,35 @@ enum WebrenderMsg {
     Net(net_traits::WebrenderImageMsg),
 }
 
+/// <https://html.spec.whatwg.org/multipage/#check-if-coop-values-require-browsing-context-group-switch>
+fn coop_values_require_browsing_context_group_switch(
+    is_initial_about_blank: bool,
+    active_document_policy: CrossOriginOpenerPolicy,
+    active_document_origin: &ImmutableOrigin,
+    response_policy: CrossOriginOpenerPolicy,
+    response_origin: &ImmutableOrigin,
+) -> bool {
+    // Step 1
+    if active_document_policy == CrossOriginOpenerPolicy::UnsafeNone &&
+        response_policy == CrossOriginOpenerPolicy::UnsafeNone
+    {
+        return false;
+    }
+    // Step 2
+    if is_initial_about_blank &&
+        active_document_policy == CrossOriginOpenerPolicy::SameOriginAllowPopups &&
+        response_policy == CrossOriginOpenerPolicy::UnsafeNone
+    {
+        return false;
+    }
+    // Step 3
+    if active_document_policy == response_policy && active_document_origin == response_origin {
+        return false;
+    }
+    // Step 4
+    true
+}
+
 /// Accept messages from content processes that need to be relayed to the WebRender
 /// instance in the parent process.
 fn handle_webrender_message(webrender_api: &webrender_api::RenderApi, msg: WebrenderMsg) {
@@ -933,6 +997,10 @@ where
                     browsing_context_group_next_id: Default::default(),
                     message_ports: HashMap::new(),
                     message_port_routers: HashMap::new(),
+                    broadcast_routers: HashMap::new(),
+                    broadcast_channels: HashMap::new(),
+                    shared_workers: HashMap::new(),
+                    web_locks: WebLockManager::new(),
                     pipelines: HashMap::new(),
                     browsing_contexts: HashMap::new(),
                     pending_changes: vec![],
@@ -1477,6 +1545,18 @@ where
 
     fn handle_request_from_network_listener(&mut self, message: (PipelineId, FetchResponseMsg)) {
         let (id, message_) = message;
+        if let FetchResponseMsg::ProcessResponse(Ok(ref metadata)) = message_ {
+            let is_attachment = match *metadata {
+                FetchMetadata::Filtered { ref unsafe_, .. } => unsafe_.is_attachment(),
+                FetchMetadata::Unfiltered(ref metadata) => metadata.is_attachment(),
+            };
+            // The response is being downloaded instead, so the document that was
+            // to replace the current one is never created.
+            if is_attachment {
+                return self.handle_abort_load_url_msg(id);
+            }
+            self.handle_navigation_response_opener_policy(id, metadata);
+        }
         let result = match self.pipelines.get(&id) {
             Some(pipeline) => {
                 let msg = ConstellationControlMsg::NavigationResponse(id, message_);
@@ -1491,15 +1571,232 @@ where
         }
     }
 
+    /// Apply the cross-origin opener policy of the response to a navigation of a top-level
+    /// browsing context, which moves the browsing context to a new browsing context group
+    /// if the policy is incompatible with the one of the document it replaces.
+    /// https://html.spec.whatwg.org/multipage/#browsing-context-group-switches-due-to-cross-origin-opener-policy
+    fn handle_navigation_response_opener_policy(
+        &mut self,
+        pipeline_id: PipelineId,
+        metadata: &FetchMetadata,
+    ) {
+        let metadata = match *metadata {
+            FetchMetadata::Filtered { ref unsafe_, .. } => unsafe_,
+            FetchMetadata::Unfiltered(ref metadata) => metadata,
+        };
+        let (browsing_context_id, top_level_browsing_context_id) =
+            match self.pipelines.get(&pipeline_id) {
+                Some(pipeline) => (
+                    pipeline.browsing_context_id,
+                    pipeline.top_level_browsing_context_id,
+                ),
+                None => return warn!("Navigation response for closed pipeline {}.", pipeline_id),
+            };
+        // Only the documents of top-level browsing contexts have an opener policy.
+        if browsing_context_id != BrowsingContextId::from(top_level_browsing_context_id) {
+            return;
+        }
+
+        let response_policy = metadata
+            .headers
+            .as_ref()
+            .map(|headers| {
+                CrossOriginOpenerPolicy::from_response_headers(&metadata.final_url, headers)
+            })
+            .unwrap_or_default();
+        let response_origin = metadata.final_url.origin();
+        if let Some(pipeline) = self.pipelines.get_mut(&pipeline_id) {
+            pipeline.opener_policy = response_policy;
+        }
+
+        // A browsing context that has yet to be created is alone in a new group,
+        // unless it is an auxiliary, whose initial document is still loading.
+        let active_pipeline = self
+            .browsing_contexts
+            .get(&browsing_context_id)
+            .and_then(|browsing_context| self.pipelines.get(&browsing_context.pipeline_id));
+        let active_pipeline = match active_pipeline {
+            Some(active_pipeline) => active_pipeline,
+            None => return,
+        };
+        let is_initial_about_blank = active_pipeline.url.as_str() == "about:blank";
+        let active_origin = match active_pipeline.load_data.load_origin {
+            // The origin of an about:blank document is the origin of its creator.
+            LoadOrigin::Script(ref origin) if is_initial_about_blank => origin.clone(),
+            _ => active_pipeline.url.origin(),
+        };
+        if !coop_values_require_browsing_context_group_switch(
+            is_initial_about_blank,
+            active_pipeline.opener_policy,
+            &active_origin,
+            response_policy,
+            &response_origin,
+        ) {
+            return;
+        }
+        self.switch_browsing_context_group(top_level_browsing_context_id, pipeline_id);
+    }
+
+    /// Move a top-level browsing context to a new browsing context group of its own,
+    /// which severs the relationship between the document loading in `pipeline_id`
+    /// and its opener.
+    fn switch_browsing_context_group(
+        &mut self,
+        top_level_browsing_context_id: TopLevelBrowsingContextId,
+        pipeline_id: PipelineId,
+    ) {
+        debug!(
+            "Moving {} to a new browsing context group.",
+            top_level_browsing_context_id
+        );
+        for bc_group in self.browsing_context_group_set.values_mut() {
+            bc_group
+                .top_level_browsing_context_set
+                .remove(&top_level_browsing_context_id);
+        }
+
+        // https://html.spec.whatwg.org/multipage/#creating-a-new-browsing-context-group
+        let mut new_bc_group: BrowsingContextGroup = Default::default();
+        let new_bc_group_id = self.next_browsing_context_group_id();
+        new_bc_group
+            .top_level_browsing_context_set
+            .insert(top_level_browsing_context_id);
+
+        let pipeline = match self.pipelines.get_mut(&pipeline_id) {
+            Some(pipeline) => pipeline,
+            None => return warn!("Switching the group of closed pipeline {}.", pipeline_id),
+        };
+        pipeline.opener = None;
+        // TODO: the event loop of the pipeline may still be shared with documents
+        // of the previous group, since it was chosen before the response arrived.
+        if let Some(host) = reg_host(&pipeline.url) {
+            new_bc_group
+                .event_loops
+                .insert(host, Rc::downgrade(&pipeline.event_loop));
+        }
+        let result = pipeline
+            .event_loop
+            .send(ConstellationControlMsg::SeverOpener(pipeline_id));
+
+        self.browsing_context_group_set
+            .insert(new_bc_group_id.clone(), new_bc_group);
+        for browsing_context in self.browsing_contexts.values_mut() {
+            if browsing_context.top_level_id == top_level_browsing_context_id {
+                browsing_context.bc_group_id = new_bc_group_id.clone();
+            }
+        }
+
+        if let Err(e) = result {
+            self.handle_send_error(pipeline_id, e);
+        }
+    }
+
+    /// The opener policy the initial about:blank document of an auxiliary browsing context
+    /// created by the document in `creator_pipeline_id` inherits.
+    /// https://html.spec.whatwg.org/multipage/#creating-a-new-browsing-context
+    fn inherited_opener_policy(&self, creator_pipeline_id: PipelineId) -> CrossOriginOpenerPolicy {
+        let creator = match self.pipelines.get(&creator_pipeline_id) {
+            Some(creator) => creator,
+            None => return CrossOriginOpenerPolicy::UnsafeNone,
+        };
+        let top_level_browsing_context_id =
+            BrowsingContextId::from(creator.top_level_browsing_context_id);
+        let top_level = self
+            .browsing_contexts
+            .get(&top_level_browsing_context_id)
+            .and_then(|browsing_context| self.pipelines.get(&browsing_context.pipeline_id));
+        match top_level {
+            Some(top_level) if top_level.url.origin() == creator.url.origin() => {
+                top_level.opener_policy
+            },
+            _ => CrossOriginOpenerPolicy::UnsafeNone,
+        }
+    }
+
     fn handle_request_from_swmanager(&mut self, message: SWManagerMsg) {
         match message {
             SWManagerMsg::OwnSender(sw_sender) => {
                 // store service worker manager for communicating with it.
                 self.swmanager_chan = Some(sw_sender);
             },
+            SWManagerMsg::UpdateState(scope, state) => {
+                self.handle_update_service_worker_state(scope, state);
+            },
+            SWManagerMsg::MatchClients(scope, sender) => {
+                let clients = self.service_worker_clients(&scope);
+                if let Err(e) = sender.send(clients) {
+                    warn!("Failed to send service worker clients ({:?}).", e);
+                }
+            },
+            SWManagerMsg::SetController(pipeline_id, scope, script_url, claimed) => {
+                let msg = ConstellationControlMsg::SetServiceWorkerController(
+                    pipeline_id,
+                    scope,
+                    script_url,
+                    claimed,
+                );
+                let result = match self.pipelines.get(&pipeline_id) {
+                    Some(pipeline) => pipeline.event_loop.send(msg),
+                    None => {
+                        return warn!("Setting the controller of closed pipeline {}.", pipeline_id)
+                    },
+                };
+                if let Err(e) = result {
+                    self.handle_send_error(pipeline_id, e);
+                }
+            },
         }
     }
 
+    /// Tell every event loop with a document of the origin of a scope about the new
+    /// state of its service worker, once each.
+    fn handle_update_service_worker_state(
+        &mut self,
+        scope: ServoUrl,
+        state: ServiceWorkerLifecycleState,
+    ) {
+        let origin = scope.origin();
+        let mut event_loops: Vec<Rc<EventLoop>> = vec![];
+        for pipeline in self.pipelines.values() {
+            if pipeline.url.origin() != origin ||
+                event_loops
+                    .iter()
+                    .any(|event_loop| Rc::ptr_eq(event_loop, &pipeline.event_loop))
+            {
+                continue;
+            }
+            event_loops.push(pipeline.event_loop.clone());
+        }
+        for event_loop in event_loops {
+            let msg = ConstellationControlMsg::UpdateServiceWorkerState(scope.clone(), state);
+            if let Err(e) = event_loop.send(msg) {
+                warn!("Failed to send service worker state ({:?}).", e);
+            }
+        }
+    }
+
+    /// The active documents whose URL is in a scope.
+    /// <https://w3c.github.io/ServiceWorker/#clients-matchall>
+    fn service_worker_clients(&self, scope: &ServoUrl) -> Vec<ServiceWorkerClientInfo> {
+        let origin = scope.origin();
+        self.browsing_contexts
+            .values()
+            .filter_map(|browsing_context| {
+                let pipeline = self.pipelines.get(&browsing_context.pipeline_id)?;
+                if pipeline.url.origin() != origin ||
+                    !pipeline.url.as_str().starts_with(scope.as_str())
+                {
+                    return None;
+                }
+                Some(ServiceWorkerClientInfo {
+                    pipeline_id: pipeline.id,
+                    url: pipeline.url.clone(),
+                    top_level: browsing_context.parent_pipeline_id.is_none(),
+                })
+            })
+            .collect()
+    }
+
     fn handle_request_from_compositor(&mut self, message: FromCompositorMsg) {
         debug!("constellation got {:?} message", message);
         match message {
@@ -1681,6 +1978,26 @@ where
             FromCompositorMsg::MediaSessionAction(action) => {
                 self.handle_media_session_action_msg(action);
             },
+            // The public and private resource threads share their downloads.
+            FromCompositorMsg::CancelDownload(id) => {
+                let msg = net_traits::CoreResourceMsg::CancelDownload(id);
+                if let Err(e) = self.public_resource_threads.send(msg) {
+                    warn!("Resource thread unavailable ({})", e);
+                }
+            },
+            FromCompositorMsg::ResumeDownload(id) => {
+                let msg = net_traits::CoreResourceMsg::ResumeDownload(id);
+                if let Err(e) = self.public_resource_threads.send(msg) {
+                    warn!("Resource thread unavailable ({})", e);
+                }
+            },
+            // The quota manager clears the data of the origin from every store.
+            FromCompositorMsg::ClearOriginData(origin) => {
+                let msg = QuotaThreadMsg::ClearOrigin(origin);
+                if let Err(e) = self.public_resource_threads.send(msg) {
+                    warn!("Quota thread unavailable ({})", e);
+                }
+            },
         }
     }
 
@@ -1691,6 +2008,13 @@ where
             content, source_pipeline_id
         );
 
+        // Workers can outlive the pipeline they were started by, so their
+        // messaging doesn't need it to still exist.
+        let content = match self.handle_messaging_request(content) {
+            Some(content) => content,
+            None => return,
+        };
+
         let source_top_ctx_id = match self
             .pipelines
             .get(&source_pipeline_id)
@@ -1701,33 +2025,25 @@ where
         };
 
         match content {
-            FromScriptMsg::CompleteMessagePortTransfer(router_id, ports) => {
-                self.handle_complete_message_port_transfer(router_id, ports);
-            },
-            FromScriptMsg::MessagePortTransferResult(router_id, succeeded, failed) => {
-                self.handle_message_port_transfer_completed(router_id, succeeded);
-                self.handle_message_port_transfer_failed(failed);
-            },
-            FromScriptMsg::RerouteMessagePort(port_id, task) => {
-                self.handle_reroute_messageport(port_id, task);
-            },
-            FromScriptMsg::MessagePortShipped(port_id) => {
-                self.handle_messageport_shipped(port_id);
-            },
-            FromScriptMsg::NewMessagePortRouter(router_id, ipc_sender) => {
-                self.handle_new_messageport_router(router_id, ipc_sender);
-            },
-            FromScriptMsg::RemoveMessagePortRouter(router_id) => {
-                self.handle_remove_messageport_router(router_id);
+            FromScriptMsg::ConnectSharedWorker(key, port, response_sender, error_sender) => {
+                self.handle_connect_shared_worker(
+                    source_pipeline_id,
+                    key,
+                    port,
+                    response_sender,
+                    error_sender,
+                );
             },
-            FromScriptMsg::NewMessagePort(router_id, port_id) => {
-                self.handle_new_messageport(router_id, port_id);
+            FromScriptMsg::RequestWebLock(request) => {
+                self.web_locks.request(source_pipeline_id, request);
             },
-            FromScriptMsg::RemoveMessagePort(port_id) => {
-                self.handle_remove_messageport(port_id);
+            FromScriptMsg::ReleaseWebLock(origin, id) => {
+                self.web_locks.release(&origin, id);
             },
-            FromScriptMsg::EntanglePorts(port1, port2) => {
-                self.handle_entangle_messageports(port1, port2);
+            FromScriptMsg::QueryWebLocks(origin, response_sender) => {
+                if let Err(e) = response_sender.send(self.web_locks.query(&origin)) {
+                    warn!("Failed to reply to web locks query ({:?}).", e);
+                }
             },
             FromScriptMsg::ForwardToEmbedder(embedder_msg) => {
                 self.embedder_proxy
@@ -2300,6 +2616,290 @@ where
         self.message_port_routers.remove(&router_id);
     }
 
+    /// Handle the messages about message-ports, broadcast-channels and shared workers,
+    /// returning any other message.
+    fn handle_messaging_request(&mut self, content: FromScriptMsg) -> Option<FromScriptMsg> {
+        match content {
+            FromScriptMsg::CompleteMessagePortTransfer(router_id, ports) => {
+                self.handle_complete_message_port_transfer(router_id, ports);
+            },
+            FromScriptMsg::MessagePortTransferResult(router_id, succeeded, failed) => {
+                self.handle_message_port_transfer_completed(router_id, succeeded);
+                self.handle_message_port_transfer_failed(failed);
+            },
+            FromScriptMsg::RerouteMessagePort(port_id, task) => {
+                self.handle_reroute_messageport(port_id, task);
+            },
+            FromScriptMsg::MessagePortShipped(port_id) => {
+                self.handle_messageport_shipped(port_id);
+            },
+            FromScriptMsg::NewMessagePortRouter(router_id, ipc_sender) => {
+                self.handle_new_messageport_router(router_id, ipc_sender);
+            },
+            FromScriptMsg::RemoveMessagePortRouter(router_id) => {
+                self.handle_remove_messageport_router(router_id);
+            },
+            FromScriptMsg::NewMessagePort(router_id, port_id) => {
+                self.handle_new_messageport(router_id, port_id);
+            },
+            FromScriptMsg::RemoveMessagePort(port_id) => {
+                self.handle_remove_messageport(port_id);
+            },
+            FromScriptMsg::EntanglePorts(port1, port2) => {
+                self.handle_entangle_messageports(port1, port2);
+            },
+            FromScriptMsg::NewBroadcastChannelRouter(router_id, ipc_sender, origin) => {
+                self.handle_new_broadcast_channel_router(router_id, ipc_sender, origin);
+            },
+            FromScriptMsg::RemoveBroadcastChannelRouter(router_id, origin) => {
+                self.handle_remove_broadcast_channel_router(router_id, origin);
+            },
+            FromScriptMsg::NewBroadcastChannelNameInRouter(router_id, channel_name, origin) => {
+                self.handle_new_broadcast_channel_name_in_router(router_id, channel_name, origin);
+            },
+            FromScriptMsg::RemoveBroadcastChannelNameInRouter(router_id, channel_name, origin) => {
+                self.handle_remove_broadcast_channel_name_in_router(
+                    router_id,
+                    channel_name,
+                    origin,
+                );
+            },
+            FromScriptMsg::ScheduleBroadcast(router_id, message) => {
+                self.handle_schedule_broadcast(router_id, message);
+            },
+            FromScriptMsg::SharedWorkerStarted(key, worker_id, sender) => {
+                self.handle_shared_worker_started(key, worker_id, sender);
+            },
+            FromScriptMsg::SharedWorkerLoaded(key, worker_id) => {
+                self.handle_shared_worker_loaded(key, worker_id);
+            },
+            FromScriptMsg::SharedWorkerClosed(key, worker_id) => {
+                self.handle_shared_worker_closed(key, worker_id);
+            },
+            content => return Some(content),
+        }
+        None
+    }
+
+    /// <https://html.spec.whatwg.org/multipage/#dom-sharedworker>
+    /// Connect a client to the shared worker with the given key,
+    /// asking the client to start it if it isn't running yet.
+    fn handle_connect_shared_worker(
+        &mut self,
+        source_pipeline_id: PipelineId,
+        key: SharedWorkerKey,
+        port: StructuredSerializedData,
+        response_sender: IpcSender<bool>,
+        error_sender: IpcSender<()>,
+    ) {
+        let needs_start = !self.shared_workers.contains_key(&key);
+        let worker = self
+            .shared_workers
+            .entry(key)
+            .or_insert_with(|| SharedWorkerInfo {
+                worker_id: None,
+                sender: None,
+                pending_connections: vec![],
+                error_senders: Some(vec![]),
+                clients: HashSet::new(),
+            });
+        worker.clients.insert(source_pipeline_id);
+        if let Some(ref mut error_senders) = worker.error_senders {
+            error_senders.push(error_sender);
+        }
+        match worker.sender {
+            Some(ref sender) => {
+                if let Err(e) = sender.send(SharedWorkerMsg::Connect(port)) {
+                    warn!("Failed to connect to shared worker ({:?}).", e);
+                }
+            },
+            None => worker.pending_connections.push(port),
+        }
+        if let Err(e) = response_sender.send(needs_start) {
+            warn!("Failed to reply to shared worker connection ({:?}).", e);
+        }
+    }
+
+    /// A shared worker started, send it the connections made in the meantime.
+    fn handle_shared_worker_started(
+        &mut self,
+        key: SharedWorkerKey,
+        worker_id: WorkerId,
+        sender: IpcSender<SharedWorkerMsg>,
+    ) {
+        let worker = match self.shared_workers.get_mut(&key) {
+            Some(worker) => worker,
+            None => {
+                // All the clients went away while the worker was starting.
+                let _ = sender.send(SharedWorkerMsg::Terminate);
+                return;
+            },
+        };
+        for port in worker.pending_connections.drain(..) {
+            if let Err(e) = sender.send(SharedWorkerMsg::Connect(port)) {
+                warn!("Failed to connect to shared worker ({:?}).", e);
+            }
+        }
+        worker.worker_id = Some(worker_id);
+        worker.sender = Some(sender);
+    }
+
+    /// A shared worker loaded its script, its clients no longer need to hear about
+    /// a failure to do so.
+    fn handle_shared_worker_loaded(&mut self, key: SharedWorkerKey, worker_id: WorkerId) {
+        if let Some(worker) = self.shared_workers.get_mut(&key) {
+            if worker.worker_id == Some(worker_id) {
+                worker.error_senders = None;
+            }
+        }
+    }
+
+    /// A shared worker closed, the next client to connect starts a new one.
+    /// If it closed before loading its script, it failed to load it,
+    /// and an error event is fired at the clients that connected to it.
+    fn handle_shared_worker_closed(&mut self, key: SharedWorkerKey, worker_id: WorkerId) {
+        let is_current = self
+            .shared_workers
+            .get(&key)
+            .map_or(false, |worker| worker.worker_id == Some(worker_id));
+        if !is_current {
+            return;
+        }
+        if let Some(worker) = self.shared_workers.remove(&key) {
+            for error_sender in worker.error_senders.into_iter().flatten() {
+                if let Err(e) = error_sender.send(()) {
+                    warn!("Failed to report a shared worker error ({:?}).", e);
+                }
+            }
+        }
+    }
+
+    /// Terminate the shared workers that were only kept alive by the pipeline that exited.
+    fn handle_shared_worker_client_exited(&mut self, pipeline_id: PipelineId) {
+        self.shared_workers.retain(|_key, worker| {
+            worker.clients.remove(&pipeline_id);
+            if !worker.clients.is_empty() {
+                return true;
+            }
+            if let Some(ref sender) = worker.sender {
+                let _ = sender.send(SharedWorkerMsg::Terminate);
+            }
+            false
+        });
+    }
+
+    /// A global has started managing broadcast-channels.
+    fn handle_new_broadcast_channel_router(
+        &mut self,
+        router_id: BroadcastChannelRouterId,
+        broadcast_ipc_sender: IpcSender<BroadcastMsg>,
+        origin: ImmutableOrigin,
+    ) {
+        if self
+            .broadcast_routers
+            .insert(router_id, broadcast_ipc_sender)
+            .is_some()
+        {
+            warn!("Multiple attempts to add BroadcastChannel router.");
+        }
+        self.broadcast_channels.entry(origin).or_default();
+    }
+
+    /// A global has stopped managing broadcast-channels.
+    fn handle_remove_broadcast_channel_router(
+        &mut self,
+        router_id: BroadcastChannelRouterId,
+        origin: ImmutableOrigin,
+    ) {
+        if self.broadcast_routers.remove(&router_id).is_none() {
+            warn!("Attempt to remove unknown BroadcastChannel router.");
+        }
+        // Drop the router from the channels it was still listening to.
+        if let Entry::Occupied(mut channels) = self.broadcast_channels.entry(origin) {
+            for routers in channels.get_mut().values_mut() {
+                routers.retain(|id| *id != router_id);
+            }
+            channels
+                .get_mut()
+                .retain(|_name, routers| !routers.is_empty());
+            if channels.get().is_empty() {
+                channels.remove();
+            }
+        }
+    }
+
+    /// A global started listening on a broadcast-channel name.
+    fn handle_new_broadcast_channel_name_in_router(
+        &mut self,
+        router_id: BroadcastChannelRouterId,
+        channel_name: String,
+        origin: ImmutableOrigin,
+    ) {
+        if !self.broadcast_routers.contains_key(&router_id) {
+            return warn!("Attempt to add channel name to an unknown BroadcastChannel router.");
+        }
+        let routers = self
+            .broadcast_channels
+            .entry(origin)
+            .or_default()
+            .entry(channel_name)
+            .or_default();
+        if !routers.contains(&router_id) {
+            routers.push(router_id);
+        }
+    }
+
+    /// A global stopped listening on a broadcast-channel name.
+    fn handle_remove_broadcast_channel_name_in_router(
+        &mut self,
+        router_id: BroadcastChannelRouterId,
+        channel_name: String,
+        origin: ImmutableOrigin,
+    ) {
+        if let Some(channels) = self.broadcast_channels.get_mut(&origin) {
+            if let Entry::Occupied(mut routers) = channels.entry(channel_name) {
+                routers.get_mut().retain(|id| *id != router_id);
+                if routers.get().is_empty() {
+                    routers.remove();
+                }
+                return;
+            }
+        }
+        warn!("Attempt to remove a channel name from an unknown BroadcastChannel router.");
+    }
+
+    /// Broadcast a message to every other same-origin global listening on the channel,
+    /// in whichever script-process they live.
+    /// <https://html.spec.whatwg.org/multipage/#dom-broadcastchannel-postmessage>
+    fn handle_schedule_broadcast(
+        &self,
+        router_id: BroadcastChannelRouterId,
+        message: BroadcastMsg,
+    ) {
+        let routers = match self
+            .broadcast_channels
+            .get(&message.origin)
+            .and_then(|channels| channels.get(&message.channel_name))
+        {
+            Some(routers) => routers,
+            None => return,
+        };
+        // The sending global dispatches to its own channels itself.
+        for target_id in routers.iter().filter(|id| **id != router_id) {
+            match self.broadcast_routers.get(target_id) {
+                Some(sender) => {
+                    if let Err(e) = sender.send(message.clone()) {
+                        warn!(
+                            "Failed to broadcast message to router {}: {:?}",
+                            target_id, e
+                        );
+                    }
+                },
+                None => warn!("No sender for BroadcastChannel router {}.", target_id),
+            }
+        }
+    }
+
     fn handle_new_messageport(&mut self, router_id: MessagePortRouterId, port_id: MessagePortId) {
         match self.message_ports.entry(port_id) {
             // If it's a new port, we should not know about it.
@@ -2490,6 +3090,11 @@ where
         let (core_sender, core_receiver) = ipc::channel().expect("Failed to create IPC channel!");
         let (storage_sender, storage_receiver) =
             ipc::channel().expect("Failed to create IPC channel!");
+        let (indexeddb_sender, indexeddb_receiver) =
+            ipc::channel().expect("Failed to create IPC channel!");
+        let (cache_storage_sender, cache_storage_receiver) =
+            ipc::channel().expect("Failed to create IPC channel!");
+        let (quota_sender, quota_receiver) = ipc::channel().expect("Failed to create IPC channel!");
 
         debug!("Exiting core resource threads.");
         if let Err(e) = self
@@ -2519,6 +3124,30 @@ where
             warn!("Exit storage thread failed ({})", e);
         }
 
+        debug!("Exiting IndexedDB resource threads.");
+        if let Err(e) = self
+            .public_resource_threads
+            .send(IndexedDBThreadMsg::Exit(indexeddb_sender))
+        {
+            warn!("Exit IndexedDB thread failed ({})", e);
+        }
+
+        debug!("Exiting cache storage resource threads.");
+        if let Err(e) = self
+            .public_resource_threads
+            .send(CacheStorageThreadMsg::Exit(cache_storage_sender))
+        {
+            warn!("Exit cache storage thread failed ({})", e);
+        }
+
+        debug!("Exiting quota thread.");
+        if let Err(e) = self
+            .public_resource_threads
+            .send(QuotaThreadMsg::Exit(quota_sender))
+        {
+            warn!("Exit quota thread failed ({})", e);
+        }
+
         debug!("Exiting bluetooth thread.");
         if let Err(e) = self.bluetooth_thread.send(BluetoothRequest::Exit) {
             warn!("Exit bluetooth thread failed ({})", e);
@@ -2591,6 +3220,15 @@ where
         if let Err(e) = storage_receiver.recv() {
             warn!("Exit storage thread failed ({})", e);
         }
+        if let Err(e) = indexeddb_receiver.recv() {
+            warn!("Exit IndexedDB thread failed ({})", e);
+        }
+        if let Err(e) = cache_storage_receiver.recv() {
+            warn!("Exit cache storage thread failed ({})", e);
+        }
+        if let Err(e) = quota_receiver.recv() {
+            warn!("Exit quota thread failed ({})", e);
+        }
 
         debug!("Asking compositor to complete shutdown.");
         self.compositor_proxy
@@ -2600,6 +3238,8 @@ where
     fn handle_pipeline_exited(&mut self, pipeline_id: PipelineId) {
         debug!("Pipeline {:?} exited.", pipeline_id);
         self.pipelines.remove(&pipeline_id);
+        self.handle_shared_worker_client_exited(pipeline_id);
+        self.web_locks.remove_pipeline(pipeline_id);
     }
 
     fn handle_send_error(&mut self, pipeline_id: PipelineId, err: IpcError) {
@@ -2923,9 +3563,14 @@ where
         request_builder: RequestBuilder,
         cancel_chan: IpcReceiver<()>,
     ) {
+        let browser_id = match self.pipelines.get(&id) {
+            Some(pipeline) => pipeline.top_level_browsing_context_id,
+            None => return warn!("Navigation request for closed pipeline {}.", id),
+        };
         let listener = NetworkListener::new(
             request_builder,
             id,
+            browser_id,
             self.public_resource_threads.clone(),
             self.network_listener_sender.clone(),
         );
@@ -3133,7 +3778,7 @@ where
                     );
                 },
             };
-        let pipeline = Pipeline::new(
+        let mut pipeline = Pipeline::new(
             new_pipeline_id,
             new_browsing_context_id,
             new_top_level_browsing_context_id,
@@ -3144,6 +3789,7 @@ where
             is_opener_visible,
             load_data,
         );
+        pipeline.opener_policy = self.inherited_opener_policy(opener_pipeline_id);
 
         assert!(!self.pipelines.contains_key(&new_pipeline_id));
         self.pipelines.insert(new_pipeline_id, pipeline);
@@ -3956,6 +4602,21 @@ where
             Some(pipeline) => pipeline.top_level_browsing_context_id,
             None => return warn!("PostMessage from closed pipeline {:?}", source_pipeline),
         };
+        // Browsing contexts in different groups can't reach each other.
+        let source_bc_group_id = self
+            .browsing_contexts
+            .get(&BrowsingContextId::from(source_browsing_context))
+            .map(|browsing_context| browsing_context.bc_group_id.clone());
+        let target_bc_group_id = self
+            .browsing_contexts
+            .get(&browsing_context_id)
+            .map(|browsing_context| browsing_context.bc_group_id.clone());
+        if source_bc_group_id != target_bc_group_id {
+            return warn!(
+                "PostMessage to {} from another browsing context group.",
+                browsing_context_id
+            );
+        }
         let msg = ConstellationControlMsg::PostMessage {
             target: pipeline_id,
             source: source_pipeline,
@@ -10,7 +10,7 @@ use crossbeam_channel::Sender;
 use http::header::LOCATION;
 use ipc_channel::ipc;
 use ipc_channel::router::ROUTER;
-use msg::constellation_msg::PipelineId;
+use msg::constellation_msg::{PipelineId, TopLevelBrowsingContextId};
 use net::http_loader::{set_default_accept, set_default_accept_language};
 use net_traits::request::{Destination, Referrer, RequestBuilder};
 use net_traits::response::ResponseInit;
@@ -21,6 +21,7 @@ pub struct NetworkListener {
     res_init: Option<ResponseInit>,
     request_builder: RequestBuilder,
     pipeline_id: PipelineId,
+    browser_id: TopLevelBrowsingContextId,
     resource_threads: ResourceThreads,
     sender: Sender<(PipelineId, FetchResponseMsg)>,
     should_send: bool,
@@ -30,6 +31,7 @@ impl NetworkListener {
     pub fn new(
         request_builder: RequestBuilder,
         pipeline_id: PipelineId,
+        browser_id: TopLevelBrowsingContextId,
         resource_threads: ResourceThreads,
         sender: Sender<(PipelineId, FetchResponseMsg)>,
     ) -> NetworkListener {
@@ -37,6 +39,7 @@ impl NetworkListener {
             res_init: None,
             request_builder,
             pipeline_id,
+            browser_id,
             resource_threads,
             sender,
             should_send: false,
@@ -52,6 +55,7 @@ impl NetworkListener {
             resource_threads: self.resource_threads.clone(),
             sender: self.sender.clone(),
             pipeline_id: self.pipeline_id.clone(),
+            browser_id: self.browser_id,
             should_send: false,
         };
 
@@ -133,6 +137,21 @@ impl NetworkListener {
                         // Ideally the Fetch code would handle manual redirects on its own
                         self.initiate_fetch(None);
                     },
+                    _ if metadata.is_attachment() => {
+                        // The response is downloaded by the resource thread, which fetches
+                        // it again, and the constellation aborts the navigation.
+                        let msg = CoreResourceMsg::Download(
+                            self.request_builder.clone(),
+                            None,
+                            Some(self.browser_id),
+                        );
+                        if let Err(e) = self.resource_threads.sender().send(msg) {
+                            warn!("Resource thread unavailable ({})", e);
+                        }
+                        self.should_send = true;
+                        self.send(FetchResponseMsg::ProcessResponse(Ok(res_metadata)));
+                        self.should_send = false;
+                    },
                     _ => {
                         // Response should be processed by script thread.
                         self.should_send = true;
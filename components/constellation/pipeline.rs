@@ -27,7 +27,7 @@ use msg::constellation_msg::{
 };
 use net::image_cache::ImageCacheImpl;
 use net_traits::image_cache::ImageCache;
-use net_traits::{IpcSend, ResourceThreads};
+use net_traits::{CrossOriginOpenerPolicy, IpcSend, ResourceThreads};
 use profile_traits::mem as profile_mem;
 use profile_traits::time;
 use script_traits::{
@@ -98,6 +98,9 @@ pub struct Pipeline {
 
     /// Has this pipeline received a notification that it is completely loaded?
     pub completely_loaded: bool,
+
+    /// The cross-origin opener policy of the document in this pipeline.
+    pub opener_policy: CrossOriginOpenerPolicy,
 }
 
 /// Initial setup data needed to construct a pipeline.
@@ -379,6 +382,7 @@ impl Pipeline {
             history_state_id: None,
             history_states: HashSet::new(),
             completely_loaded: false,
+            opener_policy: CrossOriginOpenerPolicy::UnsafeNone,
         };
 
         pipeline.notify_visibility(is_visible);
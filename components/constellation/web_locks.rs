@@ -0,0 +1,190 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! The lock managers of the Web Locks API, one per origin, which every
+//! same-origin pipeline and worker shares.
+//! <https://wicg.github.io/web-locks/#lock-managers>
+
+use msg::constellation_msg::PipelineId;
+use script_traits::WebLocksSnapshot;
+use script_traits::{WebLockEvent, WebLockId, WebLockInfo, WebLockMode, WebLockRequest};
+use servo_url::ImmutableOrigin;
+use std::collections::{HashMap, VecDeque};
+
+/// A request for a lock, held or waiting in the queue of its name.
+struct WebLockEntry {
+    /// The pipeline that made the request, whose exit releases it.
+    pipeline_id: PipelineId,
+    request: WebLockRequest,
+}
+
+impl WebLockEntry {
+    fn info(&self) -> WebLockInfo {
+        WebLockInfo {
+            name: self.request.name.clone(),
+            mode: self.request.mode,
+            client_id: self.request.client_id.clone(),
+        }
+    }
+
+    fn send(&self, event: WebLockEvent) -> bool {
+        if let Err(e) = self.request.sender.send(event) {
+            warn!("Failed to send a web lock event ({:?}).", e);
+            return false;
+        }
+        true
+    }
+}
+
+/// The held locks and the lock request queue of a name.
+#[derive(Default)]
+struct WebLockState {
+    held: Vec<WebLockEntry>,
+    /// <https://wicg.github.io/web-locks/#lock-request-queue>
+    queue: VecDeque<WebLockEntry>,
+}
+
+impl WebLockState {
+    /// Whether a request with the given mode can be granted along with the held locks.
+    fn is_compatible(&self, mode: WebLockMode) -> bool {
+        match mode {
+            WebLockMode::Exclusive => self.held.is_empty(),
+            WebLockMode::Shared => self
+                .held
+                .iter()
+                .all(|entry| entry.request.mode == WebLockMode::Shared),
+        }
+    }
+
+    /// Grant the requests at the front of the queue, for as long as they are grantable.
+    /// <https://wicg.github.io/web-locks/#process-the-lock-request-queue>
+    fn process(&mut self) {
+        while let Some(mode) = self.queue.front().map(|entry| entry.request.mode) {
+            if !self.is_compatible(mode) {
+                return;
+            }
+            let entry = self.queue.pop_front().expect("Queue can't be empty here");
+            // A client that went away doesn't hold the lock.
+            if entry.send(WebLockEvent::Granted) {
+                self.held.push(entry);
+            }
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.held.is_empty() && self.queue.is_empty()
+    }
+}
+
+#[derive(Default)]
+pub struct WebLockManager {
+    /// The state of every name with held locks or pending requests, per origin.
+    origins: HashMap<ImmutableOrigin, HashMap<String, WebLockState>>,
+}
+
+impl WebLockManager {
+    pub fn new() -> WebLockManager {
+        WebLockManager::default()
+    }
+
+    /// <https://wicg.github.io/web-locks/#request-a-lock>
+    pub fn request(&mut self, pipeline_id: PipelineId, request: WebLockRequest) {
+        let origin = request.origin.clone();
+        let name = request.name.clone();
+        let entry = WebLockEntry {
+            pipeline_id,
+            request,
+        };
+        let state = self
+            .origins
+            .entry(origin.clone())
+            .or_insert_with(HashMap::new)
+            .entry(name.clone())
+            .or_insert_with(WebLockState::default);
+        if entry.request.steal {
+            // Step 5.
+            for held in state.held.drain(..) {
+                held.send(WebLockEvent::Stolen);
+            }
+            state.queue.push_front(entry);
+        } else if entry.request.if_available &&
+            !(state.queue.is_empty() && state.is_compatible(entry.request.mode))
+        {
+            // Step 6.
+            entry.send(WebLockEvent::NotAvailable);
+        } else {
+            // Step 7.
+            state.queue.push_back(entry);
+        }
+        // Step 8.
+        state.process();
+        self.remove_if_empty(&origin, &name);
+    }
+
+    /// Release a held lock, or abort a pending request, and grant the requests it blocked.
+    /// <https://wicg.github.io/web-locks/#release-the-lock>
+    /// <https://wicg.github.io/web-locks/#abort-the-request>
+    pub fn release(&mut self, origin: &ImmutableOrigin, id: WebLockId) {
+        let names = match self.origins.get_mut(origin) {
+            Some(names) => names,
+            None => return,
+        };
+        let name = names.iter_mut().find_map(|(name, state)| {
+            let held_len = state.held.len();
+            let queue_len = state.queue.len();
+            state.held.retain(|entry| entry.request.id != id);
+            state.queue.retain(|entry| entry.request.id != id);
+            if state.held.len() == held_len && state.queue.len() == queue_len {
+                return None;
+            }
+            state.process();
+            Some(name.clone())
+        });
+        if let Some(name) = name {
+            self.remove_if_empty(origin, &name);
+        }
+    }
+
+    /// Release the locks held and requested by a pipeline that exited.
+    pub fn remove_pipeline(&mut self, pipeline_id: PipelineId) {
+        for names in self.origins.values_mut() {
+            for state in names.values_mut() {
+                state.held.retain(|entry| entry.pipeline_id != pipeline_id);
+                state.queue.retain(|entry| entry.pipeline_id != pipeline_id);
+                state.process();
+            }
+            names.retain(|_name, state| !state.is_empty());
+        }
+        self.origins.retain(|_origin, names| !names.is_empty());
+    }
+
+    /// <https://wicg.github.io/web-locks/#snapshot-the-lock-state>
+    pub fn query(&self, origin: &ImmutableOrigin) -> WebLocksSnapshot {
+        let mut snapshot = WebLocksSnapshot::default();
+        if let Some(names) = self.origins.get(origin) {
+            for state in names.values() {
+                snapshot
+                    .held
+                    .extend(state.held.iter().map(WebLockEntry::info));
+                snapshot
+                    .pending
+                    .extend(state.queue.iter().map(WebLockEntry::info));
+            }
+        }
+        snapshot
+    }
+
+    fn remove_if_empty(&mut self, origin: &ImmutableOrigin, name: &str) {
+        let names = match self.origins.get_mut(origin) {
+            Some(names) => names,
+            None => return,
+        };
+        if names.get(name).map_or(false, WebLockState::is_empty) {
+            names.remove(name);
+        }
+        if names.is_empty() {
+            self.origins.remove(origin);
+        }
+    }
+}
@@ -116,6 +116,41 @@ pub enum PromptDefinition {
     YesNo(String, IpcSender<PromptResult>),
     /// Ask the user to enter text.
     Input(String, String, IpcSender<Option<String>>),
+    /// Ask the user for a user name and password to answer an HTTP authentication challenge.
+    Credentials(
+        AuthenticationChallenge,
+        IpcSender<Option<PromptCredentials>>,
+    ),
+}
+
+/// The scheme of an HTTP authentication challenge.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub enum AuthenticationScheme {
+    /// The credentials are sent as they are entered.
+    Basic,
+    /// Only a digest of the credentials is sent.
+    Digest,
+}
+
+/// An HTTP authentication challenge the user is asked to answer.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct AuthenticationChallenge {
+    /// The URL whose fetch was challenged.
+    pub url: ServoUrl,
+    /// The protection space named by the server. This is content-provided text.
+    pub realm: Option<String>,
+    /// The scheme the credentials will be sent with.
+    pub scheme: AuthenticationScheme,
+    /// Whether the challenge comes from the proxy the request went through,
+    /// rather than from the server.
+    pub is_proxy: bool,
+}
+
+/// The credentials entered by the user in response to an `AuthenticationChallenge`.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct PromptCredentials {
+    pub user_name: String,
+    pub password: String,
 }
 
 #[derive(Deserialize, PartialEq, Serialize)]
@@ -160,9 +195,9 @@ pub enum EmbedderMsg {
     /// Sends an unconsumed key event back to the embedder.
     Keyboard(KeyboardEvent),
     /// Gets system clipboard contents
-    GetClipboardContents(IpcSender<String>),
+    GetClipboardContents(IpcSender<ClipboardContents>),
     /// Sets system clipboard contents
-    SetClipboardContents(String),
+    SetClipboardContents(ClipboardContents),
     /// Changes the cursor.
     SetCursor(Cursor),
     /// A favicon was detected
@@ -196,6 +231,14 @@ pub enum EmbedderMsg {
     /// Notifies the embedder about media session events
     /// (i.e. when there is metadata for the active media session, playback state changes...).
     MediaSessionEvent(MediaSessionEvent),
+    /// A download of the given URL is starting. The embedder replies with the path of the file
+    /// to save it to, based on the suggested file name, or with `None` to decline it.
+    DownloadStarted(DownloadId, ServoUrl, String, IpcSender<Option<String>>),
+    /// The number of bytes of a download that are on disk, and the size of the whole
+    /// download when it is known.
+    DownloadProgress(DownloadId, u64, Option<u64>),
+    /// A download stopped. Unless it is complete, it can be resumed.
+    DownloadFinished(DownloadId, DownloadStatus),
 }
 
 impl Debug for EmbedderMsg {
@@ -229,8 +272,55 @@ impl Debug for EmbedderMsg {
             EmbedderMsg::BrowserCreated(..) => write!(f, "BrowserCreated"),
             EmbedderMsg::ReportProfile(..) => write!(f, "ReportProfile"),
             EmbedderMsg::MediaSessionEvent(..) => write!(f, "MediaSessionEvent"),
+            EmbedderMsg::DownloadStarted(..) => write!(f, "DownloadStarted"),
+            EmbedderMsg::DownloadProgress(..) => write!(f, "DownloadProgress"),
+            EmbedderMsg::DownloadFinished(..) => write!(f, "DownloadFinished"),
+        }
+    }
+}
+
+/// Identifies a download for as long as Servo runs.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct DownloadId(pub u32);
+
+/// The contents of the system clipboard: the same data, in one or more MIME types.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct ClipboardContents {
+    /// The MIME types of the data, with the data in each of them, most faithful first.
+    pub representations: Vec<(String, Vec<u8>)>,
+}
+
+impl ClipboardContents {
+    pub fn from_text(text: String) -> ClipboardContents {
+        ClipboardContents {
+            representations: vec![("text/plain".to_owned(), text.into_bytes())],
         }
     }
+
+    /// The data in a MIME type, if it is available in it.
+    pub fn get(&self, mime_type: &str) -> Option<&[u8]> {
+        self.representations
+            .iter()
+            .find(|(representation_type, _)| representation_type == mime_type)
+            .map(|(_, data)| &**data)
+    }
+
+    /// The data as plain text, if it is available as such.
+    pub fn text(&self) -> Option<String> {
+        self.get("text/plain")
+            .map(|data| String::from_utf8_lossy(data).into_owned())
+    }
+}
+
+/// How a download stopped.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub enum DownloadStatus {
+    /// The whole resource is on disk.
+    Complete,
+    /// The download was cancelled, by the embedder or because no file could be chosen for it.
+    Cancelled,
+    /// The download failed, for the given reason.
+    Failed(String),
 }
 
 /// Filter for file selection;
@@ -13,7 +13,7 @@ use crate::platform::font_list::SANS_SERIF_FONT_FAMILY;
 use crate::platform::font_template::FontTemplateData;
 use app_units::Au;
 use ipc_channel::ipc::{self, IpcReceiver, IpcSender};
-use net_traits::request::{Destination, RequestBuilder};
+use net_traits::request::{CspContext, Destination, RequestBuilder};
 use net_traits::{fetch_async, CoreResourceThread, FetchResponseMsg};
 use servo_atoms::Atom;
 use servo_url::ServoUrl;
@@ -113,7 +113,12 @@ pub enum Command {
         Au,
         IpcSender<webrender_api::FontInstanceKey>,
     ),
-    AddWebFont(LowercaseString, EffectiveSources, IpcSender<()>),
+    AddWebFont(
+        LowercaseString,
+        EffectiveSources,
+        IpcSender<()>,
+        Option<CspContext>,
+    ),
     AddDownloadedWebFont(LowercaseString, ServoUrl, Vec<u8>, IpcSender<()>),
     Exit(IpcSender<()>),
     Ping,
@@ -195,8 +200,8 @@ impl FontCache {
 
                     let _ = result.send(instance_key);
                 },
-                Command::AddWebFont(family_name, sources, result) => {
-                    self.handle_add_web_font(family_name, sources, result);
+                Command::AddWebFont(family_name, sources, result, csp_context) => {
+                    self.handle_add_web_font(family_name, sources, result, csp_context);
                 },
                 Command::AddDownloadedWebFont(family_name, url, bytes, result) => {
                     let templates = &mut self.web_families.get_mut(&family_name).unwrap();
@@ -217,6 +222,7 @@ impl FontCache {
         family_name: LowercaseString,
         mut sources: EffectiveSources,
         sender: IpcSender<()>,
+        csp_context: Option<CspContext>,
     ) {
         let src = if let Some(src) = sources.next() {
             src
@@ -238,7 +244,12 @@ impl FontCache {
                     None => return,
                 };
 
-                let request = RequestBuilder::new(url.clone()).destination(Destination::Font);
+                let mut request = RequestBuilder::new(url.clone()).destination(Destination::Font);
+                if let Some(ref csp_context) = csp_context {
+                    request = request
+                        .origin(csp_context.origin.clone())
+                        .csp_list(csp_context.csp_list.clone());
+                }
 
                 let channel_to_self = self.channel_to_self.clone();
                 let bytes = Mutex::new(Vec::new());
@@ -248,6 +259,11 @@ impl FontCache {
                     match response {
                         FetchResponseMsg::ProcessRequestBody |
                         FetchResponseMsg::ProcessRequestEOF => (),
+                        FetchResponseMsg::ProcessCspViolations(violations) => {
+                            if let Some(ref csp_context) = csp_context {
+                                let _ = csp_context.violation_sender.send(violations);
+                            }
+                        },
                         FetchResponseMsg::ProcessResponse(meta_result) => {
                             trace!(
                                 "@font-face {} metadata ok={:?}",
@@ -269,6 +285,7 @@ impl FontCache {
                                     family_name.clone(),
                                     sources.clone(),
                                     sender.clone(),
+                                    csp_context.clone(),
                                 );
                                 channel_to_self.send(msg).unwrap();
                                 return;
@@ -288,6 +305,7 @@ impl FontCache {
                                         family_name.clone(),
                                         sources.clone(),
                                         sender.clone(),
+                                        csp_context.clone(),
                                     );
                                     channel_to_self.send(msg).unwrap();
                                     return;
@@ -315,7 +333,7 @@ impl FontCache {
                 if found {
                     sender.send(()).unwrap();
                 } else {
-                    let msg = Command::AddWebFont(family_name, sources, sender);
+                    let msg = Command::AddWebFont(family_name, sources, sender, csp_context);
                     self.channel_to_self.send(msg).unwrap();
                 }
             },
@@ -478,12 +496,14 @@ impl FontCacheThread {
         family: FamilyName,
         sources: EffectiveSources,
         sender: IpcSender<()>,
+        csp_context: Option<CspContext>,
     ) {
         self.chan
             .send(Command::AddWebFont(
                 LowercaseString::new(&family.name),
                 sources,
                 sender,
+                csp_context,
             ))
             .unwrap();
     }
@@ -3017,17 +3017,52 @@ impl IndexableText {
         self.inner.get(&node).map(|x| x.as_slice())
     }
 
-    // Returns the text index within a node for the point of interest.
+    // Returns the text index within a node for the point of interest, given relative to the
+    // first item of the node.
     pub fn text_index(&self, node: OpaqueNode, point_in_item: Point2D<Au>) -> Option<usize> {
-        let item = self.inner.get(&node)?;
-        // TODO(#20020): access all elements
-        let point = point_in_item + item[0].origin.to_vector();
-        let offset = point - item[0].baseline_origin;
-        Some(
-            item[0]
-                .text_run
-                .range_index_of_advance(&item[0].range, offset.x),
-        )
+        let items = self.inner.get(&node)?;
+        let point = point_in_item + items[0].origin.to_vector();
+
+        // Find the item on the line the point is on, that starts before the point.
+        let line_y = items
+            .iter()
+            .map(|item| item.origin.y)
+            .filter(|y| *y <= point.y)
+            .max()
+            .unwrap_or(items[0].origin.y);
+        let line: Vec<usize> = (0..items.len())
+            .filter(|index| items[*index].origin.y == line_y)
+            .collect();
+        let index = line
+            .iter()
+            .cloned()
+            .filter(|index| items[*index].origin.x <= point.x)
+            .last()
+            .unwrap_or(line[0]);
+        let item = &items[index];
+
+        // Count the characters laid out before that item, including the white space
+        // dropped between lines when the items share a text run.
+        let first = &items[0];
+        let preceding_characters = if Arc::ptr_eq(&item.text_run, &first.text_run) &&
+            item.range.begin() >= first.range.begin()
+        {
+            item.text_run.text[first.range.begin().to_usize()..item.range.begin().to_usize()]
+                .chars()
+                .count()
+        } else {
+            items[..index]
+                .iter()
+                .map(|item| {
+                    item.text_run.text[item.range.begin().to_usize()..item.range.end().to_usize()]
+                        .chars()
+                        .count()
+                })
+                .sum()
+        };
+
+        let offset = point - item.baseline_origin;
+        Some(preceding_characters + item.text_run.range_index_of_advance(&item.range, offset.x))
     }
 }
 
@@ -74,6 +74,7 @@ use msg::constellation_msg::{
 use msg::constellation_msg::{BrowsingContextId, MonitoredComponentId, TopLevelBrowsingContextId};
 use msg::constellation_msg::{LayoutHangAnnotation, MonitoredComponentType, PipelineId};
 use net_traits::image_cache::{ImageCache, UsePlaceholder};
+use net_traits::request::CspContext;
 use parking_lot::RwLock;
 use profile_traits::mem::{self as profile_mem, Report, ReportKind, ReportsChan};
 use profile_traits::time::{self as profile_time, profile, TimerMetadata};
@@ -254,6 +255,9 @@ pub struct LayoutThread {
     /// Load web fonts synchronously to avoid non-deterministic network-driven reflows.
     load_webfonts_synchronously: bool,
 
+    /// The content security policies that web fonts are fetched under.
+    csp_context: Option<CspContext>,
+
     /// Dumps the display list form after a layout.
     dump_display_list: bool,
 
@@ -472,6 +476,7 @@ fn add_font_face_rules(
     font_cache_sender: &IpcSender<()>,
     outstanding_web_fonts_counter: &Arc<AtomicUsize>,
     load_webfonts_synchronously: bool,
+    csp_context: &Option<CspContext>,
 ) {
     if load_webfonts_synchronously {
         let (sender, receiver) = ipc::channel().unwrap();
@@ -482,6 +487,7 @@ fn add_font_face_rules(
                     font_face.family().clone(),
                     effective_sources,
                     sender.clone(),
+                    csp_context.clone(),
                 );
                 receiver.recv().unwrap();
             }
@@ -495,6 +501,7 @@ fn add_font_face_rules(
                     font_face.family().clone(),
                     effective_sources,
                     (*font_cache_sender).clone(),
+                    csp_context.clone(),
                 );
             }
         })
@@ -614,6 +621,7 @@ impl LayoutThread {
             last_iframe_sizes: Default::default(),
             busy,
             load_webfonts_synchronously,
+            csp_context: None,
             dump_display_list,
             dump_display_list_json,
             dump_style_tree,
@@ -698,6 +706,7 @@ impl LayoutThread {
             Msg::GetWebFontLoadState(..) => LayoutHangAnnotation::GetWebFontLoadState,
             Msg::CreateLayoutThread(..) => LayoutHangAnnotation::CreateLayoutThread,
             Msg::SetFinalUrl(..) => LayoutHangAnnotation::SetFinalUrl,
+            Msg::SetCspContext(..) => LayoutHangAnnotation::SetCspContext,
             Msg::SetScrollStates(..) => LayoutHangAnnotation::SetScrollStates,
             Msg::UpdateScrollStateFromScript(..) => {
                 LayoutHangAnnotation::UpdateScrollStateFromScript
@@ -848,6 +857,9 @@ impl LayoutThread {
             Msg::SetFinalUrl(final_url) => {
                 self.url = final_url;
             },
+            Msg::SetCspContext(csp_context) => {
+                self.csp_context = Some(csp_context);
+            },
             Msg::RegisterPaint(name, mut properties, painter) => {
                 debug!("Registering the painter");
                 let properties = properties
@@ -1007,6 +1019,7 @@ impl LayoutThread {
                 &self.font_cache_sender,
                 &self.outstanding_web_fonts,
                 self.load_webfonts_synchronously,
+                &self.csp_context,
             );
         }
     }
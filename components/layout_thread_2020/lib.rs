@@ -57,6 +57,7 @@ use msg::constellation_msg::{
 use msg::constellation_msg::{LayoutHangAnnotation, MonitoredComponentType, PipelineId};
 use msg::constellation_msg::{MonitoredComponentId, TopLevelBrowsingContextId};
 use net_traits::image_cache::{ImageCache, UsePlaceholder};
+use net_traits::request::CspContext;
 use parking_lot::RwLock;
 use profile_traits::mem::{self as profile_mem, Report, ReportKind, ReportsChan};
 use profile_traits::time::{self as profile_time, profile, TimerMetadata};
@@ -217,6 +218,9 @@ pub struct LayoutThread {
     /// Load web fonts synchronously to avoid non-deterministic network-driven reflows.
     load_webfonts_synchronously: bool,
 
+    /// The content security policies that web fonts are fetched under.
+    csp_context: Option<CspContext>,
+
     /// Dumps the display list form after a layout.
     dump_display_list: bool,
 
@@ -425,6 +429,7 @@ fn add_font_face_rules(
     font_cache_sender: &IpcSender<()>,
     outstanding_web_fonts_counter: &Arc<AtomicUsize>,
     load_webfonts_synchronously: bool,
+    csp_context: &Option<CspContext>,
 ) {
     if load_webfonts_synchronously {
         let (sender, receiver) = ipc::channel().unwrap();
@@ -435,6 +440,7 @@ fn add_font_face_rules(
                     font_face.family().clone(),
                     effective_sources,
                     sender.clone(),
+                    csp_context.clone(),
                 );
                 receiver.recv().unwrap();
             }
@@ -448,6 +454,7 @@ fn add_font_face_rules(
                     font_face.family().clone(),
                     effective_sources,
                     (*font_cache_sender).clone(),
+                    csp_context.clone(),
                 );
             }
         })
@@ -561,6 +568,7 @@ impl LayoutThread {
             paint_time_metrics: paint_time_metrics,
             busy,
             load_webfonts_synchronously,
+            csp_context: None,
             relayout_event,
             dump_display_list,
             dump_display_list_json,
@@ -638,6 +646,7 @@ impl LayoutThread {
             Msg::GetWebFontLoadState(..) => LayoutHangAnnotation::GetWebFontLoadState,
             Msg::CreateLayoutThread(..) => LayoutHangAnnotation::CreateLayoutThread,
             Msg::SetFinalUrl(..) => LayoutHangAnnotation::SetFinalUrl,
+            Msg::SetCspContext(..) => LayoutHangAnnotation::SetCspContext,
             Msg::SetScrollStates(..) => LayoutHangAnnotation::SetScrollStates,
             Msg::UpdateScrollStateFromScript(..) => {
                 LayoutHangAnnotation::UpdateScrollStateFromScript
@@ -788,6 +797,9 @@ impl LayoutThread {
             Msg::SetFinalUrl(final_url) => {
                 self.url = final_url;
             },
+            Msg::SetCspContext(csp_context) => {
+                self.csp_context = Some(csp_context);
+            },
             Msg::RegisterPaint(_name, _properties, _painter) => {},
             Msg::PrepareToExit(response_chan) => {
                 self.prepare_to_exit(response_chan);
@@ -909,6 +921,7 @@ impl LayoutThread {
                 &self.font_cache_sender,
                 &self.outstanding_web_fonts,
                 self.load_webfonts_synchronously,
+                &self.csp_context,
             );
         }
     }
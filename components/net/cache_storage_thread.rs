@@ -0,0 +1,561 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! The backend of the Cache API, which owns the caches of every origin.
+//!
+//! The caches of an origin are kept in memory, and each of their entries is written to
+//! the journal of the origin as soon as it is stored. Every entry counts against the quota
+//! of the caches of its origin, and against the quota of the origin itself, which a batch
+//! of operations can't exceed.
+
+use crate::journal::{decode_binary, encode_binary, Journal};
+use crate::quota_thread::QuotaAccount;
+use http::{HeaderMap, Method};
+use ipc_channel::ipc::{self, IpcReceiver, IpcSender};
+use net_traits::cache_storage_thread::CachedResponse;
+use net_traits::cache_storage_thread::{CacheBatchOperation, CacheId, CacheQueryOptions};
+use net_traits::cache_storage_thread::{CacheRequest, CacheStorageError, CacheStorageThreadMsg};
+use net_traits::quota_thread::{QuotaClient, QuotaThreadMsg};
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use servo_url::{ImmutableOrigin, ServoUrl};
+use std::collections::HashMap;
+use std::mem;
+use std::path::PathBuf;
+use std::thread;
+use url::Url;
+
+pub trait CacheStorageThreadFactory {
+    fn new(config_dir: Option<PathBuf>, quota: Option<IpcSender<QuotaThreadMsg>>) -> Self;
+}
+
+impl CacheStorageThreadFactory for IpcSender<CacheStorageThreadMsg> {
+    /// Create a cache storage thread
+    fn new(
+        config_dir: Option<PathBuf>,
+        quota: Option<IpcSender<QuotaThreadMsg>>,
+    ) -> IpcSender<CacheStorageThreadMsg> {
+        let (chan, port) = ipc::channel().unwrap();
+        let quota = QuotaAccount::new(QuotaClient::CacheStorage, quota);
+        thread::Builder::new()
+            .name("CacheStorageManager".to_owned())
+            .spawn(move || {
+                CacheStorageManager::new(port, config_dir, quota).start();
+            })
+            .expect("Thread spawning failed");
+        chan
+    }
+}
+
+/// The key of a cache, or of one of its entries, in the journal of its origin.
+fn journal_key(cache: &str, entry: Option<u64>) -> String {
+    serde_json::to_string(&(cache, entry)).unwrap_or_default()
+}
+
+/// The number of bytes the caches of an origin can hold.
+fn quota() -> u64 {
+    pref!(dom.caches.quota_mb).max(0) as u64 * 1024 * 1024
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+struct Entry {
+    /// Orders the entries of a cache by when they were stored, and keys them in the journal.
+    id: u64,
+    request: CacheRequest,
+    response: CachedResponse,
+}
+
+impl Entry {
+    /// The number of bytes the entry counts for against the quota of its origin.
+    fn size(&self) -> u64 {
+        let headers_size = |headers: &HeaderMap| -> usize {
+            headers
+                .iter()
+                .map(|(name, value)| name.as_str().len() + value.len())
+                .sum()
+        };
+        let response = &self.response;
+        let size = self.request.url.as_str().len() +
+            headers_size(&self.request.headers) +
+            response
+                .url_list
+                .iter()
+                .map(|url| url.as_str().len())
+                .sum::<usize>() +
+            response
+                .raw_status
+                .as_ref()
+                .map_or(0, |&(_, ref text)| text.len()) +
+            headers_size(&response.headers) +
+            response.body.as_ref().map_or(0, |body| body.len());
+        size as u64
+    }
+}
+
+/// The URL a request is matched by, which ignores fragments.
+fn url_to_match(url: &ServoUrl, options: &CacheQueryOptions) -> Url {
+    let mut url = url.as_url().clone();
+    url.set_fragment(None);
+    if options.ignore_search {
+        url.set_query(None);
+    }
+    url
+}
+
+/// <https://w3c.github.io/ServiceWorker/#request-matches-cached-item-algorithm>
+fn request_matches_cached_item(
+    query: &CacheRequest,
+    entry: &Entry,
+    options: &CacheQueryOptions,
+) -> bool {
+    // Step 1.
+    if !options.ignore_method && query.method != Method::GET {
+        return false;
+    }
+
+    // Steps 2-5.
+    if url_to_match(&query.url, options) != url_to_match(&entry.request.url, options) {
+        return false;
+    }
+
+    // Step 6.
+    if options.ignore_vary {
+        return true;
+    }
+
+    // Steps 7-8.
+    entry
+        .response
+        .vary_fields()
+        .iter()
+        .all(|field| field != "*" && entry.request.header_value(field) == query.header_value(field))
+}
+
+/// Whether `entry` is in the result of querying a cache with `query`.
+/// <https://w3c.github.io/ServiceWorker/#query-cache>
+fn matches(query: Option<&CacheRequest>, entry: &Entry, options: &CacheQueryOptions) -> bool {
+    query.map_or(true, |query| {
+        request_matches_cached_item(query, entry, options)
+    })
+}
+
+struct Cache {
+    origin: ImmutableOrigin,
+    /// The name of the cache, or `None` once it was deleted and only its id refers to it.
+    name: Option<String>,
+    entries: Vec<Entry>,
+    next_entry_id: u64,
+}
+
+impl Cache {
+    fn new(origin: &ImmutableOrigin, name: &str) -> Cache {
+        Cache {
+            origin: origin.clone(),
+            name: Some(name.to_owned()),
+            entries: vec![],
+            next_entry_id: 0,
+        }
+    }
+
+    fn query<'a>(
+        &'a self,
+        query: Option<&'a CacheRequest>,
+        options: &'a CacheQueryOptions,
+    ) -> impl Iterator<Item = &'a Entry> + 'a {
+        self.entries
+            .iter()
+            .filter(move |entry| matches(query, entry, options))
+    }
+
+    /// Remove the entries that match `query`, returning them.
+    fn remove(&mut self, query: &CacheRequest, options: &CacheQueryOptions) -> Vec<Entry> {
+        let (removed, kept): (Vec<Entry>, Vec<Entry>) = mem::replace(&mut self.entries, vec![])
+            .into_iter()
+            .partition(|entry| matches(Some(query), entry, options));
+        self.entries = kept;
+        removed
+    }
+
+    /// <https://w3c.github.io/ServiceWorker/#batch-cache-operations-algorithm>
+    ///
+    /// Returns the entries the operations added and removed.
+    fn batch(
+        &mut self,
+        operations: Vec<CacheBatchOperation>,
+    ) -> Result<(Vec<Entry>, Vec<Entry>), CacheStorageError> {
+        let mut added: Vec<Entry> = vec![];
+        let mut removed = vec![];
+        for operation in operations {
+            // Step 3.3.3.
+            let (request, options) = match operation {
+                CacheBatchOperation::Put { ref request, .. } => {
+                    (request, CacheQueryOptions::default())
+                },
+                CacheBatchOperation::Delete {
+                    ref request,
+                    ref options,
+                } => (request, *options),
+            };
+            if added
+                .iter()
+                .any(|entry| matches(Some(request), entry, &options))
+            {
+                return Err(CacheStorageError::InvalidState);
+            }
+
+            match operation {
+                // Step 3.3.5.
+                CacheBatchOperation::Delete { request, options } => {
+                    removed.extend(self.remove(&request, &options));
+                },
+                // Step 3.3.6.
+                CacheBatchOperation::Put { request, response } => {
+                    removed.extend(self.remove(&request, &CacheQueryOptions::default()));
+                    let entry = Entry {
+                        id: self.next_entry_id,
+                        request,
+                        response,
+                    };
+                    self.next_entry_id += 1;
+                    self.entries.push(entry.clone());
+                    added.push(entry);
+                },
+            }
+        }
+        Ok((added, removed))
+    }
+}
+
+/// The caches of an origin.
+struct OriginCaches {
+    /// The ids of the caches, by name, in the order they were created.
+    names: Vec<(String, CacheId)>,
+    /// Where the entries of the caches are written as soon as they are stored.
+    journal: Option<Journal>,
+    /// The number of bytes the entries of the caches take.
+    usage: u64,
+    /// Orders the caches by when they were created in the journal.
+    next_order: u64,
+}
+
+struct CacheStorageManager {
+    port: IpcReceiver<CacheStorageThreadMsg>,
+    /// Where the journal of each origin is, if the caches are written to disk.
+    directory: Option<PathBuf>,
+    origins: HashMap<String, OriginCaches>,
+    caches: HashMap<CacheId, Cache>,
+    next_cache_id: CacheId,
+    /// Where the usage of the caches of each origin is accounted for.
+    quota: QuotaAccount,
+}
+
+impl CacheStorageManager {
+    fn new(
+        port: IpcReceiver<CacheStorageThreadMsg>,
+        config_dir: Option<PathBuf>,
+        quota: QuotaAccount,
+    ) -> CacheStorageManager {
+        CacheStorageManager {
+            port,
+            directory: config_dir.map(|config_dir| config_dir.join("cache_storage")),
+            origins: HashMap::new(),
+            caches: HashMap::new(),
+            next_cache_id: 0,
+            quota,
+        }
+    }
+}
+
+impl CacheStorageManager {
+    fn start(&mut self) {
+        loop {
+            match self.port.recv().unwrap() {
+                CacheStorageThreadMsg::Open {
+                    origin,
+                    name,
+                    sender,
+                } => {
+                    let id = self.open(&origin, &name);
+                    let _ = sender.send(id);
+                },
+                CacheStorageThreadMsg::Has {
+                    origin,
+                    name,
+                    sender,
+                } => {
+                    let origin = self.load_origin(&origin);
+                    let _ = sender.send(self.cache_id(&origin, &name).is_some());
+                },
+                CacheStorageThreadMsg::Delete {
+                    origin,
+                    name,
+                    sender,
+                } => {
+                    let deleted = self.delete(&origin, &name);
+                    let _ = sender.send(deleted);
+                },
+                CacheStorageThreadMsg::Keys(sender, origin) => {
+                    let origin = self.load_origin(&origin);
+                    let names = self.origins[&origin]
+                        .names
+                        .iter()
+                        .map(|&(ref name, _)| name.clone())
+                        .collect();
+                    let _ = sender.send(names);
+                },
+                CacheStorageThreadMsg::MatchStorage {
+                    origin,
+                    cache_name,
+                    request,
+                    options,
+                    sender,
+                } => {
+                    let origin = self.load_origin(&origin);
+                    let ids: Vec<CacheId> = match cache_name {
+                        Some(name) => self.cache_id(&origin, &name).into_iter().collect(),
+                        None => self.origins[&origin]
+                            .names
+                            .iter()
+                            .map(|&(_, id)| id)
+                            .collect(),
+                    };
+                    let response = ids
+                        .iter()
+                        .filter_map(|id| self.caches.get(id))
+                        .filter_map(|cache| cache.query(Some(&request), &options).next())
+                        .map(|entry| entry.response.clone())
+                        .next();
+                    let _ = sender.send(response);
+                },
+                CacheStorageThreadMsg::MatchAll {
+                    cache,
+                    request,
+                    options,
+                    sender,
+                } => {
+                    let responses = self.caches.get(&cache).map_or(vec![], |cache| {
+                        cache
+                            .query(request.as_ref(), &options)
+                            .map(|entry| entry.response.clone())
+                            .collect()
+                    });
+                    let _ = sender.send(responses);
+                },
+                CacheStorageThreadMsg::RequestKeys {
+                    cache,
+                    request,
+                    options,
+                    sender,
+                } => {
+                    let requests = self.caches.get(&cache).map_or(vec![], |cache| {
+                        cache
+                            .query(request.as_ref(), &options)
+                            .map(|entry| entry.request.clone())
+                            .collect()
+                    });
+                    let _ = sender.send(requests);
+                },
+                CacheStorageThreadMsg::Batch {
+                    cache,
+                    operations,
+                    sender,
+                } => {
+                    let result = self.batch(cache, operations);
+                    let _ = sender.send(result);
+                },
+                CacheStorageThreadMsg::ClearOrigin(origin) => {
+                    self.clear_origin(&origin);
+                },
+                CacheStorageThreadMsg::Exit(sender) => {
+                    // Entries are saved as soon as they are stored, so only wait for the last writes.
+                    for caches in self.origins.values() {
+                        if let Some(ref journal) = caches.journal {
+                            journal.flush();
+                        }
+                    }
+                    let _ = sender.send(());
+                    break;
+                },
+            }
+        }
+    }
+
+    /// Read the caches of `origin` from disk if they aren't in memory yet,
+    /// returning the key of the origin.
+    fn load_origin(&mut self, origin: &ImmutableOrigin) -> String {
+        let key = origin.ascii_serialization();
+        if self.origins.contains_key(&key) {
+            return key;
+        }
+
+        let mut caches: Vec<(u64, Cache)> = vec![];
+        let mut entries = vec![];
+        let journal = self.directory.as_ref().map(|directory| {
+            let file_name = utf8_percent_encode(&key, NON_ALPHANUMERIC).to_string();
+            let (journal, values) = Journal::open::<String>(directory, &file_name);
+            // The entries of a cache are only kept along with the cache.
+            for (entry_key, value) in values.unwrap_or_default() {
+                match serde_json::from_str::<(String, Option<u64>)>(&entry_key) {
+                    Ok((name, None)) => match decode_binary::<u64>(&value) {
+                        Some(order) => caches.push((order, Cache::new(origin, &name))),
+                        None => warn!("couldn't decode the order of cache {}", name),
+                    },
+                    Ok((name, Some(_))) => entries.push((name, value)),
+                    Err(why) => warn!("couldn't decode cache journal key {}: {}", entry_key, why),
+                }
+            }
+            journal
+        });
+
+        caches.sort_by_key(|&(order, _)| order);
+        let mut origin_caches = OriginCaches {
+            names: vec![],
+            journal,
+            usage: 0,
+            next_order: caches.last().map_or(0, |&(order, _)| order + 1),
+        };
+        for (_, mut cache) in caches {
+            let name = cache.name.clone().unwrap_or_default();
+            for &(_, ref value) in entries.iter().filter(|&&(ref cache, _)| *cache == name) {
+                match decode_binary::<Entry>(value) {
+                    Some(entry) => cache.entries.push(entry),
+                    None => warn!("couldn't restore an entry of cache {}", name),
+                }
+            }
+            cache.entries.sort_by_key(|entry| entry.id);
+            cache.next_entry_id = cache.entries.last().map_or(0, |entry| entry.id + 1);
+            origin_caches.usage += cache.entries.iter().map(Entry::size).sum::<u64>();
+
+            let id = self.next_cache_id;
+            self.next_cache_id += 1;
+            origin_caches.names.push((name, id));
+            self.caches.insert(id, cache);
+        }
+        self.origins.insert(key.clone(), origin_caches);
+        key
+    }
+
+    fn cache_id(&self, origin: &str, name: &str) -> Option<CacheId> {
+        self.origins[origin]
+            .names
+            .iter()
+            .find(|&&(ref cache, _)| cache == name)
+            .map(|&(_, id)| id)
+    }
+
+    /// <https://w3c.github.io/ServiceWorker/#cache-storage-open>
+    fn open(&mut self, cache_origin: &ImmutableOrigin, name: &str) -> CacheId {
+        let origin = self.load_origin(cache_origin);
+        if let Some(id) = self.cache_id(&origin, name) {
+            return id;
+        }
+
+        let id = self.next_cache_id;
+        self.next_cache_id += 1;
+        self.caches.insert(id, Cache::new(&cache_origin, name));
+        let caches = self.origins.get_mut(&origin).unwrap();
+        caches.names.push((name.to_owned(), id));
+        if let Some(ref journal) = caches.journal {
+            journal.put(&journal_key(name, None), &encode_binary(&caches.next_order));
+        }
+        caches.next_order += 1;
+        id
+    }
+
+    /// <https://w3c.github.io/ServiceWorker/#cache-storage-delete>
+    fn delete(&mut self, cache_origin: &ImmutableOrigin, name: &str) -> bool {
+        let origin = self.load_origin(cache_origin);
+        let caches = self.origins.get_mut(&origin).unwrap();
+        let position = match caches
+            .names
+            .iter()
+            .position(|&(ref cache, _)| cache == name)
+        {
+            Some(position) => position,
+            None => return false,
+        };
+        let (_, id) = caches.names.remove(position);
+        let cache = match self.caches.get_mut(&id) {
+            Some(cache) => cache,
+            None => return true,
+        };
+        cache.name = None;
+        caches.usage -= cache.entries.iter().map(Entry::size).sum::<u64>();
+        if let Some(ref journal) = caches.journal {
+            journal.remove(&journal_key(name, None));
+            for entry in &cache.entries {
+                journal.remove(&journal_key(name, Some(entry.id)));
+            }
+        }
+        self.quota.set_usage(cache_origin, caches.usage);
+        true
+    }
+
+    /// Delete every cache of an origin.
+    fn clear_origin(&mut self, origin: &ImmutableOrigin) {
+        let key = self.load_origin(origin);
+        let names: Vec<String> = self.origins[&key]
+            .names
+            .iter()
+            .map(|&(ref name, _)| name.clone())
+            .collect();
+        for name in names {
+            self.delete(origin, &name);
+        }
+    }
+
+    /// Run a batch of operations on a cache, undoing them if they fail
+    /// or take its origin over its quota.
+    fn batch(
+        &mut self,
+        id: CacheId,
+        operations: Vec<CacheBatchOperation>,
+    ) -> Result<bool, CacheStorageError> {
+        let cache = match self.caches.get_mut(&id) {
+            Some(cache) => cache,
+            None => return Ok(false),
+        };
+        let backup = (cache.entries.clone(), cache.next_entry_id);
+        let (added, removed) = match cache.batch(operations) {
+            Ok(changes) => changes,
+            Err(error) => {
+                cache.entries = backup.0;
+                cache.next_entry_id = backup.1;
+                return Err(error);
+            },
+        };
+
+        // Deleted caches are no longer saved, so they don't count against the quota.
+        let name = match cache.name {
+            Some(ref name) => name,
+            None => return Ok(!removed.is_empty()),
+        };
+        let caches = self
+            .origins
+            .get_mut(&cache.origin.ascii_serialization())
+            .unwrap();
+        let added_size: u64 = added.iter().map(Entry::size).sum();
+        let removed_size: u64 = removed.iter().map(Entry::size).sum();
+        let usage = caches.usage + added_size - removed_size;
+        if added_size > removed_size {
+            if usage > quota() || !self.quota.request_space(&cache.origin, usage) {
+                cache.entries = backup.0;
+                cache.next_entry_id = backup.1;
+                return Err(CacheStorageError::QuotaExceeded);
+            }
+        } else {
+            self.quota.set_usage(&cache.origin, usage);
+        }
+        caches.usage = usage;
+
+        if let Some(ref journal) = caches.journal {
+            for entry in &removed {
+                journal.remove(&journal_key(name, Some(entry.id)));
+            }
+            for entry in &added {
+                journal.put(&journal_key(name, Some(entry.id)), &encode_binary(entry));
+            }
+        }
+        Ok(!removed.is_empty())
+    }
+}
@@ -3,14 +3,25 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 
 use crate::hosts::replace_host;
-use hyper::client::connect::{Connect, Destination};
+use crate::http_auth::authorization_from_cache;
+use crate::proxy::{self, IoFuture, ProxyConfig, ProxyScheme};
+use crate::resource_thread::AuthCache;
+use futures::future;
+use hyper::client::connect::{Connect, Connected, Destination};
 use hyper::client::HttpConnector as HyperHttpConnector;
 use hyper::rt::Future;
-use hyper::{Body, Client};
-use hyper_openssl::HttpsConnector;
+use hyper::{Body, Client, Method};
+use hyper_openssl::{HttpsConnector, MaybeHttpsStream};
 use openssl::ssl::{SslConnector, SslConnectorBuilder, SslMethod, SslOptions};
 use openssl::x509;
+use servo_arc::Arc;
+use std::io::{self, Read, Write};
+use std::sync::RwLock;
+use time::precise_time_ns;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpStream;
 use tokio::prelude::future::Executor;
+use tokio::prelude::Poll;
 
 pub const BUF_SIZE: usize = 32768;
 pub const ALPN_H2_H1: &'static [u8] = b"\x02h2\x08http/1.1";
@@ -30,34 +41,193 @@ const SIGNATURE_ALGORITHMS: &'static str = concat!(
     "RSA+SHA512:RSA+SHA384:RSA+SHA256"
 );
 
+/// A TCP connection to a server or proxy, which remembers when it was established.
+#[derive(Debug)]
+pub struct TcpConnection {
+    stream: TcpStream,
+    established: u64,
+}
+
+impl TcpConnection {
+    fn new(stream: TcpStream) -> TcpConnection {
+        TcpConnection {
+            stream,
+            established: precise_time_ns(),
+        }
+    }
+}
+
+impl Read for TcpConnection {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.stream.read(buf)
+    }
+}
+
+impl Write for TcpConnection {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stream.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+impl AsyncRead for TcpConnection {
+    unsafe fn prepare_uninitialized_buffer(&self, buf: &mut [u8]) -> bool {
+        self.stream.prepare_uninitialized_buffer(buf)
+    }
+}
+
+impl AsyncWrite for TcpConnection {
+    fn shutdown(&mut self) -> Poll<(), io::Error> {
+        AsyncWrite::shutdown(&mut self.stream)
+    }
+}
+
 pub struct HttpConnector {
     inner: HyperHttpConnector,
+    proxy_config: ProxyConfig,
+    /// Where the credentials the user entered for proxies are kept.
+    auth_cache: Arc<RwLock<AuthCache>>,
 }
 
 impl HttpConnector {
-    fn new() -> HttpConnector {
+    fn new(proxy_config: ProxyConfig, auth_cache: Arc<RwLock<AuthCache>>) -> HttpConnector {
         let mut inner = HyperHttpConnector::new(4);
         inner.enforce_http(false);
         inner.set_happy_eyeballs_timeout(None);
-        HttpConnector { inner }
+        HttpConnector {
+            inner,
+            proxy_config,
+            auth_cache,
+        }
     }
 }
 
 impl Connect for HttpConnector {
-    type Transport = <HyperHttpConnector as Connect>::Transport;
-    type Error = <HyperHttpConnector as Connect>::Error;
-    type Future = <HyperHttpConnector as Connect>::Future;
+    type Transport = TcpConnection;
+    type Error = io::Error;
+    type Future = IoFuture<(TcpConnection, Connected)>;
+
+    fn connect(&self, dest: Destination) -> Self::Future {
+        Box::new(
+            self.connect_stream(dest)
+                .map(|(stream, connected)| (TcpConnection::new(stream), connected)),
+        )
+    }
+}
+
+impl HttpConnector {
+    fn connect_stream(&self, dest: Destination) -> IoFuture<(TcpStream, Connected)> {
+        let proxy = match self.proxy_config.proxy_for(dest.scheme(), dest.host()) {
+            Some(proxy) => proxy.clone(),
+            None => {
+                // Perform host replacement when making the actual TCP connection.
+                let mut new_dest = dest.clone();
+                let addr = replace_host(dest.host());
+                new_dest.set_host(&*addr).unwrap();
+                return Box::new(self.inner.connect(new_dest));
+            },
+        };
+
+        // The proxy resolves the host of the destination itself.
+        let mut proxy_dest = dest.clone();
+        if let Err(error) = proxy_dest
+            .set_scheme("http")
+            .and_then(|_| proxy_dest.set_host(&proxy.host))
+        {
+            return Box::new(future::err(io::Error::new(io::ErrorKind::Other, error)));
+        }
+        proxy_dest.set_port(proxy.port);
+
+        let is_tls = dest.scheme() == "https";
+        let host = dest.host().to_owned();
+        let port = dest.port().unwrap_or(if is_tls { 443 } else { 80 });
+        // Credentials entered by the user take precedence over those in the proxy URL,
+        // since they are only asked for once the latter have been rejected.
+        let tunnel_authorization = if proxy.scheme == ProxyScheme::Http && is_tls {
+            authorization_from_cache(
+                &self.auth_cache,
+                &proxy.auth_cache_key(),
+                &Method::CONNECT,
+                &proxy::tunnel_authority(&host, port),
+            )
+            .or_else(|| proxy.authorization())
+        } else {
+            None
+        };
+        Box::new(self.inner.connect(proxy_dest).and_then(
+            move |(stream, _)| -> IoFuture<(TcpStream, Connected)> {
+                match (proxy.scheme, is_tls) {
+                    // Requests are sent to the proxy in absolute-form.
+                    (ProxyScheme::Http, false) => {
+                        Box::new(future::ok((stream, Connected::new().proxy(true))))
+                    },
+                    (ProxyScheme::Http, true) => Box::new(
+                        proxy::http_tunnel(stream, host, port, tunnel_authorization)
+                            .map(|stream| (stream, Connected::new())),
+                    ),
+                    (ProxyScheme::Socks5, _) => Box::new(
+                        proxy::socks5_connect(stream, host, port, proxy.credentials)
+                            .map(|stream| (stream, Connected::new())),
+                    ),
+                }
+            },
+        ))
+    }
+}
+
+/// When a connection was set up, as measured for `ResourceFetchTiming`.
+/// Every response carries the timing of the connection it was received on,
+/// in its extensions. The times are in nanoseconds.
+#[derive(Clone, Copy, Debug)]
+pub struct ConnectionTiming {
+    pub connect_start: u64,
+    /// When the TLS handshake started, or zero if the connection isn't secure.
+    pub secure_connection_start: u64,
+    pub connect_end: u64,
+}
+
+/// A connector that secures connections to https URLs, negotiating HTTP/2 with
+/// the server through ALPN when the TLS configuration offers it. Hyper multiplexes
+/// all the requests to an origin over a single connection once HTTP/2 is in use.
+pub struct Connector {
+    inner: HttpsConnector<HttpConnector>,
+}
+
+impl Connect for Connector {
+    type Transport = MaybeHttpsStream<TcpConnection>;
+    type Error = <HttpsConnector<HttpConnector> as Connect>::Error;
+    type Future = Box<dyn Future<Item = (Self::Transport, Connected), Error = Self::Error> + Send>;
 
     fn connect(&self, dest: Destination) -> Self::Future {
-        // Perform host replacement when making the actual TCP connection.
-        let mut new_dest = dest.clone();
-        let addr = replace_host(dest.host());
-        new_dest.set_host(&*addr).unwrap();
-        self.inner.connect(new_dest)
+        let connect_start = precise_time_ns();
+        Box::new(self.inner.connect(dest).map(move |(stream, connected)| {
+            let (secure_connection_start, negotiated_h2) = match stream {
+                MaybeHttpsStream::Http(_) => (0, false),
+                MaybeHttpsStream::Https(ref stream) => {
+                    let stream = stream.get_ref();
+                    (
+                        stream.get_ref().established,
+                        stream.ssl().selected_alpn_protocol() == Some(&b"h2"[..]),
+                    )
+                },
+            };
+            let connected = connected.extra(ConnectionTiming {
+                connect_start,
+                secure_connection_start,
+                connect_end: precise_time_ns(),
+            });
+            if negotiated_h2 {
+                (stream, connected.negotiated_h2())
+            } else {
+                (stream, connected)
+            }
+        }))
     }
 }
 
-pub type Connector = HttpsConnector<HttpConnector>;
 pub type TlsConfig = SslConnectorBuilder;
 
 pub fn create_tls_config(certs: &str, alpn: &[u8]) -> TlsConfig {
@@ -107,12 +277,26 @@ pub fn create_tls_config(certs: &str, alpn: &[u8]) -> TlsConfig {
     cfg
 }
 
-pub fn create_http_client<E>(tls_config: TlsConfig, executor: E) -> Client<Connector, Body>
+pub fn create_http_client<E>(
+    tls_config: TlsConfig,
+    proxy_config: ProxyConfig,
+    auth_cache: Arc<RwLock<AuthCache>>,
+    executor: E,
+) -> Client<Connector, Body>
 where
     E: Executor<Box<dyn Future<Error = (), Item = ()> + Send + 'static>> + Sync + Send + 'static,
 {
-    let connector = HttpsConnector::with_connector(HttpConnector::new(), tls_config).unwrap();
+    let connector = Connector {
+        inner: HttpsConnector::with_connector(
+            HttpConnector::new(proxy_config, auth_cache),
+            tls_config,
+        )
+        .unwrap(),
+    };
 
+    // TODO: HTTP/2 streams are all sent with the default priority, since neither hyper
+    // nor the h2 crate can send PRIORITY frames yet. Once they can, the weight of
+    // a stream should depend on the destination of its request.
     Client::builder()
         .http1_title_case_headers(true)
         .executor(executor)
@@ -3,12 +3,15 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 
 //! Implementation of cookie creation and matching as specified by
-//! http://tools.ietf.org/html/rfc6265
+//! http://tools.ietf.org/html/rfc6265, including the SameSite attribute of
+//! https://tools.ietf.org/html/draft-ietf-httpbis-rfc6265bis-05
 
+use http::Method;
 use hyper_serde::{self, Serde};
-use net_traits::pub_domains::is_pub_domain;
+use net_traits::pub_domains::{is_pub_domain, reg_suffix};
+use net_traits::request::{Origin, Request};
 use net_traits::CookieSource;
-use servo_url::ServoUrl;
+use servo_url::{ImmutableOrigin, ServoUrl};
 use std::borrow::ToOwned;
 use std::net::{Ipv4Addr, Ipv6Addr};
 use time::{at, now, Duration, Tm};
@@ -36,6 +39,128 @@ pub struct Cookie {
     )]
     pub last_access: Tm,
     pub expiry_time: Option<Serde<Tm>>,
+    #[serde(default)]
+    pub same_site: SameSite,
+}
+
+/// The enforcement mode of a cookie,
+/// <https://tools.ietf.org/html/draft-ietf-httpbis-rfc6265bis-05#section-5.3.7>
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub enum SameSite {
+    Strict,
+    Lax,
+    None,
+}
+
+impl Default for SameSite {
+    /// Cookies that don't specify a SameSite attribute are treated as Lax,
+    /// <https://tools.ietf.org/html/draft-west-cookie-incrementalism-00#section-3.1>
+    fn default() -> SameSite {
+        SameSite::Lax
+    }
+}
+
+impl SameSite {
+    /// The value of the last SameSite attribute of a Set-Cookie string, if any.
+    /// cookie-rs doesn't tell `SameSite=None` apart from an absent attribute, so this is
+    /// read from the string directly.
+    fn from_cookie_string(cookie_str: &str) -> Option<SameSite> {
+        cookie_str
+            .split(';')
+            .skip(1)
+            .filter_map(|attribute| {
+                let mut parts = attribute.splitn(2, '=');
+                let name = parts.next().unwrap_or("").trim();
+                if !name.eq_ignore_ascii_case("samesite") {
+                    return None;
+                }
+                let value = parts.next().unwrap_or("").trim();
+                Some(if value.eq_ignore_ascii_case("strict") {
+                    SameSite::Strict
+                } else if value.eq_ignore_ascii_case("none") {
+                    SameSite::None
+                } else {
+                    SameSite::Lax
+                })
+            })
+            .last()
+    }
+}
+
+/// How the site of a request relates to the site that initiated it,
+/// <https://tools.ietf.org/html/draft-ietf-httpbis-rfc6265bis-05#section-5.2>
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SameSiteContext {
+    SameSite,
+    CrossSiteTopLevelNavigation { safe_method: bool },
+    CrossSite,
+}
+
+impl SameSiteContext {
+    /// The context of a request for `url` made by `initiator`, where a missing
+    /// initiator stands for the user agent itself.
+    pub fn new(
+        url: &ServoUrl,
+        initiator: Option<&ImmutableOrigin>,
+        top_level_navigation: bool,
+        method: &Method,
+    ) -> SameSiteContext {
+        let same_site = match initiator {
+            None => true,
+            Some(&ImmutableOrigin::Opaque(_)) => false,
+            Some(&ImmutableOrigin::Tuple(_, ref host, _)) => {
+                let initiator_host = host.to_string();
+                let url_host = url.host_str().unwrap_or("");
+                reg_suffix(&initiator_host).eq_ignore_ascii_case(reg_suffix(url_host))
+            },
+        };
+        if same_site {
+            SameSiteContext::SameSite
+        } else if top_level_navigation {
+            SameSiteContext::CrossSiteTopLevelNavigation {
+                safe_method: is_safe_method(method),
+            }
+        } else {
+            SameSiteContext::CrossSite
+        }
+    }
+
+    /// The context of script access to the cookies of `url`, from a document whose ancestors
+    /// have `ancestor_origins`, which is only same-site if every ancestor is.
+    /// <https://tools.ietf.org/html/draft-ietf-httpbis-rfc6265bis-05#section-5.2.1>
+    pub fn for_document(url: &ServoUrl, ancestor_origins: &[ImmutableOrigin]) -> SameSiteContext {
+        let cross_site = ancestor_origins.iter().any(|origin| {
+            SameSiteContext::new(url, Some(origin), false, &Method::GET) !=
+                SameSiteContext::SameSite
+        });
+        if cross_site {
+            SameSiteContext::CrossSite
+        } else {
+            SameSiteContext::SameSite
+        }
+    }
+
+    /// The context of the current URL of `request`.
+    pub fn for_request(request: &Request) -> SameSiteContext {
+        let initiator = match request.origin {
+            Origin::Client => None,
+            Origin::Origin(ref origin) => Some(origin),
+        };
+        SameSiteContext::new(
+            &request.current_url(),
+            initiator,
+            request.is_navigation_request() && request.top_level_navigation,
+            &request.method,
+        )
+    }
+}
+
+/// <https://tools.ietf.org/html/rfc7231#section-4.2.1>
+fn is_safe_method(method: &Method) -> bool {
+    match *method {
+        Method::GET | Method::HEAD | Method::OPTIONS | Method::TRACE => true,
+        _ => false,
+    }
 }
 
 impl Cookie {
@@ -44,10 +169,19 @@ impl Cookie {
         request: &ServoUrl,
         source: CookieSource,
     ) -> Option<Cookie> {
-        cookie_rs::Cookie::parse(cookie_str)
+        let same_site = SameSite::from_cookie_string(&cookie_str);
+        let mut cookie = cookie_rs::Cookie::parse(cookie_str)
             .ok()
             .map(|cookie| Cookie::new_wrapped(cookie, request, source))
-            .unwrap_or(None)
+            .unwrap_or(None)?;
+        if let Some(same_site) = same_site {
+            // https://tools.ietf.org/html/draft-west-cookie-incrementalism-00#section-3.2
+            if same_site == SameSite::None && !cookie.cookie.secure().unwrap_or(false) {
+                return None;
+            }
+            cookie.same_site = same_site;
+        }
+        Some(cookie)
     }
 
     /// <http://tools.ietf.org/html/rfc6265#section-5.3>
@@ -129,6 +263,11 @@ impl Cookie {
             return None;
         }
 
+        let same_site = match cookie.same_site() {
+            Some(cookie_rs::SameSite::Strict) => SameSite::Strict,
+            _ => SameSite::Lax,
+        };
+
         Some(Cookie {
             cookie,
             host_only,
@@ -136,6 +275,7 @@ impl Cookie {
             creation_time: now(),
             last_access: now(),
             expiry_time: expiry_time.map(Serde),
+            same_site,
         })
     }
 
@@ -196,8 +336,19 @@ impl Cookie {
                 string.parse::<Ipv6Addr>().is_err())
     }
 
+    /// Whether a response in `context` may set this cookie,
+    /// <https://tools.ietf.org/html/draft-ietf-httpbis-rfc6265bis-05#section-5.3> step 13
+    pub fn may_be_set_in_context(&self, context: SameSiteContext) -> bool {
+        self.same_site == SameSite::None || context != SameSiteContext::CrossSite
+    }
+
     // http://tools.ietf.org/html/rfc6265#section-5.4 step 1
-    pub fn appropriate_for_url(&self, url: &ServoUrl, source: CookieSource) -> bool {
+    pub fn appropriate_for_url(
+        &self,
+        url: &ServoUrl,
+        source: CookieSource,
+        context: SameSiteContext,
+    ) -> bool {
         let domain = url.host_str();
         if self.host_only {
             if self.cookie.domain() != domain {
@@ -224,6 +375,13 @@ impl Cookie {
             return false;
         }
 
-        true
+        // https://tools.ietf.org/html/draft-ietf-httpbis-rfc6265bis-05#section-5.4 step 1
+        match (self.same_site, context) {
+            (SameSite::None, _) | (_, SameSiteContext::SameSite) => true,
+            (SameSite::Lax, SameSiteContext::CrossSiteTopLevelNavigation { safe_method }) => {
+                safe_method
+            },
+            _ => false,
+        }
     }
 }
@@ -5,13 +5,17 @@
 //! Implementation of cookie storage as specified in
 //! http://tools.ietf.org/html/rfc6265
 
-use crate::cookie::Cookie;
+use crate::cookie::{Cookie, SameSiteContext};
+use crate::journal::Journal;
+use crate::resource_thread::read_json_from_file;
 use net_traits::pub_domains::reg_suffix;
 use net_traits::CookieSource;
 use servo_url::ServoUrl;
 use std::cmp::Ordering;
 use std::collections::hash_map::Entry;
 use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
 use time::{self, Tm};
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -19,6 +23,9 @@ pub struct CookieStorage {
     version: u32,
     cookies_map: HashMap<String, Vec<Cookie>>,
     max_per_host: usize,
+    /// Where the cookies of each registrable domain are written whenever they change.
+    #[serde(skip)]
+    journal: Option<Journal>,
 }
 
 impl CookieStorage {
@@ -27,6 +34,45 @@ impl CookieStorage {
             version: 1,
             cookies_map: HashMap::new(),
             max_per_host: max_cookies,
+            journal: None,
+        }
+    }
+
+    /// Load the cookies persisted in `config_dir`, and persist every later change to them.
+    pub fn open_journal(&mut self, config_dir: &Path) {
+        let (journal, cookies_map) = Journal::open(config_dir, "cookie_jar");
+        match cookies_map {
+            Some(cookies_map) => self.cookies_map = cookies_map,
+            None => {
+                // Carry over the cookies that older versions only wrote on shutdown.
+                let max_per_host = self.max_per_host;
+                read_json_from_file(self, config_dir, "cookie_jar.json");
+                self.max_per_host = max_per_host;
+                for (domain, cookies) in &self.cookies_map {
+                    journal.put(domain, cookies);
+                }
+                journal.flush();
+                let _ = fs::remove_file(config_dir.join("cookie_jar.json"));
+            },
+        }
+        self.journal = Some(journal);
+    }
+
+    /// Wait until every change to the cookies is persisted.
+    pub fn flush_journal(&self) {
+        if let Some(ref journal) = self.journal {
+            journal.flush();
+        }
+    }
+
+    /// Persist the cookies of the registrable domain `domain`.
+    /// Accesses to cookies are not persisted by themselves, only along with other changes.
+    fn persist(&self, domain: &str) {
+        if let Some(ref journal) = self.journal {
+            match self.cookies_map.get(domain) {
+                Some(cookies) if !cookies.is_empty() => journal.put(domain, cookies),
+                _ => journal.remove(domain),
+            }
         }
     }
 
@@ -86,10 +132,11 @@ impl CookieStorage {
     }
     pub fn clear_storage(&mut self, url: &ServoUrl) {
         let domain = reg_host(url.host_str().unwrap_or(""));
-        let cookies = self.cookies_map.entry(domain).or_insert(vec![]);
+        let cookies = self.cookies_map.entry(domain.clone()).or_insert(vec![]);
         for cookie in cookies.iter_mut() {
             cookie.set_expiry_time_negative();
         }
+        self.persist(&domain);
     }
 
     // http://tools.ietf.org/html/rfc6265#section-5.3
@@ -113,7 +160,7 @@ impl CookieStorage {
 
         // Step 12
         let domain = reg_host(&cookie.cookie.domain().as_ref().unwrap_or(&""));
-        let cookies = self.cookies_map.entry(domain).or_insert(vec![]);
+        let cookies = self.cookies_map.entry(domain.clone()).or_insert(vec![]);
 
         if cookies.len() == self.max_per_host {
             let old_len = cookies.len();
@@ -124,10 +171,12 @@ impl CookieStorage {
             if new_len == old_len &&
                 !evict_one_cookie(cookie.cookie.secure().unwrap_or(false), cookies)
             {
+                self.persist(&domain);
                 return;
             }
         }
         cookies.push(cookie);
+        self.persist(&domain);
     }
 
     pub fn cookie_comparator(a: &Cookie, b: &Cookie) -> Ordering {
@@ -147,17 +196,33 @@ impl CookieStorage {
 
     pub fn remove_expired_cookies_for_url(&mut self, url: &ServoUrl) {
         let domain = reg_host(url.host_str().unwrap_or(""));
-        if let Entry::Occupied(mut entry) = self.cookies_map.entry(domain) {
+        if let Entry::Occupied(mut entry) = self.cookies_map.entry(domain.clone()) {
             let cookies = entry.get_mut();
+            let old_len = cookies.len();
             cookies.retain(|c| !is_cookie_expired(&c));
-            if cookies.len() == 0 {
+            let new_len = cookies.len();
+            if new_len == 0 {
                 entry.remove_entry();
             }
+            if new_len != old_len {
+                self.persist(&domain);
+            }
         }
     }
 
     // http://tools.ietf.org/html/rfc6265#section-5.4
+    /// The cookies to send with a same-site request for `url`.
     pub fn cookies_for_url(&mut self, url: &ServoUrl, source: CookieSource) -> Option<String> {
+        self.cookies_for_url_in_context(url, source, SameSiteContext::SameSite)
+    }
+
+    /// The cookies to send with a request for `url`, made in `context`.
+    pub fn cookies_for_url_in_context(
+        &mut self,
+        url: &ServoUrl,
+        source: CookieSource,
+        context: SameSiteContext,
+    ) -> Option<String> {
         let filterer = |c: &&mut Cookie| -> bool {
             info!(
                 " === SENT COOKIE : {} {} {:?} {:?}",
@@ -168,10 +233,10 @@ impl CookieStorage {
             );
             info!(
                 " === SENT COOKIE RESULT {}",
-                c.appropriate_for_url(url, source)
+                c.appropriate_for_url(url, source, context)
             );
             // Step 1
-            c.appropriate_for_url(url, source)
+            c.appropriate_for_url(url, source, context)
         };
         // Step 2
         let domain = reg_host(url.host_str().unwrap_or(""));
@@ -211,7 +276,7 @@ impl CookieStorage {
 
         cookies
             .iter_mut()
-            .filter(move |c| c.appropriate_for_url(url, source))
+            .filter(move |c| c.appropriate_for_url(url, source, SameSiteContext::SameSite))
             .map(|c| {
                 c.touch();
                 c.cookie.clone()
@@ -0,0 +1,526 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! A size-bounded store of opaque entries in a directory on disk,
+//! evicting the least recently used entries once the size limit is reached.
+//!
+//! Every entry belongs to a group, whose entries are in a directory of their own,
+//! so that the size of each group is known, and a group can be removed at once.
+//! The index of the entries is kept in memory, and the files backing them are written
+//! on a dedicated thread, so that storing an entry never waits for the disk.
+//! Every entry is written to a temporary file first, and then renamed into place,
+//! so that an interrupted write never leaves a truncated entry behind.
+//! The last access of each entry is recorded in an index file, so that the eviction order
+//! survives a restart. Since entries are accessed on every cache hit, the index is only
+//! rewritten every few seconds while it changes, and when the store is flushed or dropped.
+
+use crate::resource_thread::read_json_from_file;
+use crossbeam_channel::{unbounded, Receiver, RecvTimeoutError, Sender};
+use openssl::sha::sha256;
+use percent_encoding::{percent_decode_str, utf8_percent_encode, NON_ALPHANUMERIC};
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// The file recording when each entry was last accessed.
+const INDEX_FILE_NAME: &'static str = "index.json";
+
+/// The extension of files that are still being written.
+const TEMPORARY_FILE_EXTENSION: &'static str = "tmp";
+
+/// The shortest time between two writes of the index file.
+const INDEX_WRITE_INTERVAL: Duration = Duration::from_secs(5);
+
+struct DiskCacheEntry {
+    group: String,
+    /// The size of the file backing the entry, in bytes.
+    size: u64,
+    /// Milliseconds since the epoch at which the entry was last read or written.
+    last_access: u64,
+}
+
+/// The state of a store that is shared with its writer thread.
+struct DiskCacheIndex {
+    current_size: u64,
+    entries: HashMap<String, DiskCacheEntry>,
+    /// The size of every group with entries.
+    group_sizes: HashMap<String, u64>,
+    /// The groups whose size changed since they were last taken.
+    changed_groups: HashSet<String>,
+    /// The data of the entries whose files are still being written.
+    pending_writes: HashMap<String, Arc<Vec<u8>>>,
+    /// Whether the entries changed since the index file was last written.
+    changed: bool,
+}
+
+enum DiskCacheMsg {
+    /// Write the data of the entry backed by a file name to a path.
+    Write(String, PathBuf, Arc<Vec<u8>>),
+    Remove(PathBuf),
+    RemoveDirectoryIfEmpty(PathBuf),
+    /// Write the pending entries and the index file, and reply once they are written.
+    Flush(Sender<()>),
+    Exit,
+}
+
+pub struct DiskCache {
+    directory: PathBuf,
+    max_size: u64,
+    index: Arc<Mutex<DiskCacheIndex>>,
+    /// Messages are sent while `index` is locked, so that the files
+    /// are written and removed in the order the index changed in.
+    sender: Sender<DiskCacheMsg>,
+    writer: Option<JoinHandle<()>>,
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// The name of the file backing the entry for `key`.
+fn file_name_for_key(key: &str) -> String {
+    sha256(key.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// The name of the directory holding the entries of `group`.
+fn directory_name_for_group(group: &str) -> String {
+    utf8_percent_encode(group, NON_ALPHANUMERIC).to_string()
+}
+
+impl DiskCacheIndex {
+    fn add_entry(&mut self, file_name: String, entry: DiskCacheEntry) {
+        self.current_size += entry.size;
+        *self.group_sizes.entry(entry.group.clone()).or_insert(0) += entry.size;
+        self.changed_groups.insert(entry.group.clone());
+        self.entries.insert(file_name, entry);
+        self.changed = true;
+    }
+
+    /// Forget the entry backed by `file_name`, returning it,
+    /// along with whether its group has no entries left.
+    fn remove_entry(&mut self, file_name: &str) -> Option<(DiskCacheEntry, bool)> {
+        let entry = self.entries.remove(file_name)?;
+        self.pending_writes.remove(file_name);
+        self.current_size -= entry.size;
+        let group_size = self.group_sizes.get_mut(&entry.group).map_or(0, |size| {
+            *size -= entry.size;
+            *size
+        });
+        let group_is_empty = group_size == 0 &&
+            !self
+                .entries
+                .values()
+                .any(|other| other.group == entry.group);
+        if group_is_empty {
+            self.group_sizes.remove(&entry.group);
+        }
+        self.changed_groups.insert(entry.group.clone());
+        self.changed = true;
+        Some((entry, group_is_empty))
+    }
+
+    /// Index the entries of `group`, which are in `directory`.
+    fn index_group(&mut self, group: &str, directory: &Path, last_accesses: &HashMap<String, u64>) {
+        let dir_entries = match fs::read_dir(directory) {
+            Ok(dir_entries) => dir_entries,
+            Err(why) => {
+                warn!("couldn't read {}: {}", directory.display(), why);
+                return;
+            },
+        };
+        for dir_entry in dir_entries.filter_map(Result::ok) {
+            let path = dir_entry.path();
+            let name = match path.file_name().and_then(|name| name.to_str()) {
+                Some(name) => name.to_owned(),
+                None => continue,
+            };
+            if path
+                .extension()
+                .map_or(false, |ext| ext == TEMPORARY_FILE_EXTENSION)
+            {
+                // Left over from a write that never completed.
+                let _ = fs::remove_file(&path);
+                continue;
+            }
+            let metadata = match dir_entry.metadata() {
+                Ok(metadata) if metadata.is_file() => metadata,
+                _ => continue,
+            };
+            let last_access = last_accesses.get(&name).cloned().unwrap_or_else(|| {
+                metadata
+                    .modified()
+                    .ok()
+                    .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                    .map_or(0, |duration| duration.as_millis() as u64)
+            });
+            self.add_entry(
+                name,
+                DiskCacheEntry {
+                    group: group.to_owned(),
+                    size: metadata.len(),
+                    last_access,
+                },
+            );
+        }
+    }
+}
+
+impl DiskCache {
+    /// Open the store located in `directory`, creating it if necessary,
+    /// and index the entries already present on disk.
+    pub fn new(directory: PathBuf, max_size: u64) -> DiskCache {
+        if let Err(why) = fs::create_dir_all(&directory) {
+            warn!("couldn't create {}: {}", directory.display(), why);
+        }
+
+        let mut last_accesses: HashMap<String, u64> = HashMap::new();
+        if directory.join(INDEX_FILE_NAME).exists() {
+            read_json_from_file(&mut last_accesses, &directory, INDEX_FILE_NAME);
+        }
+
+        let mut index = DiskCacheIndex {
+            current_size: 0,
+            entries: HashMap::new(),
+            group_sizes: HashMap::new(),
+            changed_groups: HashSet::new(),
+            pending_writes: HashMap::new(),
+            changed: false,
+        };
+        match fs::read_dir(&directory) {
+            Ok(dir_entries) => {
+                for dir_entry in dir_entries.filter_map(Result::ok) {
+                    let path = dir_entry.path();
+                    let group = match path.file_name().and_then(|name| name.to_str()) {
+                        Some(name) if name != INDEX_FILE_NAME => {
+                            percent_decode_str(name).decode_utf8_lossy().into_owned()
+                        },
+                        _ => continue,
+                    };
+                    if !path.is_dir() {
+                        // Entries that aren't in a group were written by an older version,
+                        // and temporary files by a write of the index that never completed.
+                        let _ = fs::remove_file(&path);
+                        continue;
+                    }
+                    index.index_group(&group, &path, &last_accesses);
+                }
+            },
+            Err(why) => warn!("couldn't read {}: {}", directory.display(), why),
+        }
+
+        let index = Arc::new(Mutex::new(index));
+        let (sender, receiver) = unbounded();
+        let writer = DiskCacheWriter {
+            directory: directory.clone(),
+            index: index.clone(),
+        };
+        let writer = thread::Builder::new()
+            .name("DiskCacheWriter".to_owned())
+            .spawn(move || writer.run(receiver))
+            .expect("Thread spawning failed");
+
+        let cache = DiskCache {
+            directory,
+            max_size,
+            index,
+            sender,
+            writer: Some(writer),
+        };
+        {
+            let mut index = cache.index.lock().unwrap();
+            cache.evict_until_within(&mut index, max_size);
+        }
+        cache
+    }
+
+    fn group_directory(&self, group: &str) -> PathBuf {
+        self.directory.join(directory_name_for_group(group))
+    }
+
+    fn path_for(&self, group: &str, file_name: &str) -> PathBuf {
+        self.group_directory(group).join(file_name)
+    }
+
+    /// Read the data stored for `key`, if any.
+    /// The file backing it is read without blocking other uses of the store.
+    pub fn get(&self, key: &str) -> Option<Vec<u8>> {
+        let file_name = file_name_for_key(key);
+        let path = {
+            let mut index = self.index.lock().unwrap();
+            let group = match index.entries.get_mut(&file_name) {
+                Some(entry) => {
+                    entry.last_access = now_ms();
+                    entry.group.clone()
+                },
+                None => return None,
+            };
+            // The writer thread records the new last access with the next write of the index.
+            index.changed = true;
+            if let Some(data) = index.pending_writes.get(&file_name) {
+                return Some((**data).clone());
+            }
+            self.path_for(&group, &file_name)
+        };
+        let mut data = vec![];
+        let read = File::open(path).and_then(|mut file| file.read_to_end(&mut data));
+        if let Err(why) = read {
+            warn!("couldn't read disk cache entry {}: {}", file_name, why);
+            let mut index = self.index.lock().unwrap();
+            // Unless it was replaced in the meantime.
+            if !index.pending_writes.contains_key(&file_name) {
+                self.remove_file(&mut index, &file_name);
+            }
+            return None;
+        }
+        Some(data)
+    }
+
+    /// Store `data` for `key` in `group`, replacing any previous data,
+    /// and evict other entries as needed to stay within the size limit.
+    pub fn put(&self, key: &str, group: &str, data: Vec<u8>) {
+        let file_name = file_name_for_key(key);
+        let mut index = self.index.lock().unwrap();
+        self.remove_file(&mut index, &file_name);
+
+        let size = data.len() as u64;
+        if size > self.max_size {
+            return;
+        }
+        self.evict_until_within(&mut index, self.max_size - size);
+
+        let data = Arc::new(data);
+        index.pending_writes.insert(file_name.clone(), data.clone());
+        index.add_entry(
+            file_name.clone(),
+            DiskCacheEntry {
+                group: group.to_owned(),
+                size,
+                last_access: now_ms(),
+            },
+        );
+        let path = self.path_for(group, &file_name);
+        let _ = self.sender.send(DiskCacheMsg::Write(file_name, path, data));
+    }
+
+    /// Remove the data stored for `key`, if any.
+    pub fn remove(&self, key: &str) {
+        let file_name = file_name_for_key(key);
+        let mut index = self.index.lock().unwrap();
+        self.remove_file(&mut index, &file_name);
+    }
+
+    /// Remove every entry of `group`.
+    pub fn remove_group(&self, group: &str) {
+        let mut index = self.index.lock().unwrap();
+        let file_names: Vec<String> = index
+            .entries
+            .iter()
+            .filter(|&(_, entry)| entry.group == group)
+            .map(|(file_name, _)| file_name.clone())
+            .collect();
+        for file_name in file_names {
+            self.remove_file(&mut index, &file_name);
+        }
+    }
+
+    /// The groups whose size changed since this was last called, along with their size.
+    pub fn take_changed_groups(&self) -> Vec<(String, u64)> {
+        let mut index = self.index.lock().unwrap();
+        let index = &mut *index;
+        let group_sizes = &index.group_sizes;
+        index
+            .changed_groups
+            .drain()
+            .map(|group| {
+                let size = group_sizes.get(&group).cloned().unwrap_or(0);
+                (group, size)
+            })
+            .collect()
+    }
+
+    /// Wait until every entry stored so far, and the index, are written.
+    pub fn flush(&self) {
+        let (sender, receiver) = unbounded();
+        if self.sender.send(DiskCacheMsg::Flush(sender)).is_ok() {
+            let _ = receiver.recv();
+        }
+    }
+
+    fn remove_file(&self, index: &mut DiskCacheIndex, file_name: &str) {
+        if let Some((entry, group_is_empty)) = index.remove_entry(file_name) {
+            let path = self.path_for(&entry.group, file_name);
+            let _ = self.sender.send(DiskCacheMsg::Remove(path));
+            if group_is_empty {
+                let directory = self.group_directory(&entry.group);
+                let _ = self
+                    .sender
+                    .send(DiskCacheMsg::RemoveDirectoryIfEmpty(directory));
+            }
+        }
+    }
+
+    /// Evict the least recently used entries, until the total size is at most `size`.
+    fn evict_until_within(&self, index: &mut DiskCacheIndex, size: u64) {
+        while index.current_size > size {
+            let oldest = index
+                .entries
+                .iter()
+                .min_by_key(|&(_, entry)| entry.last_access)
+                .map(|(file_name, _)| file_name.clone());
+            match oldest {
+                Some(file_name) => {
+                    debug!("evicting disk cache entry {}", file_name);
+                    self.remove_file(index, &file_name);
+                },
+                None => break,
+            }
+        }
+    }
+}
+
+impl Drop for DiskCache {
+    fn drop(&mut self) {
+        let _ = self.sender.send(DiskCacheMsg::Exit);
+        if let Some(writer) = self.writer.take() {
+            let _ = writer.join();
+        }
+    }
+}
+
+/// Writes and removes the files of a store, in the order they were sent in.
+struct DiskCacheWriter {
+    directory: PathBuf,
+    index: Arc<Mutex<DiskCacheIndex>>,
+}
+
+impl DiskCacheWriter {
+    fn run(self, receiver: Receiver<DiskCacheMsg>) {
+        let mut index_written_at = Instant::now();
+        loop {
+            // Wait for the next message, or until the index is due to be written,
+            // unless it is already up to date.
+            let msg = if self.index.lock().unwrap().changed {
+                let until_index_write = INDEX_WRITE_INTERVAL
+                    .checked_sub(index_written_at.elapsed())
+                    .unwrap_or_default();
+                match receiver.recv_timeout(until_index_write) {
+                    Ok(msg) => Some(msg),
+                    Err(RecvTimeoutError::Timeout) => None,
+                    Err(RecvTimeoutError::Disconnected) => break,
+                }
+            } else {
+                match receiver.recv() {
+                    Ok(msg) => Some(msg),
+                    Err(_) => break,
+                }
+            };
+
+            let mut flushes = vec![];
+            let mut exit = false;
+            for msg in msg.into_iter().chain(receiver.try_iter()) {
+                match msg {
+                    DiskCacheMsg::Write(file_name, path, data) => {
+                        self.write(&file_name, &path, &data)
+                    },
+                    DiskCacheMsg::Remove(path) => match fs::remove_file(&path) {
+                        Err(ref why) if why.kind() != ErrorKind::NotFound => {
+                            warn!("couldn't remove {}: {}", path.display(), why)
+                        },
+                        _ => {},
+                    },
+                    DiskCacheMsg::RemoveDirectoryIfEmpty(path) => {
+                        // Only succeeds for empty directories.
+                        let _ = fs::remove_dir(path);
+                    },
+                    DiskCacheMsg::Flush(sender) => flushes.push(sender),
+                    DiskCacheMsg::Exit => exit = true,
+                }
+            }
+            if exit {
+                break;
+            }
+            if !flushes.is_empty() || index_written_at.elapsed() >= INDEX_WRITE_INTERVAL {
+                self.write_index_if_changed();
+                index_written_at = Instant::now();
+            }
+            for sender in flushes {
+                let _ = sender.send(());
+            }
+        }
+        self.write_index_if_changed();
+    }
+
+    fn write(&self, file_name: &str, path: &Path, data: &Arc<Vec<u8>>) {
+        // Entries that are lost to a crash before the data reaches the disk
+        // are discarded when they fail to decode, so there is no need to sync.
+        let temporary_path = path.with_extension(TEMPORARY_FILE_EXTENSION);
+        let written = path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| File::create(&temporary_path))
+            .and_then(|mut file| file.write_all(data))
+            .and_then(|_| fs::rename(&temporary_path, path));
+        if written.is_err() {
+            let _ = fs::remove_file(&temporary_path);
+        }
+
+        let mut index = self.index.lock().unwrap();
+        let is_current = index
+            .pending_writes
+            .get(file_name)
+            .map_or(false, |pending| Arc::ptr_eq(pending, data));
+        if !is_current {
+            // The entry was replaced or removed while it was being written,
+            // and the file will be replaced or removed in turn.
+            return;
+        }
+        index.pending_writes.remove(file_name);
+        if let Err(why) = written {
+            warn!("couldn't write disk cache entry {}: {}", file_name, why);
+            if let Some((_, true)) = index.remove_entry(file_name) {
+                if let Some(directory) = path.parent() {
+                    // Only succeeds for empty directories.
+                    let _ = fs::remove_dir(directory);
+                }
+            }
+        }
+    }
+
+    /// Record when every entry was last accessed, if that changed since it was last recorded.
+    /// The index is written to a temporary file first, and then renamed into place.
+    fn write_index_if_changed(&self) {
+        let last_accesses: HashMap<String, u64> = {
+            let mut index = self.index.lock().unwrap();
+            if !index.changed {
+                return;
+            }
+            index.changed = false;
+            index
+                .entries
+                .iter()
+                .map(|(file_name, entry)| (file_name.clone(), entry.last_access))
+                .collect()
+        };
+        let path = self.directory.join(INDEX_FILE_NAME);
+        let temporary_path = path.with_extension(TEMPORARY_FILE_EXTENSION);
+        let written = serde_json::to_vec(&last_accesses)
+            .map_err(io::Error::from)
+            .and_then(|data| fs::write(&temporary_path, data))
+            .and_then(|_| fs::rename(&temporary_path, &path));
+        if let Err(why) = written {
+            warn!("couldn't write {}: {}", path.display(), why);
+            let _ = fs::remove_file(&temporary_path);
+        }
+    }
+}
@@ -0,0 +1,398 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Downloads of responses that are saved to disk rather than displayed,
+//! <https://html.spec.whatwg.org/multipage/#downloading-resources>.
+
+use crate::http_loader::HttpState;
+use content_security_policy as csp;
+use embedder_traits::{DownloadId, DownloadStatus, EmbedderMsg, EmbedderProxy};
+use headers::{ContentLength, ContentRange, HeaderMapExt};
+use http::header::{self, HeaderValue};
+use ipc_channel::ipc::{self, IpcReceiver, IpcSender};
+use msg::constellation_msg::TopLevelBrowsingContextId;
+use net_traits::request::{CacheMode, Initiator, RedirectMode, Request, RequestBuilder};
+use net_traits::response::Response;
+use net_traits::{ContentDisposition, FetchTaskTarget};
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use time::precise_time_ns;
+
+/// The shortest time between two progress reports of a download.
+const PROGRESS_INTERVAL_NS: u64 = 100 * 1000 * 1000;
+
+/// The state of a download, shared with the fetch that writes it to disk.
+struct Download {
+    id: DownloadId,
+    /// The request for the whole resource.
+    request: RequestBuilder,
+    /// The file name suggested by the page that started the download.
+    filename_hint: Option<String>,
+    browser_id: Option<TopLevelBrowsingContextId>,
+    http_state: Arc<HttpState>,
+    embedder_proxy: EmbedderProxy,
+    /// The file the download is saved to, once the embedder chose it.
+    path: Option<PathBuf>,
+    /// The number of bytes that are on disk.
+    received: u64,
+    /// The size of the resource, if the server told it.
+    total: Option<u64>,
+    /// The strong entity tag or the last modification date of the resource, which
+    /// a resumed download makes sure hasn't changed since the download started.
+    validator: Option<HeaderValue>,
+    /// Stops the fetch in progress, if there is one.
+    cancel_chan: Option<IpcSender<()>>,
+    /// Incremented for every fetch, so that a fetch that was cancelled
+    /// can't write to the file once the download was resumed.
+    generation: u32,
+    complete: bool,
+}
+
+impl Download {
+    fn send(&self, msg: EmbedderMsg) {
+        self.embedder_proxy.send((self.browser_id, msg));
+    }
+
+    /// Stop the fetch in progress and report how the download stopped.
+    fn stop(&mut self, status: DownloadStatus) {
+        if let Some(cancel_chan) = self.cancel_chan.take() {
+            let _ = cancel_chan.send(());
+        }
+        self.generation += 1;
+        self.complete = status == DownloadStatus::Complete;
+        self.send(EmbedderMsg::DownloadFinished(self.id, status));
+    }
+
+    /// The fetch that gets the part of the resource that isn't on disk yet.
+    fn fetch(download: &Arc<Mutex<Download>>) -> DownloadFetch {
+        let mut state = download.lock().unwrap();
+        let mut request = state.request.clone();
+        if state.received > 0 {
+            let range = format!("bytes={}-", state.received);
+            request
+                .headers
+                .insert(header::RANGE, HeaderValue::from_str(&range).unwrap());
+            if let Some(ref validator) = state.validator {
+                request.headers.insert(header::IF_RANGE, validator.clone());
+            }
+        }
+
+        let (cancel_sender, cancel_receiver) = ipc::channel().unwrap();
+        state.cancel_chan = Some(cancel_sender);
+        state.generation += 1;
+        DownloadFetch {
+            request,
+            target: DownloadTarget {
+                download: download.clone(),
+                generation: state.generation,
+                file: None,
+                last_progress: 0,
+            },
+            http_state: state.http_state.clone(),
+            cancel_chan: cancel_receiver,
+        }
+    }
+}
+
+/// A fetch to run on behalf of a download.
+pub struct DownloadFetch {
+    pub request: RequestBuilder,
+    pub target: DownloadTarget,
+    pub http_state: Arc<HttpState>,
+    pub cancel_chan: IpcReceiver<()>,
+}
+
+/// Keeps track of the downloads, so that they can be cancelled and resumed.
+#[derive(Default)]
+pub struct DownloadManager {
+    next_id: u32,
+    downloads: HashMap<DownloadId, Arc<Mutex<Download>>>,
+}
+
+impl DownloadManager {
+    pub fn new() -> DownloadManager {
+        DownloadManager::default()
+    }
+
+    /// Start a download, returning the fetch to run for it.
+    pub fn start(
+        &mut self,
+        mut request: RequestBuilder,
+        filename_hint: Option<String>,
+        browser_id: Option<TopLevelBrowsingContextId>,
+        http_state: &Arc<HttpState>,
+    ) -> DownloadFetch {
+        request.initiator = Initiator::Download;
+        // Navigations follow redirects themselves, but a download has no document to load.
+        request.redirect_mode = RedirectMode::Follow;
+        // The body of a download isn't kept in memory, so it can't be cached either.
+        request.cache_mode = CacheMode::NoStore;
+
+        let id = DownloadId(self.next_id);
+        self.next_id += 1;
+        let download = Arc::new(Mutex::new(Download {
+            id,
+            request,
+            filename_hint,
+            browser_id,
+            http_state: http_state.clone(),
+            embedder_proxy: http_state.embedder_proxy.lock().unwrap().clone(),
+            path: None,
+            received: 0,
+            total: None,
+            validator: None,
+            cancel_chan: None,
+            generation: 0,
+            complete: false,
+        }));
+        self.downloads.insert(id, download.clone());
+        Download::fetch(&download)
+    }
+
+    /// Stop a download, keeping what is on disk so far.
+    pub fn cancel(&mut self, id: DownloadId) {
+        let download = match self.downloads.get(&id) {
+            Some(download) => download,
+            None => return warn!("Cancelling unknown download {:?}.", id),
+        };
+        let mut download = download.lock().unwrap();
+        if download.cancel_chan.is_some() {
+            download.stop(DownloadStatus::Cancelled);
+        }
+    }
+
+    /// Resume a download that was cancelled or failed, returning the fetch to run for it,
+    /// which asks for the part of the resource that is missing.
+    pub fn resume(&mut self, id: DownloadId) -> Option<DownloadFetch> {
+        let download = match self.downloads.get(&id) {
+            Some(download) => download,
+            None => {
+                warn!("Resuming unknown download {:?}.", id);
+                return None;
+            },
+        };
+        {
+            let download = download.lock().unwrap();
+            if download.complete || download.cancel_chan.is_some() {
+                return None;
+            }
+        }
+        Some(Download::fetch(download))
+    }
+}
+
+/// Writes the response to a download's fetch to disk.
+pub struct DownloadTarget {
+    download: Arc<Mutex<Download>>,
+    generation: u32,
+    file: Option<File>,
+    /// When progress was last reported.
+    last_progress: u64,
+}
+
+impl DownloadTarget {
+    fn fail(&mut self, reason: String) {
+        self.file = None;
+        let mut download = self.download.lock().unwrap();
+        if download.generation == self.generation {
+            download.stop(DownloadStatus::Failed(reason));
+        }
+    }
+
+    /// Ask the embedder where to save the download, unless it already knows.
+    fn choose_path(&self, response: &Response) -> Option<PathBuf> {
+        let (id, url, filename, embedder_proxy, browser_id) = {
+            let download = self.download.lock().unwrap();
+            if let Some(ref path) = download.path {
+                return Some(path.clone());
+            }
+            let url = response
+                .actual_response()
+                .url()
+                .cloned()
+                .unwrap_or_else(|| download.request.url.clone());
+            // The name the server suggests takes precedence over the one the page suggests.
+            let filename = ContentDisposition::from_response_headers(&response.headers)
+                .and_then(|disposition| disposition.filename)
+                .or_else(|| download.filename_hint.clone())
+                .or_else(|| {
+                    url.as_url()
+                        .path_segments()
+                        .and_then(|segments| segments.last())
+                        .map(|segment| {
+                            percent_encoding::percent_decode_str(segment)
+                                .decode_utf8_lossy()
+                                .into_owned()
+                        })
+                });
+            (
+                download.id,
+                url,
+                sanitize_filename(filename.as_ref().map(|name| &**name).unwrap_or("")),
+                download.embedder_proxy.clone(),
+                download.browser_id,
+            )
+        };
+
+        // The download isn't locked while the user chooses, so that it can still be cancelled.
+        let (sender, receiver) = ipc::channel().unwrap();
+        embedder_proxy.send((
+            browser_id,
+            EmbedderMsg::DownloadStarted(id, url, filename, sender),
+        ));
+        let path = PathBuf::from(receiver.recv().ok()??);
+        self.download.lock().unwrap().path = Some(path.clone());
+        Some(path)
+    }
+
+    fn report_progress(&mut self, download: &Download) {
+        self.last_progress = precise_time_ns();
+        download.send(EmbedderMsg::DownloadProgress(
+            download.id,
+            download.received,
+            download.total,
+        ));
+    }
+}
+
+/// Make a suggested file name safe to create in the download directory.
+fn sanitize_filename(filename: &str) -> String {
+    let filename: String = filename
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    let filename = filename.trim().trim_matches('.');
+    if filename.is_empty() {
+        "download".to_owned()
+    } else {
+        filename.to_owned()
+    }
+}
+
+impl FetchTaskTarget for DownloadTarget {
+    fn process_request_body(&mut self, _: &Request) {}
+
+    fn process_request_eof(&mut self, _: &Request) {}
+
+    fn process_response(&mut self, response: &Response) {
+        if let Some(error) = response.get_network_error() {
+            return self.fail(format!("{:?}", error));
+        }
+        let status = response.actual_response().status.clone();
+        let status = match status {
+            Some((status, _)) if status.is_success() => status,
+            Some((status, _)) => return self.fail(format!("HTTP status {}", status)),
+            None => return self.fail("No HTTP status".to_owned()),
+        };
+
+        let path = match self.choose_path(response) {
+            Some(path) => path,
+            None => {
+                let mut download = self.download.lock().unwrap();
+                if download.generation == self.generation {
+                    download.stop(DownloadStatus::Cancelled);
+                }
+                return;
+            },
+        };
+
+        let download = self.download.clone();
+        let mut download = download.lock().unwrap();
+        if download.generation != self.generation {
+            return;
+        }
+        let headers = &response.headers;
+        // The server may send the whole resource again instead of the range asked for.
+        let resumed = download.received > 0 && status == http::StatusCode::PARTIAL_CONTENT;
+        if !resumed {
+            download.received = 0;
+            download.validator = match headers.get(header::ETAG) {
+                Some(etag) if !etag.as_bytes().starts_with(b"W/") => Some(etag.clone()),
+                _ => headers.get(header::LAST_MODIFIED).cloned(),
+            };
+        }
+        download.total = match headers.typed_get::<ContentRange>() {
+            Some(range) if resumed => range.bytes_len(),
+            _ => headers
+                .typed_get::<ContentLength>()
+                .map(|length| length.0 + download.received),
+        };
+
+        let file = if resumed {
+            OpenOptions::new().append(true).open(&path)
+        } else {
+            File::create(&path)
+        };
+        match file {
+            Ok(file) => self.file = Some(file),
+            Err(error) => {
+                drop(download);
+                return self.fail(format!("Couldn't open {}: {}", path.display(), error));
+            },
+        }
+        self.report_progress(&download);
+    }
+
+    fn process_response_chunk(&mut self, chunk: Vec<u8>) {
+        let download = self.download.clone();
+        let mut download = download.lock().unwrap();
+        if download.generation != self.generation {
+            self.file = None;
+            return;
+        }
+        let result = match self.file {
+            Some(ref mut file) => file.write_all(&chunk),
+            None => return,
+        };
+        if let Err(error) = result {
+            drop(download);
+            return self.fail(format!("Couldn't write the download: {}", error));
+        }
+        download.received += chunk.len() as u64;
+        if precise_time_ns() - self.last_progress >= PROGRESS_INTERVAL_NS {
+            self.report_progress(&download);
+        }
+    }
+
+    fn process_response_eof(&mut self, response: &Response) {
+        let file = match self.file.take() {
+            Some(file) => file,
+            None => return,
+        };
+        if let Err(error) = file.sync_all() {
+            return self.fail(format!("Couldn't write the download: {}", error));
+        }
+
+        let download = self.download.clone();
+        let mut download = download.lock().unwrap();
+        if download.generation != self.generation {
+            return;
+        }
+        let incomplete = response.aborted.load(Ordering::Acquire) ||
+            download
+                .total
+                .map_or(false, |total| download.received < total);
+        if let Some(error) = response.get_network_error() {
+            drop(download);
+            return self.fail(format!("{:?}", error));
+        }
+        if incomplete {
+            drop(download);
+            return self.fail("The connection closed before the download completed".to_owned());
+        }
+        self.report_progress(&download);
+        download.cancel_chan = None;
+        download.stop(DownloadStatus::Complete);
+    }
+
+    fn process_csp_violations(&mut self, _: &Request, _: Vec<csp::Violation>) {}
+}
@@ -15,16 +15,18 @@ use headers::{AccessControlExposeHeaders, ContentType, HeaderMapExt, Range};
 use http::header::{self, HeaderMap, HeaderName};
 use hyper::Method;
 use hyper::StatusCode;
-use ipc_channel::ipc::IpcReceiver;
+use ipc_channel::ipc::{IpcReceiver, IpcSender};
 use mime::{self, Mime};
 use net_traits::blob_url_store::{parse_blob_url, BlobURLStoreError};
 use net_traits::filemanager_thread::RelativePos;
 use net_traits::request::{
     is_cors_safelisted_method, is_cors_safelisted_request_header, Origin, ResponseTainting, Window,
 };
-use net_traits::request::{CredentialsMode, Destination, Referrer, Request, RequestMode};
+use net_traits::request::{CredentialsMode, Destination, ParserMetadata, Referrer};
+use net_traits::request::{Request, RequestMode};
 use net_traits::response::{Response, ResponseBody, ResponseType};
-use net_traits::{FetchTaskTarget, NetworkError, ReferrerPolicy, ResourceFetchTiming};
+use net_traits::{CustomResponseMediator, FetchTaskTarget, NetworkError};
+use net_traits::{ReferrerPolicy, ResourceFetchTiming};
 use net_traits::{ResourceAttribute, ResourceTimeValue};
 use servo_arc::Arc as ServoArc;
 use servo_url::ServoUrl;
@@ -56,6 +58,8 @@ pub struct FetchContext {
     pub user_agent: Cow<'static, str>,
     pub devtools_chan: Option<Sender<DevtoolsControlMsg>>,
     pub filemanager: FileManager,
+    /// Where to send requests that a service worker may handle.
+    pub swmanager_chan: Option<IpcSender<CustomResponseMediator>>,
     pub cancellation_listener: Arc<Mutex<CancellationListener>>,
     pub timing: ServoArc<Mutex<ResourceFetchTiming>>,
 }
@@ -148,28 +152,64 @@ pub fn fetch_with_cors_cache(
     main_fetch(request, cache, false, false, target, &mut None, &context);
 }
 
-/// https://www.w3.org/TR/CSP/#should-block-request
-pub fn should_request_be_blocked_by_csp(request: &Request) -> csp::CheckResult {
+/// The request that content security policies are checked against.
+fn csp_request(request: &Request) -> Option<csp::Request> {
     let origin = match &request.origin {
-        Origin::Client => return csp::CheckResult::Allowed,
+        Origin::Client => return None,
         Origin::Origin(origin) => origin,
     };
-    let csp_request = csp::Request {
-        url: request.url().into_url(),
+    // Nested navigations are the only ones subject to a directive, `frame-src`,
+    // and are told apart from top-level ones by their destination.
+    let destination = match request.destination {
+        Destination::Document if !request.top_level_navigation => Destination::IFrame,
+        destination => destination,
+    };
+    let parser_metadata = match request.parser_metadata {
+        ParserMetadata::ParserInserted => csp::ParserMetadata::ParserInserted,
+        ParserMetadata::NotParserInserted => csp::ParserMetadata::NotParserInserted,
+        ParserMetadata::Default => csp::ParserMetadata::None,
+    };
+    Some(csp::Request {
+        url: request.current_url().into_url(),
         origin: origin.clone().into_url_origin(),
         redirect_count: request.redirect_count,
-        destination: request.destination,
+        destination,
         initiator: csp::Initiator::None,
-        nonce: String::new(),
+        nonce: request.cryptographic_nonce_metadata.clone(),
         integrity_metadata: request.integrity_metadata.clone(),
-        parser_metadata: csp::ParserMetadata::None,
+        parser_metadata,
+    })
+}
+
+/// https://www.w3.org/TR/CSP/#should-block-request
+pub fn should_request_be_blocked_by_csp(
+    request: &Request,
+) -> (csp::CheckResult, Vec<csp::Violation>) {
+    match (request.csp_list.as_ref(), csp_request(request)) {
+        (Some(csp_list), Some(csp_request)) => csp_list.should_request_be_blocked(&csp_request),
+        _ => (csp::CheckResult::Allowed, Vec::new()),
+    }
+}
+
+/// https://www.w3.org/TR/CSP/#should-block-response
+pub fn should_response_to_request_be_blocked_by_csp(
+    request: &Request,
+    response: &Response,
+) -> (csp::CheckResult, Vec<csp::Violation>) {
+    let response_url = match response.actual_response().url() {
+        Some(url) => url.clone().into_url(),
+        None => request.current_url().into_url(),
     };
-    // TODO: Instead of ignoring violations, report them.
-    request
-        .csp_list
-        .as_ref()
-        .map(|c| c.should_request_be_blocked(&csp_request).0)
-        .unwrap_or(csp::CheckResult::Allowed)
+    let csp_response = csp::Response {
+        url: response_url,
+        redirect_count: request.redirect_count,
+    };
+    match (request.csp_list.as_ref(), csp_request(request)) {
+        (Some(csp_list), Some(csp_request)) => {
+            csp_list.should_response_to_request_be_blocked(&csp_request, &csp_response)
+        },
+        _ => (csp::CheckResult::Allowed, Vec::new()),
+    }
 }
 
 /// [Main fetch](https://fetch.spec.whatwg.org/#concept-main-fetch)
@@ -198,10 +238,13 @@ pub fn main_fetch(
     }
 
     // Step 2.2.
-    // TODO: Report violations.
+    let (check_result, violations) = should_request_be_blocked_by_csp(request);
+    if !violations.is_empty() {
+        target.process_csp_violations(request, violations);
+    }
 
     // Step 2.4.
-    if should_request_be_blocked_by_csp(request) == csp::CheckResult::Blocked {
+    if check_result == csp::CheckResult::Blocked {
         response = Some(Response::network_error(NetworkError::Internal(
             "Blocked by Content-Security-Policy".into(),
         )))
@@ -383,6 +426,14 @@ pub fn main_fetch(
             should_be_blocked_due_to_nosniff(request.destination, &response.headers);
         let should_replace_with_mime_type_error = !response_is_network_error &&
             should_be_blocked_due_to_mime_type(request.destination, &response.headers);
+        let should_replace_with_csp_error = !response_is_network_error && {
+            let (check_result, violations) =
+                should_response_to_request_be_blocked_by_csp(request, &response);
+            if !violations.is_empty() {
+                target.process_csp_violations(request, violations);
+            }
+            check_result == csp::CheckResult::Blocked
+        };
 
         // Step 15.
         let mut network_error_response = response
@@ -402,9 +453,14 @@ pub fn main_fetch(
 
         // Step 17.
         // TODO: handle blocking as mixed content.
-        // TODO: handle blocking by content security policy.
         let blocked_error_response;
-        let internal_response = if should_replace_with_nosniff_error {
+        let internal_response = if should_replace_with_csp_error {
+            // Defer rebinding result
+            blocked_error_response = Response::network_error(NetworkError::Internal(
+                "Blocked by Content-Security-Policy".into(),
+            ));
+            &blocked_error_response
+        } else if should_replace_with_nosniff_error {
             // Defer rebinding result
             blocked_error_response =
                 Response::network_error(NetworkError::Internal("Blocked by nosniff".into()));
@@ -501,8 +557,9 @@ pub fn main_fetch(
     // Step 24.
     target.process_response_eof(&response);
 
-    if let Ok(http_cache) = context.state.http_cache.write() {
+    if let Ok(mut http_cache) = context.state.http_cache.write() {
         http_cache.update_awaiting_consumers(&request, &response);
+        http_cache.persist(&request);
     }
 
     // Steps 25-27.
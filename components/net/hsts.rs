@@ -2,6 +2,8 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 
+use crate::journal::Journal;
+use crate::resource_thread::read_json_from_file;
 use embedder_traits::resources::{self, Resource};
 use headers::{Header, HeaderMapExt, HeaderName, HeaderValue};
 use http::HeaderMap;
@@ -10,7 +12,9 @@ use net_traits::IncludeSubdomains;
 use servo_config::pref;
 use servo_url::{Host, ServoUrl};
 use std::collections::HashMap;
+use std::fs;
 use std::net::{Ipv4Addr, Ipv6Addr};
+use std::path::Path;
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct HstsEntry {
@@ -60,12 +64,42 @@ impl HstsEntry {
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct HstsList {
     pub entries_map: HashMap<String, Vec<HstsEntry>>,
+    /// Where the entries of each base domain are written whenever they change.
+    #[serde(skip)]
+    pub journal: Option<Journal>,
 }
 
 impl HstsList {
     pub fn new() -> HstsList {
         HstsList {
             entries_map: HashMap::new(),
+            journal: None,
+        }
+    }
+
+    /// Load the entries persisted in `config_dir` on top of the current ones,
+    /// and persist every later change to them.
+    pub fn open_journal(&mut self, config_dir: &Path) {
+        let (journal, entries_map) = Journal::open::<Vec<HstsEntry>>(config_dir, "hsts_list");
+        match entries_map {
+            Some(entries_map) => self.entries_map.extend(entries_map),
+            None => {
+                // Carry over the entries that older versions only wrote on shutdown.
+                read_json_from_file(self, config_dir, "hsts_list.json");
+                for (base_domain, entries) in &self.entries_map {
+                    journal.put(base_domain, entries);
+                }
+                journal.flush();
+                let _ = fs::remove_file(config_dir.join("hsts_list.json"));
+            },
+        }
+        self.journal = Some(journal);
+    }
+
+    /// Wait until every change to the entries is persisted.
+    pub fn flush_journal(&self) {
+        if let Some(ref journal) = self.journal {
+            journal.flush();
         }
     }
 
@@ -132,12 +166,17 @@ impl HstsList {
         if !have_domain && !have_subdomain {
             entries.push(entry);
         } else if !have_subdomain {
-            for e in entries {
+            for e in entries.iter_mut() {
                 if e.matches_domain(&entry.host) {
                     e.include_subdomains = entry.include_subdomains;
                     e.max_age = entry.max_age;
                 }
             }
+        } else {
+            return;
+        }
+        if let Some(ref journal) = self.journal {
+            journal.put(base_domain, entries);
         }
     }
 
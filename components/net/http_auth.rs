@@ -0,0 +1,490 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! HTTP authentication with the Basic ([RFC 7617](https://tools.ietf.org/html/rfc7617))
+//! and Digest ([RFC 7616](https://tools.ietf.org/html/rfc7616)) schemes.
+//!
+//! Challenges from servers and proxies are answered with credentials from the `AuthCache`,
+//! or with credentials the user enters in a prompt shown by the embedder.
+
+use crate::resource_thread::{AuthCache, AuthCacheEntry};
+use embedder_traits::{AuthenticationChallenge, AuthenticationScheme, EmbedderMsg};
+use embedder_traits::{EmbedderProxy, PromptCredentials, PromptDefinition, PromptOrigin};
+use http::header::{HeaderMap, HeaderName, HeaderValue};
+use hyper::Method;
+use ipc_channel::ipc;
+use openssl::hash::{hash, MessageDigest};
+use openssl::rand::rand_bytes;
+use servo_url::ServoUrl;
+use std::sync::{Mutex, RwLock};
+
+/// The hash algorithm of a Digest challenge.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub enum DigestAlgorithm {
+    Md5,
+    Md5Sess,
+    Sha256,
+    Sha256Sess,
+}
+
+impl DigestAlgorithm {
+    fn from_name(name: &str) -> Option<DigestAlgorithm> {
+        match &*name.to_ascii_uppercase() {
+            "MD5" => Some(DigestAlgorithm::Md5),
+            "MD5-SESS" => Some(DigestAlgorithm::Md5Sess),
+            "SHA-256" => Some(DigestAlgorithm::Sha256),
+            "SHA-256-SESS" => Some(DigestAlgorithm::Sha256Sess),
+            _ => None,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match *self {
+            DigestAlgorithm::Md5 => "MD5",
+            DigestAlgorithm::Md5Sess => "MD5-sess",
+            DigestAlgorithm::Sha256 => "SHA-256",
+            DigestAlgorithm::Sha256Sess => "SHA-256-sess",
+        }
+    }
+
+    fn is_session(&self) -> bool {
+        matches!(
+            *self,
+            DigestAlgorithm::Md5Sess | DigestAlgorithm::Sha256Sess
+        )
+    }
+
+    /// The lowercase hex encoding of the hash of `data`.
+    fn hex_hash(&self, data: &str) -> String {
+        let message_digest = match *self {
+            DigestAlgorithm::Md5 | DigestAlgorithm::Md5Sess => MessageDigest::md5(),
+            DigestAlgorithm::Sha256 | DigestAlgorithm::Sha256Sess => MessageDigest::sha256(),
+        };
+        hash(message_digest, data.as_bytes())
+            .map(|bytes| to_hex(&bytes))
+            .unwrap_or_default()
+    }
+}
+
+/// The parameters of a Digest challenge needed to authenticate further requests.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct DigestChallenge {
+    pub realm: String,
+    pub nonce: String,
+    pub opaque: Option<String>,
+    pub algorithm: DigestAlgorithm,
+    /// Whether the `auth` quality of protection is used.
+    /// Servers that don't offer any are answered as described in RFC 2069.
+    pub qop_auth: bool,
+    /// How many requests have been authenticated with `nonce` so far.
+    pub nonce_count: u32,
+}
+
+impl DigestChallenge {
+    /// The value of the authorization header answering this challenge
+    /// for a request of `method` on `uri`, the request-target of the request.
+    pub fn authorization(
+        &self,
+        credentials: &AuthCacheEntry,
+        method: &Method,
+        uri: &str,
+        client_nonce: &str,
+    ) -> Option<HeaderValue> {
+        let algorithm = self.algorithm;
+        let nonce_count = format!("{:08x}", self.nonce_count);
+
+        let mut ha1 = algorithm.hex_hash(&format!(
+            "{}:{}:{}",
+            credentials.user_name, self.realm, credentials.password
+        ));
+        if algorithm.is_session() {
+            ha1 = algorithm.hex_hash(&format!("{}:{}:{}", ha1, self.nonce, client_nonce));
+        }
+        let ha2 = algorithm.hex_hash(&format!("{}:{}", method.as_str(), uri));
+        let response = if self.qop_auth {
+            algorithm.hex_hash(&format!(
+                "{}:{}:{}:{}:auth:{}",
+                ha1, self.nonce, nonce_count, client_nonce, ha2
+            ))
+        } else {
+            algorithm.hex_hash(&format!("{}:{}:{}", ha1, self.nonce, ha2))
+        };
+
+        let mut value = format!(
+            "Digest username={}, realm={}, nonce={}, uri={}, algorithm={}, response=\"{}\"",
+            quote(&credentials.user_name),
+            quote(&self.realm),
+            quote(&self.nonce),
+            quote(uri),
+            algorithm.name(),
+            response
+        );
+        if self.qop_auth {
+            value.push_str(&format!(
+                ", qop=auth, nc={}, cnonce={}",
+                nonce_count,
+                quote(client_nonce)
+            ));
+        }
+        if let Some(ref opaque) = self.opaque {
+            value.push_str(&format!(", opaque={}", quote(opaque)));
+        }
+        HeaderValue::from_str(&value).ok()
+    }
+}
+
+/// A challenge in a `WWW-Authenticate` or `Proxy-Authenticate` header,
+/// with a scheme this module can answer.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Challenge {
+    Basic {
+        realm: Option<String>,
+    },
+    Digest {
+        challenge: DigestChallenge,
+        /// Whether the credentials sent were right, and only the nonce they answered was stale.
+        stale: bool,
+    },
+}
+
+impl Challenge {
+    fn from_parts(scheme: &str, params: &[(String, String)]) -> Option<Challenge> {
+        let param = |name: &str| {
+            params
+                .iter()
+                .find(|&&(ref param_name, _)| param_name == name)
+                .map(|&(_, ref value)| value.clone())
+        };
+        match &*scheme.to_ascii_lowercase() {
+            "basic" => Some(Challenge::Basic {
+                realm: param("realm"),
+            }),
+            "digest" => {
+                let algorithm = match param("algorithm") {
+                    Some(name) => DigestAlgorithm::from_name(&name)?,
+                    None => DigestAlgorithm::Md5,
+                };
+                let qop_auth = match param("qop") {
+                    Some(qop) => {
+                        if !qop.split(',').any(|qop| qop.trim() == "auth") {
+                            // Only `auth-int` is offered, which isn't supported.
+                            return None;
+                        }
+                        true
+                    },
+                    None if algorithm.is_session() => return None,
+                    None => false,
+                };
+                let stale =
+                    param("stale").map_or(false, |stale| stale.eq_ignore_ascii_case("true"));
+                Some(Challenge::Digest {
+                    challenge: DigestChallenge {
+                        realm: param("realm").unwrap_or_default(),
+                        nonce: param("nonce")?,
+                        opaque: param("opaque"),
+                        algorithm,
+                        qop_auth,
+                        nonce_count: 0,
+                    },
+                    stale,
+                })
+            },
+            _ => None,
+        }
+    }
+
+    pub fn scheme(&self) -> AuthenticationScheme {
+        match *self {
+            Challenge::Basic { .. } => AuthenticationScheme::Basic,
+            Challenge::Digest { .. } => AuthenticationScheme::Digest,
+        }
+    }
+
+    pub fn realm(&self) -> Option<&str> {
+        match *self {
+            Challenge::Basic { ref realm } => realm.as_ref().map(|realm| &**realm),
+            Challenge::Digest { ref challenge, .. } => Some(&*challenge.realm),
+        }
+    }
+
+    /// How much to prefer answering this challenge over the others offered.
+    fn strength(&self) -> u8 {
+        match *self {
+            Challenge::Basic { .. } => 0,
+            Challenge::Digest { ref challenge, .. } => match challenge.algorithm {
+                DigestAlgorithm::Md5 | DigestAlgorithm::Md5Sess => 1,
+                DigestAlgorithm::Sha256 | DigestAlgorithm::Sha256Sess => 2,
+            },
+        }
+    }
+}
+
+/// Parses the challenges in the `header` headers, skipping those with an unsupported scheme.
+pub fn parse_challenges(headers: &HeaderMap, header: HeaderName) -> Vec<Challenge> {
+    headers
+        .get_all(header)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| ChallengeParser { input: value }.parse())
+        .collect()
+}
+
+/// The strongest challenge in the `header` headers this module can answer, if any.
+pub fn preferred_challenge(headers: &HeaderMap, header: HeaderName) -> Option<Challenge> {
+    let challenges = parse_challenges(headers, header);
+    // The first of equally strong challenges is the one the server prefers.
+    challenges.into_iter().rev().max_by_key(Challenge::strength)
+}
+
+/// A parser for the `#challenge` grammar of
+/// <https://tools.ietf.org/html/rfc7235#section-4.1>.
+struct ChallengeParser<'a> {
+    input: &'a str,
+}
+
+impl<'a> ChallengeParser<'a> {
+    fn parse(&mut self) -> Vec<Challenge> {
+        let mut challenges = vec![];
+        loop {
+            self.skip_separators();
+            let scheme = match self.token() {
+                Some(scheme) => scheme,
+                None => break,
+            };
+            if self.token68() {
+                // The credentials of schemes this module doesn't use.
+                continue;
+            }
+            let mut params = vec![];
+            loop {
+                let before_param = self.input;
+                self.skip_separators();
+                let param = self.token().and_then(|name| {
+                    self.skip_whitespace();
+                    if !self.eat('=') {
+                        return None;
+                    }
+                    self.skip_whitespace();
+                    let value = if self.input.starts_with('"') {
+                        self.quoted_string()
+                    } else {
+                        self.token().map(str::to_owned)
+                    };
+                    value.map(|value| (name.to_ascii_lowercase(), value))
+                });
+                match param {
+                    Some(param) => params.push(param),
+                    None => {
+                        // The start of the next challenge.
+                        self.input = before_param;
+                        break;
+                    },
+                }
+            }
+            challenges.extend(Challenge::from_parts(scheme, &params));
+        }
+        challenges
+    }
+
+    fn skip_whitespace(&mut self) {
+        self.input = self.input.trim_start_matches(|c| c == ' ' || c == '\t');
+    }
+
+    fn skip_separators(&mut self) {
+        self.input = self
+            .input
+            .trim_start_matches(|c| c == ' ' || c == '\t' || c == ',');
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.input.starts_with(c) {
+            self.input = &self.input[c.len_utf8()..];
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Skip a token68, if the challenge consists of one.
+    /// <https://tools.ietf.org/html/rfc7235#section-2.1>
+    fn token68(&mut self) -> bool {
+        let input = self.input;
+        self.skip_whitespace();
+        let is_token68_char = |c: char| c.is_ascii_alphanumeric() || "-._~+/".contains(c);
+        let rest = self.input.trim_start_matches(is_token68_char);
+        if rest.len() == self.input.len() {
+            self.input = input;
+            return false;
+        }
+        let rest = rest
+            .trim_start_matches('=')
+            .trim_start_matches(|c| c == ' ' || c == '\t');
+        if !rest.is_empty() && !rest.starts_with(',') {
+            self.input = input;
+            return false;
+        }
+        self.input = rest;
+        true
+    }
+
+    /// <https://tools.ietf.org/html/rfc7230#section-3.2.6>
+    fn token(&mut self) -> Option<&'a str> {
+        let is_tchar = |c: char| c.is_ascii_alphanumeric() || "!#$%&'*+-.^_`|~".contains(c);
+        let length = self
+            .input
+            .find(|c| !is_tchar(c))
+            .unwrap_or(self.input.len());
+        if length == 0 {
+            return None;
+        }
+        let (token, rest) = self.input.split_at(length);
+        self.input = rest;
+        Some(token)
+    }
+
+    /// <https://tools.ietf.org/html/rfc7230#section-3.2.6>
+    fn quoted_string(&mut self) -> Option<String> {
+        let mut value = String::new();
+        let mut chars = self.input.char_indices().skip(1);
+        while let Some((index, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.input = &self.input[index + 1..];
+                    return Some(value);
+                },
+                '\\' => value.push(chars.next()?.1),
+                c => value.push(c),
+            }
+        }
+        None
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn client_nonce() -> String {
+    let mut bytes = [0; 16];
+    if let Err(error) = rand_bytes(&mut bytes) {
+        warn!("couldn't generate a client nonce: {}", error);
+    }
+    to_hex(&bytes)
+}
+
+/// The value of the authorization header sending `user_name` and `password`
+/// with Basic authentication.
+pub fn basic_authorization(user_name: &str, password: &str) -> Option<HeaderValue> {
+    let encoded = base64::encode(&format!("{}:{}", user_name, password));
+    HeaderValue::from_str(&format!("Basic {}", encoded)).ok()
+}
+
+/// The value of the authorization header for a request of `method` on `uri`,
+/// made with the credentials cached under `key`, if any.
+///
+/// `uri` is the request-target of the request, which Digest authentication covers.
+pub fn authorization_from_cache(
+    auth_cache: &RwLock<AuthCache>,
+    key: &str,
+    method: &Method,
+    uri: &str,
+) -> Option<HeaderValue> {
+    let mut auth_cache = auth_cache.write().unwrap();
+    let auth_cache = &mut *auth_cache;
+    let credentials = auth_cache.entries.get(key)?;
+    match auth_cache.digest_challenges.get_mut(key) {
+        Some(digest_challenge) => {
+            digest_challenge.nonce_count += 1;
+            digest_challenge.authorization(credentials, method, uri, &client_nonce())
+        },
+        None => basic_authorization(&credentials.user_name, &credentials.password),
+    }
+}
+
+/// Cache `credentials` under `key`, to answer `challenge` and the requests that follow it.
+pub fn store_credentials(
+    auth_cache: &RwLock<AuthCache>,
+    key: String,
+    credentials: PromptCredentials,
+    challenge: Challenge,
+) {
+    let mut auth_cache = auth_cache.write().unwrap();
+    match challenge {
+        Challenge::Digest { challenge, .. } => {
+            auth_cache.digest_challenges.insert(key.clone(), challenge);
+        },
+        Challenge::Basic { .. } => {
+            auth_cache.digest_challenges.remove(&key);
+        },
+    }
+    auth_cache.entries.insert(
+        key.clone(),
+        AuthCacheEntry {
+            user_name: credentials.user_name,
+            password: credentials.password,
+        },
+    );
+    auth_cache.persist(&key);
+}
+
+/// Answer a Digest challenge which only marked the nonce of the cached credentials as stale,
+/// with the same credentials and the new nonce. Returns false if there is nothing to answer with.
+pub fn renew_stale_nonce(auth_cache: &RwLock<AuthCache>, key: &str, challenge: &Challenge) -> bool {
+    let digest_challenge = match *challenge {
+        Challenge::Digest {
+            ref challenge,
+            stale: true,
+        } => challenge,
+        _ => return false,
+    };
+    let mut auth_cache = auth_cache.write().unwrap();
+    if !auth_cache.entries.contains_key(key) {
+        return false;
+    }
+    auth_cache
+        .digest_challenges
+        .insert(key.to_owned(), digest_challenge.clone());
+    auth_cache.persist(key);
+    true
+}
+
+/// Remove the credentials cached under `key`, once they have been rejected.
+pub fn forget_credentials(auth_cache: &RwLock<AuthCache>, key: &str) {
+    let mut auth_cache = auth_cache.write().unwrap();
+    auth_cache.entries.remove(key);
+    auth_cache.digest_challenges.remove(key);
+    auth_cache.persist(key);
+}
+
+/// Ask the user, through the embedder, for credentials answering `challenge`
+/// to the fetch of `url`. Returns `None` if the user declined.
+pub fn prompt_user_for_credentials(
+    embedder_proxy: &Mutex<EmbedderProxy>,
+    url: &ServoUrl,
+    challenge: &Challenge,
+    is_proxy: bool,
+) -> Option<PromptCredentials> {
+    let (ipc_sender, ipc_receiver) = ipc::channel().expect("Failed to create IPC channel!");
+    let challenge = AuthenticationChallenge {
+        url: url.clone(),
+        realm: challenge.realm().map(str::to_owned),
+        scheme: challenge.scheme(),
+        is_proxy,
+    };
+    let prompt = PromptDefinition::Credentials(challenge, ipc_sender);
+    // The realm shown in the prompt is chosen by the server.
+    let msg = EmbedderMsg::Prompt(prompt, PromptOrigin::Untrusted);
+    embedder_proxy.lock().unwrap().send((None, msg));
+    match ipc_receiver.recv() {
+        Ok(credentials) => credentials,
+        Err(e) => {
+            warn!("Failed to receive credentials from embedder ({}).", e);
+            None
+        },
+    }
+}
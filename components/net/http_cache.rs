@@ -6,8 +6,14 @@
 
 //! A memory cache implementing the logic specified in <http://tools.ietf.org/html/rfc7234>
 //! and <http://tools.ietf.org/html/rfc7232>.
+//!
+//! A persistent cache additionally keeps completed resources in a size-bounded store on disk,
+//! from which they are loaded back into memory when first requested, without holding the lock
+//! on the cache while reading them.
 
+use crate::disk_cache::DiskCache;
 use crate::fetch::methods::{Data, DoneChannel};
+use crate::quota_thread::QuotaAccount;
 use crossbeam_channel::{unbounded, Sender};
 use headers::{
     CacheControl, ContentRange, Expires, HeaderMapExt, LastModified, Pragma, Range, Vary,
@@ -15,19 +21,22 @@ use headers::{
 use http::header::HeaderValue;
 use http::{header, HeaderMap};
 use hyper::{Method, StatusCode};
+use ipc_channel::ipc::IpcSender;
 use malloc_size_of::Measurable;
 use malloc_size_of::{
     MallocSizeOf, MallocSizeOfOps, MallocUnconditionalShallowSizeOf, MallocUnconditionalSizeOf,
 };
+use net_traits::quota_thread::{QuotaClient, QuotaThreadMsg};
 use net_traits::request::Request;
 use net_traits::response::{HttpsState, Response, ResponseBody};
 use net_traits::{FetchMetadata, Metadata, ResourceFetchTiming};
 use servo_arc::Arc;
-use servo_url::ServoUrl;
+use servo_url::{ImmutableOrigin, ServoUrl};
 use std::collections::HashMap;
 use std::ops::Bound;
+use std::path::Path;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Mutex;
+use std::sync::{Mutex, RwLock};
 use std::time::SystemTime;
 use time::{Duration, Timespec, Tm};
 
@@ -50,6 +59,16 @@ impl CacheKey {
             url: servo_url.clone(),
         }
     }
+
+    /// The key under which the resources are stored on disk.
+    fn disk_key(&self) -> &str {
+        self.url.as_str()
+    }
+
+    /// The group in which the resources are stored on disk, that of their origin.
+    fn disk_group(&self) -> String {
+        self.url.origin().ascii_serialization()
+    }
 }
 
 /// A complete cached resource.
@@ -72,6 +91,8 @@ struct MeasurableCachedResource {
     url_list: Vec<ServoUrl>,
     expires: Duration,
     last_validated: Tm,
+    /// Whether the resource, in its current state, has been written to disk.
+    persisted: bool,
 }
 
 impl MallocSizeOf for CachedResource {
@@ -121,11 +142,129 @@ pub struct CachedResponse {
     pub needs_validation: bool,
 }
 
-/// A memory cache.
+/// The version of the format in which resources are stored on disk.
+/// Entries stored with any other version are discarded.
+const DISK_CACHE_FORMAT_VERSION: u32 = 1;
+
+/// The name of the directory, inside the config dir, holding the disk cache.
+const DISK_CACHE_DIRECTORY_NAME: &'static str = "http_cache";
+
+/// All the resources stored on disk under a given cache key.
+#[derive(Deserialize, Serialize)]
+struct DiskCacheEntry {
+    version: u32,
+    url: ServoUrl,
+    resources: Vec<DiskCachedResource>,
+}
+
+/// A complete cached resource, in the form it is stored on disk.
+#[derive(Deserialize, Serialize)]
+struct DiskCachedResource {
+    #[serde(
+        deserialize_with = "::hyper_serde::deserialize",
+        serialize_with = "::hyper_serde::serialize"
+    )]
+    request_headers: HeaderMap,
+    #[serde(
+        deserialize_with = "::hyper_serde::deserialize",
+        serialize_with = "::hyper_serde::serialize"
+    )]
+    headers: HeaderMap,
+    body: Vec<u8>,
+    final_url: ServoUrl,
+    content_type: Option<String>,
+    charset: Option<String>,
+    metadata_status: Option<(u16, Vec<u8>)>,
+    location_url: Option<Result<ServoUrl, String>>,
+    https_state: HttpsState,
+    status: Option<(u16, String)>,
+    raw_status: Option<(u16, Vec<u8>)>,
+    url_list: Vec<ServoUrl>,
+    /// The freshness lifetime, in seconds.
+    expires: i64,
+    /// The time of the last validation, in seconds since the epoch.
+    last_validated: i64,
+}
+
+impl CachedResource {
+    /// The representation of this resource on disk,
+    /// if it is complete, and can therefore be persisted.
+    fn to_disk(&self) -> Option<DiskCachedResource> {
+        if self.aborted.load(Ordering::Acquire) {
+            return None;
+        }
+        let body = match *self.body.lock().unwrap() {
+            ResponseBody::Done(ref body) => body.clone(),
+            ResponseBody::Empty | ResponseBody::Receiving(_) => return None,
+        };
+        let metadata = &self.data.metadata;
+        Some(DiskCachedResource {
+            request_headers: self.request_headers.lock().unwrap().clone(),
+            headers: metadata.headers.lock().unwrap().clone(),
+            body,
+            final_url: metadata.data.final_url.clone(),
+            content_type: metadata.data.content_type.clone(),
+            charset: metadata.data.charset.clone(),
+            metadata_status: metadata.data.status.clone(),
+            location_url: self.data.location_url.clone(),
+            https_state: self.data.https_state,
+            status: self
+                .data
+                .status
+                .as_ref()
+                .map(|&(ref code, ref reason)| (code.as_u16(), reason.clone())),
+            raw_status: self.data.raw_status.clone(),
+            url_list: self.data.url_list.clone(),
+            expires: self.data.expires.num_seconds(),
+            last_validated: self.data.last_validated.to_timespec().sec,
+        })
+    }
+
+    /// Re-create a resource read back from disk.
+    fn from_disk(resource: DiskCachedResource) -> Option<CachedResource> {
+        let status = match resource.status {
+            Some((code, reason)) => Some((StatusCode::from_u16(code).ok()?, reason)),
+            None => None,
+        };
+        Some(CachedResource {
+            request_headers: Arc::new(Mutex::new(resource.request_headers)),
+            body: Arc::new(Mutex::new(ResponseBody::Done(resource.body))),
+            aborted: Arc::new(AtomicBool::new(false)),
+            awaiting_body: Arc::new(Mutex::new(vec![])),
+            data: Measurable(MeasurableCachedResource {
+                metadata: CachedMetadata {
+                    headers: Arc::new(Mutex::new(resource.headers)),
+                    data: Measurable(MeasurableCachedMetadata {
+                        final_url: resource.final_url,
+                        content_type: resource.content_type,
+                        charset: resource.charset,
+                        status: resource.metadata_status,
+                    }),
+                },
+                location_url: resource.location_url,
+                https_state: resource.https_state,
+                status,
+                raw_status: resource.raw_status,
+                url_list: resource.url_list,
+                expires: Duration::seconds(resource.expires),
+                last_validated: time::at(Timespec::new(resource.last_validated, 0)),
+                persisted: true,
+            }),
+        })
+    }
+}
+
+/// A memory cache, optionally backed by a disk cache.
 #[derive(MallocSizeOf)]
 pub struct HttpCache {
     /// cached responses.
     entries: HashMap<CacheKey, Vec<CachedResource>>,
+    /// The store of complete responses on disk, for a persistent cache.
+    #[ignore_malloc_size_of = "Entries are stored on disk"]
+    disk_cache: Option<Arc<DiskCache>>,
+    /// Where the size of the entries on disk for each origin is accounted for.
+    #[ignore_malloc_size_of = "Channels are hard"]
+    quota: QuotaAccount,
 }
 
 /// Determine if a response is cacheable by default <https://tools.ietf.org/html/rfc7231#section-6.1>
@@ -372,6 +511,7 @@ fn create_resource_with_bytes_from_resource(
             url_list: resource.data.url_list.clone(),
             expires: resource.data.expires.clone(),
             last_validated: resource.data.last_validated.clone(),
+            persisted: false,
         }),
     }
 }
@@ -587,6 +727,175 @@ impl HttpCache {
     pub fn new() -> HttpCache {
         HttpCache {
             entries: HashMap::new(),
+            disk_cache: None,
+            quota: QuotaAccount::new(QuotaClient::HttpCache, None),
+        }
+    }
+
+    /// Create a new persistent cache instance,
+    /// storing up to `max_disk_size` bytes of complete responses in `config_dir`.
+    pub fn new_persistent(config_dir: &Path, max_disk_size: u64) -> HttpCache {
+        HttpCache {
+            entries: HashMap::new(),
+            disk_cache: Some(Arc::new(DiskCache::new(
+                config_dir.join(DISK_CACHE_DIRECTORY_NAME),
+                max_disk_size,
+            ))),
+            quota: QuotaAccount::new(QuotaClient::HttpCache, None),
+        }
+    }
+
+    /// Account for the entries on disk with the quota manager from now on.
+    pub fn set_quota_manager(&mut self, sender: IpcSender<QuotaThreadMsg>) {
+        self.quota = QuotaAccount::new(QuotaClient::HttpCache, Some(sender));
+        self.report_usage();
+    }
+
+    /// Report the size of the entries on disk of every origin whose entries changed.
+    fn report_usage(&mut self) {
+        let disk_cache = match self.disk_cache {
+            Some(ref disk_cache) => disk_cache,
+            None => return,
+        };
+        for (group, size) in disk_cache.take_changed_groups() {
+            if let Ok(url) = ServoUrl::parse(&group) {
+                self.quota.set_usage(&url.origin(), size);
+            }
+        }
+    }
+
+    /// Remove every resource of `origin`, from memory and from disk.
+    pub fn clear_origin(&mut self, origin: &ImmutableOrigin) {
+        self.entries.retain(|key, _| key.url.origin() != *origin);
+        if let Some(ref disk_cache) = self.disk_cache {
+            disk_cache.remove_group(&origin.ascii_serialization());
+        }
+        self.report_usage();
+    }
+
+    /// Load the resources stored on disk for a request into `http_cache`,
+    /// unless resources for the request are already in memory.
+    /// The lock on the cache is not held while they are read and decoded.
+    pub fn load_from_disk(http_cache: &RwLock<HttpCache>, request: &Request) {
+        if request.method != Method::GET {
+            return;
+        }
+        let entry_key = CacheKey::new(&request);
+        let disk_cache = match http_cache.read() {
+            Ok(http_cache) => {
+                if http_cache.entries.contains_key(&entry_key) {
+                    return;
+                }
+                match http_cache.disk_cache {
+                    Some(ref disk_cache) => disk_cache.clone(),
+                    None => return,
+                }
+            },
+            Err(_) => return,
+        };
+        let data = match disk_cache.get(entry_key.disk_key()) {
+            Some(data) => data,
+            None => return,
+        };
+        let entry = bincode::deserialize::<DiskCacheEntry>(&data)
+            .ok()
+            .filter(|entry| {
+                entry.version == DISK_CACHE_FORMAT_VERSION && entry.url == entry_key.url
+            });
+        let resources: Option<Vec<CachedResource>> = entry.and_then(|entry| {
+            entry
+                .resources
+                .into_iter()
+                .map(CachedResource::from_disk)
+                .collect()
+        });
+        let mut http_cache = match http_cache.write() {
+            Ok(http_cache) => http_cache,
+            Err(_) => return,
+        };
+        if http_cache.entries.contains_key(&entry_key) {
+            // Resources were stored for the request in the meantime.
+            return;
+        }
+        match resources {
+            Some(resources) => {
+                debug!(
+                    "loaded {} resources from disk for {}",
+                    resources.len(),
+                    entry_key.url
+                );
+                http_cache.entries.insert(entry_key, resources);
+            },
+            None => {
+                warn!(
+                    "discarding corrupted disk cache entry for {}",
+                    entry_key.url
+                );
+                disk_cache.remove(entry_key.disk_key());
+                http_cache.report_usage();
+            },
+        }
+    }
+
+    /// Write the complete resources in memory for a key to disk,
+    /// replacing those previously stored.
+    fn write_to_disk(&mut self, entry_key: &CacheKey) {
+        let disk_cache = match self.disk_cache {
+            Some(ref disk_cache) => disk_cache,
+            None => return,
+        };
+        let mut resources = vec![];
+        if let Some(cached_resources) = self.entries.get_mut(entry_key) {
+            for cached_resource in cached_resources.iter_mut() {
+                if let Some(resource) = cached_resource.to_disk() {
+                    cached_resource.data.persisted = true;
+                    resources.push(resource);
+                }
+            }
+        }
+        if resources.is_empty() {
+            disk_cache.remove(entry_key.disk_key());
+        } else {
+            let entry = DiskCacheEntry {
+                version: DISK_CACHE_FORMAT_VERSION,
+                url: entry_key.url.clone(),
+                resources,
+            };
+            match bincode::serialize(&entry) {
+                Ok(data) => disk_cache.put(entry_key.disk_key(), &entry_key.disk_group(), data),
+                Err(why) => warn!(
+                    "couldn't serialize cache entry for {}: {}",
+                    entry_key.url, why
+                ),
+            }
+        }
+        self.report_usage();
+    }
+
+    /// Write the resources matching a request to disk,
+    /// if any of them completed since they were last written.
+    pub fn persist(&mut self, request: &Request) {
+        if self.disk_cache.is_none() {
+            return;
+        }
+        let entry_key = CacheKey::new(&request);
+        let needs_writing = match self.entries.get(&entry_key) {
+            Some(cached_resources) => cached_resources.iter().any(|resource| {
+                !resource.data.persisted &&
+                    !resource.aborted.load(Ordering::Acquire) &&
+                    matches!(*resource.body.lock().unwrap(), ResponseBody::Done(_))
+            }),
+            None => false,
+        };
+        if needs_writing {
+            self.write_to_disk(&entry_key);
+        }
+    }
+
+    /// Wait until every resource written to disk so far is stored.
+    pub fn flush(&self) {
+        if let Some(ref disk_cache) = self.disk_cache {
+            disk_cache.flush();
         }
     }
 
@@ -759,6 +1068,7 @@ impl HttpCache {
     ) -> Option<Response> {
         assert_eq!(response.status.map(|s| s.0), Some(StatusCode::NOT_MODIFIED));
         let entry_key = CacheKey::new(&request);
+        let mut refreshed_response = None;
         if let Some(cached_resources) = self.entries.get_mut(&entry_key) {
             for cached_resource in cached_resources.iter_mut() {
                 // done_chan will have been set to Some(..) by http_network_fetch.
@@ -796,13 +1106,19 @@ impl HttpCache {
                 constructed_response.raw_status = cached_resource.data.raw_status.clone();
                 constructed_response.url_list = cached_resource.data.url_list.clone();
                 cached_resource.data.expires = get_response_expiry(&constructed_response);
+                cached_resource.data.persisted = false;
                 let mut stored_headers = cached_resource.data.metadata.headers.lock().unwrap();
                 stored_headers.extend(response.headers);
                 constructed_response.headers = stored_headers.clone();
-                return Some(constructed_response);
+                refreshed_response = Some(constructed_response);
+                break;
             }
         }
-        None
+        if refreshed_response.is_some() {
+            // Store the updated headers, if the body is already complete.
+            self.persist(request);
+        }
+        refreshed_response
     }
 
     fn invalidate_for_url(&mut self, url: &ServoUrl) {
@@ -812,6 +1128,11 @@ impl HttpCache {
                 cached_resource.data.expires = Duration::seconds(0i64);
             }
         }
+        // Resources that are only on disk are removed rather than read back to be expired,
+        // and those in memory are written back expired.
+        if self.disk_cache.is_some() {
+            self.write_to_disk(&entry_key);
+        }
     }
 
     /// Invalidation.
@@ -858,6 +1179,8 @@ impl HttpCache {
             // responses to be stored is present in the response.
             return;
         };
+        // The resources already stored on disk were loaded when trying to construct a response,
+        // and are kept alongside the new one.
         let entry_key = CacheKey::new(&request);
         let metadata = match response.metadata() {
             Ok(FetchMetadata::Filtered {
@@ -894,6 +1217,7 @@ impl HttpCache {
                 url_list: response.url_list.clone(),
                 expires: expiry,
                 last_validated: time::now(),
+                persisted: false,
             }),
         };
         let entry = self.entries.entry(entry_key).or_insert_with(|| vec![]);
@@ -2,46 +2,51 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 
-use crate::connector::{create_http_client, Connector, TlsConfig};
-use crate::cookie;
+use crate::connector::{create_http_client, ConnectionTiming, Connector, TlsConfig};
+use crate::cookie::{self, SameSiteContext};
 use crate::cookie_storage::CookieStorage;
 use crate::decoder::Decoder;
 use crate::fetch::cors_cache::CorsCache;
 use crate::fetch::methods::{main_fetch, Data, DoneChannel, FetchContext, Target};
 use crate::hsts::HstsList;
+use crate::http_auth::{authorization_from_cache, basic_authorization, forget_credentials};
+use crate::http_auth::{preferred_challenge, prompt_user_for_credentials};
+use crate::http_auth::{renew_stale_nonce, store_credentials};
 use crate::http_cache::{CacheKey, HttpCache};
+use crate::proxy::{ProxyConfig, TunnelAuthenticationRequired};
 use crate::resource_thread::AuthCache;
 use crossbeam_channel::{unbounded, Sender};
 use devtools_traits::{
     ChromeToDevtoolsControlMsg, DevtoolsControlMsg, HttpRequest as DevtoolsHttpRequest,
 };
 use devtools_traits::{HttpResponse as DevtoolsHttpResponse, NetworkEvent};
-use headers::authorization::Basic;
+use embedder_traits::EmbedderProxy;
 use headers::{AccessControlAllowCredentials, AccessControlAllowHeaders, HeaderMapExt};
-use headers::{
-    AccessControlAllowMethods, AccessControlRequestHeaders, AccessControlRequestMethod,
-    Authorization,
-};
+use headers::{AccessControlAllowMethods, AccessControlRequestHeaders, AccessControlRequestMethod};
 use headers::{AccessControlAllowOrigin, AccessControlMaxAge};
 use headers::{CacheControl, ContentEncoding, ContentLength};
 use headers::{IfModifiedSince, LastModified, Origin as HyperOrigin, Pragma, Referer, UserAgent};
 use http::header::{self, HeaderName, HeaderValue};
-use http::{HeaderMap, Request as HyperRequest};
+use http::{HeaderMap, Request as HyperRequest, Version};
 use hyper::{Body, Client, Method, Response as HyperResponse, StatusCode};
 use hyper_serde::Serde;
+use ipc_channel::ipc;
 use msg::constellation_msg::{HistoryStateId, PipelineId};
+use net_traits::pub_domains::reg_suffix;
 use net_traits::quality::{quality_to_value, Quality, QualityItem};
 use net_traits::request::Origin::Origin as SpecificOrigin;
 use net_traits::request::{is_cors_safelisted_method, is_cors_safelisted_request_header};
-use net_traits::request::{CacheMode, CredentialsMode, Destination, Origin};
+use net_traits::request::{CacheMode, CredentialsMode, Destination, Initiator, Origin};
 use net_traits::request::{RedirectMode, Referrer, Request, RequestBuilder, RequestMode};
-use net_traits::request::{ResponseTainting, ServiceWorkersMode};
+use net_traits::request::{ResponseTainting, ServiceWorkersMode, Window};
 use net_traits::response::{HttpsState, Response, ResponseBody, ResponseType};
-use net_traits::{CookieSource, FetchMetadata, NetworkError, ReferrerPolicy};
+use net_traits::{CookieSource, CrossOriginEmbedderPolicy, CustomResponseMediator, FetchMetadata};
+use net_traits::{NetworkError, ReferrerPolicy};
 use net_traits::{
     RedirectEndValue, RedirectStartValue, ResourceAttribute, ResourceFetchTiming, ResourceTimeValue,
 };
 use servo_arc::Arc;
+use servo_config::pref;
 use servo_url::{ImmutableOrigin, ServoUrl};
 use std::collections::{HashMap, HashSet};
 use std::iter::FromIterator;
@@ -53,6 +58,7 @@ use std::time::{Duration, SystemTime};
 use time::{self, Tm};
 use tokio::prelude::{future, Future, Stream};
 use tokio::runtime::Runtime;
+use url::{Host, Position};
 
 lazy_static! {
     pub static ref HANDLE: Mutex<Runtime> = { Mutex::new(Runtime::new().unwrap()) };
@@ -77,21 +83,42 @@ pub struct HttpState {
     /// reflecting whether the cache entry is ready to read from,
     /// or whether a concurrent pending store should be awaited.
     pub http_cache_state: Mutex<HashMap<CacheKey, Arc<(Mutex<HttpCacheEntryState>, Condvar)>>>,
-    pub auth_cache: RwLock<AuthCache>,
+    /// Shared with the connector of `client`, which answers proxies with the credentials in it.
+    pub auth_cache: Arc<RwLock<AuthCache>>,
     pub history_states: RwLock<HashMap<HistoryStateId, Vec<u8>>>,
     pub client: Client<Connector, Body>,
+    /// The proxies the client connects through.
+    pub proxy_config: ProxyConfig,
+    /// Used to prompt the user for credentials.
+    pub embedder_proxy: Mutex<EmbedderProxy>,
 }
 
 impl HttpState {
-    pub fn new(tls_config: TlsConfig) -> HttpState {
+    pub fn new(tls_config: TlsConfig, embedder_proxy: EmbedderProxy) -> HttpState {
+        HttpState::new_with_proxy_config(tls_config, ProxyConfig::default(), embedder_proxy)
+    }
+
+    pub fn new_with_proxy_config(
+        tls_config: TlsConfig,
+        proxy_config: ProxyConfig,
+        embedder_proxy: EmbedderProxy,
+    ) -> HttpState {
+        let auth_cache = Arc::new(RwLock::new(AuthCache::new()));
         HttpState {
             hsts_list: RwLock::new(HstsList::new()),
             cookie_jar: RwLock::new(CookieStorage::new(150)),
-            auth_cache: RwLock::new(AuthCache::new()),
+            auth_cache: auth_cache.clone(),
             history_states: RwLock::new(HashMap::new()),
             http_cache: RwLock::new(HttpCache::new()),
             http_cache_state: Mutex::new(HashMap::new()),
-            client: create_http_client(tls_config, HANDLE.lock().unwrap().executor()),
+            client: create_http_client(
+                tls_config,
+                proxy_config.clone(),
+                auth_cache,
+                HANDLE.lock().unwrap().executor(),
+            ),
+            proxy_config,
+            embedder_proxy: Mutex::new(embedder_proxy),
         }
     }
 }
@@ -246,10 +273,13 @@ pub fn set_request_cookies(
     url: &ServoUrl,
     headers: &mut HeaderMap,
     cookie_jar: &RwLock<CookieStorage>,
+    context: SameSiteContext,
 ) {
     let mut cookie_jar = cookie_jar.write().unwrap();
     cookie_jar.remove_expired_cookies_for_url(url);
-    if let Some(cookie_list) = cookie_jar.cookies_for_url(url, CookieSource::HTTP) {
+    if let Some(cookie_list) =
+        cookie_jar.cookies_for_url_in_context(url, CookieSource::HTTP, context)
+    {
         headers.insert(
             header::COOKIE,
             HeaderValue::from_bytes(cookie_list.as_bytes()).unwrap(),
@@ -257,12 +287,19 @@ pub fn set_request_cookies(
     }
 }
 
-fn set_cookie_for_url(cookie_jar: &RwLock<CookieStorage>, request: &ServoUrl, cookie_val: &str) {
+fn set_cookie_for_url(
+    cookie_jar: &RwLock<CookieStorage>,
+    request: &ServoUrl,
+    cookie_val: &str,
+    context: SameSiteContext,
+) {
     let mut cookie_jar = cookie_jar.write().unwrap();
     let source = CookieSource::HTTP;
 
     if let Some(cookie) = cookie::Cookie::from_cookie_string(cookie_val.into(), request, source) {
-        cookie_jar.push(cookie, request, source);
+        if cookie.may_be_set_in_context(context) {
+            cookie_jar.push(cookie, request, source);
+        }
     }
 }
 
@@ -270,10 +307,11 @@ fn set_cookies_from_headers(
     url: &ServoUrl,
     headers: &HeaderMap,
     cookie_jar: &RwLock<CookieStorage>,
+    context: SameSiteContext,
 ) {
     for cookie in headers.get_all(header::SET_COOKIE) {
         if let Ok(cookie_str) = std::str::from_utf8(cookie.as_bytes()) {
-            set_cookie_for_url(&cookie_jar, &url, &cookie_str);
+            set_cookie_for_url(&cookie_jar, &url, &cookie_str, context);
         }
     }
 }
@@ -335,22 +373,12 @@ fn send_response_to_devtools(
     let _ = devtools_chan.send(DevtoolsControlMsg::FromChrome(msg));
 }
 
-fn auth_from_cache(
-    auth_cache: &RwLock<AuthCache>,
-    origin: &ImmutableOrigin,
-) -> Option<Authorization<Basic>> {
-    if let Some(ref auth_entry) = auth_cache
-        .read()
-        .unwrap()
-        .entries
-        .get(&origin.ascii_serialization())
-    {
-        let user_name = &auth_entry.user_name;
-        let password = &auth_entry.password;
-        Some(Authorization::basic(user_name, password))
-    } else {
-        None
+/// The request-target a request for `url` is sent with, which Digest authentication covers.
+fn request_target(url: &ServoUrl, proxy_config: &ProxyConfig) -> String {
+    if proxy_config.http_proxy_for_request(url).is_some() {
+        return url.as_url()[..Position::AfterQuery].to_owned();
     }
+    url.as_url()[Position::BeforePath..Position::AfterQuery].to_owned()
 }
 
 fn obtain_response(
@@ -400,15 +428,6 @@ fn obtain_response(
         .unwrap()
         .set_attribute(ResourceAttribute::DomainLookupStart);
 
-    // TODO(#21261) connect_start: set if a persistent connection is *not* used and the last non-redirected
-    // fetch passes the timing allow check
-    let connect_start = precise_time_ms();
-    context
-        .timing
-        .lock()
-        .unwrap()
-        .set_attribute(ResourceAttribute::ConnectStart(connect_start));
-
     // https://url.spec.whatwg.org/#percent-encoded-bytes
     let request = HyperRequest::builder()
         .method(method)
@@ -422,42 +441,86 @@ fn obtain_response(
         )
         .body(request_body.clone().into());
 
-    // TODO: We currently don't know when the handhhake before the connection is done
-    // so our best bet would be to set `secure_connection_start` here when we are currently
-    // fetching on a HTTPS url.
-    if url.scheme() == "https" {
-        context
-            .timing
-            .lock()
-            .unwrap()
-            .set_attribute(ResourceAttribute::SecureConnectionStart);
-    }
-
     let mut request = match request {
         Ok(request) => request,
         Err(e) => return Box::new(future::result(Err(NetworkError::from_http_error(&e)))),
     };
     *request.headers_mut() = headers.clone();
-
-    let connect_end = precise_time_ms();
-    context
-        .timing
-        .lock()
-        .unwrap()
-        .set_attribute(ResourceAttribute::ConnectEnd(connect_end));
+    // Credentials entered by the user take precedence over those in the proxy URL,
+    // since they are only asked for once the latter have been rejected.
+    let proxy_config = &context.state.proxy_config;
+    let proxy_authorization = proxy_config
+        .http_proxy_for_request(url)
+        .and_then(|proxy| {
+            authorization_from_cache(
+                &context.state.auth_cache,
+                &proxy.auth_cache_key(),
+                method,
+                &request_target(url, proxy_config),
+            )
+        })
+        .or_else(|| proxy_config.authorization_for_request(url));
+    if let Some(authorization) = proxy_authorization {
+        request
+            .headers_mut()
+            .insert(header::PROXY_AUTHORIZATION, authorization);
+    }
 
     let request_id = request_id.map(|v| v.to_owned());
     let pipeline_id = pipeline_id.clone();
     let closure_url = url.clone();
     let method = method.clone();
-    let send_start = precise_time_ms();
+    let timing = context.timing.clone();
+    let is_secure = url.scheme() == "https";
+    let send_start = time::precise_time_ns();
 
     Box::new(
         client
             .request(request)
+            .or_else(|error| {
+                let challenges = TunnelAuthenticationRequired::find(&error)
+                    .map(|tunnel_error| tunnel_error.challenges.clone());
+                match challenges {
+                    // A proxy refusing to open a tunnel until it gets credentials is answered
+                    // like one refusing to forward a request, with nothing but its challenges.
+                    Some(challenges) => {
+                        let mut response = HyperResponse::new(Body::empty());
+                        *response.status_mut() = StatusCode::PROXY_AUTHENTICATION_REQUIRED;
+                        *response.headers_mut() = challenges;
+                        response.extensions_mut().insert(TunnelResponse);
+                        Ok(response)
+                    },
+                    None => Err(error),
+                }
+            })
             .and_then(move |res| {
                 let send_end = precise_time_ms();
 
+                // A connection that was set up before the request was sent is a persistent
+                // one, for which no time is spent connecting.
+                let connection = match res.extensions().get::<ConnectionTiming>() {
+                    Some(connection) if connection.connect_start >= send_start => *connection,
+                    _ => ConnectionTiming {
+                        connect_start: send_start,
+                        secure_connection_start: if is_secure { send_start } else { 0 },
+                        connect_end: send_start,
+                    },
+                };
+                {
+                    let mut timing = timing.lock().unwrap();
+                    // Like the other times of `ResourceFetchTiming`, these are in nanoseconds.
+                    timing.set_attribute(ResourceAttribute::ConnectStart(connection.connect_start));
+                    timing.set_attribute(ResourceAttribute::SecureConnectionStart(
+                        connection.secure_connection_start,
+                    ));
+                    timing.set_attribute(ResourceAttribute::ConnectEnd(connection.connect_end));
+                    timing.set_attribute(ResourceAttribute::NextHopProtocol(
+                        alpn_protocol_id(res.version()).to_owned(),
+                    ));
+                }
+                let connect_time = (connection.connect_end - connection.connect_start) / 1000000;
+                let send_time = send_end - connection.connect_end / 1000000;
+
                 // TODO(#21271) response_start: immediately after receiving first byte of response
 
                 let msg = if let Some(request_id) = request_id {
@@ -470,14 +533,10 @@ fn obtain_response(
                             Some(request_body.clone()),
                             pipeline_id,
                             time::now(),
-                            connect_end - connect_start,
-                            send_end - send_start,
+                            connect_time,
+                            send_time,
                             is_xhr,
                         ))
-                    // TODO: ^This is not right, connect_start is taken before contructing the
-                    // request and connect_end at the end of it. send_start is takend before the
-                    // connection too. I'm not sure it's currently possible to get the time at the
-                    // point between the connection and the start of a request.
                     } else {
                         debug!("Not notifying devtools (no pipeline_id)");
                         None
@@ -492,7 +551,65 @@ fn obtain_response(
     )
 }
 
+/// Marks the response a proxy answered a request to open a tunnel with,
+/// as opposed to one received through the tunnel.
+struct TunnelResponse;
+
+/// The ALPN protocol ID of an HTTP version,
+/// <https://www.iana.org/assignments/tls-extensiontype-values/tls-extensiontype-values.xhtml#alpn-protocol-ids>.
+fn alpn_protocol_id(version: Version) -> &'static str {
+    match version {
+        Version::HTTP_09 => "http/0.9",
+        Version::HTTP_10 => "http/1.0",
+        Version::HTTP_2 => "h2",
+        _ => "http/1.1",
+    }
+}
+
 /// [HTTP fetch](https://fetch.spec.whatwg.org#http-fetch)
+/// Let the service worker that controls the client of a request handle it.
+/// The response of the service worker is not streamed, it is only sent once
+/// its whole body was read, and is buffered into a done response body.
+/// <https://w3c.github.io/ServiceWorker/#handle-fetch>
+fn handle_fetch(request: &Request, context: &FetchContext) -> Option<Response> {
+    if !pref!(dom.serviceworker.enabled) {
+        return None;
+    }
+    let swmanager_chan = context.swmanager_chan.as_ref()?;
+    let url = request.current_url();
+    match url.scheme() {
+        "http" | "https" => {},
+        _ => return None,
+    }
+
+    let (response_chan, response_port) = ipc::channel().ok()?;
+    let mediator = CustomResponseMediator {
+        response_chan,
+        load_url: url.clone(),
+        method: request.method.clone(),
+        headers: request.headers.clone(),
+        body: request.body.clone(),
+        mode: request.mode.clone(),
+        destination: request.destination,
+        client: request.pipeline_id,
+    };
+    swmanager_chan.send(mediator).ok()?;
+    let custom_response = match response_port.recv() {
+        Ok(Ok(Some(custom_response))) => custom_response,
+        Ok(Err(error)) => return Some(Response::network_error(error)),
+        Ok(Ok(None)) | Err(_) => return None,
+    };
+
+    let mut response = Response::new(url.clone(), ResourceFetchTiming::new(request.timing_type()));
+    let (status, reason) = custom_response.raw_status;
+    response.raw_status = Some((status.as_u16(), reason.clone().into_bytes()));
+    response.status = Some((status, reason));
+    response.headers = custom_response.headers;
+    response.url_list = vec![url];
+    *response.body.lock().unwrap() = ResponseBody::Done(custom_response.body);
+    Some(response)
+}
+
 pub fn http_fetch(
     request: &mut Request,
     cache: &mut CorsCache,
@@ -513,13 +630,13 @@ pub fn http_fetch(
 
     // Step 3
     if request.service_workers_mode == ServiceWorkersMode::All {
-        // TODO: Substep 1
-        // Set response to the result of invoking handle fetch for request.
+        // Substep 1
+        response = handle_fetch(request, context);
 
         // Substep 2
         if let Some(ref res) = response {
             // Subsubstep 1
-            // TODO: transmit body for request
+            // nothing to do, since the body was sent to the service worker with the request
 
             // Subsubstep 2
             // nothing to do, since actual_response is a function on response
@@ -872,7 +989,8 @@ fn http_network_or_cache_fetch(
     let mut revalidating_flag = false;
 
     // TODO: Implement Window enum for Request
-    let request_has_no_window = true;
+    // Until then, a request made for a pipeline is assumed to have a window to prompt the user in.
+    let request_has_no_window = request.window == Window::NoWindow || request.pipeline_id.is_none();
 
     // Step 5.1
     let mut http_request;
@@ -1008,6 +1126,7 @@ fn http_network_or_cache_fetch(
             &current_url,
             &mut http_request.headers,
             &context.state.cookie_jar,
+            SameSiteContext::for_request(&http_request),
         );
         // Substep 2
         if !http_request.headers.contains_key(header::AUTHORIZATION) {
@@ -1015,31 +1134,37 @@ fn http_network_or_cache_fetch(
             let mut authorization_value = None;
 
             // Substep 4
-            if let Some(basic) = auth_from_cache(&context.state.auth_cache, &current_url.origin()) {
-                if !http_request.use_url_credentials || !has_credentials(&current_url) {
-                    authorization_value = Some(basic);
-                }
+            if !http_request.use_url_credentials || !has_credentials(&current_url) {
+                authorization_value = authorization_from_cache(
+                    &context.state.auth_cache,
+                    &current_url.origin().ascii_serialization(),
+                    &http_request.method,
+                    &request_target(&current_url, &context.state.proxy_config),
+                );
             }
 
             // Substep 5
             if authentication_fetch_flag && authorization_value.is_none() {
                 if has_credentials(&current_url) {
-                    authorization_value = Some(Authorization::basic(
+                    authorization_value = basic_authorization(
                         current_url.username(),
                         current_url.password().unwrap_or(""),
-                    ));
+                    );
                 }
             }
 
             // Substep 6
-            if let Some(basic) = authorization_value {
-                http_request.headers.typed_insert(basic);
+            if let Some(authorization_value) = authorization_value {
+                http_request
+                    .headers
+                    .insert(header::AUTHORIZATION, authorization_value);
             }
         }
     }
 
     // Step 5.18
-    // TODO If there’s a proxy-authentication entry, use it as appropriate.
+    // The proxy-authentication entry is used by `obtain_response`,
+    // which is where the proxy a request is sent through is known.
 
     // If the cache is not ready to construct a response, wait.
     //
@@ -1077,6 +1202,7 @@ fn http_network_or_cache_fetch(
         }
 
         // Step 5.19
+        HttpCache::load_from_disk(&context.state.http_cache, &http_request);
         if let Ok(http_cache) = context.state.http_cache.read() {
             if let Some(response_from_cache) =
                 http_cache.construct_response(&http_request, done_chan)
@@ -1258,7 +1384,11 @@ fn http_network_or_cache_fetch(
     // TODO: if necessary set response's range-requested flag
 
     // Step 9
-    // TODO: handle CORS not set and cross-origin blocked
+    if !cors_flag && is_blocked_by_cross_origin_resource_policy(&http_request, &response) {
+        return Response::network_error(NetworkError::Internal(
+            "Blocked by Cross-Origin-Resource-Policy".into(),
+        ));
+    }
 
     // Step 10
     // FIXME: Figure out what to do with request window objects
@@ -1266,7 +1396,8 @@ fn http_network_or_cache_fetch(
         (response.status.as_ref(), cors_flag, credentials_flag)
     {
         // Substep 1
-        // TODO: Spec says requires testing on multiple WWW-Authenticate headers
+        let challenge = preferred_challenge(&response.headers, header::WWW_AUTHENTICATE);
+        let auth_cache_key = current_url.origin().ascii_serialization();
 
         // Substep 2
         if http_request.body.is_some() {
@@ -1274,13 +1405,34 @@ fn http_network_or_cache_fetch(
         }
 
         // Substep 3
+        let mut prompted = false;
         if !http_request.use_url_credentials || authentication_fetch_flag {
-            // FIXME: Prompt the user for username and password from the window
-
-            // Wrong, but will have to do until we are able to prompt the user
-            // otherwise this creates an infinite loop
-            // We basically pretend that the user declined to enter credentials
-            return response;
+            let challenge = match challenge {
+                Some(challenge) => challenge,
+                None => return response,
+            };
+            if !renew_stale_nonce(&context.state.auth_cache, &auth_cache_key, &challenge) {
+                if request_has_no_window {
+                    return response;
+                }
+                let credentials = match prompt_user_for_credentials(
+                    &context.state.embedder_proxy,
+                    &current_url,
+                    &challenge,
+                    false,
+                ) {
+                    Some(credentials) => credentials,
+                    // The user declined to enter credentials.
+                    None => return response,
+                };
+                store_credentials(
+                    &context.state.auth_cache,
+                    auth_cache_key.clone(),
+                    credentials,
+                    challenge,
+                );
+                prompted = true;
+            }
         }
 
         // Make sure this is set to None,
@@ -1295,6 +1447,11 @@ fn http_network_or_cache_fetch(
             done_chan,
             context,
         );
+
+        // Only keep the credentials the user entered if they were accepted.
+        if let (Some((StatusCode::UNAUTHORIZED, _)), true) = (response.status.as_ref(), prompted) {
+            forget_credentials(&context.state.auth_cache, &auth_cache_key);
+        }
     }
 
     // Step 11
@@ -1306,26 +1463,84 @@ fn http_network_or_cache_fetch(
             ));
         }
 
+        // Plain HTTP requests are sent to the proxy as they are, while other requests
+        // are sent through a tunnel, which the proxy refuses to open without credentials.
+        let proxy_config = &context.state.proxy_config;
+        let (auth_cache_key, is_tunnel) = match (
+            proxy_config.http_proxy_for_request(&current_url),
+            proxy_config.tunnel_proxy_for_request(&current_url),
+        ) {
+            (Some(proxy), _) => (proxy.auth_cache_key(), false),
+            (None, Some(proxy)) => (proxy.auth_cache_key(), true),
+            (None, None) => return response,
+        };
+        // Nothing the proxy sent in place of a tunnel is shown to the user.
+        let unauthenticated = |response| {
+            if is_tunnel {
+                Response::network_error(NetworkError::Internal(
+                    "Proxy authentication required".into(),
+                ))
+            } else {
+                response
+            }
+        };
+
         // Step 2
-        // TODO: Spec says requires testing on Proxy-Authenticate headers
+        let challenge = match preferred_challenge(&response.headers, header::PROXY_AUTHENTICATE) {
+            Some(challenge) => challenge,
+            None => return unauthenticated(response),
+        };
 
         // Step 3
-        // FIXME: Prompt the user for proxy authentication credentials
+        let mut prompted = false;
+        if !renew_stale_nonce(&context.state.auth_cache, &auth_cache_key, &challenge) {
+            let credentials = match prompt_user_for_credentials(
+                &context.state.embedder_proxy,
+                &current_url,
+                &challenge,
+                true,
+            ) {
+                Some(credentials) => credentials,
+                // The user declined to enter credentials,
+                // and those the proxy rejected are of no use anymore.
+                None => {
+                    forget_credentials(&context.state.auth_cache, &auth_cache_key);
+                    return unauthenticated(response);
+                },
+            };
+            store_credentials(
+                &context.state.auth_cache,
+                auth_cache_key.clone(),
+                credentials,
+                challenge,
+            );
+            prompted = true;
+        }
 
-        // Wrong, but will have to do until we are able to prompt the user
-        // otherwise this creates an infinite loop
-        // We basically pretend that the user declined to enter credentials
-        return response;
+        // Make sure this is set to None,
+        // since we're about to start a new `http_network_or_cache_fetch`.
+        *done_chan = None;
 
         // Step 4
-        // return http_network_or_cache_fetch(request, authentication_fetch_flag,
-        //                                    cors_flag, done_chan, context);
+        response = http_network_or_cache_fetch(
+            http_request,
+            authentication_fetch_flag,
+            cors_flag,
+            done_chan,
+            context,
+        );
+
+        // Only keep the credentials the user entered if they were accepted.
+        if let (Some((StatusCode::PROXY_AUTHENTICATION_REQUIRED, _)), true) =
+            (response.status.as_ref(), prompted)
+        {
+            forget_credentials(&context.state.auth_cache, &auth_cache_key);
+        }
     }
 
     // Step 12
-    if authentication_fetch_flag {
-        // TODO Create the authentication entry for request and the given realm
-    }
+    // The authentication entries are created in the `AuthCache` as soon as the user
+    // enters credentials, and are removed again above if the credentials are rejected.
 
     // Step 13
     response
@@ -1413,6 +1628,20 @@ fn http_network_fetch(
         Err(error) => return Response::network_error(error),
     };
 
+    // Only the proxy can ask for its credentials, not the server at the other end of a tunnel.
+    if res.status() == StatusCode::PROXY_AUTHENTICATION_REQUIRED &&
+        res.extensions().get::<TunnelResponse>().is_none() &&
+        context
+            .state
+            .proxy_config
+            .tunnel_proxy_for_request(&url)
+            .is_some()
+    {
+        return Response::network_error(NetworkError::Internal(
+            "Server asked for proxy credentials".into(),
+        ));
+    }
+
     if log_enabled!(log::Level::Info) {
         info!("{:?} response for {}", res.version(), url);
         for header in res.headers().iter() {
@@ -1484,6 +1713,8 @@ fn http_network_fetch(
     if cancellation_listener.lock().unwrap().cancelled() {
         return Response::network_error(NetworkError::Internal("Fetch aborted".into()));
     }
+    // Downloads are written to disk as they arrive, rather than kept in memory.
+    let keep_body = request.initiator != Initiator::Download;
 
     *res_body.lock().unwrap() = ResponseBody::Receiving(vec![]);
     let res_body2 = res_body.clone();
@@ -1523,7 +1754,9 @@ fn http_network_fetch(
                 }
                 if let ResponseBody::Receiving(ref mut body) = *res_body.lock().unwrap() {
                     let bytes = chunk.into_bytes();
-                    body.extend_from_slice(&*bytes);
+                    if keep_body {
+                        body.extend_from_slice(&*bytes);
+                    }
                     let _ = done_sender.send(Data::Payload(bytes.to_vec()));
                 }
                 future::ok(res_body)
@@ -1592,7 +1825,12 @@ fn http_network_fetch(
     // TODO this step isn't possible yet
     // Step 15
     if credentials_flag {
-        set_cookies_from_headers(&url, &response.headers, &context.state.cookie_jar);
+        set_cookies_from_headers(
+            &url,
+            &response.headers,
+            &context.state.cookie_jar,
+            SameSiteContext::for_request(request),
+        );
     }
     context
         .state
@@ -1842,6 +2080,59 @@ fn cors_check(request: &Request, response: &Response) -> Result<(), ()> {
     Err(())
 }
 
+/// <https://fetch.spec.whatwg.org/#cross-origin-resource-policy-check>
+pub fn is_blocked_by_cross_origin_resource_policy(request: &Request, response: &Response) -> bool {
+    // Step 1
+    if request.mode != RequestMode::NoCors {
+        return false;
+    }
+    let origin = match request.origin {
+        Origin::Origin(ref origin) => origin,
+        Origin::Client => return false,
+    };
+    let response = response.actual_response();
+    let response_origin = match response.url() {
+        Some(url) => url.origin(),
+        None => return false,
+    };
+
+    // https://fetch.spec.whatwg.org/#cross-origin-resource-policy-internal-check
+    let policy = response
+        .headers
+        .get("cross-origin-resource-policy")
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim);
+    let policy = match policy {
+        Some("same-origin") | Some("same-site") | Some("cross-origin") => policy,
+        _ if request.embedder_policy == CrossOriginEmbedderPolicy::RequireCorp => {
+            Some("same-origin")
+        },
+        _ => None,
+    };
+    match policy {
+        Some("same-origin") => *origin != response_origin,
+        Some("same-site") => {
+            // An insecure origin may not embed same-site resources from a secure one.
+            let upgrade =
+                origin.scheme() != Some("https") && response_origin.scheme() == Some("https");
+            !is_schemelessly_same_site(origin, &response_origin) || upgrade
+        },
+        _ => false,
+    }
+}
+
+/// <https://html.spec.whatwg.org/multipage/#schemelessly-same-site>
+fn is_schemelessly_same_site(a: &ImmutableOrigin, b: &ImmutableOrigin) -> bool {
+    match (a, b) {
+        (
+            &ImmutableOrigin::Tuple(_, Host::Domain(ref a), _),
+            &ImmutableOrigin::Tuple(_, Host::Domain(ref b), _),
+        ) => reg_suffix(a).eq_ignore_ascii_case(reg_suffix(b)),
+        (&ImmutableOrigin::Tuple(_, ref a, _), &ImmutableOrigin::Tuple(_, ref b, _)) => a == b,
+        _ => a == b,
+    }
+}
+
 fn has_credentials(url: &ServoUrl) -> bool {
     !url.username().is_empty() || url.password().is_some()
 }
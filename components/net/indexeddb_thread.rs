@@ -0,0 +1,1531 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! The backend of IndexedDB, which owns the databases of every origin.
+//!
+//! The databases of an origin are kept in memory, and the records a transaction changed
+//! are written to a journal of their own, in a single batch, whenever it commits.
+//! Values are opaque to the backend: script serializes them, and computes their keys
+//! in each index of their object store. The size of the databases of an origin,
+//! as they are written, is accounted for once a transaction changes them.
+
+use crate::journal::{decode_binary, encode_binary, Journal, JournalBatch};
+use crate::quota_thread::QuotaAccount;
+use ipc_channel::ipc::{self, IpcReceiver, IpcSender};
+use net_traits::indexeddb_thread::{AsyncOperation, BackendError, ConnectionEvent, ConnectionId};
+use net_traits::indexeddb_thread::{CursorDirection, DatabaseMetadata, IndexedDBKeyRange};
+use net_traits::indexeddb_thread::{IndexedDBKeyType, IndexedDBRecord, IndexedDBThreadMsg};
+use net_traits::indexeddb_thread::{IndexedDBTxnMode, ObjectStoreMetadata, OperationResult};
+use net_traits::indexeddb_thread::{RecordSource, UPGRADE_TRANSACTION_ID};
+use net_traits::quota_thread::{QuotaClient, QuotaThreadMsg};
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use servo_url::ImmutableOrigin;
+use std::cmp;
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::iter;
+use std::mem;
+use std::path::PathBuf;
+use std::thread;
+
+/// The largest key a key generator generates.
+const MAX_GENERATED_KEY: u64 = 1 << 53;
+
+type Key = IndexedDBKeyType;
+
+pub trait IndexedDBThreadFactory {
+    fn new(config_dir: Option<PathBuf>, quota: Option<IpcSender<QuotaThreadMsg>>) -> Self;
+}
+
+impl IndexedDBThreadFactory for IpcSender<IndexedDBThreadMsg> {
+    /// Create an IndexedDB thread
+    fn new(
+        config_dir: Option<PathBuf>,
+        quota: Option<IpcSender<QuotaThreadMsg>>,
+    ) -> IpcSender<IndexedDBThreadMsg> {
+        let (chan, port) = ipc::channel().unwrap();
+        let quota = QuotaAccount::new(QuotaClient::IndexedDB, quota);
+        thread::Builder::new()
+            .name("IndexedDBManager".to_owned())
+            .spawn(move || {
+                IndexedDBManager::new(port, config_dir, quota).start();
+            })
+            .expect("Thread spawning failed");
+        chan
+    }
+}
+
+/// The key of the metadata of a database, of the key generator of one of its object stores,
+/// or of one of the records of an object store, in a journal.
+fn journal_key(database: &str, object_store: Option<&str>, record: Option<&Key>) -> String {
+    serde_json::to_string(&(database, object_store, record.map(encode_binary))).unwrap_or_default()
+}
+
+#[derive(Clone, Deserialize, PartialEq, Serialize)]
+struct StoredRecord {
+    value: Vec<u8>,
+    /// The keys of the record in each index, so that they can be removed along with it.
+    index_keys: BTreeMap<String, Vec<Key>>,
+}
+
+#[derive(Clone, Serialize)]
+struct ObjectStore {
+    records: BTreeMap<Key, StoredRecord>,
+    /// The primary keys of the records with each key, in each index.
+    indexes: BTreeMap<String, BTreeMap<Key, BTreeSet<Key>>>,
+    /// <https://w3c.github.io/IndexedDB/#key-generator-current-number>
+    current_number: u64,
+}
+
+impl ObjectStore {
+    fn new() -> ObjectStore {
+        ObjectStore {
+            records: BTreeMap::new(),
+            indexes: BTreeMap::new(),
+            current_number: 1,
+        }
+    }
+
+    /// The keys and primary keys of the records of `source` in `query`, in order.
+    fn entries<'a>(
+        &'a self,
+        source: &RecordSource,
+        query: &IndexedDBKeyRange,
+    ) -> Box<dyn DoubleEndedIterator<Item = (&'a Key, &'a Key)> + 'a> {
+        let bounds = match query.bounds() {
+            Some(bounds) => bounds,
+            None => return Box::new(iter::empty()),
+        };
+        match *source {
+            RecordSource::ObjectStore(_) => Box::new(
+                self.records
+                    .range::<Key, _>(bounds)
+                    .map(|(key, _)| (key, key)),
+            ),
+            RecordSource::Index(_, ref index) => match self.indexes.get(index) {
+                Some(index) => Box::new(index.range::<Key, _>(bounds).flat_map(
+                    |(key, primary_keys)| {
+                        primary_keys
+                            .iter()
+                            .map(move |primary_key| (key, primary_key))
+                    },
+                )),
+                None => Box::new(iter::empty()),
+            },
+        }
+    }
+
+    fn record(&self, key: &Key, primary_key: &Key, keys_only: bool) -> IndexedDBRecord {
+        let value = if keys_only {
+            None
+        } else {
+            self.records
+                .get(primary_key)
+                .map(|record| record.value.clone())
+        };
+        IndexedDBRecord {
+            key: key.clone(),
+            primary_key: primary_key.clone(),
+            value,
+        }
+    }
+
+    /// <https://w3c.github.io/IndexedDB/#generate-a-key>
+    fn generate_key(&mut self) -> Result<Key, BackendError> {
+        if self.current_number > MAX_GENERATED_KEY {
+            return Err(BackendError::Constraint);
+        }
+        let key = Key::Number(self.current_number as f64);
+        self.current_number += 1;
+        Ok(key)
+    }
+
+    /// <https://w3c.github.io/IndexedDB/#possibly-update-the-key-generator>
+    fn possibly_update_key_generator(&mut self, key: &Key) {
+        if let Key::Number(number) = *key {
+            let number = number.min(MAX_GENERATED_KEY as f64).floor();
+            if number >= self.current_number as f64 {
+                self.current_number = number as u64 + 1;
+            }
+        }
+    }
+
+    /// <https://w3c.github.io/IndexedDB/#store-a-record-into-an-object-store>
+    fn put(
+        &mut self,
+        metadata: &ObjectStoreMetadata,
+        key: Option<Key>,
+        value: Vec<u8>,
+        index_keys: BTreeMap<String, Vec<Key>>,
+        no_overwrite: bool,
+    ) -> Result<Key, BackendError> {
+        let key = match key {
+            Some(key) => {
+                if metadata.auto_increment {
+                    self.possibly_update_key_generator(&key);
+                }
+                key
+            },
+            None => self.generate_key()?,
+        };
+        if no_overwrite && self.records.contains_key(&key) {
+            return Err(BackendError::Constraint);
+        }
+        for (name, keys) in &index_keys {
+            let unique = metadata
+                .indexes
+                .get(name)
+                .map_or(false, |index| index.unique);
+            let index = match self.indexes.get(name) {
+                Some(index) if unique => index,
+                _ => continue,
+            };
+            let taken = keys.iter().any(|index_key| {
+                index.get(index_key).map_or(false, |primary_keys| {
+                    primary_keys.iter().any(|primary_key| *primary_key != key)
+                })
+            });
+            if taken {
+                return Err(BackendError::Constraint);
+            }
+        }
+
+        self.remove(&key);
+        self.insert(key.clone(), StoredRecord { value, index_keys });
+        Ok(key)
+    }
+
+    /// Remove the record with `key`, and its entries in every index.
+    fn remove(&mut self, key: &Key) {
+        let record = match self.records.remove(key) {
+            Some(record) => record,
+            None => return,
+        };
+        for (name, keys) in record.index_keys {
+            let index = match self.indexes.get_mut(&name) {
+                Some(index) => index,
+                None => continue,
+            };
+            for index_key in keys {
+                let now_empty = index.get_mut(&index_key).map_or(false, |primary_keys| {
+                    primary_keys.remove(key);
+                    primary_keys.is_empty()
+                });
+                if now_empty {
+                    index.remove(&index_key);
+                }
+            }
+        }
+    }
+
+    /// Add a record with a new key, and its entries in every index.
+    fn insert(&mut self, key: Key, record: StoredRecord) {
+        for (name, keys) in &record.index_keys {
+            if let Some(index) = self.indexes.get_mut(name) {
+                for index_key in keys {
+                    index
+                        .entry(index_key.clone())
+                        .or_insert_with(BTreeSet::new)
+                        .insert(key.clone());
+                }
+            }
+        }
+        self.records.insert(key, record);
+    }
+
+    /// The keys of the records in `query`.
+    fn keys(&self, query: &IndexedDBKeyRange) -> Vec<Key> {
+        match query.bounds() {
+            Some(bounds) => self
+                .records
+                .range::<Key, _>(bounds)
+                .map(|(key, _)| key.clone())
+                .collect(),
+            None => vec![],
+        }
+    }
+
+    fn delete(&mut self, query: &IndexedDBKeyRange) {
+        for key in self.keys(query) {
+            self.remove(&key);
+        }
+    }
+
+    fn clear(&mut self) {
+        self.records.clear();
+        for index in self.indexes.values_mut() {
+            index.clear();
+        }
+    }
+
+    /// Add the keys that script computed for the records to a new index.
+    fn populate_index(
+        &mut self,
+        name: &str,
+        unique: bool,
+        keys: Vec<(Key, Vec<Key>)>,
+    ) -> Result<(), BackendError> {
+        let index = match self.indexes.get_mut(name) {
+            Some(index) => index,
+            None => return Ok(()),
+        };
+        for (primary_key, index_keys) in keys {
+            let record = match self.records.get_mut(&primary_key) {
+                Some(record) => record,
+                None => continue,
+            };
+            for index_key in &index_keys {
+                let primary_keys = index.entry(index_key.clone()).or_insert_with(BTreeSet::new);
+                if unique && primary_keys.iter().any(|key| *key != primary_key) {
+                    return Err(BackendError::Constraint);
+                }
+                primary_keys.insert(primary_key.clone());
+            }
+            record.index_keys.insert(name.to_owned(), index_keys);
+        }
+        Ok(())
+    }
+
+    fn delete_index(&mut self, name: &str) {
+        self.indexes.remove(name);
+        for record in self.records.values_mut() {
+            record.index_keys.remove(name);
+        }
+    }
+
+    fn rename_index(&mut self, name: &str, new_name: &str) {
+        if let Some(index) = self.indexes.remove(name) {
+            self.indexes.insert(new_name.to_owned(), index);
+        }
+        for record in self.records.values_mut() {
+            if let Some(keys) = record.index_keys.remove(name) {
+                record.index_keys.insert(new_name.to_owned(), keys);
+            }
+        }
+    }
+
+    /// The record a cursor moves to in one step.
+    ///
+    /// <https://w3c.github.io/IndexedDB/#iterate-a-cursor>
+    fn next_entry<'a>(
+        &'a self,
+        source: &RecordSource,
+        query: &IndexedDBKeyRange,
+        direction: CursorDirection,
+        key: Option<&Key>,
+        primary_key: Option<&Key>,
+        position: Option<&(Key, Key)>,
+    ) -> Option<(&'a Key, &'a Key)> {
+        // Only index cursors move between records that share a key.
+        let by_primary_key = match *source {
+            RecordSource::Index(..) => true,
+            RecordSource::ObjectStore(_) => false,
+        };
+        let mut entries = self.entries(source, query);
+        match direction {
+            CursorDirection::Next => entries.find(|&(entry_key, entry_primary_key)| {
+                let after_key = key.map_or(true, |key| match primary_key {
+                    Some(primary_key) => {
+                        entry_key > key || (entry_key == key && entry_primary_key >= primary_key)
+                    },
+                    None => entry_key >= key,
+                });
+                let after_position = position.map_or(true, |&(ref position, ref primary)| {
+                    entry_key > position ||
+                        (by_primary_key && entry_key == position && entry_primary_key > primary)
+                });
+                after_key && after_position
+            }),
+            CursorDirection::NextUnique => entries.find(|&(entry_key, _)| {
+                key.map_or(true, |key| entry_key >= key) &&
+                    position.map_or(true, |&(ref position, _)| entry_key > position)
+            }),
+            CursorDirection::Prev => entries.rev().find(|&(entry_key, entry_primary_key)| {
+                let before_key = key.map_or(true, |key| match primary_key {
+                    Some(primary_key) => {
+                        entry_key < key || (entry_key == key && entry_primary_key <= primary_key)
+                    },
+                    None => entry_key <= key,
+                });
+                let before_position = position.map_or(true, |&(ref position, ref primary)| {
+                    entry_key < position ||
+                        (by_primary_key && entry_key == position && entry_primary_key < primary)
+                });
+                before_key && before_position
+            }),
+            CursorDirection::PrevUnique => {
+                let (found_key, _) = entries.rev().find(|&(entry_key, _)| {
+                    key.map_or(true, |key| entry_key <= key) &&
+                        position.map_or(true, |&(ref position, _)| entry_key < position)
+                })?;
+                // The cursor lands on the first of the records with that key.
+                self.entries(source, &IndexedDBKeyRange::only(found_key.clone()))
+                    .next()
+            },
+        }
+    }
+}
+
+/// The data of a database: what its journal holds.
+#[derive(Clone, Default)]
+struct StoredDatabase {
+    metadata: DatabaseMetadata,
+    object_stores: BTreeMap<String, ObjectStore>,
+}
+
+impl StoredDatabase {
+    /// The number of bytes the database takes once written.
+    fn size(&self) -> u64 {
+        bincode::serialized_size(&self.metadata).unwrap_or(0) +
+            self.object_stores
+                .values()
+                .map(|object_store| bincode::serialized_size(object_store).unwrap_or(0))
+                .sum::<u64>()
+    }
+
+    fn object_store(&self, name: &str) -> Result<&ObjectStore, BackendError> {
+        self.object_stores.get(name).ok_or_else(|| {
+            warn!("Operation on missing object store {}", name);
+            BackendError::Abort
+        })
+    }
+
+    fn execute(&mut self, operation: AsyncOperation) -> Result<OperationResult, BackendError> {
+        match operation {
+            AsyncOperation::Put {
+                store,
+                key,
+                value,
+                index_keys,
+                no_overwrite,
+            } => {
+                let metadata = self.metadata.object_stores.get(&store);
+                match (metadata, self.object_stores.get_mut(&store)) {
+                    (Some(metadata), Some(object_store)) => object_store
+                        .put(metadata, key, value, index_keys, no_overwrite)
+                        .map(OperationResult::Key),
+                    _ => Err(BackendError::Abort),
+                }
+            },
+            AsyncOperation::Get {
+                source,
+                query,
+                keys_only,
+            } => {
+                let object_store = self.object_store(source.object_store())?;
+                let records = object_store
+                    .entries(&source, &query)
+                    .next()
+                    .map(|(key, primary_key)| object_store.record(key, primary_key, keys_only));
+                Ok(OperationResult::Records(records.into_iter().collect()))
+            },
+            AsyncOperation::GetAll {
+                source,
+                query,
+                count,
+                keys_only,
+            } => {
+                let object_store = self.object_store(source.object_store())?;
+                let records = object_store
+                    .entries(&source, &query)
+                    .take(count.map_or(usize::max_value(), |count| count as usize))
+                    .map(|(key, primary_key)| object_store.record(key, primary_key, keys_only))
+                    .collect();
+                Ok(OperationResult::Records(records))
+            },
+            AsyncOperation::Count { source, query } => {
+                let object_store = self.object_store(source.object_store())?;
+                let count = object_store.entries(&source, &query).count();
+                Ok(OperationResult::Count(count as u64))
+            },
+            AsyncOperation::Delete { store, query } => {
+                self.object_store(&store)?;
+                if let Some(object_store) = self.object_stores.get_mut(&store) {
+                    object_store.delete(&query);
+                }
+                Ok(OperationResult::Done)
+            },
+            AsyncOperation::Clear { store } => {
+                self.object_store(&store)?;
+                if let Some(object_store) = self.object_stores.get_mut(&store) {
+                    object_store.clear();
+                }
+                Ok(OperationResult::Done)
+            },
+            AsyncOperation::Iterate {
+                source,
+                query,
+                direction,
+                key,
+                primary_key,
+                position,
+                count,
+                keys_only,
+            } => {
+                let object_store = self.object_store(source.object_store())?;
+                let mut position = position;
+                for _ in 0..count {
+                    let next = object_store.next_entry(
+                        &source,
+                        &query,
+                        direction,
+                        key.as_ref(),
+                        primary_key.as_ref(),
+                        position.as_ref(),
+                    );
+                    position = match next {
+                        Some((key, primary_key)) => Some((key.clone(), primary_key.clone())),
+                        None => None,
+                    };
+                    if position.is_none() {
+                        break;
+                    }
+                }
+                let record = position
+                    .map(|(key, primary_key)| object_store.record(&key, &primary_key, keys_only));
+                Ok(OperationResult::Records(record.into_iter().collect()))
+            },
+            AsyncOperation::CreateObjectStore {
+                name,
+                key_path,
+                auto_increment,
+            } => {
+                let metadata = ObjectStoreMetadata {
+                    name: name.clone(),
+                    key_path,
+                    auto_increment,
+                    indexes: BTreeMap::new(),
+                };
+                self.metadata.object_stores.insert(name.clone(), metadata);
+                self.object_stores.insert(name, ObjectStore::new());
+                Ok(OperationResult::Done)
+            },
+            AsyncOperation::DeleteObjectStore { name } => {
+                self.metadata.object_stores.remove(&name);
+                self.object_stores.remove(&name);
+                Ok(OperationResult::Done)
+            },
+            AsyncOperation::RenameObjectStore { name, new_name } => {
+                if let Some(mut metadata) = self.metadata.object_stores.remove(&name) {
+                    metadata.name = new_name.clone();
+                    self.metadata
+                        .object_stores
+                        .insert(new_name.clone(), metadata);
+                }
+                if let Some(object_store) = self.object_stores.remove(&name) {
+                    self.object_stores.insert(new_name, object_store);
+                }
+                Ok(OperationResult::Done)
+            },
+            AsyncOperation::CreateIndex { store, index } => {
+                let metadata = self.metadata.object_stores.get_mut(&store);
+                match (metadata, self.object_stores.get_mut(&store)) {
+                    (Some(metadata), Some(object_store)) => {
+                        object_store
+                            .indexes
+                            .insert(index.name.clone(), BTreeMap::new());
+                        metadata.indexes.insert(index.name.clone(), index);
+                        let records = object_store
+                            .records
+                            .iter()
+                            .map(|(key, record)| IndexedDBRecord {
+                                key: key.clone(),
+                                primary_key: key.clone(),
+                                value: Some(record.value.clone()),
+                            })
+                            .collect();
+                        Ok(OperationResult::Records(records))
+                    },
+                    _ => Err(BackendError::Abort),
+                }
+            },
+            AsyncOperation::DeleteIndex { store, name } => {
+                if let Some(metadata) = self.metadata.object_stores.get_mut(&store) {
+                    metadata.indexes.remove(&name);
+                }
+                if let Some(object_store) = self.object_stores.get_mut(&store) {
+                    object_store.delete_index(&name);
+                }
+                Ok(OperationResult::Done)
+            },
+            AsyncOperation::RenameIndex {
+                store,
+                name,
+                new_name,
+            } => {
+                if let Some(metadata) = self.metadata.object_stores.get_mut(&store) {
+                    if let Some(mut index) = metadata.indexes.remove(&name) {
+                        index.name = new_name.clone();
+                        metadata.indexes.insert(new_name.clone(), index);
+                    }
+                }
+                if let Some(object_store) = self.object_stores.get_mut(&store) {
+                    object_store.rename_index(&name, &new_name);
+                }
+                Ok(OperationResult::Done)
+            },
+        }
+    }
+}
+
+/// What a transaction changes, kept to undo its changes if it aborts.
+enum Snapshot {
+    ObjectStores(Vec<(String, Option<ObjectStore>)>),
+    Database(StoredDatabase),
+}
+
+struct Transaction {
+    connection: ConnectionId,
+    id: u64,
+    scope: Vec<String>,
+    mode: IndexedDBTxnMode,
+    started: bool,
+    /// The operations waiting for the transaction to start, or for an index to be populated.
+    queue: VecDeque<(u64, AsyncOperation)>,
+    commit_requested: bool,
+    /// Whether the operations wait for script to send the keys of a new index.
+    awaiting_index_population: bool,
+    /// The keys of the records that operations changed, in each object store.
+    modified: BTreeMap<String, BTreeSet<Key>>,
+    snapshot: Option<Snapshot>,
+}
+
+impl Transaction {
+    fn new(
+        connection: ConnectionId,
+        id: u64,
+        scope: Vec<String>,
+        mode: IndexedDBTxnMode,
+    ) -> Transaction {
+        Transaction {
+            connection,
+            id,
+            scope,
+            mode,
+            started: false,
+            queue: VecDeque::new(),
+            commit_requested: false,
+            awaiting_index_population: false,
+            modified: BTreeMap::new(),
+            snapshot: None,
+        }
+    }
+
+    /// The upgrade transaction of `connection`, which starts right away.
+    fn upgrade(connection: ConnectionId, data: &StoredDatabase) -> Transaction {
+        let mut transaction = Transaction::new(
+            connection,
+            UPGRADE_TRANSACTION_ID,
+            vec![],
+            IndexedDBTxnMode::Versionchange,
+        );
+        transaction.start(data);
+        transaction
+    }
+
+    /// <https://w3c.github.io/IndexedDB/#transaction-scheduling>
+    fn blocks(&self, later: &Transaction) -> bool {
+        if self.mode == IndexedDBTxnMode::Readonly && later.mode == IndexedDBTxnMode::Readonly {
+            return false;
+        }
+        self.mode == IndexedDBTxnMode::Versionchange ||
+            later.mode == IndexedDBTxnMode::Versionchange ||
+            self.scope.iter().any(|store| later.scope.contains(store))
+    }
+
+    fn start(&mut self, data: &StoredDatabase) {
+        self.started = true;
+        self.snapshot = match self.mode {
+            IndexedDBTxnMode::Readonly => None,
+            IndexedDBTxnMode::Readwrite => Some(Snapshot::ObjectStores(
+                self.scope
+                    .iter()
+                    .map(|store| (store.clone(), data.object_stores.get(store).cloned()))
+                    .collect(),
+            )),
+            IndexedDBTxnMode::Versionchange => Some(Snapshot::Database(data.clone())),
+        };
+    }
+
+    fn can_commit(&self) -> bool {
+        self.started &&
+            self.commit_requested &&
+            !self.awaiting_index_population &&
+            self.queue.is_empty()
+    }
+
+    fn roll_back(&mut self, data: &mut StoredDatabase) {
+        match self.snapshot.take() {
+            Some(Snapshot::ObjectStores(object_stores)) => {
+                for (name, object_store) in object_stores {
+                    match object_store {
+                        Some(object_store) => data.object_stores.insert(name, object_store),
+                        None => data.object_stores.remove(&name),
+                    };
+                }
+            },
+            Some(Snapshot::Database(snapshot)) => *data = snapshot,
+            None => {},
+        }
+    }
+
+    /// Write the records the transaction changed to the journal of its database, at once.
+    fn persist(&self, data: &StoredDatabase, journal: &Journal) {
+        let name = &data.metadata.name;
+        let mut batch = JournalBatch::new();
+        match self.mode {
+            IndexedDBTxnMode::Readonly => {},
+            IndexedDBTxnMode::Readwrite => {
+                for (store, keys) in &self.modified {
+                    let object_store = match data.object_stores.get(store) {
+                        Some(object_store) => object_store,
+                        None => continue,
+                    };
+                    batch.put(
+                        &journal_key(name, Some(store), None),
+                        &encode_binary(&object_store.current_number),
+                    );
+                    for key in keys {
+                        let record_key = journal_key(name, Some(store), Some(key));
+                        match object_store.records.get(key) {
+                            Some(record) => batch.put(&record_key, &encode_binary(record)),
+                            None => batch.remove(&record_key),
+                        }
+                    }
+                }
+            },
+            IndexedDBTxnMode::Versionchange => {
+                // Object stores and indexes may have changed wholesale,
+                // so compare every record with what it was before.
+                let empty = StoredDatabase::default();
+                let snapshot = match self.snapshot {
+                    Some(Snapshot::Database(ref snapshot)) => snapshot,
+                    _ => &empty,
+                };
+                for (store, object_store) in &snapshot.object_stores {
+                    if !data.object_stores.contains_key(store) {
+                        remove_object_store(&mut batch, name, store, object_store);
+                    }
+                }
+                batch.put(
+                    &journal_key(name, None, None),
+                    &encode_binary(&data.metadata),
+                );
+                for (store, object_store) in &data.object_stores {
+                    let old_records = snapshot
+                        .object_stores
+                        .get(store)
+                        .map(|old_object_store| &old_object_store.records);
+                    batch.put(
+                        &journal_key(name, Some(store), None),
+                        &encode_binary(&object_store.current_number),
+                    );
+                    for (key, record) in &object_store.records {
+                        if old_records.and_then(|records| records.get(key)) != Some(record) {
+                            batch.put(
+                                &journal_key(name, Some(store), Some(key)),
+                                &encode_binary(record),
+                            );
+                        }
+                    }
+                    for key in old_records.into_iter().flat_map(|records| records.keys()) {
+                        if !object_store.records.contains_key(key) {
+                            batch.remove(&journal_key(name, Some(store), Some(key)));
+                        }
+                    }
+                }
+            },
+        }
+        journal.write(batch);
+    }
+}
+
+/// Remove an object store, and every one of its records, from the journal of database `name`.
+fn remove_object_store(
+    batch: &mut JournalBatch,
+    name: &str,
+    store: &str,
+    object_store: &ObjectStore,
+) {
+    batch.remove(&journal_key(name, Some(store), None));
+    for key in object_store.records.keys() {
+        batch.remove(&journal_key(name, Some(store), Some(key)));
+    }
+}
+
+struct Connection {
+    events: IpcSender<ConnectionEvent>,
+    close_pending: bool,
+    /// Whether script has yet to handle a versionchange event sent to the connection.
+    version_change_pending: bool,
+}
+
+impl Connection {
+    fn new(events: IpcSender<ConnectionEvent>) -> Connection {
+        Connection {
+            events,
+            close_pending: false,
+            version_change_pending: false,
+        }
+    }
+}
+
+/// An open or delete request.
+struct ConnectionRequest {
+    /// The version to open the database with, or `None` to delete the database.
+    version: Option<Option<u64>>,
+    events: IpcSender<ConnectionEvent>,
+}
+
+/// The open or delete request waiting for the other connections to close,
+/// or for its upgrade transaction to finish.
+struct PendingRequest {
+    old_version: u64,
+    /// The version to upgrade to, or `None` to delete the database.
+    new_version: Option<u64>,
+    events: IpcSender<ConnectionEvent>,
+    blocked: bool,
+    /// The connection whose upgrade transaction is running.
+    upgrading: Option<ConnectionId>,
+}
+
+struct Database {
+    data: StoredDatabase,
+    connections: BTreeMap<ConnectionId, Connection>,
+    /// The unfinished transactions, in the order they were created.
+    transactions: Vec<Transaction>,
+    /// <https://w3c.github.io/IndexedDB/#connection-queue>
+    requests: VecDeque<ConnectionRequest>,
+    pending_request: Option<PendingRequest>,
+    /// The size of the data of the database, when it was last accounted for.
+    size: u64,
+    /// Whether a transaction changed the data since its size was accounted for.
+    modified: bool,
+}
+
+impl Database {
+    fn new(name: &str) -> Database {
+        let mut data = StoredDatabase::default();
+        data.metadata.name = name.to_owned();
+        Database {
+            data,
+            connections: BTreeMap::new(),
+            transactions: vec![],
+            requests: VecDeque::new(),
+            pending_request: None,
+            size: 0,
+            modified: false,
+        }
+    }
+
+    /// Whether the database is only referred to by its journal, if at all.
+    fn is_idle(&self) -> bool {
+        self.connections.is_empty() && self.requests.is_empty() && self.pending_request.is_none()
+    }
+
+    fn transaction_mut(&mut self, connection: ConnectionId, id: u64) -> Option<&mut Transaction> {
+        self.transactions
+            .iter_mut()
+            .find(|transaction| transaction.connection == connection && transaction.id == id)
+    }
+
+    fn send(&self, connection: ConnectionId, event: ConnectionEvent) {
+        if let Some(connection) = self.connections.get(&connection) {
+            let _ = connection.events.send(event);
+        }
+    }
+
+    /// Start the transactions that nothing blocks anymore, run their operations,
+    /// and commit those that are done.
+    fn run_transactions(&mut self, journal: Option<&Journal>) {
+        loop {
+            for index in 0..self.transactions.len() {
+                let (earlier, later) = self.transactions.split_at_mut(index);
+                let transaction = &mut later[0];
+                if !transaction.started &&
+                    !earlier.iter().any(|earlier| earlier.blocks(transaction))
+                {
+                    transaction.start(&self.data);
+                }
+            }
+
+            for index in 0..self.transactions.len() {
+                self.run_operations(index);
+            }
+
+            match self
+                .transactions
+                .iter()
+                .position(|transaction| transaction.can_commit())
+            {
+                Some(index) => self.finish_transaction(index, Ok(()), journal),
+                None => break,
+            }
+        }
+    }
+
+    fn run_operations(&mut self, index: usize) {
+        let Database {
+            ref mut data,
+            ref mut transactions,
+            ref connections,
+            ..
+        } = *self;
+        let transaction = &mut transactions[index];
+        if !transaction.started {
+            return;
+        }
+        while !transaction.awaiting_index_population {
+            let (request, operation) = match transaction.queue.pop_front() {
+                Some(queued) => queued,
+                None => break,
+            };
+            let creates_index = match operation {
+                AsyncOperation::CreateIndex { .. } => true,
+                _ => false,
+            };
+            let modified = match operation {
+                AsyncOperation::Put { ref store, .. } => Some((store.clone(), vec![])),
+                AsyncOperation::Delete {
+                    ref store,
+                    ref query,
+                } => data
+                    .object_stores
+                    .get(store)
+                    .map(|object_store| (store.clone(), object_store.keys(query))),
+                AsyncOperation::Clear { ref store } => {
+                    data.object_stores.get(store).map(|object_store| {
+                        (
+                            store.clone(),
+                            object_store.records.keys().cloned().collect(),
+                        )
+                    })
+                },
+                _ => None,
+            };
+            let result = data.execute(operation);
+            if let (Ok(operation_result), Some((store, mut keys))) = (&result, modified) {
+                if let OperationResult::Key(ref key) = *operation_result {
+                    keys.push(key.clone());
+                }
+                transaction
+                    .modified
+                    .entry(store)
+                    .or_insert_with(BTreeSet::new)
+                    .extend(keys);
+            }
+            transaction.awaiting_index_population = creates_index && result.is_ok();
+            if let Some(connection) = connections.get(&transaction.connection) {
+                let _ = connection.events.send(ConnectionEvent::RequestResult {
+                    transaction: transaction.id,
+                    request,
+                    result,
+                });
+            }
+        }
+    }
+
+    fn populate_index(
+        &mut self,
+        connection: ConnectionId,
+        transaction: u64,
+        store: &str,
+        index: &str,
+        keys: Vec<(Key, Vec<Key>)>,
+        journal: Option<&Journal>,
+    ) {
+        let position = self.transactions.iter().position(|candidate| {
+            candidate.connection == connection &&
+                candidate.id == transaction &&
+                candidate.awaiting_index_population
+        });
+        let position = match position {
+            Some(position) => position,
+            None => return,
+        };
+        self.transactions[position].awaiting_index_population = false;
+        let unique = self
+            .data
+            .metadata
+            .object_stores
+            .get(store)
+            .and_then(|metadata| metadata.indexes.get(index))
+            .map_or(false, |index| index.unique);
+        let result = match self.data.object_stores.get_mut(store) {
+            Some(object_store) => object_store.populate_index(index, unique, keys),
+            None => Ok(()),
+        };
+        if let Err(error) = result {
+            self.finish_transaction(position, Err(error), journal);
+        }
+    }
+
+    fn abort(&mut self, connection: ConnectionId, transaction: u64, journal: Option<&Journal>) {
+        let position = self.transactions.iter().position(|candidate| {
+            candidate.connection == connection && candidate.id == transaction
+        });
+        if let Some(position) = position {
+            self.finish_transaction(position, Err(BackendError::Abort), journal);
+        }
+    }
+
+    /// Commit or abort a transaction, and if it was an upgrade transaction,
+    /// finish the open request that it was part of.
+    fn finish_transaction(
+        &mut self,
+        index: usize,
+        result: Result<(), BackendError>,
+        journal: Option<&Journal>,
+    ) {
+        let mut transaction = self.transactions.remove(index);
+        if result.is_ok() && transaction.mode != IndexedDBTxnMode::Readonly {
+            self.modified = true;
+        }
+        match (&result, journal) {
+            (Ok(()), Some(journal)) => transaction.persist(&self.data, journal),
+            (Ok(()), None) => {},
+            (Err(_), _) => transaction.roll_back(&mut self.data),
+        }
+        self.send(
+            transaction.connection,
+            ConnectionEvent::TransactionFinished {
+                transaction: transaction.id,
+                result: result.clone(),
+            },
+        );
+
+        if transaction.mode != IndexedDBTxnMode::Versionchange {
+            return;
+        }
+        let request = match self.pending_request.take() {
+            Some(request) => request,
+            None => return,
+        };
+        let connection = transaction.connection;
+        let closed = self
+            .connections
+            .get(&connection)
+            .map_or(true, |connection| connection.close_pending);
+        let event = if result.is_ok() && !closed {
+            ConnectionEvent::Opened {
+                connection,
+                metadata: self.data.metadata.clone(),
+            }
+        } else {
+            if let Some(connection) = self.connections.get_mut(&connection) {
+                connection.close_pending = true;
+            }
+            ConnectionEvent::OpenFailed(BackendError::Abort)
+        };
+        let _ = request.events.send(event);
+    }
+
+    /// Forget the connections that were closed once their transactions have finished.
+    fn remove_closed_connections(&mut self) -> Vec<ConnectionId> {
+        let closed: Vec<ConnectionId> = self
+            .connections
+            .iter()
+            .filter(|&(id, connection)| {
+                connection.close_pending &&
+                    !self
+                        .transactions
+                        .iter()
+                        .any(|transaction| transaction.connection == *id)
+            })
+            .map(|(id, _)| *id)
+            .collect();
+        for id in &closed {
+            self.connections.remove(id);
+        }
+        closed
+    }
+}
+
+/// The databases of an origin.
+struct OriginStorage {
+    origin: ImmutableOrigin,
+    databases: HashMap<String, Database>,
+    /// Where the databases are written whenever a transaction commits.
+    journal: Option<Journal>,
+}
+
+impl OriginStorage {
+    fn open(directory: Option<&PathBuf>, origin: &ImmutableOrigin) -> OriginStorage {
+        let mut databases: HashMap<String, Database> = HashMap::new();
+        let journal = directory.map(|directory| {
+            let key = origin.ascii_serialization();
+            let file_name = utf8_percent_encode(&key, NON_ALPHANUMERIC).to_string();
+            let (journal, entries) = Journal::open::<String>(directory, &file_name);
+            // The object stores of a database, and their records,
+            // are only kept along with its metadata.
+            let mut key_generators = vec![];
+            let mut records = vec![];
+            for (key, value) in entries.unwrap_or_default() {
+                match serde_json::from_str::<(String, Option<String>, Option<String>)>(&key) {
+                    Ok((name, None, _)) => match decode_binary::<DatabaseMetadata>(&value) {
+                        Some(metadata) => {
+                            let database = databases
+                                .entry(name.clone())
+                                .or_insert_with(|| Database::new(&name));
+                            for (store, store_metadata) in &metadata.object_stores {
+                                let mut object_store = ObjectStore::new();
+                                for index in store_metadata.indexes.keys() {
+                                    object_store.indexes.insert(index.clone(), BTreeMap::new());
+                                }
+                                database
+                                    .data
+                                    .object_stores
+                                    .insert(store.clone(), object_store);
+                            }
+                            database.data.metadata = metadata;
+                        },
+                        None => warn!("couldn't decode the metadata of database {}", name),
+                    },
+                    Ok((name, Some(store), None)) => key_generators.push((name, store, value)),
+                    Ok((name, Some(store), Some(key))) => records.push((name, store, key, value)),
+                    Err(why) => warn!("couldn't decode IndexedDB journal key {}: {}", key, why),
+                }
+            }
+            for (name, store, value) in key_generators {
+                let object_store = databases
+                    .get_mut(&name)
+                    .and_then(|database| database.data.object_stores.get_mut(&store));
+                match (object_store, decode_binary::<u64>(&value)) {
+                    (Some(object_store), Some(current_number)) => {
+                        object_store.current_number = current_number;
+                    },
+                    _ => warn!(
+                        "couldn't restore object store {} of database {}",
+                        store, name
+                    ),
+                }
+            }
+            for (name, store, key, value) in records {
+                let object_store = databases
+                    .get_mut(&name)
+                    .and_then(|database| database.data.object_stores.get_mut(&store));
+                match (
+                    object_store,
+                    decode_binary::<Key>(&key),
+                    decode_binary::<StoredRecord>(&value),
+                ) {
+                    (Some(object_store), Some(key), Some(record)) => {
+                        object_store.insert(key, record);
+                    },
+                    _ => warn!(
+                        "couldn't restore a record of object store {} of database {}",
+                        store, name
+                    ),
+                }
+            }
+            journal
+        });
+        for database in databases.values_mut() {
+            database.size = database.data.size();
+        }
+        OriginStorage {
+            origin: origin.clone(),
+            databases,
+            journal,
+        }
+    }
+
+    /// The number of bytes the databases of the origin take.
+    fn usage(&self) -> u64 {
+        self.databases.values().map(|database| database.size).sum()
+    }
+}
+
+struct IndexedDBManager {
+    port: IpcReceiver<IndexedDBThreadMsg>,
+    /// Where the journal of each origin is, if the databases are written to disk.
+    directory: Option<PathBuf>,
+    origins: HashMap<String, OriginStorage>,
+    /// The origin and name of the database of each connection.
+    connections: HashMap<ConnectionId, (String, String)>,
+    next_connection_id: ConnectionId,
+    /// Where the usage of the databases of each origin is accounted for.
+    quota: QuotaAccount,
+}
+
+impl IndexedDBManager {
+    fn new(
+        port: IpcReceiver<IndexedDBThreadMsg>,
+        config_dir: Option<PathBuf>,
+        quota: QuotaAccount,
+    ) -> IndexedDBManager {
+        IndexedDBManager {
+            port,
+            directory: config_dir.map(|config_dir| config_dir.join("indexeddb")),
+            origins: HashMap::new(),
+            connections: HashMap::new(),
+            next_connection_id: 0,
+            quota,
+        }
+    }
+}
+
+impl IndexedDBManager {
+    fn start(&mut self) {
+        loop {
+            match self.port.recv().unwrap() {
+                IndexedDBThreadMsg::Open {
+                    origin,
+                    name,
+                    version,
+                    events,
+                } => {
+                    let origin = self.load_origin(&origin);
+                    self.queue_request(&origin, &name, Some(version), events);
+                },
+                IndexedDBThreadMsg::DeleteDatabase {
+                    origin,
+                    name,
+                    events,
+                } => {
+                    let origin = self.load_origin(&origin);
+                    self.queue_request(&origin, &name, None, events);
+                },
+                IndexedDBThreadMsg::Databases(sender, origin) => {
+                    let origin = self.load_origin(&origin);
+                    let databases = self.origins[&origin]
+                        .databases
+                        .iter()
+                        .filter(|&(_, database)| database.data.metadata.version > 0)
+                        .map(|(name, database)| (name.clone(), database.data.metadata.version))
+                        .collect();
+                    let _ = sender.send(databases);
+                },
+                IndexedDBThreadMsg::CreateTransaction {
+                    connection,
+                    transaction,
+                    scope,
+                    mode,
+                } => {
+                    if let Some((database, _)) = self.connection_database(connection) {
+                        database.transactions.push(Transaction::new(
+                            connection,
+                            transaction,
+                            scope,
+                            mode,
+                        ));
+                    }
+                    self.update_connection(connection);
+                },
+                IndexedDBThreadMsg::Request {
+                    connection,
+                    transaction,
+                    request,
+                    operation,
+                } => {
+                    if let Some((database, _)) = self.connection_database(connection) {
+                        if let Some(transaction) = database.transaction_mut(connection, transaction)
+                        {
+                            transaction.queue.push_back((request, operation));
+                        }
+                    }
+                    self.update_connection(connection);
+                },
+                IndexedDBThreadMsg::PopulateIndex {
+                    connection,
+                    transaction,
+                    store,
+                    index,
+                    keys,
+                } => {
+                    if let Some((database, journal)) = self.connection_database(connection) {
+                        database.populate_index(
+                            connection,
+                            transaction,
+                            &store,
+                            &index,
+                            keys,
+                            journal,
+                        );
+                    }
+                    self.update_connection(connection);
+                },
+                IndexedDBThreadMsg::Commit {
+                    connection,
+                    transaction,
+                } => {
+                    if let Some((database, _)) = self.connection_database(connection) {
+                        if let Some(transaction) = database.transaction_mut(connection, transaction)
+                        {
+                            transaction.commit_requested = true;
+                        }
+                    }
+                    self.update_connection(connection);
+                },
+                IndexedDBThreadMsg::Abort {
+                    connection,
+                    transaction,
+                } => {
+                    if let Some((database, journal)) = self.connection_database(connection) {
+                        database.abort(connection, transaction, journal);
+                    }
+                    self.update_connection(connection);
+                },
+                IndexedDBThreadMsg::Close(connection) => {
+                    if let Some((database, _)) = self.connection_database(connection) {
+                        if let Some(connection) = database.connections.get_mut(&connection) {
+                            connection.close_pending = true;
+                        }
+                    }
+                    self.update_connection(connection);
+                },
+                IndexedDBThreadMsg::VersionChangeHandled(connection) => {
+                    if let Some((database, _)) = self.connection_database(connection) {
+                        if let Some(connection) = database.connections.get_mut(&connection) {
+                            connection.version_change_pending = false;
+                        }
+                    }
+                    self.update_connection(connection);
+                },
+                IndexedDBThreadMsg::ClearOrigin(origin) => {
+                    let origin = self.load_origin(&origin);
+                    let names: Vec<String> =
+                        self.origins[&origin].databases.keys().cloned().collect();
+                    for name in names {
+                        // Nothing waits for the outcome of the deletion.
+                        let (events, _) = ipc::channel().expect("ipc channel failure");
+                        self.queue_request(&origin, &name, None, events);
+                    }
+                },
+                IndexedDBThreadMsg::Exit(sender) => {
+                    // Databases are saved when transactions commit, so only wait for the last writes.
+                    for storage in self.origins.values() {
+                        if let Some(ref journal) = storage.journal {
+                            journal.flush();
+                        }
+                    }
+                    let _ = sender.send(());
+                    break;
+                },
+            }
+        }
+    }
+
+    /// Read the databases of `origin` from disk if they aren't in memory yet,
+    /// returning the key of the origin.
+    fn load_origin(&mut self, origin: &ImmutableOrigin) -> String {
+        let key = origin.ascii_serialization();
+        if !self.origins.contains_key(&key) {
+            let storage = OriginStorage::open(self.directory.as_ref(), origin);
+            self.origins.insert(key.clone(), storage);
+        }
+        key
+    }
+
+    fn queue_request(
+        &mut self,
+        origin: &str,
+        name: &str,
+        version: Option<Option<u64>>,
+        events: IpcSender<ConnectionEvent>,
+    ) {
+        if let Some(storage) = self.origins.get_mut(origin) {
+            storage
+                .databases
+                .entry(name.to_owned())
+                .or_insert_with(|| Database::new(name))
+                .requests
+                .push_back(ConnectionRequest { version, events });
+        }
+        self.update(origin, name);
+    }
+
+    fn connection_database(
+        &mut self,
+        connection: ConnectionId,
+    ) -> Option<(&mut Database, Option<&Journal>)> {
+        let (origin, name) = self.connections.get(&connection)?;
+        let storage = self.origins.get_mut(origin)?;
+        let database = storage.databases.get_mut(name)?;
+        Some((database, storage.journal.as_ref()))
+    }
+
+    fn update_connection(&mut self, connection: ConnectionId) {
+        if let Some((origin, name)) = self.connections.get(&connection).cloned() {
+            self.update(&origin, &name);
+        }
+    }
+
+    /// Make as much progress as possible with the transactions and requests of a database.
+    fn update(&mut self, origin: &str, name: &str) {
+        loop {
+            let storage = match self.origins.get_mut(origin) {
+                Some(storage) => storage,
+                None => return,
+            };
+            let database = match storage.databases.get_mut(name) {
+                Some(database) => database,
+                None => return,
+            };
+            database.run_transactions(storage.journal.as_ref());
+            for connection in database.remove_closed_connections() {
+                self.connections.remove(&connection);
+            }
+            if !self.process_request(origin, name) {
+                break;
+            }
+        }
+
+        // Forget the databases that were never created, or were deleted, once unused.
+        if let Some(storage) = self.origins.get_mut(origin) {
+            let forget = storage.databases.get(name).map_or(false, |database| {
+                database.data.metadata.version == 0 && database.is_idle()
+            });
+            if forget {
+                storage.databases.remove(name);
+            }
+        }
+
+        // Account for the size of the database once transactions changed it.
+        if let Some(storage) = self.origins.get_mut(origin) {
+            let modified = match storage.databases.get_mut(name) {
+                Some(database) if mem::replace(&mut database.modified, false) => {
+                    database.size = database.data.size();
+                    true
+                },
+                Some(_) => false,
+                // Deleting the database forgot it.
+                None => true,
+            };
+            if modified {
+                self.quota.set_usage(&storage.origin, storage.usage());
+            }
+        }
+    }
+
+    /// Advance the open or delete requests of a database, returning whether anything changed.
+    ///
+    /// <https://w3c.github.io/IndexedDB/#open-a-database>
+    /// <https://w3c.github.io/IndexedDB/#delete-a-database>
+    fn process_request(&mut self, origin: &str, name: &str) -> bool {
+        let storage = match self.origins.get_mut(origin) {
+            Some(storage) => storage,
+            None => return false,
+        };
+        let database = match storage.databases.get_mut(name) {
+            Some(database) => database,
+            None => return false,
+        };
+        let old_version = database.data.metadata.version;
+
+        let pending_request = match database.pending_request {
+            Some(ref mut pending_request) => pending_request,
+            None => {
+                let request = match database.requests.pop_front() {
+                    Some(request) => request,
+                    None => return false,
+                };
+                let new_version = match request.version {
+                    Some(version) => {
+                        let version = version.unwrap_or(cmp::max(old_version, 1));
+                        if version < old_version {
+                            let _ = request
+                                .events
+                                .send(ConnectionEvent::OpenFailed(BackendError::Version));
+                            return true;
+                        }
+                        if version == old_version {
+                            let connection = self.next_connection_id;
+                            self.next_connection_id += 1;
+                            self.connections
+                                .insert(connection, (origin.to_owned(), name.to_owned()));
+                            let _ = request.events.send(ConnectionEvent::Opened {
+                                connection,
+                                metadata: database.data.metadata.clone(),
+                            });
+                            database
+                                .connections
+                                .insert(connection, Connection::new(request.events));
+                            return true;
+                        }
+                        Some(version)
+                    },
+                    None => None,
+                };
+                // Ask the other connections to close.
+                for connection in database.connections.values_mut() {
+                    if !connection.close_pending {
+                        let event = ConnectionEvent::VersionChange {
+                            old_version,
+                            new_version,
+                        };
+                        connection.version_change_pending = connection.events.send(event).is_ok();
+                    }
+                }
+                database.pending_request = Some(PendingRequest {
+                    old_version,
+                    new_version,
+                    events: request.events,
+                    blocked: false,
+                    upgrading: None,
+                });
+                return true;
+            },
+        };
+
+        if pending_request.upgrading.is_some() ||
+            database
+                .connections
+                .values()
+                .any(|connection| connection.version_change_pending)
+        {
+            return false;
+        }
+        if !database.connections.is_empty() {
+            if !pending_request.blocked {
+                pending_request.blocked = true;
+                let _ = pending_request.events.send(ConnectionEvent::Blocked {
+                    old_version,
+                    new_version: pending_request.new_version,
+                });
+            }
+            return false;
+        }
+
+        match pending_request.new_version {
+            None => {
+                if let Some(ref journal) = storage.journal {
+                    let mut batch = JournalBatch::new();
+                    batch.remove(&journal_key(name, None, None));
+                    for (store, object_store) in &database.data.object_stores {
+                        remove_object_store(&mut batch, name, store, object_store);
+                    }
+                    journal.write(batch);
+                }
+                let _ = pending_request
+                    .events
+                    .send(ConnectionEvent::Deleted { old_version });
+                *database = Database {
+                    requests: database.requests.split_off(0),
+                    modified: true,
+                    ..Database::new(name)
+                };
+            },
+            Some(new_version) => {
+                let connection = self.next_connection_id;
+                self.next_connection_id += 1;
+                self.connections
+                    .insert(connection, (origin.to_owned(), name.to_owned()));
+                database
+                    .connections
+                    .insert(connection, Connection::new(pending_request.events.clone()));
+                pending_request.upgrading = Some(connection);
+                database
+                    .transactions
+                    .push(Transaction::upgrade(connection, &database.data));
+                database.data.metadata.version = new_version;
+                let _ = pending_request.events.send(ConnectionEvent::UpgradeNeeded {
+                    connection,
+                    old_version,
+                    metadata: database.data.metadata.clone(),
+                });
+            },
+        }
+        true
+    }
+}
@@ -0,0 +1,379 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! A crash-safe store of keyed JSON values in a single file of the profile directory.
+//!
+//! Every update is appended to the file as a length-prefixed, checksummed record,
+//! so that state survives a crash or kill as soon as the record reaches the disk.
+//! Updates of several keys that are written together share a record, so that either
+//! all or none of them survive.
+//! When the file is opened, a record that was only partially written is discarded
+//! along with everything after it. Writes happen on a dedicated thread, which also
+//! rewrites the file with only the live records once superseded ones dominate it.
+
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use flate2::Crc;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+
+/// The extension of journal files.
+const JOURNAL_FILE_EXTENSION: &'static str = "journal";
+
+/// The extension of the file a journal is compacted into, before replacing the journal.
+const COMPACTION_FILE_EXTENSION: &'static str = "compacting";
+
+/// Every record starts with the length and the CRC-32 of its payload.
+const RECORD_HEADER_LENGTH: usize = 8;
+
+/// Journals smaller than this are never compacted.
+const MIN_COMPACTION_SIZE: u64 = 64 * 1024;
+
+/// The new value of a key, or `None` if it has no value anymore.
+type Update = (String, Option<serde_json::Value>);
+
+enum JournalMsg {
+    Write(Vec<Update>),
+    Flush(Sender<()>),
+}
+
+/// A handle to a journal, through which updates are sent to its writer thread.
+#[derive(Clone, Debug)]
+pub struct Journal {
+    sender: Sender<JournalMsg>,
+}
+
+impl Journal {
+    /// Open the journal `name` in `directory`, returning the values it holds,
+    /// or `None` if there was no such journal yet.
+    pub fn open<T>(directory: &Path, name: &str) -> (Journal, Option<HashMap<String, T>>)
+    where
+        T: DeserializeOwned,
+    {
+        if let Err(why) = fs::create_dir_all(directory) {
+            warn!("couldn't create {}: {}", directory.display(), why);
+        }
+        let path = directory.join(name).with_extension(JOURNAL_FILE_EXTENSION);
+        let existed = path.exists();
+        let (writer, values) = JournalWriter::open(path);
+
+        let values = if existed {
+            let values = values
+                .into_iter()
+                .filter_map(|(key, value)| match serde_json::from_value(value) {
+                    Ok(value) => Some((key, value)),
+                    Err(why) => {
+                        warn!("couldn't decode journal entry {}: {}", key, why);
+                        None
+                    },
+                })
+                .collect();
+            Some(values)
+        } else {
+            None
+        };
+
+        let (sender, receiver) = unbounded();
+        thread::Builder::new()
+            .name(format!("Journal {}", name))
+            .spawn(move || writer.run(receiver))
+            .expect("Thread spawning failed");
+        (Journal { sender }, values)
+    }
+
+    /// Record `value` as the value for `key`.
+    pub fn put<T: Serialize>(&self, key: &str, value: &T) {
+        let mut batch = JournalBatch::new();
+        batch.put(key, value);
+        self.write(batch);
+    }
+
+    /// Record that there is no value for `key` anymore.
+    pub fn remove(&self, key: &str) {
+        let mut batch = JournalBatch::new();
+        batch.remove(key);
+        self.write(batch);
+    }
+
+    /// Record every update of `batch` at once.
+    pub fn write(&self, batch: JournalBatch) {
+        if !batch.updates.is_empty() {
+            let _ = self.sender.send(JournalMsg::Write(batch.updates));
+        }
+    }
+
+    /// Wait until every update sent so far is on disk.
+    pub fn flush(&self) {
+        let (sender, receiver) = unbounded();
+        if self.sender.send(JournalMsg::Flush(sender)).is_ok() {
+            let _ = receiver.recv();
+        }
+    }
+}
+
+/// Updates of several keys, which a journal writes in a single record.
+#[derive(Default)]
+pub struct JournalBatch {
+    updates: Vec<Update>,
+}
+
+impl JournalBatch {
+    pub fn new() -> JournalBatch {
+        JournalBatch::default()
+    }
+
+    /// Set `value` as the value for `key`.
+    pub fn put<T: Serialize>(&mut self, key: &str, value: &T) {
+        match serde_json::to_value(value) {
+            Ok(value) => self.updates.push((key.to_owned(), Some(value))),
+            Err(why) => warn!("couldn't encode journal entry {}: {}", key, why),
+        }
+    }
+
+    /// Remove the value for `key`.
+    pub fn remove(&mut self, key: &str) {
+        self.updates.push((key.to_owned(), None));
+    }
+}
+
+/// Encode a value that JSON can't represent, like binary data or keys that aren't strings,
+/// as base64-encoded bincode.
+pub fn encode_binary<T: Serialize>(value: &T) -> String {
+    base64::encode(&bincode::serialize(value).unwrap_or_default())
+}
+
+pub fn decode_binary<T: DeserializeOwned>(value: &str) -> Option<T> {
+    let bytes = base64::decode(value).ok()?;
+    bincode::deserialize(&bytes).ok()
+}
+
+/// Encode a record whose payload is either a single update, or a list of updates.
+fn encode_record<T: Serialize>(updates: &T) -> Vec<u8> {
+    let payload = serde_json::to_vec(updates).unwrap_or_default();
+    let mut crc = Crc::new();
+    crc.update(&payload);
+
+    let mut record = Vec::with_capacity(RECORD_HEADER_LENGTH + payload.len());
+    record.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    record.extend_from_slice(&crc.sum().to_le_bytes());
+    record.extend_from_slice(&payload);
+    record
+}
+
+fn decode_payload(payload: &[u8]) -> Option<Vec<Update>> {
+    match serde_json::from_slice::<Update>(payload) {
+        Ok(update) => Some(vec![update]),
+        Err(_) => serde_json::from_slice(payload).ok(),
+    }
+}
+
+/// Split the complete records off the start of `data`, returning the updates
+/// they hold in order, along with the length of data they span.
+fn decode_records(data: &[u8]) -> (Vec<Update>, usize) {
+    let mut records = vec![];
+    let mut offset = 0;
+    while data.len() - offset >= RECORD_HEADER_LENGTH {
+        let mut length = [0; 4];
+        let mut checksum = [0; 4];
+        length.copy_from_slice(&data[offset..offset + 4]);
+        checksum.copy_from_slice(&data[offset + 4..offset + 8]);
+        let end = offset + RECORD_HEADER_LENGTH + u32::from_le_bytes(length) as usize;
+        if end > data.len() {
+            break;
+        }
+        let payload = &data[offset + RECORD_HEADER_LENGTH..end];
+        let mut crc = Crc::new();
+        crc.update(payload);
+        if crc.sum() != u32::from_le_bytes(checksum) {
+            break;
+        }
+        match decode_payload(payload) {
+            Some(updates) => records.extend(updates),
+            None => break,
+        }
+        offset = end;
+    }
+    (records, offset)
+}
+
+struct JournalWriter {
+    path: PathBuf,
+    file: Option<File>,
+    /// A record holding only the current value of each key, which is what
+    /// the journal is compacted into.
+    records: HashMap<String, Vec<u8>>,
+    /// The size of the journal file.
+    journal_size: u64,
+    /// The size of the records in `records`.
+    live_size: u64,
+}
+
+impl JournalWriter {
+    /// Open the journal at `path`, returning its writer along with the values it holds.
+    fn open(path: PathBuf) -> (JournalWriter, HashMap<String, serde_json::Value>) {
+        let mut data = vec![];
+        if let Ok(mut file) = File::open(&path) {
+            if let Err(why) = file.read_to_end(&mut data) {
+                warn!("couldn't read {}: {}", path.display(), why);
+            }
+        }
+
+        let mut values = HashMap::new();
+        let (decoded, valid_length) = decode_records(&data);
+        for (key, value) in decoded {
+            match value {
+                Some(value) => values.insert(key, value),
+                None => values.remove(&key),
+            };
+        }
+        let records: HashMap<_, _> = values
+            .iter()
+            .map(|(key, value)| (key.clone(), encode_record(&(key, Some(value)))))
+            .collect();
+
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .and_then(|file| {
+                if valid_length < data.len() {
+                    warn!(
+                        "discarding {} bytes of torn writes at the end of {}",
+                        data.len() - valid_length,
+                        path.display()
+                    );
+                    file.set_len(valid_length as u64)?;
+                }
+                Ok(file)
+            });
+        let file = match file {
+            Ok(file) => Some(file),
+            Err(why) => {
+                warn!("couldn't open {}: {}", path.display(), why);
+                None
+            },
+        };
+
+        let live_size = records.values().map(|record| record.len() as u64).sum();
+        let mut writer = JournalWriter {
+            path,
+            file,
+            records,
+            journal_size: valid_length as u64,
+            live_size,
+        };
+        writer.compact_if_needed();
+        (writer, values)
+    }
+
+    fn run(mut self, receiver: Receiver<JournalMsg>) {
+        while let Ok(msg) = receiver.recv() {
+            // Write every pending update before syncing, so that bursts of updates
+            // cost a single sync.
+            let mut flushes = vec![];
+            let mut written = false;
+            for msg in Some(msg).into_iter().chain(receiver.try_iter()) {
+                match msg {
+                    JournalMsg::Write(updates) => written |= self.write(updates),
+                    JournalMsg::Flush(sender) => flushes.push(sender),
+                }
+            }
+            if written {
+                self.sync();
+                self.compact_if_needed();
+            }
+            for sender in flushes {
+                let _ = sender.send(());
+            }
+        }
+    }
+
+    /// Append a record of the updates that change something, returning whether there was one.
+    fn write(&mut self, updates: Vec<Update>) -> bool {
+        let mut changes = Vec::with_capacity(updates.len());
+        for (key, value) in updates {
+            let old_record = match value {
+                Some(ref value) => {
+                    let record = encode_record(&(&key, Some(value)));
+                    self.live_size += record.len() as u64;
+                    self.records.insert(key.clone(), record)
+                },
+                None => self.records.remove(&key),
+            };
+            if let Some(ref old_record) = old_record {
+                self.live_size -= old_record.len() as u64;
+            }
+            if value.is_some() || old_record.is_some() {
+                changes.push((key, value));
+            }
+        }
+        match changes.len() {
+            0 => return false,
+            1 => self.append(&encode_record(&changes[0])),
+            _ => self.append(&encode_record(&changes)),
+        }
+        true
+    }
+
+    fn append(&mut self, record: &[u8]) {
+        let written = match self.file {
+            Some(ref mut file) => file.write_all(record),
+            None => return,
+        };
+        match written {
+            Ok(()) => self.journal_size += record.len() as u64,
+            Err(why) => warn!("couldn't write to {}: {}", self.path.display(), why),
+        }
+    }
+
+    fn sync(&mut self) {
+        if let Some(ref file) = self.file {
+            if let Err(why) = file.sync_data() {
+                warn!("couldn't sync {}: {}", self.path.display(), why);
+            }
+        }
+    }
+
+    fn compact_if_needed(&mut self) {
+        if self.journal_size > MIN_COMPACTION_SIZE && self.journal_size > 2 * self.live_size {
+            self.compact();
+        }
+    }
+
+    /// Replace the journal with one that holds only the current value of each key.
+    /// The new journal is written to a separate file first, and then renamed into place,
+    /// so that a crash during compaction leaves the old journal intact.
+    fn compact(&mut self) {
+        let compaction_path = self.path.with_extension(COMPACTION_FILE_EXTENSION);
+        let written = File::create(&compaction_path).and_then(|mut file| {
+            for record in self.records.values() {
+                file.write_all(record)?;
+            }
+            file.sync_all()
+        });
+        let renamed = written
+            .and_then(|_| fs::rename(&compaction_path, &self.path))
+            .and_then(|_| OpenOptions::new().append(true).open(&self.path));
+        match renamed {
+            Ok(file) => {
+                debug!(
+                    "compacted {} from {} to {} bytes",
+                    self.path.display(),
+                    self.journal_size,
+                    self.live_size
+                );
+                self.file = Some(file);
+                self.journal_size = self.live_size;
+            },
+            Err(why) => {
+                warn!("couldn't compact {}: {}", self.path.display(), why);
+                let _ = fs::remove_file(&compaction_path);
+            },
+        }
+    }
+}
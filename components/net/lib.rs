@@ -19,18 +19,26 @@ extern crate serde;
 #[macro_use]
 extern crate servo_config;
 
+pub mod cache_storage_thread;
 pub mod connector;
 pub mod cookie;
 pub mod cookie_storage;
 mod data_loader;
 mod decoder;
+mod disk_cache;
+pub mod download_manager;
 pub mod filemanager_thread;
 mod hosts;
 pub mod hsts;
+pub mod http_auth;
 pub mod http_cache;
 pub mod http_loader;
 pub mod image_cache;
+pub mod indexeddb_thread;
+pub mod journal;
 pub mod mime_classifier;
+pub mod proxy;
+pub mod quota_thread;
 pub mod resource_thread;
 mod storage_thread;
 pub mod subresource_integrity;
@@ -45,4 +53,5 @@ pub mod fetch {
 pub mod test {
     pub use crate::hosts::{parse_hostsfile, replace_host_table};
     pub use crate::http_loader::HttpState;
+    pub use crate::websocket_loader::init as websocket_connect;
 }
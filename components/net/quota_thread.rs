@@ -0,0 +1,380 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! The quota manager, which accounts for the data every store holds for each origin.
+//!
+//! Stores report how many bytes each origin uses in them, and ask for space before they
+//! grow where the web page is told about running out of it. An origin can use up to its
+//! quota across every store: a share of the global limit, or all of it once its data is
+//! persistent. When the usage of every origin goes over the global limit, the least
+//! recently used origins whose data is only best-effort are evicted from every store.
+//!
+//! The quota manager never waits for a store, so that stores can wait for it.
+
+use crate::journal::Journal;
+use ipc_channel::ipc::{self, IpcReceiver, IpcSender};
+use net_traits::cache_storage_thread::CacheStorageThreadMsg;
+use net_traits::indexeddb_thread::IndexedDBThreadMsg;
+use net_traits::quota_thread::{QuotaClient, QuotaThreadMsg, StorageEstimate};
+use net_traits::storage_thread::StorageThreadMsg;
+use net_traits::{CoreResourceMsg, CoreResourceThread};
+use servo_url::ImmutableOrigin;
+use std::cmp;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The number of bytes every origin can use together.
+fn global_limit() -> u64 {
+    pref!(dom.quota.global_limit_mb).max(0) as u64 * 1024 * 1024
+}
+
+/// The number of bytes an origin whose data is best-effort can use.
+fn origin_limit() -> u64 {
+    cmp::min(
+        pref!(dom.quota.origin_limit_mb).max(0) as u64 * 1024 * 1024,
+        global_limit(),
+    )
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// A store the quota manager clears the data of origins from.
+pub enum QuotaStore {
+    /// The HTTP cache of the public resource thread.
+    Core(CoreResourceThread),
+    Storage(IpcSender<StorageThreadMsg>),
+    IndexedDB(IpcSender<IndexedDBThreadMsg>),
+    CacheStorage(IpcSender<CacheStorageThreadMsg>),
+}
+
+impl QuotaStore {
+    fn clear_origin(&self, origin: &ImmutableOrigin) {
+        let origin = origin.clone();
+        let result = match *self {
+            QuotaStore::Core(ref sender) => sender.send(CoreResourceMsg::ClearHttpCache(origin)),
+            QuotaStore::Storage(ref sender) => sender.send(StorageThreadMsg::ClearOrigin(origin)),
+            QuotaStore::IndexedDB(ref sender) => {
+                sender.send(IndexedDBThreadMsg::ClearOrigin(origin))
+            },
+            QuotaStore::CacheStorage(ref sender) => {
+                sender.send(CacheStorageThreadMsg::ClearOrigin(origin))
+            },
+        };
+        if let Err(e) = result {
+            warn!("Failed to clear the data of an origin ({}).", e);
+        }
+    }
+}
+
+/// Start the quota manager, which serves `port` and clears data from `stores`.
+pub fn start_quota_thread(
+    port: IpcReceiver<QuotaThreadMsg>,
+    config_dir: Option<PathBuf>,
+    stores: Vec<QuotaStore>,
+) {
+    thread::Builder::new()
+        .name("QuotaManager".to_owned())
+        .spawn(move || {
+            QuotaManager::new(port, config_dir, stores).start();
+        })
+        .expect("Thread spawning failed");
+}
+
+/// How a store accounts for the data it holds for each origin, if anything does.
+#[derive(Clone)]
+pub(crate) struct QuotaAccount {
+    client: QuotaClient,
+    sender: Option<IpcSender<QuotaThreadMsg>>,
+}
+
+impl QuotaAccount {
+    pub(crate) fn new(client: QuotaClient, sender: Option<IpcSender<QuotaThreadMsg>>) -> Self {
+        QuotaAccount { client, sender }
+    }
+
+    /// Report that the store holds `usage` bytes for `origin`.
+    pub(crate) fn set_usage(&self, origin: &ImmutableOrigin, usage: u64) {
+        if let Some(ref sender) = self.sender {
+            let msg = QuotaThreadMsg::SetUsage(origin.clone(), self.client, usage);
+            if let Err(e) = sender.send(msg) {
+                warn!("Failed to report the usage of an origin ({}).", e);
+            }
+        }
+    }
+
+    /// Whether the store can hold `usage` bytes for `origin`, in which case that is
+    /// recorded as its usage. Without a quota manager, there is always space.
+    pub(crate) fn request_space(&self, origin: &ImmutableOrigin, usage: u64) -> bool {
+        let sender = match self.sender {
+            Some(ref sender) => sender,
+            None => return true,
+        };
+        let (reply, receiver) = ipc::channel().expect("ipc channel failure");
+        let msg = QuotaThreadMsg::RequestSpace {
+            origin: origin.clone(),
+            client: self.client,
+            usage,
+            sender: reply,
+        };
+        if let Err(e) = sender.send(msg) {
+            warn!("Failed to request space for an origin ({}).", e);
+            return true;
+        }
+        receiver.recv().unwrap_or(true)
+    }
+}
+
+/// What the quota manager knows about an origin, as it is written to the journal.
+#[derive(Clone, Deserialize, Serialize)]
+struct OriginState {
+    origin: ImmutableOrigin,
+    /// The number of bytes the origin uses in each store.
+    usage: HashMap<QuotaClient, u64>,
+    /// <https://storage.spec.whatwg.org/#bucket-mode>
+    persisted: bool,
+    /// Milliseconds since the epoch at which the data of the origin was last used.
+    last_access: u64,
+}
+
+impl OriginState {
+    fn new(origin: &ImmutableOrigin) -> OriginState {
+        OriginState {
+            origin: origin.clone(),
+            usage: HashMap::new(),
+            persisted: false,
+            last_access: now_ms(),
+        }
+    }
+
+    fn total_usage(&self) -> u64 {
+        self.usage.values().sum()
+    }
+
+    /// <https://storage.spec.whatwg.org/#storage-quota>
+    fn quota(&self) -> u64 {
+        if self.persisted {
+            global_limit()
+        } else {
+            origin_limit()
+        }
+    }
+}
+
+struct QuotaManager {
+    port: IpcReceiver<QuotaThreadMsg>,
+    /// The state of every origin with data, by the serialization of the origin.
+    origins: HashMap<String, OriginState>,
+    stores: Vec<QuotaStore>,
+    /// Where the state of each origin is written whenever it changes.
+    journal: Option<Journal>,
+}
+
+impl QuotaManager {
+    fn new(
+        port: IpcReceiver<QuotaThreadMsg>,
+        config_dir: Option<PathBuf>,
+        stores: Vec<QuotaStore>,
+    ) -> QuotaManager {
+        let mut origins = HashMap::new();
+        let journal = config_dir.map(|config_dir| {
+            let (journal, values) = Journal::open(&config_dir, "quota");
+            origins = values.unwrap_or_default();
+            journal
+        });
+        QuotaManager {
+            port,
+            origins,
+            stores,
+            journal,
+        }
+    }
+}
+
+impl QuotaManager {
+    fn start(&mut self) {
+        loop {
+            match self.port.recv().unwrap() {
+                QuotaThreadMsg::SetUsage(origin, client, usage) => {
+                    if let Some(key) = self.set_usage(&origin, client, usage) {
+                        self.evict_for(&key);
+                    }
+                },
+                QuotaThreadMsg::RequestSpace {
+                    origin,
+                    client,
+                    usage,
+                    sender,
+                } => {
+                    let _ = sender.send(self.request_space(&origin, client, usage));
+                },
+                QuotaThreadMsg::Estimate(origin, sender) => {
+                    let key = origin.ascii_serialization();
+                    let estimate = match self.origins.get(&key) {
+                        Some(state) => StorageEstimate {
+                            usage: state.total_usage(),
+                            quota: state.quota(),
+                        },
+                        None => StorageEstimate {
+                            usage: 0,
+                            quota: origin_limit(),
+                        },
+                    };
+                    let _ = sender.send(estimate);
+                },
+                QuotaThreadMsg::Persist(origin, sender) => {
+                    if origin.is_tuple() {
+                        let key = origin.ascii_serialization();
+                        self.origins
+                            .entry(key.clone())
+                            .or_insert_with(|| OriginState::new(&origin))
+                            .persisted = true;
+                        self.save_state(&key);
+                    }
+                    let _ = sender.send(origin.is_tuple());
+                },
+                QuotaThreadMsg::Persisted(origin, sender) => {
+                    let persisted = self
+                        .origins
+                        .get(&origin.ascii_serialization())
+                        .map_or(false, |state| state.persisted);
+                    let _ = sender.send(persisted);
+                },
+                QuotaThreadMsg::ClearOrigin(origin) => {
+                    self.clear_origin(&origin);
+                },
+                QuotaThreadMsg::Exit(sender) => {
+                    // The state of origins is saved eagerly, so only wait for the last writes.
+                    if let Some(ref journal) = self.journal {
+                        journal.flush();
+                    }
+                    let _ = sender.send(());
+                    break;
+                },
+            }
+        }
+    }
+
+    /// Persist the state of the origin with the given key, if it might have changed.
+    fn save_state(&self, key: &str) {
+        if let Some(ref journal) = self.journal {
+            match self.origins.get(key) {
+                Some(state) => journal.put(key, state),
+                None => journal.remove(key),
+            }
+        }
+    }
+
+    /// Record the usage of an origin in a store, returning the key of the origin,
+    /// unless it is opaque and has no storage of its own to account for.
+    fn set_usage(
+        &mut self,
+        origin: &ImmutableOrigin,
+        client: QuotaClient,
+        usage: u64,
+    ) -> Option<String> {
+        if !origin.is_tuple() {
+            return None;
+        }
+        let key = origin.ascii_serialization();
+        {
+            let state = self
+                .origins
+                .entry(key.clone())
+                .or_insert_with(|| OriginState::new(origin));
+            state.usage.insert(client, usage);
+            state.last_access = now_ms();
+        }
+        if self.origins[&key].total_usage() == 0 && !self.origins[&key].persisted {
+            self.origins.remove(&key);
+        }
+        self.save_state(&key);
+        Some(key)
+    }
+
+    /// Whether an origin can use `usage` bytes in a store, within its quota and the
+    /// global limit once other origins are evicted, in which case that is recorded.
+    fn request_space(&mut self, origin: &ImmutableOrigin, client: QuotaClient, usage: u64) -> bool {
+        let key = origin.ascii_serialization();
+        let (current, quota) = match self.origins.get(&key) {
+            Some(state) => (
+                state.usage.get(&client).cloned().unwrap_or(0),
+                state.quota(),
+            ),
+            None => (0, origin_limit()),
+        };
+        // Using less space always succeeds.
+        if usage > current {
+            let other_usage = self
+                .origins
+                .get(&key)
+                .map_or(0, |state| state.total_usage() - current);
+            if other_usage + usage > quota {
+                return false;
+            }
+            let growth = usage - current;
+            let total: u64 = self.origins.values().map(OriginState::total_usage).sum();
+            let evictable: u64 = self
+                .origins
+                .iter()
+                .filter(|&(other, state)| *other != key && !state.persisted)
+                .map(|(_, state)| state.total_usage())
+                .sum();
+            if (total + growth).saturating_sub(global_limit()) > evictable {
+                return false;
+            }
+        }
+        if let Some(key) = self.set_usage(origin, client, usage) {
+            self.evict_for(&key);
+        }
+        true
+    }
+
+    /// Evict the least recently used origins whose data is best-effort, other than
+    /// the one with the given key, until every origin together is within the global limit.
+    /// <https://storage.spec.whatwg.org/#usage-and-quota>
+    fn evict_for(&mut self, key: &str) {
+        let mut total: u64 = self.origins.values().map(OriginState::total_usage).sum();
+        let mut candidates: Vec<(u64, String)> = self
+            .origins
+            .iter()
+            .filter(|&(other, state)| other != key && !state.persisted)
+            .map(|(other, state)| (state.last_access, other.clone()))
+            .collect();
+        candidates.sort();
+        for (_, candidate) in candidates {
+            if total <= global_limit() {
+                break;
+            }
+            let state = match self.origins.remove(&candidate) {
+                Some(state) => state,
+                None => continue,
+            };
+            debug!("Evicting the data of {}", candidate);
+            total -= state.total_usage();
+            for store in &self.stores {
+                store.clear_origin(&state.origin);
+            }
+            self.save_state(&candidate);
+        }
+    }
+
+    /// Clear the data of an origin in every store, and forget everything about it.
+    /// Stores handle their messages in order, so later requests won't see the data.
+    fn clear_origin(&mut self, origin: &ImmutableOrigin) {
+        for store in &self.stores {
+            store.clear_origin(origin);
+        }
+        let key = origin.ascii_serialization();
+        if self.origins.remove(&key).is_some() {
+            self.save_state(&key);
+        }
+    }
+}
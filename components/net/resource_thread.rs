@@ -4,15 +4,22 @@
 
 //! A thread that takes a URL and streams back the binary data.
 
+use crate::cache_storage_thread::CacheStorageThreadFactory;
 use crate::connector::{create_http_client, create_tls_config, ALPN_H2_H1};
-use crate::cookie;
+use crate::cookie::{self, SameSiteContext};
 use crate::cookie_storage::CookieStorage;
+use crate::download_manager::{DownloadFetch, DownloadManager};
 use crate::fetch::cors_cache::CorsCache;
 use crate::fetch::methods::{fetch, CancellationListener, FetchContext};
 use crate::filemanager_thread::FileManager;
 use crate::hsts::HstsList;
+use crate::http_auth::DigestChallenge;
 use crate::http_cache::HttpCache;
 use crate::http_loader::{http_redirect_fetch, HttpState, HANDLE};
+use crate::indexeddb_thread::IndexedDBThreadFactory;
+use crate::journal::Journal;
+use crate::proxy::ProxyConfig;
+use crate::quota_thread::{start_quota_thread, QuotaStore};
 use crate::storage_thread::StorageThreadFactory;
 use crate::websocket_loader;
 use crossbeam_channel::Sender;
@@ -22,6 +29,10 @@ use embedder_traits::EmbedderProxy;
 use hyper_serde::Serde;
 use ipc_channel::ipc::{self, IpcReceiver, IpcReceiverSet, IpcSender};
 use malloc_size_of::{MallocSizeOf, MallocSizeOfOps};
+use msg::constellation_msg::TopLevelBrowsingContextId;
+use net_traits::cache_storage_thread::CacheStorageThreadMsg;
+use net_traits::indexeddb_thread::IndexedDBThreadMsg;
+use net_traits::quota_thread::QuotaThreadMsg;
 use net_traits::request::{Destination, RequestBuilder};
 use net_traits::response::{Response, ResponseInit};
 use net_traits::storage_thread::StorageThreadMsg;
@@ -57,6 +68,8 @@ pub fn new_resource_threads(
     config_dir: Option<PathBuf>,
     certificate_path: Option<String>,
 ) -> (ResourceThreads, ResourceThreads) {
+    // The stores account for their data with the quota manager, which clears data from them.
+    let (quota, quota_port) = ipc::channel().unwrap();
     let (public_core, private_core) = new_core_resource_thread(
         user_agent,
         devtools_chan,
@@ -65,11 +78,33 @@ pub fn new_resource_threads(
         embedder_proxy,
         config_dir.clone(),
         certificate_path,
+        Some(quota.clone()),
+    );
+    let storage: IpcSender<StorageThreadMsg> =
+        StorageThreadFactory::new(config_dir.clone(), Some(quota.clone()));
+    let indexeddb: IpcSender<IndexedDBThreadMsg> =
+        IndexedDBThreadFactory::new(config_dir.clone(), Some(quota.clone()));
+    let cache_storage: IpcSender<CacheStorageThreadMsg> =
+        CacheStorageThreadFactory::new(config_dir.clone(), Some(quota.clone()));
+    start_quota_thread(
+        quota_port,
+        config_dir,
+        vec![
+            QuotaStore::Core(public_core.clone()),
+            QuotaStore::Storage(storage.clone()),
+            QuotaStore::IndexedDB(indexeddb.clone()),
+            QuotaStore::CacheStorage(cache_storage.clone()),
+        ],
     );
-    let storage: IpcSender<StorageThreadMsg> = StorageThreadFactory::new(config_dir);
     (
-        ResourceThreads::new(public_core, storage.clone()),
-        ResourceThreads::new(private_core, storage),
+        ResourceThreads::new(
+            public_core,
+            storage.clone(),
+            indexeddb.clone(),
+            cache_storage.clone(),
+            quota.clone(),
+        ),
+        ResourceThreads::new(private_core, storage, indexeddb, cache_storage, quota),
     )
 }
 
@@ -82,6 +117,7 @@ pub fn new_core_resource_thread(
     embedder_proxy: EmbedderProxy,
     config_dir: Option<PathBuf>,
     certificate_path: Option<String>,
+    quota: Option<IpcSender<QuotaThreadMsg>>,
 ) -> (CoreResourceThread, CoreResourceThread) {
     let (public_setup_chan, public_setup_port) = ipc::channel().unwrap();
     let (private_setup_chan, private_setup_port) = ipc::channel().unwrap();
@@ -94,7 +130,7 @@ pub fn new_core_resource_thread(
                 user_agent,
                 devtools_chan,
                 time_profiler_chan,
-                embedder_proxy,
+                embedder_proxy.clone(),
                 certificate_path.clone(),
             );
 
@@ -102,6 +138,8 @@ pub fn new_core_resource_thread(
                 resource_manager,
                 config_dir,
                 certificate_path,
+                embedder_proxy,
+                quota,
             };
 
             mem_profiler_chan.run_with_memory_reporting(
@@ -119,51 +157,76 @@ struct ResourceChannelManager {
     resource_manager: CoreResourceManager,
     config_dir: Option<PathBuf>,
     certificate_path: Option<String>,
+    embedder_proxy: EmbedderProxy,
+    /// Where the public HTTP cache accounts for the data it holds on disk.
+    quota: Option<IpcSender<QuotaThreadMsg>>,
 }
 
 fn create_http_states(
     config_dir: Option<&Path>,
     certificate_path: Option<String>,
+    embedder_proxy: EmbedderProxy,
+    quota: Option<IpcSender<QuotaThreadMsg>>,
 ) -> (Arc<HttpState>, Arc<HttpState>) {
     let mut hsts_list = HstsList::from_servo_preload();
     let mut auth_cache = AuthCache::new();
-    let http_cache = HttpCache::new();
+    let mut http_cache = match config_dir {
+        Some(config_dir) if pref!(network.http_cache.disk.enabled) => {
+            HttpCache::new_persistent(config_dir, pref!(network.http_cache.disk.max_size) as u64)
+        },
+        _ => HttpCache::new(),
+    };
+    if let Some(quota) = quota {
+        http_cache.set_quota_manager(quota);
+    }
     let mut cookie_jar = CookieStorage::new(150);
     if let Some(config_dir) = config_dir {
-        read_json_from_file(&mut auth_cache, config_dir, "auth_cache.json");
-        read_json_from_file(&mut hsts_list, config_dir, "hsts_list.json");
-        read_json_from_file(&mut cookie_jar, config_dir, "cookie_jar.json");
+        auth_cache.open_journal(config_dir);
+        hsts_list.open_journal(config_dir);
+        cookie_jar.open_journal(config_dir);
     }
 
+    let proxy_config = ProxyConfig::from_prefs_and_env();
+
     let certs = match certificate_path {
         Some(ref path) => fs::read_to_string(path).expect("Couldn't not find certificate file"),
         None => resources::read_string(Resource::SSLCertificates),
     };
 
+    let auth_cache = ServoArc::new(RwLock::new(auth_cache));
     let http_state = HttpState {
         hsts_list: RwLock::new(hsts_list),
         cookie_jar: RwLock::new(cookie_jar),
-        auth_cache: RwLock::new(auth_cache),
+        auth_cache: auth_cache.clone(),
         history_states: RwLock::new(HashMap::new()),
         http_cache: RwLock::new(http_cache),
         http_cache_state: Mutex::new(HashMap::new()),
         client: create_http_client(
             create_tls_config(&certs, ALPN_H2_H1),
+            proxy_config.clone(),
+            auth_cache,
             HANDLE.lock().unwrap().executor(),
         ),
+        proxy_config: proxy_config.clone(),
+        embedder_proxy: Mutex::new(embedder_proxy.clone()),
     };
 
+    let private_auth_cache = ServoArc::new(RwLock::new(AuthCache::new()));
     let private_http_state = HttpState {
         hsts_list: RwLock::new(HstsList::from_servo_preload()),
         cookie_jar: RwLock::new(CookieStorage::new(150)),
-        auth_cache: RwLock::new(AuthCache::new()),
+        auth_cache: private_auth_cache.clone(),
         history_states: RwLock::new(HashMap::new()),
         http_cache: RwLock::new(HttpCache::new()),
         http_cache_state: Mutex::new(HashMap::new()),
         client: create_http_client(
             create_tls_config(&certs, ALPN_H2_H1),
+            proxy_config.clone(),
+            private_auth_cache,
             HANDLE.lock().unwrap().executor(),
         ),
+        proxy_config,
+        embedder_proxy: Mutex::new(embedder_proxy),
     };
 
     (Arc::new(http_state), Arc::new(private_http_state))
@@ -180,6 +243,8 @@ impl ResourceChannelManager {
         let (public_http_state, private_http_state) = create_http_states(
             self.config_dir.as_ref().map(Deref::deref),
             self.certificate_path.clone(),
+            self.embedder_proxy.clone(),
+            self.quota.clone(),
         );
 
         let mut rx_set = IpcReceiverSet::new().unwrap();
@@ -265,6 +330,16 @@ impl ResourceChannelManager {
                         .fetch(req_init, None, DiscardFetch, http_state, None)
                 },
             },
+            CoreResourceMsg::Download(req_init, filename_hint, browser_id) => self
+                .resource_manager
+                .download(req_init, filename_hint, browser_id, http_state),
+            CoreResourceMsg::CancelDownload(id) => self.resource_manager.downloads.cancel(id),
+            CoreResourceMsg::ResumeDownload(id) => {
+                // A resumed download uses the state of the session that started it.
+                if let Some(fetch) = self.resource_manager.downloads.resume(id) {
+                    self.resource_manager.fetch_download(fetch);
+                }
+            },
             CoreResourceMsg::DeleteCookies(request) => {
                 http_state
                     .cookie_jar
@@ -273,27 +348,34 @@ impl ResourceChannelManager {
                     .clear_storage(&request);
                 return true;
             },
+            CoreResourceMsg::ClearHttpCache(origin) => {
+                http_state.http_cache.write().unwrap().clear_origin(&origin);
+            },
             CoreResourceMsg::FetchRedirect(req_init, res_init, sender, cancel_chan) => self
                 .resource_manager
                 .fetch(req_init, Some(res_init), sender, http_state, cancel_chan),
             CoreResourceMsg::SetCookieForUrl(request, cookie, source) => self
                 .resource_manager
                 .set_cookie_for_url(&request, cookie.into_inner(), source, http_state),
-            CoreResourceMsg::SetCookiesForUrl(request, cookies, source) => {
-                for cookie in cookies {
-                    self.resource_manager.set_cookie_for_url(
-                        &request,
-                        cookie.into_inner(),
-                        source,
-                        http_state,
-                    );
+            CoreResourceMsg::SetCookiesForUrl(request, cookies, ancestor_origins, source) => {
+                let context = SameSiteContext::for_document(&request, &ancestor_origins);
+                let mut cookie_jar = http_state.cookie_jar.write().unwrap();
+                // The cookies are parsed here, from their strings,
+                // since a parsed cookie loses its `SameSite=None` attribute.
+                for cookie_str in cookies {
+                    let cookie = cookie::Cookie::from_cookie_string(cookie_str, &request, source)
+                        .filter(|cookie| cookie.may_be_set_in_context(context));
+                    if let Some(cookie) = cookie {
+                        cookie_jar.push(cookie, &request, source);
+                    }
                 }
             },
-            CoreResourceMsg::GetCookiesForUrl(url, consumer, source) => {
+            CoreResourceMsg::GetCookiesForUrl(url, ancestor_origins, consumer, source) => {
+                let context = SameSiteContext::for_document(&url, &ancestor_origins);
                 let mut cookie_jar = http_state.cookie_jar.write().unwrap();
                 cookie_jar.remove_expired_cookies_for_url(&url);
                 consumer
-                    .send(cookie_jar.cookies_for_url(&url, source))
+                    .send(cookie_jar.cookies_for_url_in_context(&url, source, context))
                     .unwrap();
             },
             CoreResourceMsg::NetworkMediator(mediator_chan) => {
@@ -329,21 +411,23 @@ impl ResourceChannelManager {
             },
             CoreResourceMsg::ToFileManager(msg) => self.resource_manager.filemanager.handle(msg),
             CoreResourceMsg::Exit(sender) => {
-                if let Some(ref config_dir) = self.config_dir {
+                if self.config_dir.is_some() {
                     match http_state.auth_cache.read() {
-                        Ok(auth_cache) => {
-                            write_json_to_file(&*auth_cache, config_dir, "auth_cache.json")
-                        },
+                        Ok(auth_cache) => auth_cache.flush_journal(),
                         Err(_) => warn!("Error writing auth cache to disk"),
                     }
                     match http_state.cookie_jar.read() {
-                        Ok(jar) => write_json_to_file(&*jar, config_dir, "cookie_jar.json"),
+                        Ok(jar) => jar.flush_journal(),
                         Err(_) => warn!("Error writing cookie jar to disk"),
                     }
                     match http_state.hsts_list.read() {
-                        Ok(hsts) => write_json_to_file(&*hsts, config_dir, "hsts_list.json"),
+                        Ok(hsts) => hsts.flush_journal(),
                         Err(_) => warn!("Error writing hsts list to disk"),
                     }
+                    match http_state.http_cache.read() {
+                        Ok(http_cache) => http_cache.flush(),
+                        Err(_) => warn!("Error writing http cache index to disk"),
+                    }
                 }
                 let _ = sender.send(());
                 return false;
@@ -409,11 +493,71 @@ pub struct AuthCacheEntry {
     pub password: String,
 }
 
+/// The persisted state of an `AuthCache` for a single key.
+#[derive(Deserialize, Serialize)]
+struct AuthCacheRecord {
+    entry: AuthCacheEntry,
+    digest_challenge: Option<DigestChallenge>,
+}
+
 impl AuthCache {
     pub fn new() -> AuthCache {
         AuthCache {
             version: 1,
             entries: HashMap::new(),
+            digest_challenges: HashMap::new(),
+            journal: None,
+        }
+    }
+
+    /// Load the credentials persisted in `config_dir`, and persist every later change to them.
+    pub fn open_journal(&mut self, config_dir: &Path) {
+        let (journal, records) = Journal::open::<AuthCacheRecord>(config_dir, "auth_cache");
+        match records {
+            Some(records) => {
+                for (key, record) in records {
+                    if let Some(digest_challenge) = record.digest_challenge {
+                        self.digest_challenges.insert(key.clone(), digest_challenge);
+                    }
+                    self.entries.insert(key, record.entry);
+                }
+            },
+            None => {
+                // Carry over the credentials that older versions only wrote on shutdown.
+                read_json_from_file(self, config_dir, "auth_cache.json");
+                self.journal = Some(journal.clone());
+                for key in self.entries.keys() {
+                    self.persist(key);
+                }
+                journal.flush();
+                let _ = fs::remove_file(config_dir.join("auth_cache.json"));
+            },
+        }
+        self.journal = Some(journal);
+    }
+
+    /// Persist the credentials cached under `key`.
+    pub fn persist(&self, key: &str) {
+        let journal = match self.journal {
+            Some(ref journal) => journal,
+            None => return,
+        };
+        match self.entries.get(key) {
+            Some(entry) => journal.put(
+                key,
+                &AuthCacheRecord {
+                    entry: entry.clone(),
+                    digest_challenge: self.digest_challenges.get(key).cloned(),
+                },
+            ),
+            None => journal.remove(key),
+        }
+    }
+
+    /// Wait until every change to the credentials is persisted.
+    pub fn flush_journal(&self) {
+        if let Some(ref journal) = self.journal {
+            journal.flush();
         }
     }
 }
@@ -421,7 +565,15 @@ impl AuthCache {
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct AuthCache {
     pub version: u32,
+    /// The credentials for each origin, and for each proxy under its `Proxy::auth_cache_key`.
     pub entries: HashMap<String, AuthCacheEntry>,
+    /// The Digest challenges the credentials in `entries` answer, under the same keys.
+    /// Credentials without one are sent with Basic authentication.
+    #[serde(default)]
+    pub digest_challenges: HashMap<String, DigestChallenge>,
+    /// Where the credentials for each key are written whenever they change.
+    #[serde(skip)]
+    journal: Option<Journal>,
 }
 
 pub struct CoreResourceManager {
@@ -429,6 +581,7 @@ pub struct CoreResourceManager {
     devtools_chan: Option<Sender<DevtoolsControlMsg>>,
     swmanager_chan: Option<IpcSender<CustomResponseMediator>>,
     filemanager: FileManager,
+    downloads: DownloadManager,
     fetch_pool: rayon::ThreadPool,
     certificate_path: Option<String>,
 }
@@ -450,6 +603,7 @@ impl CoreResourceManager {
             devtools_chan: devtools_channel,
             swmanager_chan: None,
             filemanager: FileManager::new(embedder_proxy),
+            downloads: DownloadManager::new(),
             fetch_pool: pool,
             certificate_path,
         }
@@ -468,6 +622,29 @@ impl CoreResourceManager {
         }
     }
 
+    fn download(
+        &mut self,
+        request_builder: RequestBuilder,
+        filename_hint: Option<String>,
+        browser_id: Option<TopLevelBrowsingContextId>,
+        http_state: &Arc<HttpState>,
+    ) {
+        let fetch = self
+            .downloads
+            .start(request_builder, filename_hint, browser_id, http_state);
+        self.fetch_download(fetch);
+    }
+
+    fn fetch_download(&self, fetch: DownloadFetch) {
+        let DownloadFetch {
+            request,
+            target,
+            http_state,
+            cancel_chan,
+        } = fetch;
+        self.fetch(request, None, target, &http_state, Some(cancel_chan));
+    }
+
     fn fetch<Target: 'static + FetchTaskTarget + Send>(
         &self,
         request_builder: RequestBuilder,
@@ -480,6 +657,7 @@ impl CoreResourceManager {
         let ua = self.user_agent.clone();
         let dc = self.devtools_chan.clone();
         let filemanager = self.filemanager.clone();
+        let swmanager_chan = self.swmanager_chan.clone();
 
         let timing_type = match request_builder.destination {
             Destination::Document => ResourceTimingType::Navigation,
@@ -497,6 +675,7 @@ impl CoreResourceManager {
                 user_agent: ua,
                 devtools_chan: dc,
                 filemanager: filemanager,
+                swmanager_chan: swmanager_chan,
                 cancellation_listener: Arc::new(Mutex::new(CancellationListener::new(cancel_chan))),
                 timing: ServoArc::new(Mutex::new(ResourceFetchTiming::new(request.timing_type()))),
             };
@@ -532,6 +711,7 @@ impl CoreResourceManager {
             action_receiver,
             http_state.clone(),
             self.certificate_path.clone(),
+            websocket_loader::deflate_settings(),
         );
     }
 }
@@ -2,30 +2,38 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 
+use crate::journal::Journal;
+use crate::quota_thread::QuotaAccount;
 use crate::resource_thread;
 use ipc_channel::ipc::{self, IpcReceiver, IpcSender};
+use net_traits::quota_thread::{QuotaClient, QuotaThreadMsg};
 use net_traits::storage_thread::{StorageThreadMsg, StorageType};
-use servo_url::ServoUrl;
+use servo_url::{ImmutableOrigin, ServoUrl};
 use std::borrow::ToOwned;
 use std::collections::BTreeMap;
 use std::collections::HashMap;
+use std::fs;
 use std::path::PathBuf;
 use std::thread;
 
 const QUOTA_SIZE_LIMIT: usize = 5 * 1024 * 1024;
 
 pub trait StorageThreadFactory {
-    fn new(config_dir: Option<PathBuf>) -> Self;
+    fn new(config_dir: Option<PathBuf>, quota: Option<IpcSender<QuotaThreadMsg>>) -> Self;
 }
 
 impl StorageThreadFactory for IpcSender<StorageThreadMsg> {
     /// Create a storage thread
-    fn new(config_dir: Option<PathBuf>) -> IpcSender<StorageThreadMsg> {
+    fn new(
+        config_dir: Option<PathBuf>,
+        quota: Option<IpcSender<QuotaThreadMsg>>,
+    ) -> IpcSender<StorageThreadMsg> {
         let (chan, port) = ipc::channel().unwrap();
+        let quota = QuotaAccount::new(QuotaClient::LocalStorage, quota);
         thread::Builder::new()
             .name("StorageManager".to_owned())
             .spawn(move || {
-                StorageManager::new(port, config_dir).start();
+                StorageManager::new(port, config_dir, quota).start();
             })
             .expect("Thread spawning failed");
         chan
@@ -36,20 +44,45 @@ struct StorageManager {
     port: IpcReceiver<StorageThreadMsg>,
     session_data: HashMap<String, (usize, BTreeMap<String, String>)>,
     local_data: HashMap<String, (usize, BTreeMap<String, String>)>,
-    config_dir: Option<PathBuf>,
+    /// Where the local storage of each origin is written whenever it changes.
+    journal: Option<Journal>,
+    /// Where the size of the local storage of each origin is accounted for.
+    quota: QuotaAccount,
 }
 
 impl StorageManager {
-    fn new(port: IpcReceiver<StorageThreadMsg>, config_dir: Option<PathBuf>) -> StorageManager {
-        let mut local_data = HashMap::new();
-        if let Some(ref config_dir) = config_dir {
-            resource_thread::read_json_from_file(&mut local_data, config_dir, "local_data.json");
-        }
+    fn new(
+        port: IpcReceiver<StorageThreadMsg>,
+        config_dir: Option<PathBuf>,
+        quota: QuotaAccount,
+    ) -> StorageManager {
+        let mut local_data: HashMap<String, (usize, BTreeMap<String, String>)> = HashMap::new();
+        let journal = config_dir.map(|config_dir| {
+            let (journal, journaled_data) = Journal::open(&config_dir, "local_data");
+            match journaled_data {
+                Some(journaled_data) => local_data = journaled_data,
+                None => {
+                    // Carry over the data that older versions wrote as a whole.
+                    resource_thread::read_json_from_file(
+                        &mut local_data,
+                        &config_dir,
+                        "local_data.json",
+                    );
+                    for (origin, data) in &local_data {
+                        journal.put(origin, data);
+                    }
+                    journal.flush();
+                    let _ = fs::remove_file(config_dir.join("local_data.json"));
+                },
+            }
+            journal
+        });
         StorageManager {
             port: port,
             session_data: HashMap::new(),
             local_data: local_data,
-            config_dir: config_dir,
+            journal: journal,
+            quota,
         }
     }
 }
@@ -68,22 +101,35 @@ impl StorageManager {
                     self.keys(sender, url, storage_type)
                 },
                 StorageThreadMsg::SetItem(sender, url, storage_type, name, value) => {
+                    let origin = url.origin();
                     self.set_item(sender, url, storage_type, name, value);
-                    self.save_state()
+                    self.save_state(storage_type, &origin)
                 },
                 StorageThreadMsg::GetItem(sender, url, storage_type, name) => {
                     self.request_item(sender, url, storage_type, name)
                 },
                 StorageThreadMsg::RemoveItem(sender, url, storage_type, name) => {
+                    let origin = url.origin();
                     self.remove_item(sender, url, storage_type, name);
-                    self.save_state()
+                    self.save_state(storage_type, &origin)
                 },
                 StorageThreadMsg::Clear(sender, url, storage_type) => {
+                    let origin = url.origin();
                     self.clear(sender, url, storage_type);
-                    self.save_state()
+                    self.save_state(storage_type, &origin)
+                },
+                StorageThreadMsg::ClearOrigin(origin) => {
+                    let key = origin.ascii_serialization();
+                    self.session_data.remove(&key);
+                    if self.local_data.remove(&key).is_some() {
+                        self.save_state(StorageType::Local, &origin);
+                    }
                 },
                 StorageThreadMsg::Exit(sender) => {
-                    // Nothing to do since we save localstorage set eagerly.
+                    // Local storage is saved eagerly, so only wait for the last writes.
+                    if let Some(ref journal) = self.journal {
+                        journal.flush();
+                    }
                     let _ = sender.send(());
                     break;
                 },
@@ -91,10 +137,22 @@ impl StorageManager {
         }
     }
 
-    fn save_state(&self) {
-        if let Some(ref config_dir) = self.config_dir {
-            resource_thread::write_json_to_file(&self.local_data, config_dir, "local_data.json");
+    /// Persist the local storage of `origin`, and account for its size,
+    /// if it might have changed.
+    fn save_state(&self, storage_type: StorageType, origin: &ImmutableOrigin) {
+        if let StorageType::Session = storage_type {
+            return;
         }
+        let key = origin.ascii_serialization();
+        let data = self.local_data.get(&key);
+        if let Some(ref journal) = self.journal {
+            match data {
+                Some(data) => journal.put(&key, data),
+                None => journal.remove(&key),
+            }
+        }
+        self.quota
+            .set_usage(origin, data.map_or(0, |&(total, _)| total as u64));
     }
 
     fn select_data(
@@ -154,7 +212,7 @@ impl StorageManager {
     /// Sends Ok(changed, Some(old_value)) in case there was a previous
     /// value with the same key name but with different value name
     /// otherwise sends Err(()) to indicate that the operation would result in
-    /// exceeding the quota limit
+    /// exceeding the quota limit, of local storage or of the origin
     fn set_item(
         &mut self,
         sender: IpcSender<Result<(bool, Option<String>), ()>>,
@@ -163,6 +221,7 @@ impl StorageManager {
         name: String,
         value: String,
     ) {
+        let immutable_origin = url.origin();
         let origin = self.origin_as_string(url);
 
         let (this_storage_size, other_storage_size) = {
@@ -176,6 +235,7 @@ impl StorageManager {
             }
         };
 
+        let quota = self.quota.clone();
         let data = self.select_data_mut(storage_type);
         if !data.contains_key(&origin) {
             data.insert(origin.clone(), (0, BTreeMap::new()));
@@ -195,6 +255,16 @@ impl StorageManager {
                     return Err(());
                 }
 
+                // Only local storage outlives the session, and counts against the
+                // quota of the origin along with its other stores.
+                if let StorageType::Local = storage_type {
+                    if new_total_size > this_storage_size &&
+                        !quota.request_space(&immutable_origin, new_total_size as u64)
+                    {
+                        return Err(());
+                    }
+                }
+
                 let message =
                     entry
                         .insert(name.clone(), value.clone())
@@ -0,0 +1,313 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use http::header::{HeaderValue, ACCEPT_LANGUAGE, VARY};
+use http::{HeaderMap, Method};
+use ipc_channel::ipc::{self, IpcSender};
+use net::cache_storage_thread::CacheStorageThreadFactory;
+use net_traits::cache_storage_thread::CachedResponse;
+use net_traits::cache_storage_thread::{CacheBatchOperation, CacheId, CacheQueryOptions};
+use net_traits::cache_storage_thread::{CacheRequest, CacheStorageError, CacheStorageThreadMsg};
+use net_traits::response::ResponseType;
+use servo_config::set_pref;
+use servo_url::{ImmutableOrigin, ServoUrl};
+use std::env;
+use std::fs;
+use uuid::Uuid;
+
+fn origin() -> ImmutableOrigin {
+    ServoUrl::parse("https://example.com").unwrap().origin()
+}
+
+fn request(url: &str) -> CacheRequest {
+    CacheRequest {
+        method: Method::GET,
+        url: ServoUrl::parse(url).unwrap(),
+        headers: HeaderMap::new(),
+    }
+}
+
+fn response(body: &[u8]) -> CachedResponse {
+    CachedResponse {
+        response_type: ResponseType::Basic,
+        url_list: vec![],
+        raw_status: Some((200, b"OK".to_vec())),
+        headers: HeaderMap::new(),
+        body: Some(body.to_vec()),
+    }
+}
+
+fn open(backend: &IpcSender<CacheStorageThreadMsg>, name: &str) -> CacheId {
+    let (sender, receiver) = ipc::channel().unwrap();
+    backend
+        .send(CacheStorageThreadMsg::Open {
+            origin: origin(),
+            name: name.to_owned(),
+            sender,
+        })
+        .unwrap();
+    receiver.recv().unwrap()
+}
+
+fn keys(backend: &IpcSender<CacheStorageThreadMsg>) -> Vec<String> {
+    let (sender, receiver) = ipc::channel().unwrap();
+    backend
+        .send(CacheStorageThreadMsg::Keys(sender, origin()))
+        .unwrap();
+    receiver.recv().unwrap()
+}
+
+fn batch(
+    backend: &IpcSender<CacheStorageThreadMsg>,
+    cache: CacheId,
+    operations: Vec<CacheBatchOperation>,
+) -> Result<bool, CacheStorageError> {
+    let (sender, receiver) = ipc::channel().unwrap();
+    backend
+        .send(CacheStorageThreadMsg::Batch {
+            cache,
+            operations,
+            sender,
+        })
+        .unwrap();
+    receiver.recv().unwrap()
+}
+
+fn put(
+    backend: &IpcSender<CacheStorageThreadMsg>,
+    cache: CacheId,
+    request: CacheRequest,
+    response: CachedResponse,
+) -> Result<bool, CacheStorageError> {
+    batch(
+        backend,
+        cache,
+        vec![CacheBatchOperation::Put { request, response }],
+    )
+}
+
+fn match_all(
+    backend: &IpcSender<CacheStorageThreadMsg>,
+    cache: CacheId,
+    request: Option<CacheRequest>,
+    options: CacheQueryOptions,
+) -> Vec<Vec<u8>> {
+    let (sender, receiver) = ipc::channel().unwrap();
+    backend
+        .send(CacheStorageThreadMsg::MatchAll {
+            cache,
+            request,
+            options,
+            sender,
+        })
+        .unwrap();
+    receiver
+        .recv()
+        .unwrap()
+        .into_iter()
+        .map(|response| response.body.unwrap_or_default())
+        .collect()
+}
+
+#[test]
+fn test_open_keys_and_delete() {
+    let backend: IpcSender<CacheStorageThreadMsg> = CacheStorageThreadFactory::new(None, None);
+    let first = open(&backend, "first");
+    let second = open(&backend, "second");
+    assert_ne!(first, second);
+    assert_eq!(open(&backend, "first"), first);
+    assert_eq!(keys(&backend), vec!["first", "second"]);
+
+    put(
+        &backend,
+        first,
+        request("https://example.com/a"),
+        response(b"a"),
+    )
+    .unwrap();
+
+    let (sender, receiver) = ipc::channel().unwrap();
+    backend
+        .send(CacheStorageThreadMsg::Delete {
+            origin: origin(),
+            name: "first".to_owned(),
+            sender,
+        })
+        .unwrap();
+    assert!(receiver.recv().unwrap());
+    assert_eq!(keys(&backend), vec!["second"]);
+
+    // The deleted cache still works for those that had opened it.
+    let bodies = match_all(&backend, first, None, CacheQueryOptions::default());
+    assert_eq!(bodies, vec![b"a".to_vec()]);
+    assert_ne!(open(&backend, "first"), first);
+}
+
+#[test]
+fn test_put_replaces_matching_entry() {
+    let backend: IpcSender<CacheStorageThreadMsg> = CacheStorageThreadFactory::new(None, None);
+    let cache = open(&backend, "cache");
+    put(
+        &backend,
+        cache,
+        request("https://example.com/a"),
+        response(b"1"),
+    )
+    .unwrap();
+    put(
+        &backend,
+        cache,
+        request("https://example.com/b"),
+        response(b"2"),
+    )
+    .unwrap();
+    put(
+        &backend,
+        cache,
+        request("https://example.com/a#fragment"),
+        response(b"3"),
+    )
+    .unwrap();
+
+    let bodies = match_all(&backend, cache, None, CacheQueryOptions::default());
+    assert_eq!(bodies, vec![b"2".to_vec(), b"3".to_vec()]);
+}
+
+#[test]
+fn test_match_ignores_search_only_when_asked() {
+    let backend: IpcSender<CacheStorageThreadMsg> = CacheStorageThreadFactory::new(None, None);
+    let cache = open(&backend, "cache");
+    put(
+        &backend,
+        cache,
+        request("https://example.com/a?v=1"),
+        response(b"a"),
+    )
+    .unwrap();
+
+    let query = request("https://example.com/a?v=2");
+    let bodies = match_all(
+        &backend,
+        cache,
+        Some(query.clone()),
+        CacheQueryOptions::default(),
+    );
+    assert!(bodies.is_empty());
+
+    let options = CacheQueryOptions {
+        ignore_search: true,
+        ..CacheQueryOptions::default()
+    };
+    let bodies = match_all(&backend, cache, Some(query), options);
+    assert_eq!(bodies, vec![b"a".to_vec()]);
+}
+
+#[test]
+fn test_match_honors_vary() {
+    let backend: IpcSender<CacheStorageThreadMsg> = CacheStorageThreadFactory::new(None, None);
+    let cache = open(&backend, "cache");
+
+    let mut english = request("https://example.com/page");
+    english
+        .headers
+        .insert(ACCEPT_LANGUAGE, HeaderValue::from_static("en"));
+    let mut varying = response(b"en");
+    varying
+        .headers
+        .insert(VARY, HeaderValue::from_static("Accept-Language"));
+    put(&backend, cache, english.clone(), varying).unwrap();
+
+    let mut french = request("https://example.com/page");
+    french
+        .headers
+        .insert(ACCEPT_LANGUAGE, HeaderValue::from_static("fr"));
+    let bodies = match_all(
+        &backend,
+        cache,
+        Some(french.clone()),
+        CacheQueryOptions::default(),
+    );
+    assert!(bodies.is_empty());
+
+    let bodies = match_all(&backend, cache, Some(english), CacheQueryOptions::default());
+    assert_eq!(bodies, vec![b"en".to_vec()]);
+
+    let options = CacheQueryOptions {
+        ignore_vary: true,
+        ..CacheQueryOptions::default()
+    };
+    let bodies = match_all(&backend, cache, Some(french), options);
+    assert_eq!(bodies, vec![b"en".to_vec()]);
+}
+
+#[test]
+fn test_batch_that_puts_a_request_twice_fails() {
+    let backend: IpcSender<CacheStorageThreadMsg> = CacheStorageThreadFactory::new(None, None);
+    let cache = open(&backend, "cache");
+    let operations = vec![
+        CacheBatchOperation::Put {
+            request: request("https://example.com/a"),
+            response: response(b"1"),
+        },
+        CacheBatchOperation::Put {
+            request: request("https://example.com/a"),
+            response: response(b"2"),
+        },
+    ];
+    assert_eq!(
+        batch(&backend, cache, operations),
+        Err(CacheStorageError::InvalidState)
+    );
+    assert!(match_all(&backend, cache, None, CacheQueryOptions::default()).is_empty());
+}
+
+#[test]
+fn test_put_over_quota_fails() {
+    set_pref!(dom.caches.quota_mb, 1);
+    let backend: IpcSender<CacheStorageThreadMsg> = CacheStorageThreadFactory::new(None, None);
+    let cache = open(&backend, "cache");
+    put(
+        &backend,
+        cache,
+        request("https://example.com/a"),
+        response(b"a"),
+    )
+    .unwrap();
+
+    let big = response(&vec![0; 1024 * 1024]);
+    assert_eq!(
+        put(&backend, cache, request("https://example.com/big"), big),
+        Err(CacheStorageError::QuotaExceeded)
+    );
+    let bodies = match_all(&backend, cache, None, CacheQueryOptions::default());
+    assert_eq!(bodies, vec![b"a".to_vec()]);
+}
+
+#[test]
+fn test_caches_persist_across_restarts() {
+    let config_dir = env::temp_dir().join(format!("servo-caches-{}", Uuid::new_v4()));
+    fs::create_dir_all(&config_dir).unwrap();
+
+    let backend: IpcSender<CacheStorageThreadMsg> =
+        CacheStorageThreadFactory::new(Some(config_dir.clone()), None);
+    open(&backend, "first");
+    let cache = open(&backend, "second");
+    put(
+        &backend,
+        cache,
+        request("https://example.com/a"),
+        response(b"a"),
+    )
+    .unwrap();
+    let (sender, receiver) = ipc::channel().unwrap();
+    backend.send(CacheStorageThreadMsg::Exit(sender)).unwrap();
+    receiver.recv().unwrap();
+
+    let backend: IpcSender<CacheStorageThreadMsg> =
+        CacheStorageThreadFactory::new(Some(config_dir), None);
+    assert_eq!(keys(&backend), vec!["first", "second"]);
+    let cache = open(&backend, "second");
+    let bodies = match_all(&backend, cache, None, CacheQueryOptions::default());
+    assert_eq!(bodies, vec![b"a".to_vec()]);
+}
@@ -2,7 +2,8 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 
-use net::cookie::Cookie;
+use http::Method;
+use net::cookie::{Cookie, SameSite, SameSiteContext};
 use net::cookie_storage::CookieStorage;
 use net_traits::CookieSource;
 use servo_url::ServoUrl;
@@ -475,3 +476,113 @@ fn test_cookie_eviction_all_nonsecure_new_nonsecure() {
         "extra2=bar; extra3=bar; extra4=bar; extra5=bar; foo=bar"
     );
 }
+
+#[test]
+fn test_parse_same_site_attribute() {
+    let url = &ServoUrl::parse("https://example.com/").unwrap();
+    let parse = |cookie_str: &str| {
+        Cookie::from_cookie_string(cookie_str.to_owned(), url, CookieSource::HTTP)
+            .map(|cookie| cookie.same_site)
+    };
+    assert_eq!(parse("a=b"), Some(SameSite::Lax));
+    assert_eq!(parse("a=b; SameSite=Strict"), Some(SameSite::Strict));
+    assert_eq!(parse("a=b; samesite=lax"), Some(SameSite::Lax));
+    assert_eq!(parse("a=b; SameSite=None; Secure"), Some(SameSite::None));
+    assert_eq!(parse("a=b; SameSite=Bogus"), Some(SameSite::Lax));
+    assert_eq!(
+        parse("a=b; SameSite=Strict; SameSite=None; Secure"),
+        Some(SameSite::None)
+    );
+    // SameSite=None requires the Secure attribute.
+    assert_eq!(parse("a=b; SameSite=None"), None);
+}
+
+#[test]
+fn test_same_site_context() {
+    let url = ServoUrl::parse("https://www.example.com/").unwrap();
+    let same_site = ServoUrl::parse("http://static.example.com").unwrap();
+    let cross_site = ServoUrl::parse("https://example.org").unwrap();
+
+    assert_eq!(
+        SameSiteContext::new(&url, None, false, &Method::POST),
+        SameSiteContext::SameSite
+    );
+    assert_eq!(
+        SameSiteContext::new(&url, Some(&same_site.origin()), false, &Method::POST),
+        SameSiteContext::SameSite
+    );
+    assert_eq!(
+        SameSiteContext::new(&url, Some(&cross_site.origin()), false, &Method::GET),
+        SameSiteContext::CrossSite
+    );
+    assert_eq!(
+        SameSiteContext::new(&url, Some(&cross_site.origin()), true, &Method::GET),
+        SameSiteContext::CrossSiteTopLevelNavigation { safe_method: true }
+    );
+    assert_eq!(
+        SameSiteContext::new(&url, Some(&cross_site.origin()), true, &Method::POST),
+        SameSiteContext::CrossSiteTopLevelNavigation { safe_method: false }
+    );
+
+    assert_eq!(
+        SameSiteContext::for_document(&url, &[]),
+        SameSiteContext::SameSite
+    );
+    assert_eq!(
+        SameSiteContext::for_document(&url, &[same_site.origin()]),
+        SameSiteContext::SameSite
+    );
+    assert_eq!(
+        SameSiteContext::for_document(&url, &[same_site.origin(), cross_site.origin()]),
+        SameSiteContext::CrossSite
+    );
+}
+
+#[test]
+fn test_same_site_cookies_for_url_in_context() {
+    let url = ServoUrl::parse("https://example.com/").unwrap();
+    let source = CookieSource::HTTP;
+    let mut storage = CookieStorage::new(5);
+    for cookie_str in &[
+        "strict=1; SameSite=Strict",
+        "lax=1; SameSite=Lax",
+        "default=1",
+        "none=1; SameSite=None; Secure",
+    ] {
+        let cookie = Cookie::from_cookie_string(cookie_str.to_string(), &url, source).unwrap();
+        storage.push(cookie, &url, source);
+    }
+
+    let mut cookies_in_context = |context| {
+        storage
+            .cookies_for_url_in_context(&url, source, context)
+            .unwrap_or_default()
+    };
+    assert_eq!(
+        cookies_in_context(SameSiteContext::SameSite),
+        "strict=1; lax=1; default=1; none=1"
+    );
+    assert_eq!(
+        cookies_in_context(SameSiteContext::CrossSiteTopLevelNavigation { safe_method: true }),
+        "lax=1; default=1; none=1"
+    );
+    assert_eq!(
+        cookies_in_context(SameSiteContext::CrossSiteTopLevelNavigation { safe_method: false }),
+        "none=1"
+    );
+    assert_eq!(cookies_in_context(SameSiteContext::CrossSite), "none=1");
+}
+
+#[test]
+fn test_same_site_cookies_are_not_set_from_cross_site_responses() {
+    let url = ServoUrl::parse("https://example.com/").unwrap();
+    let parse = |cookie_str: &str| {
+        Cookie::from_cookie_string(cookie_str.to_owned(), &url, CookieSource::HTTP).unwrap()
+    };
+    let cross_site = SameSiteContext::CrossSite;
+    assert!(!parse("a=b").may_be_set_in_context(cross_site));
+    assert!(!parse("a=b; SameSite=Strict").may_be_set_in_context(cross_site));
+    assert!(parse("a=b; SameSite=None; Secure").may_be_set_in_context(cross_site));
+    assert!(parse("a=b; SameSite=Strict")
+        .may_be_set_in_context(SameSiteContext::CrossSiteTopLevelNavigation { safe_method: true }));
+}
@@ -0,0 +1,167 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::{create_embedder_proxy_and_receiver, make_server, new_fetch_context};
+use crossbeam_channel::{unbounded, Receiver};
+use embedder_traits::{DownloadId, DownloadStatus, EmbedderMsg};
+use http::header::{self, HeaderValue};
+use http::StatusCode;
+use hyper::{Body, Request as HyperRequest, Response as HyperResponse};
+use msg::constellation_msg::TopLevelBrowsingContextId;
+use net::download_manager::DownloadManager;
+use net::fetch::methods;
+use net_traits::request::RequestBuilder;
+use net_traits::response::Response;
+use net_traits::{FetchTaskTarget, ResourceFetchTiming, ResourceTimingType};
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::thread;
+use uuid::Uuid;
+
+const CONTENT: &'static [u8] = b"0123456789";
+
+#[derive(Debug, PartialEq)]
+enum DownloadEvent {
+    Started(DownloadId, String),
+    Finished(DownloadId, DownloadStatus),
+}
+
+/// Save every download to `path`, and forward when downloads start and finish.
+fn answer_download_prompts(
+    receiver: Receiver<(Option<TopLevelBrowsingContextId>, EmbedderMsg)>,
+    path: PathBuf,
+) -> Receiver<DownloadEvent> {
+    let (sender, events) = unbounded();
+    thread::spawn(move || {
+        while let Ok((_, msg)) = receiver.recv() {
+            match msg {
+                EmbedderMsg::DownloadStarted(id, _, filename, response_chan) => {
+                    response_chan
+                        .send(Some(path.to_string_lossy().into_owned()))
+                        .unwrap();
+                    let _ = sender.send(DownloadEvent::Started(id, filename));
+                },
+                EmbedderMsg::DownloadFinished(id, status) => {
+                    let _ = sender.send(DownloadEvent::Finished(id, status));
+                },
+                _ => {},
+            }
+        }
+    });
+    events
+}
+
+fn download_path() -> PathBuf {
+    env::temp_dir().join(format!("servo-download-{}", Uuid::new_v4()))
+}
+
+#[test]
+fn test_download_saves_the_response_to_the_chosen_file() {
+    let handler = move |_: HyperRequest<Body>, response: &mut HyperResponse<Body>| {
+        response.headers_mut().insert(
+            header::CONTENT_DISPOSITION,
+            HeaderValue::from_static("attachment; filename=\"report.txt\""),
+        );
+        *response.body_mut() = CONTENT.to_vec().into();
+    };
+    let (server, url) = make_server(handler);
+
+    let path = download_path();
+    let (embedder_proxy, embedder_receiver) = create_embedder_proxy_and_receiver();
+    let events = answer_download_prompts(embedder_receiver, path.clone());
+    let mut context = new_fetch_context(None, Some(embedder_proxy));
+
+    let mut downloads = DownloadManager::new();
+    let fetch = downloads.start(
+        RequestBuilder::new(url.clone()),
+        Some("hint.txt".to_owned()),
+        None,
+        &context.state,
+    );
+    let mut request = fetch.request.build();
+    let mut target = fetch.target;
+    methods::fetch(&mut request, &mut target, &mut context);
+    let _ = server.close();
+
+    // The name the server suggests takes precedence over the one the page suggests.
+    let id = DownloadId(0);
+    assert_eq!(
+        events.recv().unwrap(),
+        DownloadEvent::Started(id, "report.txt".to_owned())
+    );
+    assert_eq!(
+        events.recv().unwrap(),
+        DownloadEvent::Finished(id, DownloadStatus::Complete)
+    );
+    assert_eq!(fs::read(&path).unwrap(), CONTENT);
+    let _ = fs::remove_file(&path);
+}
+
+#[test]
+fn test_resumed_download_requests_the_missing_range() {
+    let handler = move |request: HyperRequest<Body>, response: &mut HyperResponse<Body>| {
+        assert_eq!(request.headers().get(header::RANGE).unwrap(), "bytes=4-");
+        assert_eq!(request.headers().get(header::IF_RANGE).unwrap(), "\"v1\"");
+        *response.status_mut() = StatusCode::PARTIAL_CONTENT;
+        response.headers_mut().insert(
+            header::CONTENT_RANGE,
+            HeaderValue::from_static("bytes 4-9/10"),
+        );
+        *response.body_mut() = CONTENT[4..].to_vec().into();
+    };
+    let (server, url) = make_server(handler);
+
+    let path = download_path();
+    let (embedder_proxy, embedder_receiver) = create_embedder_proxy_and_receiver();
+    let events = answer_download_prompts(embedder_receiver, path.clone());
+    let mut context = new_fetch_context(None, Some(embedder_proxy));
+
+    let mut downloads = DownloadManager::new();
+    let fetch = downloads.start(RequestBuilder::new(url.clone()), None, None, &context.state);
+
+    // The first fetch stops after part of the body.
+    let mut response = Response::new(
+        url.clone(),
+        ResourceFetchTiming::new(ResourceTimingType::Navigation),
+    );
+    response
+        .headers
+        .insert(header::ETAG, HeaderValue::from_static("\"v1\""));
+    response
+        .headers
+        .insert(header::CONTENT_LENGTH, HeaderValue::from_static("10"));
+    let mut target = fetch.target;
+    target.process_response(&response);
+    target.process_response_chunk(CONTENT[..4].to_vec());
+    let id = match events.recv().unwrap() {
+        DownloadEvent::Started(id, _) => id,
+        event => panic!("Unexpected download event {:?}", event),
+    };
+    downloads.cancel(id);
+    assert_eq!(
+        events.recv().unwrap(),
+        DownloadEvent::Finished(id, DownloadStatus::Cancelled)
+    );
+
+    // A cancelled fetch can't write to the file anymore.
+    target.process_response_chunk(b"garbage".to_vec());
+    target.process_response_eof(&response);
+
+    let fetch = downloads.resume(id).unwrap();
+    let mut request = fetch.request.build();
+    let mut target = fetch.target;
+    methods::fetch(&mut request, &mut target, &mut context);
+    let _ = server.close();
+
+    assert_eq!(
+        events.recv().unwrap(),
+        DownloadEvent::Finished(id, DownloadStatus::Complete)
+    );
+    assert_eq!(fs::read(&path).unwrap(), CONTENT);
+    let _ = fs::remove_file(&path);
+
+    // A complete download can't be resumed.
+    assert!(downloads.resume(id).is_none());
+}
@@ -6,9 +6,10 @@ use crate::fetch_with_context;
 use crate::fetch_with_cors_cache;
 use crate::http_loader::{expect_devtools_http_request, expect_devtools_http_response};
 use crate::{
-    create_embedder_proxy, fetch, make_server, make_ssl_server, new_fetch_context,
-    DEFAULT_USER_AGENT,
+    create_embedder_proxy, fetch, make_server, make_ssl_server, make_ssl_server_with_http2,
+    new_fetch_context, DEFAULT_USER_AGENT,
 };
+use content_security_policy::{self as csp, CspList};
 use crossbeam_channel::{unbounded, Sender};
 use devtools_traits::HttpRequest as DevtoolsHttpRequest;
 use devtools_traits::HttpResponse as DevtoolsHttpResponse;
@@ -17,14 +18,16 @@ use headers::{AccessControlAllowCredentials, AccessControlAllowHeaders, AccessCo
 use headers::{AccessControlAllowMethods, AccessControlMaxAge, HeaderMapExt};
 use headers::{CacheControl, ContentLength, ContentType, Expires, LastModified, Pragma, UserAgent};
 use http::header::{self, HeaderMap, HeaderName, HeaderValue};
-use http::{Method, StatusCode};
+use http::{Method, StatusCode, Version};
 use hyper::body::Body;
 use hyper::{Request as HyperRequest, Response as HyperResponse};
+use ipc_channel::ipc;
 use mime::{self, Mime};
 use msg::constellation_msg::TEST_PIPELINE_ID;
 use net::connector::{create_tls_config, ALPN_H2_H1};
 use net::fetch::cors_cache::CorsCache;
-use net::fetch::methods::{self, CancellationListener, FetchContext};
+use net::fetch::methods::{self, should_request_be_blocked_by_csp};
+use net::fetch::methods::{CancellationListener, FetchContext};
 use net::filemanager_thread::FileManager;
 use net::hsts::HstsEntry;
 use net::test::HttpState;
@@ -33,16 +36,19 @@ use net_traits::request::{
 };
 use net_traits::response::{CacheState, Response, ResponseBody, ResponseType};
 use net_traits::{
-    FetchTaskTarget, IncludeSubdomains, NetworkError, ReferrerPolicy, ResourceFetchTiming,
-    ResourceTimingType,
+    CrossOriginEmbedderPolicy, FetchTaskTarget, IncludeSubdomains, NetworkError, ReferrerPolicy,
+    ResourceFetchTiming, ResourceTimingType,
 };
+use net_traits::{CustomResponse, CustomResponseMediator};
 use servo_arc::Arc as ServoArc;
+use servo_config::set_pref;
 use servo_url::{ImmutableOrigin, ServoUrl};
 use std::fs;
 use std::iter::FromIterator;
 use std::path::Path;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
+use std::thread;
 use std::time::{Duration, SystemTime};
 use uuid::Uuid;
 
@@ -143,6 +149,7 @@ fn test_fetch_blob() {
             assert_eq!(self.buffer, self.expected);
             let _ = self.sender.send(response.clone());
         }
+        fn process_csp_violations(&mut self, _: &Request, _: Vec<csp::Violation>) {}
     }
 
     let context = new_fetch_context(None, None);
@@ -598,6 +605,80 @@ fn test_fetch_response_is_opaque_redirect_filtered() {
     }
 }
 
+fn parse_csp_list(policy: &str, disposition: csp::PolicyDisposition) -> Option<CspList> {
+    Some(CspList::parse(
+        policy,
+        csp::PolicySource::Header,
+        disposition,
+    ))
+}
+
+#[test]
+fn test_fetch_blocked_by_csp() {
+    let handler = move |_: HyperRequest<Body>, response: &mut HyperResponse<Body>| {
+        *response.body_mut() = b"Yay!".to_vec().into();
+    };
+    let (server, url) = make_server(handler);
+
+    let origin = Origin::Origin(url.origin());
+    let mut request = Request::new(url, Some(origin), None);
+    request.destination = Destination::Image;
+    request.csp_list = parse_csp_list("img-src 'none'", csp::PolicyDisposition::Enforce);
+
+    let (check_result, violations) = should_request_be_blocked_by_csp(&request);
+    assert!(check_result == csp::CheckResult::Blocked);
+    assert_eq!(violations.len(), 1);
+    assert_eq!(violations[0].directive.name, "img-src");
+
+    let fetch_response = fetch(&mut request, None);
+    server.close();
+    assert!(fetch_response.is_network_error());
+}
+
+#[test]
+fn test_fetch_allowed_by_report_only_csp() {
+    let handler = move |_: HyperRequest<Body>, response: &mut HyperResponse<Body>| {
+        *response.body_mut() = b"Yay!".to_vec().into();
+    };
+    let (server, url) = make_server(handler);
+
+    let origin = Origin::Origin(url.origin());
+    let mut request = Request::new(url, Some(origin), None);
+    request.csp_list = parse_csp_list("connect-src 'none'", csp::PolicyDisposition::Report);
+
+    let (check_result, violations) = should_request_be_blocked_by_csp(&request);
+    assert!(check_result == csp::CheckResult::Allowed);
+    assert_eq!(violations.len(), 1);
+    assert_eq!(violations[0].directive.name, "connect-src");
+
+    let fetch_response = fetch(&mut request, None);
+    server.close();
+    assert!(!fetch_response.is_network_error());
+    assert_eq!(
+        *fetch_response.body.lock().unwrap(),
+        ResponseBody::Done(b"Yay!".to_vec())
+    );
+}
+
+#[test]
+fn test_frame_src_only_applies_to_nested_navigations() {
+    let url = ServoUrl::parse("https://example.com/framed.html").unwrap();
+    let origin = Origin::Origin(ServoUrl::parse("https://embedder.com").unwrap().origin());
+    let mut request = Request::new(url, Some(origin), None);
+    request.destination = Destination::Document;
+    request.csp_list = parse_csp_list("frame-src 'self'", csp::PolicyDisposition::Enforce);
+
+    request.top_level_navigation = true;
+    let (check_result, violations) = should_request_be_blocked_by_csp(&request);
+    assert!(check_result == csp::CheckResult::Allowed);
+    assert!(violations.is_empty());
+
+    request.top_level_navigation = false;
+    let (check_result, violations) = should_request_be_blocked_by_csp(&request);
+    assert!(check_result == csp::CheckResult::Blocked);
+    assert_eq!(violations[0].directive.name, "frame-src");
+}
+
 #[test]
 fn test_fetch_with_local_urls_only() {
     // If flag `local_urls_only` is set, fetching a non-local URL must result in network error.
@@ -654,10 +735,11 @@ fn test_fetch_with_hsts() {
     let tls_config = create_tls_config(&certs, ALPN_H2_H1);
 
     let mut context = FetchContext {
-        state: Arc::new(HttpState::new(tls_config)),
+        state: Arc::new(HttpState::new(tls_config, create_embedder_proxy())),
         user_agent: DEFAULT_USER_AGENT.into(),
         devtools_chan: None,
         filemanager: FileManager::new(create_embedder_proxy()),
+        swmanager_chan: None,
         cancellation_listener: Arc::new(Mutex::new(CancellationListener::new(None))),
         timing: ServoArc::new(Mutex::new(ResourceFetchTiming::new(
             ResourceTimingType::Navigation,
@@ -683,6 +765,83 @@ fn test_fetch_with_hsts() {
     );
 }
 
+fn fetch_with_service_worker(
+    body: Option<Vec<u8>>,
+    reply: Result<Option<CustomResponse>, NetworkError>,
+) -> (Response, CustomResponseMediator) {
+    set_pref!(dom.serviceworker.enabled, true);
+    let handler = move |_: HyperRequest<Body>, response: &mut HyperResponse<Body>| {
+        *response.body_mut() = b"From the network".to_vec().into();
+    };
+    let (server, url) = make_server(handler);
+
+    let (swmanager_chan, swmanager_port) = ipc::channel().unwrap();
+    let mut context = new_fetch_context(None, None);
+    context.swmanager_chan = Some(swmanager_chan);
+    let intercepted = thread::spawn(move || {
+        let mediator: CustomResponseMediator = swmanager_port.recv().unwrap();
+        mediator.response_chan.send(reply).unwrap();
+        mediator
+    });
+
+    let origin = Origin::Origin(url.origin());
+    let mut request = Request::new(url, Some(origin), Some(TEST_PIPELINE_ID));
+    request.referrer = Referrer::NoReferrer;
+    if body.is_some() {
+        request.method = Method::POST;
+        request.body = body;
+    }
+    let response = fetch_with_context(&mut request, &mut context);
+    server.close();
+    (response, intercepted.join().unwrap())
+}
+
+#[test]
+fn test_fetch_with_service_worker_response() {
+    let custom_response = CustomResponse::new(
+        HeaderMap::new(),
+        (StatusCode::CREATED, "Created".to_owned()),
+        b"From the service worker".to_vec(),
+    );
+    let (response, mediator) = fetch_with_service_worker(None, Ok(Some(custom_response)));
+    let response = response.internal_response.unwrap();
+    assert_eq!(response.url(), Some(&mediator.load_url));
+    assert_eq!(response.status.as_ref().unwrap().0, StatusCode::CREATED);
+    match *response.body.lock().unwrap() {
+        ResponseBody::Done(ref body) => assert_eq!(&**body, b"From the service worker"),
+        _ => panic!("the response body is not complete"),
+    }
+}
+
+#[test]
+fn test_fetch_sends_request_body_to_service_worker() {
+    let (_, mediator) =
+        fetch_with_service_worker(Some(b"To the service worker".to_vec()), Ok(None));
+    assert_eq!(mediator.method, Method::POST);
+    assert_eq!(mediator.body, Some(b"To the service worker".to_vec()));
+}
+
+#[test]
+fn test_fetch_falls_back_to_network_without_service_worker_response() {
+    let (response, _) = fetch_with_service_worker(None, Ok(None));
+    let response = response.internal_response.unwrap();
+    match *response.body.lock().unwrap() {
+        ResponseBody::Done(ref body) => assert_eq!(&**body, b"From the network"),
+        _ => panic!("the response body is not complete"),
+    }
+}
+
+#[test]
+fn test_fetch_fails_when_service_worker_fails_it() {
+    let (response, _) = fetch_with_service_worker(
+        None,
+        Err(NetworkError::Internal(
+            "The respondWith promise was rejected".to_owned(),
+        )),
+    );
+    assert!(response.is_network_error());
+}
+
 #[test]
 fn test_load_adds_host_to_hsts_list_when_url_is_https() {
     let handler = move |_: HyperRequest<Body>, response: &mut HyperResponse<Body>| {
@@ -706,10 +865,11 @@ fn test_load_adds_host_to_hsts_list_when_url_is_https() {
     let tls_config = create_tls_config(&certs, ALPN_H2_H1);
 
     let mut context = FetchContext {
-        state: Arc::new(HttpState::new(tls_config)),
+        state: Arc::new(HttpState::new(tls_config, create_embedder_proxy())),
         user_agent: DEFAULT_USER_AGENT.into(),
         devtools_chan: None,
         filemanager: FileManager::new(create_embedder_proxy()),
+        swmanager_chan: None,
         cancellation_listener: Arc::new(Mutex::new(CancellationListener::new(None))),
         timing: ServoArc::new(Mutex::new(ResourceFetchTiming::new(
             ResourceTimingType::Navigation,
@@ -743,6 +903,73 @@ fn test_load_adds_host_to_hsts_list_when_url_is_https() {
         .is_host_secure(url.host_str().unwrap()));
 }
 
+#[test]
+fn test_fetch_negotiates_http2_and_reuses_the_connection() {
+    let http2_requests = Arc::new(AtomicUsize::new(0));
+    let counter = http2_requests.clone();
+    let handler = move |request: HyperRequest<Body>, response: &mut HyperResponse<Body>| {
+        if request.version() == Version::HTTP_2 {
+            counter.fetch_add(1, Ordering::SeqCst);
+        }
+        *response.body_mut() = b"Yay!".to_vec().into();
+    };
+    let cert_path = Path::new("../../resources/self_signed_certificate_for_testing.crt")
+        .canonicalize()
+        .unwrap();
+    let key_path = Path::new("../../resources/privatekey_for_testing.key")
+        .canonicalize()
+        .unwrap();
+    let (server, mut url) =
+        make_ssl_server_with_http2(handler, cert_path.clone(), key_path.clone(), true);
+    url.as_mut_url().set_scheme("https").unwrap();
+
+    let certs = fs::read_to_string(cert_path).expect("Couldn't find certificate file");
+    let tls_config = create_tls_config(&certs, ALPN_H2_H1);
+
+    let mut context = FetchContext {
+        state: Arc::new(HttpState::new(tls_config, create_embedder_proxy())),
+        user_agent: DEFAULT_USER_AGENT.into(),
+        devtools_chan: None,
+        filemanager: FileManager::new(create_embedder_proxy()),
+        swmanager_chan: None,
+        cancellation_listener: Arc::new(Mutex::new(CancellationListener::new(None))),
+        timing: ServoArc::new(Mutex::new(ResourceFetchTiming::new(
+            ResourceTimingType::Resource,
+        ))),
+    };
+
+    let mut timings = vec![];
+    for _ in 0..2 {
+        context.timing = ServoArc::new(Mutex::new(ResourceFetchTiming::new(
+            ResourceTimingType::Resource,
+        )));
+        let mut request = RequestBuilder::new(url.clone())
+            .method(Method::GET)
+            .body(None)
+            .destination(Destination::Document)
+            .origin(url.clone().origin())
+            .pipeline_id(Some(TEST_PIPELINE_ID))
+            .build();
+        let response = fetch_with_context(&mut request, &mut context);
+        assert!(!response.is_network_error());
+        timings.push(context.timing.lock().unwrap().clone());
+    }
+
+    let _ = server.close();
+
+    assert_eq!(http2_requests.load(Ordering::SeqCst), 2);
+    for timing in &timings {
+        assert_eq!(timing.next_hop_protocol, Some("h2".to_owned()));
+    }
+
+    // The first request sets up the connection, which the second one reuses.
+    assert!(timings[0].connect_start > 0);
+    assert!(timings[0].secure_connection_start >= timings[0].connect_start);
+    assert!(timings[0].connect_end > timings[0].secure_connection_start);
+    assert!(timings[1].connect_start > timings[0].connect_end);
+    assert_eq!(timings[1].connect_start, timings[1].connect_end);
+}
+
 #[test]
 fn test_fetch_with_sri_network_error() {
     static MESSAGE: &'static [u8] = b"alert('Hello, Network Error');";
@@ -836,6 +1063,75 @@ fn test_fetch_blocked_nosniff() {
     }
 }
 
+#[test]
+fn test_fetch_blocked_by_cross_origin_resource_policy() {
+    fn test_corp_request(
+        policy: &'static str,
+        origin: fn(&ServoUrl) -> ImmutableOrigin,
+        should_error: bool,
+    ) {
+        let handler = move |_: HyperRequest<Body>, response: &mut HyperResponse<Body>| {
+            response.headers_mut().insert(
+                HeaderName::from_static("cross-origin-resource-policy"),
+                HeaderValue::from_static(policy),
+            );
+            *response.body_mut() = b"Yay!".to_vec().into();
+        };
+        let (server, url) = make_server(handler);
+
+        let origin = origin(&url);
+        let mut request = Request::new(url, Some(Origin::Origin(origin)), None);
+        request.referrer = Referrer::NoReferrer;
+        let fetch_response = fetch(&mut request, None);
+        let _ = server.close();
+
+        assert_eq!(fetch_response.is_network_error(), should_error);
+    }
+
+    fn same_origin(url: &ServoUrl) -> ImmutableOrigin {
+        url.origin()
+    }
+    fn opaque_origin(_: &ServoUrl) -> ImmutableOrigin {
+        ImmutableOrigin::new_opaque()
+    }
+    fn secure_origin(url: &ServoUrl) -> ImmutableOrigin {
+        ImmutableOrigin::Tuple(
+            "https".into(),
+            url.host().unwrap().to_owned(),
+            url.port().unwrap(),
+        )
+    }
+
+    test_corp_request("same-origin", same_origin, false);
+    test_corp_request("same-origin", opaque_origin, true);
+    test_corp_request("same-origin", secure_origin, true);
+    test_corp_request("same-site", opaque_origin, true);
+    test_corp_request("same-site", secure_origin, false);
+    test_corp_request("cross-origin", opaque_origin, false);
+}
+
+#[test]
+fn test_fetch_blocked_by_embedder_policy() {
+    fn test_coep_request(embedder_policy: CrossOriginEmbedderPolicy, should_error: bool) {
+        let handler = move |_: HyperRequest<Body>, response: &mut HyperResponse<Body>| {
+            *response.body_mut() = b"Yay!".to_vec().into();
+        };
+        let (server, url) = make_server(handler);
+
+        let origin = Origin::Origin(ImmutableOrigin::new_opaque());
+        let mut request = Request::new(url, Some(origin), None);
+        request.referrer = Referrer::NoReferrer;
+        request.embedder_policy = embedder_policy;
+        let fetch_response = fetch(&mut request, None);
+        let _ = server.close();
+
+        assert_eq!(fetch_response.is_network_error(), should_error);
+    }
+
+    test_coep_request(CrossOriginEmbedderPolicy::UnsafeNone, false);
+    test_coep_request(CrossOriginEmbedderPolicy::RequireCorp, true);
+}
+
 fn setup_server_and_fetch(message: &'static [u8], redirect_cap: u32) -> Response {
     let handler = move |request: HyperRequest<Body>, response: &mut HyperResponse<Body>| {
         let redirects = request
@@ -66,6 +66,7 @@ fn test_base_domain_in_entries_map() {
 
     let mut list = HstsList {
         entries_map: entries_map,
+        journal: None,
     };
 
     list.push(
@@ -111,6 +112,7 @@ fn test_push_entry_with_0_max_age_evicts_entry_from_list() {
     );
     let mut list = HstsList {
         entries_map: entries_map,
+        journal: None,
     };
 
     list.push(
@@ -134,6 +136,7 @@ fn test_push_entry_to_hsts_list_should_not_add_subdomains_whose_superdomain_is_a
     );
     let mut list = HstsList {
         entries_map: entries_map,
+        journal: None,
     };
 
     list.push(
@@ -157,6 +160,7 @@ fn test_push_entry_to_hsts_list_should_update_existing_domain_entrys_include_sub
     );
     let mut list = HstsList {
         entries_map: entries_map,
+        journal: None,
     };
 
     assert!(list.is_host_secure("servo.mozilla.org"));
@@ -187,6 +191,7 @@ fn test_push_entry_to_hsts_list_should_not_create_duplicate_entry() {
     );
     let mut list = HstsList {
         entries_map: entries_map,
+        journal: None,
     };
 
     list.push(
@@ -205,6 +210,7 @@ fn test_push_entry_to_hsts_list_should_not_create_duplicate_entry() {
 fn test_push_multiple_entrie_to_hsts_list_should_add_them_all() {
     let mut list = HstsList {
         entries_map: HashMap::new(),
+        journal: None,
     };
 
     assert!(!list.is_host_secure("mozilla.org"));
@@ -223,6 +229,7 @@ fn test_push_multiple_entrie_to_hsts_list_should_add_them_all() {
 fn test_push_entry_to_hsts_list_should_add_an_entry() {
     let mut list = HstsList {
         entries_map: HashMap::new(),
+        journal: None,
     };
 
     assert!(!list.is_host_secure("mozilla.org"));
@@ -272,6 +279,7 @@ fn test_parse_hsts_preload_should_decode_host_and_includes_subdomains() {
 fn test_hsts_list_with_no_entries_map_does_not_is_host_secure() {
     let hsts_list = HstsList {
         entries_map: HashMap::new(),
+        journal: None,
     };
 
     assert!(!hsts_list.is_host_secure("mozilla.org"));
@@ -292,6 +300,7 @@ fn test_hsts_list_with_exact_domain_entry_is_is_host_secure() {
 
     let hsts_list = HstsList {
         entries_map: entries_map,
+        journal: None,
     };
 
     assert!(hsts_list.is_host_secure("mozilla.org"));
@@ -306,6 +315,7 @@ fn test_hsts_list_with_subdomain_when_include_subdomains_is_true_is_is_host_secu
     );
     let hsts_list = HstsList {
         entries_map: entries_map,
+        journal: None,
     };
 
     assert!(hsts_list.is_host_secure("servo.mozilla.org"));
@@ -325,6 +335,7 @@ fn test_hsts_list_with_subdomain_when_include_subdomains_is_false_is_not_is_host
     );
     let hsts_list = HstsList {
         entries_map: entries_map,
+        journal: None,
     };
 
     assert!(!hsts_list.is_host_secure("servo.mozilla.org"));
@@ -339,6 +350,7 @@ fn test_hsts_list_with_subdomain_when_host_is_not_a_subdomain_is_not_is_host_sec
     );
     let hsts_list = HstsList {
         entries_map: entries_map,
+        journal: None,
     };
 
     assert!(!hsts_list.is_host_secure("servo-mozilla.org"));
@@ -353,6 +365,7 @@ fn test_hsts_list_with_subdomain_when_host_is_exact_match_is_is_host_secure() {
     );
     let hsts_list = HstsList {
         entries_map: entries_map,
+        journal: None,
     };
 
     assert!(hsts_list.is_host_secure("mozilla.org"));
@@ -372,6 +385,7 @@ fn test_hsts_list_with_expired_entry_is_not_is_host_secure() {
     );
     let hsts_list = HstsList {
         entries_map: entries_map,
+        journal: None,
     };
 
     assert!(!hsts_list.is_host_secure("mozilla.org"));
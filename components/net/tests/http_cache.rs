@@ -11,6 +11,46 @@ use net_traits::request::{Origin, Request};
 use net_traits::response::{Response, ResponseBody};
 use net_traits::{ResourceFetchTiming, ResourceTimingType};
 use servo_url::ServoUrl;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use std::thread;
+use std::time::Duration;
+use uuid::Uuid;
+
+fn temporary_config_dir() -> PathBuf {
+    let config_dir = env::temp_dir().join(format!("servo-http-cache-{}", Uuid::new_v4()));
+    fs::create_dir_all(&config_dir).unwrap();
+    config_dir
+}
+
+fn store_complete_response(cache: &RwLock<HttpCache>, request: &Request, body: &[u8]) {
+    let timing = ResourceFetchTiming::new(ResourceTimingType::Navigation);
+    let mut response = Response::new(request.url(), timing);
+    // Expires header makes the response cacheable.
+    response
+        .headers
+        .insert(EXPIRES, HeaderValue::from_str("-10").unwrap());
+    *response.body.lock().unwrap() = ResponseBody::Done(body.to_vec());
+    HttpCache::load_from_disk(cache, request);
+    let mut cache = cache.write().unwrap();
+    cache.store(request, &response);
+    cache.persist(request);
+}
+
+fn open_persistent_cache(config_dir: &Path, max_disk_size: u64) -> RwLock<HttpCache> {
+    RwLock::new(HttpCache::new_persistent(config_dir, max_disk_size))
+}
+
+fn has_cached_response(cache: &RwLock<HttpCache>, request: &Request) -> bool {
+    HttpCache::load_from_disk(cache, request);
+    cache
+        .read()
+        .unwrap()
+        .construct_response(request, &mut None)
+        .is_some()
+}
 
 #[test]
 fn test_refreshing_resource_sets_done_chan_the_appropriate_value() {
@@ -48,3 +88,120 @@ fn test_refreshing_resource_sets_done_chan_the_appropriate_value() {
         }
     })
 }
+
+#[test]
+fn test_persistent_cache_constructs_response_after_restart() {
+    let config_dir = temporary_config_dir();
+    let url = ServoUrl::parse("https://servo.org").unwrap();
+    let request = Request::new(
+        url.clone(),
+        Some(Origin::Origin(url.clone().origin())),
+        Some(TEST_PIPELINE_ID),
+    );
+    {
+        let cache = open_persistent_cache(&config_dir, 1024 * 1024);
+        store_complete_response(&cache, &request, b"persisted body");
+        cache.read().unwrap().flush();
+    }
+
+    let cache = open_persistent_cache(&config_dir, 1024 * 1024);
+    HttpCache::load_from_disk(&cache, &request);
+    let mut done_chan = None;
+    let cached_response = cache
+        .read()
+        .unwrap()
+        .construct_response(&request, &mut done_chan)
+        .expect("resource to be loaded from disk");
+    assert!(done_chan.is_none());
+    assert_eq!(
+        *cached_response.response.body.lock().unwrap(),
+        ResponseBody::Done(b"persisted body".to_vec())
+    );
+    let _ = fs::remove_dir_all(&config_dir);
+}
+
+#[test]
+fn test_persistent_cache_evicts_least_recently_used_entries() {
+    let config_dir = temporary_config_dir();
+    let first_url = ServoUrl::parse("https://servo.org/first").unwrap();
+    let second_url = ServoUrl::parse("https://servo.org/second").unwrap();
+    let origin = Origin::Origin(first_url.origin());
+    let first_request = Request::new(first_url, Some(origin.clone()), Some(TEST_PIPELINE_ID));
+    let second_request = Request::new(second_url, Some(origin), Some(TEST_PIPELINE_ID));
+    let body = vec![0; 600];
+    {
+        // Room for only one of the two entries.
+        let cache = open_persistent_cache(&config_dir, 1024);
+        store_complete_response(&cache, &first_request, &body);
+        store_complete_response(&cache, &second_request, &body);
+    }
+
+    let cache = open_persistent_cache(&config_dir, 1024);
+    assert!(!has_cached_response(&cache, &first_request));
+    assert!(has_cached_response(&cache, &second_request));
+    let _ = fs::remove_dir_all(&config_dir);
+}
+
+#[test]
+fn test_persistent_cache_discards_corrupted_entries() {
+    let config_dir = temporary_config_dir();
+    let url = ServoUrl::parse("https://servo.org").unwrap();
+    let request = Request::new(
+        url.clone(),
+        Some(Origin::Origin(url.clone().origin())),
+        Some(TEST_PIPELINE_ID),
+    );
+    {
+        let cache = open_persistent_cache(&config_dir, 1024 * 1024);
+        store_complete_response(&cache, &request, b"persisted body");
+    }
+    let disk_cache_dir = config_dir.join("http_cache");
+    // Entries are grouped in a directory per origin.
+    let group_directories = || {
+        fs::read_dir(&disk_cache_dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.is_dir())
+            .collect::<Vec<_>>()
+    };
+    for group in group_directories() {
+        for entry in fs::read_dir(group).unwrap() {
+            fs::write(entry.unwrap().path(), b"garbage").unwrap();
+        }
+    }
+
+    {
+        let cache = open_persistent_cache(&config_dir, 1024 * 1024);
+        assert!(!has_cached_response(&cache, &request));
+    }
+    assert!(group_directories().is_empty());
+    let _ = fs::remove_dir_all(&config_dir);
+}
+
+#[test]
+fn test_persistent_cache_records_accesses_without_rewriting_the_index_on_every_hit() {
+    let config_dir = temporary_config_dir();
+    let url = ServoUrl::parse("https://servo.org").unwrap();
+    let request = Request::new(
+        url.clone(),
+        Some(Origin::Origin(url.clone().origin())),
+        Some(TEST_PIPELINE_ID),
+    );
+    {
+        let cache = open_persistent_cache(&config_dir, 1024 * 1024);
+        store_complete_response(&cache, &request, b"persisted body");
+        cache.read().unwrap().flush();
+    }
+    let index_path = config_dir.join("http_cache").join("index.json");
+    let stored_index = fs::read(&index_path).unwrap();
+
+    // Make sure the access is recorded with a later time than the store.
+    thread::sleep(Duration::from_millis(10));
+    let cache = open_persistent_cache(&config_dir, 1024 * 1024);
+    assert!(has_cached_response(&cache, &request));
+    assert_eq!(fs::read(&index_path).unwrap(), stored_index);
+
+    cache.read().unwrap().flush();
+    assert_ne!(fs::read(&index_path).unwrap(), stored_index);
+    let _ = fs::remove_dir_all(&config_dir);
+}
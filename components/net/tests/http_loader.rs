@@ -6,11 +6,13 @@ use crate::fetch;
 use crate::fetch_with_context;
 use crate::make_server;
 use crate::new_fetch_context;
+use crate::{answer_credentials_prompts, create_embedder_proxy_and_receiver};
 use cookie_rs::Cookie as CookiePair;
 use crossbeam_channel::{unbounded, Receiver};
 use devtools_traits::HttpRequest as DevtoolsHttpRequest;
 use devtools_traits::HttpResponse as DevtoolsHttpResponse;
 use devtools_traits::{ChromeToDevtoolsControlMsg, DevtoolsControlMsg, NetworkEvent};
+use embedder_traits::{EmbedderMsg, PromptCredentials, PromptDefinition};
 use flate2::write::{DeflateEncoder, GzEncoder};
 use flate2::Compression;
 use futures::{self, Future, Stream};
@@ -27,18 +29,21 @@ use hyper::{Request as HyperRequest, Response as HyperResponse};
 use msg::constellation_msg::TEST_PIPELINE_ID;
 use net::cookie::Cookie;
 use net::cookie_storage::CookieStorage;
+use net::http_auth::{parse_challenges, preferred_challenge, Challenge};
+use net::http_auth::{DigestAlgorithm, DigestChallenge};
 use net::http_loader::determine_request_referrer;
 use net::resource_thread::AuthCacheEntry;
 use net::test::replace_host_table;
-use net_traits::request::{CredentialsMode, Destination, RequestBuilder, RequestMode};
-use net_traits::response::ResponseBody;
+use net_traits::request::{CredentialsMode, Destination, Request, RequestBuilder, RequestMode};
+use net_traits::response::{ResponseBody, ResponseType};
 use net_traits::{CookieSource, NetworkError, ReferrerPolicy};
 use servo_url::{ImmutableOrigin, ServoUrl};
 use std::collections::HashMap;
 use std::io::Write;
 use std::str;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex, RwLock};
+use std::thread;
 use std::time::Duration;
 
 fn mock_origin() -> ImmutableOrigin {
@@ -604,6 +609,7 @@ fn test_load_sets_cookies_in_the_resource_manager_when_it_get_set_cookie_header_
         .method(Method::GET)
         .body(None)
         .destination(Destination::Document)
+        .top_level_navigation(true)
         .origin(mock_origin())
         .pipeline_id(Some(TEST_PIPELINE_ID))
         .credentials_mode(CredentialsMode::Include)
@@ -656,6 +662,7 @@ fn test_load_sets_requests_cookies_header_for_url_by_getting_cookies_from_the_re
         .method(Method::GET)
         .body(None)
         .destination(Destination::Document)
+        .top_level_navigation(true)
         .origin(mock_origin())
         .pipeline_id(Some(TEST_PIPELINE_ID))
         .credentials_mode(CredentialsMode::Include)
@@ -702,6 +709,7 @@ fn test_load_sends_cookie_if_nonhttp() {
         .method(Method::GET)
         .body(None)
         .destination(Destination::Document)
+        .top_level_navigation(true)
         .origin(mock_origin())
         .pipeline_id(Some(TEST_PIPELINE_ID))
         .credentials_mode(CredentialsMode::Include)
@@ -720,6 +728,112 @@ fn test_load_sends_cookie_if_nonhttp() {
         .is_success());
 }
 
+#[test]
+fn test_lax_cookies_are_only_sent_cross_site_with_top_level_navigations() {
+    let handler = move |request: HyperRequest<Body>, response: &mut HyperResponse<Body>| {
+        let cookies = request
+            .headers()
+            .get(header::COOKIE)
+            .map_or(vec![], |cookies| cookies.as_bytes().to_vec());
+        *response.body_mut() = cookies.into();
+    };
+    let (server, url) = make_server(handler);
+
+    let mut context = new_fetch_context(None, None);
+
+    {
+        let mut cookie_jar = context.state.cookie_jar.write().unwrap();
+        for cookie_str in &["lax=1", "strict=1; SameSite=Strict"] {
+            let cookie =
+                Cookie::from_cookie_string(cookie_str.to_string(), &url, CookieSource::HTTP)
+                    .unwrap();
+            cookie_jar.push(cookie, &url, CookieSource::HTTP);
+        }
+    }
+
+    let mut fetch_cookies = |destination, top_level_navigation| {
+        let mut request = RequestBuilder::new(url.clone())
+            .method(Method::GET)
+            .destination(destination)
+            .top_level_navigation(top_level_navigation)
+            .origin(mock_origin())
+            .pipeline_id(Some(TEST_PIPELINE_ID))
+            .credentials_mode(CredentialsMode::Include)
+            .build();
+        let response = fetch_with_context(&mut request, &mut context);
+        let body = response
+            .internal_response
+            .unwrap()
+            .body
+            .lock()
+            .unwrap()
+            .clone();
+        body
+    };
+
+    let subresource_cookies = fetch_cookies(Destination::Image, false);
+    let navigation_cookies = fetch_cookies(Destination::Document, true);
+    let _ = server.close();
+
+    assert_eq!(subresource_cookies, ResponseBody::Done(vec![]));
+    assert_eq!(navigation_cookies, ResponseBody::Done(b"lax=1".to_vec()));
+}
+
+#[test]
+fn test_navigations_initiated_cross_site_are_not_opaque_and_only_send_lax_cookies() {
+    let handler = move |request: HyperRequest<Body>, response: &mut HyperResponse<Body>| {
+        let cookies = request
+            .headers()
+            .get(header::COOKIE)
+            .map_or(vec![], |cookies| cookies.as_bytes().to_vec());
+        *response.body_mut() = cookies.into();
+    };
+    let (server, url) = make_server(handler);
+
+    let mut context = new_fetch_context(None, None);
+
+    {
+        let mut cookie_jar = context.state.cookie_jar.write().unwrap();
+        for cookie_str in &["lax=1", "strict=1; SameSite=Strict"] {
+            let cookie =
+                Cookie::from_cookie_string(cookie_str.to_string(), &url, CookieSource::HTTP)
+                    .unwrap();
+            cookie_jar.push(cookie, &url, CookieSource::HTTP);
+        }
+    }
+
+    // A navigation request carries the origin of the document that initiated it,
+    // which is cross-site here, so it has to be in navigate mode for the response
+    // not to be tainted as opaque.
+    let mut navigate = |top_level_navigation| {
+        let mut request = RequestBuilder::new(url.clone())
+            .method(Method::GET)
+            .destination(Destination::Document)
+            .mode(RequestMode::Navigate)
+            .top_level_navigation(top_level_navigation)
+            .origin(mock_origin())
+            .pipeline_id(Some(TEST_PIPELINE_ID))
+            .credentials_mode(CredentialsMode::Include)
+            .build();
+        fetch_with_context(&mut request, &mut context)
+    };
+
+    let top_level_response = navigate(true);
+    let nested_response = navigate(false);
+    let _ = server.close();
+
+    assert_eq!(top_level_response.response_type, ResponseType::Basic);
+    assert_eq!(
+        *top_level_response.body.lock().unwrap(),
+        ResponseBody::Done(b"lax=1".to_vec())
+    );
+    assert_eq!(nested_response.response_type, ResponseType::Basic);
+    assert_eq!(
+        *nested_response.body.lock().unwrap(),
+        ResponseBody::Done(vec![])
+    );
+}
+
 #[test]
 fn test_cookie_set_with_httponly_should_not_be_available_using_getcookiesforurl() {
     let handler = move |_: HyperRequest<Body>, response: &mut HyperResponse<Body>| {
@@ -739,6 +853,7 @@ fn test_cookie_set_with_httponly_should_not_be_available_using_getcookiesforurl(
         .method(Method::GET)
         .body(None)
         .destination(Destination::Document)
+        .top_level_navigation(true)
         .origin(mock_origin())
         .pipeline_id(Some(TEST_PIPELINE_ID))
         .credentials_mode(CredentialsMode::Include)
@@ -786,6 +901,7 @@ fn test_when_cookie_received_marked_secure_is_ignored_for_http() {
         .method(Method::GET)
         .body(None)
         .destination(Destination::Document)
+        .top_level_navigation(true)
         .origin(mock_origin())
         .pipeline_id(Some(TEST_PIPELINE_ID))
         .credentials_mode(CredentialsMode::Include)
@@ -1204,6 +1320,7 @@ fn test_redirect_from_x_to_y_provides_y_cookies_from_y() {
     let mut request = RequestBuilder::new(url_x.clone())
         .method(Method::GET)
         .destination(Destination::Document)
+        .top_level_navigation(true)
         .origin(mock_origin())
         .pipeline_id(Some(TEST_PIPELINE_ID))
         .credentials_mode(CredentialsMode::Include)
@@ -1253,6 +1370,7 @@ fn test_redirect_from_x_to_x_provides_x_with_cookie_from_first_response() {
     let mut request = RequestBuilder::new(url.clone())
         .method(Method::GET)
         .destination(Destination::Document)
+        .top_level_navigation(true)
         .origin(mock_origin())
         .pipeline_id(Some(TEST_PIPELINE_ID))
         .credentials_mode(CredentialsMode::Include)
@@ -1344,6 +1462,279 @@ fn test_auth_ui_needs_www_auth() {
     );
 }
 
+fn auth_request(url: &ServoUrl) -> Request {
+    RequestBuilder::new(url.clone())
+        .method(Method::GET)
+        .body(None)
+        .destination(Destination::Document)
+        .origin(mock_origin())
+        .pipeline_id(Some(TEST_PIPELINE_ID))
+        .credentials_mode(CredentialsMode::Include)
+        .build()
+}
+
+fn prompt_credentials(user_name: &str, password: &str) -> Option<PromptCredentials> {
+    Some(PromptCredentials {
+        user_name: user_name.to_owned(),
+        password: password.to_owned(),
+    })
+}
+
+#[test]
+fn test_auth_prompt_credentials_are_used_and_cached() {
+    let handler = move |request: HyperRequest<Body>, response: &mut HyperResponse<Body>| {
+        let expected = Authorization::basic("username", "test");
+        if request.headers().typed_get::<Authorization<Basic>>() != Some(expected) {
+            *response.status_mut() = StatusCode::UNAUTHORIZED;
+            response.headers_mut().insert(
+                header::WWW_AUTHENTICATE,
+                HeaderValue::from_static("Basic realm=\"servo\""),
+            );
+        }
+    };
+    let (server, url) = make_server(handler);
+
+    let (embedder_proxy, embedder_receiver) = create_embedder_proxy_and_receiver();
+    let prompts =
+        answer_credentials_prompts(embedder_receiver, prompt_credentials("username", "test"));
+    let mut context = new_fetch_context(None, Some(embedder_proxy));
+
+    let response = fetch_with_context(&mut auth_request(&url), &mut context);
+
+    let _ = server.close();
+
+    assert!(response
+        .internal_response
+        .unwrap()
+        .status
+        .unwrap()
+        .0
+        .is_success());
+    assert_eq!(prompts.load(Ordering::SeqCst), 1);
+    let auth_cache = context.state.auth_cache.read().unwrap();
+    let auth_entry = &auth_cache.entries[&url.origin().ascii_serialization()];
+    assert_eq!(auth_entry.user_name, "username");
+    assert_eq!(auth_entry.password, "test");
+}
+
+#[test]
+fn test_auth_prompt_declined_returns_the_challenge() {
+    let handler = move |_: HyperRequest<Body>, response: &mut HyperResponse<Body>| {
+        *response.status_mut() = StatusCode::UNAUTHORIZED;
+        response.headers_mut().insert(
+            header::WWW_AUTHENTICATE,
+            HeaderValue::from_static("Basic realm=\"servo\""),
+        );
+    };
+    let (server, url) = make_server(handler);
+
+    let (embedder_proxy, embedder_receiver) = create_embedder_proxy_and_receiver();
+    let prompts = answer_credentials_prompts(embedder_receiver, None);
+    let mut context = new_fetch_context(None, Some(embedder_proxy));
+
+    let response = fetch_with_context(&mut auth_request(&url), &mut context);
+
+    let _ = server.close();
+
+    assert_eq!(
+        response.internal_response.unwrap().status.unwrap().0,
+        StatusCode::UNAUTHORIZED
+    );
+    assert_eq!(prompts.load(Ordering::SeqCst), 1);
+    assert!(context.state.auth_cache.read().unwrap().entries.is_empty());
+}
+
+#[test]
+fn test_rejected_auth_prompt_credentials_are_not_cached() {
+    let handler = move |request: HyperRequest<Body>, response: &mut HyperResponse<Body>| {
+        let expected = Authorization::basic("username", "test");
+        if request.headers().typed_get::<Authorization<Basic>>() != Some(expected) {
+            *response.status_mut() = StatusCode::UNAUTHORIZED;
+            response.headers_mut().insert(
+                header::WWW_AUTHENTICATE,
+                HeaderValue::from_static("Basic realm=\"servo\""),
+            );
+        }
+    };
+    let (server, url) = make_server(handler);
+
+    // The first prompt is answered with the wrong password, and the second one is declined.
+    let (embedder_proxy, embedder_receiver) = create_embedder_proxy_and_receiver();
+    let prompts = Arc::new(AtomicUsize::new(0));
+    let counter = prompts.clone();
+    thread::spawn(move || {
+        while let Ok((_, msg)) = embedder_receiver.recv() {
+            if let EmbedderMsg::Prompt(PromptDefinition::Credentials(_, sender), _) = msg {
+                let credentials = match counter.fetch_add(1, Ordering::SeqCst) {
+                    0 => prompt_credentials("username", "wrong"),
+                    _ => None,
+                };
+                sender.send(credentials).unwrap();
+            }
+        }
+    });
+    let mut context = new_fetch_context(None, Some(embedder_proxy));
+
+    let response = fetch_with_context(&mut auth_request(&url), &mut context);
+
+    let _ = server.close();
+
+    assert_eq!(
+        response.internal_response.unwrap().status.unwrap().0,
+        StatusCode::UNAUTHORIZED
+    );
+    assert_eq!(prompts.load(Ordering::SeqCst), 2);
+    assert!(context.state.auth_cache.read().unwrap().entries.is_empty());
+}
+
+#[test]
+fn test_auth_prompt_answers_digest_challenge() {
+    let handler = move |request: HyperRequest<Body>, response: &mut HyperResponse<Body>| {
+        let authorization = request
+            .headers()
+            .get(header::AUTHORIZATION)
+            .map(|value| value.to_str().unwrap().to_owned());
+        match authorization {
+            Some(authorization) => {
+                assert!(authorization.starts_with(
+                    "Digest username=\"Mufasa\", realm=\"testrealm@host.com\", \
+                     nonce=\"dcd98b7102dd2f0e8b11d0f600bfb0c093\", uri=\"/\", algorithm=MD5, "
+                ));
+                assert!(authorization.contains(", qop=auth, nc=00000001, cnonce=\""));
+                assert!(authorization.ends_with(", opaque=\"5ccc069c403ebaf9f0171e9517f40e41\""));
+            },
+            None => {
+                *response.status_mut() = StatusCode::UNAUTHORIZED;
+                response.headers_mut().insert(
+                    header::WWW_AUTHENTICATE,
+                    HeaderValue::from_static(
+                        "Digest realm=\"testrealm@host.com\", qop=\"auth,auth-int\", \
+                         nonce=\"dcd98b7102dd2f0e8b11d0f600bfb0c093\", \
+                         opaque=\"5ccc069c403ebaf9f0171e9517f40e41\"",
+                    ),
+                );
+            },
+        }
+    };
+    let (server, url) = make_server(handler);
+
+    let (embedder_proxy, embedder_receiver) = create_embedder_proxy_and_receiver();
+    let prompts = answer_credentials_prompts(
+        embedder_receiver,
+        prompt_credentials("Mufasa", "Circle Of Life"),
+    );
+    let mut context = new_fetch_context(None, Some(embedder_proxy));
+
+    let response = fetch_with_context(&mut auth_request(&url), &mut context);
+
+    let _ = server.close();
+
+    assert!(response
+        .internal_response
+        .unwrap()
+        .status
+        .unwrap()
+        .0
+        .is_success());
+    assert_eq!(prompts.load(Ordering::SeqCst), 1);
+    let auth_cache = context.state.auth_cache.read().unwrap();
+    assert!(auth_cache
+        .digest_challenges
+        .contains_key(&url.origin().ascii_serialization()));
+}
+
+#[test]
+fn test_parse_authentication_challenges() {
+    let mut headers = HeaderMap::new();
+    headers.append(
+        header::WWW_AUTHENTICATE,
+        HeaderValue::from_static("Negotiate abc==, Basic realm=\"simple, \\\"quoted\\\"\""),
+    );
+    headers.append(
+        header::WWW_AUTHENTICATE,
+        HeaderValue::from_static("Digest realm=\"digest\", nonce=\"xyz\", algorithm=SHA-512-256"),
+    );
+    headers.append(
+        header::WWW_AUTHENTICATE,
+        HeaderValue::from_static("Digest realm=\"digest\", nonce=\"xyz\", stale=TRUE"),
+    );
+    let challenges = parse_challenges(&headers, header::WWW_AUTHENTICATE);
+    assert_eq!(
+        challenges,
+        vec![
+            Challenge::Basic {
+                realm: Some("simple, \"quoted\"".to_owned()),
+            },
+            Challenge::Digest {
+                challenge: DigestChallenge {
+                    realm: "digest".to_owned(),
+                    nonce: "xyz".to_owned(),
+                    opaque: None,
+                    algorithm: DigestAlgorithm::Md5,
+                    qop_auth: false,
+                    nonce_count: 0,
+                },
+                stale: true,
+            },
+        ]
+    );
+    assert_eq!(
+        preferred_challenge(&headers, header::WWW_AUTHENTICATE),
+        challenges.into_iter().nth(1)
+    );
+}
+
+#[test]
+fn test_digest_authorization_matches_rfc_7616_example() {
+    let mut headers = HeaderMap::new();
+    for algorithm in &["SHA-256", "MD5"] {
+        let challenge = format!(
+            "Digest realm=\"http-auth@example.org\", qop=\"auth, auth-int\", algorithm={}, \
+             nonce=\"7ypf/xlj9XXwfDPEoM4URrv/xwf94BcCAzFZH4GiTo0v\", \
+             opaque=\"FQhe/qaU925kfnzjCev0ciny7QMkPqMAFRtzCUYo5tdS\"",
+            algorithm
+        );
+        headers.append(
+            header::WWW_AUTHENTICATE,
+            HeaderValue::from_str(&challenge).unwrap(),
+        );
+    }
+    let credentials = AuthCacheEntry {
+        user_name: "Mufasa".to_owned(),
+        password: "Circle of Life".to_owned(),
+    };
+    let expected_responses = [
+        "753927fa0e85d155564e2e272a28d1802ca10daf4496794697cf8db5856cb6c1",
+        "8ca523f5e9506fed4657c9700eebdbec",
+    ];
+
+    let challenges = parse_challenges(&headers, header::WWW_AUTHENTICATE);
+    assert_eq!(challenges.len(), 2);
+    assert_eq!(
+        preferred_challenge(&headers, header::WWW_AUTHENTICATE).as_ref(),
+        challenges.first()
+    );
+    for (challenge, expected_response) in challenges.into_iter().zip(&expected_responses) {
+        let mut challenge = match challenge {
+            Challenge::Digest { challenge, .. } => challenge,
+            Challenge::Basic { .. } => panic!("expected a Digest challenge"),
+        };
+        challenge.nonce_count = 1;
+        let authorization = challenge
+            .authorization(
+                &credentials,
+                &Method::GET,
+                "/dir/index.html",
+                "f2/wE4q74E6zIJEtWaHKaf5wv/H5QzzpXusqGemxURZJ",
+            )
+            .unwrap();
+        assert!(authorization
+            .to_str()
+            .unwrap()
+            .contains(&format!("response=\"{}\"", expected_response)));
+    }
+}
+
 #[test]
 fn test_origin_set() {
     let origin_header = Arc::new(Mutex::new(None));
@@ -0,0 +1,344 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use ipc_channel::ipc::{self, IpcReceiver, IpcSender};
+use net::indexeddb_thread::IndexedDBThreadFactory;
+use net_traits::indexeddb_thread::{AsyncOperation, BackendError, ConnectionEvent, ConnectionId};
+use net_traits::indexeddb_thread::{IndexedDBKeyRange, IndexedDBKeyType, IndexedDBThreadMsg};
+use net_traits::indexeddb_thread::{
+    IndexedDBTxnMode, OperationResult, RecordSource, UPGRADE_TRANSACTION_ID,
+};
+use servo_url::{ImmutableOrigin, ServoUrl};
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use uuid::Uuid;
+
+fn origin() -> ImmutableOrigin {
+    ServoUrl::parse("https://example.com").unwrap().origin()
+}
+
+fn open(
+    backend: &IpcSender<IndexedDBThreadMsg>,
+    name: &str,
+    version: Option<u64>,
+) -> IpcReceiver<ConnectionEvent> {
+    let (events, receiver) = ipc::channel().unwrap();
+    backend
+        .send(IndexedDBThreadMsg::Open {
+            origin: origin(),
+            name: name.to_owned(),
+            version,
+            events,
+        })
+        .unwrap();
+    receiver
+}
+
+fn expect_result(events: &IpcReceiver<ConnectionEvent>, id: u64) -> OperationResult {
+    match events.recv().unwrap() {
+        ConnectionEvent::RequestResult {
+            request,
+            result: Ok(result),
+            ..
+        } if request == id => result,
+        event => panic!("Unexpected event: {:?}", event),
+    }
+}
+
+fn expect_finished(events: &IpcReceiver<ConnectionEvent>, expected: Result<(), BackendError>) {
+    match events.recv().unwrap() {
+        ConnectionEvent::TransactionFinished { result, .. } => assert_eq!(result, expected),
+        event => panic!("Unexpected event: {:?}", event),
+    }
+}
+
+/// Create a database at version 1, with an object store named "store" that has out-of-line keys.
+fn create_database(
+    backend: &IpcSender<IndexedDBThreadMsg>,
+    name: &str,
+) -> (ConnectionId, IpcReceiver<ConnectionEvent>) {
+    let events = open(backend, name, Some(1));
+    let connection = match events.recv().unwrap() {
+        ConnectionEvent::UpgradeNeeded {
+            connection,
+            old_version: 0,
+            ..
+        } => connection,
+        event => panic!("Unexpected event: {:?}", event),
+    };
+    backend
+        .send(IndexedDBThreadMsg::Request {
+            connection,
+            transaction: UPGRADE_TRANSACTION_ID,
+            request: 0,
+            operation: AsyncOperation::CreateObjectStore {
+                name: "store".to_owned(),
+                key_path: None,
+                auto_increment: false,
+            },
+        })
+        .unwrap();
+    backend
+        .send(IndexedDBThreadMsg::Commit {
+            connection,
+            transaction: UPGRADE_TRANSACTION_ID,
+        })
+        .unwrap();
+    expect_result(&events, 0);
+    expect_finished(&events, Ok(()));
+    match events.recv().unwrap() {
+        ConnectionEvent::Opened { metadata, .. } => {
+            assert_eq!(metadata.version, 1);
+            assert!(metadata.object_stores.contains_key("store"));
+        },
+        event => panic!("Unexpected event: {:?}", event),
+    }
+    (connection, events)
+}
+
+fn put(key: f64, value: Vec<u8>) -> AsyncOperation {
+    AsyncOperation::Put {
+        store: "store".to_owned(),
+        key: Some(IndexedDBKeyType::Number(key)),
+        value,
+        index_keys: BTreeMap::new(),
+        no_overwrite: true,
+    }
+}
+
+fn begin_readwrite(backend: &IpcSender<IndexedDBThreadMsg>, connection: ConnectionId) {
+    backend
+        .send(IndexedDBThreadMsg::CreateTransaction {
+            connection,
+            transaction: 1,
+            scope: vec!["store".to_owned()],
+            mode: IndexedDBTxnMode::Readwrite,
+        })
+        .unwrap();
+}
+
+fn request(
+    backend: &IpcSender<IndexedDBThreadMsg>,
+    connection: ConnectionId,
+    request: u64,
+    operation: AsyncOperation,
+) {
+    backend
+        .send(IndexedDBThreadMsg::Request {
+            connection,
+            transaction: 1,
+            request,
+            operation,
+        })
+        .unwrap();
+}
+
+#[test]
+fn test_put_then_get() {
+    let backend: IpcSender<IndexedDBThreadMsg> = IndexedDBThreadFactory::new(None, None);
+    let (connection, events) = create_database(&backend, "put_then_get");
+
+    begin_readwrite(&backend, connection);
+    request(&backend, connection, 0, put(1., vec![1, 2, 3]));
+    request(
+        &backend,
+        connection,
+        1,
+        AsyncOperation::Get {
+            source: RecordSource::ObjectStore("store".to_owned()),
+            query: IndexedDBKeyRange::only(IndexedDBKeyType::Number(1.)),
+            keys_only: false,
+        },
+    );
+    backend
+        .send(IndexedDBThreadMsg::Commit {
+            connection,
+            transaction: 1,
+        })
+        .unwrap();
+
+    match expect_result(&events, 0) {
+        OperationResult::Key(key) => assert_eq!(key, IndexedDBKeyType::Number(1.)),
+        result => panic!("Unexpected result: {:?}", result),
+    }
+    match expect_result(&events, 1) {
+        OperationResult::Records(records) => {
+            assert_eq!(records.len(), 1);
+            assert_eq!(records[0].value, Some(vec![1, 2, 3]));
+        },
+        result => panic!("Unexpected result: {:?}", result),
+    }
+    expect_finished(&events, Ok(()));
+}
+
+#[test]
+fn test_add_with_existing_key_fails() {
+    let backend: IpcSender<IndexedDBThreadMsg> = IndexedDBThreadFactory::new(None, None);
+    let (connection, events) = create_database(&backend, "add_existing_key");
+
+    begin_readwrite(&backend, connection);
+    request(&backend, connection, 0, put(1., vec![1]));
+    request(&backend, connection, 1, put(1., vec![2]));
+    expect_result(&events, 0);
+    match events.recv().unwrap() {
+        ConnectionEvent::RequestResult {
+            request: 1,
+            result: Err(error),
+            ..
+        } => assert_eq!(error, BackendError::Constraint),
+        event => panic!("Unexpected event: {:?}", event),
+    }
+
+    backend
+        .send(IndexedDBThreadMsg::Abort {
+            connection,
+            transaction: 1,
+        })
+        .unwrap();
+    expect_finished(&events, Err(BackendError::Abort));
+}
+
+#[test]
+fn test_open_with_lower_version_fails() {
+    let backend: IpcSender<IndexedDBThreadMsg> = IndexedDBThreadFactory::new(None, None);
+    let (connection, _events) = create_database(&backend, "lower_version");
+    backend.send(IndexedDBThreadMsg::Close(connection)).unwrap();
+
+    let events = open(&backend, "lower_version", Some(2));
+    let connection = match events.recv().unwrap() {
+        ConnectionEvent::UpgradeNeeded {
+            connection,
+            old_version: 1,
+            ..
+        } => connection,
+        event => panic!("Unexpected event: {:?}", event),
+    };
+    backend
+        .send(IndexedDBThreadMsg::Commit {
+            connection,
+            transaction: UPGRADE_TRANSACTION_ID,
+        })
+        .unwrap();
+    expect_finished(&events, Ok(()));
+    match events.recv().unwrap() {
+        ConnectionEvent::Opened { metadata, .. } => assert_eq!(metadata.version, 2),
+        event => panic!("Unexpected event: {:?}", event),
+    }
+
+    let events = open(&backend, "lower_version", Some(1));
+    match events.recv().unwrap() {
+        ConnectionEvent::OpenFailed(error) => assert_eq!(error, BackendError::Version),
+        event => panic!("Unexpected event: {:?}", event),
+    }
+}
+
+#[test]
+fn test_aborted_upgrade_does_not_create_database() {
+    let backend: IpcSender<IndexedDBThreadMsg> = IndexedDBThreadFactory::new(None, None);
+    let events = open(&backend, "aborted_upgrade", Some(1));
+    let connection = match events.recv().unwrap() {
+        ConnectionEvent::UpgradeNeeded { connection, .. } => connection,
+        event => panic!("Unexpected event: {:?}", event),
+    };
+    backend
+        .send(IndexedDBThreadMsg::Abort {
+            connection,
+            transaction: UPGRADE_TRANSACTION_ID,
+        })
+        .unwrap();
+    expect_finished(&events, Err(BackendError::Abort));
+    match events.recv().unwrap() {
+        ConnectionEvent::OpenFailed(error) => assert_eq!(error, BackendError::Abort),
+        event => panic!("Unexpected event: {:?}", event),
+    }
+
+    let (sender, receiver) = ipc::channel().unwrap();
+    backend
+        .send(IndexedDBThreadMsg::Databases(sender, origin()))
+        .unwrap();
+    assert!(receiver.recv().unwrap().is_empty());
+}
+
+fn commit(backend: &IpcSender<IndexedDBThreadMsg>, connection: ConnectionId) {
+    backend
+        .send(IndexedDBThreadMsg::Commit {
+            connection,
+            transaction: 1,
+        })
+        .unwrap();
+}
+
+#[test]
+fn test_committed_records_are_persisted() {
+    let config_dir = env::temp_dir().join(format!("servo-indexeddb-{}", Uuid::new_v4()));
+    {
+        let backend: IpcSender<IndexedDBThreadMsg> =
+            IndexedDBThreadFactory::new(Some(config_dir.clone()), None);
+        let (connection, events) = create_database(&backend, "persisted");
+
+        begin_readwrite(&backend, connection);
+        request(&backend, connection, 0, put(1., vec![1]));
+        request(&backend, connection, 1, put(2., vec![2]));
+        commit(&backend, connection);
+        expect_result(&events, 0);
+        expect_result(&events, 1);
+        expect_finished(&events, Ok(()));
+
+        begin_readwrite(&backend, connection);
+        request(
+            &backend,
+            connection,
+            2,
+            AsyncOperation::Delete {
+                store: "store".to_owned(),
+                query: IndexedDBKeyRange::only(IndexedDBKeyType::Number(1.)),
+            },
+        );
+        commit(&backend, connection);
+        expect_result(&events, 2);
+        expect_finished(&events, Ok(()));
+
+        let (sender, receiver) = ipc::channel().unwrap();
+        backend.send(IndexedDBThreadMsg::Exit(sender)).unwrap();
+        receiver.recv().unwrap();
+    }
+
+    let backend: IpcSender<IndexedDBThreadMsg> =
+        IndexedDBThreadFactory::new(Some(config_dir.clone()), None);
+    let events = open(&backend, "persisted", None);
+    let connection = match events.recv().unwrap() {
+        ConnectionEvent::Opened {
+            connection,
+            metadata,
+        } => {
+            assert_eq!(metadata.version, 1);
+            connection
+        },
+        event => panic!("Unexpected event: {:?}", event),
+    };
+    begin_readwrite(&backend, connection);
+    request(
+        &backend,
+        connection,
+        0,
+        AsyncOperation::GetAll {
+            source: RecordSource::ObjectStore("store".to_owned()),
+            query: IndexedDBKeyRange::unbounded(),
+            count: None,
+            keys_only: false,
+        },
+    );
+    commit(&backend, connection);
+    match expect_result(&events, 0) {
+        OperationResult::Records(records) => {
+            assert_eq!(records.len(), 1);
+            assert_eq!(records[0].key, IndexedDBKeyType::Number(2.));
+            assert_eq!(records[0].value, Some(vec![2]));
+        },
+        result => panic!("Unexpected result: {:?}", result),
+    }
+    expect_finished(&events, Ok(()));
+    let _ = fs::remove_dir_all(&config_dir);
+}
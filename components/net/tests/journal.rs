@@ -0,0 +1,148 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use net::cookie::Cookie;
+use net::cookie_storage::CookieStorage;
+use net::journal::{Journal, JournalBatch};
+use net_traits::CookieSource;
+use servo_url::ServoUrl;
+use std::collections::HashMap;
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use uuid::Uuid;
+
+fn temporary_config_dir() -> PathBuf {
+    let config_dir = env::temp_dir().join(format!("servo-journal-{}", Uuid::new_v4()));
+    fs::create_dir_all(&config_dir).unwrap();
+    config_dir
+}
+
+fn reopen(config_dir: &PathBuf) -> HashMap<String, String> {
+    let (_, values) = Journal::open(config_dir, "test");
+    values.expect("The journal should exist")
+}
+
+#[test]
+fn test_journal_persists_updates_without_shutdown() {
+    let config_dir = temporary_config_dir();
+    let (journal, values) = Journal::open::<String>(&config_dir, "test");
+    assert!(values.is_none());
+    journal.put("kept", &"first".to_owned());
+    journal.put("replaced", &"old".to_owned());
+    journal.put("replaced", &"new".to_owned());
+    journal.put("removed", &"gone".to_owned());
+    journal.remove("removed");
+    journal.flush();
+
+    let values = reopen(&config_dir);
+    assert_eq!(values.len(), 2);
+    assert_eq!(values["kept"], "first");
+    assert_eq!(values["replaced"], "new");
+    let _ = fs::remove_dir_all(&config_dir);
+}
+
+#[test]
+fn test_journal_recovers_from_torn_write() {
+    let config_dir = temporary_config_dir();
+    {
+        let (journal, _) = Journal::open::<String>(&config_dir, "test");
+        journal.put("intact", &"value".to_owned());
+        journal.flush();
+    }
+    // The start of a record whose payload never made it to the disk.
+    let journal_path = config_dir.join("test.journal");
+    let mut file = OpenOptions::new().append(true).open(&journal_path).unwrap();
+    file.write_all(&[64, 0, 0, 0, 1, 2, 3, 4, b'[']).unwrap();
+    drop(file);
+
+    let (journal, values) = Journal::open::<String>(&config_dir, "test");
+    let values = values.unwrap();
+    assert_eq!(values.len(), 1);
+    assert_eq!(values["intact"], "value");
+    journal.put("appended", &"after recovery".to_owned());
+    journal.flush();
+
+    let values = reopen(&config_dir);
+    assert_eq!(values.len(), 2);
+    assert_eq!(values["appended"], "after recovery");
+    let _ = fs::remove_dir_all(&config_dir);
+}
+
+#[test]
+fn test_journal_writes_batches_at_once() {
+    let config_dir = temporary_config_dir();
+    {
+        let (journal, _) = Journal::open::<String>(&config_dir, "test");
+        journal.put("removed", &"value".to_owned());
+        let mut batch = JournalBatch::new();
+        batch.put("first", &"1".to_owned());
+        batch.put("second", &"2".to_owned());
+        batch.remove("removed");
+        journal.write(batch);
+        journal.flush();
+    }
+    let values = reopen(&config_dir);
+    assert_eq!(values.len(), 2);
+    assert_eq!(values["first"], "1");
+    assert_eq!(values["second"], "2");
+
+    // Lose the end of the batch, as if the process died while writing it.
+    let journal_path = config_dir.join("test.journal");
+    let file = OpenOptions::new().write(true).open(&journal_path).unwrap();
+    let length = file.metadata().unwrap().len();
+    file.set_len(length - 1).unwrap();
+    drop(file);
+
+    let values = reopen(&config_dir);
+    assert_eq!(values.len(), 1);
+    assert_eq!(values["removed"], "value");
+    let _ = fs::remove_dir_all(&config_dir);
+}
+
+#[test]
+fn test_journal_is_compacted() {
+    let config_dir = temporary_config_dir();
+    let (journal, _) = Journal::open::<String>(&config_dir, "test");
+    let value = "x".repeat(1024);
+    for _ in 0..1024 {
+        journal.put("key", &value);
+        journal.flush();
+    }
+
+    let journal_size = fs::metadata(config_dir.join("test.journal")).unwrap().len();
+    assert!(journal_size < 128 * 1024);
+    assert_eq!(reopen(&config_dir)["key"], value);
+    let _ = fs::remove_dir_all(&config_dir);
+}
+
+#[test]
+fn test_cookies_are_persisted_as_they_change() {
+    let config_dir = temporary_config_dir();
+    let url = ServoUrl::parse("https://example.com/").unwrap();
+    let source = CookieSource::HTTP;
+    {
+        let mut storage = CookieStorage::new(5);
+        storage.open_journal(&config_dir);
+        for cookie_str in &["kept=1", "removed=1"] {
+            let cookie = Cookie::from_cookie_string(cookie_str.to_string(), &url, source).unwrap();
+            storage.push(cookie, &url, source);
+        }
+        let expired = "removed=1; expires=Sun, 18-Apr-2000 21:06:29 GMT";
+        let cookie = Cookie::from_cookie_string(expired.to_owned(), &url, source).unwrap();
+        storage.push(cookie, &url, source);
+        storage.remove_expired_cookies_for_url(&url);
+        storage.flush_journal();
+        // Dropped without writing anything else, as after a crash.
+    }
+
+    let mut storage = CookieStorage::new(5);
+    storage.open_journal(&config_dir);
+    assert_eq!(
+        storage.cookies_for_url(&url, source),
+        Some("kept=1".to_owned())
+    );
+    let _ = fs::remove_dir_all(&config_dir);
+}
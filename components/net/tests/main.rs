@@ -7,28 +7,38 @@
 #[macro_use]
 extern crate lazy_static;
 
+mod cache_storage_thread;
 mod cookie;
 mod cookie_http_state;
 mod data_loader;
+mod download_manager;
 mod fetch;
 mod file_loader;
 mod filemanager_thread;
 mod hsts;
 mod http_cache;
 mod http_loader;
+mod indexeddb_thread;
+mod journal;
 mod mime_classifier;
+mod proxy;
+mod quota_thread;
 mod resource_thread;
 mod subresource_integrity;
+mod websocket;
 
-use crossbeam_channel::{unbounded, Sender};
+use content_security_policy as csp;
+use crossbeam_channel::{unbounded, Receiver, Sender};
 use devtools_traits::DevtoolsControlMsg;
 use embedder_traits::resources::{self, Resource};
-use embedder_traits::{EmbedderProxy, EventLoopWaker};
+use embedder_traits::{EmbedderMsg, EmbedderProxy, EventLoopWaker};
+use embedder_traits::{PromptCredentials, PromptDefinition};
 use futures::{Future, Stream};
 use hyper::server::conn::Http;
 use hyper::server::Server as HyperServer;
 use hyper::service::service_fn_ok;
 use hyper::{Body, Request as HyperRequest, Response as HyperResponse};
+use msg::constellation_msg::TopLevelBrowsingContextId;
 use net::connector::{create_tls_config, ALPN_H2_H1};
 use net::fetch::cors_cache::CorsCache;
 use net::fetch::methods::{self, CancellationListener, FetchContext};
@@ -37,12 +47,14 @@ use net::test::HttpState;
 use net_traits::request::Request;
 use net_traits::response::Response;
 use net_traits::{FetchTaskTarget, ResourceFetchTiming, ResourceTimingType};
-use openssl::ssl::{SslAcceptor, SslFiletype, SslMethod};
+use openssl::ssl::{select_next_proto, AlpnError, SslAcceptor, SslFiletype, SslMethod};
 use servo_arc::Arc as ServoArc;
 use servo_url::ServoUrl;
 use std::net::TcpListener as StdTcpListener;
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
+use std::thread;
 use tokio::net::TcpListener;
 use tokio::reactor::Handle;
 use tokio::runtime::Runtime;
@@ -59,7 +71,14 @@ struct FetchResponseCollector {
 }
 
 fn create_embedder_proxy() -> EmbedderProxy {
-    let (sender, _) = unbounded();
+    create_embedder_proxy_and_receiver().0
+}
+
+fn create_embedder_proxy_and_receiver() -> (
+    EmbedderProxy,
+    Receiver<(Option<TopLevelBrowsingContextId>, EmbedderMsg)>,
+) {
+    let (sender, receiver) = unbounded();
     let event_loop_waker = || {
         struct DummyEventLoopWaker {}
         impl DummyEventLoopWaker {
@@ -77,10 +96,31 @@ fn create_embedder_proxy() -> EmbedderProxy {
         Box::new(DummyEventLoopWaker::new())
     };
 
-    EmbedderProxy {
+    let embedder_proxy = EmbedderProxy {
         sender: sender,
         event_loop_waker: event_loop_waker(),
-    }
+    };
+
+    (embedder_proxy, receiver)
+}
+
+/// Answer every credentials prompt sent to the embedder with `credentials`,
+/// and count the prompts.
+fn answer_credentials_prompts(
+    receiver: Receiver<(Option<TopLevelBrowsingContextId>, EmbedderMsg)>,
+    credentials: Option<PromptCredentials>,
+) -> Arc<AtomicUsize> {
+    let prompts = Arc::new(AtomicUsize::new(0));
+    let counter = prompts.clone();
+    thread::spawn(move || {
+        while let Ok((_, msg)) = receiver.recv() {
+            if let EmbedderMsg::Prompt(PromptDefinition::Credentials(_, sender), _) = msg {
+                counter.fetch_add(1, Ordering::SeqCst);
+                sender.send(credentials.clone()).unwrap();
+            }
+        }
+    });
+    prompts
 }
 
 fn new_fetch_context(
@@ -91,10 +131,11 @@ fn new_fetch_context(
     let tls_config = create_tls_config(&certs, ALPN_H2_H1);
     let sender = fc.unwrap_or_else(|| create_embedder_proxy());
     FetchContext {
-        state: Arc::new(HttpState::new(tls_config)),
+        state: Arc::new(HttpState::new(tls_config, sender.clone())),
         user_agent: DEFAULT_USER_AGENT.into(),
         devtools_chan: dc,
         filemanager: FileManager::new(sender),
+        swmanager_chan: None,
         cancellation_listener: Arc::new(Mutex::new(CancellationListener::new(None))),
         timing: ServoArc::new(Mutex::new(ResourceFetchTiming::new(
             ResourceTimingType::Navigation,
@@ -110,6 +151,7 @@ impl FetchTaskTarget for FetchResponseCollector {
     fn process_response_eof(&mut self, response: &Response) {
         let _ = self.sender.send(response.clone());
     }
+    fn process_csp_violations(&mut self, _: &Request, _: Vec<csp::Violation>) {}
 }
 
 fn fetch(request: &mut Request, dc: Option<Sender<DevtoolsControlMsg>>) -> Response {
@@ -177,6 +219,20 @@ where
 }
 
 fn make_ssl_server<H>(handler: H, cert_path: PathBuf, key_path: PathBuf) -> (Server, ServoUrl)
+where
+    H: Fn(HyperRequest<Body>, &mut HyperResponse<Body>) + Send + Sync + 'static,
+{
+    make_ssl_server_with_http2(handler, cert_path, key_path, false)
+}
+
+/// Make a server that speaks HTTP/2 with the clients that offer it through ALPN
+/// if `http2` is true, and HTTP/1.1 otherwise.
+fn make_ssl_server_with_http2<H>(
+    handler: H,
+    cert_path: PathBuf,
+    key_path: PathBuf,
+    http2: bool,
+) -> (Server, ServoUrl)
 where
     H: Fn(HyperRequest<Body>, &mut HyperResponse<Body>) + Send + Sync + 'static,
 {
@@ -194,6 +250,11 @@ where
         tls_server_config
             .set_private_key_file(&key_path, SslFiletype::PEM)
             .unwrap();
+        if http2 {
+            tls_server_config.set_alpn_select_callback(|_, client_protocols| {
+                select_next_proto(b"\x02h2", client_protocols).ok_or(AlpnError::NOACK)
+            });
+        }
 
         let handler = handler.clone();
         tls_server_config
@@ -202,6 +263,7 @@ where
             .map_err(|_| ())
             .and_then(move |ssl| {
                 Http::new()
+                    .http2_only(http2)
                     .serve_connection(
                         ssl,
                         service_fn_ok(move |req: HyperRequest<Body>| {
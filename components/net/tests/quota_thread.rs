@@ -0,0 +1,178 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use ipc_channel::ipc::{self, IpcReceiver, IpcSender};
+use net::quota_thread::{start_quota_thread, QuotaStore};
+use net_traits::quota_thread::{QuotaClient, QuotaThreadMsg, StorageEstimate};
+use net_traits::storage_thread::StorageThreadMsg;
+use servo_url::{ImmutableOrigin, ServoUrl};
+use std::thread;
+use std::time::Duration;
+
+const MB: u64 = 1024 * 1024;
+
+fn origin(url: &str) -> ImmutableOrigin {
+    ServoUrl::parse(url).unwrap().origin()
+}
+
+/// Start a quota manager, along with a store that only records what it is asked to clear.
+fn start() -> (IpcSender<QuotaThreadMsg>, IpcReceiver<StorageThreadMsg>) {
+    let (quota, port) = ipc::channel().unwrap();
+    let (store, store_port) = ipc::channel().unwrap();
+    start_quota_thread(port, None, vec![QuotaStore::Storage(store)]);
+    (quota, store_port)
+}
+
+fn estimate(quota: &IpcSender<QuotaThreadMsg>, origin: &ImmutableOrigin) -> StorageEstimate {
+    let (sender, receiver) = ipc::channel().unwrap();
+    quota
+        .send(QuotaThreadMsg::Estimate(origin.clone(), sender))
+        .unwrap();
+    receiver.recv().unwrap()
+}
+
+fn persisted(quota: &IpcSender<QuotaThreadMsg>, origin: &ImmutableOrigin) -> bool {
+    let (sender, receiver) = ipc::channel().unwrap();
+    quota
+        .send(QuotaThreadMsg::Persisted(origin.clone(), sender))
+        .unwrap();
+    receiver.recv().unwrap()
+}
+
+fn persist(quota: &IpcSender<QuotaThreadMsg>, origin: &ImmutableOrigin) -> bool {
+    let (sender, receiver) = ipc::channel().unwrap();
+    quota
+        .send(QuotaThreadMsg::Persist(origin.clone(), sender))
+        .unwrap();
+    receiver.recv().unwrap()
+}
+
+fn request_space(quota: &IpcSender<QuotaThreadMsg>, origin: &ImmutableOrigin, usage: u64) -> bool {
+    let (sender, receiver) = ipc::channel().unwrap();
+    quota
+        .send(QuotaThreadMsg::RequestSpace {
+            origin: origin.clone(),
+            client: QuotaClient::IndexedDB,
+            usage,
+            sender,
+        })
+        .unwrap();
+    receiver.recv().unwrap()
+}
+
+fn expect_cleared(store: &IpcReceiver<StorageThreadMsg>, expected: &ImmutableOrigin) {
+    match store.recv().unwrap() {
+        StorageThreadMsg::ClearOrigin(origin) => assert_eq!(origin, *expected),
+        msg => panic!("unexpected message {:?}", msg),
+    }
+}
+
+#[test]
+fn test_estimate_accounts_for_every_store() {
+    let (quota, _store) = start();
+    let origin = origin("https://example.com");
+    assert_eq!(estimate(&quota, &origin).usage, 0);
+
+    quota
+        .send(QuotaThreadMsg::SetUsage(
+            origin.clone(),
+            QuotaClient::LocalStorage,
+            10,
+        ))
+        .unwrap();
+    quota
+        .send(QuotaThreadMsg::SetUsage(
+            origin.clone(),
+            QuotaClient::CacheStorage,
+            20,
+        ))
+        .unwrap();
+    quota
+        .send(QuotaThreadMsg::SetUsage(
+            origin.clone(),
+            QuotaClient::LocalStorage,
+            5,
+        ))
+        .unwrap();
+    assert_eq!(
+        estimate(&quota, &origin),
+        StorageEstimate {
+            usage: 25,
+            quota: 200 * MB,
+        }
+    );
+}
+
+#[test]
+fn test_persist() {
+    let (quota, _store) = start();
+    let origin = origin("https://example.com");
+    assert!(!persisted(&quota, &origin));
+    assert!(persist(&quota, &origin));
+    assert!(persisted(&quota, &origin));
+    assert_eq!(estimate(&quota, &origin).quota, 1024 * MB);
+
+    let opaque = ServoUrl::parse("data:text/html,").unwrap().origin();
+    assert!(!persist(&quota, &opaque));
+}
+
+#[test]
+fn test_request_space_within_origin_quota() {
+    let (quota, _store) = start();
+    let origin = origin("https://example.com");
+    assert!(!request_space(&quota, &origin, 201 * MB));
+    assert_eq!(estimate(&quota, &origin).usage, 0);
+    assert!(request_space(&quota, &origin, 100 * MB));
+    assert_eq!(estimate(&quota, &origin).usage, 100 * MB);
+
+    // A persistent origin can use up to the global limit.
+    assert!(persist(&quota, &origin));
+    assert!(request_space(&quota, &origin, 500 * MB));
+}
+
+#[test]
+fn test_least_recently_used_best_effort_origin_is_evicted() {
+    let (quota, store) = start();
+    let old = origin("https://old.example.com");
+    let persistent = origin("https://persistent.example.com");
+    let recent = origin("https://recent.example.com");
+
+    quota
+        .send(QuotaThreadMsg::SetUsage(
+            old.clone(),
+            QuotaClient::HttpCache,
+            600 * MB,
+        ))
+        .unwrap();
+    thread::sleep(Duration::from_millis(10));
+    assert!(persist(&quota, &persistent));
+    assert!(request_space(&quota, &persistent, 300 * MB));
+    thread::sleep(Duration::from_millis(10));
+    assert!(request_space(&quota, &recent, 190 * MB));
+
+    expect_cleared(&store, &old);
+    assert_eq!(estimate(&quota, &old).usage, 0);
+    assert_eq!(estimate(&quota, &persistent).usage, 300 * MB);
+    assert_eq!(estimate(&quota, &recent).usage, 190 * MB);
+
+    // Persistent data is never evicted, whatever its last use.
+    assert!(request_space(&quota, &persistent, 900 * MB));
+    expect_cleared(&store, &recent);
+    assert!(!request_space(&quota, &recent, 200 * MB));
+}
+
+#[test]
+fn test_clear_origin() {
+    let (quota, store) = start();
+    let origin = origin("https://example.com");
+    assert!(persist(&quota, &origin));
+    assert!(request_space(&quota, &origin, MB));
+
+    quota
+        .send(QuotaThreadMsg::ClearOrigin(origin.clone()))
+        .unwrap();
+    expect_cleared(&store, &origin);
+    assert!(!persisted(&quota, &origin));
+    assert_eq!(estimate(&quota, &origin).usage, 0);
+}
@@ -6,9 +6,10 @@ use crate::create_embedder_proxy;
 use ipc_channel::ipc;
 use net::resource_thread::new_core_resource_thread;
 use net::test::parse_hostsfile;
-use net_traits::CoreResourceMsg;
+use net_traits::{CookieSource, CoreResourceMsg};
 use profile_traits::mem::ProfilerChan as MemProfilerChan;
 use profile_traits::time::ProfilerChan;
+use servo_url::{ImmutableOrigin, ServoUrl};
 use std::net::IpAddr;
 
 fn ip(s: &str) -> IpAddr {
@@ -28,11 +29,68 @@ fn test_exit() {
         create_embedder_proxy(),
         None,
         None,
+        None,
     );
     resource_thread.send(CoreResourceMsg::Exit(sender)).unwrap();
     receiver.recv().unwrap();
 }
 
+#[test]
+fn test_document_cookies_depend_on_the_site_of_the_ancestors() {
+    let (tx, _rx) = ipc::channel().unwrap();
+    let (mtx, _mrx) = ipc::channel().unwrap();
+    let (resource_thread, _private_resource_thread) = new_core_resource_thread(
+        "".into(),
+        None,
+        ProfilerChan(tx),
+        MemProfilerChan(mtx),
+        create_embedder_proxy(),
+        None,
+        None,
+        None,
+    );
+    let url = ServoUrl::parse("https://example.com/").unwrap();
+    let cross_site_ancestors = vec![ServoUrl::parse("https://example.org").unwrap().origin()];
+
+    let set_cookies = |cookies: &[&str], ancestor_origins: &[ImmutableOrigin]| {
+        let cookies = cookies.iter().map(|cookie| cookie.to_string()).collect();
+        let msg = CoreResourceMsg::SetCookiesForUrl(
+            url.clone(),
+            cookies,
+            ancestor_origins.to_vec(),
+            CookieSource::NonHTTP,
+        );
+        resource_thread.send(msg).unwrap();
+    };
+    let get_cookies = |ancestor_origins: &[ImmutableOrigin]| {
+        let (sender, receiver) = ipc::channel().unwrap();
+        let msg = CoreResourceMsg::GetCookiesForUrl(
+            url.clone(),
+            ancestor_origins.to_vec(),
+            sender,
+            CookieSource::NonHTTP,
+        );
+        resource_thread.send(msg).unwrap();
+        receiver.recv().unwrap()
+    };
+
+    // A cross-site document can only set cookies meant to be used cross-site.
+    set_cookies(
+        &["lax=1", "none=1; SameSite=None; Secure"],
+        &cross_site_ancestors,
+    );
+    set_cookies(&["strict=1; SameSite=Strict"], &[]);
+    assert_eq!(get_cookies(&[]), Some("none=1; strict=1".to_owned()));
+    assert_eq!(
+        get_cookies(&cross_site_ancestors),
+        Some("none=1".to_owned())
+    );
+
+    let (sender, receiver) = ipc::channel().unwrap();
+    resource_thread.send(CoreResourceMsg::Exit(sender)).unwrap();
+    receiver.recv().unwrap();
+}
+
 #[test]
 fn test_parse_hostsfile() {
     let mock_hosts_file_content = "127.0.0.1 foo.bar.com\n127.0.0.2 servo.test.server";
@@ -0,0 +1,190 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::create_embedder_proxy;
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use embedder_traits::resources::{self, Resource};
+use ipc_channel::ipc::{self, IpcReceiver, IpcSender};
+use net::connector::{create_tls_config, ALPN_H1};
+use net::test::{websocket_connect, HttpState};
+use net_traits::request::{RequestBuilder, RequestMode};
+use net_traits::{MessageData, WebSocketDomAction, WebSocketNetworkEvent};
+use servo_url::ServoUrl;
+use std::sync::Arc;
+use std::thread;
+use ws::deflate::{DeflateHandler, DeflateSettings};
+use ws::{Handler, Message, Request, Response, Result as WebSocketResult, WebSocket};
+
+/// A server that echoes messages, and reports the extensions offered by clients.
+struct EchoServer {
+    out: ws::Sender,
+    offers: Sender<Option<String>>,
+    /// The extensions to answer with, instead of negotiating them.
+    answer: Option<&'static str>,
+}
+
+impl Handler for EchoServer {
+    fn on_request(&mut self, req: &Request) -> WebSocketResult<Response> {
+        let offer = req
+            .header("Sec-WebSocket-Extensions")
+            .map(|offer| String::from_utf8_lossy(offer).into_owned());
+        let _ = self.offers.send(offer);
+        let mut res = Response::from_request(req)?;
+        if let Some(answer) = self.answer {
+            res.add_extension(answer);
+        }
+        Ok(res)
+    }
+
+    fn on_message(&mut self, message: Message) -> WebSocketResult<()> {
+        self.out.send(message)
+    }
+}
+
+/// Start an echo server, whose handlers are wrapped by `wrap`.
+fn start_server<H, F>(answer: Option<&'static str>, wrap: F) -> (ServoUrl, Receiver<Option<String>>)
+where
+    H: Handler,
+    F: Fn(EchoServer) -> H + Send + 'static,
+{
+    let (offers, offers_receiver) = unbounded();
+    let server = WebSocket::new(move |out| {
+        wrap(EchoServer {
+            out,
+            offers: offers.clone(),
+            answer,
+        })
+    })
+    .unwrap()
+    .bind("127.0.0.1:0")
+    .unwrap();
+    let url = format!("ws://127.0.0.1:{}/", server.local_addr().unwrap().port());
+    thread::spawn(move || server.run());
+    (ServoUrl::parse(&url).unwrap(), offers_receiver)
+}
+
+fn connect(
+    url: ServoUrl,
+    deflate_settings: Option<DeflateSettings>,
+) -> (
+    IpcReceiver<WebSocketNetworkEvent>,
+    IpcSender<WebSocketDomAction>,
+) {
+    let certs = resources::read_string(Resource::SSLCertificates);
+    let tls_config = create_tls_config(&certs, ALPN_H1);
+    let http_state = Arc::new(HttpState::new(tls_config, create_embedder_proxy()));
+    let request = RequestBuilder::new(url.clone())
+        .origin(url.origin())
+        .mode(RequestMode::WebSocket { protocols: vec![] });
+    let (event_sender, event_receiver) = ipc::channel().unwrap();
+    let (action_sender, action_receiver) = ipc::channel().unwrap();
+    websocket_connect(
+        request,
+        event_sender,
+        action_receiver,
+        http_state,
+        None,
+        deflate_settings,
+    );
+    (event_receiver, action_sender)
+}
+
+fn extensions_in_use(event: WebSocketNetworkEvent) -> Option<String> {
+    match event {
+        WebSocketNetworkEvent::ConnectionEstablished {
+            extensions_in_use, ..
+        } => extensions_in_use,
+        _ => panic!("the connection was not established"),
+    }
+}
+
+fn assert_echoes(
+    events: &IpcReceiver<WebSocketNetworkEvent>,
+    actions: &IpcSender<WebSocketDomAction>,
+) {
+    let message = r#"{"metric": "latency", "value": 42}"#.repeat(100);
+    for _ in 0..2 {
+        actions
+            .send(WebSocketDomAction::SendMessage(MessageData::Text(
+                message.clone(),
+            )))
+            .unwrap();
+        match events.recv().unwrap() {
+            WebSocketNetworkEvent::MessageReceived(MessageData::Text(echo)) => {
+                assert_eq!(echo, message)
+            },
+            _ => panic!("the message was not echoed"),
+        }
+    }
+}
+
+#[test]
+fn test_websocket_negotiates_permessage_deflate() {
+    let (url, offers) = start_server(None, DeflateHandler::new);
+    let (events, actions) = connect(url, Some(DeflateSettings::default()));
+
+    let offer = offers.recv().unwrap().unwrap();
+    assert!(offer.starts_with("permessage-deflate"));
+    assert!(!offer.contains("server_no_context_takeover"));
+    let extensions = extensions_in_use(events.recv().unwrap()).unwrap();
+    assert!(extensions.starts_with("permessage-deflate"));
+    assert!(!extensions.contains("no_context_takeover"));
+    assert_echoes(&events, &actions);
+}
+
+#[test]
+fn test_websocket_requests_server_no_context_takeover() {
+    let (url, offers) = start_server(None, DeflateHandler::new);
+    let settings = DeflateSettings {
+        request_no_context_takeover: true,
+        ..DeflateSettings::default()
+    };
+    let (events, actions) = connect(url, Some(settings));
+
+    let offer = offers.recv().unwrap().unwrap();
+    assert!(offer.contains("server_no_context_takeover"));
+    let extensions = extensions_in_use(events.recv().unwrap()).unwrap();
+    assert!(extensions.contains("server_no_context_takeover"));
+    assert_echoes(&events, &actions);
+}
+
+#[test]
+fn test_websocket_accepts_client_no_context_takeover() {
+    let (url, _) = start_server(
+        Some("permessage-deflate; client_no_context_takeover"),
+        |server| server,
+    );
+    let (events, _actions) = connect(url, Some(DeflateSettings::default()));
+
+    let extensions = extensions_in_use(events.recv().unwrap()).unwrap();
+    assert!(extensions.contains("client_no_context_takeover"));
+}
+
+#[test]
+fn test_websocket_fails_when_refusing_client_no_context_takeover() {
+    let (url, _) = start_server(
+        Some("permessage-deflate; client_no_context_takeover"),
+        |server| server,
+    );
+    let settings = DeflateSettings {
+        accept_no_context_takeover: false,
+        ..DeflateSettings::default()
+    };
+    let (events, _actions) = connect(url, Some(settings));
+
+    match events.recv().unwrap() {
+        WebSocketNetworkEvent::Fail => {},
+        _ => panic!("the connection did not fail"),
+    }
+}
+
+#[test]
+fn test_websocket_without_permessage_deflate() {
+    let (url, offers) = start_server(None, DeflateHandler::new);
+    let (events, actions) = connect(url, None);
+
+    assert_eq!(offers.recv().unwrap(), None);
+    assert_eq!(extensions_in_use(events.recv().unwrap()), None);
+    assert_echoes(&events, &actions);
+}
@@ -3,26 +3,30 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 
 use crate::connector::{create_tls_config, ALPN_H1};
-use crate::cookie::Cookie;
-use crate::fetch::methods::should_be_blocked_due_to_bad_port;
+use crate::cookie::{Cookie, SameSiteContext};
+use crate::fetch::methods::{should_be_blocked_due_to_bad_port, should_request_be_blocked_by_csp};
 use crate::hosts::replace_host;
 use crate::http_loader::HttpState;
+use content_security_policy as csp;
 use embedder_traits::resources::{self, Resource};
 use headers::Host;
 use http::header::{self, HeaderMap, HeaderName, HeaderValue};
 use http::uri::Authority;
+use http::Method;
 use ipc_channel::ipc::{IpcReceiver, IpcSender};
 use ipc_channel::router::ROUTER;
 use net_traits::request::{RequestBuilder, RequestMode};
 use net_traits::{CookieSource, MessageData};
 use net_traits::{WebSocketDomAction, WebSocketNetworkEvent};
 use openssl::ssl::SslStream;
+use servo_config::pref;
 use servo_url::ServoUrl;
 use std::fs;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::thread;
 use url::Url;
+use ws::deflate::{DeflateBuilder, DeflateHandler, DeflateSettings};
 use ws::util::TcpStream;
 use ws::{
     CloseCode, Factory, Handler, Handshake, Message, Request, Response as WsResponse, Sender,
@@ -41,6 +45,7 @@ struct Client<'a> {
     event_sender: &'a IpcSender<WebSocketNetworkEvent>,
     protocol_in_use: Option<String>,
     certificate_path: Option<String>,
+    same_site_context: SameSiteContext,
 }
 
 impl<'a> Factory for Client<'a> {
@@ -55,6 +60,51 @@ impl<'a> Factory for Client<'a> {
     }
 }
 
+/// A client that offers the permessage-deflate extension,
+/// <https://tools.ietf.org/html/rfc7692>.
+struct DeflateClient<'a> {
+    client: Client<'a>,
+    settings: DeflateSettings,
+}
+
+impl<'a> Factory for DeflateClient<'a> {
+    type Handler = DeflateHandler<Client<'a>>;
+
+    fn connection_made(&mut self, _: Sender) -> Self::Handler {
+        DeflateBuilder::new()
+            .with_settings(self.settings)
+            .build(self.client.clone())
+    }
+
+    fn connection_lost(&mut self, _: Self::Handler) {
+        let _ = self.client.event_sender.send(WebSocketNetworkEvent::Fail);
+    }
+}
+
+/// The permessage-deflate settings of the clients, `None` if they don't offer the extension.
+pub fn deflate_settings() -> Option<DeflateSettings> {
+    if !pref!(network.websocket.permessage_deflate.enabled) {
+        return None;
+    }
+    Some(DeflateSettings {
+        // Whether to ask the server to compress every message on its own.
+        request_no_context_takeover: pref!(
+            network
+                .websocket
+                .permessage_deflate
+                .request_server_no_context_takeover
+        ),
+        // Whether to agree to compress every message on its own if the server asks.
+        accept_no_context_takeover: pref!(
+            network
+                .websocket
+                .permessage_deflate
+                .accept_client_no_context_takeover
+        ),
+        ..DeflateSettings::default()
+    })
+}
+
 impl<'a> Handler for Client<'a> {
     fn build_request(&mut self, url: &Url) -> WebSocketResult<Request> {
         let mut req = Request::from_url(url)?;
@@ -71,8 +121,11 @@ impl<'a> Handler for Client<'a> {
 
         let mut cookie_jar = self.http_state.cookie_jar.write().unwrap();
         cookie_jar.remove_expired_cookies_for_url(self.resource_url);
-        if let Some(cookie_list) = cookie_jar.cookies_for_url(self.resource_url, CookieSource::HTTP)
-        {
+        if let Some(cookie_list) = cookie_jar.cookies_for_url_in_context(
+            self.resource_url,
+            CookieSource::HTTP,
+            self.same_site_context,
+        ) {
             req.headers_mut()
                 .push(("Cookie".into(), cookie_list.as_bytes().to_owned()))
         }
@@ -86,9 +139,22 @@ impl<'a> Handler for Client<'a> {
             let name = HeaderName::from_bytes(name.as_bytes()).unwrap();
             let value = HeaderValue::from_bytes(&value).unwrap();
 
-            headers.insert(name, value);
+            headers.append(name, value);
         }
 
+        // https://tools.ietf.org/html/rfc6455#section-9.1
+        // The server may split the extensions in use across several headers.
+        let extensions_in_use: Vec<&str> = headers
+            .get_all(header::SEC_WEBSOCKET_EXTENSIONS)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .collect();
+        let extensions_in_use = if extensions_in_use.is_empty() {
+            None
+        } else {
+            Some(extensions_in_use.join(", "))
+        };
+
         let mut jar = self.http_state.cookie_jar.write().unwrap();
         // TODO(eijebong): Replace thise once typed headers settled on a cookie impl
         for cookie in headers.get_all(header::SET_COOKIE) {
@@ -96,7 +162,9 @@ impl<'a> Handler for Client<'a> {
                 if let Some(cookie) =
                     Cookie::from_cookie_string(s.into(), self.resource_url, CookieSource::HTTP)
                 {
-                    jar.push(cookie, self.resource_url, CookieSource::HTTP);
+                    if cookie.may_be_set_in_context(self.same_site_context) {
+                        jar.push(cookie, self.resource_url, CookieSource::HTTP);
+                    }
                 }
             }
         }
@@ -111,6 +179,7 @@ impl<'a> Handler for Client<'a> {
             .event_sender
             .send(WebSocketNetworkEvent::ConnectionEstablished {
                 protocol_in_use: self.protocol_in_use.clone(),
+                extensions_in_use,
             });
         Ok(())
     }
@@ -188,6 +257,7 @@ pub fn init(
     dom_action_receiver: IpcReceiver<WebSocketDomAction>,
     http_state: Arc<HttpState>,
     certificate_path: Option<String>,
+    deflate_settings: Option<DeflateSettings>,
 ) {
     thread::Builder::new()
         .name(format!("WebSocket connection to {}", req_builder.url))
@@ -224,6 +294,18 @@ pub fn init(
                 return;
             }
 
+            let (check_result, violations) =
+                should_request_be_blocked_by_csp(&req_builder.clone().build());
+            if !violations.is_empty() {
+                let _ = resource_event_sender
+                    .send(WebSocketNetworkEvent::ReportCspViolations(violations));
+            }
+            if check_result == csp::CheckResult::Blocked {
+                debug!("Failed to establish a WebSocket connection: blocked by CSP");
+                let _ = resource_event_sender.send(WebSocketNetworkEvent::Fail);
+                return;
+            }
+
             let host = replace_host(req_builder.url.host_str().unwrap());
             let mut net_url = req_builder.url.clone().into_url();
             net_url.set_host(Some(&host)).unwrap();
@@ -251,49 +333,71 @@ pub fn init(
                 event_sender: &resource_event_sender,
                 protocol_in_use: None,
                 certificate_path,
+                same_site_context: SameSiteContext::new(
+                    &req_builder.url,
+                    Some(&req_builder.origin),
+                    false,
+                    &Method::GET,
+                ),
             };
-            let mut ws = WebSocket::new(client).unwrap();
-
-            if let Err(e) = ws.connect(net_url) {
-                debug!("Failed to establish a WebSocket connection: {:?}", e);
-                return;
-            };
+            match deflate_settings {
+                Some(settings) => run(
+                    DeflateClient { client, settings },
+                    net_url,
+                    dom_action_receiver,
+                    &resource_event_sender,
+                ),
+                None => run(client, net_url, dom_action_receiver, &resource_event_sender),
+            }
+        })
+        .expect("Thread spawning failed");
+}
 
-            let ws_sender = ws.broadcaster();
-            let initiated_close = Arc::new(AtomicBool::new(false));
-
-            ROUTER.add_route(
-                dom_action_receiver.to_opaque(),
-                Box::new(move |message| {
-                    let dom_action = message.to().expect("Ws dom_action message to deserialize");
-                    match dom_action {
-                        WebSocketDomAction::SendMessage(MessageData::Text(data)) => {
-                            ws_sender.send(Message::text(data)).unwrap();
-                        },
-                        WebSocketDomAction::SendMessage(MessageData::Binary(data)) => {
-                            ws_sender.send(Message::binary(data)).unwrap();
-                        },
-                        WebSocketDomAction::Close(code, reason) => {
-                            if !initiated_close.fetch_or(true, Ordering::SeqCst) {
-                                match code {
-                                    Some(code) => ws_sender
-                                        .close_with_reason(
-                                            code.into(),
-                                            reason.unwrap_or("".to_owned()),
-                                        )
-                                        .unwrap(),
-                                    None => ws_sender.close(CloseCode::Status).unwrap(),
-                                };
-                            }
-                        },
+/// Connect to `net_url` with the clients made by `factory`, and run the connection
+/// until it closes.
+fn run<F: Factory>(
+    factory: F,
+    net_url: Url,
+    dom_action_receiver: IpcReceiver<WebSocketDomAction>,
+    resource_event_sender: &IpcSender<WebSocketNetworkEvent>,
+) {
+    let mut ws = WebSocket::new(factory).unwrap();
+
+    if let Err(e) = ws.connect(net_url) {
+        debug!("Failed to establish a WebSocket connection: {:?}", e);
+        return;
+    };
+
+    let ws_sender = ws.broadcaster();
+    let initiated_close = Arc::new(AtomicBool::new(false));
+
+    ROUTER.add_route(
+        dom_action_receiver.to_opaque(),
+        Box::new(move |message| {
+            let dom_action = message.to().expect("Ws dom_action message to deserialize");
+            match dom_action {
+                WebSocketDomAction::SendMessage(MessageData::Text(data)) => {
+                    ws_sender.send(Message::text(data)).unwrap();
+                },
+                WebSocketDomAction::SendMessage(MessageData::Binary(data)) => {
+                    ws_sender.send(Message::binary(data)).unwrap();
+                },
+                WebSocketDomAction::Close(code, reason) => {
+                    if !initiated_close.fetch_or(true, Ordering::SeqCst) {
+                        match code {
+                            Some(code) => ws_sender
+                                .close_with_reason(code.into(), reason.unwrap_or("".to_owned()))
+                                .unwrap(),
+                            None => ws_sender.close(CloseCode::Status).unwrap(),
+                        };
                     }
-                }),
-            );
+                },
+            }
+        }),
+    );
 
-            if let Err(e) = ws.run() {
-                debug!("Failed to run WebSocket: {:?}", e);
-                let _ = resource_event_sender.send(WebSocketNetworkEvent::Fail);
-            };
-        })
-        .expect("Thread spawning failed");
+    if let Err(e) = ws.run() {
+        debug!("Failed to run WebSocket: {:?}", e);
+        let _ = resource_event_sender.send(WebSocketNetworkEvent::Fail);
+    };
 }
@@ -0,0 +1,177 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::response::ResponseType;
+use http::{HeaderMap, Method};
+use ipc_channel::ipc::IpcSender;
+use servo_url::{ImmutableOrigin, ServoUrl};
+
+/// Identifies a cache, across the caches of every origin.
+pub type CacheId = u64;
+
+/// What a cache keeps of a request: what later requests are matched against.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct CacheRequest {
+    #[serde(
+        deserialize_with = "::hyper_serde::deserialize",
+        serialize_with = "::hyper_serde::serialize"
+    )]
+    pub method: Method,
+    pub url: ServoUrl,
+    #[serde(
+        deserialize_with = "::hyper_serde::deserialize",
+        serialize_with = "::hyper_serde::serialize"
+    )]
+    pub headers: HeaderMap,
+}
+
+impl CacheRequest {
+    /// The combined value of the header `name`, if the request has it.
+    /// <https://fetch.spec.whatwg.org/#concept-header-list-get>
+    pub fn header_value(&self, name: &str) -> Option<Vec<u8>> {
+        combined_value(&self.headers, name)
+    }
+}
+
+/// A response stored in a cache, along with its whole body.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct CachedResponse {
+    pub response_type: ResponseType,
+    pub url_list: Vec<ServoUrl>,
+    pub raw_status: Option<(u16, Vec<u8>)>,
+    #[serde(
+        deserialize_with = "::hyper_serde::deserialize",
+        serialize_with = "::hyper_serde::serialize"
+    )]
+    pub headers: HeaderMap,
+    /// The bytes of the body, or `None` for a null body.
+    pub body: Option<Vec<u8>>,
+}
+
+impl CachedResponse {
+    /// The lowercased field names of the `Vary` header of the response.
+    pub fn vary_fields(&self) -> Vec<String> {
+        combined_value(&self.headers, "vary")
+            .map(|value| {
+                String::from_utf8_lossy(&value)
+                    .split(',')
+                    .map(|field| field.trim().to_ascii_lowercase())
+                    .filter(|field| !field.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+/// <https://fetch.spec.whatwg.org/#concept-header-list-get>
+fn combined_value(headers: &HeaderMap, name: &str) -> Option<Vec<u8>> {
+    let mut values = headers.get_all(name).iter().peekable();
+    values.peek()?;
+    let mut combined = vec![];
+    for (i, value) in values.enumerate() {
+        if i > 0 {
+            combined.extend_from_slice(b", ");
+        }
+        combined.extend_from_slice(value.as_bytes());
+    }
+    Some(combined)
+}
+
+/// <https://w3c.github.io/ServiceWorker/#dictdef-cachequeryoptions>
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize)]
+pub struct CacheQueryOptions {
+    pub ignore_search: bool,
+    pub ignore_method: bool,
+    pub ignore_vary: bool,
+}
+
+/// <https://w3c.github.io/ServiceWorker/#dfn-cache-batch-operation>
+#[derive(Debug, Deserialize, Serialize)]
+pub enum CacheBatchOperation {
+    Put {
+        request: CacheRequest,
+        response: CachedResponse,
+    },
+    Delete {
+        request: CacheRequest,
+        options: CacheQueryOptions,
+    },
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum CacheStorageError {
+    /// A batch puts or deletes an entry that it put itself.
+    InvalidState,
+    /// Storing the entries would take the caches of the origin over their quota.
+    QuotaExceeded,
+}
+
+/// Request operations on the caches of an origin.
+#[derive(Debug, Deserialize, Serialize)]
+pub enum CacheStorageThreadMsg {
+    /// Open the cache with the given name, creating it if needed.
+    Open {
+        origin: ImmutableOrigin,
+        name: String,
+        sender: IpcSender<CacheId>,
+    },
+
+    /// Whether the origin has a cache with the given name.
+    Has {
+        origin: ImmutableOrigin,
+        name: String,
+        sender: IpcSender<bool>,
+    },
+
+    /// Delete the cache with the given name, replying whether there was one.
+    /// The entries of the cache stay available to its id, but are no longer saved.
+    Delete {
+        origin: ImmutableOrigin,
+        name: String,
+        sender: IpcSender<bool>,
+    },
+
+    /// Gets the names of the caches of an origin, in the order they were created.
+    Keys(IpcSender<Vec<String>>, ImmutableOrigin),
+
+    /// Find the first response matching a request in the caches of an origin,
+    /// or only in the cache with the given name.
+    MatchStorage {
+        origin: ImmutableOrigin,
+        cache_name: Option<String>,
+        request: CacheRequest,
+        options: CacheQueryOptions,
+        sender: IpcSender<Option<CachedResponse>>,
+    },
+
+    /// Gets the responses in a cache that match a request, or all of them.
+    MatchAll {
+        cache: CacheId,
+        request: Option<CacheRequest>,
+        options: CacheQueryOptions,
+        sender: IpcSender<Vec<CachedResponse>>,
+    },
+
+    /// Gets the requests in a cache that match a request, or all of them.
+    RequestKeys {
+        cache: CacheId,
+        request: Option<CacheRequest>,
+        options: CacheQueryOptions,
+        sender: IpcSender<Vec<CacheRequest>>,
+    },
+
+    /// Run operations on a cache all at once, replying whether they deleted any entry.
+    /// If one fails, none of them has any effect.
+    Batch {
+        cache: CacheId,
+        operations: Vec<CacheBatchOperation>,
+        sender: IpcSender<Result<bool, CacheStorageError>>,
+    },
+
+    /// Delete the caches of an origin.
+    ClearOrigin(ImmutableOrigin),
+
+    /// send a reply when done cleaning up thread resources and then shut it down
+    Exit(IpcSender<()>),
+}
@@ -0,0 +1,427 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use ipc_channel::ipc::IpcSender;
+use servo_url::ImmutableOrigin;
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::ops::Bound;
+
+/// Identifies a connection to a database, across all the databases of the backend.
+pub type ConnectionId = u64;
+
+/// The id the backend gives to the upgrade transaction of a connection.
+/// Script numbers the other transactions of a connection from 1.
+pub const UPGRADE_TRANSACTION_ID: u64 = 0;
+
+/// <https://w3c.github.io/IndexedDB/#key-construct>
+#[derive(Clone, Debug, Deserialize, MallocSizeOf, Serialize)]
+pub enum IndexedDBKeyType {
+    Number(f64),
+    /// The time value of a date, in milliseconds since the epoch.
+    Date(f64),
+    String(String),
+    Binary(Vec<u8>),
+    Array(Vec<IndexedDBKeyType>),
+}
+
+impl IndexedDBKeyType {
+    /// The position of the type of the key in the order of key types.
+    fn type_order(&self) -> u8 {
+        match *self {
+            IndexedDBKeyType::Number(_) => 0,
+            IndexedDBKeyType::Date(_) => 1,
+            IndexedDBKeyType::String(_) => 2,
+            IndexedDBKeyType::Binary(_) => 3,
+            IndexedDBKeyType::Array(_) => 4,
+        }
+    }
+}
+
+/// <https://w3c.github.io/IndexedDB/#compare-two-keys>
+impl Ord for IndexedDBKeyType {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (IndexedDBKeyType::Number(a), IndexedDBKeyType::Number(b)) |
+            (IndexedDBKeyType::Date(a), IndexedDBKeyType::Date(b)) => {
+                // Keys are never NaN.
+                a.partial_cmp(b).unwrap_or(Ordering::Equal)
+            },
+            // Strings compare by code units, not by code points.
+            (IndexedDBKeyType::String(a), IndexedDBKeyType::String(b)) => {
+                a.encode_utf16().cmp(b.encode_utf16())
+            },
+            (IndexedDBKeyType::Binary(a), IndexedDBKeyType::Binary(b)) => a.cmp(b),
+            (IndexedDBKeyType::Array(a), IndexedDBKeyType::Array(b)) => a.cmp(b),
+            _ => self.type_order().cmp(&other.type_order()),
+        }
+    }
+}
+
+impl PartialOrd for IndexedDBKeyType {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for IndexedDBKeyType {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for IndexedDBKeyType {}
+
+/// <https://w3c.github.io/IndexedDB/#range-construct>
+#[derive(Clone, Debug, Deserialize, MallocSizeOf, Serialize)]
+pub struct IndexedDBKeyRange {
+    pub lower: Option<IndexedDBKeyType>,
+    pub upper: Option<IndexedDBKeyType>,
+    pub lower_open: bool,
+    pub upper_open: bool,
+}
+
+impl IndexedDBKeyRange {
+    /// The range that contains every key.
+    pub fn unbounded() -> IndexedDBKeyRange {
+        IndexedDBKeyRange {
+            lower: None,
+            upper: None,
+            lower_open: false,
+            upper_open: false,
+        }
+    }
+
+    /// The range that only contains `key`.
+    pub fn only(key: IndexedDBKeyType) -> IndexedDBKeyRange {
+        IndexedDBKeyRange {
+            lower: Some(key.clone()),
+            upper: Some(key),
+            lower_open: false,
+            upper_open: false,
+        }
+    }
+
+    /// <https://w3c.github.io/IndexedDB/#in>
+    pub fn contains(&self, key: &IndexedDBKeyType) -> bool {
+        let above_lower = match self.lower {
+            Some(ref lower) if self.lower_open => key > lower,
+            Some(ref lower) => key >= lower,
+            None => true,
+        };
+        let below_upper = match self.upper {
+            Some(ref upper) if self.upper_open => key < upper,
+            Some(ref upper) => key <= upper,
+            None => true,
+        };
+        above_lower && below_upper
+    }
+
+    /// The bounds of the range, suitable for `BTreeMap::range`,
+    /// or `None` if no key is in the range.
+    pub fn bounds(&self) -> Option<(Bound<&IndexedDBKeyType>, Bound<&IndexedDBKeyType>)> {
+        if let (Some(lower), Some(upper)) = (&self.lower, &self.upper) {
+            match lower.cmp(upper) {
+                Ordering::Greater => return None,
+                Ordering::Equal if self.lower_open || self.upper_open => return None,
+                _ => {},
+            }
+        }
+        let bound = |key: &Option<IndexedDBKeyType>, open: bool| match *key {
+            Some(ref key) if open => Bound::Excluded(key),
+            Some(ref key) => Bound::Included(key),
+            None => Bound::Unbounded,
+        };
+        Some((
+            bound(&self.lower, self.lower_open),
+            bound(&self.upper, self.upper_open),
+        ))
+    }
+}
+
+/// <https://w3c.github.io/IndexedDB/#key-path-construct>
+#[derive(Clone, Debug, Deserialize, MallocSizeOf, PartialEq, Serialize)]
+pub enum KeyPath {
+    String(String),
+    Sequence(Vec<String>),
+}
+
+#[derive(Clone, Debug, Deserialize, MallocSizeOf, Serialize)]
+pub struct IndexMetadata {
+    pub name: String,
+    pub key_path: KeyPath,
+    pub unique: bool,
+    pub multi_entry: bool,
+}
+
+#[derive(Clone, Debug, Deserialize, MallocSizeOf, Serialize)]
+pub struct ObjectStoreMetadata {
+    pub name: String,
+    pub key_path: Option<KeyPath>,
+    pub auto_increment: bool,
+    pub indexes: BTreeMap<String, IndexMetadata>,
+}
+
+/// The schema of a database, which only changes in upgrade transactions.
+#[derive(Clone, Debug, Default, Deserialize, MallocSizeOf, Serialize)]
+pub struct DatabaseMetadata {
+    pub name: String,
+    /// The version of the database, which is 0 until it is first created.
+    pub version: u64,
+    pub object_stores: BTreeMap<String, ObjectStoreMetadata>,
+}
+
+/// <https://w3c.github.io/IndexedDB/#transaction-mode>
+#[derive(Clone, Copy, Debug, Deserialize, Eq, MallocSizeOf, PartialEq, Serialize)]
+pub enum IndexedDBTxnMode {
+    Readonly,
+    Readwrite,
+    Versionchange,
+}
+
+/// <https://w3c.github.io/IndexedDB/#cursor-direction>
+#[derive(Clone, Copy, Debug, Deserialize, Eq, MallocSizeOf, PartialEq, Serialize)]
+pub enum CursorDirection {
+    Next,
+    NextUnique,
+    Prev,
+    PrevUnique,
+}
+
+/// The records an operation reads from: those of an object store, or of one of its indexes.
+#[derive(Clone, Debug, Deserialize, MallocSizeOf, Serialize)]
+pub enum RecordSource {
+    ObjectStore(String),
+    /// The name of an object store and of one of its indexes.
+    Index(String, String),
+}
+
+impl RecordSource {
+    pub fn object_store(&self) -> &str {
+        match *self {
+            RecordSource::ObjectStore(ref store) | RecordSource::Index(ref store, _) => store,
+        }
+    }
+}
+
+/// The operations script requests within a transaction. The backend runs them
+/// in the order they were requested, once the transaction has started.
+#[derive(Debug, Deserialize, Serialize)]
+pub enum AsyncOperation {
+    /// Store a value, generating a key for it if `key` is `None`.
+    /// `index_keys` are the keys of the value in each index of the store.
+    Put {
+        store: String,
+        key: Option<IndexedDBKeyType>,
+        value: Vec<u8>,
+        index_keys: BTreeMap<String, Vec<IndexedDBKeyType>>,
+        no_overwrite: bool,
+    },
+    /// Read the first record in `query`.
+    Get {
+        source: RecordSource,
+        query: IndexedDBKeyRange,
+        keys_only: bool,
+    },
+    /// Read the first `count` records in `query`, or all of them.
+    GetAll {
+        source: RecordSource,
+        query: IndexedDBKeyRange,
+        count: Option<u32>,
+        keys_only: bool,
+    },
+    Count {
+        source: RecordSource,
+        query: IndexedDBKeyRange,
+    },
+    Delete {
+        store: String,
+        query: IndexedDBKeyRange,
+    },
+    Clear {
+        store: String,
+    },
+    /// <https://w3c.github.io/IndexedDB/#iterate-a-cursor>
+    ///
+    /// `position` is the key and primary key the cursor is at, if any.
+    Iterate {
+        source: RecordSource,
+        query: IndexedDBKeyRange,
+        direction: CursorDirection,
+        key: Option<IndexedDBKeyType>,
+        primary_key: Option<IndexedDBKeyType>,
+        position: Option<(IndexedDBKeyType, IndexedDBKeyType)>,
+        count: u32,
+        keys_only: bool,
+    },
+    CreateObjectStore {
+        name: String,
+        key_path: Option<KeyPath>,
+        auto_increment: bool,
+    },
+    DeleteObjectStore {
+        name: String,
+    },
+    RenameObjectStore {
+        name: String,
+        new_name: String,
+    },
+    /// Create an empty index, and read the records of the store so that script can compute
+    /// their keys in the index. The operations that follow wait for those keys,
+    /// which script sends with `IndexedDBThreadMsg::PopulateIndex`.
+    CreateIndex {
+        store: String,
+        index: IndexMetadata,
+    },
+    DeleteIndex {
+        store: String,
+        name: String,
+    },
+    RenameIndex {
+        store: String,
+        name: String,
+        new_name: String,
+    },
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct IndexedDBRecord {
+    /// The key of the record in its source.
+    pub key: IndexedDBKeyType,
+    pub primary_key: IndexedDBKeyType,
+    /// The serialized value, unless only keys were requested.
+    pub value: Option<Vec<u8>>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub enum OperationResult {
+    Done,
+    /// The key a value was stored with.
+    Key(IndexedDBKeyType),
+    Records(Vec<IndexedDBRecord>),
+    Count(u64),
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum BackendError {
+    /// A uniqueness constraint would be violated, or the key generator is exhausted.
+    Constraint,
+    /// The requested version is lower than the version of the database.
+    Version,
+    /// The operation was aborted, along with its transaction.
+    Abort,
+}
+
+/// Request operations on the databases of an origin.
+#[derive(Debug, Deserialize, Serialize)]
+pub enum IndexedDBThreadMsg {
+    /// Open a connection to a database, creating or upgrading it if needed.
+    /// The progress of the request, and then the events of the connection, are sent to `events`.
+    Open {
+        origin: ImmutableOrigin,
+        name: String,
+        version: Option<u64>,
+        events: IpcSender<ConnectionEvent>,
+    },
+
+    /// Delete a database once every connection to it is closed.
+    DeleteDatabase {
+        origin: ImmutableOrigin,
+        name: String,
+        events: IpcSender<ConnectionEvent>,
+    },
+
+    /// Gets the names and versions of the databases of an origin.
+    Databases(IpcSender<Vec<(String, u64)>>, ImmutableOrigin),
+
+    /// Create a transaction, which starts once no earlier transaction it conflicts with is running.
+    CreateTransaction {
+        connection: ConnectionId,
+        transaction: u64,
+        scope: Vec<String>,
+        mode: IndexedDBTxnMode,
+    },
+
+    /// Queue an operation in a transaction; its result is sent as a `RequestResult`.
+    Request {
+        connection: ConnectionId,
+        transaction: u64,
+        request: u64,
+        operation: AsyncOperation,
+    },
+
+    /// The keys of each record in the index that a `CreateIndex` operation created,
+    /// as pairs of a primary key and the keys of its value in the index.
+    PopulateIndex {
+        connection: ConnectionId,
+        transaction: u64,
+        store: String,
+        index: String,
+        keys: Vec<(IndexedDBKeyType, Vec<IndexedDBKeyType>)>,
+    },
+
+    /// Commit a transaction once its queued operations have run.
+    Commit {
+        connection: ConnectionId,
+        transaction: u64,
+    },
+
+    /// Abort a transaction, undoing its changes.
+    Abort {
+        connection: ConnectionId,
+        transaction: u64,
+    },
+
+    /// Close a connection once its transactions have finished.
+    Close(ConnectionId),
+
+    /// Script has fired the versionchange event that a `VersionChange` asked for.
+    VersionChangeHandled(ConnectionId),
+
+    /// Delete the databases of an origin, once their connections have closed.
+    ClearOrigin(ImmutableOrigin),
+
+    /// send a reply when done cleaning up thread resources and then shut it down
+    Exit(IpcSender<()>),
+}
+
+/// The events of an open or delete request, and then of the connection it opened.
+#[derive(Debug, Deserialize, Serialize)]
+pub enum ConnectionEvent {
+    /// Other connections are still open after being asked to close.
+    Blocked {
+        old_version: u64,
+        new_version: Option<u64>,
+    },
+    /// The database needs upgrading, in the upgrade transaction of `connection`.
+    UpgradeNeeded {
+        connection: ConnectionId,
+        old_version: u64,
+        metadata: DatabaseMetadata,
+    },
+    Opened {
+        connection: ConnectionId,
+        metadata: DatabaseMetadata,
+    },
+    OpenFailed(BackendError),
+    Deleted {
+        old_version: u64,
+    },
+    /// Another request wants to upgrade or delete the database; `VersionChangeHandled`
+    /// is expected once the connection had a chance to close.
+    VersionChange {
+        old_version: u64,
+        new_version: Option<u64>,
+    },
+    RequestResult {
+        transaction: u64,
+        request: u64,
+        result: Result<OperationResult, BackendError>,
+    },
+    /// A transaction committed, or was aborted with the given error.
+    TransactionFinished {
+        transaction: u64,
+        result: Result<(), BackendError>,
+    },
+}
@@ -15,13 +15,18 @@ extern crate malloc_size_of_derive;
 #[macro_use]
 extern crate serde;
 
+use crate::cache_storage_thread::CacheStorageThreadMsg;
 use crate::filemanager_thread::FileManagerThreadMsg;
-use crate::request::{Request, RequestBuilder};
+use crate::indexeddb_thread::IndexedDBThreadMsg;
+use crate::quota_thread::QuotaThreadMsg;
+use crate::request::{Destination, Request, RequestBuilder, RequestMode};
 use crate::response::{HttpsState, Response, ResponseInit};
 use crate::storage_thread::StorageThreadMsg;
+use content_security_policy as csp;
 use cookie::Cookie;
+use embedder_traits::DownloadId;
 use headers::{ContentType, HeaderMapExt, ReferrerPolicy as ReferrerPolicyHeader};
-use http::{Error as HttpError, HeaderMap};
+use http::{header, Error as HttpError, HeaderMap, Method};
 use hyper::Error as HyperError;
 use hyper::StatusCode;
 use hyper_serde::Serde;
@@ -29,16 +34,19 @@ use ipc_channel::ipc::{self, IpcReceiver, IpcSender};
 use ipc_channel::router::ROUTER;
 use ipc_channel::Error as IpcError;
 use mime::Mime;
-use msg::constellation_msg::HistoryStateId;
-use servo_url::ServoUrl;
+use msg::constellation_msg::{HistoryStateId, PipelineId, TopLevelBrowsingContextId};
+use servo_url::{ImmutableOrigin, ServoUrl};
 use time::precise_time_ns;
 use webrender_api::ImageKey;
 
 pub mod blob_url_store;
+pub mod cache_storage_thread;
 pub mod filemanager_thread;
 pub mod image_cache;
+pub mod indexeddb_thread;
 pub mod pub_domains;
 pub mod quality;
+pub mod quota_thread;
 pub mod request;
 pub mod response;
 pub mod storage_thread;
@@ -98,10 +106,30 @@ impl CustomResponse {
     }
 }
 
+/// A request for a service worker to handle a fetch.
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct CustomResponseMediator {
-    pub response_chan: IpcSender<Option<CustomResponse>>,
+    /// Receives the response of the service worker, `None` if the request should
+    /// go to the network, or an error if the fetch should fail.
+    pub response_chan: IpcSender<Result<Option<CustomResponse>, NetworkError>>,
     pub load_url: ServoUrl,
+    #[serde(
+        deserialize_with = "::hyper_serde::deserialize",
+        serialize_with = "::hyper_serde::serialize"
+    )]
+    pub method: Method,
+    #[serde(
+        deserialize_with = "::hyper_serde::deserialize",
+        serialize_with = "::hyper_serde::serialize"
+    )]
+    pub headers: HeaderMap,
+    /// The body of the request, which is sent whole to the service worker.
+    pub body: Option<Vec<u8>>,
+    pub mode: RequestMode,
+    pub destination: Destination,
+    /// The client of the request: the document a navigation loads, or the one
+    /// that made a subresource request.
+    pub client: Option<PipelineId>,
 }
 
 /// [Policies](https://w3c.github.io/webappsec-referrer-policy/#referrer-policy-states)
@@ -145,6 +173,173 @@ impl From<ReferrerPolicyHeader> for ReferrerPolicy {
     }
 }
 
+/// The value of a [cross-origin opener policy](https://html.spec.whatwg.org/multipage/#cross-origin-opener-policy-value),
+/// which decides whether a document shares its browsing context group with its opener.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, MallocSizeOf, PartialEq, Serialize)]
+pub enum CrossOriginOpenerPolicy {
+    /// "unsafe-none"
+    UnsafeNone,
+    /// "same-origin-allow-popups"
+    SameOriginAllowPopups,
+    /// "same-origin"
+    SameOrigin,
+}
+
+impl Default for CrossOriginOpenerPolicy {
+    fn default() -> Self {
+        CrossOriginOpenerPolicy::UnsafeNone
+    }
+}
+
+impl CrossOriginOpenerPolicy {
+    /// <https://html.spec.whatwg.org/multipage/#obtain-coop>
+    pub fn from_response_headers(url: &ServoUrl, headers: &HeaderMap) -> CrossOriginOpenerPolicy {
+        match policy_header_value(url, headers, "cross-origin-opener-policy") {
+            Some("same-origin") => CrossOriginOpenerPolicy::SameOrigin,
+            Some("same-origin-allow-popups") => CrossOriginOpenerPolicy::SameOriginAllowPopups,
+            _ => CrossOriginOpenerPolicy::UnsafeNone,
+        }
+    }
+}
+
+/// The value of an [embedder policy](https://html.spec.whatwg.org/multipage/#embedder-policy-value),
+/// which decides whether a document may load cross-origin resources that don't opt into it.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, MallocSizeOf, PartialEq, Serialize)]
+pub enum CrossOriginEmbedderPolicy {
+    /// "unsafe-none"
+    UnsafeNone,
+    /// "require-corp"
+    RequireCorp,
+}
+
+impl Default for CrossOriginEmbedderPolicy {
+    fn default() -> Self {
+        CrossOriginEmbedderPolicy::UnsafeNone
+    }
+}
+
+impl CrossOriginEmbedderPolicy {
+    /// <https://html.spec.whatwg.org/multipage/#obtain-an-embedder-policy>
+    pub fn from_response_headers(url: &ServoUrl, headers: &HeaderMap) -> CrossOriginEmbedderPolicy {
+        match policy_header_value(url, headers, "cross-origin-embedder-policy") {
+            Some("require-corp") => CrossOriginEmbedderPolicy::RequireCorp,
+            _ => CrossOriginEmbedderPolicy::UnsafeNone,
+        }
+    }
+}
+
+/// The token of a policy header, which is a structured header item whose parameters
+/// are ignored. The policy headers are only honoured in secure contexts.
+fn policy_header_value<'a>(url: &ServoUrl, headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    let is_loopback = url.host_str() == Some("localhost") ||
+        url.host_str() == Some("127.0.0.1") ||
+        url.host_str() == Some("[::1]");
+    if !url.is_secure_scheme() && !is_loopback {
+        return None;
+    }
+    let mut values = headers.get_all(name).iter();
+    let value = values.next()?;
+    if values.next().is_some() {
+        return None;
+    }
+    value.to_str().ok()?.split(';').next().map(str::trim)
+}
+
+/// The `Content-Disposition` of a response, <https://tools.ietf.org/html/rfc6266>.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ContentDisposition {
+    /// Whether the response is to be downloaded rather than displayed.
+    pub is_attachment: bool,
+    /// The name the server suggests saving the response as.
+    pub filename: Option<String>,
+}
+
+impl ContentDisposition {
+    pub fn from_response_headers(headers: &HeaderMap) -> Option<ContentDisposition> {
+        let value = headers.get(header::CONTENT_DISPOSITION)?;
+        let value = String::from_utf8_lossy(value.as_bytes());
+        let mut parts = split_header_parameters(&value).into_iter();
+        let disposition_type = parts.next()?;
+        // Unknown disposition types are handled like attachment,
+        // https://tools.ietf.org/html/rfc6266#section-4.2
+        let is_attachment = !disposition_type.eq_ignore_ascii_case("inline");
+
+        let mut filename = None;
+        let mut extended_filename = None;
+        for part in parts {
+            let mut parameter = part.splitn(2, '=');
+            let name = parameter.next().unwrap_or("").trim().to_ascii_lowercase();
+            let value = match parameter.next() {
+                Some(value) => value.trim(),
+                None => continue,
+            };
+            match &*name {
+                "filename" => filename = Some(unquote(value)),
+                "filename*" => extended_filename = decode_extended_value(value),
+                _ => {},
+            }
+        }
+        Some(ContentDisposition {
+            is_attachment,
+            filename: extended_filename
+                .or(filename)
+                .filter(|name| !name.is_empty()),
+        })
+    }
+}
+
+/// Split a header value on the semicolons that aren't in quoted strings.
+fn split_header_parameters(value: &str) -> Vec<&str> {
+    let mut parts = vec![];
+    let mut start = 0;
+    let mut in_quotes = false;
+    let mut escaped = false;
+    for (index, c) in value.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_quotes => escaped = true,
+            '"' => in_quotes = !in_quotes,
+            ';' if !in_quotes => {
+                parts.push(value[start..index].trim());
+                start = index + 1;
+            },
+            _ => {},
+        }
+    }
+    parts.push(value[start..].trim());
+    parts
+}
+
+fn unquote(value: &str) -> String {
+    if value.len() < 2 || !value.starts_with('"') || !value.ends_with('"') {
+        return value.to_owned();
+    }
+    let mut unquoted = String::with_capacity(value.len());
+    let mut chars = value[1..value.len() - 1].chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unquoted.extend(chars.next()),
+            c => unquoted.push(c),
+        }
+    }
+    unquoted
+}
+
+/// <https://tools.ietf.org/html/rfc5987#section-3.2>
+fn decode_extended_value(value: &str) -> Option<String> {
+    let mut parts = value.splitn(3, '\'');
+    let charset = parts.next()?;
+    let _language = parts.next()?;
+    let bytes = percent_encoding::percent_decode_str(parts.next()?);
+    if charset.eq_ignore_ascii_case("utf-8") {
+        bytes.decode_utf8().ok().map(|value| value.into_owned())
+    } else if charset.eq_ignore_ascii_case("iso-8859-1") {
+        Some(bytes.map(char::from).collect())
+    } else {
+        None
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 pub enum FetchResponseMsg {
     // todo: should have fields for transmitted/total bytes
@@ -154,6 +349,7 @@ pub enum FetchResponseMsg {
     ProcessResponse(Result<FetchMetadata, NetworkError>),
     ProcessResponseChunk(Vec<u8>),
     ProcessResponseEOF(Result<ResourceFetchTiming, NetworkError>),
+    ProcessCspViolations(Vec<csp::Violation>),
 }
 
 pub trait FetchTaskTarget {
@@ -179,6 +375,11 @@ pub trait FetchTaskTarget {
     ///
     /// Fired when the response is fully fetched
     fn process_response_eof(&mut self, response: &Response);
+
+    /// <https://www.w3.org/TR/CSP/#report-for-request>
+    ///
+    /// Fired when the request or its response violates a content security policy
+    fn process_csp_violations(&mut self, request: &Request, violations: Vec<csp::Violation>);
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -204,6 +405,7 @@ pub trait FetchResponseListener {
     fn process_response(&mut self, metadata: Result<FetchMetadata, NetworkError>);
     fn process_response_chunk(&mut self, chunk: Vec<u8>);
     fn process_response_eof(&mut self, response: Result<ResourceFetchTiming, NetworkError>);
+    fn process_csp_violations(&mut self, violations: Vec<csp::Violation>);
     fn resource_timing(&self) -> &ResourceFetchTiming;
     fn resource_timing_mut(&mut self) -> &mut ResourceFetchTiming;
     fn submit_resource_timing(&mut self);
@@ -237,6 +439,10 @@ impl FetchTaskTarget for IpcSender<FetchResponseMsg> {
                 .clone())));
         }
     }
+
+    fn process_csp_violations(&mut self, _: &Request, violations: Vec<csp::Violation>) {
+        let _ = self.send(FetchResponseMsg::ProcessCspViolations(violations));
+    }
 }
 
 /// A fetch task that discards all data it's sent,
@@ -254,6 +460,8 @@ impl FetchTaskTarget for DiscardFetch {
     fn process_response_chunk(&mut self, _: Vec<u8>) {}
 
     fn process_response_eof(&mut self, _: &Response) {}
+
+    fn process_csp_violations(&mut self, _: &Request, _: Vec<csp::Violation>) {}
 }
 
 pub trait Action<Listener> {
@@ -285,6 +493,9 @@ impl<T: FetchResponseListener> Action<T> for FetchResponseMsg {
                     Err(e) => listener.process_response_eof(Err(e)),
                 }
             },
+            FetchResponseMsg::ProcessCspViolations(violations) => {
+                listener.process_csp_violations(violations)
+            },
         }
     }
 }
@@ -316,13 +527,25 @@ where
 pub struct ResourceThreads {
     core_thread: CoreResourceThread,
     storage_thread: IpcSender<StorageThreadMsg>,
+    indexeddb_thread: IpcSender<IndexedDBThreadMsg>,
+    cache_storage_thread: IpcSender<CacheStorageThreadMsg>,
+    quota_thread: IpcSender<QuotaThreadMsg>,
 }
 
 impl ResourceThreads {
-    pub fn new(c: CoreResourceThread, s: IpcSender<StorageThreadMsg>) -> ResourceThreads {
+    pub fn new(
+        c: CoreResourceThread,
+        s: IpcSender<StorageThreadMsg>,
+        i: IpcSender<IndexedDBThreadMsg>,
+        cs: IpcSender<CacheStorageThreadMsg>,
+        q: IpcSender<QuotaThreadMsg>,
+    ) -> ResourceThreads {
         ResourceThreads {
             core_thread: c,
             storage_thread: s,
+            indexeddb_thread: i,
+            cache_storage_thread: cs,
+            quota_thread: q,
         }
     }
 }
@@ -347,6 +570,36 @@ impl IpcSend<StorageThreadMsg> for ResourceThreads {
     }
 }
 
+impl IpcSend<IndexedDBThreadMsg> for ResourceThreads {
+    fn send(&self, msg: IndexedDBThreadMsg) -> IpcSendResult {
+        self.indexeddb_thread.send(msg)
+    }
+
+    fn sender(&self) -> IpcSender<IndexedDBThreadMsg> {
+        self.indexeddb_thread.clone()
+    }
+}
+
+impl IpcSend<CacheStorageThreadMsg> for ResourceThreads {
+    fn send(&self, msg: CacheStorageThreadMsg) -> IpcSendResult {
+        self.cache_storage_thread.send(msg)
+    }
+
+    fn sender(&self) -> IpcSender<CacheStorageThreadMsg> {
+        self.cache_storage_thread.clone()
+    }
+}
+
+impl IpcSend<QuotaThreadMsg> for ResourceThreads {
+    fn send(&self, msg: QuotaThreadMsg) -> IpcSendResult {
+        self.quota_thread.send(msg)
+    }
+
+    fn sender(&self) -> IpcSender<QuotaThreadMsg> {
+        self.quota_thread.clone()
+    }
+}
+
 // Ignore the sub-fields
 malloc_size_of_is_0!(ResourceThreads);
 
@@ -370,10 +623,14 @@ pub enum WebSocketDomAction {
 
 #[derive(Debug, Deserialize, Serialize)]
 pub enum WebSocketNetworkEvent {
-    ConnectionEstablished { protocol_in_use: Option<String> },
+    ConnectionEstablished {
+        protocol_in_use: Option<String>,
+        extensions_in_use: Option<String>,
+    },
     MessageReceived(MessageData),
     Close(Option<u16>, String),
     Fail,
+    ReportCspViolations(Vec<csp::Violation>),
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -404,10 +661,17 @@ pub enum CoreResourceMsg {
     ),
     /// Store a cookie for a given originating URL
     SetCookieForUrl(ServoUrl, Serde<Cookie<'static>>, CookieSource),
-    /// Store a set of cookies for a given originating URL
-    SetCookiesForUrl(ServoUrl, Vec<Serde<Cookie<'static>>>, CookieSource),
-    /// Retrieve the stored cookies for a given URL
-    GetCookiesForUrl(ServoUrl, IpcSender<Option<String>>, CookieSource),
+    /// Store a set of cookie strings for a given originating URL, set by a document
+    /// whose ancestors have the given origins
+    SetCookiesForUrl(ServoUrl, Vec<String>, Vec<ImmutableOrigin>, CookieSource),
+    /// Retrieve the stored cookies for a given URL, read by a document
+    /// whose ancestors have the given origins
+    GetCookiesForUrl(
+        ServoUrl,
+        Vec<ImmutableOrigin>,
+        IpcSender<Option<String>>,
+        CookieSource,
+    ),
     /// Get a cookie by name for a given originating URL
     GetCookiesDataForUrl(
         ServoUrl,
@@ -415,6 +679,8 @@ pub enum CoreResourceMsg {
         CookieSource,
     ),
     DeleteCookies(ServoUrl),
+    /// Remove the responses to requests for the URLs of an origin from the HTTP cache
+    ClearHttpCache(ImmutableOrigin),
     /// Get a history state by a given history state id
     GetHistoryState(HistoryStateId, IpcSender<Option<Vec<u8>>>),
     /// Set a history state for a given history state id
@@ -427,6 +693,17 @@ pub enum CoreResourceMsg {
     NetworkMediator(IpcSender<CustomResponseMediator>),
     /// Message forwarded to file manager's handler
     ToFileManager(FileManagerThreadMsg),
+    /// Save the response to a request to disk, with the file name a page suggests for it,
+    /// reporting the progress to the embedder on behalf of the given browser.
+    Download(
+        RequestBuilder,
+        Option<String>,
+        Option<TopLevelBrowsingContextId>,
+    ),
+    /// Stop a download, keeping what is on disk so far.
+    CancelDownload(DownloadId),
+    /// Continue a download that was cancelled or failed from where it stopped.
+    ResumeDownload(DownloadId),
     /// Break the load handler loop, send a reply when done cleaning up local resources
     /// and exit
     Exit(IpcSender<()>),
@@ -475,6 +752,9 @@ pub struct ResourceFetchTiming {
     pub connect_start: u64,
     pub connect_end: u64,
     pub start_time: u64,
+    /// The ALPN protocol ID of the protocol the resource was fetched with,
+    /// <https://w3c.github.io/resource-timing/#dom-performanceresourcetiming-nexthopprotocol>.
+    pub next_hop_protocol: Option<String>,
 }
 
 pub enum RedirectStartValue {
@@ -507,7 +787,8 @@ pub enum ResourceAttribute {
     FetchStart,
     ConnectStart(u64),
     ConnectEnd(u64),
-    SecureConnectionStart,
+    SecureConnectionStart(u64),
+    NextHopProtocol(String),
     ResponseEnd,
     StartTime(ResourceTimeValue),
 }
@@ -537,6 +818,7 @@ impl ResourceFetchTiming {
             connect_end: 0,
             response_end: 0,
             start_time: 0,
+            next_hop_protocol: None,
         }
     }
 
@@ -572,9 +854,8 @@ impl ResourceFetchTiming {
             ResourceAttribute::FetchStart => self.fetch_start = precise_time_ns(),
             ResourceAttribute::ConnectStart(val) => self.connect_start = val,
             ResourceAttribute::ConnectEnd(val) => self.connect_end = val,
-            ResourceAttribute::SecureConnectionStart => {
-                self.secure_connection_start = precise_time_ns()
-            },
+            ResourceAttribute::SecureConnectionStart(val) => self.secure_connection_start = val,
+            ResourceAttribute::NextHopProtocol(protocol) => self.next_hop_protocol = Some(protocol),
             ResourceAttribute::ResponseEnd => self.response_end = precise_time_ns(),
             ResourceAttribute::StartTime(val) => match val {
                 ResourceTimeValue::RedirectStart
@@ -602,6 +883,8 @@ impl ResourceFetchTiming {
         self.redirect_start = 0;
         self.connect_start = 0;
         self.connect_end = 0;
+        self.secure_connection_start = 0;
+        self.next_hop_protocol = None;
     }
 }
 
@@ -675,6 +958,14 @@ impl Metadata {
             self.content_type = Some(Serde(ContentType::from(mime.clone())));
         }
     }
+
+    /// Whether the response is to be downloaded rather than displayed.
+    pub fn is_attachment(&self) -> bool {
+        self.headers
+            .as_ref()
+            .and_then(|headers| ContentDisposition::from_response_headers(headers))
+            .map_or(false, |disposition| disposition.is_attachment)
+    }
 }
 
 /// The creator of a given cookie
@@ -0,0 +1,56 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use ipc_channel::ipc::IpcSender;
+use servo_url::ImmutableOrigin;
+
+/// The stores whose data counts against the quota of an origin.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub enum QuotaClient {
+    LocalStorage,
+    IndexedDB,
+    CacheStorage,
+    HttpCache,
+}
+
+/// <https://storage.spec.whatwg.org/#dictdef-storageestimate>
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct StorageEstimate {
+    /// The number of bytes the origin uses, across every store.
+    pub usage: u64,
+    /// The number of bytes the origin can use.
+    pub quota: u64,
+}
+
+/// Request operations on the usage and quota of origins.
+#[derive(Debug, Deserialize, Serialize)]
+pub enum QuotaThreadMsg {
+    /// A store reports how many bytes an origin uses in it.
+    SetUsage(ImmutableOrigin, QuotaClient, u64),
+
+    /// Whether a store can hold the given number of bytes for an origin, within its quota.
+    /// If it can, the usage is recorded as if it was reported.
+    RequestSpace {
+        origin: ImmutableOrigin,
+        client: QuotaClient,
+        usage: u64,
+        sender: IpcSender<bool>,
+    },
+
+    /// Gets the usage and quota of an origin.
+    Estimate(ImmutableOrigin, IpcSender<StorageEstimate>),
+
+    /// Mark the data of an origin as persistent, so that it is never evicted,
+    /// replying whether it is.
+    Persist(ImmutableOrigin, IpcSender<bool>),
+
+    /// Whether the data of an origin is persistent.
+    Persisted(ImmutableOrigin, IpcSender<bool>),
+
+    /// Clear the data of an origin in every store, and forget its persistence.
+    ClearOrigin(ImmutableOrigin),
+
+    /// send a reply when done cleaning up thread resources and then shut it down
+    Exit(IpcSender<()>),
+}
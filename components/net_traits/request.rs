@@ -2,11 +2,12 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 
-use crate::ReferrerPolicy;
 use crate::ResourceTimingType;
+use crate::{CrossOriginEmbedderPolicy, ReferrerPolicy};
 use content_security_policy::{self as csp, CspList};
 use http::HeaderMap;
 use hyper::Method;
+use ipc_channel::ipc::IpcSender;
 use mime::Mime;
 use msg::constellation_msg::PipelineId;
 use servo_url::{ImmutableOrigin, ServoUrl};
@@ -147,15 +148,19 @@ pub struct RequestBuilder {
     pub pipeline_id: Option<PipelineId>,
     pub redirect_mode: RedirectMode,
     pub integrity_metadata: String,
+    pub cryptographic_nonce_metadata: String,
     // This is nominally a part of the client's global object.
     // It is copied here to avoid having to reach across the thread
     // boundary every time a redirect occurs.
     #[ignore_malloc_size_of = "Defined in rust-content-security-policy"]
     pub csp_list: Option<CspList>,
+    // This is nominally a part of the client's policy container.
+    pub embedder_policy: CrossOriginEmbedderPolicy,
     // to keep track of redirects
     pub url_list: Vec<ServoUrl>,
     pub parser_metadata: ParserMetadata,
     pub initiator: Initiator,
+    pub top_level_navigation: bool,
 }
 
 impl RequestBuilder {
@@ -180,10 +185,13 @@ impl RequestBuilder {
             pipeline_id: None,
             redirect_mode: RedirectMode::Follow,
             integrity_metadata: "".to_owned(),
+            cryptographic_nonce_metadata: "".to_owned(),
             url_list: vec![],
             parser_metadata: ParserMetadata::Default,
             initiator: Initiator::None,
             csp_list: None,
+            embedder_policy: CrossOriginEmbedderPolicy::UnsafeNone,
+            top_level_navigation: false,
         }
     }
 
@@ -272,11 +280,39 @@ impl RequestBuilder {
         self
     }
 
+    pub fn cryptographic_nonce_metadata(mut self, nonce: String) -> RequestBuilder {
+        self.cryptographic_nonce_metadata = nonce;
+        self
+    }
+
+    pub fn csp_list(mut self, csp_list: Option<CspList>) -> RequestBuilder {
+        self.csp_list = csp_list;
+        self
+    }
+
+    pub fn embedder_policy(mut self, embedder_policy: CrossOriginEmbedderPolicy) -> RequestBuilder {
+        self.embedder_policy = embedder_policy;
+        self
+    }
+
     pub fn parser_metadata(mut self, parser_metadata: ParserMetadata) -> RequestBuilder {
         self.parser_metadata = parser_metadata;
         self
     }
 
+    pub fn top_level_navigation(mut self, top_level_navigation: bool) -> RequestBuilder {
+        self.top_level_navigation = top_level_navigation;
+        self
+    }
+
+    pub fn service_workers_mode(
+        mut self,
+        service_workers_mode: ServiceWorkersMode,
+    ) -> RequestBuilder {
+        self.service_workers_mode = service_workers_mode;
+        self
+    }
+
     pub fn build(self) -> Request {
         let mut request = Request::new(
             self.url.clone(),
@@ -306,12 +342,27 @@ impl RequestBuilder {
         request.redirect_count = url_list.len() as u32 - 1;
         request.url_list = url_list;
         request.integrity_metadata = self.integrity_metadata;
+        request.cryptographic_nonce_metadata = self.cryptographic_nonce_metadata;
         request.parser_metadata = self.parser_metadata;
         request.csp_list = self.csp_list;
+        request.embedder_policy = self.embedder_policy;
+        request.top_level_navigation = self.top_level_navigation;
         request
     }
 }
 
+/// The content security policies of a document that fetches made on its behalf
+/// by other threads, such as web fonts, are subject to.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct CspContext {
+    /// The origin of the document.
+    pub origin: ImmutableOrigin,
+    /// The policies of the document.
+    pub csp_list: Option<CspList>,
+    /// Where to report the violations of the policies, to be delivered by the document.
+    pub violation_sender: IpcSender<Vec<csp::Violation>>,
+}
+
 /// A [Request](https://fetch.spec.whatwg.org/#concept-request) as defined by
 /// the Fetch spec.
 #[derive(Clone, MallocSizeOf)]
@@ -365,6 +416,8 @@ pub struct Request {
     pub redirect_mode: RedirectMode,
     /// <https://fetch.spec.whatwg.org/#concept-request-integrity-metadata>
     pub integrity_metadata: String,
+    /// <https://fetch.spec.whatwg.org/#concept-request-nonce-metadata>
+    pub cryptographic_nonce_metadata: String,
     // Use the last method on url_list to act as spec current url field, and
     // first method to act as spec url field
     /// <https://fetch.spec.whatwg.org/#concept-request-url-list>
@@ -380,6 +433,12 @@ pub struct Request {
     // boundary every time a redirect occurs.
     #[ignore_malloc_size_of = "Defined in rust-content-security-policy"]
     pub csp_list: Option<CspList>,
+    /// The embedder policy of the request's client, which decides whether responses
+    /// to no-cors requests have to opt into being embedded cross-origin.
+    pub embedder_policy: CrossOriginEmbedderPolicy,
+    /// Whether this request navigates a top-level browsing context,
+    /// which determines the "site for cookies" of the request.
+    pub top_level_navigation: bool,
 }
 
 impl Request {
@@ -408,11 +467,14 @@ impl Request {
             cache_mode: CacheMode::Default,
             redirect_mode: RedirectMode::Follow,
             integrity_metadata: String::new(),
+            cryptographic_nonce_metadata: String::new(),
             url_list: vec![url],
             parser_metadata: ParserMetadata::Default,
             redirect_count: 0,
             response_tainting: ResponseTainting::Basic,
             csp_list: None,
+            embedder_policy: CrossOriginEmbedderPolicy::UnsafeNone,
+            top_level_navigation: false,
         }
     }
 
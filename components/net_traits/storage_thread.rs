@@ -3,7 +3,7 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 
 use ipc_channel::ipc::IpcSender;
-use servo_url::ServoUrl;
+use servo_url::{ImmutableOrigin, ServoUrl};
 
 #[derive(Clone, Copy, Debug, Deserialize, MallocSizeOf, Serialize)]
 pub enum StorageType {
@@ -41,6 +41,9 @@ pub enum StorageThreadMsg {
     /// clears the associated storage data by removing all the key/value pairs
     Clear(IpcSender<bool>, ServoUrl, StorageType),
 
+    /// removes the local and session storage data of an origin
+    ClearOrigin(ImmutableOrigin),
+
     /// send a reply when done cleaning up thread resources and then shut it down
     Exit(IpcSender<()>),
 }
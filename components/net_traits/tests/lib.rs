@@ -2,7 +2,13 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 
+use http::header::{HeaderMap, HeaderValue};
+use net_traits::cache_storage_thread::CachedResponse;
+use net_traits::indexeddb_thread::{IndexedDBKeyRange, IndexedDBKeyType};
+use net_traits::response::ResponseType;
+use net_traits::{ContentDisposition, CrossOriginEmbedderPolicy, CrossOriginOpenerPolicy};
 use net_traits::{ResourceAttribute, ResourceFetchTiming, ResourceTimeValue, ResourceTimingType};
+use servo_url::ServoUrl;
 
 #[test]
 fn test_set_start_time_to_fetch_start_if_nonzero_tao() {
@@ -210,3 +216,147 @@ fn test_reset_start_time() {
         "failed to reset `start_time`"
     );
 }
+
+#[test]
+fn test_cross_origin_policies_from_response_headers() {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        "cross-origin-opener-policy",
+        HeaderValue::from_static("same-origin-allow-popups; report-to=\"coop\""),
+    );
+    headers.insert(
+        "cross-origin-embedder-policy",
+        HeaderValue::from_static("require-corp"),
+    );
+
+    let secure_url = ServoUrl::parse("https://example.com/").unwrap();
+    assert_eq!(
+        CrossOriginOpenerPolicy::from_response_headers(&secure_url, &headers),
+        CrossOriginOpenerPolicy::SameOriginAllowPopups
+    );
+    assert_eq!(
+        CrossOriginEmbedderPolicy::from_response_headers(&secure_url, &headers),
+        CrossOriginEmbedderPolicy::RequireCorp
+    );
+
+    // The headers are ignored outside of secure contexts.
+    let insecure_url = ServoUrl::parse("http://example.com/").unwrap();
+    assert_eq!(
+        CrossOriginOpenerPolicy::from_response_headers(&insecure_url, &headers),
+        CrossOriginOpenerPolicy::UnsafeNone
+    );
+    assert_eq!(
+        CrossOriginEmbedderPolicy::from_response_headers(&insecure_url, &headers),
+        CrossOriginEmbedderPolicy::UnsafeNone
+    );
+
+    headers.insert(
+        "cross-origin-opener-policy",
+        HeaderValue::from_static("same-origin-plus-coep"),
+    );
+    assert_eq!(
+        CrossOriginOpenerPolicy::from_response_headers(&secure_url, &headers),
+        CrossOriginOpenerPolicy::UnsafeNone
+    );
+}
+
+#[test]
+fn test_content_disposition_from_response_headers() {
+    let disposition = |value: &'static str| {
+        let mut headers = HeaderMap::new();
+        headers.insert("content-disposition", HeaderValue::from_static(value));
+        ContentDisposition::from_response_headers(&headers).unwrap()
+    };
+
+    assert_eq!(
+        ContentDisposition::from_response_headers(&HeaderMap::new()),
+        None
+    );
+    assert_eq!(
+        disposition("inline"),
+        ContentDisposition {
+            is_attachment: false,
+            filename: None,
+        }
+    );
+    assert_eq!(
+        disposition("Attachment; filename=report.pdf"),
+        ContentDisposition {
+            is_attachment: true,
+            filename: Some("report.pdf".to_owned()),
+        }
+    );
+    assert_eq!(
+        disposition(r#"attachment; filename="a \"quoted\"; name.txt""#).filename,
+        Some(r#"a "quoted"; name.txt"#.to_owned())
+    );
+    assert_eq!(
+        disposition("attachment; filename=fallback.txt; filename*=UTF-8''%e2%82%ac%20rates.txt")
+            .filename,
+        Some("€ rates.txt".to_owned())
+    );
+    assert!(disposition("x-unknown-type").is_attachment);
+}
+
+#[test]
+fn test_indexeddb_key_order() {
+    use IndexedDBKeyType::{Array, Binary, Date, Number};
+
+    // Keys of different types are ordered by type.
+    let mut keys = vec![
+        Array(vec![]),
+        Binary(vec![0]),
+        IndexedDBKeyType::String("a".to_owned()),
+        Date(0.),
+        Number(1.),
+    ];
+    keys.sort();
+    assert_eq!(
+        keys,
+        vec![
+            Number(1.),
+            Date(0.),
+            IndexedDBKeyType::String("a".to_owned()),
+            Binary(vec![0]),
+            Array(vec![]),
+        ]
+    );
+
+    // Strings compare by UTF-16 code units, so U+FF5E sorts after U+1F600.
+    assert!(
+        IndexedDBKeyType::String("\u{1F600}".to_owned()) <
+            IndexedDBKeyType::String("\u{FF5E}".to_owned())
+    );
+
+    // Arrays compare item by item, then by length.
+    assert!(Array(vec![Number(1.)]) < Array(vec![Number(1.), Number(0.)]));
+    assert!(Array(vec![Number(2.)]) > Array(vec![Number(1.), Number(0.)]));
+
+    let range = IndexedDBKeyRange {
+        lower: Some(Number(1.)),
+        upper: Some(Number(2.)),
+        lower_open: true,
+        upper_open: false,
+    };
+    assert!(!range.contains(&Number(1.)));
+    assert!(range.contains(&Number(2.)));
+    assert!(!range.contains(&IndexedDBKeyType::String("1".to_owned())));
+}
+
+#[test]
+fn test_cached_response_vary_fields() {
+    let mut headers = HeaderMap::new();
+    headers.append("vary", HeaderValue::from_static("Accept-Language, ,Origin"));
+    headers.append("vary", HeaderValue::from_static("*"));
+    let response = CachedResponse {
+        response_type: ResponseType::Basic,
+        url_list: vec![],
+        raw_status: Some((200, b"OK".to_vec())),
+        headers,
+        body: None,
+    };
+    assert_eq!(
+        response.vary_fields(),
+        vec!["accept-language", "origin", "*"]
+    );
+}
@@ -2,19 +2,24 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 
-use crate::dom::bindings::cell::Ref;
+use crate::dom::bindings::cell::{DomRefCell, Ref};
 use crate::dom::bindings::codegen::Bindings::FormDataBinding::FormDataMethods;
 use crate::dom::bindings::error::{Error, Fallible};
 use crate::dom::bindings::reflector::DomObject;
-use crate::dom::bindings::root::DomRoot;
+use crate::dom::bindings::root::{Dom, DomRoot};
 use crate::dom::bindings::str::USVString;
 use crate::dom::bindings::trace::RootedTraceableBox;
 use crate::dom::blob::{normalize_type_string, Blob};
 use crate::dom::formdata::FormData;
 use crate::dom::globalscope::GlobalScope;
 use crate::dom::promise::Promise;
-use crate::realms::{AlreadyInRealm, InRealm};
+use crate::dom::readablestream::ReadableStream;
+use crate::dom::readablestreamdefaultreader::ReadableStreamDefaultReader;
+use crate::dom::request::Request;
+use crate::fetch::fetch_request;
+use crate::realms::{enter_realm, AlreadyInRealm, InRealm};
 use crate::script_runtime::JSContext;
+use crate::streams::{react_to_promise, read_result_parts, uint8_array_bytes};
 use js::jsapi::Heap;
 use js::jsapi::JSObject;
 use js::jsapi::JS_ClearPendingException;
@@ -23,9 +28,11 @@ use js::jsval::JSVal;
 use js::jsval::UndefinedValue;
 use js::rust::wrappers::JS_GetPendingException;
 use js::rust::wrappers::JS_ParseJSON;
+use js::rust::HandleValue;
 use js::typedarray::{ArrayBuffer, CreateWith};
 use mime::{self, Mime};
 use script_traits::serializable::BlobImpl;
+use std::mem;
 use std::ptr;
 use std::rc::Rc;
 use std::str;
@@ -52,9 +59,9 @@ pub enum FetchedData {
 // https://fetch.spec.whatwg.org/#concept-body-consume-body
 #[allow(unrooted_must_root)]
 pub fn consume_body<T: BodyOperations + DomObject>(object: &T, body_type: BodyType) -> Rc<Promise> {
-    let in_realm_proof = AlreadyInRealm::assert(&object.global());
-    let promise =
-        Promise::new_in_current_realm(&object.global(), InRealm::Already(&in_realm_proof));
+    let global = object.global();
+    let in_realm_proof = AlreadyInRealm::assert(&global);
+    let promise = Promise::new_in_current_realm(&global, InRealm::Already(&in_realm_proof));
 
     // Step 1
     if object.get_body_used() || object.is_locked() {
@@ -64,31 +71,133 @@ pub fn consume_body<T: BodyOperations + DomObject>(object: &T, body_type: BodyTy
         return promise;
     }
 
-    object.set_body_promise(&promise, body_type);
+    let mime_type = object.get_mime_type().clone();
 
-    // Steps 2-4
-    // TODO: Body does not yet have a stream.
+    // Step 5
+    let stream = match object.get_body() {
+        Some(stream) => stream,
+        None => {
+            resolve_with_package_data(&global, &promise, vec![], body_type, &mime_type);
+            return promise;
+        },
+    };
 
-    consume_body_with_promise(object, body_type, &promise);
+    // Step 6
+    let reader = match stream.acquire_default_reader() {
+        Ok(reader) => reader,
+        Err(error) => {
+            promise.reject_error(error);
+            return promise;
+        },
+    };
+    let body_reader = Rc::new(BodyReader {
+        reader: Dom::from_ref(&*reader),
+        bytes: DomRefCell::new(vec![]),
+        consumer: BodyConsumer::Package(body_type, mime_type),
+        promise: promise.clone(),
+    });
+    BodyReader::read_next(&body_reader);
 
     promise
 }
 
-// https://fetch.spec.whatwg.org/#concept-body-consume-body
+/// Read all the bytes of the stream body of `request`, then fetch it with them
+/// as its body, settling `promise` like `fetch()` does.
 #[allow(unrooted_must_root)]
-pub fn consume_body_with_promise<T: BodyOperations + DomObject>(
-    object: &T,
-    body_type: BodyType,
-    promise: &Promise,
-) {
-    // Step 5
-    let body = match object.take_body() {
-        Some(body) => body,
-        None => return,
+pub fn read_body_then_fetch(request: &Request, stream: &ReadableStream, promise: &Rc<Promise>) {
+    let reader = match stream.acquire_default_reader() {
+        Ok(reader) => reader,
+        Err(error) => return promise.reject_error(error),
     };
+    let body_reader = Rc::new(BodyReader {
+        reader: Dom::from_ref(&*reader),
+        bytes: DomRefCell::new(vec![]),
+        consumer: BodyConsumer::Fetch(Dom::from_ref(request)),
+        promise: promise.clone(),
+    });
+    BodyReader::read_next(&body_reader);
+}
+
+/// What is done with the bytes of a body once they are all read.
+#[unrooted_must_root_lint::must_root]
+#[derive(JSTraceable, MallocSizeOf)]
+enum BodyConsumer {
+    /// Resolve the promise with the bytes packaged as the given type,
+    /// according to the given MIME type.
+    Package(BodyType, Vec<u8>),
+    /// Fetch the request, with the bytes as its body.
+    Fetch(Dom<Request>),
+}
+
+/// The state of a read of all the bytes in the stream of a body.
+/// <https://fetch.spec.whatwg.org/#body-fully-read>
+#[unrooted_must_root_lint::must_root]
+#[derive(JSTraceable, MallocSizeOf)]
+struct BodyReader {
+    reader: Dom<ReadableStreamDefaultReader>,
+    bytes: DomRefCell<Vec<u8>>,
+    consumer: BodyConsumer,
+    #[ignore_malloc_size_of = "Rc"]
+    promise: Rc<Promise>,
+}
+
+impl BodyReader {
+    #[allow(unrooted_must_root)]
+    fn read_next(state: &Rc<BodyReader>) {
+        let read_promise = state.reader.read();
+        react_to_promise(
+            &read_promise,
+            state.clone(),
+            BodyReader::chunk_read,
+            BodyReader::read_failed,
+        );
+    }
 
-    let pkg_data_results =
-        run_package_data_algorithm(object, body, body_type, object.get_mime_type());
+    fn chunk_read(state: &Rc<BodyReader>, cx: JSContext, result: HandleValue) {
+        rooted!(in(*cx) let mut chunk = UndefinedValue());
+        if read_result_parts(cx, result, chunk.handle_mut()) {
+            let bytes = mem::replace(&mut *state.bytes.borrow_mut(), vec![]);
+            match state.consumer {
+                BodyConsumer::Package(body_type, ref mime_type) => resolve_with_package_data(
+                    &state.promise.global(),
+                    &state.promise,
+                    bytes,
+                    body_type,
+                    mime_type,
+                ),
+                BodyConsumer::Fetch(ref request) => {
+                    request.set_body_bytes(bytes);
+                    fetch_request(&request.global(), request, &state.promise);
+                },
+            }
+            return;
+        }
+        match uint8_array_bytes(cx, chunk.handle()) {
+            Some(bytes) => {
+                state.bytes.borrow_mut().extend_from_slice(&bytes);
+                BodyReader::read_next(state);
+            },
+            None => state.promise.reject_error(Error::Type(
+                "The body's stream contains a chunk that isn't a Uint8Array".to_string(),
+            )),
+        }
+    }
+
+    fn read_failed(state: &Rc<BodyReader>, cx: JSContext, error: HandleValue) {
+        let _ac = enter_realm(&*state.promise);
+        state.promise.reject(cx, error);
+    }
+}
+
+/// Resolve `promise` with the result of packaging the bytes of a body.
+fn resolve_with_package_data(
+    global: &GlobalScope,
+    promise: &Promise,
+    bytes: Vec<u8>,
+    body_type: BodyType,
+    mime_type: &[u8],
+) {
+    let pkg_data_results = run_package_data_algorithm(global, bytes, body_type, mime_type);
 
     match pkg_data_results {
         Ok(results) => {
@@ -106,21 +215,18 @@ pub fn consume_body_with_promise<T: BodyOperations + DomObject>(
 }
 
 // https://fetch.spec.whatwg.org/#concept-body-package-data
-#[allow(unsafe_code)]
-fn run_package_data_algorithm<T: BodyOperations + DomObject>(
-    object: &T,
+fn run_package_data_algorithm(
+    global: &GlobalScope,
     bytes: Vec<u8>,
     body_type: BodyType,
-    mime_type: Ref<Vec<u8>>,
+    mime: &[u8],
 ) -> Fallible<FetchedData> {
-    let global = object.global();
     let cx = global.get_cx();
-    let mime = &*mime_type;
     match body_type {
         BodyType::Text => run_text_data_algorithm(bytes),
         BodyType::Json => run_json_data_algorithm(cx, bytes),
-        BodyType::Blob => run_blob_data_algorithm(&global, bytes, mime),
-        BodyType::FormData => run_form_data_algorithm(&global, bytes, mime),
+        BodyType::Blob => run_blob_data_algorithm(global, bytes, mime),
+        BodyType::FormData => run_form_data_algorithm(global, bytes, mime),
         BodyType::ArrayBuffer => run_array_buffer_data_algorithm(cx, bytes),
     }
 }
@@ -219,11 +325,18 @@ pub fn run_array_buffer_data_algorithm(cx: JSContext, bytes: Vec<u8>) -> Fallibl
 }
 
 pub trait BodyOperations {
-    fn get_body_used(&self) -> bool;
-    fn set_body_promise(&self, p: &Rc<Promise>, body_type: BodyType);
-    /// Returns `Some(_)` if the body is complete, `None` if there is more to
-    /// come.
-    fn take_body(&self) -> Option<Vec<u8>>;
-    fn is_locked(&self) -> bool;
+    /// The stream of the body, or `None` if the body is null.
+    fn get_body(&self) -> Option<DomRoot<ReadableStream>>;
     fn get_mime_type(&self) -> Ref<Vec<u8>>;
+
+    // https://fetch.spec.whatwg.org/#dom-body-bodyused
+    fn get_body_used(&self) -> bool {
+        self.get_body()
+            .map_or(false, |stream| stream.is_disturbed())
+    }
+
+    // https://fetch.spec.whatwg.org/#concept-body-locked
+    fn is_locked(&self) -> bool {
+        self.get_body().map_or(false, |stream| stream.is_locked())
+    }
 }
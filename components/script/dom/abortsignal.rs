@@ -0,0 +1,203 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::dom::bindings::cell::DomRefCell;
+use crate::dom::bindings::codegen::Bindings::AbortSignalBinding;
+use crate::dom::bindings::codegen::Bindings::AbortSignalBinding::AbortSignalMethods;
+use crate::dom::bindings::codegen::Bindings::EventListenerBinding::EventListener;
+use crate::dom::bindings::codegen::Bindings::EventTargetBinding::EventListenerOptions;
+use crate::dom::bindings::inheritance::Castable;
+use crate::dom::bindings::reflector::reflect_dom_object;
+use crate::dom::bindings::root::{Dom, DomRoot};
+use crate::dom::bindings::str::DOMString;
+use crate::dom::eventtarget::EventTarget;
+use crate::dom::globalscope::GlobalScope;
+use crate::dom::lockmanager::LockManager;
+use crate::dom::readablestream::PipeTo;
+use crate::fetch::FetchContext;
+use dom_struct::dom_struct;
+use script_traits::WebLockId;
+use std::cell::Cell;
+use std::rc::Rc;
+use std::sync::{Mutex, Weak};
+
+/// The steps that run when a signal is aborted.
+///
+/// <https://dom.spec.whatwg.org/#abortsignal-abort-algorithms>
+#[unrooted_must_root_lint::must_root]
+#[derive(Clone, JSTraceable, MallocSizeOf)]
+enum AbortAlgorithm {
+    /// Remove an event listener that was added with this signal.
+    RemoveEventListener {
+        target: Dom<EventTarget>,
+        ty: DOMString,
+        #[ignore_malloc_size_of = "Rc"]
+        listener: Rc<EventListener>,
+        capture: bool,
+    },
+    /// Signal abort on a signal that follows this one.
+    Follow(Dom<AbortSignal>),
+    /// Abort a fetch, unless it already finished.
+    Fetch(#[ignore_malloc_size_of = "Arc"] Weak<Mutex<FetchContext>>),
+    /// Shut down a pipe between two streams.
+    PipeTo(#[ignore_malloc_size_of = "Rc"] Rc<PipeTo>),
+    /// Abort a request for a lock, unless it was already granted.
+    LockRequest(Dom<LockManager>, WebLockId),
+}
+
+impl AbortAlgorithm {
+    fn run(self) {
+        match self {
+            AbortAlgorithm::RemoveEventListener {
+                target,
+                ty,
+                listener,
+                capture,
+            } => target.remove_event_listener(ty, Some(listener), EventListenerOptions { capture }),
+            AbortAlgorithm::Follow(signal) => signal.signal_abort(),
+            AbortAlgorithm::Fetch(context) => {
+                if let Some(context) = context.upgrade() {
+                    context.lock().unwrap().abort();
+                }
+            },
+            AbortAlgorithm::PipeTo(pipe) => PipeTo::abort(&pipe),
+            AbortAlgorithm::LockRequest(manager, id) => manager.abort_request(id),
+        }
+    }
+}
+
+// https://dom.spec.whatwg.org/#interface-AbortSignal
+#[dom_struct]
+pub struct AbortSignal {
+    eventtarget: EventTarget,
+    aborted: Cell<bool>,
+    abort_algorithms: DomRefCell<Vec<AbortAlgorithm>>,
+}
+
+impl AbortSignal {
+    fn new_inherited() -> AbortSignal {
+        AbortSignal {
+            eventtarget: EventTarget::new_inherited(),
+            aborted: Cell::new(false),
+            abort_algorithms: DomRefCell::new(vec![]),
+        }
+    }
+
+    pub fn new(global: &GlobalScope) -> DomRoot<AbortSignal> {
+        reflect_dom_object(
+            Box::new(AbortSignal::new_inherited()),
+            global,
+            AbortSignalBinding::Wrap,
+        )
+    }
+
+    pub fn aborted(&self) -> bool {
+        self.aborted.get()
+    }
+
+    /// <https://dom.spec.whatwg.org/#abortsignal-add>
+    fn add_algorithm(&self, algorithm: AbortAlgorithm) {
+        // Step 1.
+        if self.aborted.get() {
+            return;
+        }
+
+        // Step 2.
+        self.abort_algorithms.borrow_mut().push(algorithm);
+    }
+
+    /// Remove an event listener from `target` once this signal is aborted.
+    pub fn add_event_listener_removal(
+        &self,
+        target: &EventTarget,
+        ty: DOMString,
+        listener: Rc<EventListener>,
+        capture: bool,
+    ) {
+        self.add_algorithm(AbortAlgorithm::RemoveEventListener {
+            target: Dom::from_ref(target),
+            ty,
+            listener,
+            capture,
+        });
+    }
+
+    /// Abort an ongoing fetch once this signal is aborted.
+    pub fn add_fetch(&self, context: Weak<Mutex<FetchContext>>) {
+        self.add_algorithm(AbortAlgorithm::Fetch(context));
+    }
+
+    /// Forget about a fetch that finished, so that aborting this signal no longer aborts it.
+    pub fn remove_fetch(&self, context: &Weak<Mutex<FetchContext>>) {
+        self.abort_algorithms
+            .borrow_mut()
+            .retain(|algorithm| match algorithm {
+                AbortAlgorithm::Fetch(other) => !Weak::ptr_eq(other, context),
+                _ => true,
+            });
+    }
+
+    /// Shut down a pipe between two streams once this signal is aborted.
+    pub fn add_pipe(&self, pipe: Rc<PipeTo>) {
+        self.add_algorithm(AbortAlgorithm::PipeTo(pipe));
+    }
+
+    /// Abort a request for a lock once this signal is aborted.
+    pub fn add_lock_request(&self, manager: &LockManager, id: WebLockId) {
+        self.add_algorithm(AbortAlgorithm::LockRequest(Dom::from_ref(manager), id));
+    }
+
+    /// <https://dom.spec.whatwg.org/#abortsignal-follow>
+    pub fn follow(&self, parent: &AbortSignal) {
+        // Step 1.
+        if self.aborted.get() {
+            return;
+        }
+
+        // Step 2.
+        if parent.aborted.get() {
+            self.signal_abort();
+            return;
+        }
+
+        // Step 3.
+        parent.add_algorithm(AbortAlgorithm::Follow(Dom::from_ref(self)));
+    }
+
+    /// <https://dom.spec.whatwg.org/#abortsignal-signal-abort>
+    #[allow(unrooted_must_root)]
+    pub fn signal_abort(&self) {
+        // Step 1.
+        if self.aborted.get() {
+            return;
+        }
+
+        // Step 2.
+        self.aborted.set(true);
+
+        // Step 3. The algorithms stay in the list, which keeps what they
+        // refer to traced, until all of them ran.
+        let count = self.abort_algorithms.borrow().len();
+        for index in 0..count {
+            let algorithm = self.abort_algorithms.borrow()[index].clone();
+            algorithm.run();
+        }
+
+        // Step 4.
+        self.abort_algorithms.borrow_mut().clear();
+
+        // Step 5.
+        self.upcast::<EventTarget>().fire_event(atom!("abort"));
+    }
+}
+
+impl AbortSignalMethods for AbortSignal {
+    // https://dom.spec.whatwg.org/#dom-abortsignal-aborted
+    fn Aborted(&self) -> bool {
+        self.aborted.get()
+    }
+
+    // https://dom.spec.whatwg.org/#dom-abortsignal-onabort
+    event_handler!(abort, GetOnabort, SetOnabort);
+}
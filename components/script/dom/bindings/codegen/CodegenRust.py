@@ -6095,6 +6095,7 @@ def generate_imports(config, cgthings, descriptors, callbacks=None, dictionaries
         'crate::dom::bindings::callback::CallbackObject',
         'crate::dom::bindings::callback::ExceptionHandling',
         'crate::dom::bindings::callback::wrap_call_this_object',
+        'crate::dom::bindings::callback::ThisReflector',
         'crate::dom::bindings::conversions::ConversionBehavior',
         'crate::dom::bindings::conversions::ConversionResult',
         'crate::dom::bindings::conversions::DOM_OBJECT_SLOT',
@@ -7005,7 +7006,7 @@ class CGCallback(CGClass):
             })
         return [ClassMethod(method.name + '_', method.returnType, args,
                             bodyInHeader=True,
-                            templateArgs=["T: DomObject"],
+                            templateArgs=["T: ThisReflector"],
                             body=bodyWithThis,
                             visibility='pub'),
                 ClassMethod(method.name + '__', method.returnType, argsWithoutThis,
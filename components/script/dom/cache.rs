@@ -0,0 +1,610 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::body::{consume_body, BodyOperations, BodyType};
+use crate::dom::bindings::cell::DomRefCell;
+use crate::dom::bindings::codegen::Bindings::CacheBinding;
+use crate::dom::bindings::codegen::Bindings::CacheBinding::{CacheMethods, CacheQueryOptions};
+use crate::dom::bindings::codegen::Bindings::RequestBinding::{RequestInfo, RequestInit};
+use crate::dom::bindings::codegen::Bindings::ResponseBinding::{
+    ResponseMethods, ResponseType as DOMResponseType,
+};
+use crate::dom::bindings::conversions::root_from_handlevalue;
+use crate::dom::bindings::error::{Error, Fallible};
+use crate::dom::bindings::refcounted::TrustedPromise;
+use crate::dom::bindings::reflector::{reflect_dom_object, DomObject, Reflector};
+use crate::dom::bindings::root::{Dom, DomRoot};
+use crate::dom::bindings::utils::to_frozen_array;
+use crate::dom::globalscope::GlobalScope;
+use crate::dom::promise::Promise;
+use crate::dom::request::Request;
+use crate::dom::response::Response;
+use crate::fetch::Fetch;
+use crate::realms::{enter_realm, InRealm};
+use crate::script_runtime::JSContext;
+use crate::streams::react_to_promise;
+use crate::task_source::dom_manipulation::DOMManipulationTaskSource;
+use crate::task_source::TaskSource;
+use dom_struct::dom_struct;
+use http::Method;
+use ipc_channel::ipc::{self, IpcSender};
+use ipc_channel::router::ROUTER;
+use js::rust::HandleValue;
+use js::typedarray::ArrayBuffer;
+use net_traits::cache_storage_thread::CacheQueryOptions as NetTraitsCacheQueryOptions;
+use net_traits::cache_storage_thread::CachedResponse;
+use net_traits::cache_storage_thread::{CacheBatchOperation, CacheId, CacheRequest};
+use net_traits::cache_storage_thread::{CacheStorageError, CacheStorageThreadMsg};
+use net_traits::IpcSend;
+use serde::{Deserialize, Serialize};
+use std::cell::Cell;
+use std::rc::Rc;
+
+// https://w3c.github.io/ServiceWorker/#cache-interface
+#[dom_struct]
+pub struct Cache {
+    reflector_: Reflector,
+    /// The id of the cache in the backend.
+    id: CacheId,
+}
+
+impl Cache {
+    fn new_inherited(id: CacheId) -> Cache {
+        Cache {
+            reflector_: Reflector::new(),
+            id,
+        }
+    }
+
+    pub fn new(global: &GlobalScope, id: CacheId) -> DomRoot<Cache> {
+        reflect_dom_object(
+            Box::new(Cache::new_inherited(id)),
+            global,
+            CacheBinding::Wrap,
+        )
+    }
+
+    /// <https://w3c.github.io/ServiceWorker/#dom-cache-matchall>
+    fn match_all<F>(
+        &self,
+        request: Option<RequestInfo>,
+        options: &CacheQueryOptions,
+        comp: InRealm,
+        settle: F,
+    ) -> Rc<Promise>
+    where
+        F: FnOnce(&Promise, Vec<CachedResponse>) + Send + 'static,
+    {
+        let global = self.global();
+        let promise = Promise::new_in_current_realm(&global, comp);
+
+        // Steps 1-2.
+        let request = match request {
+            Some(request) => match query_request(&global, request, options) {
+                Ok(Some(request)) => Some(request),
+                Ok(None) => {
+                    settle(&promise, vec![]);
+                    return promise;
+                },
+                Err(error) => {
+                    promise.reject_error(error);
+                    return promise;
+                },
+            },
+            None => None,
+        };
+
+        // Steps 3-5.
+        let cache = self.id;
+        let options = options.into();
+        send_cache_storage_msg(
+            &promise,
+            |sender| CacheStorageThreadMsg::MatchAll {
+                cache,
+                request,
+                options,
+                sender,
+            },
+            settle,
+        );
+        promise
+    }
+
+    /// Steps 1-4 of <https://w3c.github.io/ServiceWorker/#cache-addAll>.
+    fn checked_request(global: &GlobalScope, request: RequestInfo) -> Fallible<DomRoot<Request>> {
+        let request = Request::Constructor(global, request, RequestInit::empty())?;
+        let cache_request = request.cache_request();
+        if !is_http_scheme(&cache_request) || cache_request.method != Method::GET {
+            return Err(Error::Type(
+                "Only GET requests to http(s) URLs can be cached".to_owned(),
+            ));
+        }
+        Ok(request)
+    }
+}
+
+/// The request a query of a cache looks for, or `None` if no entry can match it.
+pub fn query_request(
+    global: &GlobalScope,
+    request: RequestInfo,
+    options: &CacheQueryOptions,
+) -> Fallible<Option<CacheRequest>> {
+    let request = match request {
+        RequestInfo::Request(request) => request.cache_request(),
+        RequestInfo::USVString(url) => {
+            Request::Constructor(global, RequestInfo::USVString(url), RequestInit::empty())?
+                .cache_request()
+        },
+    };
+    if request.method != Method::GET && !options.ignoreMethod {
+        return Ok(None);
+    }
+    Ok(Some(request))
+}
+
+fn is_http_scheme(request: &CacheRequest) -> bool {
+    let scheme = request.url.scheme();
+    scheme == "http" || scheme == "https"
+}
+
+/// Check that a response can be stored in a cache.
+fn check_response(response: &Response) -> Fallible<()> {
+    if response.Status() == 206 {
+        return Err(Error::Type("Partial responses can't be cached".to_owned()));
+    }
+    if response
+        .cached_response(None)
+        .vary_fields()
+        .iter()
+        .any(|field| field == "*")
+    {
+        return Err(Error::Type(
+            "Responses that vary on every header can't be cached".to_owned(),
+        ));
+    }
+    if response.get_body_used() || response.is_locked() {
+        return Err(Error::Type(
+            "The response's body is disturbed or locked".to_owned(),
+        ));
+    }
+    Ok(())
+}
+
+impl<'a> From<&'a CacheQueryOptions> for NetTraitsCacheQueryOptions {
+    fn from(options: &'a CacheQueryOptions) -> NetTraitsCacheQueryOptions {
+        NetTraitsCacheQueryOptions {
+            ignore_search: options.ignoreSearch,
+            ignore_method: options.ignoreMethod,
+            ignore_vary: options.ignoreVary,
+        }
+    }
+}
+
+impl From<CacheStorageError> for Error {
+    fn from(error: CacheStorageError) -> Error {
+        match error {
+            CacheStorageError::InvalidState => Error::InvalidState,
+            CacheStorageError::QuotaExceeded => Error::QuotaExceeded,
+        }
+    }
+}
+
+/// Send a message to the cache storage backend, and settle `promise` with its reply
+/// in a task of the DOM manipulation task source.
+pub fn send_cache_storage_msg<T, M, F>(promise: &Rc<Promise>, message: M, settle: F)
+where
+    T: for<'de> Deserialize<'de> + Serialize + Send + 'static,
+    M: FnOnce(IpcSender<T>) -> CacheStorageThreadMsg,
+    F: FnOnce(&Promise, T) + Send + 'static,
+{
+    let global = promise.global();
+    let (sender, receiver) = ipc::channel().unwrap();
+    let mut reply = Some((TrustedPromise::new(promise.clone()), settle));
+    let task_source = global.dom_manipulation_task_source();
+    let canceller = global.task_canceller(DOMManipulationTaskSource::NAME);
+    ROUTER.add_route(
+        receiver.to_opaque(),
+        Box::new(move |message| {
+            let (promise, settle) = match reply.take() {
+                Some(reply) => reply,
+                None => return,
+            };
+            let result: T = match message.to() {
+                Ok(result) => result,
+                Err(why) => return warn!("couldn't decode a cache storage reply: {}", why),
+            };
+            let _ = task_source.queue_with_canceller(
+                task!(settle_cache_promise: move || {
+                    let promise = promise.root();
+                    let _ac = enter_realm(&*promise);
+                    settle(&promise, result);
+                }),
+                &canceller,
+            );
+        }),
+    );
+    if global.resource_threads().send(message(sender)).is_err() {
+        promise.reject_error(Error::Operation);
+    }
+}
+
+/// Resolve `promise` with the responses a cache holds.
+fn resolve_with_responses(promise: &Promise, responses: Vec<CachedResponse>) {
+    let global = promise.global();
+    let responses: Vec<DomRoot<Response>> = responses
+        .into_iter()
+        .map(|response| Response::from_cached_response(&global, response))
+        .collect();
+    let cx = global.get_cx();
+    rooted!(in(*cx) let array = to_frozen_array(&responses, cx));
+    promise.resolve(cx, array.handle());
+}
+
+/// The entries that `Cache.put` or `Cache.addAll` store at once, when the bodies
+/// of all their responses have been read.
+#[unrooted_must_root_lint::must_root]
+#[derive(JSTraceable, MallocSizeOf)]
+struct PendingPut {
+    cache: Dom<Cache>,
+    #[ignore_malloc_size_of = "Rc"]
+    promise: Rc<Promise>,
+    /// The requests, along with their responses once they are available.
+    #[ignore_malloc_size_of = "Defined in net_traits"]
+    entries: DomRefCell<Vec<(CacheRequest, Option<CachedResponse>)>>,
+    /// The number of responses that are still being fetched or read.
+    remaining: Cell<usize>,
+}
+
+/// The response of one of the requests of a `PendingPut`.
+#[unrooted_must_root_lint::must_root]
+#[derive(Clone, JSTraceable, MallocSizeOf)]
+struct PendingResponse {
+    #[ignore_malloc_size_of = "Rc"]
+    put: Rc<PendingPut>,
+    index: usize,
+}
+
+impl PendingPut {
+    #[allow(unrooted_must_root)]
+    fn new(cache: &Cache, promise: &Rc<Promise>, requests: Vec<CacheRequest>) -> Rc<PendingPut> {
+        Rc::new(PendingPut {
+            cache: Dom::from_ref(cache),
+            promise: promise.clone(),
+            remaining: Cell::new(requests.len()),
+            entries: DomRefCell::new(
+                requests
+                    .into_iter()
+                    .map(|request| (request, None))
+                    .collect(),
+            ),
+        })
+    }
+
+    /// Read the body of the response to the request at `index`.
+    #[allow(unrooted_must_root)]
+    fn read_response(put: &Rc<PendingPut>, index: usize, response: &Response) {
+        put.entries.borrow_mut()[index].1 = Some(response.cached_response(None));
+        if response.get_body().is_none() {
+            return put.response_ready();
+        }
+        let body_promise = consume_body(response, BodyType::ArrayBuffer);
+        react_to_promise(
+            &body_promise,
+            PendingResponse {
+                put: put.clone(),
+                index,
+            },
+            PendingResponse::body_read,
+            PendingResponse::failed,
+        );
+    }
+
+    fn response_ready(&self) {
+        self.remaining.set(self.remaining.get() - 1);
+        if self.remaining.get() == 0 {
+            self.store();
+        }
+    }
+
+    /// <https://w3c.github.io/ServiceWorker/#batch-cache-operations-algorithm>
+    fn store(&self) {
+        // Another response failed, and rejected the promise.
+        if self.promise.is_fulfilled() {
+            return;
+        }
+        let operations = self
+            .entries
+            .borrow_mut()
+            .drain(..)
+            .filter_map(|(request, response)| {
+                response.map(|response| CacheBatchOperation::Put { request, response })
+            })
+            .collect();
+        let cache = self.cache.id;
+        send_cache_storage_msg(
+            &self.promise,
+            |sender| CacheStorageThreadMsg::Batch {
+                cache,
+                operations,
+                sender,
+            },
+            |promise, result: Result<bool, CacheStorageError>| match result {
+                Ok(_) => promise.resolve_native(&()),
+                Err(error) => promise.reject_error(error.into()),
+            },
+        );
+    }
+}
+
+impl PendingResponse {
+    #[allow(unrooted_must_root)]
+    fn fetched(pending: &PendingResponse, cx: JSContext, value: HandleValue) {
+        let response = match root_from_handlevalue::<Response>(value, *cx) {
+            Ok(response) => response,
+            Err(()) => {
+                return pending.put.promise.reject_error(Error::Type(
+                    "The fetch didn't result in a response".to_owned(),
+                ))
+            },
+        };
+        if response.Type() == DOMResponseType::Error || !response.Ok() {
+            return pending.put.promise.reject_error(Error::Type(
+                "Only successful responses can be added to a cache".to_owned(),
+            ));
+        }
+        if let Err(error) = check_response(&response) {
+            return pending.put.promise.reject_error(error);
+        }
+        PendingPut::read_response(&pending.put, pending.index, &response);
+    }
+
+    #[allow(unsafe_code)]
+    fn body_read(pending: &PendingResponse, cx: JSContext, value: HandleValue) {
+        let bytes = if value.is_object() {
+            typedarray!(in(*cx) let buffer: ArrayBuffer = value.to_object());
+            buffer
+                .ok()
+                .map(|buffer| unsafe { buffer.as_slice().to_vec() })
+        } else {
+            None
+        };
+        {
+            let mut entries = pending.put.entries.borrow_mut();
+            if let Some(ref mut response) = entries[pending.index].1 {
+                response.body = Some(bytes.unwrap_or_default());
+            }
+        }
+        pending.put.response_ready();
+    }
+
+    fn failed(pending: &PendingResponse, cx: JSContext, error: HandleValue) {
+        let _ac = enter_realm(&*pending.put.promise);
+        pending.put.promise.reject(cx, error);
+    }
+}
+
+impl CacheMethods for Cache {
+    // https://w3c.github.io/ServiceWorker/#dom-cache-match
+    fn Match(
+        &self,
+        request: RequestInfo,
+        options: &CacheQueryOptions,
+        comp: InRealm,
+    ) -> Rc<Promise> {
+        self.match_all(
+            Some(request),
+            options,
+            comp,
+            |promise, responses| match responses.into_iter().next() {
+                Some(response) => {
+                    let response = Response::from_cached_response(&promise.global(), response);
+                    promise.resolve_native(&response);
+                },
+                None => promise.resolve_native(&()),
+            },
+        )
+    }
+
+    // https://w3c.github.io/ServiceWorker/#dom-cache-matchall
+    fn MatchAll(
+        &self,
+        request: Option<RequestInfo>,
+        options: &CacheQueryOptions,
+        comp: InRealm,
+    ) -> Rc<Promise> {
+        self.match_all(request, options, comp, resolve_with_responses)
+    }
+
+    // https://w3c.github.io/ServiceWorker/#dom-cache-add
+    fn Add(&self, request: RequestInfo, comp: InRealm) -> Rc<Promise> {
+        self.AddAll(vec![request], comp)
+    }
+
+    // https://w3c.github.io/ServiceWorker/#dom-cache-addall
+    #[allow(unrooted_must_root)]
+    fn AddAll(&self, requests: Vec<RequestInfo>, comp: InRealm) -> Rc<Promise> {
+        let global = self.global();
+        let promise = Promise::new_in_current_realm(&global, comp);
+
+        // Steps 1-4.
+        let mut request_objects = vec![];
+        for request in requests {
+            match Cache::checked_request(&global, request) {
+                Ok(request) => request_objects.push(request),
+                Err(error) => {
+                    promise.reject_error(error);
+                    return promise;
+                },
+            }
+        }
+
+        // Steps 5-7.
+        let put = PendingPut::new(
+            self,
+            &promise,
+            request_objects
+                .iter()
+                .map(|request| request.cache_request())
+                .collect(),
+        );
+        if request_objects.is_empty() {
+            put.store();
+            return promise;
+        }
+        for (index, request) in request_objects.into_iter().enumerate() {
+            let fetch_promise = Fetch(
+                &global,
+                RequestInfo::Request(request),
+                RequestInit::empty(),
+                comp,
+            );
+            react_to_promise(
+                &fetch_promise,
+                PendingResponse {
+                    put: put.clone(),
+                    index,
+                },
+                PendingResponse::fetched,
+                PendingResponse::failed,
+            );
+        }
+
+        // Step 8.
+        promise
+    }
+
+    // https://w3c.github.io/ServiceWorker/#dom-cache-put
+    fn Put(&self, request: RequestInfo, response: &Response, comp: InRealm) -> Rc<Promise> {
+        let global = self.global();
+        let promise = Promise::new_in_current_realm(&global, comp);
+
+        // Steps 1-8.
+        let request = match request {
+            RequestInfo::Request(request) => request.cache_request(),
+            RequestInfo::USVString(url) => match Request::Constructor(
+                &global,
+                RequestInfo::USVString(url),
+                RequestInit::empty(),
+            ) {
+                Ok(request) => request.cache_request(),
+                Err(error) => {
+                    promise.reject_error(error);
+                    return promise;
+                },
+            },
+        };
+        if !is_http_scheme(&request) || request.method != Method::GET {
+            promise.reject_error(Error::Type(
+                "Only GET requests to http(s) URLs can be cached".to_owned(),
+            ));
+            return promise;
+        }
+        if let Err(error) = check_response(response) {
+            promise.reject_error(error);
+            return promise;
+        }
+
+        // Steps 9-15.
+        let put = PendingPut::new(self, &promise, vec![request]);
+        PendingPut::read_response(&put, 0, response);
+        promise
+    }
+
+    // https://w3c.github.io/ServiceWorker/#dom-cache-delete
+    fn Delete(
+        &self,
+        request: RequestInfo,
+        options: &CacheQueryOptions,
+        comp: InRealm,
+    ) -> Rc<Promise> {
+        let global = self.global();
+        let promise = Promise::new_in_current_realm(&global, comp);
+
+        // Steps 1-2.
+        let request = match query_request(&global, request, options) {
+            Ok(Some(request)) => request,
+            Ok(None) => {
+                promise.resolve_native(&false);
+                return promise;
+            },
+            Err(error) => {
+                promise.reject_error(error);
+                return promise;
+            },
+        };
+
+        // Steps 3-7.
+        let cache = self.id;
+        let operations = vec![CacheBatchOperation::Delete {
+            request,
+            options: options.into(),
+        }];
+        send_cache_storage_msg(
+            &promise,
+            |sender| CacheStorageThreadMsg::Batch {
+                cache,
+                operations,
+                sender,
+            },
+            |promise, result: Result<bool, CacheStorageError>| match result {
+                Ok(deleted) => promise.resolve_native(&deleted),
+                Err(error) => promise.reject_error(error.into()),
+            },
+        );
+        promise
+    }
+
+    // https://w3c.github.io/ServiceWorker/#dom-cache-keys
+    fn Keys(
+        &self,
+        request: Option<RequestInfo>,
+        options: &CacheQueryOptions,
+        comp: InRealm,
+    ) -> Rc<Promise> {
+        let global = self.global();
+        let promise = Promise::new_in_current_realm(&global, comp);
+
+        // Steps 1-3.
+        let request = match request {
+            Some(request) => match query_request(&global, request, options) {
+                Ok(Some(request)) => Some(request),
+                Ok(None) => {
+                    let cx = global.get_cx();
+                    rooted!(in(*cx) let array = to_frozen_array::<DomRoot<Request>>(&[], cx));
+                    promise.resolve(cx, array.handle());
+                    return promise;
+                },
+                Err(error) => {
+                    promise.reject_error(error);
+                    return promise;
+                },
+            },
+            None => None,
+        };
+
+        // Steps 4-5.
+        let cache = self.id;
+        let options = options.into();
+        send_cache_storage_msg(
+            &promise,
+            |sender| CacheStorageThreadMsg::RequestKeys {
+                cache,
+                request,
+                options,
+                sender,
+            },
+            |promise, requests: Vec<CacheRequest>| {
+                let global = promise.global();
+                let requests: Vec<DomRoot<Request>> = requests
+                    .into_iter()
+                    .map(|request| Request::from_cache_request(&global, request))
+                    .collect();
+                let cx = global.get_cx();
+                rooted!(in(*cx) let array = to_frozen_array(&requests, cx));
+                promise.resolve(cx, array.handle());
+            },
+        );
+        promise
+    }
+}
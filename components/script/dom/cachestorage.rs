@@ -0,0 +1,167 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::dom::bindings::codegen::Bindings::CacheStorageBinding;
+use crate::dom::bindings::codegen::Bindings::CacheStorageBinding::{
+    CacheStorageMethods, MultiCacheQueryOptions,
+};
+use crate::dom::bindings::codegen::Bindings::RequestBinding::RequestInfo;
+use crate::dom::bindings::error::{Error, Fallible};
+use crate::dom::bindings::reflector::{reflect_dom_object, DomObject, Reflector};
+use crate::dom::bindings::root::DomRoot;
+use crate::dom::bindings::str::DOMString;
+use crate::dom::cache::{query_request, send_cache_storage_msg, Cache};
+use crate::dom::globalscope::GlobalScope;
+use crate::dom::promise::Promise;
+use crate::dom::response::Response;
+use crate::realms::InRealm;
+use dom_struct::dom_struct;
+use ipc_channel::ipc::IpcSender;
+use net_traits::cache_storage_thread::{CacheId, CacheStorageThreadMsg, CachedResponse};
+use serde::{Deserialize, Serialize};
+use servo_url::ImmutableOrigin;
+use std::rc::Rc;
+
+// https://w3c.github.io/ServiceWorker/#cachestorage-interface
+#[dom_struct]
+pub struct CacheStorage {
+    reflector_: Reflector,
+}
+
+impl CacheStorage {
+    fn new_inherited() -> CacheStorage {
+        CacheStorage {
+            reflector_: Reflector::new(),
+        }
+    }
+
+    pub fn new(global: &GlobalScope) -> DomRoot<CacheStorage> {
+        reflect_dom_object(
+            Box::new(CacheStorage::new_inherited()),
+            global,
+            CacheStorageBinding::Wrap,
+        )
+    }
+
+    /// The origin whose caches script can access, which can't be an opaque origin.
+    fn storage_origin(&self) -> Fallible<ImmutableOrigin> {
+        let origin = self.global().origin().immutable().clone();
+        if !origin.is_tuple() {
+            return Err(Error::Security);
+        }
+        Ok(origin)
+    }
+
+    /// Send a message about the caches of the origin, or reject the promise if
+    /// script can't access them.
+    fn send<T, M, F>(&self, comp: InRealm, message: M, settle: F) -> Rc<Promise>
+    where
+        T: for<'de> Deserialize<'de> + Serialize + Send + 'static,
+        M: FnOnce(ImmutableOrigin, IpcSender<T>) -> CacheStorageThreadMsg,
+        F: FnOnce(&Promise, T) + Send + 'static,
+    {
+        let promise = Promise::new_in_current_realm(&self.global(), comp);
+        match self.storage_origin() {
+            Ok(origin) => {
+                send_cache_storage_msg(&promise, |sender| message(origin, sender), settle)
+            },
+            Err(error) => promise.reject_error(error),
+        }
+        promise
+    }
+}
+
+impl CacheStorageMethods for CacheStorage {
+    // https://w3c.github.io/ServiceWorker/#cache-storage-match
+    fn Match(
+        &self,
+        request: RequestInfo,
+        options: &MultiCacheQueryOptions,
+        comp: InRealm,
+    ) -> Rc<Promise> {
+        let request = match query_request(&self.global(), request, &options.parent) {
+            Ok(Some(request)) => request,
+            Ok(None) => {
+                let promise = Promise::new_in_current_realm(&self.global(), comp);
+                promise.resolve_native(&());
+                return promise;
+            },
+            Err(error) => {
+                let promise = Promise::new_in_current_realm(&self.global(), comp);
+                promise.reject_error(error);
+                return promise;
+            },
+        };
+        let cache_name = options.cacheName.as_ref().map(|name| name.to_string());
+        let query_options = (&options.parent).into();
+        self.send(
+            comp,
+            |origin, sender| CacheStorageThreadMsg::MatchStorage {
+                origin,
+                cache_name,
+                request,
+                options: query_options,
+                sender,
+            },
+            |promise, response: Option<CachedResponse>| match response {
+                Some(response) => {
+                    let response = Response::from_cached_response(&promise.global(), response);
+                    promise.resolve_native(&response);
+                },
+                None => promise.resolve_native(&()),
+            },
+        )
+    }
+
+    // https://w3c.github.io/ServiceWorker/#cache-storage-has
+    fn Has(&self, cache_name: DOMString, comp: InRealm) -> Rc<Promise> {
+        self.send(
+            comp,
+            |origin, sender| CacheStorageThreadMsg::Has {
+                origin,
+                name: cache_name.to_string(),
+                sender,
+            },
+            |promise, has: bool| promise.resolve_native(&has),
+        )
+    }
+
+    // https://w3c.github.io/ServiceWorker/#cache-storage-open
+    fn Open(&self, cache_name: DOMString, comp: InRealm) -> Rc<Promise> {
+        self.send(
+            comp,
+            |origin, sender| CacheStorageThreadMsg::Open {
+                origin,
+                name: cache_name.to_string(),
+                sender,
+            },
+            |promise, id: CacheId| {
+                let cache = Cache::new(&promise.global(), id);
+                promise.resolve_native(&cache);
+            },
+        )
+    }
+
+    // https://w3c.github.io/ServiceWorker/#cache-storage-delete
+    fn Delete(&self, cache_name: DOMString, comp: InRealm) -> Rc<Promise> {
+        self.send(
+            comp,
+            |origin, sender| CacheStorageThreadMsg::Delete {
+                origin,
+                name: cache_name.to_string(),
+                sender,
+            },
+            |promise, deleted: bool| promise.resolve_native(&deleted),
+        )
+    }
+
+    // https://w3c.github.io/ServiceWorker/#cache-storage-keys
+    fn Keys(&self, comp: InRealm) -> Rc<Promise> {
+        self.send(
+            comp,
+            |origin, sender| CacheStorageThreadMsg::Keys(sender, origin),
+            |promise, names: Vec<String>| promise.resolve_native(&names),
+        )
+    }
+}
@@ -4,15 +4,17 @@
 
 use crate::dom::bindings::codegen::Bindings::ClientBinding::FrameType;
 use crate::dom::bindings::codegen::Bindings::ClientBinding::{ClientMethods, Wrap};
+use crate::dom::bindings::inheritance::Castable;
 use crate::dom::bindings::reflector::{reflect_dom_object, Reflector};
 use crate::dom::bindings::root::{DomRoot, MutNullableDom};
 use crate::dom::bindings::str::{DOMString, USVString};
+use crate::dom::globalscope::GlobalScope;
 use crate::dom::serviceworker::ServiceWorker;
 use crate::dom::window::Window;
 use dom_struct::dom_struct;
+use script_traits::ServiceWorkerClientInfo;
 use servo_url::ServoUrl;
 use std::default::Default;
-use uuid::Uuid;
 
 #[dom_struct]
 pub struct Client {
@@ -20,29 +22,45 @@ pub struct Client {
     active_worker: MutNullableDom<ServiceWorker>,
     url: ServoUrl,
     frame_type: FrameType,
-    #[ignore_malloc_size_of = "Defined in uuid"]
-    id: Uuid,
+    /// The id of the pipeline of the window, which service workers know it by.
+    id: DOMString,
 }
 
 impl Client {
-    fn new_inherited(url: ServoUrl) -> Client {
+    fn new_inherited(url: ServoUrl, frame_type: FrameType, id: DOMString) -> Client {
         Client {
             reflector_: Reflector::new(),
             active_worker: Default::default(),
             url: url,
-            frame_type: FrameType::None,
-            id: Uuid::new_v4(),
+            frame_type: frame_type,
+            id: id,
         }
     }
 
     pub fn new(window: &Window) -> DomRoot<Client> {
+        let id = DOMString::from_string(window.upcast::<GlobalScope>().pipeline_id().to_string());
         reflect_dom_object(
-            Box::new(Client::new_inherited(window.get_url())),
+            Box::new(Client::new_inherited(window.get_url(), FrameType::None, id)),
             window,
             Wrap,
         )
     }
 
+    /// A window as the service worker in `global` sees it.
+    pub fn from_info(global: &GlobalScope, info: &ServiceWorkerClientInfo) -> DomRoot<Client> {
+        let frame_type = if info.top_level {
+            FrameType::Top_level
+        } else {
+            FrameType::Nested
+        };
+        let id = DOMString::from_string(info.pipeline_id.to_string());
+        reflect_dom_object(
+            Box::new(Client::new_inherited(info.url.clone(), frame_type, id)),
+            global,
+            Wrap,
+        )
+    }
+
     pub fn creation_url(&self) -> ServoUrl {
         self.url.clone()
     }
@@ -69,7 +87,6 @@ impl ClientMethods for Client {
 
     // https://w3c.github.io/ServiceWorker/#client-id
     fn Id(&self) -> DOMString {
-        let uid_str = format!("{}", self.id);
-        DOMString::from_string(uid_str)
+        self.id.clone()
     }
 }
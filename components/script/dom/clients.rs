@@ -0,0 +1,175 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::dom::bindings::codegen::Bindings::ClientsBinding::{
+    ClientQueryOptions, ClientType, ClientsMethods, Wrap,
+};
+use crate::dom::bindings::error::Error;
+use crate::dom::bindings::inheritance::Castable;
+use crate::dom::bindings::refcounted::TrustedPromise;
+use crate::dom::bindings::reflector::{reflect_dom_object, DomObject, Reflector};
+use crate::dom::bindings::root::DomRoot;
+use crate::dom::bindings::str::DOMString;
+use crate::dom::client::Client;
+use crate::dom::promise::Promise;
+use crate::dom::serviceworkerglobalscope::ServiceWorkerGlobalScope;
+use crate::realms::{enter_realm, InRealm};
+use crate::task_source::dom_manipulation::DOMManipulationTaskSource;
+use crate::task_source::TaskSource;
+use dom_struct::dom_struct;
+use ipc_channel::ipc::{self, IpcSender};
+use ipc_channel::router::ROUTER;
+use script_traits::{ServiceWorkerClientInfo, ServiceWorkerMsg};
+use serde::{Deserialize, Serialize};
+use std::rc::Rc;
+
+// https://w3c.github.io/ServiceWorker/#clients-interface
+#[dom_struct]
+pub struct Clients {
+    reflector_: Reflector,
+}
+
+impl Clients {
+    fn new_inherited() -> Clients {
+        Clients {
+            reflector_: Reflector::new(),
+        }
+    }
+
+    pub fn new(global: &ServiceWorkerGlobalScope) -> DomRoot<Clients> {
+        reflect_dom_object(Box::new(Clients::new_inherited()), global, Wrap)
+    }
+
+    /// Ask the service worker manager about the clients of this service worker,
+    /// and settle `promise` with the reply.
+    fn send<T, M, F>(&self, promise: &Rc<Promise>, message: M, settle: F)
+    where
+        T: for<'de> Deserialize<'de> + Serialize + Send + 'static,
+        M: FnOnce(IpcSender<T>) -> ServiceWorkerMsg,
+        F: FnOnce(&Promise, T) + Send + 'static,
+    {
+        let global = self.global();
+        let worker = match global.downcast::<ServiceWorkerGlobalScope>() {
+            Some(worker) => worker,
+            None => return promise.reject_error(Error::InvalidState),
+        };
+        let (sender, receiver) = ipc::channel().unwrap();
+        let mut reply = Some((TrustedPromise::new(promise.clone()), settle));
+        let task_source = global.dom_manipulation_task_source();
+        let canceller = global.task_canceller(DOMManipulationTaskSource::NAME);
+        ROUTER.add_route(
+            receiver.to_opaque(),
+            Box::new(move |message| {
+                let (promise, settle) = match reply.take() {
+                    Some(reply) => reply,
+                    None => return,
+                };
+                let result: T = match message.to() {
+                    Ok(result) => result,
+                    Err(why) => return warn!("couldn't decode a clients reply: {}", why),
+                };
+                let _ = task_source.queue_with_canceller(
+                    task!(settle_clients_promise: move || {
+                        let promise = promise.root();
+                        let _ac = enter_realm(&*promise);
+                        settle(&promise, result);
+                    }),
+                    &canceller,
+                );
+            }),
+        );
+        if worker.swmanager_sender().send(message(sender)).is_err() {
+            promise.reject_error(Error::Operation);
+        }
+    }
+
+    /// The clients in the scope of this service worker, including those it doesn't
+    /// control if `include_uncontrolled` is set.
+    fn match_clients<F>(&self, include_uncontrolled: bool, comp: InRealm, settle: F) -> Rc<Promise>
+    where
+        F: FnOnce(&Promise, Vec<ServiceWorkerClientInfo>) + Send + 'static,
+    {
+        let promise = Promise::new_in_current_realm(&self.global(), comp);
+        let scope_url = match self.global().downcast::<ServiceWorkerGlobalScope>() {
+            Some(worker) => worker.scope_url().clone(),
+            None => {
+                promise.reject_error(Error::InvalidState);
+                return promise;
+            },
+        };
+        self.send(
+            &promise,
+            |sender| ServiceWorkerMsg::MatchClients(scope_url, include_uncontrolled, sender),
+            settle,
+        );
+        promise
+    }
+}
+
+impl ClientsMethods for Clients {
+    // https://w3c.github.io/ServiceWorker/#clients-get
+    fn Get(&self, id: DOMString, comp: InRealm) -> Rc<Promise> {
+        let id = String::from(id);
+        self.match_clients(true, comp, move |promise, clients| {
+            let client = clients
+                .iter()
+                .find(|client| client.pipeline_id.to_string() == id);
+            match client {
+                Some(client) => {
+                    promise.resolve_native(&Client::from_info(&promise.global(), client))
+                },
+                None => promise.resolve_native(&()),
+            }
+        })
+    }
+
+    // https://w3c.github.io/ServiceWorker/#clients-matchall
+    fn MatchAll(&self, options: &ClientQueryOptions, comp: InRealm) -> Rc<Promise> {
+        // Only windows can be clients for now.
+        let matches_windows = match options.type_ {
+            ClientType::Window | ClientType::All => true,
+            ClientType::Worker | ClientType::Sharedworker => false,
+        };
+        self.match_clients(
+            options.includeUncontrolled,
+            comp,
+            move |promise, clients| {
+                let global = promise.global();
+                let clients: Vec<DomRoot<Client>> = if matches_windows {
+                    clients
+                        .iter()
+                        .map(|client| Client::from_info(&global, client))
+                        .collect()
+                } else {
+                    vec![]
+                };
+                promise.resolve_native(&clients);
+            },
+        )
+    }
+
+    // https://w3c.github.io/ServiceWorker/#clients-claim
+    fn Claim(&self, comp: InRealm) -> Rc<Promise> {
+        let promise = Promise::new_in_current_realm(&self.global(), comp);
+        let scope_url = match self.global().downcast::<ServiceWorkerGlobalScope>() {
+            Some(worker) => worker.scope_url().clone(),
+            None => {
+                promise.reject_error(Error::InvalidState);
+                return promise;
+            },
+        };
+        self.send(
+            &promise,
+            |sender| ServiceWorkerMsg::Claim(scope_url, sender),
+            |promise, active: bool| {
+                if active {
+                    promise.resolve_native(&());
+                } else {
+                    promise.reject_error(Error::InvalidState);
+                }
+            },
+        );
+        promise
+    }
+}
@@ -0,0 +1,182 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::dom::bindings::codegen::Bindings::ClipboardBinding::{ClipboardMethods, Wrap};
+use crate::dom::bindings::codegen::Bindings::PermissionStatusBinding::{
+    PermissionName, PermissionState,
+};
+use crate::dom::bindings::error::Error;
+use crate::dom::bindings::refcounted::TrustedPromise;
+use crate::dom::bindings::reflector::{reflect_dom_object, DomObject};
+use crate::dom::bindings::root::DomRoot;
+use crate::dom::bindings::str::DOMString;
+use crate::dom::clipboarditem::ClipboardItem;
+use crate::dom::eventtarget::EventTarget;
+use crate::dom::globalscope::GlobalScope;
+use crate::dom::permissions::{get_descriptor_permission_state, get_stored_permission_state};
+use crate::dom::promise::Promise;
+use crate::realms::{enter_realm, InRealm};
+use crate::task_source::dom_manipulation::DOMManipulationTaskSource;
+use crate::task_source::TaskSource;
+use dom_struct::dom_struct;
+use embedder_traits::{ClipboardContents, EmbedderMsg};
+use ipc_channel::ipc;
+use ipc_channel::router::ROUTER;
+use script_traits::ScriptMsg;
+use std::rc::Rc;
+
+/// The types of data that can be written to the clipboard.
+/// <https://w3c.github.io/clipboard-apis/#mandatory-data-types-x>
+const WRITABLE_TYPES: &[&str] = &["text/plain", "text/html", "image/png"];
+
+// https://w3c.github.io/clipboard-apis/#clipboard-interface
+#[dom_struct]
+pub struct Clipboard {
+    eventtarget: EventTarget,
+}
+
+impl Clipboard {
+    fn new_inherited() -> Clipboard {
+        Clipboard {
+            eventtarget: EventTarget::new_inherited(),
+        }
+    }
+
+    pub fn new(global: &GlobalScope) -> DomRoot<Clipboard> {
+        reflect_dom_object(Box::new(Clipboard::new_inherited()), global, Wrap)
+    }
+
+    /// Whether script may read the clipboard, rejecting `promise` if it may not.
+    /// <https://w3c.github.io/clipboard-apis/#check-clipboard-read-permission>
+    fn is_read_permitted(&self, promise: &Promise) -> bool {
+        let global = self.global();
+        match get_descriptor_permission_state(PermissionName::Clipboard_read, Some(&global)) {
+            PermissionState::Granted => true,
+            _ => {
+                promise.reject_error(Error::NotAllowed);
+                false
+            },
+        }
+    }
+
+    /// Whether script may write to the clipboard, rejecting `promise` if it may not.
+    /// Writes are allowed without asking the user as long as they recently interacted
+    /// with the page, and didn't deny the permission.
+    /// <https://w3c.github.io/clipboard-apis/#check-clipboard-write-permission>
+    fn is_write_permitted(&self, promise: &Promise) -> bool {
+        let global = self.global();
+        let state = get_stored_permission_state(PermissionName::Clipboard_write, &global);
+        if state != PermissionState::Denied && global.as_window().has_transient_activation() {
+            return true;
+        }
+        promise.reject_error(Error::NotAllowed);
+        false
+    }
+
+    /// Ask the embedder for the contents of the system clipboard, and settle `promise` with them.
+    fn read_clipboard(&self, promise: &Rc<Promise>, settle: fn(&Promise, ClipboardContents)) {
+        let global = self.global();
+        let (sender, receiver) = ipc::channel().expect("ipc channel failure");
+        let mut trusted_promise = Some(TrustedPromise::new(promise.clone()));
+        let task_source = global.dom_manipulation_task_source();
+        let canceller = global.task_canceller(DOMManipulationTaskSource::NAME);
+        ROUTER.add_route(
+            receiver.to_opaque(),
+            Box::new(move |message| {
+                let promise = match trusted_promise.take() {
+                    Some(promise) => promise,
+                    None => return,
+                };
+                let contents: ClipboardContents = match message.to() {
+                    Ok(contents) => contents,
+                    Err(e) => return warn!("Error receiving the clipboard contents: {:?}", e),
+                };
+                let _ = task_source.queue_with_canceller(
+                    task!(settle_clipboard_promise: move || {
+                        let promise = promise.root();
+                        let _ac = enter_realm(&*promise);
+                        settle(&promise, contents);
+                    }),
+                    &canceller,
+                );
+            }),
+        );
+        let msg = ScriptMsg::ForwardToEmbedder(EmbedderMsg::GetClipboardContents(sender));
+        if let Err(e) = global.script_to_constellation_chan().send(msg) {
+            warn!("Failed to ask for the clipboard contents ({:?})", e);
+        }
+    }
+
+    /// Replace the contents of the system clipboard, and resolve `promise` once they are.
+    fn write_clipboard(&self, promise: &Promise, contents: ClipboardContents) {
+        let msg = ScriptMsg::ForwardToEmbedder(EmbedderMsg::SetClipboardContents(contents));
+        if let Err(e) = self.global().script_to_constellation_chan().send(msg) {
+            warn!("Failed to set the clipboard contents ({:?})", e);
+        }
+        promise.resolve_native(&());
+    }
+}
+
+impl ClipboardMethods for Clipboard {
+    // https://w3c.github.io/clipboard-apis/#dom-clipboard-read
+    fn Read(&self, comp: InRealm) -> Rc<Promise> {
+        let promise = Promise::new_in_current_realm(&self.global(), comp);
+        if self.is_read_permitted(&promise) {
+            self.read_clipboard(&promise, |promise, contents| {
+                let item = ClipboardItem::from_clipboard_contents(&promise.global(), contents);
+                promise.resolve_native(&vec![item]);
+            });
+        }
+        promise
+    }
+
+    // https://w3c.github.io/clipboard-apis/#dom-clipboard-readtext
+    fn ReadText(&self, comp: InRealm) -> Rc<Promise> {
+        let promise = Promise::new_in_current_realm(&self.global(), comp);
+        if self.is_read_permitted(&promise) {
+            self.read_clipboard(&promise, |promise, contents| {
+                let text = DOMString::from(contents.text().unwrap_or_default());
+                promise.resolve_native(&text);
+            });
+        }
+        promise
+    }
+
+    // https://w3c.github.io/clipboard-apis/#dom-clipboard-write
+    fn Write(&self, data: Vec<DomRoot<ClipboardItem>>, comp: InRealm) -> Rc<Promise> {
+        let promise = Promise::new_in_current_realm(&self.global(), comp);
+        if !self.is_write_permitted(&promise) {
+            return promise;
+        }
+        // The system clipboard holds a single item.
+        let item = match &*data {
+            [item] => item,
+            _ => {
+                promise.reject_error(Error::NotAllowed);
+                return promise;
+            },
+        };
+        if item
+            .types()
+            .any(|mime_type| !WRITABLE_TYPES.contains(&&**mime_type))
+        {
+            promise.reject_error(Error::NotAllowed);
+            return promise;
+        }
+        match item.clipboard_contents() {
+            Ok(contents) => self.write_clipboard(&promise, contents),
+            Err(()) => promise.reject_error(Error::NotReadable),
+        }
+        promise
+    }
+
+    // https://w3c.github.io/clipboard-apis/#dom-clipboard-writetext
+    fn WriteText(&self, data: DOMString, comp: InRealm) -> Rc<Promise> {
+        let promise = Promise::new_in_current_realm(&self.global(), comp);
+        if self.is_write_permitted(&promise) {
+            self.write_clipboard(&promise, ClipboardContents::from_text(data.into()));
+        }
+        promise
+    }
+}
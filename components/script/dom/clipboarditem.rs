@@ -0,0 +1,151 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::dom::bindings::cell::DomRefCell;
+use crate::dom::bindings::codegen::Bindings::ClipboardItemBinding::{ClipboardItemMethods, Wrap};
+use crate::dom::bindings::codegen::UnionTypes::StringOrBlob;
+use crate::dom::bindings::error::{Error, Fallible};
+use crate::dom::bindings::inheritance::Castable;
+use crate::dom::bindings::record::Record;
+use crate::dom::bindings::reflector::{reflect_dom_object, DomObject, Reflector};
+use crate::dom::bindings::root::{Dom, DomRoot};
+use crate::dom::bindings::str::DOMString;
+use crate::dom::bindings::utils::to_frozen_array;
+use crate::dom::blob::Blob;
+use crate::dom::globalscope::GlobalScope;
+use crate::dom::promise::Promise;
+use crate::dom::window::Window;
+use crate::realms::InRealm;
+use crate::script_runtime::JSContext;
+use dom_struct::dom_struct;
+use embedder_traits::ClipboardContents;
+use js::jsapi::Heap;
+use js::jsval::JSVal;
+use script_traits::serializable::BlobImpl;
+use std::rc::Rc;
+
+// https://w3c.github.io/clipboard-apis/#clipboarditem
+#[dom_struct]
+pub struct ClipboardItem {
+    reflector_: Reflector,
+    /// The representations of the item, as their MIME type and data.
+    representations: Vec<(DOMString, Dom<Blob>)>,
+    #[ignore_malloc_size_of = "mozjs"]
+    frozen_types: DomRefCell<Option<Heap<JSVal>>>,
+}
+
+impl ClipboardItem {
+    #[allow(unrooted_must_root)]
+    fn new_inherited(representations: &[(DOMString, DomRoot<Blob>)]) -> ClipboardItem {
+        ClipboardItem {
+            reflector_: Reflector::new(),
+            representations: representations
+                .iter()
+                .map(|(mime_type, blob)| (mime_type.clone(), Dom::from_ref(&**blob)))
+                .collect(),
+            frozen_types: DomRefCell::new(None),
+        }
+    }
+
+    pub fn new(
+        global: &GlobalScope,
+        representations: &[(DOMString, DomRoot<Blob>)],
+    ) -> DomRoot<ClipboardItem> {
+        reflect_dom_object(
+            Box::new(ClipboardItem::new_inherited(representations)),
+            global,
+            Wrap,
+        )
+    }
+
+    /// An item with the contents of the system clipboard.
+    pub fn from_clipboard_contents(
+        global: &GlobalScope,
+        contents: ClipboardContents,
+    ) -> DomRoot<ClipboardItem> {
+        let representations: Vec<_> = contents
+            .representations
+            .into_iter()
+            .map(|(mime_type, data)| {
+                let blob = Blob::new(global, BlobImpl::new_from_bytes(data, mime_type.clone()));
+                (DOMString::from(mime_type), blob)
+            })
+            .collect();
+        ClipboardItem::new(global, &representations)
+    }
+
+    /// The representations of the item, as the data of the clipboard they would be written to.
+    pub fn clipboard_contents(&self) -> Result<ClipboardContents, ()> {
+        let representations = self
+            .representations
+            .iter()
+            .map(|(mime_type, blob)| Ok((String::from(mime_type.clone()), blob.get_bytes()?)))
+            .collect::<Result<Vec<_>, ()>>()?;
+        Ok(ClipboardContents { representations })
+    }
+
+    pub fn types(&self) -> impl Iterator<Item = &DOMString> {
+        self.representations.iter().map(|(mime_type, _)| mime_type)
+    }
+
+    // https://w3c.github.io/clipboard-apis/#dom-clipboarditem-clipboarditem
+    #[allow(non_snake_case)]
+    pub fn Constructor(
+        window: &Window,
+        items: Record<DOMString, StringOrBlob>,
+    ) -> Fallible<DomRoot<ClipboardItem>> {
+        // Step 1.
+        if items.is_empty() {
+            return Err(Error::Type("A clipboard item needs data".to_owned()));
+        }
+        // Steps 2-3.
+        let global = window.upcast::<GlobalScope>();
+        let representations: Vec<_> = items
+            .iter()
+            .map(|(mime_type, data)| {
+                let blob = match data {
+                    StringOrBlob::String(text) => Blob::new(
+                        global,
+                        BlobImpl::new_from_bytes(
+                            text.as_bytes().to_vec(),
+                            mime_type.to_ascii_lowercase(),
+                        ),
+                    ),
+                    StringOrBlob::Blob(blob) => DomRoot::from_ref(&**blob),
+                };
+                (mime_type.clone(), blob)
+            })
+            .collect();
+        Ok(ClipboardItem::new(global, &representations))
+    }
+}
+
+impl ClipboardItemMethods for ClipboardItem {
+    // https://w3c.github.io/clipboard-apis/#dom-clipboarditem-types
+    fn Types(&self, cx: JSContext) -> JSVal {
+        if let Some(types) = &*self.frozen_types.borrow() {
+            return types.get();
+        }
+        let types: Vec<DOMString> = self.types().cloned().collect();
+        let frozen_types = to_frozen_array(types.as_slice(), cx);
+        let heap_val = Heap::default();
+        heap_val.set(frozen_types);
+        *self.frozen_types.borrow_mut() = Some(heap_val);
+        frozen_types
+    }
+
+    // https://w3c.github.io/clipboard-apis/#dom-clipboarditem-gettype
+    fn GetType(&self, type_: DOMString, comp: InRealm) -> Rc<Promise> {
+        let promise = Promise::new_in_current_realm(&self.global(), comp);
+        match self
+            .representations
+            .iter()
+            .find(|(mime_type, _)| *mime_type == type_)
+        {
+            Some((_, blob)) => promise.resolve_native(&DomRoot::from_ref(&**blob)),
+            None => promise.reject_error(Error::NotFound),
+        }
+        promise
+    }
+}
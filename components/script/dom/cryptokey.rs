@@ -0,0 +1,260 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::dom::bindings::codegen::Bindings::CryptoKeyBinding;
+use crate::dom::bindings::codegen::Bindings::CryptoKeyBinding::{
+    CryptoKeyMethods, KeyType, KeyUsage,
+};
+use crate::dom::bindings::reflector::{reflect_dom_object, Reflector};
+use crate::dom::bindings::root::DomRoot;
+use crate::dom::bindings::utils::set_dictionary_property;
+use crate::dom::globalscope::GlobalScope;
+use crate::dom::subtlecrypto::{AlgorithmName, NamedCurve};
+use crate::script_runtime::JSContext;
+use crate::streams::create_uint8_array;
+use dom_struct::dom_struct;
+use js::conversions::ToJSValConvertible;
+use js::jsapi::{Heap, JSObject, JS_NewPlainObject};
+use js::jsval::{ObjectValue, UndefinedValue};
+use js::rust::HandleObject;
+use openssl::pkey::{PKey, Private, Public};
+use std::ptr::NonNull;
+
+/// The parameters of the algorithm a key can be used with.
+/// <https://w3c.github.io/webcrypto/#dom-cryptokey-algorithm>
+#[derive(Clone, Debug, JSTraceable, MallocSizeOf, PartialEq)]
+pub enum KeyAlgorithm {
+    Aes {
+        name: AlgorithmName,
+        length: u16,
+    },
+    Hmac {
+        hash: AlgorithmName,
+        length: u32,
+    },
+    RsaHashed {
+        name: AlgorithmName,
+        modulus_length: u32,
+        public_exponent: Vec<u8>,
+        hash: AlgorithmName,
+    },
+    Ec {
+        name: AlgorithmName,
+        named_curve: NamedCurve,
+    },
+    Kdf {
+        name: AlgorithmName,
+    },
+}
+
+impl KeyAlgorithm {
+    pub fn name(&self) -> AlgorithmName {
+        match *self {
+            KeyAlgorithm::Aes { name, .. } |
+            KeyAlgorithm::RsaHashed { name, .. } |
+            KeyAlgorithm::Ec { name, .. } |
+            KeyAlgorithm::Kdf { name } => name,
+            KeyAlgorithm::Hmac { .. } => AlgorithmName::Hmac,
+        }
+    }
+
+    /// The hash algorithm the key is used with, if any.
+    pub fn hash(&self) -> Option<AlgorithmName> {
+        match *self {
+            KeyAlgorithm::Hmac { hash, .. } | KeyAlgorithm::RsaHashed { hash, .. } => Some(hash),
+            _ => None,
+        }
+    }
+
+    pub fn named_curve(&self) -> Option<NamedCurve> {
+        match *self {
+            KeyAlgorithm::Ec { named_curve, .. } => Some(named_curve),
+            _ => None,
+        }
+    }
+}
+
+/// The key material behind a `CryptoKey`.
+#[derive(Clone)]
+pub enum KeyHandle {
+    Secret(Vec<u8>),
+    Private(PKey<Private>),
+    Public(PKey<Public>),
+}
+
+unsafe_no_jsmanaged_fields!(KeyHandle);
+
+// https://w3c.github.io/webcrypto/#cryptokey-interface
+#[dom_struct]
+pub struct CryptoKey {
+    reflector_: Reflector,
+    extractable: bool,
+    algorithm: KeyAlgorithm,
+    usages: Vec<KeyUsage>,
+    #[ignore_malloc_size_of = "Defined in openssl"]
+    handle: KeyHandle,
+    /// The object returned by the `algorithm` attribute, created on first access.
+    #[ignore_malloc_size_of = "mozjs"]
+    algorithm_object: Heap<*mut JSObject>,
+    /// The object returned by the `usages` attribute, created on first access.
+    #[ignore_malloc_size_of = "mozjs"]
+    usages_object: Heap<*mut JSObject>,
+}
+
+impl CryptoKey {
+    fn new_inherited(
+        extractable: bool,
+        algorithm: KeyAlgorithm,
+        usages: Vec<KeyUsage>,
+        handle: KeyHandle,
+    ) -> CryptoKey {
+        CryptoKey {
+            reflector_: Reflector::new(),
+            extractable,
+            algorithm,
+            usages,
+            handle,
+            algorithm_object: Heap::default(),
+            usages_object: Heap::default(),
+        }
+    }
+
+    pub fn new(
+        global: &GlobalScope,
+        extractable: bool,
+        algorithm: KeyAlgorithm,
+        usages: Vec<KeyUsage>,
+        handle: KeyHandle,
+    ) -> DomRoot<CryptoKey> {
+        reflect_dom_object(
+            Box::new(CryptoKey::new_inherited(
+                extractable,
+                algorithm,
+                usages,
+                handle,
+            )),
+            global,
+            CryptoKeyBinding::Wrap,
+        )
+    }
+
+    pub fn key_type(&self) -> KeyType {
+        match self.handle {
+            KeyHandle::Secret(_) => KeyType::Secret,
+            KeyHandle::Private(_) => KeyType::Private,
+            KeyHandle::Public(_) => KeyType::Public,
+        }
+    }
+
+    pub fn is_extractable(&self) -> bool {
+        self.extractable
+    }
+
+    pub fn algorithm(&self) -> &KeyAlgorithm {
+        &self.algorithm
+    }
+
+    pub fn usages(&self) -> &[KeyUsage] {
+        &self.usages
+    }
+
+    pub fn has_usage(&self, usage: KeyUsage) -> bool {
+        self.usages.contains(&usage)
+    }
+
+    pub fn handle(&self) -> &KeyHandle {
+        &self.handle
+    }
+
+    #[allow(unsafe_code)]
+    fn create_algorithm_object(&self, cx: JSContext) -> *mut JSObject {
+        rooted!(in(*cx) let object = unsafe { JS_NewPlainObject(*cx) });
+        assert!(!object.is_null());
+        set_property(cx, object.handle(), "name", self.algorithm.name().as_str());
+        match self.algorithm {
+            KeyAlgorithm::Aes { length, .. } => {
+                set_property(cx, object.handle(), "length", &length);
+            },
+            KeyAlgorithm::Hmac { hash, length } => {
+                set_hash_property(cx, object.handle(), hash);
+                set_property(cx, object.handle(), "length", &length);
+            },
+            KeyAlgorithm::RsaHashed {
+                modulus_length,
+                ref public_exponent,
+                hash,
+                ..
+            } => {
+                set_property(cx, object.handle(), "modulusLength", &modulus_length);
+                rooted!(in(*cx) let mut exponent = UndefinedValue());
+                create_uint8_array(cx, public_exponent, exponent.handle_mut())
+                    .expect("Failed to create the public exponent of a key");
+                set_dictionary_property(*cx, object.handle(), "publicExponent", exponent.handle())
+                    .expect("Failed to set the public exponent of a key");
+                set_hash_property(cx, object.handle(), hash);
+            },
+            KeyAlgorithm::Ec { named_curve, .. } => {
+                set_property(cx, object.handle(), "namedCurve", named_curve.as_str());
+            },
+            KeyAlgorithm::Kdf { .. } => {},
+        }
+        object.get()
+    }
+}
+
+/// Set the property `name` of `object` to `value`.
+#[allow(unsafe_code)]
+pub fn set_property<T: ToJSValConvertible + ?Sized>(
+    cx: JSContext,
+    object: HandleObject,
+    name: &str,
+    value: &T,
+) {
+    rooted!(in(*cx) let mut js_value = UndefinedValue());
+    unsafe { value.to_jsval(*cx, js_value.handle_mut()) };
+    set_dictionary_property(*cx, object, name, js_value.handle())
+        .expect("Failed to set a property of a crypto object");
+}
+
+/// Set the `hash` property of `object` to a `KeyAlgorithm` dictionary naming `hash`.
+#[allow(unsafe_code)]
+fn set_hash_property(cx: JSContext, object: HandleObject, hash: AlgorithmName) {
+    rooted!(in(*cx) let hash_object = unsafe { JS_NewPlainObject(*cx) });
+    assert!(!hash_object.is_null());
+    set_property(cx, hash_object.handle(), "name", hash.as_str());
+    rooted!(in(*cx) let hash_value = ObjectValue(hash_object.get()));
+    set_dictionary_property(*cx, object, "hash", hash_value.handle())
+        .expect("Failed to set the hash of a key algorithm");
+}
+
+impl CryptoKeyMethods for CryptoKey {
+    // https://w3c.github.io/webcrypto/#dom-cryptokey-type
+    fn Type(&self) -> KeyType {
+        self.key_type()
+    }
+
+    // https://w3c.github.io/webcrypto/#dom-cryptokey-extractable
+    fn Extractable(&self) -> bool {
+        self.extractable
+    }
+
+    // https://w3c.github.io/webcrypto/#dom-cryptokey-algorithm
+    fn Algorithm(&self, cx: JSContext) -> NonNull<JSObject> {
+        if self.algorithm_object.get().is_null() {
+            self.algorithm_object.set(self.create_algorithm_object(cx));
+        }
+        NonNull::new(self.algorithm_object.get()).unwrap()
+    }
+
+    // https://w3c.github.io/webcrypto/#dom-cryptokey-usages
+    #[allow(unsafe_code)]
+    fn Usages(&self, cx: JSContext) -> NonNull<JSObject> {
+        if self.usages_object.get().is_null() {
+            rooted!(in(*cx) let mut usages = UndefinedValue());
+            unsafe { self.usages.to_jsval(*cx, usages.handle_mut()) };
+            self.usages_object.set(usages.to_object());
+        }
+        NonNull::new(self.usages_object.get()).unwrap()
+    }
+}
@@ -0,0 +1,250 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::dom::bindings::cell::DomRefCell;
+use crate::dom::bindings::codegen::Bindings::DataTransferBinding::{DataTransferMethods, Wrap};
+use crate::dom::bindings::inheritance::Castable;
+use crate::dom::bindings::reflector::{reflect_dom_object, DomObject, Reflector};
+use crate::dom::bindings::root::{Dom, DomRoot, MutNullableDom};
+use crate::dom::bindings::str::DOMString;
+use crate::dom::bindings::utils::to_frozen_array;
+use crate::dom::blob::Blob;
+use crate::dom::file::File;
+use crate::dom::filelist::FileList;
+use crate::dom::window::Window;
+use crate::script_runtime::JSContext;
+use dom_struct::dom_struct;
+use embedder_traits::ClipboardContents;
+use js::jsapi::Heap;
+use js::jsval::JSVal;
+use std::cell::Cell;
+
+/// <https://html.spec.whatwg.org/multipage/#drag-data-store-mode>
+#[derive(Clone, Copy, JSTraceable, MallocSizeOf, PartialEq)]
+pub enum DataStoreMode {
+    ReadWrite,
+    ReadOnly,
+    Protected,
+}
+
+/// The format an item is stored under, for a format given to `getData` and friends.
+fn normalize_format(format: &str) -> DOMString {
+    let format = format.to_ascii_lowercase();
+    DOMString::from(match &*format {
+        "text" => "text/plain",
+        "url" => "text/uri-list",
+        _ => &*format,
+    })
+}
+
+// https://html.spec.whatwg.org/multipage/#datatransfer
+#[dom_struct]
+pub struct DataTransfer {
+    reflector_: Reflector,
+    drop_effect: DomRefCell<DOMString>,
+    effect_allowed: DomRefCell<DOMString>,
+    mode: Cell<DataStoreMode>,
+    /// The plain Unicode string items of the drag data store, as their format and data.
+    strings: DomRefCell<Vec<(DOMString, DOMString)>>,
+    /// The file items of the drag data store.
+    files: DomRefCell<Vec<Dom<File>>>,
+    file_list: MutNullableDom<FileList>,
+    #[ignore_malloc_size_of = "mozjs"]
+    frozen_types: DomRefCell<Option<Heap<JSVal>>>,
+}
+
+impl DataTransfer {
+    fn new_inherited(mode: DataStoreMode) -> DataTransfer {
+        DataTransfer {
+            reflector_: Reflector::new(),
+            drop_effect: DomRefCell::new(DOMString::from("none")),
+            effect_allowed: DomRefCell::new(DOMString::from("none")),
+            mode: Cell::new(mode),
+            strings: DomRefCell::new(vec![]),
+            files: DomRefCell::new(vec![]),
+            file_list: Default::default(),
+            frozen_types: DomRefCell::new(None),
+        }
+    }
+
+    pub fn new(window: &Window, mode: DataStoreMode) -> DomRoot<DataTransfer> {
+        reflect_dom_object(Box::new(DataTransfer::new_inherited(mode)), window, Wrap)
+    }
+
+    // https://html.spec.whatwg.org/multipage/#dom-datatransfer
+    #[allow(non_snake_case)]
+    pub fn Constructor(window: &Window) -> DomRoot<DataTransfer> {
+        DataTransfer::new(window, DataStoreMode::ReadWrite)
+    }
+
+    pub fn set_mode(&self, mode: DataStoreMode) {
+        self.mode.set(mode);
+    }
+
+    /// Adds an item of the user agent to the drag data store, whatever its mode.
+    pub fn add_string(&self, format: &str, data: DOMString) {
+        self.strings
+            .borrow_mut()
+            .push((normalize_format(format), data));
+        *self.frozen_types.borrow_mut() = None;
+    }
+
+    /// The data of a string item of the drag data store, whatever its mode.
+    pub fn string(&self, format: &str) -> Option<DOMString> {
+        let format = normalize_format(format);
+        self.strings
+            .borrow()
+            .iter()
+            .find(|(item_format, _)| *item_format == format)
+            .map(|(_, data)| data.clone())
+    }
+
+    /// Adds a file of the user agent to the drag data store, whatever its mode.
+    pub fn add_file(&self, file: &File) {
+        self.files.borrow_mut().push(Dom::from_ref(file));
+        *self.frozen_types.borrow_mut() = None;
+    }
+
+    /// The items of the drag data store, as the data of the clipboard they would be written to.
+    pub fn clipboard_contents(&self) -> ClipboardContents {
+        let mut representations: Vec<(String, Vec<u8>)> = self
+            .strings
+            .borrow()
+            .iter()
+            .map(|(format, data)| (String::from(format.clone()), data.as_bytes().to_vec()))
+            .collect();
+        for file in self.files.borrow().iter() {
+            let blob = file.upcast::<Blob>();
+            if let Ok(bytes) = blob.get_bytes() {
+                representations.push((blob.type_string(), bytes));
+            }
+        }
+        ClipboardContents { representations }
+    }
+}
+
+impl DataTransferMethods for DataTransfer {
+    // https://html.spec.whatwg.org/multipage/#dom-datatransfer-dropeffect
+    fn DropEffect(&self) -> DOMString {
+        self.drop_effect.borrow().clone()
+    }
+
+    // https://html.spec.whatwg.org/multipage/#dom-datatransfer-dropeffect
+    fn SetDropEffect(&self, value: DOMString) {
+        match &*value {
+            "none" | "copy" | "link" | "move" => *self.drop_effect.borrow_mut() = value,
+            _ => {},
+        }
+    }
+
+    // https://html.spec.whatwg.org/multipage/#dom-datatransfer-effectallowed
+    fn EffectAllowed(&self) -> DOMString {
+        self.effect_allowed.borrow().clone()
+    }
+
+    // https://html.spec.whatwg.org/multipage/#dom-datatransfer-effectallowed
+    fn SetEffectAllowed(&self, value: DOMString) {
+        if self.mode.get() != DataStoreMode::ReadWrite {
+            return;
+        }
+        match &*value {
+            "none" | "copy" | "copyLink" | "copyMove" | "link" | "linkMove" | "move" | "all" |
+            "uninitialized" => *self.effect_allowed.borrow_mut() = value,
+            _ => {},
+        }
+    }
+
+    // https://html.spec.whatwg.org/multipage/#dom-datatransfer-types
+    fn Types(&self, cx: JSContext) -> JSVal {
+        if let Some(types) = &*self.frozen_types.borrow() {
+            return types.get();
+        }
+        let mut types: Vec<DOMString> = self
+            .strings
+            .borrow()
+            .iter()
+            .map(|(format, _)| format.clone())
+            .collect();
+        if !self.files.borrow().is_empty() {
+            types.push(DOMString::from("Files"));
+        }
+        let frozen_types = to_frozen_array(types.as_slice(), cx);
+        let heap_val = Heap::default();
+        heap_val.set(frozen_types);
+        *self.frozen_types.borrow_mut() = Some(heap_val);
+        frozen_types
+    }
+
+    // https://html.spec.whatwg.org/multipage/#dom-datatransfer-getdata
+    fn GetData(&self, format: DOMString) -> DOMString {
+        // Step 2.
+        if self.mode.get() == DataStoreMode::Protected {
+            return DOMString::new();
+        }
+        // Steps 3-5.
+        let convert_to_url = format.eq_ignore_ascii_case("url");
+        // Step 6.
+        let data = match self.string(&format) {
+            Some(data) => data,
+            None => return DOMString::new(),
+        };
+        // Step 7: the first URL of a text/uri-list, whose other lines are comments.
+        if convert_to_url {
+            return data
+                .lines()
+                .map(str::trim)
+                .find(|line| !line.is_empty() && !line.starts_with('#'))
+                .map_or_else(DOMString::new, DOMString::from);
+        }
+        // Step 8.
+        data
+    }
+
+    // https://html.spec.whatwg.org/multipage/#dom-datatransfer-setdata
+    fn SetData(&self, format: DOMString, data: DOMString) {
+        // Step 2.
+        if self.mode.get() != DataStoreMode::ReadWrite {
+            return;
+        }
+        // Steps 3-4.
+        let format = normalize_format(&format);
+        // Steps 5-6.
+        let mut strings = self.strings.borrow_mut();
+        strings.retain(|(item_format, _)| *item_format != format);
+        strings.push((format, data));
+        *self.frozen_types.borrow_mut() = None;
+    }
+
+    // https://html.spec.whatwg.org/multipage/#dom-datatransfer-cleardata
+    fn ClearData(&self, format: Option<DOMString>) {
+        // Step 2.
+        if self.mode.get() != DataStoreMode::ReadWrite {
+            return;
+        }
+        // Steps 3-5.
+        match format {
+            None => self.strings.borrow_mut().clear(),
+            Some(format) => {
+                let format = normalize_format(&format);
+                self.strings
+                    .borrow_mut()
+                    .retain(|(item_format, _)| *item_format != format);
+            },
+        }
+        *self.frozen_types.borrow_mut() = None;
+    }
+
+    // https://html.spec.whatwg.org/multipage/#dom-datatransfer-files
+    fn Files(&self) -> DomRoot<FileList> {
+        self.file_list.or_init(|| {
+            let files = self
+                .files
+                .borrow()
+                .iter()
+                .map(|file| DomRoot::from_ref(&**file))
+                .collect();
+            FileList::new(self.global().as_window(), files)
+        })
+    }
+}
@@ -292,6 +292,7 @@ impl DedicatedWorkerGlobalScope {
         let top_level_browsing_context_id = TopLevelBrowsingContextId::installed();
         let current_global = GlobalScope::current().expect("No current global object");
         let origin = current_global.origin().immutable().clone();
+        let csp_list = current_global.get_csp_list();
         let parent = current_global.runtime_handle();
 
         thread::Builder::new()
@@ -323,7 +324,8 @@ impl DedicatedWorkerGlobalScope {
                     .pipeline_id(Some(pipeline_id))
                     .referrer(referrer)
                     .referrer_policy(referrer_policy)
-                    .origin(origin);
+                    .origin(origin)
+                    .csp_list(csp_list);
 
                 let runtime = unsafe {
                     let task_source = NetworkingTaskSource(
@@ -410,6 +412,10 @@ impl DedicatedWorkerGlobalScope {
                             while !scope.is_closing() {
                                 run_worker_event_loop(&*global, Some(&worker));
                             }
+                            // Let other connections to the databases of the worker upgrade them.
+                            scope.upcast::<GlobalScope>().close_indexeddb_connections();
+                            // Stop receiving messages on the ports and channels of the worker.
+                            scope.upcast::<GlobalScope>().remove_web_messaging_infra();
                         },
                         reporter_name,
                         parent_sender,
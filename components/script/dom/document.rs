@@ -2,6 +2,7 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 
+use crate::clipboard_provider::ClipboardProvider;
 use crate::document_loader::{DocumentLoader, LoadType};
 use crate::dom::activation::{synthetic_click_activation, ActivationSource};
 use crate::dom::attr::Attr;
@@ -18,13 +19,15 @@ use crate::dom::bindings::codegen::Bindings::HTMLIFrameElementBinding::HTMLIFram
 use crate::dom::bindings::codegen::Bindings::NodeBinding::NodeMethods;
 use crate::dom::bindings::codegen::Bindings::NodeFilterBinding::NodeFilter;
 use crate::dom::bindings::codegen::Bindings::PerformanceBinding::PerformanceMethods;
+use crate::dom::bindings::codegen::Bindings::RangeBinding::RangeMethods;
+use crate::dom::bindings::codegen::Bindings::SelectionBinding::SelectionMethods;
 use crate::dom::bindings::codegen::Bindings::ShadowRootBinding::ShadowRootMethods;
 use crate::dom::bindings::codegen::Bindings::TouchBinding::TouchMethods;
 use crate::dom::bindings::codegen::Bindings::WindowBinding::{
     FrameRequestCallback, ScrollBehavior, WindowMethods,
 };
 use crate::dom::bindings::codegen::UnionTypes::{NodeOrString, StringOrElementCreationOptions};
-use crate::dom::bindings::error::{Error, ErrorResult, Fallible};
+use crate::dom::bindings::error::{Error, ErrorInfo, ErrorResult, Fallible};
 use crate::dom::bindings::inheritance::{Castable, ElementTypeId, HTMLElementTypeId, NodeTypeId};
 use crate::dom::bindings::num::Finite;
 use crate::dom::bindings::refcounted::{Trusted, TrustedPromise};
@@ -35,12 +38,15 @@ use crate::dom::bindings::xmlname::XMLName::InvalidXMLName;
 use crate::dom::bindings::xmlname::{
     namespace_from_domstring, validate_and_extract, xml_name_type,
 };
+use crate::dom::caretposition::CaretPosition;
 use crate::dom::cdatasection::CDATASection;
+use crate::dom::clipboardevent::{ClipboardEvent, ClipboardEventType};
 use crate::dom::comment::Comment;
 use crate::dom::compositionevent::CompositionEvent;
 use crate::dom::cssstylesheet::CSSStyleSheet;
 use crate::dom::customelementregistry::CustomElementDefinition;
 use crate::dom::customevent::CustomEvent;
+use crate::dom::datatransfer::{DataStoreMode, DataTransfer};
 use crate::dom::documentfragment::DocumentFragment;
 use crate::dom::documentorshadowroot::{DocumentOrShadowRoot, StyleSheetInDocument};
 use crate::dom::documenttype::DocumentType;
@@ -51,6 +57,7 @@ use crate::dom::element::{
 };
 use crate::dom::event::{Event, EventBubbles, EventCancelable, EventDefault, EventStatus};
 use crate::dom::eventtarget::EventTarget;
+use crate::dom::file::File;
 use crate::dom::focusevent::FocusEvent;
 use crate::dom::globalscope::GlobalScope;
 use crate::dom::hashchangeevent::HashChangeEvent;
@@ -66,8 +73,11 @@ use crate::dom::htmlheadelement::HTMLHeadElement;
 use crate::dom::htmlhtmlelement::HTMLHtmlElement;
 use crate::dom::htmliframeelement::HTMLIFrameElement;
 use crate::dom::htmlimageelement::HTMLImageElement;
+use crate::dom::htmlinputelement::HTMLInputElement;
 use crate::dom::htmlscriptelement::{HTMLScriptElement, ScriptResult};
+use crate::dom::htmltextareaelement::HTMLTextAreaElement;
 use crate::dom::htmltitleelement::HTMLTitleElement;
+use crate::dom::intersectionobserver::IntersectionObserver;
 use crate::dom::keyboardevent::KeyboardEvent;
 use crate::dom::location::Location;
 use crate::dom::messageevent::MessageEvent;
@@ -80,6 +90,8 @@ use crate::dom::pagetransitionevent::PageTransitionEvent;
 use crate::dom::processinginstruction::ProcessingInstruction;
 use crate::dom::promise::Promise;
 use crate::dom::range::Range;
+use crate::dom::resizeobserver::ResizeObserver;
+use crate::dom::selection::Selection;
 use crate::dom::servoparser::ServoParser;
 use crate::dom::shadowroot::ShadowRoot;
 use crate::dom::storageevent::StorageEvent;
@@ -95,6 +107,7 @@ use crate::dom::webglrenderingcontext::WebGLRenderingContext;
 use crate::dom::wheelevent::WheelEvent;
 use crate::dom::window::{ReflowReason, Window};
 use crate::dom::windowproxy::WindowProxy;
+use crate::editing::{self, EditCommand, EditHistory};
 use crate::fetch::FetchCanceller;
 use crate::realms::{AlreadyInRealm, InRealm};
 use crate::script_runtime::JSContext;
@@ -104,19 +117,20 @@ use crate::stylesheet_set::StylesheetSetRef;
 use crate::task::TaskBox;
 use crate::task_source::{TaskSource, TaskSourceName};
 use crate::timers::OneshotTimerCallback;
+use crate::user_activation::is_activation_triggering_key_event;
 use canvas_traits::webgl::{self, SwapChainId, WebGLContextId, WebGLMsg};
 use content_security_policy::{self as csp, CspList};
-use cookie::Cookie;
 use devtools_traits::ScriptToDevtoolsControlMsg;
 use dom_struct::dom_struct;
-use embedder_traits::EmbedderMsg;
+use embedder_traits::{ClipboardContents, EmbedderMsg};
 use encoding_rs::{Encoding, UTF_8};
 use euclid::default::Point2D;
+use html5ever::serialize::{serialize, SerializeOpts, TraversalScope};
 use html5ever::{LocalName, Namespace, QualName};
-use hyper_serde::Serde;
 use ipc_channel::ipc::{self, IpcSender};
 use js::jsapi::{JSObject, JSRuntime};
-use keyboard_types::{Code, Key, KeyState};
+use js::rust::HandleValue;
+use keyboard_types::{Code, Key, KeyState, Modifiers};
 use metrics::{
     InteractiveFlag, InteractiveMetrics, InteractiveWindow, ProfilerMetadataFactory,
     ProgressiveWebMetric,
@@ -124,17 +138,18 @@ use metrics::{
 use mime::{self, Mime};
 use msg::constellation_msg::BrowsingContextId;
 use net_traits::pub_domains::is_pub_domain;
-use net_traits::request::RequestBuilder;
+use net_traits::request::{CspContext, RequestBuilder};
 use net_traits::response::HttpsState;
 use net_traits::CookieSource::NonHTTP;
 use net_traits::CoreResourceMsg::{GetCookiesForUrl, SetCookiesForUrl};
-use net_traits::{FetchResponseMsg, IpcSend, ReferrerPolicy};
+use net_traits::{CrossOriginEmbedderPolicy, FetchResponseMsg, IpcSend, ReferrerPolicy};
 use num_traits::ToPrimitive;
 use percent_encoding::percent_decode;
 use profile_traits::ipc as profile_ipc;
 use profile_traits::time::{TimerMetadata, TimerMetadataFrameType, TimerMetadataReflowType};
 use ref_slice::ref_slice;
 use script_layout_interface::message::{Msg, ReflowGoal};
+use script_traits::serializable::BlobImpl;
 use script_traits::{AnimationState, DocumentActivity, MouseButton, MouseEventType};
 use script_traits::{
     MsDuration, ScriptMsg, TouchEventType, TouchId, UntrustedNodeAddress, WheelDelta,
@@ -399,6 +414,33 @@ pub struct Document {
     /// https://html.spec.whatwg.org/multipage/#concept-document-csp-list
     #[ignore_malloc_size_of = "Defined in rust-content-security-policy"]
     csp_list: DomRefCell<Option<CspList>>,
+    /// The endpoints named by the `Reporting-Endpoints` header of the document's response,
+    /// <https://w3c.github.io/reporting/#header>
+    reporting_endpoints: DomRefCell<HashMap<String, ServoUrl>>,
+    /// The origins of the documents of the ancestor browsing contexts, nearest first.
+    ancestor_origins: DomRefCell<Vec<ImmutableOrigin>>,
+    /// <https://html.spec.whatwg.org/multipage/#policy-container-embedder-policy>
+    embedder_policy: Cell<CrossOriginEmbedderPolicy>,
+    /// The intersection observers of the window of this document that observe any target.
+    intersection_observers: DomRefCell<Vec<Dom<IntersectionObserver>>>,
+    /// <https://w3c.github.io/IntersectionObserver/#document-intersectionobservertaskqueued>
+    intersection_observer_task_queued: Cell<bool>,
+    /// <https://drafts.csswg.org/resize-observer/#dom-document-resizeobservers-slot>
+    resize_observers: DomRefCell<Vec<Dom<ResizeObserver>>>,
+    /// <https://w3c.github.io/selection-api/#dfn-selection>
+    selection: MutNullableDom<Selection>,
+    /// <https://w3c.github.io/selection-api/#dfn-has-scheduled-selectionchange-event>
+    has_scheduled_selectionchange_event: Cell<bool>,
+    /// Whether the selection changed since layout was last told what to highlight.
+    selection_highlight_dirty: Cell<bool>,
+    /// Whether the selection is being extended by dragging the mouse.
+    selecting_with_mouse: Cell<bool>,
+    /// The modifier keys held down as of the last key event.
+    modifiers: Cell<Modifiers>,
+    /// <https://html.spec.whatwg.org/multipage/#design-mode-enabled>
+    design_mode: Cell<bool>,
+    /// The edits of the content of editing hosts that can be undone and redone.
+    edit_history: DomRefCell<EditHistory>,
 }
 
 #[derive(JSTraceable, MallocSizeOf)]
@@ -610,6 +652,7 @@ impl Document {
             self.GetDocumentElement().map_or(false, |root| {
                 root.upcast::<Node>().has_dirty_descendants() ||
                     !self.pending_restyles.borrow().is_empty() ||
+                    self.selection_highlight_dirty.get() ||
                     self.needs_paint()
             })
     }
@@ -903,6 +946,9 @@ impl Document {
             let node = elem.upcast::<Node>();
             // FIXME: pass appropriate relatedTarget
             self.fire_focus_event(FocusEventType::Focus, node, None);
+            if node.is_editing_host() {
+                editing::editing_host_focused(self, elem);
+            }
             // Update the focus state for all elements in the focus chain.
             // https://html.spec.whatwg.org/multipage/#focus-chain
             if focus_type == FocusType::Element {
@@ -948,7 +994,7 @@ impl Document {
     pub fn handle_mouse_event(
         &self,
         js_runtime: *mut JSRuntime,
-        _button: MouseButton,
+        button: MouseButton,
         client_point: Point2D<f32>,
         mouse_event_type: MouseEventType,
         node_address: Option<UntrustedNodeAddress>,
@@ -1015,19 +1061,37 @@ impl Document {
         // https://html.spec.whatwg.org/multipage/#run-authentic-click-activation-steps
         let activatable = el.as_maybe_activatable();
         match mouse_event_type {
-            MouseEventType::Click => el.authentic_click_activation(event),
+            MouseEventType::Click => {
+                el.authentic_click_activation(event);
+                // Clicking editable content focuses its editing host.
+                if let Some(host) = node.editing_host() {
+                    self.request_focus(&host);
+                }
+            },
             MouseEventType::MouseDown => {
                 if let Some(a) = activatable {
                     a.enter_formal_activation_state();
                 }
 
+                // https://html.spec.whatwg.org/multipage/#activation-triggering-input-event
+                self.window.notify_activation();
+
                 let target = node.upcast();
                 event.fire(target);
+
+                // Text controls handle selection within themselves.
+                let is_text_control = el.is::<HTMLInputElement>() || el.is::<HTMLTextAreaElement>();
+                if let MouseButton::Left = button {
+                    if !event.DefaultPrevented() && !is_text_control {
+                        self.begin_mouse_selection(client_point);
+                    }
+                }
             },
             MouseEventType::MouseUp => {
                 if let Some(a) = activatable {
                     a.exit_formal_activation_state();
                 }
+                self.selecting_with_mouse.set(false);
 
                 let target = node.upcast();
                 event.fire(target);
@@ -1177,6 +1241,14 @@ impl Document {
             pressed_mouse_buttons,
         );
 
+        if self.selecting_with_mouse.get() {
+            if pressed_mouse_buttons & MouseButton::Left as u16 != 0 {
+                self.extend_mouse_selection(client_point);
+            } else {
+                self.selecting_with_mouse.set(false);
+            }
+        }
+
         // Nothing more to do here, mousemove is sent,
         // and the element under the mouse hasn't changed.
         if maybe_new_target == prev_mouse_over_target.get() {
@@ -1396,6 +1468,10 @@ impl Document {
             false,
         );
         let event = event.upcast::<Event>();
+        // https://html.spec.whatwg.org/multipage/#activation-triggering-input-event
+        if let TouchEventType::Up = event_type {
+            window.notify_activation();
+        }
         let result = event.fire(&target);
 
         window.reflow(ReflowGoal::Full, ReflowReason::MouseEvent);
@@ -1408,6 +1484,7 @@ impl Document {
 
     /// The entry point for all key processing for web content
     pub fn dispatch_key_event(&self, keyboard_event: ::keyboard_types::KeyboardEvent) {
+        self.modifiers.set(keyboard_event.modifiers);
         let focused = self.get_focused_element();
         let body = self.GetBody();
 
@@ -1434,6 +1511,10 @@ impl Document {
             keyboard_event.key.legacy_keycode(),
         );
         let event = keyevent.upcast::<Event>();
+        // https://html.spec.whatwg.org/multipage/#activation-triggering-input-event
+        if is_activation_triggering_key_event(&keyboard_event) {
+            self.window.notify_activation();
+        }
         event.fire(target);
         let mut cancel_state = event.get_cancel_state();
 
@@ -1469,6 +1550,25 @@ impl Document {
             let msg = EmbedderMsg::Keyboard(keyboard_event.clone());
             self.send_to_embedder(msg);
 
+            // Text controls take their own clipboard actions.
+            let focused_text_control = focused.as_ref().map_or(false, |focused| {
+                focused.is::<HTMLInputElement>() || focused.is::<HTMLTextAreaElement>()
+            });
+            if !focused_text_control {
+                let clipboard_action = ClipboardEventType::from_shortcut(
+                    keyboard_event.key.clone(),
+                    keyboard_event.modifiers,
+                );
+                match clipboard_action {
+                    Some(action) if keyboard_event.state == KeyState::Down => {
+                        self.take_clipboard_action(action)
+                    },
+                    _ => {
+                        editing::handle_key_event(self, &keyboard_event);
+                    },
+                }
+            }
+
             // This behavior is unspecced
             // We are supposed to dispatch synthetic click activation for Space and/or Return,
             // however *when* we do it is up to us.
@@ -1733,6 +1833,7 @@ impl Document {
         fetch_target: IpcSender<FetchResponseMsg>,
     ) {
         request.csp_list = self.get_csp_list().map(|x| x.clone());
+        request.embedder_policy = self.embedder_policy();
         let mut loader = self.loader.borrow_mut();
         loader.fetch_async(load, request, fetch_target);
     }
@@ -1919,6 +2020,7 @@ impl Document {
         }
         // https://w3c.github.io/FileAPI/#lifeTime
         global_scope.clean_up_all_file_resources();
+        global_scope.close_indexeddb_connections();
 
         // Step 15, End
         self.decr_ignore_opens_during_unload_counter();
@@ -2827,10 +2929,36 @@ impl Document {
             media_controls: DomRefCell::new(HashMap::new()),
             dirty_webgl_contexts: DomRefCell::new(HashMap::new()),
             csp_list: DomRefCell::new(None),
+            reporting_endpoints: DomRefCell::new(HashMap::new()),
+            ancestor_origins: DomRefCell::new(vec![]),
+            embedder_policy: Cell::new(CrossOriginEmbedderPolicy::UnsafeNone),
+            intersection_observers: DomRefCell::new(vec![]),
+            intersection_observer_task_queued: Cell::new(false),
+            resize_observers: DomRefCell::new(vec![]),
+            selection: MutNullableDom::new(None),
+            has_scheduled_selectionchange_event: Cell::new(false),
+            selection_highlight_dirty: Cell::new(false),
+            selecting_with_mouse: Cell::new(false),
+            modifiers: Cell::new(Modifiers::empty()),
+            design_mode: Cell::new(false),
+            edit_history: Default::default(),
         }
     }
 
     pub fn set_csp_list(&self, csp_list: Option<CspList>) {
+        if csp_list.is_some() {
+            // Layout fetches web fonts on behalf of the document.
+            let global = self.window.upcast::<GlobalScope>();
+            let csp_context = CspContext {
+                origin: self.origin.immutable().clone(),
+                csp_list: csp_list.clone(),
+                violation_sender: global.csp_violation_sender(),
+            };
+            self.window
+                .layout_chan()
+                .send(Msg::SetCspContext(csp_context))
+                .unwrap();
+        }
         *self.csp_list.borrow_mut() = csp_list;
     }
 
@@ -2850,13 +2978,395 @@ impl Document {
                 .get_attribute(&ns!(), &local_name!("nonce"))
                 .map(|attr| Cow::Owned(attr.value().to_string())),
         };
-        // TODO: Instead of ignoring violations, report them.
-        self.get_csp_list()
-            .map(|c| {
-                c.should_elements_inline_type_behavior_be_blocked(&element, type_, source)
-                    .0
-            })
-            .unwrap_or(csp::CheckResult::Allowed)
+        let (result, violations) = match self.get_csp_list() {
+            Some(csp_list) => {
+                csp_list.should_elements_inline_type_behavior_be_blocked(&element, type_, source)
+            },
+            None => return csp::CheckResult::Allowed,
+        };
+        if !violations.is_empty() {
+            self.window
+                .upcast::<GlobalScope>()
+                .report_csp_violations(violations, Some(el));
+        }
+        result
+    }
+
+    pub fn set_reporting_endpoints(&self, endpoints: HashMap<String, ServoUrl>) {
+        *self.reporting_endpoints.borrow_mut() = endpoints;
+    }
+
+    pub fn reporting_endpoint(&self, name: &str) -> Option<ServoUrl> {
+        self.reporting_endpoints.borrow().get(name).cloned()
+    }
+
+    pub fn set_ancestor_origins(&self, ancestor_origins: Vec<ImmutableOrigin>) {
+        *self.ancestor_origins.borrow_mut() = ancestor_origins;
+    }
+
+    /// The origins of the documents of the ancestor browsing contexts, nearest first.
+    pub fn ancestor_origins(&self) -> Vec<ImmutableOrigin> {
+        self.ancestor_origins.borrow().clone()
+    }
+
+    pub fn set_embedder_policy(&self, embedder_policy: CrossOriginEmbedderPolicy) {
+        self.embedder_policy.set(embedder_policy);
+    }
+
+    pub fn embedder_policy(&self) -> CrossOriginEmbedderPolicy {
+        self.embedder_policy.get()
+    }
+
+    pub fn add_intersection_observer(&self, observer: &IntersectionObserver) {
+        let mut observers = self.intersection_observers.borrow_mut();
+        if !observers.iter().any(|other| &**other == observer) {
+            observers.push(Dom::from_ref(observer));
+        }
+    }
+
+    pub fn remove_intersection_observer(&self, observer: &IntersectionObserver) {
+        self.intersection_observers
+            .borrow_mut()
+            .retain(|other| &**other != observer);
+    }
+
+    /// <https://w3c.github.io/IntersectionObserver/#run-the-update-intersection-observations-steps>
+    pub fn update_intersection_observations(&self) {
+        if self.intersection_observers.borrow().is_empty() {
+            return;
+        }
+        // Step 1.
+        let time = *self.window.upcast::<GlobalScope>().performance().Now();
+        // Step 2.
+        let observers: Vec<DomRoot<IntersectionObserver>> = self
+            .intersection_observers
+            .borrow()
+            .iter()
+            .map(|observer| DomRoot::from_ref(&**observer))
+            .collect();
+        for observer in observers {
+            observer.update_intersection_observations(time);
+        }
+    }
+
+    /// <https://w3c.github.io/IntersectionObserver/#queue-an-intersection-observer-task>
+    pub fn queue_intersection_observer_task(&self) {
+        // Steps 1-2.
+        if self.intersection_observer_task_queued.get() {
+            return;
+        }
+        self.intersection_observer_task_queued.set(true);
+        // Step 3.
+        let document = Trusted::new(self);
+        let _ = self
+            .window
+            .task_manager()
+            .dom_manipulation_task_source()
+            .queue(
+                task!(notify_intersection_observers: move || {
+                    document.root().notify_intersection_observers();
+                }),
+                self.window.upcast(),
+            );
+    }
+
+    /// <https://w3c.github.io/IntersectionObserver/#notify-intersection-observers-algo>
+    fn notify_intersection_observers(&self) {
+        // Step 1.
+        self.intersection_observer_task_queued.set(false);
+        // Steps 2-3.
+        let observers: Vec<DomRoot<IntersectionObserver>> = self
+            .intersection_observers
+            .borrow()
+            .iter()
+            .map(|observer| DomRoot::from_ref(&**observer))
+            .collect();
+        for observer in observers {
+            observer.notify();
+        }
+    }
+
+    pub fn add_resize_observer(&self, observer: &ResizeObserver) {
+        self.resize_observers
+            .borrow_mut()
+            .push(Dom::from_ref(observer));
+    }
+
+    fn resize_observers(&self) -> Vec<DomRoot<ResizeObserver>> {
+        self.resize_observers
+            .borrow()
+            .iter()
+            .map(|observer| DomRoot::from_ref(&**observer))
+            .collect()
+    }
+
+    /// <https://drafts.csswg.org/resize-observer/#gather-active-observations-h>
+    pub fn gather_active_resize_observations_at_depth(&self, depth: usize) {
+        for observer in self.resize_observers() {
+            observer.gather_active_observations_at_depth(depth);
+        }
+    }
+
+    /// <https://drafts.csswg.org/resize-observer/#has-active-observations-h>
+    pub fn has_active_resize_observations(&self) -> bool {
+        self.resize_observers
+            .borrow()
+            .iter()
+            .any(|observer| observer.has_active_observations())
+    }
+
+    /// <https://drafts.csswg.org/resize-observer/#has-skipped-observations-h>
+    pub fn has_skipped_resize_observations(&self) -> bool {
+        self.resize_observers
+            .borrow()
+            .iter()
+            .any(|observer| observer.has_skipped_observations())
+    }
+
+    /// <https://drafts.csswg.org/resize-observer/#broadcast-active-observations>
+    pub fn broadcast_active_resize_observations(&self) -> usize {
+        self.resize_observers()
+            .iter()
+            .map(|observer| observer.broadcast_active_observations())
+            .min()
+            .unwrap_or(usize::max_value())
+    }
+
+    /// <https://drafts.csswg.org/resize-observer/#deliver-resize-error>
+    pub fn deliver_resize_loop_error_notification(&self) {
+        let error_info = ErrorInfo {
+            message: "ResizeObserver loop completed with undelivered notifications.".to_owned(),
+            filename: self.url().into_string(),
+            lineno: 0,
+            column: 0,
+        };
+        self.window
+            .upcast::<GlobalScope>()
+            .report_an_error(error_info, HandleValue::null());
+    }
+
+    /// <https://w3c.github.io/selection-api/#dfn-selection>
+    pub fn selection(&self) -> DomRoot<Selection> {
+        self.selection.or_init(|| Selection::new(self))
+    }
+
+    /// Called when the selection of this document changes, to schedule a `selectionchange`
+    /// event and to update its highlight with the next reflow.
+    pub fn selection_changed(&self) {
+        self.selection_highlight_dirty.set(true);
+        // https://w3c.github.io/selection-api/#scheduling-selectionhange-event
+        if self.has_scheduled_selectionchange_event.get() {
+            return;
+        }
+        self.has_scheduled_selectionchange_event.set(true);
+        let document = Trusted::new(self);
+        let _ = self
+            .window
+            .task_manager()
+            .user_interaction_task_source()
+            .queue(
+                task!(fire_selectionchange_event: move || {
+                    let document = document.root();
+                    document.has_scheduled_selectionchange_event.set(false);
+                    document.upcast::<EventTarget>().fire_event(atom!("selectionchange"));
+                }),
+                self.window.upcast(),
+            );
+    }
+
+    /// Tells layout which text the selection covers, before a reflow. The highlight is
+    /// recomputed when the DOM changed as well, since the selection may now cover other text.
+    pub fn update_selection_highlight(&self) {
+        let selection = match self.selection.get() {
+            Some(selection) => selection,
+            None => return,
+        };
+        let dom_changed = self
+            .GetDocumentElement()
+            .map_or(false, |root| root.upcast::<Node>().has_dirty_descendants());
+        if !self.selection_highlight_dirty.get() && !(dom_changed && selection.has_highlight()) {
+            return;
+        }
+        self.selection_highlight_dirty.set(false);
+        selection.update_highlight();
+    }
+
+    // https://html.spec.whatwg.org/multipage/#design-mode-enabled
+    pub fn design_mode(&self) -> bool {
+        self.design_mode.get()
+    }
+
+    pub fn edit_history(&self) -> &DomRefCell<EditHistory> {
+        &self.edit_history
+    }
+
+    /// Starts selecting with the mouse at a point in the viewport, collapsing the selection
+    /// there, or extending it there when shift is held.
+    fn begin_mouse_selection(&self, client_point: Point2D<f32>) {
+        if !self.has_browsing_context {
+            return;
+        }
+        let (node, offset) = match self.document_or_shadow_root.caret_from_point(&client_point) {
+            Some(caret) => caret,
+            None => return,
+        };
+        let selection = self.selection();
+        if self.modifiers.get().contains(Modifiers::SHIFT) && selection.RangeCount() > 0 {
+            let _ = selection.Extend(&node, offset);
+        } else {
+            // https://w3c.github.io/selection-api/#selectstart-event
+            let event = node
+                .upcast::<EventTarget>()
+                .fire_bubbling_cancelable_event(atom!("selectstart"));
+            if event.DefaultPrevented() {
+                return;
+            }
+            let _ = selection.Collapse(Some(&node), offset);
+        }
+        self.selecting_with_mouse.set(true);
+    }
+
+    /// Extends the selection being made with the mouse to a point in the viewport.
+    fn extend_mouse_selection(&self, client_point: Point2D<f32>) {
+        if let Some((node, offset)) = self.document_or_shadow_root.caret_from_point(&client_point) {
+            let _ = self.selection().Extend(&node, offset);
+        }
+    }
+
+    /// Takes a clipboard action of the user, unless script cancels its clipboard event.
+    /// <https://w3c.github.io/clipboard-apis/#clipboard-actions>
+    fn take_clipboard_action(&self, action: ClipboardEventType) {
+        let data_transfer = match self.fire_clipboard_event(action) {
+            Some(data_transfer) => data_transfer,
+            None => return,
+        };
+        match action {
+            ClipboardEventType::Copy => self.copy_selection_to_clipboard(),
+            ClipboardEventType::Cut => {
+                if editing::active_editing_host(self).is_some() {
+                    self.copy_selection_to_clipboard();
+                    editing::delete_by_cut(self);
+                }
+            },
+            ClipboardEventType::Paste => {
+                if let Some(text) = data_transfer.string("text/plain") {
+                    editing::insert_from_paste(self, &text);
+                }
+            },
+        }
+    }
+
+    /// Fires the event of a clipboard action at the focused area of the document, or at the
+    /// editable content the selection is in, returning its data if it was not canceled.
+    /// <https://w3c.github.io/clipboard-apis/#fire-a-clipboard-event>
+    pub fn fire_clipboard_event(
+        &self,
+        action: ClipboardEventType,
+    ) -> Option<DomRoot<DataTransfer>> {
+        // A text control takes the action if it is focused, and otherwise editable content does,
+        // falling back to the focused element.
+        let target: DomRoot<Node> = match self.get_focused_element() {
+            Some(focused)
+                if focused.is::<HTMLInputElement>() || focused.is::<HTMLTextAreaElement>() =>
+            {
+                DomRoot::upcast(focused)
+            },
+            focused => match (editing::active_editing_host(self), self.selection().range()) {
+                (Some(host), Some(range)) => range
+                    .StartContainer()
+                    .inclusive_ancestors(ShadowIncluding::No)
+                    .find(|node| node.is::<Element>())
+                    .unwrap_or_else(|| DomRoot::upcast(host)),
+                _ => match focused.or_else(|| self.GetBody().map(DomRoot::upcast)) {
+                    Some(element) => DomRoot::upcast(element),
+                    None => DomRoot::from_ref(self.upcast::<Node>()),
+                },
+            },
+        };
+
+        // Only a paste makes the clipboard readable, and only a copy or a cut makes it writable.
+        let mode = match action {
+            ClipboardEventType::Paste => DataStoreMode::ReadOnly,
+            ClipboardEventType::Copy | ClipboardEventType::Cut => DataStoreMode::ReadWrite,
+        };
+        let data_transfer = DataTransfer::new(&self.window, mode);
+        let mut clipboard = self
+            .window
+            .upcast::<GlobalScope>()
+            .script_to_constellation_chan()
+            .clone();
+        if action == ClipboardEventType::Paste {
+            let contents = clipboard.clipboard_data();
+            for (mime_type, data) in contents.representations {
+                if mime_type.starts_with("text/") {
+                    let data = String::from_utf8_lossy(&data).into_owned();
+                    data_transfer.add_string(&mime_type, DOMString::from(data));
+                } else if mime_type.starts_with("image/") {
+                    let name = DOMString::from(mime_type.replacen('/', ".", 1));
+                    let blob_impl = BlobImpl::new_from_bytes(data, mime_type);
+                    let file = File::new(self.window.upcast(), blob_impl, name, None);
+                    data_transfer.add_file(&file);
+                }
+            }
+        }
+
+        let event = ClipboardEvent::new(
+            &self.window,
+            Atom::from(action.as_str()),
+            EventBubbles::Bubbles,
+            EventCancelable::Cancelable,
+            Some(&data_transfer),
+        );
+        let status = event.upcast::<Event>().fire(target.upcast());
+        data_transfer.set_mode(DataStoreMode::Protected);
+        if status == EventStatus::NotCanceled {
+            return Some(data_transfer);
+        }
+
+        // Script provides the data of the clipboard in place of a copy or a cut.
+        if action != ClipboardEventType::Paste {
+            let contents = data_transfer.clipboard_contents();
+            if !contents.representations.is_empty() {
+                clipboard.set_clipboard_data(contents);
+            }
+        }
+        None
+    }
+
+    /// The selected content, as markup and as plain text.
+    fn selection_clipboard_contents(&self) -> Option<ClipboardContents> {
+        let selection = self.selection.get()?;
+        let text = selection.Stringifier();
+        if text.is_empty() {
+            return None;
+        }
+        let mut representations = vec![];
+        if let Some(fragment) = selection
+            .range()
+            .and_then(|range| range.CloneContents().ok())
+        {
+            let mut markup = vec![];
+            let opts = SerializeOpts {
+                traversal_scope: TraversalScope::ChildrenOnly(None),
+                ..Default::default()
+            };
+            if serialize(&mut markup, &fragment.upcast::<Node>(), opts).is_ok() {
+                representations.push(("text/html".to_owned(), markup));
+            }
+        }
+        representations.push(("text/plain".to_owned(), String::from(text).into_bytes()));
+        Some(ClipboardContents { representations })
+    }
+
+    /// Copies the selected content to the clipboard of the embedder.
+    fn copy_selection_to_clipboard(&self) {
+        if let Some(contents) = self.selection_clipboard_contents() {
+            self.window
+                .upcast::<GlobalScope>()
+                .script_to_constellation_chan()
+                .clone()
+                .set_clipboard_data(contents);
+        }
     }
 
     /// Prevent any JS or layout from running until the corresponding call to
@@ -4240,7 +4750,7 @@ impl DocumentMethods for Document {
             .window
             .upcast::<GlobalScope>()
             .resource_threads()
-            .send(GetCookiesForUrl(url, tx, NonHTTP));
+            .send(GetCookiesForUrl(url, self.ancestor_origins(), tx, NonHTTP));
         let cookies = rx.recv().unwrap();
         Ok(cookies.map_or(DOMString::new(), DOMString::from))
     }
@@ -4255,17 +4765,16 @@ impl DocumentMethods for Document {
             return Err(Error::Security);
         }
 
-        let cookies = if let Some(cookie) = Cookie::parse(cookie.to_string()).ok().map(Serde) {
-            vec![cookie]
-        } else {
-            vec![]
-        };
-
         let _ = self
             .window
             .upcast::<GlobalScope>()
             .resource_threads()
-            .send(SetCookiesForUrl(self.url(), cookies, NonHTTP));
+            .send(SetCookiesForUrl(
+                self.url(),
+                vec![cookie.into()],
+                self.ancestor_origins(),
+                NonHTTP,
+            ));
         Ok(())
     }
 
@@ -4418,6 +4927,114 @@ impl DocumentMethods for Document {
         )
     }
 
+    // https://drafts.csswg.org/cssom-view/#dom-document-caretpositionfrompoint
+    fn CaretPositionFromPoint(
+        &self,
+        x: Finite<f64>,
+        y: Finite<f64>,
+    ) -> Option<DomRoot<CaretPosition>> {
+        self.document_or_shadow_root
+            .caret_position_from_point(x, y, self.has_browsing_context)
+    }
+
+    // https://html.spec.whatwg.org/multipage/#dom-document-designmode
+    fn DesignMode(&self) -> DOMString {
+        DOMString::from(if self.design_mode.get() { "on" } else { "off" })
+    }
+
+    // https://html.spec.whatwg.org/multipage/#dom-document-designmode
+    fn SetDesignMode(&self, value: DOMString) {
+        // Step 1.
+        let value = value.to_ascii_lowercase();
+        // Step 2.
+        if value == "on" && !self.design_mode.get() {
+            self.design_mode.set(true);
+            if let Some(root) = self.GetDocumentElement() {
+                let _ = self.selection().Collapse(Some(root.upcast()), 0);
+            }
+            // TODO: Run the focusing steps for the document element.
+        }
+        // Step 3.
+        if value == "off" && self.design_mode.get() {
+            self.design_mode.set(false);
+            // Text that was editable no longer gets a caret drawn in it.
+            self.selection_highlight_dirty.set(true);
+        }
+    }
+
+    // https://w3c.github.io/editing/docs/execCommand/#execcommand()
+    fn ExecCommand(
+        &self,
+        command_id: DOMString,
+        _show_ui: bool,
+        value: DOMString,
+    ) -> Fallible<bool> {
+        if !self.is_html_document {
+            return Err(Error::InvalidState);
+        }
+        Ok(EditCommand::from_name(&command_id)
+            .map_or(false, |command| editing::exec_command(self, command, value)))
+    }
+
+    // https://w3c.github.io/editing/docs/execCommand/#querycommandenabled()
+    fn QueryCommandEnabled(&self, command_id: DOMString) -> Fallible<bool> {
+        if !self.is_html_document {
+            return Err(Error::InvalidState);
+        }
+        Ok(
+            EditCommand::from_name(&command_id).map_or(false, |command| {
+                editing::query_command_enabled(self, command)
+            }),
+        )
+    }
+
+    // https://w3c.github.io/editing/docs/execCommand/#querycommandindeterm()
+    fn QueryCommandIndeterm(&self, command_id: DOMString) -> Fallible<bool> {
+        if !self.is_html_document {
+            return Err(Error::InvalidState);
+        }
+        Ok(
+            EditCommand::from_name(&command_id).map_or(false, |command| {
+                editing::query_command_indeterm(self, command)
+            }),
+        )
+    }
+
+    // https://w3c.github.io/editing/docs/execCommand/#querycommandstate()
+    fn QueryCommandState(&self, command_id: DOMString) -> Fallible<bool> {
+        if !self.is_html_document {
+            return Err(Error::InvalidState);
+        }
+        Ok(EditCommand::from_name(&command_id)
+            .map_or(false, |command| editing::query_command_state(self, command)))
+    }
+
+    // https://w3c.github.io/editing/docs/execCommand/#querycommandsupported()
+    fn QueryCommandSupported(&self, command_id: DOMString) -> Fallible<bool> {
+        if !self.is_html_document {
+            return Err(Error::InvalidState);
+        }
+        Ok(EditCommand::from_name(&command_id).is_some())
+    }
+
+    // https://w3c.github.io/editing/docs/execCommand/#querycommandvalue()
+    fn QueryCommandValue(&self, _command_id: DOMString) -> Fallible<DOMString> {
+        if !self.is_html_document {
+            return Err(Error::InvalidState);
+        }
+        // None of the supported commands have a value.
+        Ok(DOMString::new())
+    }
+
+    // https://w3c.github.io/selection-api/#dom-document-getselection
+    fn GetSelection(&self) -> Option<DomRoot<Selection>> {
+        if self.has_browsing_context {
+            Some(self.selection())
+        } else {
+            None
+        }
+    }
+
     // https://html.spec.whatwg.org/multipage/#dom-document-open
     fn Open(
         &self,
@@ -3,19 +3,25 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 
 use crate::dom::bindings::cell::DomRefCell;
+use crate::dom::bindings::codegen::Bindings::DocumentBinding::DocumentMethods;
 use crate::dom::bindings::codegen::Bindings::NodeBinding::NodeBinding::NodeMethods;
 use crate::dom::bindings::inheritance::Castable;
 use crate::dom::bindings::num::Finite;
 use crate::dom::bindings::root::{Dom, DomRoot};
+use crate::dom::caretposition::CaretPosition;
+use crate::dom::characterdata::CharacterData;
 use crate::dom::element::Element;
 use crate::dom::htmlelement::HTMLElement;
 use crate::dom::htmlmetaelement::HTMLMetaElement;
-use crate::dom::node::{self, Node, VecPreOrderInsertionHelper};
+use crate::dom::node::{self, window_from_node, Node, VecPreOrderInsertionHelper};
+use crate::dom::text::Text;
 use crate::dom::window::Window;
 use crate::stylesheet_set::StylesheetSetRef;
-use euclid::default::Point2D;
+use app_units::Au;
+use euclid::default::{Point2D, Rect, Size2D, Vector2D};
 use js::jsapi::JS_GetRuntime;
 use script_layout_interface::message::{NodesFromPointQueryType, QueryMsg};
+use script_layout_interface::rpc::TextIndexResponse;
 use script_traits::UntrustedNodeAddress;
 use servo_arc::Arc;
 use servo_atoms::Atom;
@@ -75,6 +81,131 @@ impl ::style::stylesheets::StylesheetInDocument for StyleSheetInDocument {
     }
 }
 
+fn au_rect_to_f32_rect(rect: Rect<Au>) -> Rect<f32> {
+    Rect::new(
+        Point2D::new(rect.origin.x.to_f32_px(), rect.origin.y.to_f32_px()),
+        Size2D::new(rect.size.width.to_f32_px(), rect.size.height.to_f32_px()),
+    )
+}
+
+/// A point in the viewport, in the coordinate space layout positions the boxes of a node in,
+/// accounting for the scrolling of its ancestors.
+fn page_point(node: &Node, client_point: &Point2D<f32>) -> Point2D<f32> {
+    let document_element = node.owner_doc().GetDocumentElement();
+    let viewport = window_from_node(node).current_viewport().origin;
+    let mut point = *client_point + Vector2D::new(viewport.x.to_f32_px(), viewport.y.to_f32_px());
+    for ancestor in node.ancestors().filter_map(DomRoot::downcast::<Element>) {
+        // The document element's overflow applies to the viewport instead.
+        if ancestor.potentially_scrollable() && Some(&ancestor) != document_element.as_ref() {
+            let offset = ancestor.upcast::<Node>().scroll_offset();
+            point += Vector2D::new(offset.x.abs(), offset.y.abs());
+        }
+    }
+    point
+}
+
+/// Maps an index into the characters layout rendered for a text node to an offset into its
+/// data, skipping the white space that was collapsed away.
+fn offset_of_rendered_index(text: &Text, index: usize) -> u32 {
+    let collapses_white_space = text
+        .upcast::<Node>()
+        .GetParentElement()
+        .and_then(|parent| parent.style())
+        .map_or(true, |style| {
+            !style.get_inherited_text().white_space.preserve_spaces()
+        });
+    let data = text.upcast::<CharacterData>().data();
+    let mut rendered_index = 0;
+    let mut offset = 0;
+    // White space at the start of the node is assumed to collapse with what precedes it.
+    let mut follows_white_space = collapses_white_space;
+    for c in data.chars() {
+        let is_white_space = collapses_white_space && c.is_ascii_whitespace();
+        if !(is_white_space && follows_white_space) {
+            if rendered_index == index {
+                return offset;
+            }
+            rendered_index += 1;
+        }
+        follows_white_space = is_white_space;
+        offset += c.len_utf16() as u32;
+    }
+    offset
+}
+
+/// The offset of the caret at a point in the page, within a text node.
+fn text_offset_at(text: &Text, point: Point2D<f32>) -> u32 {
+    let node = text.upcast::<Node>();
+    let first_box = match node.content_boxes().first() {
+        Some(first_box) => au_rect_to_f32_rect(*first_box),
+        None => return 0,
+    };
+    let point_in_node = point - first_box.origin.to_vector();
+    let TextIndexResponse(index) = window_from_node(node).text_index_query(node, point_in_node);
+    index.map_or(0, |index| offset_of_rendered_index(text, index))
+}
+
+/// How far a position is from a span along one axis.
+fn distance_to_span(position: f32, start: f32, end: f32) -> f32 {
+    if position < start {
+        start - position
+    } else if position > end {
+        position - end
+    } else {
+        0.
+    }
+}
+
+/// The boundary point closest to a point in the page, within an element that was hit
+/// where it has no text of its own.
+fn caret_in_element(node: &Node, point: Point2D<f32>) -> (DomRoot<Node>, u32) {
+    // Find the child box closest to the point, vertically first.
+    let mut closest: Option<((f32, f32), DomRoot<Node>, Rect<f32>)> = None;
+    for child in node.children() {
+        let boxes = if child.is::<Text>() {
+            child.content_boxes()
+        } else if child.is::<Element>() {
+            child.bounding_content_box().into_iter().collect()
+        } else {
+            continue;
+        };
+        for child_box in boxes {
+            let child_box = au_rect_to_f32_rect(child_box);
+            let distance = (
+                distance_to_span(point.y, child_box.min_y(), child_box.max_y()),
+                distance_to_span(point.x, child_box.min_x(), child_box.max_x()),
+            );
+            if closest
+                .as_ref()
+                .map_or(true, |&(closest_distance, ..)| distance < closest_distance)
+            {
+                closest = Some((distance, child.clone(), child_box));
+            }
+        }
+    }
+
+    let (child, child_box) = match closest {
+        Some((_, child, child_box)) => (child, child_box),
+        None => return (DomRoot::from_ref(node), 0),
+    };
+    if child.is::<Text>() {
+        // Move the point into the box, so that layout picks a character on its line.
+        let clamp = |position: f32, start: f32, end: f32| position.min(end - 1.).max(start);
+        let point = Point2D::new(
+            clamp(point.x, child_box.min_x(), child_box.max_x()),
+            clamp(point.y, child_box.min_y(), child_box.max_y()),
+        );
+        let offset = text_offset_at(child.downcast::<Text>().unwrap(), point);
+        return (child, offset);
+    }
+    let offset = if point.x > child_box.center().x {
+        child.index() + 1
+    } else {
+        child.index()
+    };
+    (DomRoot::from_ref(node), offset)
+}
+
 // https://w3c.github.io/webcomponents/spec/shadow/#extensions-to-the-documentorshadowroot-mixin
 #[unrooted_must_root_lint::must_root]
 #[derive(JSTraceable, MallocSizeOf)]
@@ -144,6 +275,45 @@ impl DocumentOrShadowRoot {
         }
     }
 
+    /// The boundary point the caret would be placed at for a point in the viewport.
+    #[allow(unsafe_code)]
+    pub fn caret_from_point(&self, client_point: &Point2D<f32>) -> Option<(DomRoot<Node>, u32)> {
+        let address = *self
+            .nodes_from_point(client_point, NodesFromPointQueryType::Topmost)
+            .first()?;
+        let js_runtime = unsafe { JS_GetRuntime(*self.window.get_cx()) };
+        let node = unsafe { node::from_untrusted_node_address(js_runtime, address) };
+        let point = page_point(&node, client_point);
+        if let Some(text) = node.downcast::<Text>() {
+            let offset = text_offset_at(text, point);
+            return Some((DomRoot::from_ref(text.upcast()), offset));
+        }
+        Some(caret_in_element(&node, point))
+    }
+
+    // https://drafts.csswg.org/cssom-view/#dom-document-caretpositionfrompoint
+    pub fn caret_position_from_point(
+        &self,
+        x: Finite<f64>,
+        y: Finite<f64>,
+        has_browsing_context: bool,
+    ) -> Option<DomRoot<CaretPosition>> {
+        let x = *x as f32;
+        let y = *y as f32;
+        let viewport = self.window.window_size().initial_viewport;
+
+        if !has_browsing_context {
+            return None;
+        }
+
+        if x < 0.0 || y < 0.0 || x > viewport.width || y > viewport.height {
+            return None;
+        }
+
+        let (node, offset) = self.caret_from_point(&Point2D::new(x, y))?;
+        Some(CaretPosition::new(&self.window, &node, offset))
+    }
+
     #[allow(unsafe_code)]
     // https://drafts.csswg.org/cssom-view/#dom-document-elementsfrompoint
     pub fn elements_from_point(
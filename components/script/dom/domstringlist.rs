@@ -7,7 +7,7 @@ use crate::dom::bindings::codegen::Bindings::DOMStringListBinding::DOMStringList
 use crate::dom::bindings::reflector::{reflect_dom_object, Reflector};
 use crate::dom::bindings::root::DomRoot;
 use crate::dom::bindings::str::DOMString;
-use crate::dom::window::Window;
+use crate::dom::globalscope::GlobalScope;
 use dom_struct::dom_struct;
 
 #[dom_struct]
@@ -17,7 +17,6 @@ pub struct DOMStringList {
 }
 
 impl DOMStringList {
-    #[allow(unused)]
     pub fn new_inherited(strings: Vec<DOMString>) -> DOMStringList {
         DOMStringList {
             reflector_: Reflector::new(),
@@ -25,11 +24,10 @@ impl DOMStringList {
         }
     }
 
-    #[allow(unused)]
-    pub fn new(window: &Window, strings: Vec<DOMString>) -> DomRoot<DOMStringList> {
+    pub fn new(global: &GlobalScope, strings: Vec<DOMString>) -> DomRoot<DOMStringList> {
         reflect_dom_object(
             Box::new(DOMStringList::new_inherited(strings)),
-            window,
+            global,
             DOMStringListBinding::Wrap,
         )
     }
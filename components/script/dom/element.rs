@@ -86,6 +86,7 @@ use crate::dom::window::ReflowReason;
 use crate::script_thread::ScriptThread;
 use crate::stylesheet_loader::StylesheetOwner;
 use crate::task::TaskOnce;
+use content_security_policy as csp;
 use devtools_traits::AttrInfo;
 use dom_struct::dom_struct;
 use html5ever::serialize;
@@ -408,7 +409,7 @@ impl Element {
     }
 
     // https://drafts.csswg.org/cssom-view/#potentially-scrollable
-    fn potentially_scrollable(&self) -> bool {
+    pub fn potentially_scrollable(&self) -> bool {
         self.has_css_layout_box() && !self.has_any_visible_overflow()
     }
 
@@ -2716,7 +2717,7 @@ impl VirtualMethods for Element {
                             _ => false,
                         };
 
-                        let block = if is_declaration {
+                        if is_declaration {
                             let mut value = AttrValue::String(String::new());
                             attr.swap_value(&mut value);
                             let (serialization, block) = match value {
@@ -2725,18 +2726,26 @@ impl VirtualMethods for Element {
                             };
                             let mut value = AttrValue::String(serialization);
                             attr.swap_value(&mut value);
-                            block
+                            Some(block)
+                        } else if doc.should_elements_inline_type_behavior_be_blocked(
+                            self,
+                            csp::InlineCheckType::StyleAttribute,
+                            &attr.value(),
+                        ) == csp::CheckResult::Blocked
+                        {
+                            // https://drafts.csswg.org/cssom/#the-elementcssinlinestyle-mixin
+                            None
                         } else {
                             let win = window_from_node(self);
-                            Arc::new(doc.style_shared_lock().wrap(parse_style_attribute(
-                                &attr.value(),
-                                &doc.base_url(),
-                                win.css_error_reporter(),
-                                doc.quirks_mode(),
+                            Some(Arc::new(doc.style_shared_lock().wrap(
+                                parse_style_attribute(
+                                    &attr.value(),
+                                    &doc.base_url(),
+                                    win.css_error_reporter(),
+                                    doc.quirks_mode(),
+                                ),
                             )))
-                        };
-
-                        Some(block)
+                        }
                     },
                     AttributeMutation::Removed => None,
                 };
@@ -17,6 +17,8 @@ use crate::dom::bindings::str::DOMString;
 use crate::dom::document::Document;
 use crate::dom::eventtarget::{CompiledEventListener, EventTarget, ListenerPhase};
 use crate::dom::globalscope::GlobalScope;
+use crate::dom::idbrequest::IDBRequest;
+use crate::dom::idbtransaction::IDBTransaction;
 use crate::dom::node::Node;
 use crate::dom::performance::reduce_timing_resolution;
 use crate::dom::virtualmethods::vtable_for;
@@ -139,6 +141,16 @@ impl Event {
                     event_path.push(DomRoot::from_ref(document.window().upcast()));
                 }
             }
+        } else if let Some(request) = target.downcast::<IDBRequest>() {
+            // https://w3c.github.io/IndexedDB/#request-construct
+            if let Some(transaction) = request.transaction() {
+                let db = transaction.db();
+                event_path.push(DomRoot::upcast(transaction));
+                event_path.push(DomRoot::upcast(db));
+            }
+        } else if let Some(transaction) = target.downcast::<IDBTransaction>() {
+            // https://w3c.github.io/IndexedDB/#transaction-construct
+            event_path.push(DomRoot::upcast(transaction.db()));
         }
         event_path
     }
@@ -705,8 +705,18 @@ impl EventTarget {
             Some(l) => l,
             None => return,
         };
+
+        // Step 2 of https://dom.spec.whatwg.org/#add-an-event-listener
+        if options
+            .signal
+            .as_ref()
+            .map_or(false, |signal| signal.aborted())
+        {
+            return;
+        }
+
         let mut handlers = self.handlers.borrow_mut();
-        let entry = match handlers.entry(Atom::from(ty)) {
+        let entry = match handlers.entry(Atom::from(ty.clone())) {
             Occupied(entry) => entry.into_mut(),
             Vacant(entry) => entry.insert(EventListeners(vec![])),
         };
@@ -718,11 +728,16 @@ impl EventTarget {
         };
         let new_entry = EventListenerEntry {
             phase: phase,
-            listener: EventListenerType::Additive(listener),
+            listener: EventListenerType::Additive(listener.clone()),
             once: options.once,
         };
         if !entry.contains(&new_entry) {
             entry.push(new_entry);
+
+            // Step 6 of https://dom.spec.whatwg.org/#add-an-event-listener
+            if let Some(signal) = options.signal {
+                signal.add_event_listener_removal(self, ty, listener, options.parent.capture);
+            }
         }
     }
 
@@ -804,6 +819,7 @@ impl From<AddEventListenerOptionsOrBoolean> for AddEventListenerOptions {
             AddEventListenerOptionsOrBoolean::Boolean(capture) => Self {
                 parent: EventListenerOptions { capture },
                 once: false,
+                signal: None,
             },
         }
     }
@@ -6,21 +6,31 @@ use crate::dom::bindings::codegen::Bindings::EventBinding::{self, EventMethods};
 use crate::dom::bindings::codegen::Bindings::ExtendableEventBinding;
 use crate::dom::bindings::error::{Error, ErrorResult, Fallible};
 use crate::dom::bindings::inheritance::Castable;
-use crate::dom::bindings::reflector::reflect_dom_object;
-use crate::dom::bindings::root::DomRoot;
+use crate::dom::bindings::reflector::{reflect_dom_object, DomObject};
+use crate::dom::bindings::root::{Dom, DomRoot};
 use crate::dom::bindings::str::DOMString;
 use crate::dom::event::Event;
+use crate::dom::promise::Promise;
 use crate::dom::serviceworkerglobalscope::ServiceWorkerGlobalScope;
 use crate::script_runtime::JSContext;
+use crate::streams::react_to_promise;
 use dom_struct::dom_struct;
 use js::rust::HandleValue;
 use servo_atoms::Atom;
+use std::cell::Cell;
 
 // https://w3c.github.io/ServiceWorker/#extendable-event
 #[dom_struct]
 pub struct ExtendableEvent {
     event: Event,
-    extensions_allowed: bool,
+    extensions_allowed: Cell<bool>,
+    /// The number of promises passed to `waitUntil` that haven't settled yet.
+    pending_promises: Cell<u32>,
+    /// Whether one of the promises passed to `waitUntil` was rejected.
+    rejected: Cell<bool>,
+    /// Whether the service worker waits for the promises passed to `waitUntil`
+    /// before going on with its lifecycle.
+    notify_when_settled: Cell<bool>,
 }
 
 #[allow(non_snake_case)]
@@ -28,7 +38,10 @@ impl ExtendableEvent {
     pub fn new_inherited() -> ExtendableEvent {
         ExtendableEvent {
             event: Event::new_inherited(),
-            extensions_allowed: true,
+            extensions_allowed: Cell::new(true),
+            pending_promises: Cell::new(0),
+            rejected: Cell::new(false),
+            notify_when_settled: Cell::new(false),
         }
     }
     pub fn new(
@@ -62,14 +75,73 @@ impl ExtendableEvent {
         ))
     }
 
+    // https://w3c.github.io/ServiceWorker/#dfn-active
+    fn is_active(&self) -> bool {
+        self.event.dispatching() || self.pending_promises.get() > 0
+    }
+
+    /// <https://w3c.github.io/ServiceWorker/#extendableevent-add-lifetime-promise>
+    #[allow(unrooted_must_root)]
+    pub fn add_lifetime_promise(&self, promise: &Promise) {
+        self.pending_promises.set(self.pending_promises.get() + 1);
+        react_to_promise(
+            promise,
+            Dom::from_ref(self),
+            ExtendableEvent::lifetime_promise_fulfilled,
+            ExtendableEvent::lifetime_promise_rejected,
+        );
+    }
+
+    fn lifetime_promise_fulfilled(event: &Dom<Self>, _cx: JSContext, _v: HandleValue) {
+        event.lifetime_promise_settled();
+    }
+
+    fn lifetime_promise_rejected(event: &Dom<Self>, _cx: JSContext, _v: HandleValue) {
+        event.rejected.set(true);
+        event.lifetime_promise_settled();
+    }
+
+    fn lifetime_promise_settled(&self) {
+        self.pending_promises.set(self.pending_promises.get() - 1);
+        if !self.is_active() {
+            self.extensions_allowed.set(false);
+        }
+        self.notify_if_settled();
+    }
+
+    /// Once the event was dispatched, tell the service worker when all the promises
+    /// passed to `waitUntil` have settled.
+    pub fn finish_dispatch(&self) {
+        self.notify_when_settled.set(true);
+        if self.pending_promises.get() == 0 {
+            self.extensions_allowed.set(false);
+        }
+        self.notify_if_settled();
+    }
+
+    fn notify_if_settled(&self) {
+        if !self.notify_when_settled.get() || self.is_active() {
+            return;
+        }
+        self.notify_when_settled.set(false);
+        if let Some(global) = self.global().downcast::<ServiceWorkerGlobalScope>() {
+            global.lifecycle_event_finished(self.event.type_(), !self.rejected.get());
+        }
+    }
+
     // https://w3c.github.io/ServiceWorker/#wait-until-method
-    pub fn WaitUntil(&self, _cx: JSContext, _val: HandleValue) -> ErrorResult {
+    pub fn WaitUntil(&self, cx: JSContext, val: HandleValue) -> ErrorResult {
         // Step 1
-        if !self.extensions_allowed {
+        if !self.IsTrusted() {
             return Err(Error::InvalidState);
         }
         // Step 2
-        // TODO add a extended_promises array to enqueue the `val`
+        if !self.extensions_allowed.get() || !self.is_active() {
+            return Err(Error::InvalidState);
+        }
+        // Step 3
+        let promise = Promise::new_resolved(&self.global(), cx, val)?;
+        self.add_lifetime_promise(&promise);
         Ok(())
     }
 
@@ -0,0 +1,253 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::body::{consume_body, BodyOperations, BodyType};
+use crate::dom::bindings::cell::DomRefCell;
+use crate::dom::bindings::codegen::Bindings::EventBinding::EventMethods;
+use crate::dom::bindings::codegen::Bindings::FetchEventBinding;
+use crate::dom::bindings::codegen::Bindings::FetchEventBinding::FetchEventMethods;
+use crate::dom::bindings::codegen::Bindings::ResponseBinding::ResponseMethods;
+use crate::dom::bindings::codegen::Bindings::ResponseBinding::ResponseType as DOMResponseType;
+use crate::dom::bindings::conversions::root_from_handlevalue;
+use crate::dom::bindings::error::{Error, ErrorResult, Fallible};
+use crate::dom::bindings::inheritance::Castable;
+use crate::dom::bindings::reflector::reflect_dom_object;
+use crate::dom::bindings::root::{Dom, DomRoot};
+use crate::dom::bindings::str::DOMString;
+use crate::dom::event::Event;
+use crate::dom::extendableevent::ExtendableEvent;
+use crate::dom::globalscope::GlobalScope;
+use crate::dom::promise::Promise;
+use crate::dom::request::Request;
+use crate::dom::response::Response;
+use crate::dom::serviceworkerglobalscope::ServiceWorkerGlobalScope;
+use crate::script_runtime::JSContext;
+use crate::streams::react_to_promise;
+use dom_struct::dom_struct;
+use hyper::StatusCode;
+use ipc_channel::ipc::IpcSender;
+use js::rust::HandleValue;
+use js::typedarray::ArrayBuffer;
+use net_traits::{CustomResponse, NetworkError};
+use servo_atoms::Atom;
+use std::cell::Cell;
+
+/// Where the response of a service worker to a fetch goes.
+type ResponseSender = IpcSender<Result<Option<CustomResponse>, NetworkError>>;
+
+// https://w3c.github.io/ServiceWorker/#fetchevent-interface
+#[dom_struct]
+pub struct FetchEvent {
+    event: ExtendableEvent,
+    request: Dom<Request>,
+    client_id: DOMString,
+    resulting_client_id: DOMString,
+    respond_with_entered: Cell<bool>,
+    /// The fetch waiting for the response, `None` for events that script creates
+    /// or once the fetch got its response.
+    #[ignore_malloc_size_of = "Defined in ipc-channel"]
+    response_chan: DomRefCell<Option<ResponseSender>>,
+    /// The response passed to `respondWith`, while its body is being read.
+    pending_response: DomRefCell<Option<CustomResponse>>,
+}
+
+impl FetchEvent {
+    fn new_inherited(
+        request: &Request,
+        client_id: DOMString,
+        resulting_client_id: DOMString,
+    ) -> FetchEvent {
+        FetchEvent {
+            event: ExtendableEvent::new_inherited(),
+            request: Dom::from_ref(request),
+            client_id,
+            resulting_client_id,
+            respond_with_entered: Cell::new(false),
+            response_chan: DomRefCell::new(None),
+            pending_response: DomRefCell::new(None),
+        }
+    }
+
+    pub fn new(
+        global: &GlobalScope,
+        type_: Atom,
+        bubbles: bool,
+        cancelable: bool,
+        request: &Request,
+        client_id: DOMString,
+        resulting_client_id: DOMString,
+    ) -> DomRoot<FetchEvent> {
+        let ev = reflect_dom_object(
+            Box::new(FetchEvent::new_inherited(
+                request,
+                client_id,
+                resulting_client_id,
+            )),
+            global,
+            FetchEventBinding::Wrap,
+        );
+        {
+            let event = ev.upcast::<Event>();
+            event.init_event(type_, bubbles, cancelable);
+        }
+        ev
+    }
+
+    #[allow(non_snake_case)]
+    pub fn Constructor(
+        worker: &ServiceWorkerGlobalScope,
+        type_: DOMString,
+        init: &FetchEventBinding::FetchEventInit,
+    ) -> Fallible<DomRoot<FetchEvent>> {
+        Ok(FetchEvent::new(
+            worker.upcast(),
+            Atom::from(type_),
+            init.parent.parent.bubbles,
+            init.parent.parent.cancelable,
+            &init.request,
+            init.clientId.clone(),
+            init.resultingClientId.clone(),
+        ))
+    }
+
+    /// Send the response to `respondWith`, or lack thereof, to the fetch that
+    /// fired this event.
+    pub fn set_response_chan(&self, response_chan: ResponseSender) {
+        *self.response_chan.borrow_mut() = Some(response_chan);
+    }
+
+    /// Let the fetch go to the network if script didn't call `respondWith`
+    /// while the event was dispatched.
+    /// <https://w3c.github.io/ServiceWorker/#handle-fetch>
+    pub fn finish_dispatch(&self) {
+        if !self.respond_with_entered.get() {
+            self.respond(Ok(None));
+        }
+    }
+
+    fn respond(&self, response: Result<Option<CustomResponse>, NetworkError>) {
+        if let Some(response_chan) = self.response_chan.borrow_mut().take() {
+            let _ = response_chan.send(response);
+        }
+    }
+
+    fn respond_with_error(&self, message: &str) {
+        self.respond(Err(NetworkError::Internal(message.to_owned())));
+    }
+
+    #[allow(unrooted_must_root)]
+    fn response_fulfilled(event: &Dom<Self>, cx: JSContext, value: HandleValue) {
+        let response = match root_from_handlevalue::<Response>(value, *cx) {
+            Ok(response) => response,
+            Err(()) => return event.respond_with_error("respondWith got a non-Response"),
+        };
+        if response.Type() == DOMResponseType::Error {
+            return event.respond_with_error("respondWith got a network error");
+        }
+        if response.get_body_used() || response.is_locked() {
+            return event.respond_with_error("respondWith got a response with a used body");
+        }
+        let cached = response.cached_response(None);
+        let (code, reason) = cached.raw_status.unwrap_or((200, b"OK".to_vec()));
+        let status = match StatusCode::from_u16(code) {
+            Ok(status) => status,
+            Err(_) => return event.respond_with_error("respondWith got an invalid status"),
+        };
+        let reason = String::from_utf8_lossy(&reason).into_owned();
+        *event.pending_response.borrow_mut() = Some(CustomResponse::new(
+            cached.headers,
+            (status, reason),
+            vec![],
+        ));
+        if response.get_body().is_none() {
+            return event.body_read(vec![]);
+        }
+        let body_promise = consume_body(&*response, BodyType::ArrayBuffer);
+        react_to_promise(
+            &body_promise,
+            Dom::from_ref(&**event),
+            FetchEvent::body_fulfilled,
+            FetchEvent::body_rejected,
+        );
+    }
+
+    fn response_rejected(event: &Dom<Self>, _cx: JSContext, _error: HandleValue) {
+        event.respond_with_error("The promise passed to respondWith was rejected");
+    }
+
+    #[allow(unsafe_code)]
+    fn body_fulfilled(event: &Dom<Self>, cx: JSContext, value: HandleValue) {
+        let bytes = if value.is_object() {
+            typedarray!(in(*cx) let buffer: ArrayBuffer = value.to_object());
+            buffer
+                .ok()
+                .map(|buffer| unsafe { buffer.as_slice().to_vec() })
+        } else {
+            None
+        };
+        event.body_read(bytes.unwrap_or_default());
+    }
+
+    fn body_rejected(event: &Dom<Self>, _cx: JSContext, _error: HandleValue) {
+        event.respond_with_error("Reading the body of the response failed");
+    }
+
+    fn body_read(&self, body: Vec<u8>) {
+        let response = self.pending_response.borrow_mut().take();
+        if let Some(mut response) = response {
+            response.body = body;
+            self.respond(Ok(Some(response)));
+        }
+    }
+}
+
+impl FetchEventMethods for FetchEvent {
+    // https://w3c.github.io/ServiceWorker/#fetch-event-request
+    fn Request(&self) -> DomRoot<Request> {
+        DomRoot::from_ref(&*self.request)
+    }
+
+    // https://w3c.github.io/ServiceWorker/#fetch-event-clientid
+    fn ClientId(&self) -> DOMString {
+        self.client_id.clone()
+    }
+
+    // https://w3c.github.io/ServiceWorker/#fetch-event-resultingclientid
+    fn ResultingClientId(&self) -> DOMString {
+        self.resulting_client_id.clone()
+    }
+
+    #[allow(unrooted_must_root)]
+    // https://w3c.github.io/ServiceWorker/#fetch-event-respondwith
+    fn RespondWith(&self, response: &Promise) -> ErrorResult {
+        let event = self.upcast::<Event>();
+        // Step 1
+        if !event.dispatching() {
+            return Err(Error::InvalidState);
+        }
+        // Step 2
+        if self.respond_with_entered.get() {
+            return Err(Error::InvalidState);
+        }
+        // Step 3
+        self.event.add_lifetime_promise(response);
+        // Steps 4-5
+        event.StopImmediatePropagation();
+        // Step 6
+        self.respond_with_entered.set(true);
+        // Steps 8-11
+        react_to_promise(
+            response,
+            Dom::from_ref(self),
+            FetchEvent::response_fulfilled,
+            FetchEvent::response_rejected,
+        );
+        Ok(())
+    }
+
+    // https://dom.spec.whatwg.org/#dom-event-istrusted
+    fn IsTrusted(&self) -> bool {
+        self.event.IsTrusted()
+    }
+}
@@ -3,6 +3,7 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 
 use crate::dom::bindings::cell::DomRefCell;
+use crate::dom::bindings::codegen::Bindings::BroadcastChannelBinding::BroadcastChannelMethods;
 use crate::dom::bindings::codegen::Bindings::EventSourceBinding::EventSourceBinding::EventSourceMethods;
 use crate::dom::bindings::codegen::Bindings::VoidFunctionBinding::VoidFunction;
 use crate::dom::bindings::codegen::Bindings::WindowBinding::WindowMethods;
@@ -12,26 +13,33 @@ use crate::dom::bindings::error::{report_pending_exception, Error, ErrorInfo};
 use crate::dom::bindings::inheritance::Castable;
 use crate::dom::bindings::refcounted::{Trusted, TrustedPromise};
 use crate::dom::bindings::reflector::DomObject;
-use crate::dom::bindings::root::{DomRoot, MutNullableDom};
+use crate::dom::bindings::root::{Dom, DomRoot, MutNullableDom};
 use crate::dom::bindings::settings_stack::{entry_global, incumbent_global, AutoEntryScript};
 use crate::dom::bindings::str::DOMString;
 use crate::dom::bindings::structuredclone;
 use crate::dom::bindings::weakref::{DOMTracker, WeakRef};
 use crate::dom::blob::Blob;
+use crate::dom::broadcastchannel::BroadcastChannel;
+use crate::dom::cachestorage::CacheStorage;
 use crate::dom::crypto::Crypto;
 use crate::dom::dedicatedworkerglobalscope::DedicatedWorkerGlobalScope;
+use crate::dom::element::Element;
 use crate::dom::errorevent::ErrorEvent;
 use crate::dom::event::{Event, EventBubbles, EventCancelable, EventStatus};
 use crate::dom::eventsource::EventSource;
 use crate::dom::eventtarget::EventTarget;
 use crate::dom::file::File;
 use crate::dom::htmlscriptelement::ScriptId;
+use crate::dom::idbdatabase::IDBDatabase;
+use crate::dom::idbfactory::IDBFactory;
+use crate::dom::idbtransaction::IDBTransaction;
 use crate::dom::identityhub::Identities;
 use crate::dom::messageevent::MessageEvent;
 use crate::dom::messageport::MessagePort;
 use crate::dom::paintworkletglobalscope::PaintWorkletGlobalScope;
 use crate::dom::performance::Performance;
 use crate::dom::promise::Promise;
+use crate::dom::sharedworkerglobalscope::SharedWorkerGlobalScope;
 use crate::dom::window::Window;
 use crate::dom::workerglobalscope::WorkerGlobalScope;
 use crate::dom::workletglobalscope::WorkletGlobalScope;
@@ -40,7 +48,9 @@ use crate::realms::enter_realm;
 use crate::script_module::ModuleTree;
 use crate::script_runtime::{CommonScriptMsg, JSContext as SafeJSContext, ScriptChan, ScriptPort};
 use crate::script_thread::{MainThreadScriptChan, ScriptThread};
+use crate::security_manager;
 use crate::task::TaskCanceller;
+use crate::task_source::database_access::DatabaseAccessTaskSource;
 use crate::task_source::dom_manipulation::DOMManipulationTaskSource;
 use crate::task_source::file_reading::FileReadingTaskSource;
 use crate::task_source::networking::NetworkingTaskSource;
@@ -53,7 +63,7 @@ use crate::task_source::TaskSource;
 use crate::task_source::TaskSourceName;
 use crate::timers::{IsInterval, OneshotTimerCallback, OneshotTimerHandle};
 use crate::timers::{OneshotTimers, TimerCallback};
-use content_security_policy::CspList;
+use content_security_policy::{self as csp, CspList};
 use devtools_traits::{PageError, ScriptToDevtoolsControlMsg};
 use dom_struct::dom_struct;
 use ipc_channel::ipc::{self, IpcSender};
@@ -69,18 +79,22 @@ use js::rust::wrappers::EvaluateUtf8;
 use js::rust::{get_object_class, CompileOptionsWrapper, ParentRuntime, Runtime};
 use js::rust::{HandleValue, MutableHandleValue};
 use js::{JSCLASS_IS_DOMJSCLASS, JSCLASS_IS_GLOBAL};
-use msg::constellation_msg::{BlobId, MessagePortId, MessagePortRouterId, PipelineId};
+use msg::constellation_msg::{
+    BlobId, BroadcastChannelRouterId, MessagePortId, MessagePortRouterId, PipelineId,
+};
 use net_traits::blob_url_store::{get_blob_origin, BlobBuf};
 use net_traits::filemanager_thread::{
     FileManagerResult, FileManagerThreadMsg, ReadFileProgress, RelativePos,
 };
 use net_traits::image_cache::ImageCache;
-use net_traits::{CoreResourceMsg, CoreResourceThread, IpcSend, ResourceThreads};
+use net_traits::{CoreResourceMsg, CoreResourceThread, CrossOriginEmbedderPolicy};
+use net_traits::{IpcSend, ResourceThreads};
 use profile_traits::{ipc as profile_ipc, mem as profile_mem, time as profile_time};
 use script_traits::serializable::{BlobData, BlobImpl, FileBlob};
 use script_traits::transferable::MessagePortImpl;
 use script_traits::{
-    MessagePortMsg, MsDuration, PortMessageTask, ScriptMsg, ScriptToConstellationChan, TimerEvent,
+    BroadcastMsg, MessagePortMsg, MsDuration, PortMessageTask, ScriptMsg,
+    ScriptToConstellationChan, TimerEvent,
 };
 use script_traits::{TimerEventId, TimerSchedulerMsg, TimerSource};
 use servo_url::{MutableOrigin, ServoUrl};
@@ -115,10 +129,21 @@ impl Drop for AutoCloseWorker {
 pub struct GlobalScope {
     eventtarget: EventTarget,
     crypto: MutNullableDom<Crypto>,
+    indexed_db: MutNullableDom<IDBFactory>,
+    caches: MutNullableDom<CacheStorage>,
+
+    /// The IndexedDB transactions created since the last microtask checkpoint.
+    indexeddb_transactions: DomRefCell<Vec<Dom<IDBTransaction>>>,
+
+    /// The open IndexedDB connections, which are closed when the global goes away.
+    indexeddb_connections: DomRefCell<Vec<Dom<IDBDatabase>>>,
 
     /// The message-port router id for this global, if it is managing ports.
     message_port_state: DomRefCell<MessagePortState>,
 
+    /// The broadcast channels managed by this global, if any.
+    broadcast_channel_state: DomRefCell<BroadcastChannelState>,
+
     /// The blobs managed by this global, if any.
     blob_state: DomRefCell<BlobState>,
 
@@ -228,6 +253,13 @@ struct MessageListener {
     context: Trusted<GlobalScope>,
 }
 
+/// A wrapper for broadcasts coming in over IPC, and the event-loop.
+struct BroadcastListener {
+    canceller: TaskCanceller,
+    task_source: DOMManipulationTaskSource,
+    context: Trusted<GlobalScope>,
+}
+
 /// A wrapper between timer events coming in over IPC, and the event-loop.
 struct TimerListener {
     canceller: TaskCanceller,
@@ -282,6 +314,20 @@ pub enum BlobState {
     UnManaged,
 }
 
+/// State representing whether this global is currently managing broadcast channels.
+#[derive(JSTraceable, MallocSizeOf)]
+pub enum BroadcastChannelState {
+    /// The broadcast-channel router id for this global, and the channels it manages
+    /// by name, in creation order, as needed to "sort destinations" in
+    /// <https://html.spec.whatwg.org/multipage/#dom-broadcastchannel-postmessage>.
+    Managed(
+        BroadcastChannelRouterId,
+        HashMap<DOMString, VecDeque<Dom<BroadcastChannel>>>,
+    ),
+    /// This global is not managing any broadcast channels at this time.
+    UnManaged,
+}
+
 /// Data representing a message-port managed by this global.
 #[derive(JSTraceable, MallocSizeOf)]
 pub enum ManagedMessagePort {
@@ -334,6 +380,29 @@ impl TimerListener {
     }
 }
 
+impl BroadcastListener {
+    /// Handle a broadcast coming in over IPC,
+    /// by queueing the appropriate task on the relevant event-loop.
+    fn handle(&self, event: BroadcastMsg) {
+        let context = self.context.clone();
+
+        // Note: strictly speaking we should just queue the message event tasks,
+        // not queue a task that then queues more tasks.
+        // This however seems to be hard to avoid in the light of the IPC.
+        // One can imagine queueing tasks directly,
+        // for channels that would be in the same script-thread.
+        let _ = self.task_source.queue_with_canceller(
+            task!(broadcast_message_event: move || {
+                let global = context.root();
+                // Step 10 of https://html.spec.whatwg.org/multipage/#dom-broadcastchannel-postmessage,
+                // For each BroadcastChannel object destination in destinations, queue a task.
+                global.broadcast_message_event(event);
+            }),
+            &self.canceller,
+        );
+    }
+}
+
 impl MessageListener {
     /// A new message came in, handle it via a task enqueued on the event-loop.
     /// A task is required, since we are using a trusted globalscope,
@@ -484,9 +553,14 @@ impl GlobalScope {
     ) -> Self {
         Self {
             message_port_state: DomRefCell::new(MessagePortState::UnManaged),
+            broadcast_channel_state: DomRefCell::new(BroadcastChannelState::UnManaged),
             blob_state: DomRefCell::new(BlobState::UnManaged),
             eventtarget: EventTarget::new_inherited(),
             crypto: Default::default(),
+            indexed_db: Default::default(),
+            caches: Default::default(),
+            indexeddb_transactions: Default::default(),
+            indexeddb_connections: Default::default(),
             pipeline_id,
             devtools_wants_updates: Default::default(),
             console_timers: DomRefCell::new(Default::default()),
@@ -591,6 +665,29 @@ impl GlobalScope {
     pub fn perform_a_dom_garbage_collection_checkpoint(&self) {
         self.perform_a_message_port_garbage_collection_checkpoint();
         self.perform_a_blob_garbage_collection_checkpoint();
+        self.perform_a_broadcast_channel_garbage_collection_checkpoint();
+    }
+
+    /// Remove the routers for ports and broadcast-channels.
+    pub fn remove_web_messaging_infra(&self) {
+        self.remove_message_ports_router();
+        self.remove_broadcast_channel_router();
+    }
+
+    /// Update our state to un-managed,
+    /// and tell the constellation to drop the sender to our broadcast router.
+    fn remove_broadcast_channel_router(&self) {
+        if let BroadcastChannelState::Managed(router_id, _channels) =
+            &*self.broadcast_channel_state.borrow()
+        {
+            let _ =
+                self.script_to_constellation_chan()
+                    .send(ScriptMsg::RemoveBroadcastChannelRouter(
+                        router_id.clone(),
+                        self.origin().immutable().clone(),
+                    ));
+        }
+        *self.broadcast_channel_state.borrow_mut() = BroadcastChannelState::UnManaged;
     }
 
     /// Update our state to un-managed,
@@ -606,6 +703,196 @@ impl GlobalScope {
         *self.message_port_state.borrow_mut() = MessagePortState::UnManaged;
     }
 
+    /// <https://html.spec.whatwg.org/multipage/#dom-broadcastchannel-postmessage>
+    /// Step 7 and following steps.
+    pub fn schedule_broadcast(&self, msg: BroadcastMsg, channel_id: &Uuid) {
+        // First, broadcast locally, to the other channels of this global.
+        // The destinations are decided now, so that closing one of them
+        // doesn't cancel the delivery of already posted messages.
+        let destinations: Vec<Trusted<BroadcastChannel>> = self
+            .broadcast_destinations(&msg.channel_name, Some(channel_id))
+            .iter()
+            .map(|channel| Trusted::new(&**channel))
+            .collect();
+        if !destinations.is_empty() {
+            let this = Trusted::new(self);
+            let local_msg = msg.clone();
+            let _ = self.dom_manipulation_task_source().queue(
+                task!(broadcast_message_event: move || {
+                    let global = this.root();
+                    let destinations = destinations.iter().map(|channel| channel.root()).collect();
+                    global.dispatch_broadcast(destinations, local_msg);
+                }),
+                self,
+            );
+        }
+
+        if let BroadcastChannelState::Managed(router_id, _) =
+            &*self.broadcast_channel_state.borrow()
+        {
+            // Second, broadcast to other globals via the constellation.
+            //
+            // Note: for globals in the same script-thread,
+            // we could skip the hop to the constellation.
+            let _ = self
+                .script_to_constellation_chan()
+                .send(ScriptMsg::ScheduleBroadcast(router_id.clone(), msg));
+        } else {
+            panic!("Attempt to broadcast a message via global not managing any channels.");
+        }
+    }
+
+    /// Deliver a message broadcast by another global.
+    pub fn broadcast_message_event(&self, event: BroadcastMsg) {
+        let destinations = self.broadcast_destinations(&event.channel_name, None);
+        self.dispatch_broadcast(destinations, event);
+    }
+
+    /// <https://html.spec.whatwg.org/multipage/#dom-broadcastchannel-postmessage>
+    /// Steps 7 to 9, the channels of this global that should receive a message.
+    fn broadcast_destinations(
+        &self,
+        channel_name: &str,
+        channel_id: Option<&Uuid>,
+    ) -> Vec<DomRoot<BroadcastChannel>> {
+        // Step 7, if this is a window, its document must be fully active.
+        if let Some(window) = self.downcast::<Window>() {
+            if !window.Document().is_fully_active() {
+                return vec![];
+            }
+        }
+
+        // Step 8 and 9, the channels that aren't closed and have the same name,
+        // excluding the one the message was sent on.
+        match &*self.broadcast_channel_state.borrow() {
+            BroadcastChannelState::Managed(_, channels) => {
+                match channels.get(&DOMString::from(channel_name)) {
+                    // Step 10, the channels are kept in creation order.
+                    Some(channels) => channels
+                        .iter()
+                        .filter(|channel| !channel.closed())
+                        .filter(|channel| Some(channel.id()) != channel_id)
+                        .map(|channel| DomRoot::from_ref(&**channel))
+                        .collect(),
+                    None => vec![],
+                }
+            },
+            BroadcastChannelState::UnManaged => vec![],
+        }
+    }
+
+    /// <https://html.spec.whatwg.org/multipage/#dom-broadcastchannel-postmessage>
+    /// Step 10, fire a message event at each of the destinations.
+    fn dispatch_broadcast(&self, destinations: Vec<DomRoot<BroadcastChannel>>, msg: BroadcastMsg) {
+        let BroadcastMsg { data, origin, .. } = msg;
+        for destination in destinations {
+            // Step 10.3, deserialize a fresh copy of the message for each destination.
+            rooted!(in(*self.get_cx()) let mut message = UndefinedValue());
+            if let Ok(ports) =
+                structuredclone::read(self, data.clone_for_broadcast(), message.handle_mut())
+            {
+                // Step 10.4, fire a message event.
+                MessageEvent::dispatch_jsval(
+                    destination.upcast(),
+                    self,
+                    message.handle(),
+                    Some(&origin.ascii_serialization()),
+                    None,
+                    ports,
+                );
+            } else {
+                // Step 10.3, fire a messageerror event if deserialization fails.
+                MessageEvent::dispatch_error(destination.upcast(), self);
+            }
+        }
+    }
+
+    /// Start tracking a broadcast-channel.
+    pub fn track_broadcast_channel(&self, dom_channel: &BroadcastChannel) {
+        let mut current_state = self.broadcast_channel_state.borrow_mut();
+
+        if let BroadcastChannelState::UnManaged = &*current_state {
+            // Setup a route for IPC, for broadcasts from the constellation to our channels.
+            let (broadcast_control_sender, broadcast_control_receiver) =
+                ipc::channel().expect("ipc channel failure");
+            let context = Trusted::new(self);
+            let (task_source, canceller) = (
+                self.dom_manipulation_task_source(),
+                self.task_canceller(TaskSourceName::DOMManipulation),
+            );
+            let listener = BroadcastListener {
+                canceller,
+                task_source,
+                context,
+            };
+            ROUTER.add_route(
+                broadcast_control_receiver.to_opaque(),
+                Box::new(move |message| {
+                    let msg = message.to();
+                    match msg {
+                        Ok(msg) => listener.handle(msg),
+                        Err(err) => warn!("Error receiving a BroadcastMsg: {:?}", err),
+                    }
+                }),
+            );
+            let router_id = BroadcastChannelRouterId::new();
+            *current_state = BroadcastChannelState::Managed(router_id.clone(), HashMap::new());
+            let _ = self
+                .script_to_constellation_chan()
+                .send(ScriptMsg::NewBroadcastChannelRouter(
+                    router_id,
+                    broadcast_control_sender,
+                    self.origin().immutable().clone(),
+                ));
+        }
+
+        if let BroadcastChannelState::Managed(router_id, channels) = &mut *current_state {
+            let entry = channels.entry(dom_channel.Name()).or_insert_with(|| {
+                let _ = self.script_to_constellation_chan().send(
+                    ScriptMsg::NewBroadcastChannelNameInRouter(
+                        router_id.clone(),
+                        dom_channel.Name().to_string(),
+                        self.origin().immutable().clone(),
+                    ),
+                );
+                VecDeque::new()
+            });
+            entry.push_back(Dom::from_ref(dom_channel));
+        } else {
+            panic!("track_broadcast_channel should have first switched the state to managed.");
+        }
+    }
+
+    /// Stop tracking the broadcast-channels that were closed,
+    /// they can't receive messages anymore.
+    pub fn perform_a_broadcast_channel_garbage_collection_checkpoint(&self) {
+        let is_empty = if let BroadcastChannelState::Managed(router_id, channels) =
+            &mut *self.broadcast_channel_state.borrow_mut()
+        {
+            channels.retain(|name, channels| {
+                channels.retain(|chan| !chan.closed());
+                if channels.is_empty() {
+                    let _ = self.script_to_constellation_chan().send(
+                        ScriptMsg::RemoveBroadcastChannelNameInRouter(
+                            router_id.clone(),
+                            name.to_string(),
+                            self.origin().immutable().clone(),
+                        ),
+                    );
+                    false
+                } else {
+                    true
+                }
+            });
+            channels.is_empty()
+        } else {
+            false
+        };
+        if is_empty {
+            self.remove_broadcast_channel_router();
+        }
+    }
+
     /// <https://html.spec.whatwg.org/multipage/#entangle>
     pub fn entangle_ports(&self, port1: MessagePortId, port2: MessagePortId) {
         if let MessagePortState::Managed(_id, message_ports) =
@@ -1422,6 +1709,53 @@ impl GlobalScope {
         canceled_any_fetch
     }
 
+    /// Remember a transaction created in the current task, to deactivate it
+    /// at the next microtask checkpoint.
+    pub fn register_indexeddb_transaction(&self, transaction: &IDBTransaction) {
+        self.indexeddb_transactions
+            .borrow_mut()
+            .push(Dom::from_ref(transaction));
+    }
+
+    /// <https://w3c.github.io/IndexedDB/#cleanup-indexed-database-transactions>
+    pub fn cleanup_indexeddb_transactions(&self) {
+        let transactions: Vec<DomRoot<IDBTransaction>> = self
+            .indexeddb_transactions
+            .borrow_mut()
+            .drain(..)
+            .map(|transaction| DomRoot::from_ref(&*transaction))
+            .collect();
+        for transaction in transactions {
+            transaction.deactivate();
+        }
+    }
+
+    pub fn track_indexeddb_connection(&self, connection: &IDBDatabase) {
+        self.indexeddb_connections
+            .borrow_mut()
+            .push(Dom::from_ref(connection));
+    }
+
+    pub fn untrack_indexeddb_connection(&self, connection: &IDBDatabase) {
+        self.indexeddb_connections
+            .borrow_mut()
+            .retain(|tracked| &**tracked as *const _ != connection as *const _);
+    }
+
+    /// Close the IndexedDB connections of a global that goes away, so that they
+    /// don't block other connections to their databases.
+    pub fn close_indexeddb_connections(&self) {
+        let connections: Vec<DomRoot<IDBDatabase>> = self
+            .indexeddb_connections
+            .borrow()
+            .iter()
+            .map(|connection| DomRoot::from_ref(&**connection))
+            .collect();
+        for connection in connections {
+            connection.close();
+        }
+    }
+
     /// Returns the global scope of the realm that the given DOM object's reflector
     /// was created in.
     #[allow(unsafe_code)]
@@ -1527,6 +1861,14 @@ impl GlobalScope {
         self.crypto.or_init(|| Crypto::new(self))
     }
 
+    pub fn indexed_db(&self) -> DomRoot<IDBFactory> {
+        self.indexed_db.or_init(|| IDBFactory::new(self))
+    }
+
+    pub fn caches(&self) -> DomRoot<CacheStorage> {
+        self.caches.or_init(|| CacheStorage::new(self))
+    }
+
     pub fn live_devtools_updates(&self) -> bool {
         self.devtools_wants_updates.get()
     }
@@ -1622,6 +1964,9 @@ impl GlobalScope {
         if let Some(worker) = self.downcast::<DedicatedWorkerGlobalScope>() {
             return worker.image_cache();
         }
+        if let Some(worker) = self.downcast::<SharedWorkerGlobalScope>() {
+            return worker.image_cache();
+        }
         if let Some(worker) = self.downcast::<PaintWorkletGlobalScope>() {
             return worker.image_cache();
         }
@@ -1797,6 +2142,16 @@ impl GlobalScope {
 
     /// `TaskSource` to send messages to the websocket task source of
     /// this global scope.
+    pub fn database_access_task_source(&self) -> DatabaseAccessTaskSource {
+        if let Some(window) = self.downcast::<Window>() {
+            return window.task_manager().database_access_task_source();
+        }
+        if let Some(worker) = self.downcast::<WorkerGlobalScope>() {
+            return worker.database_access_task_source();
+        }
+        unreachable!();
+    }
+
     pub fn websocket_task_source(&self) -> WebsocketTaskSource {
         if let Some(window) = self.downcast::<Window>() {
             return window.task_manager().websocket_task_source();
@@ -2101,6 +2456,59 @@ impl GlobalScope {
         None
     }
 
+    /// <https://html.spec.whatwg.org/multipage/#concept-settings-object-policy-container>
+    pub fn embedder_policy(&self) -> CrossOriginEmbedderPolicy {
+        if let Some(window) = self.downcast::<Window>() {
+            return window.Document().embedder_policy();
+        }
+        // TODO: Worker and Worklet global scopes.
+        CrossOriginEmbedderPolicy::UnsafeNone
+    }
+
+    /// <https://w3c.github.io/webappsec-csp/#report-violation>
+    pub fn report_csp_violations(
+        &self,
+        violations: Vec<csp::Violation>,
+        element: Option<&Element>,
+    ) {
+        security_manager::report_violations(self, violations, element);
+    }
+
+    /// A channel through which other threads report the violations of the content security
+    /// policies of this global, such as those of the fetches of web fonts.
+    pub fn csp_violation_sender(&self) -> IpcSender<Vec<csp::Violation>> {
+        let (sender, receiver) = ipc::channel().unwrap();
+        let trusted_global = Trusted::new(self);
+        let task_source = self.dom_manipulation_task_source();
+        let canceller = self.task_canceller(TaskSourceName::DOMManipulation);
+        ROUTER.add_route(
+            receiver.to_opaque(),
+            Box::new(move |message| {
+                let violations = match message.to() {
+                    Ok(violations) => violations,
+                    Err(e) => return warn!("Error receiving CSP violations: {:?}", e),
+                };
+                let global = trusted_global.clone();
+                let _ = task_source.queue_with_canceller(
+                    task!(report_csp_violations: move || {
+                        global.root().report_csp_violations(violations, None);
+                    }),
+                    &canceller,
+                );
+            }),
+        );
+        sender
+    }
+
+    /// The URL of the endpoint that the `Reporting-Endpoints` header
+    /// of this global's document names `name`.
+    pub fn reporting_endpoint(&self, name: &str) -> Option<ServoUrl> {
+        if let Some(window) = self.downcast::<Window>() {
+            return window.Document().reporting_endpoint(name);
+        }
+        None
+    }
+
     pub fn wgpu_create_adapter_ids(&self) -> SmallVec<[AdapterId; 4]> {
         self.gpu_id_hub.borrow_mut().create_adapter_ids()
     }
@@ -28,7 +28,8 @@ use crate::dom::virtualmethods::VirtualMethods;
 use crate::task_source::TaskSource;
 use dom_struct::dom_struct;
 use html5ever::{LocalName, Prefix};
-use net_traits::request::Referrer;
+use net_traits::request::{CredentialsMode, Destination, Referrer, RequestBuilder, RequestMode};
+use net_traits::CoreResourceMsg;
 use num_traits::ToPrimitive;
 use script_traits::{HistoryEntryReplacement, LoadData, LoadOrigin};
 use servo_url::ServoUrl;
@@ -172,6 +173,12 @@ impl HTMLAnchorElementMethods for HTMLAnchorElement {
     // https://html.spec.whatwg.org/multipage/#attr-hyperlink-target
     make_setter!(SetTarget, "target");
 
+    // https://html.spec.whatwg.org/multipage/#attr-hyperlink-download
+    make_getter!(Download, "download");
+
+    // https://html.spec.whatwg.org/multipage/#attr-hyperlink-download
+    make_setter!(SetDownload, "download");
+
     // https://html.spec.whatwg.org/multipage/#dom-hyperlink-hash
     fn Hash(&self) -> USVString {
         // Step 1.
@@ -570,8 +577,66 @@ impl Activatable for HTMLAnchorElement {
         }
 
         // Step 2.
-        //TODO: Download the link is `download` attribute is set.
-        follow_hyperlink(element, ismap_suffix);
+        if element.has_attribute(&local_name!("download")) {
+            download_hyperlink(element, ismap_suffix);
+        } else {
+            follow_hyperlink(element, ismap_suffix);
+        }
+    }
+}
+
+/// <https://html.spec.whatwg.org/multipage/#downloading-hyperlinks>
+fn download_hyperlink(subject: &Element, hyperlink_suffix: Option<String>) {
+    // Step 1.
+    if subject.cannot_navigate() {
+        return;
+    }
+
+    // Step 3.
+    let document = document_from_node(subject);
+    let mut href = match subject.get_attribute(&ns!(), &local_name!("href")) {
+        Some(attribute) => attribute.Value(),
+        None => return,
+    };
+    // Step 4.
+    if let Some(ref suffix) = hyperlink_suffix {
+        href.push_str(suffix);
+    }
+    let url = match document.url().join(&href) {
+        Ok(url) => url,
+        Err(_) => return,
+    };
+
+    // The download attribute is ignored for cross-origin resources, which are
+    // navigated to instead, so that a page can't rename another site's responses.
+    let is_local_scheme = url.scheme() == "data" || url.scheme() == "blob";
+    if !is_local_scheme && &url.origin() != document.origin().immutable() {
+        return follow_hyperlink(subject, hyperlink_suffix);
+    }
+
+    // Steps 5-8.
+    let filename = subject
+        .get_string_attribute(&local_name!("download"))
+        .to_string();
+    let window = document.window();
+    let global = window.upcast::<GlobalScope>();
+    let request = RequestBuilder::new(url)
+        .destination(Destination::Document)
+        .mode(RequestMode::Navigate)
+        .credentials_mode(CredentialsMode::Include)
+        .use_url_credentials(true)
+        .origin(document.origin().immutable().clone())
+        .referrer(Some(Referrer::ReferrerUrl(document.url())))
+        .referrer_policy(referrer_policy_for_element(subject))
+        .pipeline_id(Some(global.pipeline_id()));
+    let browser_id = window.window_proxy().top_level_browsing_context_id();
+    let msg = CoreResourceMsg::Download(
+        request,
+        Some(filename).filter(|filename| !filename.is_empty()),
+        Some(browser_id),
+    );
+    if let Err(e) = global.core_resource_thread().send(msg) {
+        warn!("Resource thread unavailable ({})", e);
     }
 }
 
@@ -123,17 +123,18 @@ impl HTMLElement {
                     }
                 },
                 _ => {
-                    if let Some(attr) = element.get_attribute(&ns!(), &local_name!("draggable")) {
-                        let value = attr.value();
-                        let is_true = match *value {
-                            AttrValue::String(ref string) => string == "true",
-                            _ => false,
+                    let is_draggable =
+                        match element.get_attribute(&ns!(), &local_name!("draggable")) {
+                            Some(attr) => match *attr.value() {
+                                AttrValue::String(ref string) => string == "true",
+                                _ => false,
+                            },
+                            None => false,
                         };
-                        node.set_flag(NodeFlags::SEQUENTIALLY_FOCUSABLE, is_true);
-                    } else {
-                        node.set_flag(NodeFlags::SEQUENTIALLY_FOCUSABLE, false);
-                    }
-                    //TODO set SEQUENTIALLY_FOCUSABLE flag if editing host
+                    node.set_flag(
+                        NodeFlags::SEQUENTIALLY_FOCUSABLE,
+                        is_draggable || node.is_editing_host(),
+                    );
                     //TODO set SEQUENTIALLY_FOCUSABLE flag if "sorting interface th elements"
                 },
             }
@@ -451,6 +452,37 @@ impl HTMLElementMethods for HTMLElement {
         rect.size.height.to_nearest_px()
     }
 
+    // https://html.spec.whatwg.org/multipage/#dom-contenteditable
+    fn ContentEditable(&self) -> DOMString {
+        DOMString::from(match self.content_editable_state() {
+            ContentEditableState::True => "true",
+            ContentEditableState::False => "false",
+            ContentEditableState::PlaintextOnly => "plaintext-only",
+            ContentEditableState::Inherit => "inherit",
+        })
+    }
+
+    // https://html.spec.whatwg.org/multipage/#dom-contenteditable
+    fn SetContentEditable(&self, value: DOMString) -> ErrorResult {
+        let element = self.upcast::<Element>();
+        let value = value.to_ascii_lowercase();
+        match &*value {
+            "inherit" => {
+                element.remove_attribute(&ns!(), &local_name!("contenteditable"));
+            },
+            "true" | "false" | "plaintext-only" => element
+                .set_string_attribute(&local_name!("contenteditable"), DOMString::from(value)),
+            _ => return Err(Error::Syntax),
+        }
+        Ok(())
+    }
+
+    // https://html.spec.whatwg.org/multipage/#dom-iscontenteditable
+    fn IsContentEditable(&self) -> bool {
+        let node = self.upcast::<Node>();
+        node.is_editing_host() || node.is_editable()
+    }
+
     // https://html.spec.whatwg.org/multipage/#the-innertext-idl-attribute
     fn InnerText(&self) -> DOMString {
         let node = self.upcast::<Node>();
@@ -589,7 +621,35 @@ fn to_camel_case(name: &str) -> Option<DOMString> {
     Some(DOMString::from(result))
 }
 
+/// The states of the `contenteditable` attribute.
+/// <https://html.spec.whatwg.org/multipage/#attr-contenteditable>
+#[derive(Clone, Copy, PartialEq)]
+pub enum ContentEditableState {
+    True,
+    False,
+    PlaintextOnly,
+    Inherit,
+}
+
 impl HTMLElement {
+    // https://html.spec.whatwg.org/multipage/#attr-contenteditable
+    pub fn content_editable_state(&self) -> ContentEditableState {
+        let attr = match self
+            .upcast::<Element>()
+            .get_attribute(&ns!(), &local_name!("contenteditable"))
+        {
+            Some(attr) => attr,
+            None => return ContentEditableState::Inherit,
+        };
+        let value = attr.value().to_ascii_lowercase();
+        match &*value {
+            "" | "true" => ContentEditableState::True,
+            "false" => ContentEditableState::False,
+            "plaintext-only" => ContentEditableState::PlaintextOnly,
+            _ => ContentEditableState::Inherit,
+        }
+    }
+
     pub fn set_custom_attr(&self, name: DOMString, value: DOMString) -> ErrorResult {
         if name
             .chars()
@@ -740,6 +800,7 @@ impl VirtualMethods for HTMLElement {
                     DOMString::from(&**attr.value()),
                 );
             },
+            (&local_name!("contenteditable"), _) => self.update_sequentially_focusable_status(),
             _ => {},
         }
     }
@@ -27,6 +27,7 @@ use crate::dom::window::ReflowReason;
 use crate::dom::windowproxy::WindowProxy;
 use crate::script_thread::ScriptThread;
 use crate::task_source::TaskSource;
+use content_security_policy as csp;
 use dom_struct::dom_struct;
 use html5ever::{LocalName, Prefix};
 use ipc_channel::ipc;
@@ -40,7 +41,7 @@ use script_traits::{
     LoadOrigin, UpdatePipelineIdReason, WindowSizeData,
 };
 use script_traits::{NewLayoutInfo, ScriptMsg};
-use servo_url::ServoUrl;
+use servo_url::{ImmutableOrigin, ServoUrl};
 use std::cell::Cell;
 use style::attr::{AttrValue, LengthOrPercentageOrAuto};
 
@@ -106,6 +107,43 @@ impl HTMLIFrameElement {
             .unwrap_or_else(|| ServoUrl::parse("about:blank").unwrap())
     }
 
+    /// The origins that `frame-ancestors` directives are checked against in the nested
+    /// browsing context: this element's document and its own ancestors, nearest first.
+    fn ancestor_origins_of_nested_browsing_context(&self) -> Vec<ImmutableOrigin> {
+        let document = document_from_node(self);
+        let mut ancestor_origins = vec![document.origin().immutable().clone()];
+        ancestor_origins.extend(document.ancestor_origins());
+        ancestor_origins
+    }
+
+    /// <https://w3c.github.io/webappsec-csp/#should-block-navigation-request>,
+    /// checking the navigation of the nested browsing context against `frame-src`.
+    fn should_navigation_be_blocked_by_csp(&self, url: &ServoUrl) -> bool {
+        let document = document_from_node(self);
+        let csp_list = match document.get_csp_list() {
+            Some(csp_list) => csp_list.clone(),
+            None => return false,
+        };
+        let request = csp::Request {
+            url: url.clone().into_url(),
+            origin: document.origin().immutable().clone().into_url_origin(),
+            redirect_count: 0,
+            destination: csp::Destination::IFrame,
+            initiator: csp::Initiator::None,
+            nonce: String::new(),
+            integrity_metadata: String::new(),
+            parser_metadata: csp::ParserMetadata::None,
+        };
+        let (result, violations) = csp_list.should_request_be_blocked(&request);
+        if !violations.is_empty() {
+            document
+                .window()
+                .upcast::<GlobalScope>()
+                .report_csp_violations(violations, Some(self.upcast()));
+        }
+        result == csp::CheckResult::Blocked
+    }
+
     pub fn navigate_or_reload_child_browsing_context(
         &self,
         mut load_data: LoadData,
@@ -249,6 +287,7 @@ impl HTMLIFrameElement {
             );
             let element = self.upcast::<Element>();
             load_data.srcdoc = String::from(element.get_string_attribute(&local_name!("srcdoc")));
+            load_data.ancestor_origins = self.ancestor_origins_of_nested_browsing_context();
             self.navigate_or_reload_child_browsing_context(
                 load_data,
                 NavigationType::InitialAboutBlank,
@@ -273,20 +312,24 @@ impl HTMLIFrameElement {
         if mode == ProcessingMode::FirstTime &&
             !self.upcast::<Element>().has_attribute(&local_name!("src"))
         {
-            let this = Trusted::new(self);
-            let pipeline_id = self.pipeline_id().unwrap();
-            // FIXME(nox): Why are errors silenced here?
-            let _ = window.task_manager().dom_manipulation_task_source().queue(
-                task!(iframe_load_event_steps: move || {
-                    this.root().iframe_load_event_steps(pipeline_id);
-                }),
-                window.upcast(),
-            );
+            self.queue_iframe_load_event_steps();
             return;
         }
 
         let url = self.get_url();
 
+        // A navigation that `frame-src` blocks leaves the nested browsing context
+        // on its current document.
+        if url.scheme() != "about" &&
+            url.scheme() != "javascript" &&
+            self.should_navigation_be_blocked_by_csp(&url)
+        {
+            if mode == ProcessingMode::FirstTime {
+                self.queue_iframe_load_event_steps();
+            }
+            return;
+        }
+
         // TODO: check ancestor browsing contexts for same URL
 
         let creator_pipeline_id = if url.as_str() == "about:blank" {
@@ -296,13 +339,14 @@ impl HTMLIFrameElement {
         };
 
         let document = document_from_node(self);
-        let load_data = LoadData::new(
+        let mut load_data = LoadData::new(
             LoadOrigin::Script(document.origin().immutable().clone()),
             url,
             creator_pipeline_id,
             Some(Referrer::ReferrerUrl(document.url())),
             document.get_referrer_policy(),
         );
+        load_data.ancestor_origins = self.ancestor_origins_of_nested_browsing_context();
 
         let pipeline_id = self.pipeline_id();
         // If the initial `about:blank` page is the current page, load with replacement enabled,
@@ -323,13 +367,14 @@ impl HTMLIFrameElement {
         let document = document_from_node(self);
         let window = window_from_node(self);
         let pipeline_id = Some(window.upcast::<GlobalScope>().pipeline_id());
-        let load_data = LoadData::new(
+        let mut load_data = LoadData::new(
             LoadOrigin::Script(document.origin().immutable().clone()),
             url,
             pipeline_id,
             Some(Referrer::ReferrerUrl(document.url().clone())),
             document.get_referrer_policy(),
         );
+        load_data.ancestor_origins = self.ancestor_origins_of_nested_browsing_context();
         let browsing_context_id = BrowsingContextId::new();
         let top_level_browsing_context_id = window.window_proxy().top_level_browsing_context_id();
         self.pipeline_id.set(None);
@@ -429,6 +474,19 @@ impl HTMLIFrameElement {
         }
     }
 
+    fn queue_iframe_load_event_steps(&self) {
+        let window = window_from_node(self);
+        let this = Trusted::new(self);
+        let pipeline_id = self.pipeline_id().unwrap();
+        // FIXME(nox): Why are errors silenced here?
+        let _ = window.task_manager().dom_manipulation_task_source().queue(
+            task!(iframe_load_event_steps: move || {
+                this.root().iframe_load_event_steps(pipeline_id);
+            }),
+            window.upcast(),
+        );
+    }
+
     /// https://html.spec.whatwg.org/multipage/#iframe-load-event-steps steps 1-4
     pub fn iframe_load_event_steps(&self, loaded_pipeline: PipelineId) {
         // TODO(#9592): assert that the load blocker is present at all times when we
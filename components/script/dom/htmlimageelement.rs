@@ -50,6 +50,7 @@ use crate::network_listener::{self, NetworkListener, PreInvoke, ResourceTimingLi
 use crate::script_thread::ScriptThread;
 use crate::task_source::TaskSource;
 use app_units::{Au, AU_PER_PX};
+use content_security_policy as csp;
 use cssparser::{Parser, ParserInput};
 use dom_struct::dom_struct;
 use euclid::Point2D;
@@ -267,6 +268,11 @@ impl FetchResponseListener for ImageContext {
     fn submit_resource_timing(&mut self) {
         network_listener::submit_timing(self)
     }
+
+    fn process_csp_violations(&mut self, violations: Vec<csp::Violation>) {
+        let global = &self.resource_timing_global();
+        global.report_csp_violations(violations, None);
+    }
 }
 
 impl ResourceTimingListener for ImageContext {
@@ -394,7 +400,9 @@ impl HTMLImageElement {
             } else {
                 FromPictureOrSrcSet::No
             },
-        );
+        )
+        .csp_list(document.get_csp_list().map(|c| c.clone()))
+        .embedder_policy(document.embedder_policy());
 
         // This is a background load because the load blocker already fulfills the
         // purpose of delaying the document's load event.
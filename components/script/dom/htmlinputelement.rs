@@ -18,6 +18,7 @@ use crate::dom::bindings::inheritance::Castable;
 use crate::dom::bindings::reflector::DomObject;
 use crate::dom::bindings::root::{Dom, DomRoot, LayoutDom, MutNullableDom};
 use crate::dom::bindings::str::{DOMString, USVString};
+use crate::dom::clipboardevent::ClipboardEventType;
 use crate::dom::compositionevent::CompositionEvent;
 use crate::dom::document::Document;
 use crate::dom::element::{
@@ -2235,6 +2236,18 @@ impl VirtualMethods for HTMLInputElement {
             self.input_type().is_textual_or_password()
         {
             if let Some(keyevent) = event.downcast::<KeyboardEvent>() {
+                // A clipboard action is taken only if the clipboard event is not canceled.
+                if let Some(action) =
+                    ClipboardEventType::from_shortcut(keyevent.key(), keyevent.modifiers())
+                {
+                    if event.IsTrusted() &&
+                        document_from_node(self)
+                            .fire_clipboard_event(action)
+                            .is_none()
+                    {
+                        return;
+                    }
+                }
                 // This can't be inlined, as holding on to textinput.borrow_mut()
                 // during self.implicit_submission will cause a panic.
                 let action = self.textinput.borrow_mut().handle_keydown(keyevent);
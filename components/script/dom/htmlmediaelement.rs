@@ -65,6 +65,7 @@ use crate::network_listener::{self, NetworkListener, PreInvoke, ResourceTimingLi
 use crate::realms::InRealm;
 use crate::script_thread::ScriptThread;
 use crate::task_source::TaskSource;
+use content_security_policy as csp;
 use dom_struct::dom_struct;
 use embedder_traits::resources::{self, Resource as EmbedderResource};
 use embedder_traits::{MediaPositionState, MediaSessionEvent, MediaSessionPlaybackState};
@@ -843,7 +844,9 @@ impl HTMLMediaElement {
             .origin(document.origin().immutable().clone())
             .pipeline_id(Some(self.global().pipeline_id()))
             .referrer(Some(Referrer::ReferrerUrl(document.url())))
-            .referrer_policy(document.get_referrer_policy());
+            .referrer_policy(document.get_referrer_policy())
+            .csp_list(document.get_csp_list().map(|c| c.clone()))
+            .embedder_policy(document.embedder_policy());
 
         let mut current_fetch_context = self.current_fetch_context.borrow_mut();
         if let Some(ref mut current_fetch_context) = *current_fetch_context {
@@ -2819,6 +2822,12 @@ impl FetchResponseListener for HTMLMediaElementFetchListener {
     fn submit_resource_timing(&mut self) {
         network_listener::submit_timing(self)
     }
+
+    fn process_csp_violations(&mut self, violations: Vec<csp::Violation>) {
+        let global = &self.resource_timing_global();
+        let elem = self.elem.root();
+        global.report_csp_violations(violations, Some(elem.upcast()));
+    }
 }
 
 impl ResourceTimingListener for HTMLMediaElementFetchListener {
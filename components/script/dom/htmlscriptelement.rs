@@ -38,7 +38,9 @@ use ipc_channel::ipc;
 use ipc_channel::router::ROUTER;
 use js::jsval::UndefinedValue;
 use msg::constellation_msg::PipelineId;
-use net_traits::request::{CorsSettings, CredentialsMode, Destination, Referrer, RequestBuilder};
+use net_traits::request::{
+    CorsSettings, CredentialsMode, Destination, ParserMetadata, Referrer, RequestBuilder,
+};
 use net_traits::ReferrerPolicy;
 use net_traits::{FetchMetadata, FetchResponseListener, Metadata, NetworkError};
 use net_traits::{ResourceFetchTiming, ResourceTimingType};
@@ -294,6 +296,12 @@ impl FetchResponseListener for ClassicContext {
     fn submit_resource_timing(&mut self) {
         network_listener::submit_timing(self)
     }
+
+    fn process_csp_violations(&mut self, violations: Vec<csp::Violation>) {
+        let global = &self.resource_timing_global();
+        let elem = self.elem.root();
+        global.report_csp_violations(violations, Some(elem.upcast()));
+    }
 }
 
 impl ResourceTimingListener for ClassicContext {
@@ -354,7 +362,17 @@ fn fetch_a_classic_script(
         Referrer::ReferrerUrl(doc.url()),
         doc.get_referrer_policy(),
         integrity_metadata,
-    );
+    )
+    .cryptographic_nonce_metadata(String::from(
+        script
+            .upcast::<Element>()
+            .get_string_attribute(&local_name!("nonce")),
+    ))
+    .parser_metadata(if script.parser_inserted.get() {
+        ParserMetadata::ParserInserted
+    } else {
+        ParserMetadata::NotParserInserted
+    });
 
     // TODO: Step 3, Add custom steps to perform fetch
 
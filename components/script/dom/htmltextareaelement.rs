@@ -13,6 +13,7 @@ use crate::dom::bindings::error::ErrorResult;
 use crate::dom::bindings::inheritance::Castable;
 use crate::dom::bindings::root::{DomRoot, LayoutDom, MutNullableDom};
 use crate::dom::bindings::str::DOMString;
+use crate::dom::clipboardevent::ClipboardEventType;
 use crate::dom::compositionevent::CompositionEvent;
 use crate::dom::document::Document;
 use crate::dom::element::RawLayoutElementHelpers;
@@ -552,6 +553,18 @@ impl VirtualMethods for HTMLTextAreaElement {
             document_from_node(self).request_focus(self.upcast());
         } else if event.type_() == atom!("keydown") && !event.DefaultPrevented() {
             if let Some(kevent) = event.downcast::<KeyboardEvent>() {
+                // A clipboard action is taken only if the clipboard event is not canceled.
+                if let Some(action) =
+                    ClipboardEventType::from_shortcut(kevent.key(), kevent.modifiers())
+                {
+                    if event.IsTrusted() &&
+                        document_from_node(self)
+                            .fire_clipboard_event(action)
+                            .is_none()
+                    {
+                        return;
+                    }
+                }
                 // This can't be inlined, as holding on to textinput.borrow_mut()
                 // during self.implicit_submission will cause a panic.
                 let action = self.textinput.borrow_mut().handle_keydown(kevent);
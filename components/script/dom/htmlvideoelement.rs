@@ -22,6 +22,7 @@ use crate::dom::virtualmethods::VirtualMethods;
 use crate::fetch::FetchCanceller;
 use crate::image_listener::{add_cache_listener_for_element, ImageCacheListener};
 use crate::network_listener::{self, NetworkListener, PreInvoke, ResourceTimingListener};
+use content_security_policy as csp;
 use dom_struct::dom_struct;
 use euclid::default::Size2D;
 use html5ever::{LocalName, Prefix};
@@ -196,7 +197,9 @@ impl HTMLVideoElement {
             .credentials_mode(CredentialsMode::Include)
             .use_url_credentials(true)
             .origin(document.origin().immutable().clone())
-            .pipeline_id(Some(document.global().pipeline_id()));
+            .pipeline_id(Some(document.global().pipeline_id()))
+            .csp_list(document.get_csp_list().map(|c| c.clone()))
+            .embedder_policy(document.embedder_policy());
 
         // Step 5.
         // This delay must be independent from the ones created by HTMLMediaElement during
@@ -369,6 +372,12 @@ impl FetchResponseListener for PosterFrameFetchContext {
     fn submit_resource_timing(&mut self) {
         network_listener::submit_timing(self)
     }
+
+    fn process_csp_violations(&mut self, violations: Vec<csp::Violation>) {
+        let global = &self.resource_timing_global();
+        let elem = self.elem.root();
+        global.report_csp_violations(violations, Some(elem.upcast()));
+    }
 }
 
 impl ResourceTimingListener for PosterFrameFetchContext {
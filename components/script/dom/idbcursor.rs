@@ -0,0 +1,435 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::dom::bindings::cell::DomRefCell;
+use crate::dom::bindings::codegen::Bindings::IDBCursorBinding;
+use crate::dom::bindings::codegen::Bindings::IDBCursorBinding::{
+    IDBCursorDirection, IDBCursorMethods,
+};
+use crate::dom::bindings::codegen::UnionTypes::{
+    IDBObjectStoreOrIDBIndex, IDBObjectStoreOrIDBIndexOrIDBCursor,
+};
+use crate::dom::bindings::error::{Error, ErrorResult, Fallible};
+use crate::dom::bindings::reflector::{reflect_dom_object, DomObject, Reflector};
+use crate::dom::bindings::root::{Dom, DomRoot, MutNullableDom};
+use crate::dom::globalscope::GlobalScope;
+use crate::dom::idbindex::IDBIndex;
+use crate::dom::idbobjectstore::IDBObjectStore;
+use crate::dom::idbrequest::IDBRequest;
+use crate::dom::idbtransaction::{IDBTransaction, ResultKind};
+use crate::indexed_db::{
+    convert_value_to_key, deserialize_value, extract_key, key_type_to_jsval, record_value,
+    serialize_value, KeyExtraction,
+};
+use crate::script_runtime::JSContext;
+use dom_struct::dom_struct;
+use js::jsapi::Heap;
+use js::jsval::{JSVal, UndefinedValue};
+use js::rust::HandleValue;
+use net_traits::indexeddb_thread::RecordSource;
+use net_traits::indexeddb_thread::{AsyncOperation, CursorDirection, IndexedDBKeyRange};
+use net_traits::indexeddb_thread::{IndexedDBKeyType, IndexedDBRecord, IndexedDBTxnMode};
+use std::cell::Cell;
+
+#[unrooted_must_root_lint::must_root]
+#[derive(JSTraceable, MallocSizeOf)]
+enum CursorSource {
+    ObjectStore(Dom<IDBObjectStore>),
+    Index(Dom<IDBIndex>),
+}
+
+// https://w3c.github.io/IndexedDB/#cursor-interface
+#[dom_struct]
+pub struct IDBCursor {
+    reflector_: Reflector,
+    source: CursorSource,
+    transaction: Dom<IDBTransaction>,
+    direction: CursorDirection,
+    range: IndexedDBKeyRange,
+    keys_only: bool,
+    /// The key path at which the keys of the object store are injected into its values.
+    inject_path: Option<String>,
+    request: MutNullableDom<IDBRequest>,
+    /// The key and primary key of the record the cursor is at.
+    position: DomRefCell<Option<(IndexedDBKeyType, IndexedDBKeyType)>>,
+    #[ignore_malloc_size_of = "Defined in rust-mozjs"]
+    key: Heap<JSVal>,
+    #[ignore_malloc_size_of = "Defined in rust-mozjs"]
+    primary_key: Heap<JSVal>,
+    #[ignore_malloc_size_of = "Defined in rust-mozjs"]
+    value: Heap<JSVal>,
+    got_value: Cell<bool>,
+}
+
+impl IDBCursor {
+    #[allow(unrooted_must_root)]
+    pub fn new_inherited(
+        source: IDBObjectStoreOrIDBIndex,
+        transaction: &IDBTransaction,
+        direction: CursorDirection,
+        range: IndexedDBKeyRange,
+        keys_only: bool,
+        inject_path: Option<String>,
+    ) -> IDBCursor {
+        let source = match source {
+            IDBObjectStoreOrIDBIndex::IDBObjectStore(store) => {
+                CursorSource::ObjectStore(Dom::from_ref(&*store))
+            },
+            IDBObjectStoreOrIDBIndex::IDBIndex(index) => {
+                CursorSource::Index(Dom::from_ref(&*index))
+            },
+        };
+        IDBCursor {
+            reflector_: Reflector::new(),
+            source,
+            transaction: Dom::from_ref(transaction),
+            direction,
+            range,
+            keys_only,
+            inject_path,
+            request: Default::default(),
+            position: DomRefCell::new(None),
+            key: Heap::default(),
+            primary_key: Heap::default(),
+            value: Heap::default(),
+            got_value: Cell::new(false),
+        }
+    }
+
+    /// A cursor that only reads the keys of records.
+    pub fn new(
+        global: &GlobalScope,
+        source: IDBObjectStoreOrIDBIndex,
+        transaction: &IDBTransaction,
+        direction: CursorDirection,
+        range: IndexedDBKeyRange,
+    ) -> DomRoot<IDBCursor> {
+        reflect_dom_object(
+            Box::new(IDBCursor::new_inherited(
+                source,
+                transaction,
+                direction,
+                range,
+                true,
+                None,
+            )),
+            global,
+            IDBCursorBinding::Wrap,
+        )
+    }
+
+    pub fn set_request(&self, request: &IDBRequest) {
+        self.request.set(Some(request));
+    }
+
+    pub fn value(&self) -> JSVal {
+        self.value.get()
+    }
+
+    /// <https://w3c.github.io/IndexedDB/#cursor-effective-object-store>
+    fn object_store(&self) -> DomRoot<IDBObjectStore> {
+        match self.source {
+            CursorSource::ObjectStore(ref store) => DomRoot::from_ref(&**store),
+            CursorSource::Index(ref index) => index.object_store(),
+        }
+    }
+
+    fn record_source(&self) -> RecordSource {
+        match self.source {
+            CursorSource::ObjectStore(ref store) => RecordSource::ObjectStore(store.name()),
+            CursorSource::Index(ref index) => {
+                RecordSource::Index(index.object_store().name(), index.name())
+            },
+        }
+    }
+
+    fn is_source_deleted(&self) -> bool {
+        match self.source {
+            CursorSource::ObjectStore(ref store) => store.is_deleted(),
+            CursorSource::Index(ref index) => index.is_deleted(),
+        }
+    }
+
+    /// The checks that precede every move of the cursor.
+    fn check_iterable(&self) -> ErrorResult {
+        if !self.transaction.is_active() {
+            return Err(Error::TransactionInactive);
+        }
+        if self.is_source_deleted() {
+            return Err(Error::InvalidState);
+        }
+        if !self.got_value.get() {
+            return Err(Error::InvalidState);
+        }
+        Ok(())
+    }
+
+    /// The checks that precede every change to the record the cursor is at.
+    fn check_writable(&self) -> Fallible<(IndexedDBKeyType, IndexedDBKeyType)> {
+        if !self.transaction.is_active() {
+            return Err(Error::TransactionInactive);
+        }
+        if self.transaction.mode() == IndexedDBTxnMode::Readonly {
+            return Err(Error::ReadOnly);
+        }
+        if self.is_source_deleted() {
+            return Err(Error::InvalidState);
+        }
+        if !self.got_value.get() || self.keys_only {
+            return Err(Error::InvalidState);
+        }
+        self.position.borrow().clone().ok_or(Error::InvalidState)
+    }
+
+    /// <https://w3c.github.io/IndexedDB/#iterate-a-cursor>
+    ///
+    /// Move the cursor `count` times, or to the first record at or after `key` and `primary_key`.
+    pub fn iterate(
+        &self,
+        key: Option<IndexedDBKeyType>,
+        primary_key: Option<IndexedDBKeyType>,
+        count: u32,
+    ) {
+        let request = self.request.get().expect("Cursor without a request");
+        request.set_pending();
+        self.got_value.set(false);
+        let operation = AsyncOperation::Iterate {
+            source: self.record_source(),
+            query: self.range.clone(),
+            direction: self.direction,
+            key,
+            primary_key,
+            position: self.position.borrow().clone(),
+            count,
+            keys_only: self.keys_only,
+        };
+        self.transaction.queue_iteration(operation, &request, self);
+    }
+
+    /// The cursor moved to `record`.
+    pub fn set_record(&self, record: IndexedDBRecord) -> Fallible<()> {
+        let global = self.global();
+        let cx = global.get_cx();
+        rooted!(in(*cx) let mut value = UndefinedValue());
+        key_type_to_jsval(cx, &record.key, value.handle_mut());
+        self.key.set(value.get());
+        key_type_to_jsval(cx, &record.primary_key, value.handle_mut());
+        self.primary_key.set(value.get());
+        if !self.keys_only {
+            let inject_path = self.inject_path.as_ref().map(|path| &**path);
+            record_value(&global, &record, inject_path, value.handle_mut())?;
+            self.value.set(value.get());
+        }
+        *self.position.borrow_mut() = Some((record.key, record.primary_key));
+        self.got_value.set(true);
+        Ok(())
+    }
+
+    /// The cursor moved past the last record in its range.
+    pub fn set_exhausted(&self) {
+        *self.position.borrow_mut() = None;
+        self.key.set(UndefinedValue());
+        self.primary_key.set(UndefinedValue());
+        self.value.set(UndefinedValue());
+        self.got_value.set(false);
+    }
+
+    fn is_forward(&self) -> bool {
+        match self.direction {
+            CursorDirection::Next | CursorDirection::NextUnique => true,
+            CursorDirection::Prev | CursorDirection::PrevUnique => false,
+        }
+    }
+
+    fn request_with_self_as_source(&self) -> DomRoot<IDBRequest> {
+        IDBRequest::new(
+            &self.global(),
+            IDBObjectStoreOrIDBIndexOrIDBCursor::IDBCursor(DomRoot::from_ref(self)),
+            &self.transaction,
+        )
+    }
+}
+
+impl IDBCursorMethods for IDBCursor {
+    // https://w3c.github.io/IndexedDB/#dom-idbcursor-source
+    fn Source(&self) -> IDBObjectStoreOrIDBIndex {
+        match self.source {
+            CursorSource::ObjectStore(ref store) => {
+                IDBObjectStoreOrIDBIndex::IDBObjectStore(DomRoot::from_ref(store))
+            },
+            CursorSource::Index(ref index) => {
+                IDBObjectStoreOrIDBIndex::IDBIndex(DomRoot::from_ref(index))
+            },
+        }
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbcursor-direction
+    fn Direction(&self) -> IDBCursorDirection {
+        match self.direction {
+            CursorDirection::Next => IDBCursorDirection::Next,
+            CursorDirection::NextUnique => IDBCursorDirection::Nextunique,
+            CursorDirection::Prev => IDBCursorDirection::Prev,
+            CursorDirection::PrevUnique => IDBCursorDirection::Prevunique,
+        }
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbcursor-key
+    fn Key(&self, _cx: JSContext) -> JSVal {
+        self.key.get()
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbcursor-primarykey
+    fn PrimaryKey(&self, _cx: JSContext) -> JSVal {
+        self.primary_key.get()
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbcursor-request
+    fn Request(&self) -> DomRoot<IDBRequest> {
+        self.request.get().expect("Cursor without a request")
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbcursor-advance
+    fn Advance(&self, count: u32) -> ErrorResult {
+        // Step 1.
+        if count == 0 {
+            return Err(Error::Type("The count must not be 0".to_owned()));
+        }
+
+        // Steps 2-5.
+        self.check_iterable()?;
+
+        // Steps 6-9.
+        self.iterate(None, None, count);
+        Ok(())
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbcursor-continue
+    fn Continue(&self, cx: JSContext, key: HandleValue) -> ErrorResult {
+        // Steps 1-4.
+        self.check_iterable()?;
+
+        // Step 5.
+        let key = if key.is_undefined() {
+            None
+        } else {
+            let key = convert_value_to_key(cx, key, &mut vec![])?;
+            if let Some((ref position, _)) = *self.position.borrow() {
+                let behind = if self.is_forward() {
+                    key <= *position
+                } else {
+                    key >= *position
+                };
+                if behind {
+                    return Err(Error::Data);
+                }
+            }
+            Some(key)
+        };
+
+        // Steps 6-9.
+        self.iterate(key, None, 1);
+        Ok(())
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbcursor-continueprimarykey
+    fn ContinuePrimaryKey(
+        &self,
+        cx: JSContext,
+        key: HandleValue,
+        primary_key: HandleValue,
+    ) -> ErrorResult {
+        // Steps 1-3.
+        if !self.transaction.is_active() {
+            return Err(Error::TransactionInactive);
+        }
+        if self.is_source_deleted() {
+            return Err(Error::InvalidState);
+        }
+
+        // Steps 4-5.
+        if let CursorSource::ObjectStore(_) = self.source {
+            return Err(Error::InvalidAccess);
+        }
+        match self.direction {
+            CursorDirection::Next | CursorDirection::Prev => {},
+            _ => return Err(Error::InvalidAccess),
+        }
+
+        // Step 6.
+        if !self.got_value.get() {
+            return Err(Error::InvalidState);
+        }
+
+        // Steps 7-10.
+        let key = convert_value_to_key(cx, key, &mut vec![])?;
+        let primary_key = convert_value_to_key(cx, primary_key, &mut vec![])?;
+
+        // Steps 11-14.
+        if let Some((ref position, ref object_store_position)) = *self.position.borrow() {
+            let behind = if self.is_forward() {
+                key < *position || (key == *position && primary_key <= *object_store_position)
+            } else {
+                key > *position || (key == *position && primary_key >= *object_store_position)
+            };
+            if behind {
+                return Err(Error::Data);
+            }
+        }
+
+        // Steps 15-18.
+        self.iterate(Some(key), Some(primary_key), 1);
+        Ok(())
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbcursor-update
+    fn Update(&self, cx: JSContext, value: HandleValue) -> Fallible<DomRoot<IDBRequest>> {
+        // Steps 1-6.
+        let (_, primary_key) = self.check_writable()?;
+        let object_store = self.object_store();
+        let metadata = object_store.metadata()?;
+
+        // Steps 7-8.
+        let serialized = serialize_value(cx, value)?;
+        rooted!(in(*cx) let mut clone = UndefinedValue());
+        deserialize_value(&self.global(), &serialized, clone.handle_mut())?;
+
+        // Step 9.
+        if let Some(ref key_path) = metadata.key_path {
+            match extract_key(cx, clone.handle(), key_path, false)? {
+                KeyExtraction::Key(ref key) if *key == primary_key => {},
+                _ => return Err(Error::Data),
+            }
+        }
+
+        // Step 10.
+        let index_keys = IDBObjectStore::index_keys(cx, clone.handle(), &metadata)?;
+        let request = self.request_with_self_as_source();
+        let operation = AsyncOperation::Put {
+            store: object_store.name(),
+            key: Some(primary_key),
+            value: serialized,
+            index_keys,
+            no_overwrite: false,
+        };
+        self.transaction
+            .queue_operation(operation, Some(&request), ResultKind::Key);
+        Ok(request)
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbcursor-delete
+    fn Delete(&self) -> Fallible<DomRoot<IDBRequest>> {
+        // Steps 1-6.
+        let (_, primary_key) = self.check_writable()?;
+
+        // Step 7.
+        let request = self.request_with_self_as_source();
+        let operation = AsyncOperation::Delete {
+            store: self.object_store().name(),
+            query: IndexedDBKeyRange::only(primary_key),
+        };
+        self.transaction
+            .queue_operation(operation, Some(&request), ResultKind::Undefined);
+        Ok(request)
+    }
+}
@@ -0,0 +1,55 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::dom::bindings::codegen::Bindings::IDBCursorWithValueBinding;
+use crate::dom::bindings::codegen::Bindings::IDBCursorWithValueBinding::IDBCursorWithValueMethods;
+use crate::dom::bindings::codegen::UnionTypes::IDBObjectStoreOrIDBIndex;
+use crate::dom::bindings::reflector::reflect_dom_object;
+use crate::dom::bindings::root::DomRoot;
+use crate::dom::globalscope::GlobalScope;
+use crate::dom::idbcursor::IDBCursor;
+use crate::dom::idbtransaction::IDBTransaction;
+use crate::script_runtime::JSContext;
+use dom_struct::dom_struct;
+use js::jsval::JSVal;
+use net_traits::indexeddb_thread::{CursorDirection, IndexedDBKeyRange};
+
+// https://w3c.github.io/IndexedDB/#idbcursorwithvalue
+#[dom_struct]
+pub struct IDBCursorWithValue {
+    cursor: IDBCursor,
+}
+
+impl IDBCursorWithValue {
+    pub fn new(
+        global: &GlobalScope,
+        source: IDBObjectStoreOrIDBIndex,
+        transaction: &IDBTransaction,
+        direction: CursorDirection,
+        range: IndexedDBKeyRange,
+        inject_path: Option<String>,
+    ) -> DomRoot<IDBCursorWithValue> {
+        reflect_dom_object(
+            Box::new(IDBCursorWithValue {
+                cursor: IDBCursor::new_inherited(
+                    source,
+                    transaction,
+                    direction,
+                    range,
+                    false,
+                    inject_path,
+                ),
+            }),
+            global,
+            IDBCursorWithValueBinding::Wrap,
+        )
+    }
+}
+
+impl IDBCursorWithValueMethods for IDBCursorWithValue {
+    // https://w3c.github.io/IndexedDB/#dom-idbcursorwithvalue-value
+    fn Value(&self, _cx: JSContext) -> JSVal {
+        self.cursor.value()
+    }
+}
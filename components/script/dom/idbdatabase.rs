@@ -0,0 +1,390 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::dom::bindings::cell::{DomRefCell, Ref};
+use crate::dom::bindings::codegen::Bindings::IDBDatabaseBinding;
+use crate::dom::bindings::codegen::Bindings::IDBDatabaseBinding::{
+    IDBDatabaseMethods, IDBObjectStoreParameters,
+};
+use crate::dom::bindings::codegen::Bindings::IDBTransactionBinding::IDBTransactionMode;
+use crate::dom::bindings::codegen::UnionTypes::StringOrStringSequence;
+use crate::dom::bindings::error::{Error, ErrorResult, Fallible};
+use crate::dom::bindings::inheritance::Castable;
+use crate::dom::bindings::reflector::{reflect_dom_object, DomObject};
+use crate::dom::bindings::root::{Dom, DomRoot, MutNullableDom};
+use crate::dom::bindings::str::DOMString;
+use crate::dom::domstringlist::DOMStringList;
+use crate::dom::event::{Event, EventBubbles, EventCancelable};
+use crate::dom::eventtarget::EventTarget;
+use crate::dom::globalscope::GlobalScope;
+use crate::dom::idbobjectstore::IDBObjectStore;
+use crate::dom::idbtransaction::{IDBTransaction, ResultKind};
+use crate::dom::idbversionchangeevent::IDBVersionChangeEvent;
+use crate::indexed_db::{is_valid_key_path, key_path_from_idl};
+use dom_struct::dom_struct;
+use net_traits::indexeddb_thread::{AsyncOperation, ConnectionEvent, ConnectionId};
+use net_traits::indexeddb_thread::{DatabaseMetadata, IndexedDBThreadMsg, IndexedDBTxnMode};
+use net_traits::indexeddb_thread::{KeyPath, ObjectStoreMetadata};
+use net_traits::IpcSend;
+use std::cell::Cell;
+use std::collections::{BTreeMap, HashMap};
+
+// https://w3c.github.io/IndexedDB/#database-interface
+#[dom_struct]
+pub struct IDBDatabase {
+    eventtarget: EventTarget,
+    connection: ConnectionId,
+    /// The schema of the database as this connection sees it, which changes
+    /// as soon as script changes it in an upgrade transaction.
+    metadata: DomRefCell<DatabaseMetadata>,
+    /// <https://w3c.github.io/IndexedDB/#connection-close-pending-flag>
+    close_pending: Cell<bool>,
+    /// The transactions of the connection that haven't finished, by id.
+    transactions: DomRefCell<HashMap<u64, Dom<IDBTransaction>>>,
+    next_transaction_id: Cell<u64>,
+    upgrade_transaction: MutNullableDom<IDBTransaction>,
+}
+
+impl IDBDatabase {
+    fn new_inherited(connection: ConnectionId, metadata: DatabaseMetadata) -> IDBDatabase {
+        IDBDatabase {
+            eventtarget: EventTarget::new_inherited(),
+            connection,
+            metadata: DomRefCell::new(metadata),
+            close_pending: Cell::new(false),
+            transactions: DomRefCell::new(HashMap::new()),
+            next_transaction_id: Cell::new(1),
+            upgrade_transaction: Default::default(),
+        }
+    }
+
+    pub fn new(
+        global: &GlobalScope,
+        connection: ConnectionId,
+        metadata: DatabaseMetadata,
+    ) -> DomRoot<IDBDatabase> {
+        reflect_dom_object(
+            Box::new(IDBDatabase::new_inherited(connection, metadata)),
+            global,
+            IDBDatabaseBinding::Wrap,
+        )
+    }
+
+    pub fn connection(&self) -> ConnectionId {
+        self.connection
+    }
+
+    pub fn send(&self, message: IndexedDBThreadMsg) {
+        let _ = self.global().resource_threads().send(message);
+    }
+
+    pub fn metadata(&self) -> Ref<DatabaseMetadata> {
+        self.metadata.borrow()
+    }
+
+    pub fn set_metadata(&self, metadata: DatabaseMetadata) {
+        *self.metadata.borrow_mut() = metadata;
+    }
+
+    pub fn object_store_metadata(&self, name: &str) -> Option<ObjectStoreMetadata> {
+        self.metadata.borrow().object_stores.get(name).cloned()
+    }
+
+    /// Change the metadata of an object store, in an upgrade transaction.
+    pub fn update_object_stores<F>(&self, update: F)
+    where
+        F: FnOnce(&mut BTreeMap<String, ObjectStoreMetadata>),
+    {
+        update(&mut self.metadata.borrow_mut().object_stores);
+    }
+
+    pub fn object_store_names(&self) -> Vec<DOMString> {
+        self.metadata
+            .borrow()
+            .object_stores
+            .keys()
+            .map(|name| DOMString::from(name.clone()))
+            .collect()
+    }
+
+    pub fn upgrade_transaction(&self) -> Option<DomRoot<IDBTransaction>> {
+        self.upgrade_transaction.get()
+    }
+
+    pub fn set_upgrade_transaction(&self, transaction: Option<&IDBTransaction>) {
+        self.upgrade_transaction.set(transaction);
+    }
+
+    pub fn add_transaction(&self, transaction: &IDBTransaction) {
+        self.transactions
+            .borrow_mut()
+            .insert(transaction.id(), Dom::from_ref(transaction));
+    }
+
+    /// The connection was closed by the backend, after its upgrade transaction was aborted.
+    pub fn mark_closed(&self) {
+        self.close_pending.set(true);
+        self.global().untrack_indexeddb_connection(self);
+    }
+
+    /// <https://w3c.github.io/IndexedDB/#close-a-database-connection>
+    pub fn close(&self) {
+        if self.close_pending.get() {
+            return;
+        }
+        self.close_pending.set(true);
+        self.send(IndexedDBThreadMsg::Close(self.connection));
+        self.global().untrack_indexeddb_connection(self);
+    }
+
+    pub fn handle_event(&self, event: ConnectionEvent) {
+        match event {
+            // https://w3c.github.io/IndexedDB/#open-a-database, step 10.3.
+            ConnectionEvent::VersionChange {
+                old_version,
+                new_version,
+            } => {
+                if !self.close_pending.get() {
+                    let event = IDBVersionChangeEvent::new(
+                        &self.global(),
+                        atom!("versionchange"),
+                        EventBubbles::DoesNotBubble,
+                        EventCancelable::NotCancelable,
+                        old_version,
+                        new_version,
+                    );
+                    event.upcast::<Event>().fire(self.upcast());
+                }
+                self.send(IndexedDBThreadMsg::VersionChangeHandled(self.connection));
+            },
+            ConnectionEvent::RequestResult {
+                transaction,
+                request,
+                result,
+            } => {
+                let transaction = self
+                    .transactions
+                    .borrow()
+                    .get(&transaction)
+                    .map(|transaction| DomRoot::from_ref(&**transaction));
+                if let Some(transaction) = transaction {
+                    transaction.handle_result(request, result);
+                }
+            },
+            ConnectionEvent::TransactionFinished {
+                transaction: id,
+                result,
+            } => {
+                let transaction = self
+                    .transactions
+                    .borrow_mut()
+                    .remove(&id)
+                    .map(|transaction| DomRoot::from_ref(&*transaction));
+                if let Some(transaction) = transaction {
+                    transaction.handle_finished(result);
+                }
+            },
+            event => warn!("Unexpected event for a connection: {:?}", event),
+        }
+    }
+}
+
+impl IDBDatabaseMethods for IDBDatabase {
+    // https://w3c.github.io/IndexedDB/#dom-idbdatabase-name
+    fn Name(&self) -> DOMString {
+        DOMString::from(self.metadata.borrow().name.clone())
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbdatabase-version
+    fn Version(&self) -> u64 {
+        self.metadata.borrow().version
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbdatabase-objectstorenames
+    fn ObjectStoreNames(&self) -> DomRoot<DOMStringList> {
+        DOMStringList::new(&self.global(), self.object_store_names())
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbdatabase-transaction
+    fn Transaction(
+        &self,
+        store_names: StringOrStringSequence,
+        mode: IDBTransactionMode,
+    ) -> Fallible<DomRoot<IDBTransaction>> {
+        // Step 1.
+        let upgrading = self
+            .upgrade_transaction
+            .get()
+            .map_or(false, |transaction| !transaction.is_finished());
+        if upgrading {
+            return Err(Error::InvalidState);
+        }
+
+        // Step 2.
+        if self.close_pending.get() {
+            return Err(Error::InvalidState);
+        }
+
+        // Step 3.
+        let mut scope = match store_names {
+            StringOrStringSequence::String(name) => vec![name.to_string()],
+            StringOrStringSequence::StringSequence(names) => {
+                names.into_iter().map(|name| name.to_string()).collect()
+            },
+        };
+        scope.sort();
+        scope.dedup();
+
+        // Step 4.
+        {
+            let metadata = self.metadata.borrow();
+            if scope
+                .iter()
+                .any(|name| !metadata.object_stores.contains_key(name))
+            {
+                return Err(Error::NotFound);
+            }
+        }
+
+        // Step 5.
+        if scope.is_empty() {
+            return Err(Error::InvalidAccess);
+        }
+
+        // Step 6.
+        let mode = match mode {
+            IDBTransactionMode::Readonly => IndexedDBTxnMode::Readonly,
+            IDBTransactionMode::Readwrite => IndexedDBTxnMode::Readwrite,
+            IDBTransactionMode::Versionchange => {
+                return Err(Error::Type("Invalid transaction mode".to_owned()));
+            },
+        };
+
+        // Steps 7-8.
+        let id = self.next_transaction_id.get();
+        self.next_transaction_id.set(id + 1);
+        self.send(IndexedDBThreadMsg::CreateTransaction {
+            connection: self.connection,
+            transaction: id,
+            scope: scope.clone(),
+            mode,
+        });
+        let global = self.global();
+        let transaction = IDBTransaction::new(&global, self, id, mode, scope);
+        global.register_indexeddb_transaction(&transaction);
+
+        // Step 9.
+        Ok(transaction)
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbdatabase-close
+    fn Close(&self) {
+        self.close();
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbdatabase-createobjectstore
+    fn CreateObjectStore(
+        &self,
+        name: DOMString,
+        options: &IDBObjectStoreParameters,
+    ) -> Fallible<DomRoot<IDBObjectStore>> {
+        // Steps 1-2.
+        let transaction = self.upgrade_transaction.get().ok_or(Error::InvalidState)?;
+
+        // Step 3.
+        if !transaction.is_active() {
+            return Err(Error::TransactionInactive);
+        }
+
+        // Steps 4-5.
+        let key_path = options.keyPath.as_ref().map(key_path_from_idl);
+        if let Some(ref key_path) = key_path {
+            if !is_valid_key_path(key_path) {
+                return Err(Error::Syntax);
+            }
+        }
+
+        // Step 6.
+        let name = name.to_string();
+        if self.metadata.borrow().object_stores.contains_key(&name) {
+            return Err(Error::Constraint);
+        }
+
+        // Steps 7-8.
+        let auto_increment = options.autoIncrement;
+        match key_path {
+            Some(KeyPath::Sequence(_)) if auto_increment => return Err(Error::InvalidAccess),
+            Some(KeyPath::String(ref path)) if auto_increment && path.is_empty() => {
+                return Err(Error::InvalidAccess);
+            },
+            _ => {},
+        }
+
+        // Steps 9-10.
+        self.update_object_stores(|object_stores| {
+            object_stores.insert(
+                name.clone(),
+                ObjectStoreMetadata {
+                    name: name.clone(),
+                    key_path: key_path.clone(),
+                    auto_increment,
+                    indexes: BTreeMap::new(),
+                },
+            );
+        });
+        transaction.queue_operation(
+            AsyncOperation::CreateObjectStore {
+                name: name.clone(),
+                key_path,
+                auto_increment,
+            },
+            None,
+            ResultKind::Undefined,
+        );
+
+        // Step 11.
+        Ok(transaction.object_store_handle(&name))
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbdatabase-deleteobjectstore
+    fn DeleteObjectStore(&self, name: DOMString) -> ErrorResult {
+        // Steps 1-2.
+        let transaction = self.upgrade_transaction.get().ok_or(Error::InvalidState)?;
+
+        // Step 3.
+        if !transaction.is_active() {
+            return Err(Error::TransactionInactive);
+        }
+
+        // Step 4.
+        let name = name.to_string();
+        if !self.metadata.borrow().object_stores.contains_key(&name) {
+            return Err(Error::NotFound);
+        }
+
+        // Steps 5-6.
+        self.update_object_stores(|object_stores| {
+            object_stores.remove(&name);
+        });
+        transaction.forget_object_store_handle(&name);
+        transaction.queue_operation(
+            AsyncOperation::DeleteObjectStore { name },
+            None,
+            ResultKind::Undefined,
+        );
+        Ok(())
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbdatabase-onabort
+    event_handler!(abort, GetOnabort, SetOnabort);
+
+    // https://w3c.github.io/IndexedDB/#dom-idbdatabase-onclose
+    event_handler!(close, GetOnclose, SetOnclose);
+
+    // https://w3c.github.io/IndexedDB/#dom-idbdatabase-onerror
+    event_handler!(error, GetOnerror, SetOnerror);
+
+    // https://w3c.github.io/IndexedDB/#dom-idbdatabase-onversionchange
+    event_handler!(versionchange, GetOnversionchange, SetOnversionchange);
+}
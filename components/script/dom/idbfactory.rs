@@ -0,0 +1,167 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::dom::bindings::codegen::Bindings::IDBFactoryBinding;
+use crate::dom::bindings::codegen::Bindings::IDBFactoryBinding::{
+    IDBDatabaseInfo, IDBFactoryMethods,
+};
+use crate::dom::bindings::error::{Error, Fallible};
+use crate::dom::bindings::refcounted::TrustedPromise;
+use crate::dom::bindings::reflector::{reflect_dom_object, DomObject, Reflector};
+use crate::dom::bindings::root::DomRoot;
+use crate::dom::bindings::str::DOMString;
+use crate::dom::globalscope::GlobalScope;
+use crate::dom::idbopendbrequest::IDBOpenDBRequest;
+use crate::dom::promise::Promise;
+use crate::indexed_db::convert_value_to_key;
+use crate::realms::InRealm;
+use crate::script_runtime::JSContext;
+use crate::task_source::database_access::DatabaseAccessTaskSource;
+use crate::task_source::TaskSource;
+use dom_struct::dom_struct;
+use ipc_channel::ipc;
+use ipc_channel::router::ROUTER;
+use js::rust::HandleValue;
+use net_traits::indexeddb_thread::IndexedDBThreadMsg;
+use net_traits::IpcSend;
+use servo_url::ImmutableOrigin;
+use std::cmp::Ordering;
+use std::rc::Rc;
+
+// https://w3c.github.io/IndexedDB/#factory-interface
+#[dom_struct]
+pub struct IDBFactory {
+    reflector_: Reflector,
+}
+
+impl IDBFactory {
+    fn new_inherited() -> IDBFactory {
+        IDBFactory {
+            reflector_: Reflector::new(),
+        }
+    }
+
+    pub fn new(global: &GlobalScope) -> DomRoot<IDBFactory> {
+        reflect_dom_object(
+            Box::new(IDBFactory::new_inherited()),
+            global,
+            IDBFactoryBinding::Wrap,
+        )
+    }
+
+    /// The origin whose databases script can access, which can't be an opaque origin.
+    fn storage_origin(&self) -> Fallible<ImmutableOrigin> {
+        let origin = self.global().origin().immutable().clone();
+        if !origin.is_tuple() {
+            return Err(Error::Security);
+        }
+        Ok(origin)
+    }
+}
+
+impl IDBFactoryMethods for IDBFactory {
+    // https://w3c.github.io/IndexedDB/#dom-idbfactory-open
+    fn Open(&self, name: DOMString, version: Option<u64>) -> Fallible<DomRoot<IDBOpenDBRequest>> {
+        // Step 1.
+        if version == Some(0) {
+            return Err(Error::Type("The version must not be 0".to_owned()));
+        }
+
+        // Steps 2-3.
+        let origin = self.storage_origin()?;
+
+        // Steps 4-6.
+        let request = IDBOpenDBRequest::new(&self.global());
+        request.start(|events| IndexedDBThreadMsg::Open {
+            origin,
+            name: name.to_string(),
+            version,
+            events,
+        });
+
+        // Step 7.
+        Ok(request)
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbfactory-deletedatabase
+    fn DeleteDatabase(&self, name: DOMString) -> Fallible<DomRoot<IDBOpenDBRequest>> {
+        // Steps 1-2.
+        let origin = self.storage_origin()?;
+
+        // Steps 3-4.
+        let request = IDBOpenDBRequest::new(&self.global());
+        request.start(|events| IndexedDBThreadMsg::DeleteDatabase {
+            origin,
+            name: name.to_string(),
+            events,
+        });
+
+        // Step 5.
+        Ok(request)
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbfactory-databases
+    fn Databases(&self, comp: InRealm) -> Rc<Promise> {
+        let global = self.global();
+        let promise = Promise::new_in_current_realm(&global, comp);
+
+        // Steps 1-4.
+        let origin = match self.storage_origin() {
+            Ok(origin) => origin,
+            Err(error) => {
+                promise.reject_error(error);
+                return promise;
+            },
+        };
+
+        // Step 5.
+        let (sender, receiver) = ipc::channel().unwrap();
+        let mut trusted_promise = Some(TrustedPromise::new(promise.clone()));
+        let task_source = global.database_access_task_source();
+        let canceller = global.task_canceller(DatabaseAccessTaskSource::NAME);
+        ROUTER.add_route(
+            receiver.to_opaque(),
+            Box::new(move |message| {
+                let promise = match trusted_promise.take() {
+                    Some(promise) => promise,
+                    None => return,
+                };
+                let databases: Vec<(String, u64)> = message.to().unwrap_or_default();
+                let _ = task_source.queue_with_canceller(
+                    task!(resolve_databases: move || {
+                        let databases: Vec<IDBDatabaseInfo> = databases
+                            .into_iter()
+                            .map(|(name, version)| IDBDatabaseInfo {
+                                name: Some(DOMString::from(name)),
+                                version: Some(version),
+                            })
+                            .collect();
+                        promise.root().resolve_native(&databases);
+                    }),
+                    &canceller,
+                );
+            }),
+        );
+        let _ = global
+            .resource_threads()
+            .send(IndexedDBThreadMsg::Databases(sender, origin));
+
+        // Step 6.
+        promise
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbfactory-cmp
+    fn Cmp(&self, cx: JSContext, first: HandleValue, second: HandleValue) -> Fallible<i16> {
+        // Steps 1-4.
+        let first = convert_value_to_key(cx, first, &mut vec![])?;
+        let second = convert_value_to_key(cx, second, &mut vec![])?;
+
+        // Step 5.
+        Ok(match first.cmp(&second) {
+            Ordering::Less => -1,
+            Ordering::Equal => 0,
+            Ordering::Greater => 1,
+        })
+    }
+}
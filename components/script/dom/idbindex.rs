@@ -0,0 +1,359 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::dom::bindings::cell::DomRefCell;
+use crate::dom::bindings::codegen::Bindings::IDBCursorBinding::IDBCursorDirection;
+use crate::dom::bindings::codegen::Bindings::IDBIndexBinding;
+use crate::dom::bindings::codegen::Bindings::IDBIndexBinding::IDBIndexMethods;
+use crate::dom::bindings::codegen::UnionTypes::{
+    IDBObjectStoreOrIDBIndex, IDBObjectStoreOrIDBIndexOrIDBCursor,
+};
+use crate::dom::bindings::error::{Error, ErrorResult, Fallible};
+use crate::dom::bindings::reflector::{reflect_dom_object, DomObject, Reflector};
+use crate::dom::bindings::root::{Dom, DomRoot};
+use crate::dom::bindings::str::DOMString;
+use crate::dom::globalscope::GlobalScope;
+use crate::dom::idbcursor::IDBCursor;
+use crate::dom::idbcursorwithvalue::IDBCursorWithValue;
+use crate::dom::idbobjectstore::IDBObjectStore;
+use crate::dom::idbrequest::IDBRequest;
+use crate::dom::idbtransaction::ResultKind;
+use crate::indexed_db::{
+    convert_value_to_key_range, cursor_direction, key_injection_path, key_path_to_jsval,
+};
+use crate::script_runtime::JSContext;
+use dom_struct::dom_struct;
+use js::jsval::{JSVal, UndefinedValue};
+use js::rust::HandleValue;
+use net_traits::indexeddb_thread::{AsyncOperation, IndexMetadata, IndexedDBTxnMode};
+use net_traits::indexeddb_thread::{ObjectStoreMetadata, RecordSource};
+use std::cell::Cell;
+
+// https://w3c.github.io/IndexedDB/#index-interface
+#[dom_struct]
+pub struct IDBIndex {
+    reflector_: Reflector,
+    object_store: Dom<IDBObjectStore>,
+    name: DomRefCell<String>,
+    /// Whether the index was deleted in an upgrade transaction.
+    deleted: Cell<bool>,
+}
+
+impl IDBIndex {
+    fn new_inherited(object_store: &IDBObjectStore, name: &str) -> IDBIndex {
+        IDBIndex {
+            reflector_: Reflector::new(),
+            object_store: Dom::from_ref(object_store),
+            name: DomRefCell::new(name.to_owned()),
+            deleted: Cell::new(false),
+        }
+    }
+
+    pub fn new(
+        global: &GlobalScope,
+        object_store: &IDBObjectStore,
+        name: &str,
+    ) -> DomRoot<IDBIndex> {
+        reflect_dom_object(
+            Box::new(IDBIndex::new_inherited(object_store, name)),
+            global,
+            IDBIndexBinding::Wrap,
+        )
+    }
+
+    pub fn name(&self) -> String {
+        self.name.borrow().clone()
+    }
+
+    pub fn object_store(&self) -> DomRoot<IDBObjectStore> {
+        DomRoot::from_ref(&*self.object_store)
+    }
+
+    pub fn is_deleted(&self) -> bool {
+        self.deleted.get() || self.object_store.is_deleted()
+    }
+
+    pub fn mark_deleted(&self) {
+        self.deleted.set(true);
+    }
+
+    fn metadata(&self) -> Fallible<IndexMetadata> {
+        self.object_store
+            .metadata()?
+            .indexes
+            .get(&*self.name.borrow())
+            .cloned()
+            .ok_or(Error::InvalidState)
+    }
+
+    /// The checks that precede every read from an index, which return
+    /// the metadata of its object store.
+    fn check_usable(&self) -> Fallible<ObjectStoreMetadata> {
+        if self.deleted.get() {
+            return Err(Error::InvalidState);
+        }
+        self.object_store.check_usable()
+    }
+
+    fn source(&self) -> RecordSource {
+        RecordSource::Index(self.object_store.name(), self.name())
+    }
+
+    /// Queue `operation`, whose result becomes the result of a new request.
+    fn queue_request(&self, operation: AsyncOperation, kind: ResultKind) -> DomRoot<IDBRequest> {
+        let transaction = self.object_store.transaction();
+        let request = IDBRequest::new(
+            &self.global(),
+            IDBObjectStoreOrIDBIndexOrIDBCursor::IDBIndex(DomRoot::from_ref(self)),
+            &transaction,
+        );
+        transaction.queue_operation(operation, Some(&request), kind);
+        request
+    }
+
+    /// <https://w3c.github.io/IndexedDB/#dom-idbindex-opencursor>
+    fn open_cursor(
+        &self,
+        cx: JSContext,
+        query: HandleValue,
+        direction: IDBCursorDirection,
+        keys_only: bool,
+    ) -> Fallible<DomRoot<IDBRequest>> {
+        // Steps 1-5.
+        let metadata = self.check_usable()?;
+
+        // Steps 6-7.
+        let range = convert_value_to_key_range(cx, query, false)?;
+
+        // Steps 8-10.
+        let global = self.global();
+        let transaction = self.object_store.transaction();
+        let source = IDBObjectStoreOrIDBIndex::IDBIndex(DomRoot::from_ref(self));
+        let direction = cursor_direction(direction);
+        let cursor = if keys_only {
+            IDBCursor::new(&global, source, &transaction, direction, range)
+        } else {
+            DomRoot::upcast(IDBCursorWithValue::new(
+                &global,
+                source,
+                &transaction,
+                direction,
+                range,
+                key_injection_path(&metadata),
+            ))
+        };
+        let request = IDBRequest::new(
+            &global,
+            IDBObjectStoreOrIDBIndexOrIDBCursor::IDBIndex(DomRoot::from_ref(self)),
+            &transaction,
+        );
+        cursor.set_request(&request);
+        cursor.iterate(None, None, 1);
+        Ok(request)
+    }
+}
+
+impl IDBIndexMethods for IDBIndex {
+    // https://w3c.github.io/IndexedDB/#dom-idbindex-name
+    fn Name(&self) -> DOMString {
+        DOMString::from(self.name())
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbindex-name
+    fn SetName(&self, value: DOMString) -> ErrorResult {
+        // Steps 1-4.
+        let transaction = self.object_store.transaction();
+        if transaction.mode() != IndexedDBTxnMode::Versionchange {
+            return Err(Error::InvalidState);
+        }
+
+        // Step 5.
+        if self.is_deleted() {
+            return Err(Error::InvalidState);
+        }
+
+        // Step 6.
+        if !transaction.is_active() {
+            return Err(Error::TransactionInactive);
+        }
+
+        // Step 7.
+        let name = self.name();
+        let new_name = value.to_string();
+        if name == new_name {
+            return Ok(());
+        }
+
+        // Step 8.
+        let store = self.object_store.name();
+        let metadata = self.object_store.metadata()?;
+        if metadata.indexes.contains_key(&new_name) {
+            return Err(Error::Constraint);
+        }
+
+        // Steps 9-10.
+        transaction.db().update_object_stores(|object_stores| {
+            if let Some(metadata) = object_stores.get_mut(&store) {
+                if let Some(mut index) = metadata.indexes.remove(&name) {
+                    index.name = new_name.clone();
+                    metadata.indexes.insert(new_name.clone(), index);
+                }
+            }
+        });
+        *self.name.borrow_mut() = new_name.clone();
+        self.object_store.rename_index_handle(&name, &new_name);
+        transaction.queue_operation(
+            AsyncOperation::RenameIndex {
+                store,
+                name,
+                new_name,
+            },
+            None,
+            ResultKind::Undefined,
+        );
+        Ok(())
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbindex-objectstore
+    fn ObjectStore(&self) -> DomRoot<IDBObjectStore> {
+        self.object_store()
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbindex-keypath
+    fn KeyPath(&self, cx: JSContext) -> JSVal {
+        let key_path = self.metadata().ok().map(|metadata| metadata.key_path);
+        rooted!(in(*cx) let mut value = UndefinedValue());
+        key_path_to_jsval(cx, key_path.as_ref(), value.handle_mut());
+        value.get()
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbindex-multientry
+    fn MultiEntry(&self) -> bool {
+        self.metadata()
+            .map_or(false, |metadata| metadata.multi_entry)
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbindex-unique
+    fn Unique(&self) -> bool {
+        self.metadata().map_or(false, |metadata| metadata.unique)
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbindex-get
+    fn Get(&self, cx: JSContext, query: HandleValue) -> Fallible<DomRoot<IDBRequest>> {
+        // Steps 1-5.
+        let metadata = self.check_usable()?;
+
+        // Step 6.
+        let query = convert_value_to_key_range(cx, query, true)?;
+
+        // Step 7.
+        let operation = AsyncOperation::Get {
+            source: self.source(),
+            query,
+            keys_only: false,
+        };
+        let kind = ResultKind::Value(key_injection_path(&metadata));
+        Ok(self.queue_request(operation, kind))
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbindex-getkey
+    fn GetKey(&self, cx: JSContext, query: HandleValue) -> Fallible<DomRoot<IDBRequest>> {
+        // Steps 1-5.
+        self.check_usable()?;
+
+        // Step 6.
+        let query = convert_value_to_key_range(cx, query, true)?;
+
+        // Step 7.
+        let operation = AsyncOperation::Get {
+            source: self.source(),
+            query,
+            keys_only: true,
+        };
+        Ok(self.queue_request(operation, ResultKind::PrimaryKey))
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbindex-getall
+    fn GetAll(
+        &self,
+        cx: JSContext,
+        query: HandleValue,
+        count: Option<u32>,
+    ) -> Fallible<DomRoot<IDBRequest>> {
+        // Steps 1-5.
+        let metadata = self.check_usable()?;
+
+        // Step 6.
+        let query = convert_value_to_key_range(cx, query, false)?;
+
+        // Step 7.
+        let operation = AsyncOperation::GetAll {
+            source: self.source(),
+            query,
+            count: count.filter(|count| *count > 0),
+            keys_only: false,
+        };
+        let kind = ResultKind::Values(key_injection_path(&metadata));
+        Ok(self.queue_request(operation, kind))
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbindex-getallkeys
+    fn GetAllKeys(
+        &self,
+        cx: JSContext,
+        query: HandleValue,
+        count: Option<u32>,
+    ) -> Fallible<DomRoot<IDBRequest>> {
+        // Steps 1-5.
+        self.check_usable()?;
+
+        // Step 6.
+        let query = convert_value_to_key_range(cx, query, false)?;
+
+        // Step 7.
+        let operation = AsyncOperation::GetAll {
+            source: self.source(),
+            query,
+            count: count.filter(|count| *count > 0),
+            keys_only: true,
+        };
+        Ok(self.queue_request(operation, ResultKind::PrimaryKeys))
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbindex-count
+    fn Count(&self, cx: JSContext, query: HandleValue) -> Fallible<DomRoot<IDBRequest>> {
+        // Steps 1-5.
+        self.check_usable()?;
+
+        // Step 6.
+        let query = convert_value_to_key_range(cx, query, false)?;
+
+        // Step 7.
+        let operation = AsyncOperation::Count {
+            source: self.source(),
+            query,
+        };
+        Ok(self.queue_request(operation, ResultKind::Count))
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbindex-opencursor
+    fn OpenCursor(
+        &self,
+        cx: JSContext,
+        query: HandleValue,
+        direction: IDBCursorDirection,
+    ) -> Fallible<DomRoot<IDBRequest>> {
+        self.open_cursor(cx, query, direction, false)
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbindex-openkeycursor
+    fn OpenKeyCursor(
+        &self,
+        cx: JSContext,
+        query: HandleValue,
+        direction: IDBCursorDirection,
+    ) -> Fallible<DomRoot<IDBRequest>> {
+        self.open_cursor(cx, query, direction, true)
+    }
+}
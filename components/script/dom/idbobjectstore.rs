@@ -0,0 +1,636 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::dom::bindings::cell::DomRefCell;
+use crate::dom::bindings::codegen::Bindings::IDBCursorBinding::IDBCursorDirection;
+use crate::dom::bindings::codegen::Bindings::IDBObjectStoreBinding;
+use crate::dom::bindings::codegen::Bindings::IDBObjectStoreBinding::{
+    IDBIndexParameters, IDBObjectStoreMethods,
+};
+use crate::dom::bindings::codegen::UnionTypes::{
+    IDBObjectStoreOrIDBIndex, IDBObjectStoreOrIDBIndexOrIDBCursor, StringOrStringSequence,
+};
+use crate::dom::bindings::error::{Error, ErrorResult, Fallible};
+use crate::dom::bindings::reflector::{reflect_dom_object, DomObject, Reflector};
+use crate::dom::bindings::root::{Dom, DomRoot};
+use crate::dom::bindings::str::DOMString;
+use crate::dom::domstringlist::DOMStringList;
+use crate::dom::globalscope::GlobalScope;
+use crate::dom::idbcursor::IDBCursor;
+use crate::dom::idbcursorwithvalue::IDBCursorWithValue;
+use crate::dom::idbindex::IDBIndex;
+use crate::dom::idbrequest::IDBRequest;
+use crate::dom::idbtransaction::{IDBTransaction, ResultKind};
+use crate::indexed_db::{
+    can_inject_key, convert_value_to_key, convert_value_to_key_range, cursor_direction,
+    deserialize_value, extract_key, index_keys, is_valid_key_path, key_injection_path,
+    key_path_from_idl, key_path_to_jsval, serialize_value, KeyExtraction,
+};
+use crate::script_runtime::JSContext;
+use dom_struct::dom_struct;
+use js::jsval::{JSVal, UndefinedValue};
+use js::rust::{HandleValue, MutableHandleValue};
+use net_traits::indexeddb_thread::{AsyncOperation, IndexMetadata, IndexedDBKeyType};
+use net_traits::indexeddb_thread::{IndexedDBTxnMode, KeyPath, ObjectStoreMetadata, RecordSource};
+use std::cell::Cell;
+use std::collections::{BTreeMap, HashMap};
+
+// https://w3c.github.io/IndexedDB/#object-store-interface
+#[dom_struct]
+pub struct IDBObjectStore {
+    reflector_: Reflector,
+    name: DomRefCell<String>,
+    transaction: Dom<IDBTransaction>,
+    /// The index handles created from this object store handle.
+    indexes: DomRefCell<HashMap<String, Dom<IDBIndex>>>,
+    /// Whether the object store was deleted in an upgrade transaction.
+    deleted: Cell<bool>,
+}
+
+impl IDBObjectStore {
+    fn new_inherited(name: &str, transaction: &IDBTransaction) -> IDBObjectStore {
+        IDBObjectStore {
+            reflector_: Reflector::new(),
+            name: DomRefCell::new(name.to_owned()),
+            transaction: Dom::from_ref(transaction),
+            indexes: DomRefCell::new(HashMap::new()),
+            deleted: Cell::new(false),
+        }
+    }
+
+    pub fn new(
+        global: &GlobalScope,
+        name: &str,
+        transaction: &IDBTransaction,
+    ) -> DomRoot<IDBObjectStore> {
+        reflect_dom_object(
+            Box::new(IDBObjectStore::new_inherited(name, transaction)),
+            global,
+            IDBObjectStoreBinding::Wrap,
+        )
+    }
+
+    pub fn name(&self) -> String {
+        self.name.borrow().clone()
+    }
+
+    pub fn transaction(&self) -> DomRoot<IDBTransaction> {
+        DomRoot::from_ref(&*self.transaction)
+    }
+
+    pub fn is_deleted(&self) -> bool {
+        self.deleted.get()
+    }
+
+    pub fn mark_deleted(&self) {
+        self.deleted.set(true);
+    }
+
+    /// The metadata of the object store, which is gone once it is deleted.
+    pub fn metadata(&self) -> Fallible<ObjectStoreMetadata> {
+        self.transaction
+            .db()
+            .object_store_metadata(&self.name.borrow())
+            .ok_or(Error::InvalidState)
+    }
+
+    /// The checks that precede every operation on the records of an object store.
+    pub fn check_usable(&self) -> Fallible<ObjectStoreMetadata> {
+        if self.deleted.get() {
+            return Err(Error::InvalidState);
+        }
+        let metadata = self.metadata()?;
+        if !self.transaction.is_active() {
+            return Err(Error::TransactionInactive);
+        }
+        Ok(metadata)
+    }
+
+    /// The checks that precede every change to the records of an object store.
+    pub fn check_writable(&self) -> Fallible<ObjectStoreMetadata> {
+        let metadata = self.check_usable()?;
+        if self.transaction.mode() == IndexedDBTxnMode::Readonly {
+            return Err(Error::ReadOnly);
+        }
+        Ok(metadata)
+    }
+
+    /// The checks that precede every change to the schema of an object store.
+    fn check_upgradable(&self) -> Fallible<ObjectStoreMetadata> {
+        if self.transaction.mode() != IndexedDBTxnMode::Versionchange {
+            return Err(Error::InvalidState);
+        }
+        if self.deleted.get() {
+            return Err(Error::InvalidState);
+        }
+        let metadata = self.metadata()?;
+        if !self.transaction.is_active() {
+            return Err(Error::TransactionInactive);
+        }
+        Ok(metadata)
+    }
+
+    fn source(&self) -> RecordSource {
+        RecordSource::ObjectStore(self.name())
+    }
+
+    /// Queue `operation`, whose result becomes the result of a new request.
+    fn queue_request(&self, operation: AsyncOperation, kind: ResultKind) -> DomRoot<IDBRequest> {
+        let request = IDBRequest::new(
+            &self.global(),
+            IDBObjectStoreOrIDBIndexOrIDBCursor::IDBObjectStore(DomRoot::from_ref(self)),
+            &self.transaction,
+        );
+        self.transaction
+            .queue_operation(operation, Some(&request), kind);
+        request
+    }
+
+    /// <https://w3c.github.io/IndexedDB/#clone-a-value>
+    ///
+    /// Serialize `value`, and deserialize it into `clone` to extract its keys.
+    fn clone_value(
+        &self,
+        cx: JSContext,
+        value: HandleValue,
+        clone: MutableHandleValue,
+    ) -> Fallible<Vec<u8>> {
+        let serialized = serialize_value(cx, value)?;
+        deserialize_value(&self.global(), &serialized, clone)?;
+        Ok(serialized)
+    }
+
+    /// The keys that a value has in each index of the object store.
+    pub fn index_keys(
+        cx: JSContext,
+        value: HandleValue,
+        metadata: &ObjectStoreMetadata,
+    ) -> Fallible<BTreeMap<String, Vec<IndexedDBKeyType>>> {
+        let mut keys = BTreeMap::new();
+        for index in metadata.indexes.values() {
+            keys.insert(index.name.clone(), index_keys(cx, value, index)?);
+        }
+        Ok(keys)
+    }
+
+    /// <https://w3c.github.io/IndexedDB/#add-or-put>
+    fn add_or_put(
+        &self,
+        cx: JSContext,
+        value: HandleValue,
+        key: HandleValue,
+        no_overwrite: bool,
+    ) -> Fallible<DomRoot<IDBRequest>> {
+        // Steps 1-5.
+        let metadata = self.check_writable()?;
+
+        // Step 6.
+        if metadata.key_path.is_some() && !key.is_undefined() {
+            return Err(Error::Data);
+        }
+
+        // Step 7.
+        if metadata.key_path.is_none() && !metadata.auto_increment && key.is_undefined() {
+            return Err(Error::Data);
+        }
+
+        // Step 8.
+        let mut key = if key.is_undefined() {
+            None
+        } else {
+            Some(convert_value_to_key(cx, key, &mut vec![])?)
+        };
+
+        // Steps 9-10.
+        rooted!(in(*cx) let mut clone = UndefinedValue());
+        let serialized = self.clone_value(cx, value, clone.handle_mut())?;
+
+        // Step 11.
+        if let Some(ref key_path) = metadata.key_path {
+            match extract_key(cx, clone.handle(), key_path, false)? {
+                KeyExtraction::Key(extracted) => key = Some(extracted),
+                KeyExtraction::Invalid => return Err(Error::Data),
+                KeyExtraction::Failure => match *key_path {
+                    KeyPath::String(ref path) if metadata.auto_increment => {
+                        if !can_inject_key(cx, clone.handle(), path)? {
+                            return Err(Error::Data);
+                        }
+                    },
+                    _ => return Err(Error::Data),
+                },
+            }
+        }
+
+        // Step 12.
+        let index_keys = IDBObjectStore::index_keys(cx, clone.handle(), &metadata)?;
+        let operation = AsyncOperation::Put {
+            store: self.name(),
+            key,
+            value: serialized,
+            index_keys,
+            no_overwrite,
+        };
+        Ok(self.queue_request(operation, ResultKind::Key))
+    }
+
+    /// <https://w3c.github.io/IndexedDB/#dom-idbobjectstore-opencursor>
+    fn open_cursor(
+        &self,
+        cx: JSContext,
+        query: HandleValue,
+        direction: IDBCursorDirection,
+        keys_only: bool,
+    ) -> Fallible<DomRoot<IDBRequest>> {
+        // Steps 1-5.
+        let metadata = self.check_usable()?;
+
+        // Steps 6-7.
+        let range = convert_value_to_key_range(cx, query, false)?;
+
+        // Steps 8-10.
+        let global = self.global();
+        let source = IDBObjectStoreOrIDBIndex::IDBObjectStore(DomRoot::from_ref(self));
+        let direction = cursor_direction(direction);
+        let inject_path = key_injection_path(&metadata);
+        let cursor = if keys_only {
+            IDBCursor::new(&global, source, &self.transaction, direction, range)
+        } else {
+            DomRoot::upcast(IDBCursorWithValue::new(
+                &global,
+                source,
+                &self.transaction,
+                direction,
+                range,
+                inject_path,
+            ))
+        };
+        let request = IDBRequest::new(
+            &global,
+            IDBObjectStoreOrIDBIndexOrIDBCursor::IDBObjectStore(DomRoot::from_ref(self)),
+            &self.transaction,
+        );
+        cursor.set_request(&request);
+        cursor.iterate(None, None, 1);
+        Ok(request)
+    }
+
+    fn index_handle(&self, metadata: &IndexMetadata) -> DomRoot<IDBIndex> {
+        if let Some(index) = self.indexes.borrow().get(&metadata.name) {
+            return DomRoot::from_ref(&**index);
+        }
+        let index = IDBIndex::new(&self.global(), self, &metadata.name);
+        self.indexes
+            .borrow_mut()
+            .insert(metadata.name.clone(), Dom::from_ref(&*index));
+        index
+    }
+
+    pub fn rename_index_handle(&self, name: &str, new_name: &str) {
+        let mut indexes = self.indexes.borrow_mut();
+        if let Some(index) = indexes.remove(name) {
+            indexes.insert(new_name.to_owned(), index);
+        }
+    }
+}
+
+impl IDBObjectStoreMethods for IDBObjectStore {
+    // https://w3c.github.io/IndexedDB/#dom-idbobjectstore-name
+    fn Name(&self) -> DOMString {
+        DOMString::from(self.name())
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbobjectstore-name
+    fn SetName(&self, value: DOMString) -> ErrorResult {
+        // Steps 1-6.
+        self.check_upgradable()?;
+
+        // Step 7.
+        let name = self.name();
+        let new_name = value.to_string();
+        if name == new_name {
+            return Ok(());
+        }
+
+        // Step 8.
+        let db = self.transaction.db();
+        if db.object_store_metadata(&new_name).is_some() {
+            return Err(Error::Constraint);
+        }
+
+        // Steps 9-10.
+        db.update_object_stores(|object_stores| {
+            if let Some(mut metadata) = object_stores.remove(&name) {
+                metadata.name = new_name.clone();
+                object_stores.insert(new_name.clone(), metadata);
+            }
+        });
+        *self.name.borrow_mut() = new_name.clone();
+        self.transaction
+            .rename_object_store_handle(&name, &new_name);
+        self.transaction.queue_operation(
+            AsyncOperation::RenameObjectStore { name, new_name },
+            None,
+            ResultKind::Undefined,
+        );
+        Ok(())
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbobjectstore-keypath
+    fn KeyPath(&self, cx: JSContext) -> JSVal {
+        let key_path = self.metadata().ok().and_then(|metadata| metadata.key_path);
+        rooted!(in(*cx) let mut value = UndefinedValue());
+        key_path_to_jsval(cx, key_path.as_ref(), value.handle_mut());
+        value.get()
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbobjectstore-indexnames
+    fn IndexNames(&self) -> DomRoot<DOMStringList> {
+        let names = self
+            .metadata()
+            .map(|metadata| {
+                metadata
+                    .indexes
+                    .keys()
+                    .cloned()
+                    .map(DOMString::from)
+                    .collect()
+            })
+            .unwrap_or_default();
+        DOMStringList::new(&self.global(), names)
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbobjectstore-transaction
+    fn Transaction(&self) -> DomRoot<IDBTransaction> {
+        self.transaction()
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbobjectstore-autoincrement
+    fn AutoIncrement(&self) -> bool {
+        self.metadata()
+            .map_or(false, |metadata| metadata.auto_increment)
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbobjectstore-put
+    fn Put(
+        &self,
+        cx: JSContext,
+        value: HandleValue,
+        key: HandleValue,
+    ) -> Fallible<DomRoot<IDBRequest>> {
+        self.add_or_put(cx, value, key, false)
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbobjectstore-add
+    fn Add(
+        &self,
+        cx: JSContext,
+        value: HandleValue,
+        key: HandleValue,
+    ) -> Fallible<DomRoot<IDBRequest>> {
+        self.add_or_put(cx, value, key, true)
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbobjectstore-delete
+    fn Delete(&self, cx: JSContext, query: HandleValue) -> Fallible<DomRoot<IDBRequest>> {
+        // Steps 1-6.
+        self.check_writable()?;
+
+        // Step 7.
+        let query = convert_value_to_key_range(cx, query, true)?;
+
+        // Step 8.
+        let operation = AsyncOperation::Delete {
+            store: self.name(),
+            query,
+        };
+        Ok(self.queue_request(operation, ResultKind::Undefined))
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbobjectstore-clear
+    fn Clear(&self) -> Fallible<DomRoot<IDBRequest>> {
+        // Steps 1-6.
+        self.check_writable()?;
+
+        // Step 7.
+        let operation = AsyncOperation::Clear { store: self.name() };
+        Ok(self.queue_request(operation, ResultKind::Undefined))
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbobjectstore-get
+    fn Get(&self, cx: JSContext, query: HandleValue) -> Fallible<DomRoot<IDBRequest>> {
+        // Steps 1-5.
+        let metadata = self.check_usable()?;
+
+        // Step 6.
+        let query = convert_value_to_key_range(cx, query, true)?;
+
+        // Step 7.
+        let operation = AsyncOperation::Get {
+            source: self.source(),
+            query,
+            keys_only: false,
+        };
+        let kind = ResultKind::Value(key_injection_path(&metadata));
+        Ok(self.queue_request(operation, kind))
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbobjectstore-getkey
+    fn GetKey(&self, cx: JSContext, query: HandleValue) -> Fallible<DomRoot<IDBRequest>> {
+        // Steps 1-5.
+        self.check_usable()?;
+
+        // Step 6.
+        let query = convert_value_to_key_range(cx, query, true)?;
+
+        // Step 7.
+        let operation = AsyncOperation::Get {
+            source: self.source(),
+            query,
+            keys_only: true,
+        };
+        Ok(self.queue_request(operation, ResultKind::PrimaryKey))
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbobjectstore-getall
+    fn GetAll(
+        &self,
+        cx: JSContext,
+        query: HandleValue,
+        count: Option<u32>,
+    ) -> Fallible<DomRoot<IDBRequest>> {
+        // Steps 1-5.
+        let metadata = self.check_usable()?;
+
+        // Step 6.
+        let query = convert_value_to_key_range(cx, query, false)?;
+
+        // Step 7.
+        let operation = AsyncOperation::GetAll {
+            source: self.source(),
+            query,
+            count: count.filter(|count| *count > 0),
+            keys_only: false,
+        };
+        let kind = ResultKind::Values(key_injection_path(&metadata));
+        Ok(self.queue_request(operation, kind))
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbobjectstore-getallkeys
+    fn GetAllKeys(
+        &self,
+        cx: JSContext,
+        query: HandleValue,
+        count: Option<u32>,
+    ) -> Fallible<DomRoot<IDBRequest>> {
+        // Steps 1-5.
+        self.check_usable()?;
+
+        // Step 6.
+        let query = convert_value_to_key_range(cx, query, false)?;
+
+        // Step 7.
+        let operation = AsyncOperation::GetAll {
+            source: self.source(),
+            query,
+            count: count.filter(|count| *count > 0),
+            keys_only: true,
+        };
+        Ok(self.queue_request(operation, ResultKind::PrimaryKeys))
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbobjectstore-count
+    fn Count(&self, cx: JSContext, query: HandleValue) -> Fallible<DomRoot<IDBRequest>> {
+        // Steps 1-5.
+        self.check_usable()?;
+
+        // Step 6.
+        let query = convert_value_to_key_range(cx, query, false)?;
+
+        // Step 7.
+        let operation = AsyncOperation::Count {
+            source: self.source(),
+            query,
+        };
+        Ok(self.queue_request(operation, ResultKind::Count))
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbobjectstore-opencursor
+    fn OpenCursor(
+        &self,
+        cx: JSContext,
+        query: HandleValue,
+        direction: IDBCursorDirection,
+    ) -> Fallible<DomRoot<IDBRequest>> {
+        self.open_cursor(cx, query, direction, false)
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbobjectstore-openkeycursor
+    fn OpenKeyCursor(
+        &self,
+        cx: JSContext,
+        query: HandleValue,
+        direction: IDBCursorDirection,
+    ) -> Fallible<DomRoot<IDBRequest>> {
+        self.open_cursor(cx, query, direction, true)
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbobjectstore-index
+    fn Index(&self, name: DOMString) -> Fallible<DomRoot<IDBIndex>> {
+        // Steps 1-4.
+        if self.deleted.get() || self.transaction.is_finished() {
+            return Err(Error::InvalidState);
+        }
+
+        // Steps 5-6.
+        let metadata = self.metadata()?;
+        let index = metadata.indexes.get(&*name).ok_or(Error::NotFound)?;
+        Ok(self.index_handle(index))
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbobjectstore-createindex
+    fn CreateIndex(
+        &self,
+        name: DOMString,
+        key_path: StringOrStringSequence,
+        options: &IDBIndexParameters,
+    ) -> Fallible<DomRoot<IDBIndex>> {
+        // Steps 1-6.
+        let metadata = self.check_upgradable()?;
+
+        // Step 7.
+        let name = name.to_string();
+        if metadata.indexes.contains_key(&name) {
+            return Err(Error::Constraint);
+        }
+
+        // Step 8.
+        let key_path = key_path_from_idl(&key_path);
+        if !is_valid_key_path(&key_path) {
+            return Err(Error::Syntax);
+        }
+
+        // Steps 9-10.
+        let multi_entry = options.multiEntry;
+        if let KeyPath::Sequence(_) = key_path {
+            if multi_entry {
+                return Err(Error::InvalidAccess);
+            }
+        }
+
+        // Steps 11-13.
+        let index = IndexMetadata {
+            name: name.clone(),
+            key_path,
+            unique: options.unique,
+            multi_entry,
+        };
+        let store = self.name();
+        self.transaction.db().update_object_stores(|object_stores| {
+            if let Some(metadata) = object_stores.get_mut(&store) {
+                metadata.indexes.insert(name.clone(), index.clone());
+            }
+        });
+        self.transaction.queue_operation(
+            AsyncOperation::CreateIndex {
+                store: store.clone(),
+                index: index.clone(),
+            },
+            None,
+            ResultKind::IndexPopulation {
+                store,
+                index: index.clone(),
+            },
+        );
+        Ok(self.index_handle(&index))
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbobjectstore-deleteindex
+    fn DeleteIndex(&self, name: DOMString) -> ErrorResult {
+        // Steps 1-6.
+        let metadata = self.check_upgradable()?;
+
+        // Step 7.
+        let name = name.to_string();
+        if !metadata.indexes.contains_key(&name) {
+            return Err(Error::NotFound);
+        }
+
+        // Steps 8-9.
+        let store = self.name();
+        self.transaction.db().update_object_stores(|object_stores| {
+            if let Some(metadata) = object_stores.get_mut(&store) {
+                metadata.indexes.remove(&name);
+            }
+        });
+        if let Some(index) = self.indexes.borrow_mut().remove(&name) {
+            index.mark_deleted();
+        }
+        self.transaction.queue_operation(
+            AsyncOperation::DeleteIndex { store, name },
+            None,
+            ResultKind::Undefined,
+        );
+        Ok(())
+    }
+}
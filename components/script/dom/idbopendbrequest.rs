@@ -0,0 +1,212 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::dom::bindings::codegen::Bindings::IDBOpenDBRequestBinding;
+use crate::dom::bindings::codegen::Bindings::IDBOpenDBRequestBinding::IDBOpenDBRequestMethods;
+use crate::dom::bindings::conversions::ToJSValConvertible;
+use crate::dom::bindings::inheritance::Castable;
+use crate::dom::bindings::refcounted::Trusted;
+use crate::dom::bindings::reflector::{reflect_dom_object, DomObject};
+use crate::dom::bindings::root::{DomRoot, MutNullableDom};
+use crate::dom::event::{Event, EventBubbles, EventCancelable};
+use crate::dom::globalscope::GlobalScope;
+use crate::dom::idbdatabase::IDBDatabase;
+use crate::dom::idbrequest::IDBRequest;
+use crate::dom::idbtransaction::IDBTransaction;
+use crate::dom::idbversionchangeevent::IDBVersionChangeEvent;
+use crate::indexed_db::backend_error_name;
+use crate::task_source::database_access::DatabaseAccessTaskSource;
+use crate::task_source::TaskSource;
+use dom_struct::dom_struct;
+use ipc_channel::ipc::{self, IpcSender};
+use ipc_channel::router::ROUTER;
+use js::jsval::UndefinedValue;
+use net_traits::indexeddb_thread::{ConnectionEvent, DatabaseMetadata, IndexedDBThreadMsg};
+use net_traits::indexeddb_thread::{ConnectionId, IndexedDBTxnMode, UPGRADE_TRANSACTION_ID};
+use net_traits::IpcSend;
+
+// https://w3c.github.io/IndexedDB/#idbopendbrequest
+#[dom_struct]
+pub struct IDBOpenDBRequest {
+    request: IDBRequest,
+    /// The connection that the request opened, once the backend created it.
+    connection: MutNullableDom<IDBDatabase>,
+}
+
+impl IDBOpenDBRequest {
+    fn new_inherited() -> IDBOpenDBRequest {
+        IDBOpenDBRequest {
+            request: IDBRequest::new_inherited(None, None),
+            connection: Default::default(),
+        }
+    }
+
+    pub fn new(global: &GlobalScope) -> DomRoot<IDBOpenDBRequest> {
+        reflect_dom_object(
+            Box::new(IDBOpenDBRequest::new_inherited()),
+            global,
+            IDBOpenDBRequestBinding::Wrap,
+        )
+    }
+
+    /// Send the open or delete request that `message` builds to the backend,
+    /// and handle the events it sends back on the database access task source.
+    pub fn start<F>(&self, message: F)
+    where
+        F: FnOnce(IpcSender<ConnectionEvent>) -> IndexedDBThreadMsg,
+    {
+        let global = self.global();
+        let (events_sender, events_receiver) = ipc::channel().unwrap();
+        let backend: IpcSender<IndexedDBThreadMsg> = global.resource_threads().sender();
+        let trusted = Trusted::new(self);
+        let task_source = global.database_access_task_source();
+        let canceller = global.task_canceller(DatabaseAccessTaskSource::NAME);
+        let mut connection: Option<ConnectionId> = None;
+        ROUTER.add_route(
+            events_receiver.to_opaque(),
+            Box::new(move |message| {
+                let event: ConnectionEvent = match message.to() {
+                    Ok(event) => event,
+                    Err(_) => return,
+                };
+                match event {
+                    ConnectionEvent::UpgradeNeeded { connection: id, .. } |
+                    ConnectionEvent::Opened { connection: id, .. } => connection = Some(id),
+                    _ => {},
+                }
+                let request = trusted.clone();
+                let queued = task_source.queue_with_canceller(
+                    task!(handle_connection_event: move || {
+                        request.root().handle_event(event);
+                    }),
+                    &canceller,
+                );
+                // Don't leave the backend waiting for a global that is gone.
+                if let (Err(_), Some(connection)) = (queued, connection) {
+                    let _ = backend.send(IndexedDBThreadMsg::VersionChangeHandled(connection));
+                    let _ = backend.send(IndexedDBThreadMsg::Close(connection));
+                }
+            }),
+        );
+        let _ = global.resource_threads().send(message(events_sender));
+    }
+
+    #[allow(unsafe_code)]
+    fn set_result_to_connection(&self, connection: &IDBDatabase) {
+        let cx = self.global().get_cx();
+        rooted!(in(*cx) let mut result = UndefinedValue());
+        unsafe { connection.reflector().to_jsval(*cx, result.handle_mut()) };
+        self.upcast::<IDBRequest>().set_result(result.handle());
+    }
+
+    fn connection(&self, id: ConnectionId, metadata: DatabaseMetadata) -> DomRoot<IDBDatabase> {
+        if let Some(connection) = self.connection.get() {
+            return connection;
+        }
+        let global = self.global();
+        let connection = IDBDatabase::new(&global, id, metadata);
+        global.track_indexeddb_connection(&connection);
+        self.connection.set(Some(&connection));
+        connection
+    }
+
+    fn handle_event(&self, event: ConnectionEvent) {
+        let global = self.global();
+        let request = self.upcast::<IDBRequest>();
+        match event {
+            ConnectionEvent::Blocked {
+                old_version,
+                new_version,
+            } => {
+                let event = IDBVersionChangeEvent::new(
+                    &global,
+                    atom!("blocked"),
+                    EventBubbles::DoesNotBubble,
+                    EventCancelable::NotCancelable,
+                    old_version,
+                    new_version,
+                );
+                event.upcast::<Event>().fire(self.upcast());
+            },
+            // https://w3c.github.io/IndexedDB/#run-an-upgrade-transaction
+            ConnectionEvent::UpgradeNeeded {
+                connection,
+                old_version,
+                metadata,
+            } => {
+                let new_version = metadata.version;
+                let mut old_metadata = metadata.clone();
+                old_metadata.version = old_version;
+                let connection = self.connection(connection, metadata);
+                let transaction = IDBTransaction::new(
+                    &global,
+                    &connection,
+                    UPGRADE_TRANSACTION_ID,
+                    IndexedDBTxnMode::Versionchange,
+                    vec![],
+                );
+                transaction.set_old_metadata(old_metadata);
+                connection.set_upgrade_transaction(Some(&transaction));
+                request.set_transaction(Some(&transaction));
+                self.set_result_to_connection(&connection);
+
+                let event = IDBVersionChangeEvent::new(
+                    &global,
+                    atom!("upgradeneeded"),
+                    EventBubbles::DoesNotBubble,
+                    EventCancelable::NotCancelable,
+                    old_version,
+                    Some(new_version),
+                );
+                request.dispatch_in_transaction(event.upcast());
+                transaction.deactivate();
+            },
+            ConnectionEvent::Opened {
+                connection,
+                metadata,
+            } => {
+                let connection = self.connection(connection, metadata);
+                connection.set_upgrade_transaction(None);
+                request.set_transaction(None);
+                self.set_result_to_connection(&connection);
+                request.fire_success();
+            },
+            ConnectionEvent::OpenFailed(error) => {
+                if let Some(connection) = self.connection.get() {
+                    connection.set_upgrade_transaction(None);
+                    connection.mark_closed();
+                }
+                request.set_transaction(None);
+                request.set_error(backend_error_name(&error));
+                request.fire_error();
+            },
+            ConnectionEvent::Deleted { old_version } => {
+                rooted!(in(*global.get_cx()) let result = UndefinedValue());
+                request.set_result(result.handle());
+                let event = IDBVersionChangeEvent::new(
+                    &global,
+                    atom!("success"),
+                    EventBubbles::DoesNotBubble,
+                    EventCancelable::NotCancelable,
+                    old_version,
+                    None,
+                );
+                event.upcast::<Event>().fire(self.upcast());
+            },
+            event => {
+                if let Some(connection) = self.connection.get() {
+                    connection.handle_event(event);
+                }
+            },
+        }
+    }
+}
+
+impl IDBOpenDBRequestMethods for IDBOpenDBRequest {
+    // https://w3c.github.io/IndexedDB/#dom-idbopendbrequest-onblocked
+    event_handler!(blocked, GetOnblocked, SetOnblocked);
+
+    // https://w3c.github.io/IndexedDB/#dom-idbopendbrequest-onupgradeneeded
+    event_handler!(upgradeneeded, GetOnupgradeneeded, SetOnupgradeneeded);
+}
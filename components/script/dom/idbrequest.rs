@@ -0,0 +1,207 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::dom::bindings::codegen::Bindings::EventBinding::EventMethods;
+use crate::dom::bindings::codegen::Bindings::IDBRequestBinding;
+use crate::dom::bindings::codegen::Bindings::IDBRequestBinding::{
+    IDBRequestMethods, IDBRequestReadyState,
+};
+use crate::dom::bindings::codegen::UnionTypes::IDBObjectStoreOrIDBIndexOrIDBCursor;
+use crate::dom::bindings::error::{Error, Fallible};
+use crate::dom::bindings::inheritance::Castable;
+use crate::dom::bindings::reflector::{reflect_dom_object, DomObject};
+use crate::dom::bindings::root::{Dom, DomRoot, MutNullableDom};
+use crate::dom::domexception::{DOMErrorName, DOMException};
+use crate::dom::event::{Event, EventBubbles, EventCancelable};
+use crate::dom::eventtarget::EventTarget;
+use crate::dom::globalscope::GlobalScope;
+use crate::dom::idbcursor::IDBCursor;
+use crate::dom::idbindex::IDBIndex;
+use crate::dom::idbobjectstore::IDBObjectStore;
+use crate::dom::idbtransaction::IDBTransaction;
+use crate::script_runtime::JSContext;
+use dom_struct::dom_struct;
+use js::jsapi::Heap;
+use js::jsval::{JSVal, UndefinedValue};
+use js::rust::HandleValue;
+use std::cell::Cell;
+
+#[unrooted_must_root_lint::must_root]
+#[derive(JSTraceable, MallocSizeOf)]
+enum RequestSource {
+    ObjectStore(Dom<IDBObjectStore>),
+    Index(Dom<IDBIndex>),
+    Cursor(Dom<IDBCursor>),
+}
+
+// https://w3c.github.io/IndexedDB/#request-api
+#[dom_struct]
+pub struct IDBRequest {
+    eventtarget: EventTarget,
+    #[ignore_malloc_size_of = "Defined in rust-mozjs"]
+    result: Heap<JSVal>,
+    error: MutNullableDom<DOMException>,
+    source: Option<RequestSource>,
+    transaction: MutNullableDom<IDBTransaction>,
+    ready_state: Cell<IDBRequestReadyState>,
+}
+
+impl IDBRequest {
+    #[allow(unrooted_must_root)]
+    pub fn new_inherited(
+        source: Option<IDBObjectStoreOrIDBIndexOrIDBCursor>,
+        transaction: Option<&IDBTransaction>,
+    ) -> IDBRequest {
+        let source = source.map(|source| match source {
+            IDBObjectStoreOrIDBIndexOrIDBCursor::IDBObjectStore(store) => {
+                RequestSource::ObjectStore(Dom::from_ref(&*store))
+            },
+            IDBObjectStoreOrIDBIndexOrIDBCursor::IDBIndex(index) => {
+                RequestSource::Index(Dom::from_ref(&*index))
+            },
+            IDBObjectStoreOrIDBIndexOrIDBCursor::IDBCursor(cursor) => {
+                RequestSource::Cursor(Dom::from_ref(&*cursor))
+            },
+        });
+        IDBRequest {
+            eventtarget: EventTarget::new_inherited(),
+            result: Heap::default(),
+            error: Default::default(),
+            source,
+            transaction: MutNullableDom::new(transaction),
+            ready_state: Cell::new(IDBRequestReadyState::Pending),
+        }
+    }
+
+    pub fn new(
+        global: &GlobalScope,
+        source: IDBObjectStoreOrIDBIndexOrIDBCursor,
+        transaction: &IDBTransaction,
+    ) -> DomRoot<IDBRequest> {
+        reflect_dom_object(
+            Box::new(IDBRequest::new_inherited(Some(source), Some(transaction))),
+            global,
+            IDBRequestBinding::Wrap,
+        )
+    }
+
+    pub fn transaction(&self) -> Option<DomRoot<IDBTransaction>> {
+        self.transaction.get()
+    }
+
+    pub fn set_transaction(&self, transaction: Option<&IDBTransaction>) {
+        self.transaction.set(transaction);
+    }
+
+    /// Wait for another result, as a cursor does when it iterates.
+    pub fn set_pending(&self) {
+        self.ready_state.set(IDBRequestReadyState::Pending);
+    }
+
+    pub fn set_result(&self, result: HandleValue) {
+        self.ready_state.set(IDBRequestReadyState::Done);
+        self.error.set(None);
+        self.result.set(result.get());
+    }
+
+    pub fn set_error(&self, error: DOMErrorName) {
+        self.ready_state.set(IDBRequestReadyState::Done);
+        self.result.set(UndefinedValue());
+        self.error
+            .set(Some(&DOMException::new(&self.global(), error)));
+    }
+
+    pub fn error(&self) -> Option<DomRoot<DOMException>> {
+        self.error.get()
+    }
+
+    /// <https://w3c.github.io/IndexedDB/#fire-a-success-event>
+    pub fn fire_success(&self) {
+        let event = Event::new(
+            &self.global(),
+            atom!("success"),
+            EventBubbles::DoesNotBubble,
+            EventCancelable::NotCancelable,
+        );
+        self.dispatch_in_transaction(&event);
+    }
+
+    /// Fire an error event, returning whether it was canceled.
+    ///
+    /// <https://w3c.github.io/IndexedDB/#fire-an-error-event>
+    pub fn fire_error(&self) -> bool {
+        let event = Event::new(
+            &self.global(),
+            atom!("error"),
+            EventBubbles::Bubbles,
+            EventCancelable::Cancelable,
+        );
+        self.dispatch_in_transaction(&event);
+        event.DefaultPrevented()
+    }
+
+    /// Dispatch `event` at the request, with its transaction active during the dispatch.
+    pub fn dispatch_in_transaction(&self, event: &Event) {
+        let transaction = self.transaction.get();
+        let activated = transaction
+            .as_ref()
+            .map_or(false, |transaction| transaction.activate());
+        event.fire(self.upcast());
+        if activated {
+            if let Some(transaction) = transaction {
+                transaction.deactivate();
+            }
+        }
+    }
+}
+
+impl IDBRequestMethods for IDBRequest {
+    // https://w3c.github.io/IndexedDB/#dom-idbrequest-result
+    fn GetResult(&self, _cx: JSContext) -> Fallible<JSVal> {
+        if self.ready_state.get() == IDBRequestReadyState::Pending {
+            return Err(Error::InvalidState);
+        }
+        Ok(self.result.get())
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbrequest-error
+    fn GetError(&self) -> Fallible<Option<DomRoot<DOMException>>> {
+        if self.ready_state.get() == IDBRequestReadyState::Pending {
+            return Err(Error::InvalidState);
+        }
+        Ok(self.error.get())
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbrequest-source
+    fn GetSource(&self) -> Option<IDBObjectStoreOrIDBIndexOrIDBCursor> {
+        match self.source {
+            Some(RequestSource::ObjectStore(ref store)) => Some(
+                IDBObjectStoreOrIDBIndexOrIDBCursor::IDBObjectStore(DomRoot::from_ref(store)),
+            ),
+            Some(RequestSource::Index(ref index)) => Some(
+                IDBObjectStoreOrIDBIndexOrIDBCursor::IDBIndex(DomRoot::from_ref(index)),
+            ),
+            Some(RequestSource::Cursor(ref cursor)) => Some(
+                IDBObjectStoreOrIDBIndexOrIDBCursor::IDBCursor(DomRoot::from_ref(cursor)),
+            ),
+            None => None,
+        }
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbrequest-transaction
+    fn GetTransaction(&self) -> Option<DomRoot<IDBTransaction>> {
+        self.transaction.get()
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbrequest-readystate
+    fn ReadyState(&self) -> IDBRequestReadyState {
+        self.ready_state.get()
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbrequest-onsuccess
+    event_handler!(success, GetOnsuccess, SetOnsuccess);
+
+    // https://w3c.github.io/IndexedDB/#dom-idbrequest-onerror
+    event_handler!(error, GetOnerror, SetOnerror);
+}
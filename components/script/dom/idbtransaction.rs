@@ -0,0 +1,599 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::dom::bindings::cell::DomRefCell;
+use crate::dom::bindings::codegen::Bindings::IDBTransactionBinding;
+use crate::dom::bindings::codegen::Bindings::IDBTransactionBinding::{
+    IDBTransactionMethods, IDBTransactionMode,
+};
+use crate::dom::bindings::conversions::ToJSValConvertible;
+use crate::dom::bindings::error::{Error, ErrorResult, Fallible};
+use crate::dom::bindings::inheritance::Castable;
+use crate::dom::bindings::reflector::{reflect_dom_object, DomObject};
+use crate::dom::bindings::root::{Dom, DomRoot, MutNullableDom};
+use crate::dom::bindings::str::DOMString;
+use crate::dom::bindings::utils::set_dictionary_property;
+use crate::dom::domexception::{DOMErrorName, DOMException};
+use crate::dom::domstringlist::DOMStringList;
+use crate::dom::event::{Event, EventBubbles, EventCancelable};
+use crate::dom::eventtarget::EventTarget;
+use crate::dom::globalscope::GlobalScope;
+use crate::dom::idbcursor::IDBCursor;
+use crate::dom::idbdatabase::IDBDatabase;
+use crate::dom::idbobjectstore::IDBObjectStore;
+use crate::dom::idbrequest::IDBRequest;
+use crate::indexed_db::{backend_error_name, index_keys, key_injection_path};
+use crate::indexed_db::{key_type_to_jsval, record_value};
+use crate::script_runtime::JSContext;
+use dom_struct::dom_struct;
+use js::jsapi::{HandleValueArray, JS_NewArrayObject};
+use js::jsval::{NullValue, ObjectValue, UndefinedValue};
+use js::rust::MutableHandleValue;
+use net_traits::indexeddb_thread::{AsyncOperation, BackendError, DatabaseMetadata};
+use net_traits::indexeddb_thread::{IndexMetadata, IndexedDBKeyType, IndexedDBThreadMsg};
+use net_traits::indexeddb_thread::{IndexedDBRecord, IndexedDBTxnMode, OperationResult};
+use std::cell::Cell;
+use std::collections::{HashMap, VecDeque};
+
+/// <https://w3c.github.io/IndexedDB/#transaction-lifetime>
+#[derive(Clone, Copy, Debug, JSTraceable, MallocSizeOf, PartialEq)]
+enum TransactionState {
+    Active,
+    Inactive,
+    Committing,
+    Finished,
+}
+
+/// How the result of an operation becomes the result of its request.
+#[derive(JSTraceable, MallocSizeOf)]
+pub enum ResultKind {
+    /// The key a value was stored with.
+    Key,
+    /// The value of the first record read, with its primary key injected
+    /// at the given key path if the object store generated it.
+    Value(Option<String>),
+    /// The primary key of the first record read.
+    PrimaryKey,
+    /// The values of the records read, as an array.
+    Values(Option<String>),
+    /// The primary keys of the records read, as an array.
+    PrimaryKeys,
+    Count,
+    Undefined,
+    /// The record a cursor moved to.
+    Cursor,
+    /// The records of a new index, whose keys are computed and sent back to the backend.
+    IndexPopulation {
+        store: String,
+        index: IndexMetadata,
+    },
+}
+
+/// An operation whose result hasn't been handled yet.
+#[unrooted_must_root_lint::must_root]
+#[derive(JSTraceable, MallocSizeOf)]
+struct PendingOperation {
+    id: u64,
+    request: Option<Dom<IDBRequest>>,
+    cursor: Option<Dom<IDBCursor>>,
+    kind: ResultKind,
+}
+
+// https://w3c.github.io/IndexedDB/#transaction
+#[dom_struct]
+pub struct IDBTransaction {
+    eventtarget: EventTarget,
+    db: Dom<IDBDatabase>,
+    id: u64,
+    mode: IndexedDBTxnMode,
+    /// The names of the object stores in the scope of the transaction,
+    /// unless it is an upgrade transaction, whose scope is the whole database.
+    scope: Vec<String>,
+    state: Cell<TransactionState>,
+    error: MutNullableDom<DOMException>,
+    /// The operations sent to the backend, in the order their results will come back.
+    operations: DomRefCell<VecDeque<PendingOperation>>,
+    next_operation_id: Cell<u64>,
+    /// The object store handles created in this transaction.
+    object_stores: DomRefCell<HashMap<String, Dom<IDBObjectStore>>>,
+    /// The schema of the database before an upgrade transaction, to restore it on abort.
+    old_metadata: DomRefCell<Option<DatabaseMetadata>>,
+}
+
+impl IDBTransaction {
+    fn new_inherited(
+        db: &IDBDatabase,
+        id: u64,
+        mode: IndexedDBTxnMode,
+        scope: Vec<String>,
+    ) -> IDBTransaction {
+        IDBTransaction {
+            eventtarget: EventTarget::new_inherited(),
+            db: Dom::from_ref(db),
+            id,
+            mode,
+            scope,
+            state: Cell::new(TransactionState::Active),
+            error: Default::default(),
+            operations: DomRefCell::new(VecDeque::new()),
+            next_operation_id: Cell::new(0),
+            object_stores: DomRefCell::new(HashMap::new()),
+            old_metadata: DomRefCell::new(None),
+        }
+    }
+
+    pub fn new(
+        global: &GlobalScope,
+        db: &IDBDatabase,
+        id: u64,
+        mode: IndexedDBTxnMode,
+        scope: Vec<String>,
+    ) -> DomRoot<IDBTransaction> {
+        let transaction = reflect_dom_object(
+            Box::new(IDBTransaction::new_inherited(db, id, mode, scope)),
+            global,
+            IDBTransactionBinding::Wrap,
+        );
+        db.add_transaction(&transaction);
+        transaction
+    }
+
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn mode(&self) -> IndexedDBTxnMode {
+        self.mode
+    }
+
+    pub fn db(&self) -> DomRoot<IDBDatabase> {
+        DomRoot::from_ref(&*self.db)
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.state.get() == TransactionState::Active
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.state.get() == TransactionState::Finished
+    }
+
+    pub fn set_old_metadata(&self, metadata: DatabaseMetadata) {
+        *self.old_metadata.borrow_mut() = Some(metadata);
+    }
+
+    /// Make an inactive transaction active, returning whether it was inactive.
+    pub fn activate(&self) -> bool {
+        if self.state.get() != TransactionState::Inactive {
+            return false;
+        }
+        self.state.set(TransactionState::Active);
+        true
+    }
+
+    /// Make an active transaction inactive, committing it if it has nothing left to do.
+    pub fn deactivate(&self) {
+        if self.state.get() == TransactionState::Active {
+            self.state.set(TransactionState::Inactive);
+        }
+        self.maybe_commit();
+    }
+
+    /// <https://w3c.github.io/IndexedDB/#transaction-commit>
+    ///
+    /// An inactive transaction commits automatically once all its requests were handled.
+    fn maybe_commit(&self) {
+        if self.state.get() == TransactionState::Inactive && self.operations.borrow().is_empty() {
+            self.commit();
+        }
+    }
+
+    fn commit(&self) {
+        self.state.set(TransactionState::Committing);
+        self.db.send(IndexedDBThreadMsg::Commit {
+            connection: self.db.connection(),
+            transaction: self.id,
+        });
+    }
+
+    /// <https://w3c.github.io/IndexedDB/#abort-a-transaction>
+    ///
+    /// The requests and the transaction are told about the abort once the backend undid it.
+    pub fn abort(&self, error: Option<&DOMException>) {
+        if self.state.get() == TransactionState::Finished {
+            return;
+        }
+        self.error.set(error);
+        self.state.set(TransactionState::Finished);
+        self.db.send(IndexedDBThreadMsg::Abort {
+            connection: self.db.connection(),
+            transaction: self.id,
+        });
+    }
+
+    /// Queue an operation in the backend, whose result becomes the result of `request`.
+    pub fn queue_operation(
+        &self,
+        operation: AsyncOperation,
+        request: Option<&IDBRequest>,
+        kind: ResultKind,
+    ) {
+        self.push_operation(operation, request, None, kind);
+    }
+
+    /// Queue the iteration of `cursor`, which becomes the result of `request`.
+    pub fn queue_iteration(
+        &self,
+        operation: AsyncOperation,
+        request: &IDBRequest,
+        cursor: &IDBCursor,
+    ) {
+        self.push_operation(operation, Some(request), Some(cursor), ResultKind::Cursor);
+    }
+
+    fn push_operation(
+        &self,
+        operation: AsyncOperation,
+        request: Option<&IDBRequest>,
+        cursor: Option<&IDBCursor>,
+        kind: ResultKind,
+    ) {
+        let id = self.next_operation_id.get();
+        self.next_operation_id.set(id + 1);
+        self.db.send(IndexedDBThreadMsg::Request {
+            connection: self.db.connection(),
+            transaction: self.id,
+            request: id,
+            operation,
+        });
+        self.operations.borrow_mut().push_back(PendingOperation {
+            id,
+            request: request.map(Dom::from_ref),
+            cursor: cursor.map(Dom::from_ref),
+            kind,
+        });
+    }
+
+    #[allow(unrooted_must_root)]
+    fn take_operation(
+        &self,
+        id: u64,
+    ) -> Option<(
+        Option<DomRoot<IDBRequest>>,
+        Option<DomRoot<IDBCursor>>,
+        ResultKind,
+    )> {
+        let mut operations = self.operations.borrow_mut();
+        let position = operations.iter().position(|operation| operation.id == id)?;
+        let operation = operations.remove(position)?;
+        Some((
+            operation
+                .request
+                .map(|request| DomRoot::from_ref(&*request)),
+            operation.cursor.map(|cursor| DomRoot::from_ref(&*cursor)),
+            operation.kind,
+        ))
+    }
+
+    /// The object store handle for `name` in this transaction.
+    pub fn object_store_handle(&self, name: &str) -> DomRoot<IDBObjectStore> {
+        if let Some(store) = self.object_stores.borrow().get(name) {
+            return DomRoot::from_ref(&**store);
+        }
+        let store = IDBObjectStore::new(&self.global(), name, self);
+        self.object_stores
+            .borrow_mut()
+            .insert(name.to_owned(), Dom::from_ref(&*store));
+        store
+    }
+
+    pub fn forget_object_store_handle(&self, name: &str) {
+        if let Some(store) = self.object_stores.borrow_mut().remove(name) {
+            store.mark_deleted();
+        }
+    }
+
+    pub fn rename_object_store_handle(&self, name: &str, new_name: &str) {
+        let mut object_stores = self.object_stores.borrow_mut();
+        if let Some(store) = object_stores.remove(name) {
+            object_stores.insert(new_name.to_owned(), store);
+        }
+    }
+
+    /// Handle the result of an operation, firing a success or error event at its request.
+    pub fn handle_result(&self, id: u64, result: Result<OperationResult, BackendError>) {
+        // Once aborted, the requests only learn about the abort.
+        if self.state.get() == TransactionState::Finished {
+            return;
+        }
+        let (request, cursor, kind) = match self.take_operation(id) {
+            Some(operation) => operation,
+            None => {
+                warn!("Result for unknown IndexedDB request {}", id);
+                return;
+            },
+        };
+
+        match (kind, request) {
+            (ResultKind::IndexPopulation { store, index }, _) => {
+                if let Ok(OperationResult::Records(records)) = result {
+                    self.populate_index(store, index, records);
+                }
+            },
+            (kind, Some(request)) => {
+                let cx = self.global().get_cx();
+                rooted!(in(*cx) let mut value = UndefinedValue());
+                let result =
+                    result
+                        .map_err(|error| backend_error_name(&error))
+                        .and_then(|result| {
+                            self.convert_result(
+                                cx,
+                                kind,
+                                cursor.as_deref(),
+                                result,
+                                value.handle_mut(),
+                            )
+                        });
+                match result {
+                    Ok(()) => {
+                        request.set_result(value.handle());
+                        request.fire_success();
+                    },
+                    Err(error) => {
+                        request.set_error(error);
+                        if !request.fire_error() {
+                            self.abort(request.error().as_deref());
+                        }
+                    },
+                }
+            },
+            (_, None) => {},
+        }
+        self.maybe_commit();
+    }
+
+    #[allow(unsafe_code)]
+    fn convert_result(
+        &self,
+        cx: JSContext,
+        kind: ResultKind,
+        cursor: Option<&IDBCursor>,
+        result: OperationResult,
+        mut rval: MutableHandleValue,
+    ) -> Result<(), DOMErrorName> {
+        let global = self.global();
+        match (kind, result) {
+            (ResultKind::Key, OperationResult::Key(key)) => key_type_to_jsval(cx, &key, rval),
+            (ResultKind::Value(inject), OperationResult::Records(records)) => {
+                if let Some(record) = records.first() {
+                    record_value(&global, record, inject.as_ref().map(|path| &**path), rval)
+                        .map_err(|_| DOMErrorName::DataCloneError)?;
+                }
+            },
+            (ResultKind::PrimaryKey, OperationResult::Records(records)) => {
+                if let Some(record) = records.first() {
+                    key_type_to_jsval(cx, &record.primary_key, rval);
+                }
+            },
+            (ResultKind::Values(inject), OperationResult::Records(records)) => unsafe {
+                rooted!(in(*cx) let array = JS_NewArrayObject(*cx, &HandleValueArray::new()));
+                for (index, record) in records.iter().enumerate() {
+                    rooted!(in(*cx) let mut value = UndefinedValue());
+                    record_value(
+                        &global,
+                        record,
+                        inject.as_ref().map(|path| &**path),
+                        value.handle_mut(),
+                    )
+                    .map_err(|_| DOMErrorName::DataCloneError)?;
+                    let _ = set_dictionary_property(
+                        *cx,
+                        array.handle(),
+                        &index.to_string(),
+                        value.handle(),
+                    );
+                }
+                rval.set(ObjectValue(array.get()));
+            },
+            (ResultKind::PrimaryKeys, OperationResult::Records(records)) => {
+                let keys = records
+                    .into_iter()
+                    .map(|record| record.primary_key)
+                    .collect();
+                key_type_to_jsval(cx, &IndexedDBKeyType::Array(keys), rval);
+            },
+            (ResultKind::Count, OperationResult::Count(count)) => unsafe {
+                (count as f64).to_jsval(*cx, rval)
+            },
+            (ResultKind::Cursor, OperationResult::Records(records)) => {
+                let cursor = cursor.expect("Iteration without a cursor");
+                match records.into_iter().next() {
+                    Some(record) => {
+                        cursor
+                            .set_record(record)
+                            .map_err(|_| DOMErrorName::DataCloneError)?;
+                        unsafe { cursor.reflector().to_jsval(*cx, rval) };
+                    },
+                    None => {
+                        cursor.set_exhausted();
+                        rval.set(NullValue());
+                    },
+                }
+            },
+            (ResultKind::Undefined, _) => {},
+            (_, result) => warn!("Unexpected IndexedDB result {:?}", result),
+        }
+        Ok(())
+    }
+
+    /// Compute the keys that the records of a new index have in it.
+    fn populate_index(&self, store: String, index: IndexMetadata, records: Vec<IndexedDBRecord>) {
+        let global = self.global();
+        let cx = global.get_cx();
+        let inject = self
+            .db
+            .object_store_metadata(&store)
+            .and_then(|metadata| key_injection_path(&metadata));
+        let mut keys = vec![];
+        for record in records {
+            rooted!(in(*cx) let mut value = UndefinedValue());
+            if record_value(
+                &global,
+                &record,
+                inject.as_ref().map(|path| &**path),
+                value.handle_mut(),
+            )
+            .is_err()
+            {
+                continue;
+            }
+            if let Ok(index_keys) = index_keys(cx, value.handle(), &index) {
+                keys.push((record.primary_key, index_keys));
+            }
+        }
+        self.db.send(IndexedDBThreadMsg::PopulateIndex {
+            connection: self.db.connection(),
+            transaction: self.id,
+            store,
+            index: index.name,
+            keys,
+        });
+    }
+
+    /// The transaction committed or was aborted in the backend.
+    #[allow(unrooted_must_root)]
+    pub fn handle_finished(&self, result: Result<(), BackendError>) {
+        let global = self.global();
+        let aborted_by_script = self.state.get() == TransactionState::Finished;
+        self.state.set(TransactionState::Finished);
+        let error = match result {
+            Ok(()) => {
+                self.upcast::<EventTarget>().fire_event(atom!("complete"));
+                return;
+            },
+            Err(error) => error,
+        };
+
+        if !aborted_by_script {
+            let exception = DOMException::new(&global, backend_error_name(&error));
+            self.error.set(Some(&exception));
+        }
+        if let Some(metadata) = self.old_metadata.borrow_mut().take() {
+            self.db.set_metadata(metadata);
+        }
+        let requests: Vec<DomRoot<IDBRequest>> = self
+            .operations
+            .borrow_mut()
+            .drain(..)
+            .filter_map(|operation| {
+                operation
+                    .request
+                    .map(|request| DomRoot::from_ref(&*request))
+            })
+            .collect();
+        for request in requests {
+            request.set_error(DOMErrorName::AbortError);
+            request.fire_error();
+        }
+        let event = Event::new(
+            &global,
+            atom!("abort"),
+            EventBubbles::Bubbles,
+            EventCancelable::NotCancelable,
+        );
+        event.fire(self.upcast());
+    }
+}
+
+impl IDBTransactionMethods for IDBTransaction {
+    // https://w3c.github.io/IndexedDB/#dom-idbtransaction-objectstorenames
+    fn ObjectStoreNames(&self) -> DomRoot<DOMStringList> {
+        let names = match self.mode {
+            IndexedDBTxnMode::Versionchange => self.db.object_store_names(),
+            _ => self
+                .scope
+                .iter()
+                .map(|name| DOMString::from(name.clone()))
+                .collect(),
+        };
+        DOMStringList::new(&self.global(), names)
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbtransaction-mode
+    fn Mode(&self) -> IDBTransactionMode {
+        match self.mode {
+            IndexedDBTxnMode::Readonly => IDBTransactionMode::Readonly,
+            IndexedDBTxnMode::Readwrite => IDBTransactionMode::Readwrite,
+            IndexedDBTxnMode::Versionchange => IDBTransactionMode::Versionchange,
+        }
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbtransaction-db
+    fn Db(&self) -> DomRoot<IDBDatabase> {
+        self.db()
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbtransaction-error
+    fn GetError(&self) -> Option<DomRoot<DOMException>> {
+        self.error.get()
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbtransaction-objectstore
+    fn ObjectStore(&self, name: DOMString) -> Fallible<DomRoot<IDBObjectStore>> {
+        // Step 1.
+        if self.state.get() == TransactionState::Finished {
+            return Err(Error::InvalidState);
+        }
+
+        // Step 2.
+        let name = name.to_string();
+        let in_scope = match self.mode {
+            IndexedDBTxnMode::Versionchange => self.db.object_store_metadata(&name).is_some(),
+            _ => self.scope.contains(&name),
+        };
+        if !in_scope {
+            return Err(Error::NotFound);
+        }
+
+        // Step 3.
+        Ok(self.object_store_handle(&name))
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbtransaction-commit
+    fn Commit(&self) -> ErrorResult {
+        // Step 1.
+        if self.state.get() != TransactionState::Active {
+            return Err(Error::InvalidState);
+        }
+
+        // Step 2.
+        self.commit();
+        Ok(())
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbtransaction-abort
+    fn Abort(&self) -> ErrorResult {
+        // Step 1.
+        match self.state.get() {
+            TransactionState::Committing | TransactionState::Finished => {
+                return Err(Error::InvalidState);
+            },
+            _ => {},
+        }
+
+        // Steps 2-3.
+        self.abort(None);
+        Ok(())
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbtransaction-onabort
+    event_handler!(abort, GetOnabort, SetOnabort);
+
+    // https://w3c.github.io/IndexedDB/#dom-idbtransaction-oncomplete
+    event_handler!(complete, GetOncomplete, SetOncomplete);
+
+    // https://w3c.github.io/IndexedDB/#dom-idbtransaction-onerror
+    event_handler!(error, GetOnerror, SetOnerror);
+}
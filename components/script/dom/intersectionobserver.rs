@@ -0,0 +1,698 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::dom::bindings::callback::ExceptionHandling;
+use crate::dom::bindings::cell::DomRefCell;
+use crate::dom::bindings::codegen::Bindings::DocumentBinding::DocumentMethods;
+use crate::dom::bindings::codegen::Bindings::IntersectionObserverBinding::{
+    IntersectionObserverCallback, IntersectionObserverInit, IntersectionObserverMethods, Wrap,
+};
+use crate::dom::bindings::codegen::Bindings::WindowBinding::WindowMethods;
+use crate::dom::bindings::codegen::UnionTypes::{DoubleOrDoubleSequence, ElementOrDocument};
+use crate::dom::bindings::error::{Error, Fallible};
+use crate::dom::bindings::inheritance::Castable;
+use crate::dom::bindings::reflector::{reflect_dom_object, DomObject, Reflector};
+use crate::dom::bindings::root::{Dom, DomRoot};
+use crate::dom::bindings::str::DOMString;
+use crate::dom::bindings::utils::to_frozen_array;
+use crate::dom::document::Document;
+use crate::dom::domrectreadonly::DOMRectReadOnly;
+use crate::dom::element::Element;
+use crate::dom::intersectionobserverentry::IntersectionObserverEntry;
+use crate::dom::node::{Node, ShadowIncluding};
+use crate::dom::window::Window;
+use crate::script_runtime::JSContext;
+use cssparser::{Parser, ParserInput, Token};
+use dom_struct::dom_struct;
+use euclid::default::{Point2D, Rect, Size2D, Vector2D};
+use js::jsapi::Heap;
+use js::jsval::JSVal;
+use std::cell::Cell;
+use std::rc::Rc;
+
+/// One side of the root margin.
+/// <https://w3c.github.io/IntersectionObserver/#dom-intersectionobserver-rootmargin>
+#[derive(Clone, Copy, JSTraceable, MallocSizeOf)]
+enum MarginLength {
+    Pixels(f64),
+    /// A percentage of the width or height of the root intersection rectangle.
+    Percentage(f64),
+}
+
+impl MarginLength {
+    fn resolve(&self, basis: f64) -> f64 {
+        match *self {
+            MarginLength::Pixels(pixels) => pixels,
+            MarginLength::Percentage(percentage) => basis * percentage / 100.0,
+        }
+    }
+
+    fn serialize(&self) -> String {
+        match *self {
+            MarginLength::Pixels(pixels) => format!("{}px", pixels),
+            MarginLength::Percentage(percentage) => format!("{}%", percentage),
+        }
+    }
+}
+
+/// <https://w3c.github.io/IntersectionObserver/#dom-intersectionobserver-rootmargin>
+#[derive(Clone, Copy, JSTraceable, MallocSizeOf)]
+struct RootMargin {
+    top: MarginLength,
+    right: MarginLength,
+    bottom: MarginLength,
+    left: MarginLength,
+}
+
+impl RootMargin {
+    fn new(
+        top: MarginLength,
+        right: MarginLength,
+        bottom: MarginLength,
+        left: MarginLength,
+    ) -> RootMargin {
+        RootMargin {
+            top,
+            right,
+            bottom,
+            left,
+        }
+    }
+}
+
+/// <https://w3c.github.io/IntersectionObserver/#parse-a-root-margin>
+fn parse_root_margin(value: &str) -> Option<RootMargin> {
+    let mut input = ParserInput::new(value);
+    let mut parser = Parser::new(&mut input);
+    let mut margins = vec![];
+    loop {
+        let token = match parser.next() {
+            Ok(token) => token.clone(),
+            Err(_) => break,
+        };
+        let margin = match token {
+            Token::Dimension {
+                value, ref unit, ..
+            } => {
+                let pixels_per_unit = match_ignore_ascii_case! { &**unit,
+                    "px" => 1.,
+                    "in" => 96.,
+                    "cm" => 96. / 2.54,
+                    "mm" => 96. / 25.4,
+                    "q" => 96. / 101.6,
+                    "pt" => 96. / 72.,
+                    "pc" => 16.,
+                    _ => return None,
+                };
+                MarginLength::Pixels(value as f64 * pixels_per_unit)
+            },
+            Token::Percentage { unit_value, .. } => {
+                MarginLength::Percentage(unit_value as f64 * 100.)
+            },
+            _ => return None,
+        };
+        margins.push(margin);
+    }
+    // Missing sides are filled in like those of the CSS margin shorthand.
+    match margins[..] {
+        [] => {
+            let zero = MarginLength::Pixels(0.);
+            Some(RootMargin::new(zero, zero, zero, zero))
+        },
+        [all] => Some(RootMargin::new(all, all, all, all)),
+        [vertical, horizontal] => Some(RootMargin::new(vertical, horizontal, vertical, horizontal)),
+        [top, horizontal, bottom] => Some(RootMargin::new(top, horizontal, bottom, horizontal)),
+        [top, right, bottom, left] => Some(RootMargin::new(top, right, bottom, left)),
+        _ => None,
+    }
+}
+
+/// The intersection of two rectangles, which is empty rather than none
+/// when they are only adjacent.
+/// <https://w3c.github.io/IntersectionObserver/#edge-adjacent-intersection>
+fn edge_inclusive_intersection(a: &Rect<f64>, b: &Rect<f64>) -> Option<Rect<f64>> {
+    let min = Point2D::new(a.min_x().max(b.min_x()), a.min_y().max(b.min_y()));
+    let max = Point2D::new(a.max_x().min(b.max_x()), a.max_y().min(b.max_y()));
+    if max.x < min.x || max.y < min.y {
+        return None;
+    }
+    Some(Rect::new(min, Size2D::new(max.x - min.x, max.y - min.y)))
+}
+
+fn area(rect: &Rect<f64>) -> f64 {
+    rect.size.width * rect.size.height
+}
+
+/// The scroll offset of an element whose content is scrolled.
+fn scroll_offset(element: &Element) -> Vector2D<f64> {
+    let offset = element.upcast::<Node>().scroll_offset();
+    Vector2D::new(offset.x.abs() as f64, offset.y.abs() as f64)
+}
+
+/// Whether an element clips its content, which it then scrolls.
+/// The document element's overflow applies to the viewport instead.
+fn clips_content(element: &Element) -> bool {
+    element.potentially_scrollable() &&
+        element
+            .upcast::<Node>()
+            .owner_doc()
+            .GetDocumentElement()
+            .as_deref() !=
+            Some(element)
+}
+
+/// The border box of an element, in the coordinate space of the viewport of its document,
+/// accounting for the scrolling of its ancestors.
+fn client_border_box(element: &Element) -> Option<Rect<f64>> {
+    let node = element.upcast::<Node>();
+    let rect = node.bounding_content_box()?;
+    let mut rect = Rect::new(
+        Point2D::new(rect.origin.x.to_f64_px(), rect.origin.y.to_f64_px()),
+        Size2D::new(rect.size.width.to_f64_px(), rect.size.height.to_f64_px()),
+    );
+    for ancestor in node
+        .ancestors()
+        .filter_map(DomRoot::downcast::<Element>)
+        .filter(|ancestor| clips_content(ancestor))
+    {
+        rect = rect.translate(-scroll_offset(&ancestor));
+    }
+    let viewport = node.owner_doc().window().current_viewport().origin;
+    Some(rect.translate(-Vector2D::new(
+        viewport.x.to_f64_px(),
+        viewport.y.to_f64_px(),
+    )))
+}
+
+/// The padding box of an element, in the coordinate space of the viewport of its document.
+fn client_padding_box(element: &Element) -> Option<Rect<f64>> {
+    let border_box = client_border_box(element)?;
+    // The client rect is the padding box, relative to the border box.
+    let padding_box = element.upcast::<Node>().client_rect();
+    Some(Rect::new(
+        border_box.origin + Vector2D::new(padding_box.origin.x as f64, padding_box.origin.y as f64),
+        Size2D::new(
+            padding_box.size.width as f64,
+            padding_box.size.height as f64,
+        ),
+    ))
+}
+
+/// The viewport of a document, in its own coordinate space.
+fn client_viewport(document: &Document) -> Rect<f64> {
+    let size = document.window().current_viewport().size;
+    Rect::new(
+        Point2D::zero(),
+        Size2D::new(size.width.to_f64_px(), size.height.to_f64_px()),
+    )
+}
+
+/// What the intersection of a target with the root of an observer is made of.
+struct Intersection {
+    /// The border box of the target, in the coordinate space of its viewport.
+    target_rect: Rect<f64>,
+    /// The intersection, in the coordinate space of the viewport of the target,
+    /// if the target intersects the root.
+    intersection_rect: Option<Rect<f64>>,
+    /// The root intersection rectangle, in the coordinate space of the viewport of the root,
+    /// unless it can't be revealed to the target.
+    root_bounds: Option<Rect<f64>>,
+}
+
+/// The root of an observer, with which the intersection of every target is computed.
+#[unrooted_must_root_lint::must_root]
+#[derive(JSTraceable, MallocSizeOf)]
+enum IntersectionRoot {
+    /// <https://w3c.github.io/IntersectionObserver/#intersectionobserver-implicit-root>
+    Implicit,
+    Element(Dom<Element>),
+    Document(Dom<Document>),
+}
+
+/// <https://w3c.github.io/IntersectionObserver/#intersectionobserverregistration>
+#[unrooted_must_root_lint::must_root]
+#[derive(JSTraceable, MallocSizeOf)]
+struct IntersectionObserverRegistration {
+    target: Dom<Element>,
+    previous_threshold_index: Cell<i32>,
+    previous_is_intersecting: Cell<bool>,
+}
+
+// https://w3c.github.io/IntersectionObserver/#intersection-observer-interface
+#[dom_struct]
+pub struct IntersectionObserver {
+    reflector_: Reflector,
+    #[ignore_malloc_size_of = "can't measure Rc values"]
+    callback: Rc<IntersectionObserverCallback>,
+    root: IntersectionRoot,
+    root_margin: RootMargin,
+    thresholds: Vec<f64>,
+    #[ignore_malloc_size_of = "mozjs"]
+    frozen_thresholds: DomRefCell<Option<Heap<JSVal>>>,
+    /// <https://w3c.github.io/IntersectionObserver/#dom-intersectionobserver-queuedentries-slot>
+    queued_entries: DomRefCell<Vec<Dom<IntersectionObserverEntry>>>,
+    /// <https://w3c.github.io/IntersectionObserver/#dom-intersectionobserver-observationtargets-slot>
+    observation_targets: DomRefCell<Vec<IntersectionObserverRegistration>>,
+}
+
+impl IntersectionObserver {
+    #[allow(unrooted_must_root)]
+    fn new_inherited(
+        callback: Rc<IntersectionObserverCallback>,
+        root: IntersectionRoot,
+        root_margin: RootMargin,
+        thresholds: Vec<f64>,
+    ) -> IntersectionObserver {
+        IntersectionObserver {
+            reflector_: Reflector::new(),
+            callback,
+            root,
+            root_margin,
+            thresholds,
+            frozen_thresholds: DomRefCell::new(None),
+            queued_entries: DomRefCell::new(vec![]),
+            observation_targets: DomRefCell::new(vec![]),
+        }
+    }
+
+    /// <https://w3c.github.io/IntersectionObserver/#initialize-a-new-intersectionobserver>
+    #[allow(non_snake_case)]
+    #[allow(unrooted_must_root)]
+    pub fn Constructor(
+        window: &Window,
+        callback: Rc<IntersectionObserverCallback>,
+        options: &IntersectionObserverInit,
+    ) -> Fallible<DomRoot<IntersectionObserver>> {
+        // Steps 2-3.
+        let root_margin = match parse_root_margin(&options.rootMargin) {
+            Some(root_margin) => root_margin,
+            None => return Err(Error::Syntax),
+        };
+
+        // Steps 4-7.
+        let mut thresholds: Vec<f64> = match options.threshold {
+            Some(DoubleOrDoubleSequence::Double(threshold)) => vec![*threshold],
+            Some(DoubleOrDoubleSequence::DoubleSequence(ref thresholds)) => {
+                thresholds.iter().map(|threshold| **threshold).collect()
+            },
+            None => vec![0.],
+        };
+        if thresholds
+            .iter()
+            .any(|&threshold| threshold < 0. || threshold > 1.)
+        {
+            return Err(Error::Range(
+                "Thresholds must be in the range [0, 1]".to_owned(),
+            ));
+        }
+        thresholds.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        if thresholds.is_empty() {
+            thresholds.push(0.);
+        }
+
+        // Step 8.
+        let root = match options.root {
+            Some(ElementOrDocument::Element(ref element)) => {
+                IntersectionRoot::Element(Dom::from_ref(&**element))
+            },
+            Some(ElementOrDocument::Document(ref document)) => {
+                IntersectionRoot::Document(Dom::from_ref(&**document))
+            },
+            None => IntersectionRoot::Implicit,
+        };
+
+        Ok(reflect_dom_object(
+            Box::new(IntersectionObserver::new_inherited(
+                callback,
+                root,
+                root_margin,
+                thresholds,
+            )),
+            window,
+            Wrap,
+        ))
+    }
+
+    fn owner_document(&self) -> DomRoot<Document> {
+        DomRoot::downcast::<Window>(self.global())
+            .expect("IntersectionObserver is only exposed to windows")
+            .Document()
+    }
+
+    /// Invoke the callback with the entries queued since it was last invoked, if any.
+    /// <https://w3c.github.io/IntersectionObserver/#notify-intersection-observers-algo>, step 3.
+    pub fn notify(&self) {
+        let entries: Vec<DomRoot<IntersectionObserverEntry>> = self
+            .queued_entries
+            .borrow_mut()
+            .drain(..)
+            .map(|entry| DomRoot::from_ref(&*entry))
+            .collect();
+        if entries.is_empty() {
+            return;
+        }
+        let _ = self
+            .callback
+            .Call_(self, entries, self, ExceptionHandling::Report);
+    }
+
+    /// <https://w3c.github.io/IntersectionObserver/#queue-an-intersectionobserverentry>
+    fn queue_entry(&self, time: f64, intersection: &Intersection, target: &Element) {
+        let window = DomRoot::downcast::<Window>(self.global())
+            .expect("IntersectionObserver is only exposed to windows");
+        let rect = |rect: &Rect<f64>| {
+            DOMRectReadOnly::new(
+                window.upcast(),
+                rect.origin.x,
+                rect.origin.y,
+                rect.size.width,
+                rect.size.height,
+            )
+        };
+        let root_bounds = intersection.root_bounds.as_ref().map(|bounds| rect(bounds));
+        let intersection_rect = intersection.intersection_rect.unwrap_or_else(Rect::zero);
+        let entry = IntersectionObserverEntry::new(
+            &window,
+            time,
+            root_bounds.as_deref(),
+            &rect(&intersection.target_rect),
+            &rect(&intersection_rect),
+            intersection.intersection_rect.is_some(),
+            self.intersection_ratio(intersection),
+            target,
+        );
+        self.queued_entries
+            .borrow_mut()
+            .push(Dom::from_ref(&*entry));
+        self.owner_document().queue_intersection_observer_task();
+    }
+
+    /// <https://w3c.github.io/IntersectionObserver/#run-the-update-intersection-observations-steps>,
+    /// step 2.
+    pub fn update_intersection_observations(&self, time: f64) {
+        let targets: Vec<(DomRoot<Element>, i32, bool)> = self
+            .observation_targets
+            .borrow()
+            .iter()
+            .map(|registration| {
+                (
+                    DomRoot::from_ref(&*registration.target),
+                    registration.previous_threshold_index.get(),
+                    registration.previous_is_intersecting.get(),
+                )
+            })
+            .collect();
+        for (target, previous_threshold_index, previous_is_intersecting) in targets {
+            // Steps 2.2.1-9.
+            let intersection = self.compute_intersection(&target);
+            let is_intersecting = intersection.intersection_rect.is_some();
+
+            // Step 2.2.10.
+            let threshold_index = if is_intersecting {
+                let ratio = self.intersection_ratio(&intersection);
+                self.thresholds
+                    .iter()
+                    .position(|&threshold| threshold > ratio)
+                    .unwrap_or(self.thresholds.len()) as i32
+            } else {
+                0
+            };
+
+            // Steps 2.2.11-13.
+            if threshold_index == previous_threshold_index &&
+                is_intersecting == previous_is_intersecting
+            {
+                continue;
+            }
+
+            // Step 2.2.14.
+            self.queue_entry(time, &intersection, &target);
+
+            // Steps 2.2.15-16, unless the target was unobserved by the callback.
+            let targets = self.observation_targets.borrow();
+            if let Some(registration) = targets
+                .iter()
+                .find(|registration| *registration.target == *target)
+            {
+                registration.previous_threshold_index.set(threshold_index);
+                registration.previous_is_intersecting.set(is_intersecting);
+            }
+        }
+    }
+
+    /// <https://w3c.github.io/IntersectionObserver/#dom-intersectionobserverentry-intersectionratio>
+    fn intersection_ratio(&self, intersection: &Intersection) -> f64 {
+        let intersection_rect = match intersection.intersection_rect {
+            Some(intersection_rect) => intersection_rect,
+            None => return 0.,
+        };
+        let target_area = area(&intersection.target_rect);
+        if target_area > 0. {
+            area(&intersection_rect) / target_area
+        } else {
+            1.
+        }
+    }
+
+    /// The root intersection rectangle, in the coordinate space of the viewport of the root,
+    /// with the root margin applied.
+    /// <https://w3c.github.io/IntersectionObserver/#intersectionobserver-root-intersection-rectangle>
+    fn root_intersection_rectangle(
+        &self,
+        root: Option<&Element>,
+        document: &Document,
+    ) -> Option<Rect<f64>> {
+        let rect = match root {
+            Some(root) if clips_content(root) => client_padding_box(root)?,
+            Some(root) => client_border_box(root)?,
+            None => client_viewport(document),
+        };
+        let (width, height) = (rect.size.width, rect.size.height);
+        let top = self.root_margin.top.resolve(height);
+        let right = self.root_margin.right.resolve(width);
+        let bottom = self.root_margin.bottom.resolve(height);
+        let left = self.root_margin.left.resolve(width);
+        Some(Rect::new(
+            Point2D::new(rect.origin.x - left, rect.origin.y - top),
+            Size2D::new(
+                (width + left + right).max(0.),
+                (height + top + bottom).max(0.),
+            ),
+        ))
+    }
+
+    /// Steps 2.2.1-9 of the update intersection observations steps, for a target.
+    /// <https://w3c.github.io/IntersectionObserver/#compute-the-intersection>
+    fn compute_intersection(&self, target: &Element) -> Intersection {
+        let not_intersecting = |target_rect, root_bounds| Intersection {
+            target_rect,
+            intersection_rect: None,
+            root_bounds,
+        };
+
+        // Step 2.2.3.
+        let target_rect = match client_border_box(target) {
+            Some(target_rect) => target_rect,
+            None => return not_intersecting(Rect::zero(), None),
+        };
+
+        // Steps 2.2.4-5, a target outside the document of an explicit root,
+        // or outside the root element, never intersects it.
+        let target_document = target.upcast::<Node>().owner_doc();
+        let root_element = match self.root {
+            IntersectionRoot::Element(ref root) => {
+                let root_document = root.upcast::<Node>().owner_doc();
+                let root_bounds = self.root_intersection_rectangle(Some(&**root), &root_document);
+                if root_document != target_document ||
+                    &**root == target ||
+                    !root
+                        .upcast::<Node>()
+                        .is_shadow_including_inclusive_ancestor_of(target.upcast())
+                {
+                    return not_intersecting(target_rect, root_bounds);
+                }
+                Some(DomRoot::from_ref(&**root))
+            },
+            IntersectionRoot::Document(ref root) => {
+                if **root != *target_document {
+                    let root_bounds = self.root_intersection_rectangle(None, root);
+                    return not_intersecting(target_rect, root_bounds);
+                }
+                None
+            },
+            IntersectionRoot::Implicit => None,
+        };
+
+        // Step 2.2.6, clip the target by every ancestor up to the root, crossing into
+        // the documents of ancestor browsing contexts for the implicit root.
+        let mut rect = Some(target_rect);
+        let mut document = target_document;
+        // How far the viewport of the target is from that of the current document.
+        let mut offset = Vector2D::zero();
+        let mut container = DomRoot::from_ref(target);
+        loop {
+            let ancestors = container
+                .upcast::<Node>()
+                .inclusive_ancestors(ShadowIncluding::Yes)
+                .skip(1)
+                .filter_map(DomRoot::downcast::<Element>);
+            for ancestor in ancestors {
+                if root_element.as_deref() == Some(&*ancestor) {
+                    break;
+                }
+                if clips_content(&ancestor) {
+                    rect = rect.and_then(|rect| {
+                        edge_inclusive_intersection(&rect, &client_padding_box(&ancestor)?)
+                    });
+                }
+            }
+            if root_element.is_some() {
+                break;
+            }
+            if let IntersectionRoot::Document(_) = self.root {
+                break;
+            }
+            // The implicit root is the document of the top-level browsing context,
+            // whose geometry can only be used when it is in this event loop and same-origin.
+            let frame_element = document
+                .browsing_context()
+                .and_then(|context| context.frame_element().map(DomRoot::from_ref));
+            let frame_element = match frame_element {
+                Some(frame_element) => frame_element,
+                None => break,
+            };
+            let parent_document = frame_element.upcast::<Node>().owner_doc();
+            if !parent_document
+                .origin()
+                .same_origin_domain(document.origin())
+            {
+                break;
+            }
+            let frame_rect = match client_padding_box(&frame_element) {
+                Some(frame_rect) => frame_rect,
+                None => return not_intersecting(target_rect, None),
+            };
+            rect = rect
+                .and_then(|rect| edge_inclusive_intersection(&rect, &client_viewport(&document)))
+                .map(|rect| rect.translate(frame_rect.origin.to_vector()));
+            offset += frame_rect.origin.to_vector();
+            document = parent_document;
+            container = frame_element;
+        }
+
+        // A target in a document whose ancestor can't be reached, for the implicit root,
+        // is only clipped by the viewport of its document, which it isn't told about.
+        let reached_root = root_element.is_some() ||
+            match self.root {
+                IntersectionRoot::Implicit => document
+                    .browsing_context()
+                    .map_or(true, |context| context.parent().is_none()),
+                _ => true,
+            };
+        let root_bounds = if reached_root {
+            self.root_intersection_rectangle(root_element.as_deref(), &document)
+        } else {
+            Some(client_viewport(&document))
+        };
+
+        // Steps 2.2.6-7, intersect with the root and map back to the viewport of the target.
+        let intersection_rect = match (rect, root_bounds) {
+            (Some(rect), Some(root_bounds)) => {
+                edge_inclusive_intersection(&rect, &root_bounds).map(|rect| rect.translate(-offset))
+            },
+            _ => None,
+        };
+
+        Intersection {
+            target_rect,
+            intersection_rect,
+            root_bounds: if reached_root { root_bounds } else { None },
+        }
+    }
+}
+
+impl IntersectionObserverMethods for IntersectionObserver {
+    // https://w3c.github.io/IntersectionObserver/#dom-intersectionobserver-root
+    fn GetRoot(&self) -> Option<ElementOrDocument> {
+        match self.root {
+            IntersectionRoot::Implicit => None,
+            IntersectionRoot::Element(ref element) => {
+                Some(ElementOrDocument::Element(DomRoot::from_ref(&**element)))
+            },
+            IntersectionRoot::Document(ref document) => {
+                Some(ElementOrDocument::Document(DomRoot::from_ref(&**document)))
+            },
+        }
+    }
+
+    // https://w3c.github.io/IntersectionObserver/#dom-intersectionobserver-rootmargin
+    fn RootMargin(&self) -> DOMString {
+        let margin = &self.root_margin;
+        DOMString::from(format!(
+            "{} {} {} {}",
+            margin.top.serialize(),
+            margin.right.serialize(),
+            margin.bottom.serialize(),
+            margin.left.serialize()
+        ))
+    }
+
+    // https://w3c.github.io/IntersectionObserver/#dom-intersectionobserver-thresholds
+    fn Thresholds(&self, cx: JSContext) -> JSVal {
+        if let Some(thresholds) = &*self.frozen_thresholds.borrow() {
+            return thresholds.get();
+        }
+        let thresholds = to_frozen_array(self.thresholds.as_slice(), cx);
+        let heap_val = Heap::default();
+        heap_val.set(thresholds);
+        *self.frozen_thresholds.borrow_mut() = Some(heap_val);
+        thresholds
+    }
+
+    // https://w3c.github.io/IntersectionObserver/#dom-intersectionobserver-observe
+    fn Observe(&self, target: &Element) {
+        let mut targets = self.observation_targets.borrow_mut();
+        // Step 1.
+        if targets
+            .iter()
+            .any(|registration| &*registration.target == target)
+        {
+            return;
+        }
+        // Steps 2-4.
+        targets.push(IntersectionObserverRegistration {
+            target: Dom::from_ref(target),
+            previous_threshold_index: Cell::new(-1),
+            previous_is_intersecting: Cell::new(false),
+        });
+        if targets.len() == 1 {
+            self.owner_document().add_intersection_observer(self);
+        }
+    }
+
+    // https://w3c.github.io/IntersectionObserver/#dom-intersectionobserver-unobserve
+    fn Unobserve(&self, target: &Element) {
+        let mut targets = self.observation_targets.borrow_mut();
+        let len = targets.len();
+        targets.retain(|registration| &*registration.target != target);
+        if len > 0 && targets.is_empty() {
+            self.owner_document().remove_intersection_observer(self);
+        }
+    }
+
+    // https://w3c.github.io/IntersectionObserver/#dom-intersectionobserver-disconnect
+    fn Disconnect(&self) {
+        self.observation_targets.borrow_mut().clear();
+        self.owner_document().remove_intersection_observer(self);
+    }
+
+    // https://w3c.github.io/IntersectionObserver/#dom-intersectionobserver-takerecords
+    fn TakeRecords(&self) -> Vec<DomRoot<IntersectionObserverEntry>> {
+        self.queued_entries
+            .borrow_mut()
+            .drain(..)
+            .map(|entry| DomRoot::from_ref(&*entry))
+            .collect()
+    }
+}
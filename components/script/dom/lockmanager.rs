@@ -0,0 +1,382 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::dom::abortsignal::AbortSignal;
+use crate::dom::bindings::callback::ExceptionHandling;
+use crate::dom::bindings::cell::DomRefCell;
+use crate::dom::bindings::codegen::Bindings::DocumentBinding::DocumentMethods;
+use crate::dom::bindings::codegen::Bindings::LockManagerBinding::{
+    LockGrantedCallback, LockInfo, LockManagerMethods, LockManagerSnapshot, LockMode, LockOptions,
+    Wrap,
+};
+use crate::dom::bindings::codegen::Bindings::WindowBinding::WindowMethods;
+use crate::dom::bindings::error::Error;
+use crate::dom::bindings::inheritance::Castable;
+use crate::dom::bindings::refcounted::{Trusted, TrustedPromise};
+use crate::dom::bindings::reflector::{reflect_dom_object, DomObject, Reflector};
+use crate::dom::bindings::root::{Dom, DomRoot};
+use crate::dom::bindings::str::DOMString;
+use crate::dom::globalscope::GlobalScope;
+use crate::dom::lock::Lock;
+use crate::dom::promise::Promise;
+use crate::dom::window::Window;
+use crate::realms::{enter_realm, InRealm};
+use crate::script_runtime::JSContext as SafeJSContext;
+use crate::streams::{promise_from_call_result, react_to_promise, resolve_with_promise};
+use crate::task_source::dom_manipulation::DOMManipulationTaskSource;
+use crate::task_source::TaskSource;
+use dom_struct::dom_struct;
+use ipc_channel::ipc;
+use ipc_channel::router::ROUTER;
+use js::rust::HandleValue;
+use script_traits::{ScriptMsg, WebLockEvent, WebLockId, WebLockInfo, WebLockMode};
+use script_traits::{WebLockRequest, WebLocksSnapshot};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// A request for a lock, from when it is made until the promise it returned settles.
+/// <https://wicg.github.io/web-locks/#lock-request>
+#[unrooted_must_root_lint::must_root]
+#[derive(JSTraceable, MallocSizeOf)]
+struct PendingLockRequest {
+    name: DOMString,
+    mode: LockMode,
+    #[ignore_malloc_size_of = "Rc"]
+    callback: Rc<LockGrantedCallback>,
+    /// The promise returned by `request`, which is also the released promise of the lock.
+    #[ignore_malloc_size_of = "Rc"]
+    promise: Rc<Promise>,
+    /// Whether the lock was granted, after which aborting the request does nothing.
+    granted: bool,
+}
+
+/// What to do once the promise returned by the callback of a granted lock settles.
+#[unrooted_must_root_lint::must_root]
+#[derive(Clone, JSTraceable, MallocSizeOf)]
+struct HeldLock {
+    manager: Dom<LockManager>,
+    id: WebLockId,
+    /// The promise of the result of the callback.
+    #[ignore_malloc_size_of = "Rc"]
+    waiting: Rc<Promise>,
+}
+
+impl HeldLock {
+    /// <https://wicg.github.io/web-locks/#request-a-lock>, step 10 of the
+    /// steps once the lock is granted.
+    fn waiting_settled(&self, _cx: SafeJSContext, _value: HandleValue) {
+        let request = self.manager.requests.borrow_mut().remove(&self.id);
+        // A stolen lock was already released, and its promise rejected.
+        if let Some(request) = request {
+            self.manager.release(self.id);
+            resolve_with_promise(&request.promise, &self.waiting);
+        }
+    }
+}
+
+// https://wicg.github.io/web-locks/#api-lock-manager
+#[dom_struct]
+pub struct LockManager {
+    reflector_: Reflector,
+    /// The requests made through this lock manager, until their promise settles.
+    requests: DomRefCell<HashMap<WebLockId, PendingLockRequest>>,
+}
+
+impl LockManager {
+    fn new_inherited() -> LockManager {
+        LockManager {
+            reflector_: Reflector::new(),
+            requests: DomRefCell::new(HashMap::new()),
+        }
+    }
+
+    pub fn new(global: &GlobalScope) -> DomRoot<LockManager> {
+        reflect_dom_object(Box::new(LockManager::new_inherited()), global, Wrap)
+    }
+
+    /// <https://wicg.github.io/web-locks/#api-lock-manager-request>
+    fn request_lock(
+        &self,
+        name: DOMString,
+        options: &LockOptions,
+        callback: Rc<LockGrantedCallback>,
+        comp: InRealm,
+    ) -> Rc<Promise> {
+        let global = self.global();
+        let promise = Promise::new_in_current_realm(&global, comp);
+
+        // Step 3.
+        if let Some(window) = global.downcast::<Window>() {
+            if !window.Document().is_fully_active() {
+                promise.reject_error(Error::InvalidState);
+                return promise;
+            }
+        }
+
+        // Step 4.
+        let origin = global.origin().immutable().clone();
+        if !origin.is_tuple() {
+            promise.reject_error(Error::Security);
+            return promise;
+        }
+
+        // Step 5.
+        if name.starts_with('-') {
+            promise.reject_error(Error::NotSupported);
+            return promise;
+        }
+
+        // Step 6.
+        if options.steal && options.ifAvailable {
+            promise.reject_error(Error::NotSupported);
+            return promise;
+        }
+
+        // Step 7.
+        if options.steal && options.mode != LockMode::Exclusive {
+            promise.reject_error(Error::NotSupported);
+            return promise;
+        }
+
+        // Step 8.
+        if let Some(ref signal) = options.signal {
+            if options.steal || options.ifAvailable {
+                promise.reject_error(Error::NotSupported);
+                return promise;
+            }
+            // Step 9.
+            if signal.aborted() {
+                promise.reject_error(Error::Abort);
+                return promise;
+            }
+        }
+
+        // Step 10, the lock manager in the constellation handles the request,
+        // and tells us what happened to it.
+        let id = WebLockId::new();
+        let (sender, receiver) = ipc::channel().expect("ipc channel failure");
+        let manager = Trusted::new(self);
+        let task_source = global.dom_manipulation_task_source();
+        let canceller = global.task_canceller(DOMManipulationTaskSource::NAME);
+        ROUTER.add_route(
+            receiver.to_opaque(),
+            Box::new(move |message| {
+                let event: WebLockEvent = match message.to() {
+                    Ok(event) => event,
+                    Err(e) => return warn!("Error receiving a WebLockEvent: {:?}", e),
+                };
+                let manager = manager.clone();
+                let _ = task_source.queue_with_canceller(
+                    task!(handle_web_lock_event: move || {
+                        manager.root().handle_event(id, event);
+                    }),
+                    &canceller,
+                );
+            }),
+        );
+
+        self.requests.borrow_mut().insert(
+            id,
+            PendingLockRequest {
+                name: name.clone(),
+                mode: options.mode,
+                callback,
+                promise: promise.clone(),
+                granted: false,
+            },
+        );
+        if let Some(ref signal) = options.signal {
+            signal.add_lock_request(self, id);
+        }
+
+        let request = WebLockRequest {
+            id,
+            origin,
+            name: String::from(name),
+            mode: match options.mode {
+                LockMode::Shared => WebLockMode::Shared,
+                LockMode::Exclusive => WebLockMode::Exclusive,
+            },
+            client_id: global.pipeline_id().to_string(),
+            if_available: options.ifAvailable,
+            steal: options.steal,
+            sender,
+        };
+        let _ = global
+            .script_to_constellation_chan()
+            .send(ScriptMsg::RequestWebLock(request));
+        promise
+    }
+
+    /// Tell the lock manager that a held lock was released, or a pending request aborted.
+    fn release(&self, id: WebLockId) {
+        let global = self.global();
+        let origin = global.origin().immutable().clone();
+        let _ = global
+            .script_to_constellation_chan()
+            .send(ScriptMsg::ReleaseWebLock(origin, id));
+    }
+
+    #[allow(unrooted_must_root)]
+    fn handle_event(&self, id: WebLockId, event: WebLockEvent) {
+        let global = self.global();
+        let _ac = enter_realm(&*global);
+        match event {
+            // https://wicg.github.io/web-locks/#process-the-lock-request-queue, step 2.4.
+            WebLockEvent::Granted => {
+                let (callback, lock) = {
+                    let mut requests = self.requests.borrow_mut();
+                    let request = match requests.get_mut(&id) {
+                        Some(request) => request,
+                        // The request was aborted while the lock was being granted.
+                        None => return,
+                    };
+                    request.granted = true;
+                    let lock = Lock::new(&global, request.name.clone(), request.mode);
+                    (request.callback.clone(), lock)
+                };
+                let result = callback.Call__(Some(&*lock), ExceptionHandling::Rethrow);
+                let waiting = promise_from_call_result(&global, result);
+                react_to_promise(
+                    &waiting,
+                    HeldLock {
+                        manager: Dom::from_ref(self),
+                        id,
+                        waiting: waiting.clone(),
+                    },
+                    HeldLock::waiting_settled,
+                    HeldLock::waiting_settled,
+                );
+            },
+            // https://wicg.github.io/web-locks/#request-a-lock, step 6.
+            WebLockEvent::NotAvailable => {
+                let request = match self.requests.borrow_mut().remove(&id) {
+                    Some(request) => request,
+                    None => return,
+                };
+                let result = request.callback.Call__(None, ExceptionHandling::Rethrow);
+                let waiting = promise_from_call_result(&global, result);
+                resolve_with_promise(&request.promise, &waiting);
+            },
+            // https://wicg.github.io/web-locks/#request-a-lock, step 5.
+            WebLockEvent::Stolen => {
+                if let Some(request) = self.requests.borrow_mut().remove(&id) {
+                    request.promise.reject_error(Error::Abort);
+                }
+            },
+        }
+    }
+
+    /// Abort a request that wasn't granted yet, once its signal is aborted.
+    /// <https://wicg.github.io/web-locks/#api-lock-manager-request>, step 11.
+    pub fn abort_request(&self, id: WebLockId) {
+        let request = {
+            let mut requests = self.requests.borrow_mut();
+            match requests.get(&id) {
+                Some(request) if !request.granted => requests.remove(&id),
+                _ => None,
+            }
+        };
+        if let Some(request) = request {
+            self.release(id);
+            request.promise.reject_error(Error::Abort);
+        }
+    }
+}
+
+impl LockManagerMethods for LockManager {
+    // https://wicg.github.io/web-locks/#dom-lockmanager-request
+    fn Request(
+        &self,
+        name: DOMString,
+        callback: Rc<LockGrantedCallback>,
+        comp: InRealm,
+    ) -> Rc<Promise> {
+        let options = LockOptions {
+            mode: LockMode::Exclusive,
+            ifAvailable: false,
+            steal: false,
+            signal: None,
+        };
+        self.request_lock(name, &options, callback, comp)
+    }
+
+    // https://wicg.github.io/web-locks/#dom-lockmanager-request-name-options-callback
+    fn Request_(
+        &self,
+        name: DOMString,
+        options: &LockOptions,
+        callback: Rc<LockGrantedCallback>,
+        comp: InRealm,
+    ) -> Rc<Promise> {
+        self.request_lock(name, options, callback, comp)
+    }
+
+    // https://wicg.github.io/web-locks/#dom-lockmanager-query
+    fn Query(&self, comp: InRealm) -> Rc<Promise> {
+        let global = self.global();
+        let promise = Promise::new_in_current_realm(&global, comp);
+
+        // Step 2.
+        if let Some(window) = global.downcast::<Window>() {
+            if !window.Document().is_fully_active() {
+                promise.reject_error(Error::InvalidState);
+                return promise;
+            }
+        }
+
+        // Step 3.
+        let origin = global.origin().immutable().clone();
+        if !origin.is_tuple() {
+            promise.reject_error(Error::Security);
+            return promise;
+        }
+
+        // Step 4.
+        let (sender, receiver) = ipc::channel().expect("ipc channel failure");
+        let mut trusted_promise = Some(TrustedPromise::new(promise.clone()));
+        let task_source = global.dom_manipulation_task_source();
+        let canceller = global.task_canceller(DOMManipulationTaskSource::NAME);
+        ROUTER.add_route(
+            receiver.to_opaque(),
+            Box::new(move |message| {
+                let promise = match trusted_promise.take() {
+                    Some(promise) => promise,
+                    None => return,
+                };
+                let snapshot: WebLocksSnapshot = match message.to() {
+                    Ok(snapshot) => snapshot,
+                    Err(e) => return warn!("Error receiving a WebLocksSnapshot: {:?}", e),
+                };
+                let _ = task_source.queue_with_canceller(
+                    task!(resolve_web_locks_query: move || {
+                        let promise = promise.root();
+                        let _ac = enter_realm(&*promise);
+                        promise.resolve_native(&LockManagerSnapshot {
+                            held: Some(snapshot.held.iter().map(lock_info).collect()),
+                            pending: Some(snapshot.pending.iter().map(lock_info).collect()),
+                        });
+                    }),
+                    &canceller,
+                );
+            }),
+        );
+        let _ = global
+            .script_to_constellation_chan()
+            .send(ScriptMsg::QueryWebLocks(origin, sender));
+        promise
+    }
+}
+
+/// <https://wicg.github.io/web-locks/#dictdef-lockinfo>
+fn lock_info(info: &WebLockInfo) -> LockInfo {
+    LockInfo {
+        name: Some(DOMString::from(info.name.clone())),
+        mode: Some(match info.mode {
+            WebLockMode::Shared => LockMode::Shared,
+            WebLockMode::Exclusive => LockMode::Exclusive,
+        }),
+        clientId: Some(DOMString::from(info.client_id.clone())),
+    }
+}
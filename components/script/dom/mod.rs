@@ -209,6 +209,8 @@ pub mod types {
     include!(concat!(env!("OUT_DIR"), "/InterfaceTypes.rs"));
 }
 
+pub mod abortcontroller;
+pub mod abortsignal;
 pub mod abstractworker;
 pub mod abstractworkerglobalscope;
 pub mod activation;
@@ -239,14 +241,22 @@ pub mod bluetoothremotegattdescriptor;
 pub mod bluetoothremotegattserver;
 pub mod bluetoothremotegattservice;
 pub mod bluetoothuuid;
+pub mod broadcastchannel;
+pub mod cache;
+pub mod cachestorage;
 pub mod canvasgradient;
 pub mod canvaspattern;
 pub mod canvasrenderingcontext2d;
+pub mod caretposition;
 pub mod cdatasection;
 pub mod channelmergernode;
 pub mod channelsplitternode;
 pub mod characterdata;
 pub mod client;
+pub mod clients;
+pub mod clipboard;
+pub mod clipboardevent;
+pub mod clipboarditem;
 pub mod closeevent;
 pub mod comment;
 pub mod compositionevent;
@@ -254,6 +264,7 @@ pub mod console;
 pub mod constantsourcenode;
 mod create;
 pub mod crypto;
+pub mod cryptokey;
 pub mod css;
 pub mod cssconditionrule;
 pub mod cssfontfacerule;
@@ -273,6 +284,7 @@ pub mod csssupportsrule;
 pub mod cssviewportrule;
 pub mod customelementregistry;
 pub mod customevent;
+pub mod datatransfer;
 pub mod dedicatedworkerglobalscope;
 pub mod dissimilaroriginlocation;
 pub mod dissimilaroriginwindow;
@@ -302,6 +314,7 @@ pub mod extendableevent;
 pub mod extendablemessageevent;
 pub mod fakexrdevice;
 pub mod fakexrinputcontroller;
+pub mod fetchevent;
 pub mod file;
 pub mod filelist;
 pub mod filereader;
@@ -400,11 +413,26 @@ pub mod htmltrackelement;
 pub mod htmlulistelement;
 pub mod htmlunknownelement;
 pub mod htmlvideoelement;
+pub mod idbcursor;
+pub mod idbcursorwithvalue;
+pub mod idbdatabase;
+pub mod idbfactory;
+pub mod idbindex;
+pub mod idbkeyrange;
+pub mod idbobjectstore;
+pub mod idbopendbrequest;
+pub mod idbrequest;
+pub mod idbtransaction;
+pub mod idbversionchangeevent;
 pub mod identityhub;
 pub mod imagedata;
 pub mod inputevent;
+pub mod intersectionobserver;
+pub mod intersectionobserverentry;
 pub mod keyboardevent;
 pub mod location;
+pub mod lock;
+pub mod lockmanager;
 pub mod mediadevices;
 pub mod mediaelementaudiosourcenode;
 pub mod mediaerror;
@@ -464,7 +492,16 @@ pub mod promiserejectionevent;
 pub mod radionodelist;
 pub mod range;
 pub mod raredata;
+pub mod readablebytestreamcontroller;
+pub mod readablestream;
+pub mod readablestreambyobreader;
+pub mod readablestreambyobrequest;
+pub mod readablestreamdefaultcontroller;
+pub mod readablestreamdefaultreader;
 pub mod request;
+pub mod resizeobserver;
+pub mod resizeobserverentry;
+pub mod resizeobserversize;
 pub mod response;
 pub mod rtcicecandidate;
 pub mod rtcpeerconnection;
@@ -472,19 +509,25 @@ pub mod rtcpeerconnectioniceevent;
 pub mod rtcsessiondescription;
 pub mod rtctrackevent;
 pub mod screen;
+pub mod securitypolicyviolationevent;
+pub mod selection;
 pub mod serviceworker;
 pub mod serviceworkercontainer;
 pub mod serviceworkerglobalscope;
 pub mod serviceworkerregistration;
 pub mod servoparser;
 pub mod shadowroot;
+pub mod sharedworker;
+pub mod sharedworkerglobalscope;
 pub mod stereopannernode;
 pub mod storage;
 pub mod storageevent;
+pub mod storagemanager;
 pub mod stylepropertymapreadonly;
 pub mod stylesheet;
 pub mod stylesheetlist;
 pub mod submitevent;
+pub mod subtlecrypto;
 pub mod svgelement;
 pub mod svggraphicselement;
 pub mod svgsvgelement;
@@ -509,6 +552,8 @@ pub mod touch;
 pub mod touchevent;
 pub mod touchlist;
 pub mod trackevent;
+pub mod transformstream;
+pub mod transformstreamdefaultcontroller;
 pub mod transitionevent;
 pub mod treewalker;
 pub mod uievent;
@@ -561,6 +606,9 @@ pub mod workerlocation;
 pub mod workernavigator;
 pub mod worklet;
 pub mod workletglobalscope;
+pub mod writablestream;
+pub mod writablestreamdefaultcontroller;
+pub mod writablestreamdefaultwriter;
 pub mod xmldocument;
 pub mod xmlhttprequest;
 pub mod xmlhttprequesteventtarget;
@@ -125,6 +125,13 @@ impl MutationObserver {
 
     /// <https://dom.spec.whatwg.org/#queueing-a-mutation-record>
     pub fn queue_a_mutation_record(target: &Node, attr_type: Mutation) {
+        // Edits are undone by reverting the mutations they made.
+        target
+            .owner_doc()
+            .edit_history()
+            .borrow_mut()
+            .note_mutation(target, &attr_type);
+
         if !target.global().as_window().get_exists_mut_observer() {
             return;
         }
@@ -9,8 +9,10 @@ use crate::dom::bindings::reflector::{reflect_dom_object, DomObject, Reflector};
 use crate::dom::bindings::root::{DomRoot, MutNullableDom};
 use crate::dom::bindings::str::DOMString;
 use crate::dom::bluetooth::Bluetooth;
+use crate::dom::clipboard::Clipboard;
 use crate::dom::gamepadlist::GamepadList;
 use crate::dom::gpu::GPU;
+use crate::dom::lockmanager::LockManager;
 use crate::dom::mediadevices::MediaDevices;
 use crate::dom::mediasession::MediaSession;
 use crate::dom::mimetypearray::MimeTypeArray;
@@ -19,6 +21,7 @@ use crate::dom::permissions::Permissions;
 use crate::dom::pluginarray::PluginArray;
 use crate::dom::promise::Promise;
 use crate::dom::serviceworkercontainer::ServiceWorkerContainer;
+use crate::dom::storagemanager::StorageManager;
 use crate::dom::window::Window;
 use crate::dom::xr::XR;
 use crate::realms::InRealm;
@@ -38,6 +41,9 @@ pub struct Navigator {
     permissions: MutNullableDom<Permissions>,
     mediasession: MutNullableDom<MediaSession>,
     gpu: MutNullableDom<GPU>,
+    locks: MutNullableDom<LockManager>,
+    storage: MutNullableDom<StorageManager>,
+    clipboard: MutNullableDom<Clipboard>,
 }
 
 impl Navigator {
@@ -54,6 +60,9 @@ impl Navigator {
             permissions: Default::default(),
             mediasession: Default::default(),
             gpu: Default::default(),
+            locks: Default::default(),
+            storage: Default::default(),
+            clipboard: Default::default(),
         }
     }
 
@@ -64,6 +73,11 @@ impl Navigator {
             NavigatorBinding::Wrap,
         )
     }
+
+    /// The service worker container, if script has used it.
+    pub fn service_worker_container(&self) -> Option<DomRoot<ServiceWorkerContainer>> {
+        self.service_worker.get()
+    }
 }
 
 impl NavigatorMethods for Navigator {
@@ -171,6 +185,21 @@ impl NavigatorMethods for Navigator {
             .or_init(|| Permissions::new(&self.global()))
     }
 
+    // https://wicg.github.io/web-locks/#dom-navigatorlocks-locks
+    fn Locks(&self) -> DomRoot<LockManager> {
+        self.locks.or_init(|| LockManager::new(&self.global()))
+    }
+
+    // https://storage.spec.whatwg.org/#dom-navigatorstorage-storage
+    fn Storage(&self) -> DomRoot<StorageManager> {
+        self.storage.or_init(|| StorageManager::new(&self.global()))
+    }
+
+    // https://w3c.github.io/clipboard-apis/#dom-navigator-clipboard
+    fn Clipboard(&self) -> DomRoot<Clipboard> {
+        self.clipboard.or_init(|| Clipboard::new(&self.global()))
+    }
+
     // https://w3c.github.io/webvr/spec/1.1/#navigator-getvrdisplays-attribute
     fn GetVRDisplays(&self, comp: InRealm) -> Rc<Promise> {
         let promise = Promise::new_in_current_realm(&self.global(), comp);
@@ -42,7 +42,7 @@ use crate::dom::globalscope::GlobalScope;
 use crate::dom::htmlbodyelement::HTMLBodyElement;
 use crate::dom::htmlcanvaselement::{HTMLCanvasElement, LayoutHTMLCanvasElementHelpers};
 use crate::dom::htmlcollection::HTMLCollection;
-use crate::dom::htmlelement::HTMLElement;
+use crate::dom::htmlelement::{ContentEditableState, HTMLElement};
 use crate::dom::htmliframeelement::{HTMLIFrameElement, HTMLIFrameElementLayoutMethods};
 use crate::dom::htmlimageelement::{HTMLImageElement, LayoutHTMLImageElementHelpers};
 use crate::dom::htmlinputelement::{HTMLInputElement, LayoutHTMLInputElementHelpers};
@@ -59,7 +59,7 @@ use crate::dom::raredata::NodeRareData;
 use crate::dom::shadowroot::{LayoutShadowRootHelpers, ShadowRoot};
 use crate::dom::stylesheetlist::StyleSheetListOwner;
 use crate::dom::svgsvgelement::{LayoutSVGSVGElementHelpers, SVGSVGElement};
-use crate::dom::text::Text;
+use crate::dom::text::{LayoutTextHelpers, Text};
 use crate::dom::virtualmethods::{vtable_for, VirtualMethods};
 use crate::dom::window::Window;
 use crate::script_runtime::JSContext;
@@ -565,6 +565,46 @@ impl Node {
         self.type_id() == NodeTypeId::DocumentType
     }
 
+    /// <https://w3c.github.io/editing/docs/execCommand/#editing-host>
+    pub fn is_editing_host(&self) -> bool {
+        let element = match self.downcast::<HTMLElement>() {
+            Some(element) => element,
+            None => return false,
+        };
+        match element.content_editable_state() {
+            ContentEditableState::True | ContentEditableState::PlaintextOnly => true,
+            ContentEditableState::False | ContentEditableState::Inherit => self
+                .GetParentNode()
+                .and_then(|parent| DomRoot::downcast::<Document>(parent))
+                .map_or(false, |document| document.design_mode()),
+        }
+    }
+
+    /// <https://w3c.github.io/editing/docs/execCommand/#editable>
+    pub fn is_editable(&self) -> bool {
+        if self.is_editing_host() {
+            return false;
+        }
+        if let Some(element) = self.downcast::<HTMLElement>() {
+            if element.content_editable_state() == ContentEditableState::False {
+                return false;
+            }
+        }
+        self.GetParentNode().map_or(false, |parent| {
+            parent.is_editing_host() || parent.is_editable()
+        })
+    }
+
+    /// <https://w3c.github.io/editing/docs/execCommand/#editing-host-of>
+    pub fn editing_host(&self) -> Option<DomRoot<Element>> {
+        if !self.is_editing_host() && !self.is_editable() {
+            return None;
+        }
+        self.inclusive_ancestors(ShadowIncluding::No)
+            .find(|node| node.is_editing_host())
+            .and_then(DomRoot::downcast::<Element>)
+    }
+
     pub fn get_flag(&self, flag: NodeFlags) -> bool {
         self.flags.get().contains(flag)
     }
@@ -669,7 +709,7 @@ impl Node {
         parent.ancestors().any(|ancestor| &*ancestor == self)
     }
 
-    fn is_shadow_including_inclusive_ancestor_of(&self, node: &Node) -> bool {
+    pub fn is_shadow_including_inclusive_ancestor_of(&self, node: &Node) -> bool {
         node.inclusive_ancestors(ShadowIncluding::Yes)
             .any(|ancestor| &*ancestor == self)
     }
@@ -1376,6 +1416,10 @@ impl LayoutNodeHelpers for LayoutDom<Node> {
 
     #[allow(unsafe_code)]
     fn selection(&self) -> Option<Range<usize>> {
+        if let Some(text) = self.downcast::<Text>() {
+            return unsafe { text.selection_for_layout() };
+        }
+
         if let Some(area) = self.downcast::<HTMLTextAreaElement>() {
             return unsafe { area.selection_for_layout() };
         }
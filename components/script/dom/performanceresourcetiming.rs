@@ -55,13 +55,10 @@ pub struct PerformanceResourceTiming {
     decoded_body_size: u64, //size in octets
 }
 
-// TODO(#21269): next_hop
 // TODO(#21264): worker_start
 // TODO(#21258): fetch_start
 // TODO(#21259): domain_lookup_start
 // TODO(#21260): domain_lookup_end
-// TODO(#21261): connect_start
-// TODO(#21262): connect_end
 impl PerformanceResourceTiming {
     pub fn new_inherited(
         url: ServoUrl,
@@ -117,7 +114,12 @@ impl PerformanceResourceTiming {
                 resource_timing.response_end as f64 - resource_timing.start_time as f64,
             ),
             initiator_type: initiator_type,
-            next_hop: next_hop,
+            next_hop: next_hop.or_else(|| {
+                resource_timing
+                    .next_hop_protocol
+                    .clone()
+                    .map(DOMString::from)
+            }),
             worker_start: 0.,
             redirect_start: resource_timing.redirect_start as f64,
             redirect_end: resource_timing.redirect_end as f64,
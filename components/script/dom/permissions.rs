@@ -286,6 +286,26 @@ impl PermissionAlgorithm for Permissions {
     fn permission_revoke(_descriptor: &PermissionDescriptor, _status: &PermissionStatus) {}
 }
 
+/// The state of a permission as last queried, requested or revoked, without prompting
+/// the user, which is `Prompt` if it never was. Meant for features only exposed to
+/// secure contexts, for which the non-secure context prompt of
+/// `get_descriptor_permission_state` doesn't apply.
+pub fn get_stored_permission_state(
+    permission_name: PermissionName,
+    global: &GlobalScope,
+) -> PermissionState {
+    if pref!(dom.permissions.testing.allowed_in_nonsecure_contexts) {
+        return PermissionState::Granted;
+    }
+    global
+        .as_window()
+        .permission_state_invocation_results()
+        .borrow()
+        .get(&permission_name.to_string())
+        .cloned()
+        .unwrap_or(PermissionState::Prompt)
+}
+
 // https://w3c.github.io/permissions/#permission-state
 pub fn get_descriptor_permission_state(
     permission_name: PermissionName,
@@ -389,5 +409,9 @@ fn allowed_in_nonsecure_contexts(permission_name: &PermissionName) -> bool {
         PermissionName::Bluetooth => false,
         // https://storage.spec.whatwg.org/#dom-permissionname-persistent-storage
         PermissionName::Persistent_storage => false,
+        // https://w3c.github.io/clipboard-apis/#dom-permissionname-clipboard-read
+        PermissionName::Clipboard_read => false,
+        // https://w3c.github.io/clipboard-apis/#dom-permissionname-clipboard-write
+        PermissionName::Clipboard_write => false,
     }
 }
@@ -14,7 +14,7 @@ use crate::dom::bindings::error::{Error, ErrorResult, Fallible};
 use crate::dom::bindings::inheritance::Castable;
 use crate::dom::bindings::inheritance::{CharacterDataTypeId, NodeTypeId};
 use crate::dom::bindings::reflector::{reflect_dom_object, Reflector};
-use crate::dom::bindings::root::{Dom, DomRoot, MutDom};
+use crate::dom::bindings::root::{Dom, DomRoot, MutDom, MutNullableDom};
 use crate::dom::bindings::str::DOMString;
 use crate::dom::bindings::trace::JSTraceable;
 use crate::dom::bindings::weakref::{WeakRef, WeakRefVec};
@@ -24,6 +24,7 @@ use crate::dom::documentfragment::DocumentFragment;
 use crate::dom::element::Element;
 use crate::dom::htmlscriptelement::HTMLScriptElement;
 use crate::dom::node::{Node, ShadowIncluding, UnbindContext};
+use crate::dom::selection::Selection;
 use crate::dom::text::Text;
 use crate::dom::window::Window;
 use dom_struct::dom_struct;
@@ -37,6 +38,8 @@ pub struct Range {
     reflector_: Reflector,
     start: BoundaryPoint,
     end: BoundaryPoint,
+    /// The selection this range is the range of, if any.
+    associated_selection: MutNullableDom<Selection>,
 }
 
 impl Range {
@@ -50,6 +53,7 @@ impl Range {
             reflector_: Reflector::new(),
             start: BoundaryPoint::new(start_container, start_offset),
             end: BoundaryPoint::new(end_container, end_offset),
+            associated_selection: MutNullableDom::new(None),
         }
     }
 
@@ -89,8 +93,23 @@ impl Range {
         Ok(Range::new_with_doc(&document))
     }
 
+    pub fn associated_selection(&self) -> Option<DomRoot<Selection>> {
+        self.associated_selection.get()
+    }
+
+    pub fn set_associated_selection(&self, selection: Option<&Selection>) {
+        self.associated_selection.set(selection);
+    }
+
+    /// Lets the selection this range is associated with know that a boundary point moved.
+    fn report_change(&self) {
+        if let Some(selection) = self.associated_selection.get() {
+            selection.range_changed();
+        }
+    }
+
     // https://dom.spec.whatwg.org/#contained
-    fn contains(&self, node: &Node) -> bool {
+    pub fn contains(&self, node: &Node) -> bool {
         match (
             bp_position(node, 0, &self.StartContainer(), self.StartOffset()),
             bp_position(node, node.len(), &self.EndContainer(), self.EndOffset()),
@@ -174,6 +193,7 @@ impl Range {
             }
         }
         self.start.set(node, offset);
+        self.report_change();
     }
 
     // https://dom.spec.whatwg.org/#concept-range-bp-set
@@ -189,6 +209,7 @@ impl Range {
             }
         }
         self.end.set(node, offset);
+        self.report_change();
     }
 
     // https://dom.spec.whatwg.org/#dom-range-comparepointnode-offset
@@ -1059,7 +1080,7 @@ impl PartialEq for BoundaryPoint {
 }
 
 // https://dom.spec.whatwg.org/#concept-range-bp-position
-fn bp_position(a_node: &Node, a_offset: u32, b_node: &Node, b_offset: u32) -> Option<Ordering> {
+pub fn bp_position(a_node: &Node, a_offset: u32, b_node: &Node, b_offset: u32) -> Option<Ordering> {
     if a_node as *const Node == b_node as *const Node {
         // Step 1.
         return Some(a_offset.cmp(&b_offset));
@@ -1143,9 +1164,11 @@ impl WeakRangeVec {
                 }
                 if &range.start.node == child {
                     range.start.set(context.parent, offset);
+                    range.report_change();
                 }
                 if &range.end.node == child {
                     range.end.set(context.parent, offset);
+                    range.report_change();
                 }
             });
 
@@ -1170,9 +1193,11 @@ impl WeakRangeVec {
                 }
                 if &range.start.node == node {
                     range.start.set(sibling, range.StartOffset() + length);
+                    range.report_change();
                 }
                 if &range.end.node == node {
                     range.end.set(sibling, range.EndOffset() + length);
+                    range.report_change();
                 }
             });
 
@@ -1213,9 +1238,11 @@ impl WeakRangeVec {
 
                 if move_start {
                     range.start.set(child, new_offset);
+                    range.report_change();
                 }
                 if move_end {
                     range.end.set(child, new_offset);
+                    range.report_change();
                 }
             });
         }
@@ -1274,9 +1301,11 @@ impl WeakRangeVec {
 
                 if move_start {
                     range.start.set(sibling, start_offset - offset);
+                    range.report_change();
                 }
                 if move_end {
                     range.end.set(sibling, end_offset - offset);
+                    range.report_change();
                 }
             });
         }
@@ -1290,9 +1319,11 @@ impl WeakRangeVec {
                 let range = entry.root().unwrap();
                 if &range.start.node == node && offset == range.StartOffset() {
                     range.start.set_offset(offset + 1);
+                    range.report_change();
                 }
                 if &range.end.node == node && offset == range.EndOffset() {
                     range.end.set_offset(offset + 1);
+                    range.report_change();
                 }
             });
         }
@@ -1305,10 +1336,12 @@ impl WeakRangeVec {
                 let start_offset = range.StartOffset();
                 if &range.start.node == node && start_offset > offset {
                     range.start.set_offset(f(start_offset));
+                    range.report_change();
                 }
                 let end_offset = range.EndOffset();
                 if &range.end.node == node && end_offset > offset {
                     range.end.set_offset(f(end_offset));
+                    range.report_change();
                 }
             });
         }
@@ -0,0 +1,754 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::dom::bindings::cell::DomRefCell;
+use crate::dom::bindings::codegen::Bindings::ReadableByteStreamControllerBinding;
+use crate::dom::bindings::codegen::Bindings::ReadableByteStreamControllerBinding::ReadableByteStreamControllerMethods;
+use crate::dom::bindings::codegen::UnionTypes::ReadableStreamDefaultControllerOrReadableByteStreamController as ReadableStreamController;
+use crate::dom::bindings::error::{Error, ErrorResult, Fallible};
+use crate::dom::bindings::reflector::{reflect_dom_object, DomObject, Reflector};
+use crate::dom::bindings::root::{Dom, DomRoot, MutNullableDom};
+use crate::dom::promise::Promise;
+use crate::dom::readablestream::{ReadableStream, ReadableStreamState, UnderlyingSource};
+use crate::dom::readablestreambyobrequest::ReadableStreamBYOBRequest;
+use crate::dom::readablestreamdefaultreader::resolve_read_request;
+use crate::realms::enter_realm;
+use crate::script_runtime::JSContext;
+use crate::streams::{create_uint8_array, create_view, element_size, error_to_jsval};
+use crate::streams::{react_to_promise, resolved_promise, same_buffer, view_byte_length};
+use crate::streams::{view_byte_offset, view_bytes, view_type, write_into_view};
+use dom_struct::dom_struct;
+use js::jsapi::{Heap, JSObject, Type};
+use js::jsval::UndefinedValue;
+use js::rust::{CustomAutoRooterGuard, HandleObject, HandleValue, MutableHandleValue};
+use js::typedarray::ArrayBufferView;
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::mem;
+use std::rc::Rc;
+
+/// A pending read into a view that was given to a BYOB reader.
+/// <https://streams.spec.whatwg.org/#pull-into-descriptor>
+///
+/// The bytes of the stream are copied into the view as they arrive, rather
+/// than transferring its buffer back and forth between the reader and the
+/// underlying source.
+#[derive(JSTraceable, MallocSizeOf)]
+struct PullIntoDescriptor {
+    #[ignore_malloc_size_of = "Defined in rust-mozjs"]
+    view: Box<Heap<*mut JSObject>>,
+    /// The offset of the view into its buffer.
+    byte_offset: usize,
+    byte_length: usize,
+    bytes_filled: usize,
+    element_size: usize,
+}
+
+// https://streams.spec.whatwg.org/#rbs-controller-class
+#[dom_struct]
+pub struct ReadableByteStreamController {
+    reflector_: Reflector,
+    stream: Dom<ReadableStream>,
+    /// The chunks that no read asked for yet.
+    queue: DomRefCell<VecDeque<Vec<u8>>>,
+    queue_total_size: Cell<usize>,
+    started: Cell<bool>,
+    close_requested: Cell<bool>,
+    pull_again: Cell<bool>,
+    pulling: Cell<bool>,
+    strategy_hwm: f64,
+    pending_pull_intos: DomRefCell<VecDeque<PullIntoDescriptor>>,
+    byob_request: MutNullableDom<ReadableStreamBYOBRequest>,
+    /// The source of the bytes, which is cleared once no more bytes can be enqueued.
+    source: DomRefCell<Option<UnderlyingSource>>,
+}
+
+impl ReadableByteStreamController {
+    #[allow(unrooted_must_root)]
+    fn new_inherited(
+        stream: &ReadableStream,
+        source: UnderlyingSource,
+        strategy_hwm: f64,
+    ) -> ReadableByteStreamController {
+        ReadableByteStreamController {
+            reflector_: Reflector::new(),
+            stream: Dom::from_ref(stream),
+            queue: DomRefCell::new(VecDeque::new()),
+            queue_total_size: Cell::new(0),
+            started: Cell::new(false),
+            close_requested: Cell::new(false),
+            pull_again: Cell::new(false),
+            pulling: Cell::new(false),
+            strategy_hwm,
+            pending_pull_intos: DomRefCell::new(VecDeque::new()),
+            byob_request: Default::default(),
+            source: DomRefCell::new(Some(source)),
+        }
+    }
+
+    /// <https://streams.spec.whatwg.org/#set-up-readable-byte-stream-controller>
+    #[allow(unrooted_must_root)]
+    pub fn setup(
+        stream: &ReadableStream,
+        source: UnderlyingSource,
+        strategy_hwm: f64,
+    ) -> Fallible<DomRoot<ReadableByteStreamController>> {
+        let global = stream.global();
+
+        // Steps 1-11.
+        let controller = reflect_dom_object(
+            Box::new(ReadableByteStreamController::new_inherited(
+                stream,
+                source.clone(),
+                strategy_hwm,
+            )),
+            &*global,
+            ReadableByteStreamControllerBinding::Wrap,
+        );
+        stream.set_byte_controller(&controller);
+
+        // Steps 12-15.
+        let start_promise = source.start(
+            &global,
+            ReadableStreamController::ReadableByteStreamController(controller.clone()),
+        )?;
+        react_to_promise(
+            &start_promise,
+            Dom::from_ref(&*controller),
+            ReadableByteStreamController::start_fulfilled,
+            ReadableByteStreamController::start_rejected,
+        );
+        Ok(controller)
+    }
+
+    fn start_fulfilled(controller: &Dom<Self>, _cx: JSContext, _v: HandleValue) {
+        controller.started.set(true);
+        controller.call_pull_if_needed();
+    }
+
+    fn start_rejected(controller: &Dom<Self>, _cx: JSContext, error: HandleValue) {
+        controller.error(error);
+    }
+
+    /// <https://streams.spec.whatwg.org/#readable-byte-stream-controller-call-pull-if-needed>
+    #[allow(unrooted_must_root)]
+    fn call_pull_if_needed(&self) {
+        // Steps 1-2.
+        if !self.should_call_pull() {
+            return;
+        }
+
+        // Step 3.
+        if self.pulling.get() {
+            self.pull_again.set(true);
+            return;
+        }
+
+        // Steps 5-7.
+        let source = match self.source.borrow().clone() {
+            Some(source) => source,
+            None => return,
+        };
+        self.pulling.set(true);
+        let pull_promise = source.pull(
+            &self.global(),
+            ReadableStreamController::ReadableByteStreamController(DomRoot::from_ref(self)),
+        );
+        react_to_promise(
+            &pull_promise,
+            Dom::from_ref(self),
+            ReadableByteStreamController::pull_fulfilled,
+            ReadableByteStreamController::pull_rejected,
+        );
+    }
+
+    fn pull_fulfilled(controller: &Dom<Self>, _cx: JSContext, _v: HandleValue) {
+        controller.pulling.set(false);
+        if controller.pull_again.get() {
+            controller.pull_again.set(false);
+            controller.call_pull_if_needed();
+        }
+    }
+
+    fn pull_rejected(controller: &Dom<Self>, _cx: JSContext, error: HandleValue) {
+        controller.error(error);
+    }
+
+    /// <https://streams.spec.whatwg.org/#readable-byte-stream-controller-should-call-pull>
+    fn should_call_pull(&self) -> bool {
+        // Steps 2-4.
+        if !self.can_close_or_enqueue() || !self.started.get() {
+            return false;
+        }
+
+        // Steps 5-6.
+        if self.stream.num_read_requests() > 0 {
+            return true;
+        }
+
+        // Steps 7-9.
+        self.desired_size().map_or(false, |size| size > 0.)
+    }
+
+    /// <https://streams.spec.whatwg.org/#readable-byte-stream-controller-clear-algorithms>
+    fn clear_algorithms(&self) {
+        *self.source.borrow_mut() = None;
+    }
+
+    /// <https://streams.spec.whatwg.org/#readable-byte-stream-controller-invalidate-byob-request>
+    fn invalidate_byob_request(&self) {
+        if let Some(request) = self.byob_request.get() {
+            request.invalidate();
+        }
+        self.byob_request.set(None);
+    }
+
+    /// <https://streams.spec.whatwg.org/#readable-byte-stream-controller-clear-pending-pull-intos>
+    fn clear_pending_pull_intos(&self) {
+        self.invalidate_byob_request();
+        self.pending_pull_intos.borrow_mut().clear();
+    }
+
+    fn reset_queue(&self) {
+        self.queue.borrow_mut().clear();
+        self.queue_total_size.set(0);
+    }
+
+    /// <https://streams.spec.whatwg.org/#readable-byte-stream-controller-get-desired-size>
+    pub fn desired_size(&self) -> Option<f64> {
+        match self.stream.state() {
+            ReadableStreamState::Errored => None,
+            ReadableStreamState::Closed => Some(0.),
+            ReadableStreamState::Readable => {
+                Some(self.strategy_hwm - self.queue_total_size.get() as f64)
+            },
+        }
+    }
+
+    /// Whether bytes can be enqueued into the stream or the stream can be closed.
+    pub fn can_close_or_enqueue(&self) -> bool {
+        !self.close_requested.get() && self.stream.is_readable()
+    }
+
+    /// <https://streams.spec.whatwg.org/#readable-byte-stream-controller-close>
+    pub fn close(&self) -> ErrorResult {
+        // Step 2.
+        if !self.can_close_or_enqueue() {
+            return Ok(());
+        }
+
+        // Step 3.
+        if self.queue_total_size.get() > 0 {
+            self.close_requested.set(true);
+            return Ok(());
+        }
+
+        // Step 4.
+        let partially_filled = self
+            .pending_pull_intos
+            .borrow()
+            .front()
+            .map_or(false, |descriptor| {
+                descriptor.bytes_filled % descriptor.element_size != 0
+            });
+        if partially_filled {
+            let global = self.global();
+            let cx = global.get_cx();
+            rooted!(in(*cx) let mut error = UndefinedValue());
+            let message = "The stream closed in the middle of an element of a view".to_owned();
+            error_to_jsval(
+                cx,
+                &global,
+                Error::Type(message.clone()),
+                error.handle_mut(),
+            );
+            self.error(error.handle());
+            return Err(Error::Type(message));
+        }
+
+        // The pending reads into views end along with the stream, as their
+        // sources would otherwise have to respond to them with no bytes.
+        self.invalidate_byob_request();
+        let pending_pull_intos =
+            mem::replace(&mut *self.pending_pull_intos.borrow_mut(), VecDeque::new());
+        for descriptor in pending_pull_intos {
+            self.commit_pull_into_descriptor(&descriptor, true);
+        }
+
+        // Steps 5-6.
+        self.clear_algorithms();
+        self.stream.close();
+        Ok(())
+    }
+
+    /// Enqueue a copy of the bytes of a chunk.
+    /// <https://streams.spec.whatwg.org/#readable-byte-stream-controller-enqueue>
+    pub fn enqueue_bytes(&self, bytes: Vec<u8>) {
+        let cx = self.global().get_cx();
+
+        // Step 9.
+        self.invalidate_byob_request();
+
+        // Steps 10-12.
+        if let Some(reader) = self.stream.default_reader() {
+            if reader.num_read_requests() > 0 {
+                rooted!(in(*cx) let mut chunk = UndefinedValue());
+                if create_uint8_array(cx, &bytes, chunk.handle_mut()).is_ok() {
+                    reader.fulfill_read_request(cx, chunk.handle(), false);
+                }
+            } else {
+                self.enqueue_chunk_to_queue(bytes);
+            }
+        } else if self.stream.byob_reader().is_some() {
+            self.enqueue_chunk_to_queue(bytes);
+            self.process_pull_into_descriptors_using_queue();
+        } else {
+            self.enqueue_chunk_to_queue(bytes);
+        }
+
+        // Step 13.
+        self.call_pull_if_needed();
+    }
+
+    /// <https://streams.spec.whatwg.org/#readable-byte-stream-controller-enqueue-chunk-to-queue>
+    fn enqueue_chunk_to_queue(&self, bytes: Vec<u8>) {
+        self.queue_total_size
+            .set(self.queue_total_size.get() + bytes.len());
+        self.queue.borrow_mut().push_back(bytes);
+    }
+
+    /// <https://streams.spec.whatwg.org/#readable-byte-stream-controller-error>
+    pub fn error(&self, error: HandleValue) {
+        // Step 2.
+        if !self.stream.is_readable() {
+            return;
+        }
+
+        // Steps 3-6.
+        self.clear_pending_pull_intos();
+        self.reset_queue();
+        self.clear_algorithms();
+        self.stream.error(error);
+    }
+
+    /// <https://streams.spec.whatwg.org/#readable-byte-stream-controller-handle-queue-drain>
+    fn handle_queue_drain(&self) {
+        if self.queue_total_size.get() == 0 && self.close_requested.get() {
+            self.clear_algorithms();
+            self.stream.close();
+        } else {
+            self.call_pull_if_needed();
+        }
+    }
+
+    /// Copy as many bytes from the queue into the view of `descriptor` as fit,
+    /// returning whether the view now holds at least one whole element.
+    /// <https://streams.spec.whatwg.org/#readable-byte-stream-controller-fill-pull-into-descriptor-from-queue>
+    fn fill_pull_into_descriptor_from_queue(&self, descriptor: &mut PullIntoDescriptor) -> bool {
+        let cx = self.global().get_cx();
+
+        // Steps 1-7.
+        let max_bytes_to_copy = self
+            .queue_total_size
+            .get()
+            .min(descriptor.byte_length - descriptor.bytes_filled);
+        let max_bytes_filled = descriptor.bytes_filled + max_bytes_to_copy;
+        let max_aligned_bytes = max_bytes_filled - max_bytes_filled % descriptor.element_size;
+        let current_aligned_bytes =
+            descriptor.bytes_filled - descriptor.bytes_filled % descriptor.element_size;
+        let (mut total_bytes_to_copy_remaining, ready) =
+            if max_aligned_bytes > current_aligned_bytes {
+                (max_aligned_bytes - descriptor.bytes_filled, true)
+            } else {
+                (max_bytes_to_copy, false)
+            };
+
+        // Step 8.
+        rooted!(in(*cx) let view = descriptor.view.get());
+        let mut queue = self.queue.borrow_mut();
+        while total_bytes_to_copy_remaining > 0 {
+            let head = queue
+                .front_mut()
+                .expect("Filled a view from an empty queue");
+            let bytes_to_copy = total_bytes_to_copy_remaining.min(head.len());
+            write_into_view(
+                cx,
+                view.handle(),
+                descriptor.bytes_filled,
+                &head[..bytes_to_copy],
+            );
+            if bytes_to_copy == head.len() {
+                queue.pop_front();
+            } else {
+                head.drain(..bytes_to_copy);
+            }
+            self.queue_total_size
+                .set(self.queue_total_size.get() - bytes_to_copy);
+            descriptor.bytes_filled += bytes_to_copy;
+            total_bytes_to_copy_remaining -= bytes_to_copy;
+        }
+
+        ready
+    }
+
+    /// <https://streams.spec.whatwg.org/#readable-byte-stream-controller-process-pull-into-descriptors-using-queue>
+    fn process_pull_into_descriptors_using_queue(&self) {
+        while self.queue_total_size.get() > 0 {
+            let mut descriptor = match self.pending_pull_intos.borrow_mut().pop_front() {
+                Some(descriptor) => descriptor,
+                None => return,
+            };
+            if self.fill_pull_into_descriptor_from_queue(&mut descriptor) {
+                self.invalidate_byob_request();
+                self.commit_pull_into_descriptor(&descriptor, false);
+            } else {
+                self.pending_pull_intos.borrow_mut().push_front(descriptor);
+                return;
+            }
+        }
+    }
+
+    /// Fulfill the read into the view of `descriptor` with the bytes it was filled with.
+    /// <https://streams.spec.whatwg.org/#readable-byte-stream-controller-commit-pull-into-descriptor>
+    fn commit_pull_into_descriptor(&self, descriptor: &PullIntoDescriptor, done: bool) {
+        let cx = self.global().get_cx();
+        rooted!(in(*cx) let view = descriptor.view.get());
+        rooted!(in(*cx) let mut filled_view = UndefinedValue());
+        let _ = create_view(
+            cx,
+            view.handle(),
+            view_type(view.handle()),
+            descriptor.byte_offset,
+            descriptor.bytes_filled,
+            filled_view.handle_mut(),
+        );
+        if let Some(reader) = self.stream.byob_reader() {
+            if reader.num_read_into_requests() > 0 {
+                reader.fulfill_read_into_request(cx, filled_view.handle(), done);
+            }
+        }
+    }
+
+    /// <https://streams.spec.whatwg.org/#readable-byte-stream-controller-pull-into>
+    #[allow(unrooted_must_root)]
+    pub fn pull_into(&self, cx: JSContext, view: HandleObject, promise: Rc<Promise>) {
+        // Steps 1-8.
+        let view_type = view_type(view);
+        let mut descriptor = PullIntoDescriptor {
+            view: Heap::boxed(view.get()),
+            byte_offset: view_byte_offset(view),
+            byte_length: view_byte_length(view),
+            bytes_filled: 0,
+            element_size: element_size(view_type),
+        };
+        let reader = match self.stream.byob_reader() {
+            Some(reader) => reader,
+            None => return,
+        };
+
+        // Step 9.
+        if !self.pending_pull_intos.borrow().is_empty() {
+            self.pending_pull_intos.borrow_mut().push_back(descriptor);
+            reader.add_read_into_request(promise);
+            return;
+        }
+
+        // Step 10.
+        if self.stream.is_closed() {
+            rooted!(in(*cx) let mut empty_view = UndefinedValue());
+            let _ = create_view(
+                cx,
+                view,
+                view_type,
+                descriptor.byte_offset,
+                0,
+                empty_view.handle_mut(),
+            );
+            resolve_read_request(cx, &promise, empty_view.handle(), true);
+            return;
+        }
+
+        // Step 11.
+        if self.queue_total_size.get() > 0 {
+            if self.fill_pull_into_descriptor_from_queue(&mut descriptor) {
+                rooted!(in(*cx) let mut filled_view = UndefinedValue());
+                let _ = create_view(
+                    cx,
+                    view,
+                    view_type,
+                    descriptor.byte_offset,
+                    descriptor.bytes_filled,
+                    filled_view.handle_mut(),
+                );
+                self.handle_queue_drain();
+                resolve_read_request(cx, &promise, filled_view.handle(), false);
+                return;
+            }
+            if self.close_requested.get() {
+                let global = self.global();
+                rooted!(in(*cx) let mut error = UndefinedValue());
+                error_to_jsval(
+                    cx,
+                    &global,
+                    Error::Type(
+                        "The stream closed in the middle of an element of a view".to_owned(),
+                    ),
+                    error.handle_mut(),
+                );
+                self.error(error.handle());
+                let _ac = enter_realm(&*promise);
+                promise.reject(cx, error.handle());
+                return;
+            }
+        }
+
+        // Steps 12-14.
+        self.pending_pull_intos.borrow_mut().push_back(descriptor);
+        reader.add_read_into_request(promise);
+        self.call_pull_if_needed();
+    }
+
+    /// The view of the current BYOB request, which looks at the part of the
+    /// view of the first pending read that is yet to be filled.
+    fn create_byob_request_view(&self, cx: JSContext, rval: MutableHandleValue) {
+        let pending_pull_intos = self.pending_pull_intos.borrow();
+        let descriptor = pending_pull_intos
+            .front()
+            .expect("Created a BYOB request without a pending read");
+        rooted!(in(*cx) let view = descriptor.view.get());
+        let _ = create_view(
+            cx,
+            view.handle(),
+            Type::Uint8,
+            descriptor.byte_offset + descriptor.bytes_filled,
+            descriptor.byte_length - descriptor.bytes_filled,
+            rval,
+        );
+    }
+
+    /// <https://streams.spec.whatwg.org/#readable-byte-stream-controller-respond>
+    pub fn respond(&self, bytes_written: usize) -> ErrorResult {
+        {
+            let pending_pull_intos = self.pending_pull_intos.borrow();
+            let descriptor = pending_pull_intos
+                .front()
+                .ok_or_else(|| Error::Type("There is no pending read to respond to".to_owned()))?;
+
+            // Steps 3-4.
+            if self.stream.is_closed() {
+                if bytes_written != 0 {
+                    return Err(Error::Type(
+                        "A closed stream can only respond with no bytes".to_owned(),
+                    ));
+                }
+            } else {
+                if bytes_written == 0 {
+                    return Err(Error::Type(
+                        "A readable stream must respond with some bytes".to_owned(),
+                    ));
+                }
+                if descriptor.bytes_filled + bytes_written > descriptor.byte_length {
+                    return Err(Error::Range(
+                        "More bytes were written than the view can hold".to_owned(),
+                    ));
+                }
+            }
+        }
+
+        // Step 6.
+        self.respond_internal(bytes_written);
+        Ok(())
+    }
+
+    /// <https://streams.spec.whatwg.org/#readable-byte-stream-controller-respond-with-new-view>
+    pub fn respond_with_new_view(&self, cx: JSContext, view: HandleObject) -> ErrorResult {
+        let byte_length = view_byte_length(view);
+        {
+            let pending_pull_intos = self.pending_pull_intos.borrow();
+            let descriptor = pending_pull_intos
+                .front()
+                .ok_or_else(|| Error::Type("There is no pending read to respond to".to_owned()))?;
+
+            // Steps 4-5.
+            if self.stream.is_closed() {
+                if byte_length != 0 {
+                    return Err(Error::Type(
+                        "A closed stream can only respond with an empty view".to_owned(),
+                    ));
+                }
+            } else if byte_length == 0 {
+                return Err(Error::Type(
+                    "A readable stream must respond with a non-empty view".to_owned(),
+                ));
+            }
+
+            // Steps 6-8.
+            rooted!(in(*cx) let descriptor_view = descriptor.view.get());
+            if !same_buffer(cx, view, descriptor_view.handle()) ||
+                view_byte_offset(view) != descriptor.byte_offset + descriptor.bytes_filled
+            {
+                return Err(Error::Range(
+                    "The view doesn't look at the region the stream asked to be filled".to_owned(),
+                ));
+            }
+            if descriptor.bytes_filled + byte_length > descriptor.byte_length {
+                return Err(Error::Range(
+                    "The view is larger than the region the stream asked to be filled".to_owned(),
+                ));
+            }
+        }
+
+        // Step 11.
+        self.respond_internal(byte_length);
+        Ok(())
+    }
+
+    /// <https://streams.spec.whatwg.org/#readable-byte-stream-controller-respond-internal>
+    fn respond_internal(&self, bytes_written: usize) {
+        // Step 3.
+        self.invalidate_byob_request();
+
+        if self.stream.is_closed() {
+            // https://streams.spec.whatwg.org/#readable-byte-stream-controller-respond-in-closed-state
+            let pending_pull_intos =
+                mem::replace(&mut *self.pending_pull_intos.borrow_mut(), VecDeque::new());
+            for descriptor in pending_pull_intos {
+                self.commit_pull_into_descriptor(&descriptor, true);
+            }
+        } else {
+            // https://streams.spec.whatwg.org/#readable-byte-stream-controller-respond-in-readable-state
+            let mut descriptor = match self.pending_pull_intos.borrow_mut().pop_front() {
+                Some(descriptor) => descriptor,
+                None => return,
+            };
+
+            // Steps 2-3.
+            descriptor.bytes_filled += bytes_written;
+            if descriptor.bytes_filled < descriptor.element_size {
+                self.pending_pull_intos.borrow_mut().push_front(descriptor);
+                return self.call_pull_if_needed();
+            }
+
+            // Steps 5-7.
+            let remainder_size = descriptor.bytes_filled % descriptor.element_size;
+            if remainder_size > 0 {
+                let cx = self.global().get_cx();
+                rooted!(in(*cx) let view = descriptor.view.get());
+                let bytes = view_bytes(cx, view.handle());
+                let end = descriptor.bytes_filled;
+                self.enqueue_chunk_to_queue(bytes[end - remainder_size..end].to_vec());
+            }
+            descriptor.bytes_filled -= remainder_size;
+
+            // Steps 8-9.
+            self.commit_pull_into_descriptor(&descriptor, false);
+            self.process_pull_into_descriptors_using_queue();
+        }
+
+        // Step 5.
+        self.call_pull_if_needed();
+    }
+
+    /// <https://streams.spec.whatwg.org/#rbs-controller-private-pull>
+    #[allow(unrooted_must_root)]
+    pub fn pull_steps(&self, promise: Rc<Promise>) {
+        let cx = self.global().get_cx();
+
+        // Step 3.
+        let chunk = self.queue.borrow_mut().pop_front();
+        if let Some(bytes) = chunk {
+            self.queue_total_size
+                .set(self.queue_total_size.get() - bytes.len());
+            self.handle_queue_drain();
+            rooted!(in(*cx) let mut chunk = UndefinedValue());
+            match create_uint8_array(cx, &bytes, chunk.handle_mut()) {
+                Ok(()) => resolve_read_request(cx, &promise, chunk.handle(), false),
+                Err(error) => promise.reject_error(error),
+            }
+            return;
+        }
+
+        // Steps 5-6.
+        if let Some(reader) = self.stream.default_reader() {
+            reader.add_read_request(promise);
+        }
+        self.call_pull_if_needed();
+    }
+
+    /// <https://streams.spec.whatwg.org/#rbs-controller-private-cancel>
+    #[allow(unrooted_must_root)]
+    pub fn cancel_steps(&self, reason: HandleValue) -> Rc<Promise> {
+        // Steps 1-2.
+        self.clear_pending_pull_intos();
+        self.reset_queue();
+
+        // Steps 3-5.
+        let source = self.source.borrow().clone();
+        let result = match source {
+            Some(source) => source.cancel(&self.global(), reason),
+            None => resolved_promise(&self.global()),
+        };
+        self.clear_algorithms();
+        result
+    }
+}
+
+impl ReadableByteStreamControllerMethods for ReadableByteStreamController {
+    // https://streams.spec.whatwg.org/#rbs-controller-byob-request
+    fn GetByobRequest(&self) -> Option<DomRoot<ReadableStreamBYOBRequest>> {
+        if self.byob_request.get().is_none() && !self.pending_pull_intos.borrow().is_empty() {
+            let cx = self.global().get_cx();
+            rooted!(in(*cx) let mut view = UndefinedValue());
+            self.create_byob_request_view(cx, view.handle_mut());
+            if view.is_object() {
+                rooted!(in(*cx) let view = view.to_object());
+                self.byob_request
+                    .set(Some(&*ReadableStreamBYOBRequest::new(self, view.handle())));
+            }
+        }
+        self.byob_request.get()
+    }
+
+    // https://streams.spec.whatwg.org/#rbs-controller-desired-size
+    fn GetDesiredSize(&self) -> Option<f64> {
+        self.desired_size()
+    }
+
+    // https://streams.spec.whatwg.org/#rbs-controller-close
+    fn Close(&self) -> ErrorResult {
+        // Steps 1-2.
+        if !self.can_close_or_enqueue() {
+            return Err(Error::Type("The stream can't be closed".to_owned()));
+        }
+
+        // Step 3.
+        self.close()
+    }
+
+    // https://streams.spec.whatwg.org/#rbs-controller-enqueue
+    fn Enqueue(&self, cx: JSContext, chunk: CustomAutoRooterGuard<ArrayBufferView>) -> ErrorResult {
+        rooted!(in(*cx) let chunk = *chunk.underlying_object());
+
+        // Step 1.
+        if view_byte_length(chunk.handle()) == 0 {
+            return Err(Error::Type("The chunk must not be empty".to_owned()));
+        }
+
+        // Steps 3-4.
+        if !self.can_close_or_enqueue() {
+            return Err(Error::Type(
+                "Chunks can't be enqueued into the stream".to_owned(),
+            ));
+        }
+
+        // Step 5.
+        self.enqueue_bytes(view_bytes(cx, chunk.handle()));
+        Ok(())
+    }
+
+    // https://streams.spec.whatwg.org/#rbs-controller-error
+    fn Error(&self, _cx: JSContext, error: HandleValue) {
+        self.error(error);
+    }
+}
@@ -0,0 +1,1086 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::dom::abortsignal::AbortSignal;
+use crate::dom::bindings::callback::ExceptionHandling;
+use crate::dom::bindings::cell::DomRefCell;
+use crate::dom::bindings::codegen::Bindings::QueuingStrategyBinding::{
+    QueuingStrategy, QueuingStrategySize,
+};
+use crate::dom::bindings::codegen::Bindings::ReadableStreamBinding;
+use crate::dom::bindings::codegen::Bindings::ReadableStreamBinding::{
+    ReadableStreamGetReaderOptions, ReadableStreamMethods, ReadableStreamReaderMode,
+    ReadableWritablePair, StreamPipeOptions,
+};
+use crate::dom::bindings::codegen::Bindings::UnderlyingSourceBinding::{
+    ReadableStreamType, UnderlyingSource as UnderlyingSourceDict, UnderlyingSourceCancelCallback,
+    UnderlyingSourcePullCallback, UnderlyingSourceStartCallback,
+};
+use crate::dom::bindings::codegen::UnionTypes::ReadableStreamDefaultControllerOrReadableByteStreamController as ReadableStreamController;
+use crate::dom::bindings::codegen::UnionTypes::ReadableStreamDefaultReaderOrReadableStreamBYOBReader as ReadableStreamReader;
+use crate::dom::bindings::conversions::{ConversionResult, ToJSValConvertible};
+use crate::dom::bindings::error::{Error, Fallible};
+use crate::dom::bindings::reflector::{reflect_dom_object, DomObject, Reflector};
+use crate::dom::bindings::root::{Dom, DomRoot, MutNullableDom};
+use crate::dom::globalscope::GlobalScope;
+use crate::dom::promise::Promise;
+use crate::dom::readablebytestreamcontroller::ReadableByteStreamController;
+use crate::dom::readablestreambyobreader::ReadableStreamBYOBReader;
+use crate::dom::readablestreamdefaultcontroller::ReadableStreamDefaultController;
+use crate::dom::readablestreamdefaultreader::ReadableStreamDefaultReader;
+use crate::dom::transformstream::TransformStream;
+use crate::dom::writablestream::WritableStream;
+use crate::dom::writablestreamdefaultwriter::WritableStreamDefaultWriter;
+use crate::realms::enter_realm;
+use crate::script_runtime::JSContext;
+use crate::streams::wait_for_all;
+use crate::streams::{error_to_jsval, extract_high_water_mark, ignore, mark_as_handled};
+use crate::streams::{promise_from_call_result, react_to_promise, read_result_parts};
+use crate::streams::{rejected_promise, rejected_promise_with_error, resolve_with_promise};
+use crate::streams::{resolved_promise, transform_to_undefined, uint8_array_bytes};
+use dom_struct::dom_struct;
+use js::jsapi::{Heap, JSObject};
+use js::jsval::{JSVal, ObjectValue, UndefinedValue};
+use js::rust::{HandleObject, HandleValue, MutableHandleValue};
+use std::cell::Cell;
+use std::ptr;
+use std::rc::Rc;
+
+/// <https://streams.spec.whatwg.org/#readablestream-state>
+#[derive(Clone, Copy, Debug, JSTraceable, MallocSizeOf, PartialEq)]
+pub enum ReadableStreamState {
+    Readable,
+    Closed,
+    Errored,
+}
+
+/// An underlying source given to the `ReadableStream` constructor by script.
+#[derive(JSTraceable)]
+pub struct JsUnderlyingSource {
+    /// The `this` object of the callbacks.
+    object: Heap<*mut JSObject>,
+    start: Option<Rc<UnderlyingSourceStartCallback>>,
+    pull: Option<Rc<UnderlyingSourcePullCallback>>,
+    cancel: Option<Rc<UnderlyingSourceCancelCallback>>,
+}
+
+impl JsUnderlyingSource {
+    fn new(object: HandleObject, dict: &UnderlyingSourceDict) -> Rc<JsUnderlyingSource> {
+        let source = Rc::new(JsUnderlyingSource {
+            object: Heap::default(),
+            start: dict.start.clone(),
+            pull: dict.pull.clone(),
+            cancel: dict.cancel.clone(),
+        });
+        source.object.set(object.get());
+        source
+    }
+}
+
+/// The algorithms with which a controller starts, pulls from and cancels the
+/// source of the chunks of its stream.
+/// <https://streams.spec.whatwg.org/#underlying-source-api>
+#[unrooted_must_root_lint::must_root]
+#[derive(Clone, JSTraceable, MallocSizeOf)]
+pub enum UnderlyingSource {
+    Js(#[ignore_malloc_size_of = "Rc"] Rc<JsUnderlyingSource>),
+    /// A source that the engine feeds, such as the body of a fetch.
+    Native,
+    /// The readable side of a transform stream.
+    Transform(Dom<TransformStream>),
+    /// A branch of a teed stream, with its index.
+    Tee(#[ignore_malloc_size_of = "Rc"] Rc<TeeState>, usize),
+}
+
+impl UnderlyingSource {
+    /// The start algorithm, which may throw if the source was given by script.
+    #[allow(unrooted_must_root)]
+    pub fn start(
+        &self,
+        global: &GlobalScope,
+        controller: ReadableStreamController,
+    ) -> Fallible<Rc<Promise>> {
+        match *self {
+            UnderlyingSource::Js(ref source) => match source.start {
+                Some(ref start) => {
+                    let cx = global.get_cx();
+                    rooted!(in(*cx) let this = source.object.get());
+                    let result =
+                        start.Call_(&this.handle(), controller, ExceptionHandling::Rethrow)?;
+                    Ok(promise_from_call_result(global, Ok(result)))
+                },
+                None => Ok(resolved_promise(global)),
+            },
+            UnderlyingSource::Transform(ref stream) => Ok(stream.start_promise()),
+            UnderlyingSource::Native | UnderlyingSource::Tee(..) => Ok(resolved_promise(global)),
+        }
+    }
+
+    /// The pull algorithm.
+    #[allow(unrooted_must_root)]
+    pub fn pull(&self, global: &GlobalScope, controller: ReadableStreamController) -> Rc<Promise> {
+        match *self {
+            UnderlyingSource::Js(ref source) => match source.pull {
+                Some(ref pull) => {
+                    let cx = global.get_cx();
+                    rooted!(in(*cx) let this = source.object.get());
+                    let result = pull.Call_(&this.handle(), controller, ExceptionHandling::Rethrow);
+                    promise_from_call_result(global, result)
+                },
+                None => resolved_promise(global),
+            },
+            UnderlyingSource::Native => resolved_promise(global),
+            UnderlyingSource::Transform(ref stream) => stream.source_pull(),
+            UnderlyingSource::Tee(ref state, _) => TeeState::pull(state),
+        }
+    }
+
+    /// The cancel algorithm.
+    #[allow(unrooted_must_root)]
+    pub fn cancel(&self, global: &GlobalScope, reason: HandleValue) -> Rc<Promise> {
+        match *self {
+            UnderlyingSource::Js(ref source) => match source.cancel {
+                Some(ref cancel) => {
+                    let cx = global.get_cx();
+                    rooted!(in(*cx) let this = source.object.get());
+                    let result = cancel.Call_(&this.handle(), reason, ExceptionHandling::Rethrow);
+                    promise_from_call_result(global, result)
+                },
+                None => resolved_promise(global),
+            },
+            UnderlyingSource::Native => resolved_promise(global),
+            UnderlyingSource::Transform(ref stream) => stream.source_cancel(reason),
+            UnderlyingSource::Tee(ref state, index) => TeeState::cancel(state, index, reason),
+        }
+    }
+}
+
+// https://streams.spec.whatwg.org/#rs-class
+#[dom_struct]
+pub struct ReadableStream {
+    reflector_: Reflector,
+    state: Cell<ReadableStreamState>,
+    #[ignore_malloc_size_of = "Defined in rust-mozjs"]
+    stored_error: Heap<JSVal>,
+    disturbed: Cell<bool>,
+    default_controller: MutNullableDom<ReadableStreamDefaultController>,
+    byte_controller: MutNullableDom<ReadableByteStreamController>,
+    default_reader: MutNullableDom<ReadableStreamDefaultReader>,
+    byob_reader: MutNullableDom<ReadableStreamBYOBReader>,
+}
+
+impl ReadableStream {
+    fn new_inherited() -> ReadableStream {
+        ReadableStream {
+            reflector_: Reflector::new(),
+            state: Cell::new(ReadableStreamState::Readable),
+            stored_error: Heap::default(),
+            disturbed: Cell::new(false),
+            default_controller: Default::default(),
+            byte_controller: Default::default(),
+            default_reader: Default::default(),
+            byob_reader: Default::default(),
+        }
+    }
+
+    fn new(global: &GlobalScope) -> DomRoot<ReadableStream> {
+        reflect_dom_object(
+            Box::new(ReadableStream::new_inherited()),
+            global,
+            ReadableStreamBinding::Wrap,
+        )
+    }
+
+    /// <https://streams.spec.whatwg.org/#create-readable-stream>
+    #[allow(unrooted_must_root)]
+    pub fn new_with_source(
+        global: &GlobalScope,
+        source: UnderlyingSource,
+        high_water_mark: f64,
+        size: Option<Rc<QueuingStrategySize>>,
+    ) -> DomRoot<ReadableStream> {
+        let stream = ReadableStream::new(global);
+        ReadableStreamDefaultController::setup(&stream, source, high_water_mark, size)
+            .expect("The start algorithm of an internal source threw");
+        stream
+    }
+
+    /// <https://streams.spec.whatwg.org/#abstract-opdef-createreadablebytestream>
+    #[allow(unrooted_must_root)]
+    pub fn new_bytes_with_source(
+        global: &GlobalScope,
+        source: UnderlyingSource,
+    ) -> DomRoot<ReadableStream> {
+        let stream = ReadableStream::new(global);
+        ReadableByteStreamController::setup(&stream, source, 0.)
+            .expect("The start algorithm of an internal source threw");
+        stream
+    }
+
+    /// A byte stream whose chunks the engine enqueues with `enqueue_native`.
+    pub fn new_native(global: &GlobalScope) -> DomRoot<ReadableStream> {
+        ReadableStream::new_bytes_with_source(global, UnderlyingSource::Native)
+    }
+
+    /// A closed byte stream that holds `bytes`.
+    pub fn new_from_bytes(global: &GlobalScope, bytes: Vec<u8>) -> DomRoot<ReadableStream> {
+        let stream = ReadableStream::new_native(global);
+        stream.enqueue_native(bytes);
+        stream.close_native();
+        stream
+    }
+
+    pub fn state(&self) -> ReadableStreamState {
+        self.state.get()
+    }
+
+    pub fn is_readable(&self) -> bool {
+        self.state.get() == ReadableStreamState::Readable
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.state.get() == ReadableStreamState::Closed
+    }
+
+    pub fn is_errored(&self) -> bool {
+        self.state.get() == ReadableStreamState::Errored
+    }
+
+    /// <https://streams.spec.whatwg.org/#is-readable-stream-locked>
+    pub fn is_locked(&self) -> bool {
+        self.default_reader.get().is_some() || self.byob_reader.get().is_some()
+    }
+
+    /// <https://streams.spec.whatwg.org/#is-readable-stream-disturbed>
+    pub fn is_disturbed(&self) -> bool {
+        self.disturbed.get()
+    }
+
+    pub fn set_disturbed(&self) {
+        self.disturbed.set(true);
+    }
+
+    pub fn get_stored_error(&self, mut rval: MutableHandleValue) {
+        rval.set(self.stored_error.get());
+    }
+
+    pub fn default_controller(&self) -> Option<DomRoot<ReadableStreamDefaultController>> {
+        self.default_controller.get()
+    }
+
+    pub fn set_default_controller(&self, controller: &ReadableStreamDefaultController) {
+        self.default_controller.set(Some(controller));
+    }
+
+    pub fn byte_controller(&self) -> Option<DomRoot<ReadableByteStreamController>> {
+        self.byte_controller.get()
+    }
+
+    pub fn set_byte_controller(&self, controller: &ReadableByteStreamController) {
+        self.byte_controller.set(Some(controller));
+    }
+
+    pub fn default_reader(&self) -> Option<DomRoot<ReadableStreamDefaultReader>> {
+        self.default_reader.get()
+    }
+
+    pub fn set_default_reader(&self, reader: Option<&ReadableStreamDefaultReader>) {
+        self.default_reader.set(reader);
+    }
+
+    pub fn byob_reader(&self) -> Option<DomRoot<ReadableStreamBYOBReader>> {
+        self.byob_reader.get()
+    }
+
+    pub fn set_byob_reader(&self, reader: Option<&ReadableStreamBYOBReader>) {
+        self.byob_reader.set(reader);
+    }
+
+    /// <https://streams.spec.whatwg.org/#readable-stream-get-num-read-requests>
+    /// and <https://streams.spec.whatwg.org/#readable-stream-get-num-read-into-requests>
+    pub fn num_read_requests(&self) -> usize {
+        if let Some(reader) = self.default_reader.get() {
+            reader.num_read_requests()
+        } else if let Some(reader) = self.byob_reader.get() {
+            reader.num_read_into_requests()
+        } else {
+            0
+        }
+    }
+
+    /// The steps a controller takes when a default reader reads from this stream.
+    #[allow(unrooted_must_root)]
+    pub fn pull_steps(&self, promise: Rc<Promise>) {
+        if let Some(controller) = self.default_controller.get() {
+            controller.pull_steps(promise);
+        } else if let Some(controller) = self.byte_controller.get() {
+            controller.pull_steps(promise);
+        }
+    }
+
+    /// <https://streams.spec.whatwg.org/#acquire-readable-stream-reader>
+    pub fn acquire_default_reader(&self) -> Fallible<DomRoot<ReadableStreamDefaultReader>> {
+        ReadableStreamDefaultReader::new(&self.global(), self)
+    }
+
+    /// <https://streams.spec.whatwg.org/#readable-stream-cancel>
+    #[allow(unrooted_must_root)]
+    pub fn cancel(&self, reason: HandleValue) -> Rc<Promise> {
+        let global = self.global();
+
+        // Step 1.
+        self.disturbed.set(true);
+
+        match self.state.get() {
+            // Step 2.
+            ReadableStreamState::Closed => return resolved_promise(&global),
+            // Step 3.
+            ReadableStreamState::Errored => {
+                let cx = global.get_cx();
+                rooted!(in(*cx) let mut error = UndefinedValue());
+                self.get_stored_error(error.handle_mut());
+                return rejected_promise(&global, error.handle());
+            },
+            ReadableStreamState::Readable => {},
+        }
+
+        // Steps 4-6.
+        self.close();
+
+        // Step 7.
+        let source_cancel_promise = if let Some(controller) = self.default_controller.get() {
+            controller.cancel_steps(reason)
+        } else if let Some(controller) = self.byte_controller.get() {
+            controller.cancel_steps(reason)
+        } else {
+            resolved_promise(&global)
+        };
+
+        // Step 8.
+        transform_to_undefined(&source_cancel_promise)
+    }
+
+    /// <https://streams.spec.whatwg.org/#readable-stream-close>
+    pub fn close(&self) {
+        // Steps 1-2.
+        assert!(self.is_readable());
+        self.state.set(ReadableStreamState::Closed);
+
+        // Steps 3-7.
+        if let Some(reader) = self.default_reader.get() {
+            reader.close();
+        } else if let Some(reader) = self.byob_reader.get() {
+            reader.close();
+        }
+    }
+
+    /// <https://streams.spec.whatwg.org/#readable-stream-error>
+    pub fn error(&self, error: HandleValue) {
+        // Steps 1-3.
+        assert!(self.is_readable());
+        self.state.set(ReadableStreamState::Errored);
+        self.stored_error.set(error.get());
+
+        // Steps 4-9.
+        if let Some(reader) = self.default_reader.get() {
+            reader.error(error);
+        } else if let Some(reader) = self.byob_reader.get() {
+            reader.error(error);
+        }
+    }
+
+    /// Enqueue bytes that the engine received into this byte stream.
+    pub fn enqueue_native(&self, bytes: Vec<u8>) {
+        if let Some(controller) = self.byte_controller.get() {
+            if controller.can_close_or_enqueue() && !bytes.is_empty() {
+                controller.enqueue_bytes(bytes);
+            }
+        }
+    }
+
+    /// Close this byte stream once the engine received all of its bytes.
+    pub fn close_native(&self) {
+        if let Some(controller) = self.byte_controller.get() {
+            if controller.can_close_or_enqueue() {
+                let _ = controller.close();
+            }
+        }
+    }
+
+    /// Error this stream with `error`, if it is still readable.
+    pub fn error_native(&self, error: Error) {
+        if !self.is_readable() {
+            return;
+        }
+        let global = self.global();
+        let cx = global.get_cx();
+        rooted!(in(*cx) let mut value = UndefinedValue());
+        error_to_jsval(cx, &global, error, value.handle_mut());
+        if let Some(controller) = self.default_controller.get() {
+            controller.error(value.handle());
+        } else if let Some(controller) = self.byte_controller.get() {
+            controller.error(value.handle());
+        }
+    }
+
+    /// <https://streams.spec.whatwg.org/#readable-stream-tee>
+    #[allow(unrooted_must_root)]
+    pub fn tee(&self) -> Fallible<Vec<DomRoot<ReadableStream>>> {
+        let global = self.global();
+
+        // Steps 1-3.
+        let reader = self.acquire_default_reader()?;
+        let bytes = self.byte_controller.get().is_some();
+
+        // Steps 4-11.
+        let state = Rc::new(TeeState {
+            stream: Dom::from_ref(self),
+            reader: Dom::from_ref(&*reader),
+            bytes,
+            reading: Cell::new(false),
+            read_again: Cell::new(false),
+            branches: vec![TeeBranch::default(), TeeBranch::default()],
+            cancel_promise: Promise::new(&global),
+        });
+
+        // Steps 12-19.
+        let branches: Vec<_> = (0..2)
+            .map(|index| {
+                let source = UnderlyingSource::Tee(state.clone(), index);
+                if bytes {
+                    ReadableStream::new_bytes_with_source(&global, source)
+                } else {
+                    ReadableStream::new_with_source(&global, source, 1., None)
+                }
+            })
+            .collect();
+        for (branch, stream) in state.branches.iter().zip(&branches) {
+            branch.stream.set(Some(stream));
+        }
+
+        // Step 20.
+        react_to_promise(
+            &reader.closed(),
+            state.clone(),
+            ignore,
+            TeeState::source_errored,
+        );
+
+        // Step 21.
+        Ok(branches)
+    }
+
+    /// <https://streams.spec.whatwg.org/#readable-stream-pipe-to>
+    #[allow(unrooted_must_root)]
+    pub fn pipe_to(
+        &self,
+        dest: &WritableStream,
+        prevent_close: bool,
+        prevent_abort: bool,
+        prevent_cancel: bool,
+        signal: Option<&AbortSignal>,
+    ) -> Rc<Promise> {
+        let global = self.global();
+
+        // Steps 1-10.
+        let reader = match self.acquire_default_reader() {
+            Ok(reader) => reader,
+            Err(error) => return rejected_promise_with_error(&global, error),
+        };
+        let writer = match WritableStreamDefaultWriter::new(&global, dest) {
+            Ok(writer) => writer,
+            Err(error) => {
+                reader.release();
+                return rejected_promise_with_error(&global, error);
+            },
+        };
+
+        // Step 11.
+        self.disturbed.set(true);
+
+        // Steps 12-13.
+        let pipe = Rc::new(PipeTo {
+            source: Dom::from_ref(self),
+            dest: Dom::from_ref(dest),
+            reader: Dom::from_ref(&*reader),
+            writer: Dom::from_ref(&*writer),
+            prevent_close,
+            prevent_abort,
+            prevent_cancel,
+            shutting_down: Cell::new(false),
+            current_write: DomRefCell::new(None),
+            action: Cell::new(None),
+            error: Heap::default(),
+            has_error: Cell::new(false),
+            promise: Promise::new(&global),
+        });
+
+        // Steps 14-15.
+        PipeTo::start(&pipe, signal);
+
+        // Step 16.
+        pipe.promise.clone()
+    }
+}
+
+impl ReadableStreamMethods for ReadableStream {
+    // https://streams.spec.whatwg.org/#rs-constructor
+    #[allow(unrooted_must_root)]
+    fn Constructor(
+        cx: JSContext,
+        global: &GlobalScope,
+        underlying_source: Option<*mut JSObject>,
+        strategy: &QueuingStrategy,
+    ) -> Fallible<DomRoot<ReadableStream>> {
+        // Steps 1-2.
+        rooted!(in(*cx) let object = underlying_source.unwrap_or(ptr::null_mut()));
+        rooted!(in(*cx) let mut value = UndefinedValue());
+        if !object.is_null() {
+            value.set(ObjectValue(object.get()));
+        }
+        let dict = match UnderlyingSourceDict::new(cx, value.handle()) {
+            Ok(ConversionResult::Success(dict)) => dict,
+            Ok(ConversionResult::Failure(error)) => return Err(Error::Type(error.into_owned())),
+            Err(()) => return Err(Error::JSFailed),
+        };
+
+        // Step 3.
+        let stream = ReadableStream::new(global);
+        let source = UnderlyingSource::Js(JsUnderlyingSource::new(object.handle(), &dict));
+
+        if dict.type_ == Some(ReadableStreamType::Bytes) {
+            // Step 4.1.
+            if strategy.size.is_some() {
+                return Err(Error::Range(
+                    "The strategy of a byte stream can't have a size function".to_owned(),
+                ));
+            }
+
+            // Steps 4.2-4.3.
+            let high_water_mark = extract_high_water_mark(strategy, 0.)?;
+            ReadableByteStreamController::setup(&stream, source, high_water_mark)?;
+        } else {
+            // Steps 5.1-5.4.
+            let high_water_mark = extract_high_water_mark(strategy, 1.)?;
+            ReadableStreamDefaultController::setup(
+                &stream,
+                source,
+                high_water_mark,
+                strategy.size.clone(),
+            )?;
+        }
+
+        Ok(stream)
+    }
+
+    // https://streams.spec.whatwg.org/#rs-locked
+    fn Locked(&self) -> bool {
+        self.is_locked()
+    }
+
+    // https://streams.spec.whatwg.org/#rs-cancel
+    fn Cancel(&self, _cx: JSContext, reason: HandleValue) -> Rc<Promise> {
+        // Step 1.
+        if self.is_locked() {
+            return rejected_promise_with_error(
+                &self.global(),
+                Error::Type("The stream is locked".to_owned()),
+            );
+        }
+
+        // Step 2.
+        self.cancel(reason)
+    }
+
+    // https://streams.spec.whatwg.org/#rs-get-reader
+    fn GetReader(
+        &self,
+        options: &ReadableStreamGetReaderOptions,
+    ) -> Fallible<ReadableStreamReader> {
+        let global = self.global();
+        match options.mode {
+            // Step 1.
+            None => Ok(ReadableStreamReader::ReadableStreamDefaultReader(
+                ReadableStreamDefaultReader::new(&global, self)?,
+            )),
+            // Steps 2-3.
+            Some(ReadableStreamReaderMode::Byob) => {
+                Ok(ReadableStreamReader::ReadableStreamBYOBReader(
+                    ReadableStreamBYOBReader::new(&global, self)?,
+                ))
+            },
+        }
+    }
+
+    // https://streams.spec.whatwg.org/#rs-pipe-through
+    fn PipeThrough(
+        &self,
+        transform: &ReadableWritablePair,
+        options: &StreamPipeOptions,
+    ) -> Fallible<DomRoot<ReadableStream>> {
+        // Step 1.
+        if self.is_locked() {
+            return Err(Error::Type("The stream is locked".to_owned()));
+        }
+
+        // Step 2.
+        if transform.writable.is_locked() {
+            return Err(Error::Type("The writable stream is locked".to_owned()));
+        }
+
+        // Steps 3-5.
+        let promise = self.pipe_to(
+            &transform.writable,
+            options.preventClose,
+            options.preventAbort,
+            options.preventCancel,
+            options.signal.as_deref(),
+        );
+
+        // Step 6.
+        mark_as_handled(&promise);
+
+        // Step 7.
+        Ok(DomRoot::from_ref(&*transform.readable))
+    }
+
+    // https://streams.spec.whatwg.org/#rs-pipe-to
+    fn PipeTo(&self, destination: &WritableStream, options: &StreamPipeOptions) -> Rc<Promise> {
+        // Step 1.
+        if self.is_locked() {
+            return rejected_promise_with_error(
+                &self.global(),
+                Error::Type("The stream is locked".to_owned()),
+            );
+        }
+
+        // Step 2.
+        if destination.is_locked() {
+            return rejected_promise_with_error(
+                &self.global(),
+                Error::Type("The destination is locked".to_owned()),
+            );
+        }
+
+        // Steps 3-4.
+        self.pipe_to(
+            destination,
+            options.preventClose,
+            options.preventAbort,
+            options.preventCancel,
+            options.signal.as_deref(),
+        )
+    }
+
+    // https://streams.spec.whatwg.org/#rs-tee
+    fn Tee(&self) -> Fallible<Vec<DomRoot<ReadableStream>>> {
+        self.tee()
+    }
+}
+
+/// The state of one of the branches of a teed stream.
+#[unrooted_must_root_lint::must_root]
+#[derive(Default, JSTraceable, MallocSizeOf)]
+struct TeeBranch {
+    stream: MutNullableDom<ReadableStream>,
+    canceled: Cell<bool>,
+    #[ignore_malloc_size_of = "Defined in rust-mozjs"]
+    reason: Heap<JSVal>,
+}
+
+/// The state that the two branches of a teed stream share.
+/// <https://streams.spec.whatwg.org/#abstract-opdef-readablestreamdefaulttee>
+#[unrooted_must_root_lint::must_root]
+#[derive(JSTraceable, MallocSizeOf)]
+pub struct TeeState {
+    stream: Dom<ReadableStream>,
+    reader: Dom<ReadableStreamDefaultReader>,
+    /// Whether the branches are byte streams, which get a copy of each chunk.
+    bytes: bool,
+    reading: Cell<bool>,
+    read_again: Cell<bool>,
+    branches: Vec<TeeBranch>,
+    #[ignore_malloc_size_of = "Rc"]
+    cancel_promise: Rc<Promise>,
+}
+
+impl TeeState {
+    /// <https://streams.spec.whatwg.org/#ref-for-abstract-opdef-readablestreamdefaulttee-pullalgorithm>
+    #[allow(unrooted_must_root)]
+    fn pull(state: &Rc<TeeState>) -> Rc<Promise> {
+        let global = state.stream.global();
+
+        // Step 1.
+        if state.reading.get() {
+            state.read_again.set(true);
+            return resolved_promise(&global);
+        }
+
+        // Steps 2-4.
+        state.reading.set(true);
+        let read = state.reader.read();
+        react_to_promise(
+            &read,
+            state.clone(),
+            TeeState::read_fulfilled,
+            TeeState::read_rejected,
+        );
+
+        // Step 5.
+        resolved_promise(&global)
+    }
+
+    #[allow(unrooted_must_root)]
+    fn read_fulfilled(state: &Rc<TeeState>, cx: JSContext, result: HandleValue) {
+        rooted!(in(*cx) let mut chunk = UndefinedValue());
+        if read_result_parts(cx, result, chunk.handle_mut()) {
+            return TeeState::close_steps(state);
+        }
+
+        // Chunk steps.
+        state.read_again.set(false);
+        let bytes = if state.bytes {
+            uint8_array_bytes(cx, chunk.handle())
+        } else {
+            None
+        };
+        for branch in state
+            .branches
+            .iter()
+            .filter(|branch| !branch.canceled.get())
+        {
+            let stream = branch
+                .stream
+                .get()
+                .expect("Branch of a tee without a stream");
+            if let Some(controller) = stream.default_controller() {
+                if controller.can_close_or_enqueue() {
+                    let _ = controller.enqueue(cx, chunk.handle());
+                }
+            } else if let Some(ref bytes) = bytes {
+                stream.enqueue_native(bytes.clone());
+            }
+        }
+        state.reading.set(false);
+        if state.read_again.get() {
+            TeeState::pull(state);
+        }
+    }
+
+    fn read_rejected(state: &Rc<TeeState>, _cx: JSContext, _reason: HandleValue) {
+        state.reading.set(false);
+    }
+
+    fn close_steps(state: &TeeState) {
+        state.reading.set(false);
+        for branch in state
+            .branches
+            .iter()
+            .filter(|branch| !branch.canceled.get())
+        {
+            let stream = branch
+                .stream
+                .get()
+                .expect("Branch of a tee without a stream");
+            if let Some(controller) = stream.default_controller() {
+                if controller.can_close_or_enqueue() {
+                    controller.close();
+                }
+            } else {
+                stream.close_native();
+            }
+        }
+        if !state.branches.iter().all(|branch| branch.canceled.get()) {
+            state.cancel_promise.resolve_native(&());
+        }
+    }
+
+    fn source_errored(state: &Rc<TeeState>, _cx: JSContext, reason: HandleValue) {
+        for branch in &state.branches {
+            let stream = branch
+                .stream
+                .get()
+                .expect("Branch of a tee without a stream");
+            if let Some(controller) = stream.default_controller() {
+                controller.error(reason);
+            } else if let Some(controller) = stream.byte_controller() {
+                controller.error(reason);
+            }
+        }
+        if !state.branches.iter().all(|branch| branch.canceled.get()) {
+            state.cancel_promise.resolve_native(&());
+        }
+    }
+
+    /// <https://streams.spec.whatwg.org/#ref-for-abstract-opdef-readablestreamdefaulttee-cancel1algorithm>
+    #[allow(unsafe_code)]
+    fn cancel(state: &Rc<TeeState>, index: usize, reason: HandleValue) -> Rc<Promise> {
+        // Steps 1-2.
+        let branch = &state.branches[index];
+        branch.canceled.set(true);
+        branch.reason.set(reason.get());
+
+        // Step 3.
+        if state.branches.iter().all(|branch| branch.canceled.get()) {
+            let cx = state.stream.global().get_cx();
+            rooted!(in(*cx) let mut composite_reason = UndefinedValue());
+            let reasons: Vec<JSVal> = state
+                .branches
+                .iter()
+                .map(|branch| branch.reason.get())
+                .collect();
+            unsafe { reasons.to_jsval(*cx, composite_reason.handle_mut()) };
+            let cancel_result = state.stream.cancel(composite_reason.handle());
+            resolve_with_promise(&state.cancel_promise, &cancel_result);
+        }
+
+        // Step 4.
+        state.cancel_promise.clone()
+    }
+}
+
+/// What a pipe does to its streams when it shuts down.
+#[derive(Clone, Copy, JSTraceable, MallocSizeOf)]
+enum PipeAction {
+    CloseDestination,
+    AbortDestination,
+    CancelSource,
+    /// Abort the destination and cancel the source because the signal of the pipe aborted.
+    SignalAbort,
+}
+
+/// The state of a pipe from a readable stream to a writable stream.
+/// <https://streams.spec.whatwg.org/#readable-stream-pipe-to>
+#[unrooted_must_root_lint::must_root]
+#[derive(JSTraceable, MallocSizeOf)]
+pub struct PipeTo {
+    source: Dom<ReadableStream>,
+    dest: Dom<WritableStream>,
+    reader: Dom<ReadableStreamDefaultReader>,
+    writer: Dom<WritableStreamDefaultWriter>,
+    prevent_close: bool,
+    prevent_abort: bool,
+    prevent_cancel: bool,
+    shutting_down: Cell<bool>,
+    /// The promise of the latest write to the destination.
+    #[ignore_malloc_size_of = "Rc"]
+    current_write: DomRefCell<Option<Rc<Promise>>>,
+    action: Cell<Option<PipeAction>>,
+    /// The error with which the pipe finalizes, if `has_error` is set.
+    #[ignore_malloc_size_of = "Defined in rust-mozjs"]
+    error: Heap<JSVal>,
+    has_error: Cell<bool>,
+    #[ignore_malloc_size_of = "Rc"]
+    promise: Rc<Promise>,
+}
+
+impl PipeTo {
+    #[allow(unrooted_must_root)]
+    fn start(pipe: &Rc<PipeTo>, signal: Option<&AbortSignal>) {
+        // Step 14.
+        if let Some(signal) = signal {
+            if signal.aborted() {
+                return PipeTo::abort(pipe);
+            }
+            signal.add_pipe(pipe.clone());
+        }
+
+        // Step 15, the conditions in which a pipe shuts down.
+        react_to_promise(
+            &pipe.reader.closed(),
+            pipe.clone(),
+            PipeTo::source_closed,
+            PipeTo::source_errored,
+        );
+        react_to_promise(
+            &pipe.writer.closed(),
+            pipe.clone(),
+            ignore,
+            PipeTo::dest_errored,
+        );
+        if pipe.dest.close_queued_or_in_flight() || pipe.dest.is_closed() {
+            let global = pipe.source.global();
+            let cx = global.get_cx();
+            rooted!(in(*cx) let mut error = UndefinedValue());
+            error_to_jsval(
+                cx,
+                &global,
+                Error::Type("The destination is closed".to_owned()),
+                error.handle_mut(),
+            );
+            let action = if pipe.prevent_cancel {
+                None
+            } else {
+                Some(PipeAction::CancelSource)
+            };
+            return PipeTo::shutdown(pipe, action, Some(error.handle()));
+        }
+
+        PipeTo::pipe_loop(pipe);
+    }
+
+    /// Read a chunk from the source once the destination is ready for it.
+    fn pipe_loop(pipe: &Rc<PipeTo>) {
+        if pipe.shutting_down.get() {
+            return;
+        }
+        react_to_promise(
+            &pipe.writer.ready(),
+            pipe.clone(),
+            PipeTo::read_chunk,
+            ignore,
+        );
+    }
+
+    fn read_chunk(pipe: &Rc<PipeTo>, _cx: JSContext, _v: HandleValue) {
+        if pipe.shutting_down.get() {
+            return;
+        }
+        react_to_promise(
+            &pipe.reader.read(),
+            pipe.clone(),
+            PipeTo::write_chunk,
+            ignore,
+        );
+    }
+
+    fn write_chunk(pipe: &Rc<PipeTo>, cx: JSContext, result: HandleValue) {
+        if pipe.shutting_down.get() {
+            return;
+        }
+        rooted!(in(*cx) let mut chunk = UndefinedValue());
+        if read_result_parts(cx, result, chunk.handle_mut()) {
+            return;
+        }
+        let write = pipe.writer.write(cx, chunk.handle());
+        mark_as_handled(&write);
+        *pipe.current_write.borrow_mut() = Some(write);
+        PipeTo::pipe_loop(pipe);
+    }
+
+    fn source_closed(pipe: &Rc<PipeTo>, _cx: JSContext, _v: HandleValue) {
+        let action = if pipe.prevent_close {
+            None
+        } else {
+            Some(PipeAction::CloseDestination)
+        };
+        PipeTo::shutdown(pipe, action, None);
+    }
+
+    fn source_errored(pipe: &Rc<PipeTo>, _cx: JSContext, error: HandleValue) {
+        let action = if pipe.prevent_abort {
+            None
+        } else {
+            Some(PipeAction::AbortDestination)
+        };
+        PipeTo::shutdown(pipe, action, Some(error));
+    }
+
+    fn dest_errored(pipe: &Rc<PipeTo>, _cx: JSContext, error: HandleValue) {
+        let action = if pipe.prevent_cancel {
+            None
+        } else {
+            Some(PipeAction::CancelSource)
+        };
+        PipeTo::shutdown(pipe, action, Some(error));
+    }
+
+    /// The abort algorithm that the signal of the pipe runs.
+    pub fn abort(pipe: &Rc<PipeTo>) {
+        let global = pipe.source.global();
+        let cx = global.get_cx();
+        rooted!(in(*cx) let mut error = UndefinedValue());
+        error_to_jsval(cx, &global, Error::Abort, error.handle_mut());
+        PipeTo::shutdown(pipe, Some(PipeAction::SignalAbort), Some(error.handle()));
+    }
+
+    /// <https://streams.spec.whatwg.org/#rs-pipeTo-shutdown-with-action>
+    /// and <https://streams.spec.whatwg.org/#rs-pipeTo-shutdown>
+    #[allow(unrooted_must_root)]
+    fn shutdown(pipe: &Rc<PipeTo>, action: Option<PipeAction>, error: Option<HandleValue>) {
+        // Steps 1-2.
+        if pipe.shutting_down.get() {
+            return;
+        }
+        pipe.shutting_down.set(true);
+        if let Some(error) = error {
+            pipe.error.set(error.get());
+            pipe.has_error.set(true);
+        }
+        pipe.action.set(action);
+
+        // Step 3.
+        let current_write = pipe.current_write.borrow_mut().take();
+        match current_write {
+            Some(write) if pipe.dest.is_writable() && !pipe.dest.close_queued_or_in_flight() => {
+                react_to_promise(
+                    &write,
+                    pipe.clone(),
+                    PipeTo::perform_action,
+                    PipeTo::perform_action,
+                )
+            },
+            _ => {
+                let cx = pipe.source.global().get_cx();
+                rooted!(in(*cx) let undefined = UndefinedValue());
+                PipeTo::perform_action(pipe, cx, undefined.handle());
+            },
+        }
+    }
+
+    #[allow(unrooted_must_root)]
+    fn perform_action(pipe: &Rc<PipeTo>, cx: JSContext, _v: HandleValue) {
+        let global = pipe.source.global();
+        rooted!(in(*cx) let error = pipe.error.get());
+        let action_promise = match pipe.action.get() {
+            None => return PipeTo::finalize(pipe),
+            Some(PipeAction::CloseDestination) => pipe.writer.close_with_error_propagation(),
+            Some(PipeAction::AbortDestination) => pipe.dest.abort(error.handle()),
+            Some(PipeAction::CancelSource) => pipe.source.cancel(error.handle()),
+            Some(PipeAction::SignalAbort) => {
+                let mut actions = vec![];
+                if !pipe.prevent_abort && pipe.dest.is_writable() {
+                    actions.push(pipe.dest.abort(error.handle()));
+                }
+                if !pipe.prevent_cancel && pipe.source.is_readable() {
+                    actions.push(pipe.source.cancel(error.handle()));
+                }
+                wait_for_all(&global, actions)
+            },
+        };
+        react_to_promise(
+            &action_promise,
+            pipe.clone(),
+            PipeTo::action_fulfilled,
+            PipeTo::action_rejected,
+        );
+    }
+
+    fn action_fulfilled(pipe: &Rc<PipeTo>, _cx: JSContext, _v: HandleValue) {
+        PipeTo::finalize(pipe);
+    }
+
+    fn action_rejected(pipe: &Rc<PipeTo>, _cx: JSContext, error: HandleValue) {
+        pipe.error.set(error.get());
+        pipe.has_error.set(true);
+        PipeTo::finalize(pipe);
+    }
+
+    /// <https://streams.spec.whatwg.org/#rs-pipeTo-finalize>
+    fn finalize(pipe: &PipeTo) {
+        // Steps 1-2.
+        pipe.writer.release();
+        pipe.reader.release();
+
+        // Steps 4-5.
+        if pipe.has_error.get() {
+            let cx = pipe.source.global().get_cx();
+            rooted!(in(*cx) let error = pipe.error.get());
+            let _ac = enter_realm(&*pipe.promise);
+            pipe.promise.reject(cx, error.handle());
+        } else {
+            pipe.promise.resolve_native(&());
+        }
+    }
+}
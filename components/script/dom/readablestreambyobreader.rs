@@ -0,0 +1,214 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::dom::bindings::cell::DomRefCell;
+use crate::dom::bindings::codegen::Bindings::ReadableStreamBYOBReaderBinding;
+use crate::dom::bindings::codegen::Bindings::ReadableStreamBYOBReaderBinding::ReadableStreamBYOBReaderMethods;
+use crate::dom::bindings::error::{Error, Fallible};
+use crate::dom::bindings::reflector::{reflect_dom_object, DomObject, Reflector};
+use crate::dom::bindings::root::{DomRoot, MutNullableDom};
+use crate::dom::globalscope::GlobalScope;
+use crate::dom::promise::Promise;
+use crate::dom::readablestream::{ReadableStream, ReadableStreamState};
+use crate::dom::readablestreamdefaultreader::{initial_closed_promise, resolve_read_request};
+use crate::realms::enter_realm;
+use crate::script_runtime::JSContext;
+use crate::streams::{mark_as_handled, rejected_promise_with_error, view_byte_length};
+use dom_struct::dom_struct;
+use js::jsval::UndefinedValue;
+use js::rust::{CustomAutoRooterGuard, HandleValue};
+use js::typedarray::ArrayBufferView;
+use std::collections::VecDeque;
+use std::mem;
+use std::rc::Rc;
+
+// https://streams.spec.whatwg.org/#byob-reader-class
+#[dom_struct]
+pub struct ReadableStreamBYOBReader {
+    reflector_: Reflector,
+    stream: MutNullableDom<ReadableStream>,
+    #[ignore_malloc_size_of = "Rc"]
+    closed_promise: DomRefCell<Rc<Promise>>,
+    /// The promises of pending reads into views, which resolve with read results.
+    #[ignore_malloc_size_of = "Rc"]
+    read_into_requests: DomRefCell<VecDeque<Rc<Promise>>>,
+}
+
+impl ReadableStreamBYOBReader {
+    fn new_inherited(global: &GlobalScope) -> ReadableStreamBYOBReader {
+        ReadableStreamBYOBReader {
+            reflector_: Reflector::new(),
+            stream: Default::default(),
+            closed_promise: DomRefCell::new(Promise::new(global)),
+            read_into_requests: DomRefCell::new(VecDeque::new()),
+        }
+    }
+
+    /// <https://streams.spec.whatwg.org/#set-up-readable-stream-byob-reader>
+    pub fn new(
+        global: &GlobalScope,
+        stream: &ReadableStream,
+    ) -> Fallible<DomRoot<ReadableStreamBYOBReader>> {
+        // Step 1.
+        if stream.is_locked() {
+            return Err(Error::Type("The stream is locked".to_owned()));
+        }
+
+        // Step 2.
+        if stream.byte_controller().is_none() {
+            return Err(Error::Type(
+                "A BYOB reader can only read from a byte stream".to_owned(),
+            ));
+        }
+
+        // Steps 3-4.
+        let reader = reflect_dom_object(
+            Box::new(ReadableStreamBYOBReader::new_inherited(global)),
+            global,
+            ReadableStreamBYOBReaderBinding::Wrap,
+        );
+        reader.stream.set(Some(stream));
+        stream.set_byob_reader(Some(&reader));
+        *reader.closed_promise.borrow_mut() = initial_closed_promise(global, stream);
+        Ok(reader)
+    }
+
+    pub fn num_read_into_requests(&self) -> usize {
+        self.read_into_requests.borrow().len()
+    }
+
+    pub fn add_read_into_request(&self, promise: Rc<Promise>) {
+        self.read_into_requests.borrow_mut().push_back(promise);
+    }
+
+    /// <https://streams.spec.whatwg.org/#readable-stream-fulfill-read-into-request>
+    pub fn fulfill_read_into_request(&self, cx: JSContext, view: HandleValue, done: bool) {
+        let promise = self
+            .read_into_requests
+            .borrow_mut()
+            .pop_front()
+            .expect("Fulfilled a read-into request that doesn't exist");
+        resolve_read_request(cx, &promise, view, done);
+    }
+
+    /// Resolve the pending reads and the closed promise of this reader once its stream closes.
+    pub fn close(&self) {
+        let cx = self.global().get_cx();
+        rooted!(in(*cx) let undefined = UndefinedValue());
+        let requests = mem::replace(&mut *self.read_into_requests.borrow_mut(), VecDeque::new());
+        for promise in requests {
+            resolve_read_request(cx, &promise, undefined.handle(), true);
+        }
+        self.closed_promise.borrow().resolve_native(&());
+    }
+
+    /// Reject the pending reads and the closed promise of this reader once its stream errors.
+    pub fn error(&self, error: HandleValue) {
+        let cx = self.global().get_cx();
+        let closed_promise = self.closed_promise.borrow().clone();
+        let _ac = enter_realm(&*closed_promise);
+        closed_promise.reject(cx, error);
+        mark_as_handled(&closed_promise);
+        let requests = mem::replace(&mut *self.read_into_requests.borrow_mut(), VecDeque::new());
+        for promise in requests {
+            promise.reject(cx, error);
+        }
+    }
+
+    /// <https://streams.spec.whatwg.org/#abstract-opdef-readablestreambyobreaderrelease>
+    pub fn release(&self) {
+        let stream = match self.stream.get() {
+            Some(stream) => stream,
+            None => return,
+        };
+        let global = self.global();
+
+        // https://streams.spec.whatwg.org/#readable-stream-reader-generic-release
+        let error = || Error::Type("The reader has been released".to_owned());
+        if stream.is_readable() {
+            self.closed_promise.borrow().reject_error(error());
+        } else {
+            *self.closed_promise.borrow_mut() = rejected_promise_with_error(&global, error());
+        }
+        mark_as_handled(&self.closed_promise.borrow());
+        stream.set_byob_reader(None);
+        self.stream.set(None);
+
+        // https://streams.spec.whatwg.org/#abstract-opdef-readablestreambyobreadererrorreadintorequests
+        let requests = mem::replace(&mut *self.read_into_requests.borrow_mut(), VecDeque::new());
+        for promise in requests {
+            promise.reject_error(error());
+        }
+    }
+}
+
+impl ReadableStreamBYOBReaderMethods for ReadableStreamBYOBReader {
+    // https://streams.spec.whatwg.org/#byob-reader-constructor
+    fn Constructor(
+        global: &GlobalScope,
+        stream: &ReadableStream,
+    ) -> Fallible<DomRoot<ReadableStreamBYOBReader>> {
+        ReadableStreamBYOBReader::new(global, stream)
+    }
+
+    // https://streams.spec.whatwg.org/#byob-reader-read
+    #[allow(unrooted_must_root)]
+    fn Read(&self, cx: JSContext, view: CustomAutoRooterGuard<ArrayBufferView>) -> Rc<Promise> {
+        let global = self.global();
+        rooted!(in(*cx) let view = *view.underlying_object());
+
+        // Step 1.
+        if view_byte_length(view.handle()) == 0 {
+            return rejected_promise_with_error(
+                &global,
+                Error::Type("The view must not be empty".to_owned()),
+            );
+        }
+
+        // Step 3.
+        let stream = match self.stream.get() {
+            Some(stream) => stream,
+            None => {
+                return rejected_promise_with_error(
+                    &global,
+                    Error::Type("The reader has been released".to_owned()),
+                )
+            },
+        };
+
+        // Steps 4-5, https://streams.spec.whatwg.org/#readable-stream-byob-reader-read
+        let promise = Promise::new(&global);
+        stream.set_disturbed();
+        if stream.state() == ReadableStreamState::Errored {
+            rooted!(in(*cx) let mut error = UndefinedValue());
+            stream.get_stored_error(error.handle_mut());
+            let _ac = enter_realm(&*promise);
+            promise.reject(cx, error.handle());
+        } else if let Some(controller) = stream.byte_controller() {
+            controller.pull_into(cx, view.handle(), promise.clone());
+        }
+        promise
+    }
+
+    // https://streams.spec.whatwg.org/#byob-reader-release-lock
+    fn ReleaseLock(&self) {
+        self.release();
+    }
+
+    // https://streams.spec.whatwg.org/#generic-reader-closed
+    fn Closed(&self) -> Rc<Promise> {
+        self.closed_promise.borrow().clone()
+    }
+
+    // https://streams.spec.whatwg.org/#generic-reader-cancel
+    fn Cancel(&self, _cx: JSContext, reason: HandleValue) -> Rc<Promise> {
+        match self.stream.get() {
+            Some(stream) => stream.cancel(reason),
+            None => rejected_promise_with_error(
+                &self.global(),
+                Error::Type("The reader has been released".to_owned()),
+            ),
+        }
+    }
+}
@@ -0,0 +1,97 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::dom::bindings::codegen::Bindings::ReadableStreamBYOBRequestBinding;
+use crate::dom::bindings::codegen::Bindings::ReadableStreamBYOBRequestBinding::ReadableStreamBYOBRequestMethods;
+use crate::dom::bindings::error::{Error, ErrorResult};
+use crate::dom::bindings::reflector::{reflect_dom_object, DomObject, Reflector};
+use crate::dom::bindings::root::{DomRoot, MutNullableDom};
+use crate::dom::readablebytestreamcontroller::ReadableByteStreamController;
+use crate::script_runtime::JSContext;
+use dom_struct::dom_struct;
+use js::jsapi::{Heap, JSObject};
+use js::rust::{CustomAutoRooterGuard, HandleObject};
+use js::typedarray::ArrayBufferView;
+use std::ptr::NonNull;
+
+// https://streams.spec.whatwg.org/#rs-byob-request-class
+#[dom_struct]
+pub struct ReadableStreamBYOBRequest {
+    reflector_: Reflector,
+    controller: MutNullableDom<ReadableByteStreamController>,
+    /// A view on the part of the buffer of the pending read that is yet to be filled.
+    #[ignore_malloc_size_of = "Defined in rust-mozjs"]
+    view: Heap<*mut JSObject>,
+}
+
+impl ReadableStreamBYOBRequest {
+    fn new_inherited(controller: &ReadableByteStreamController) -> ReadableStreamBYOBRequest {
+        ReadableStreamBYOBRequest {
+            reflector_: Reflector::new(),
+            controller: MutNullableDom::new(Some(controller)),
+            view: Heap::default(),
+        }
+    }
+
+    pub fn new(
+        controller: &ReadableByteStreamController,
+        view: HandleObject,
+    ) -> DomRoot<ReadableStreamBYOBRequest> {
+        let request = reflect_dom_object(
+            Box::new(ReadableStreamBYOBRequest::new_inherited(controller)),
+            &*controller.global(),
+            ReadableStreamBYOBRequestBinding::Wrap,
+        );
+        request.view.set(view.get());
+        request
+    }
+
+    /// <https://streams.spec.whatwg.org/#readable-byte-stream-controller-invalidate-byob-request>
+    pub fn invalidate(&self) {
+        self.controller.set(None);
+        self.view.set(std::ptr::null_mut());
+    }
+}
+
+impl ReadableStreamBYOBRequestMethods for ReadableStreamBYOBRequest {
+    // https://streams.spec.whatwg.org/#rs-byob-request-view
+    #[allow(unsafe_code)]
+    fn GetView(&self, _cx: JSContext) -> Option<NonNull<JSObject>> {
+        let view = self.view.get();
+        if view.is_null() {
+            None
+        } else {
+            unsafe { Some(NonNull::new_unchecked(view)) }
+        }
+    }
+
+    // https://streams.spec.whatwg.org/#rs-byob-request-respond
+    fn Respond(&self, bytes_written: u64) -> ErrorResult {
+        // Step 1.
+        let controller = self
+            .controller
+            .get()
+            .ok_or_else(|| Error::Type("The request was already responded to".to_owned()))?;
+
+        // Step 4.
+        controller.respond(bytes_written as usize)
+    }
+
+    // https://streams.spec.whatwg.org/#rs-byob-request-respond-with-new-view
+    fn RespondWithNewView(
+        &self,
+        cx: JSContext,
+        view: CustomAutoRooterGuard<ArrayBufferView>,
+    ) -> ErrorResult {
+        // Step 1.
+        let controller = self
+            .controller
+            .get()
+            .ok_or_else(|| Error::Type("The request was already responded to".to_owned()))?;
+
+        // Step 3.
+        rooted!(in(*cx) let view = *view.underlying_object());
+        controller.respond_with_new_view(cx, view.handle())
+    }
+}
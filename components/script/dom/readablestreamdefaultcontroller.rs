@@ -0,0 +1,337 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::dom::bindings::cell::DomRefCell;
+use crate::dom::bindings::codegen::Bindings::QueuingStrategyBinding::QueuingStrategySize;
+use crate::dom::bindings::codegen::Bindings::ReadableStreamDefaultControllerBinding;
+use crate::dom::bindings::codegen::Bindings::ReadableStreamDefaultControllerBinding::ReadableStreamDefaultControllerMethods;
+use crate::dom::bindings::codegen::UnionTypes::ReadableStreamDefaultControllerOrReadableByteStreamController as ReadableStreamController;
+use crate::dom::bindings::error::{Error, ErrorResult, Fallible};
+use crate::dom::bindings::reflector::{reflect_dom_object, DomObject, Reflector};
+use crate::dom::bindings::root::{Dom, DomRoot};
+use crate::dom::promise::Promise;
+use crate::dom::readablestream::{ReadableStream, ReadableStreamState, UnderlyingSource};
+use crate::dom::readablestreamdefaultreader::resolve_read_request;
+use crate::script_runtime::JSContext;
+use crate::streams::QueueWithSizes;
+use crate::streams::{chunk_size, error_to_jsval, react_to_promise, resolved_promise};
+use dom_struct::dom_struct;
+use js::jsval::UndefinedValue;
+use js::rust::wrappers::JS_SetPendingException;
+use js::rust::HandleValue;
+use std::cell::Cell;
+use std::rc::Rc;
+
+// https://streams.spec.whatwg.org/#rs-default-controller-class
+#[dom_struct]
+pub struct ReadableStreamDefaultController {
+    reflector_: Reflector,
+    stream: Dom<ReadableStream>,
+    queue: DomRefCell<QueueWithSizes>,
+    started: Cell<bool>,
+    close_requested: Cell<bool>,
+    pull_again: Cell<bool>,
+    pulling: Cell<bool>,
+    strategy_hwm: f64,
+    #[ignore_malloc_size_of = "Rc"]
+    strategy_size: DomRefCell<Option<Rc<QueuingStrategySize>>>,
+    /// The source of the chunks, which is cleared once no more chunks can be enqueued.
+    source: DomRefCell<Option<UnderlyingSource>>,
+}
+
+impl ReadableStreamDefaultController {
+    #[allow(unrooted_must_root)]
+    fn new_inherited(
+        stream: &ReadableStream,
+        source: UnderlyingSource,
+        strategy_hwm: f64,
+        strategy_size: Option<Rc<QueuingStrategySize>>,
+    ) -> ReadableStreamDefaultController {
+        ReadableStreamDefaultController {
+            reflector_: Reflector::new(),
+            stream: Dom::from_ref(stream),
+            queue: Default::default(),
+            started: Cell::new(false),
+            close_requested: Cell::new(false),
+            pull_again: Cell::new(false),
+            pulling: Cell::new(false),
+            strategy_hwm,
+            strategy_size: DomRefCell::new(strategy_size),
+            source: DomRefCell::new(Some(source)),
+        }
+    }
+
+    /// <https://streams.spec.whatwg.org/#set-up-readable-stream-default-controller>
+    #[allow(unrooted_must_root)]
+    pub fn setup(
+        stream: &ReadableStream,
+        source: UnderlyingSource,
+        strategy_hwm: f64,
+        strategy_size: Option<Rc<QueuingStrategySize>>,
+    ) -> Fallible<DomRoot<ReadableStreamDefaultController>> {
+        let global = stream.global();
+
+        // Steps 1-8.
+        let controller = reflect_dom_object(
+            Box::new(ReadableStreamDefaultController::new_inherited(
+                stream,
+                source.clone(),
+                strategy_hwm,
+                strategy_size,
+            )),
+            &*global,
+            ReadableStreamDefaultControllerBinding::Wrap,
+        );
+        stream.set_default_controller(&controller);
+
+        // Steps 9-11.
+        let start_promise = source.start(
+            &global,
+            ReadableStreamController::ReadableStreamDefaultController(controller.clone()),
+        )?;
+        react_to_promise(
+            &start_promise,
+            Dom::from_ref(&*controller),
+            ReadableStreamDefaultController::start_fulfilled,
+            ReadableStreamDefaultController::start_rejected,
+        );
+        Ok(controller)
+    }
+
+    fn start_fulfilled(controller: &Dom<Self>, _cx: JSContext, _v: HandleValue) {
+        controller.started.set(true);
+        controller.call_pull_if_needed();
+    }
+
+    fn start_rejected(controller: &Dom<Self>, _cx: JSContext, error: HandleValue) {
+        controller.error(error);
+    }
+
+    /// <https://streams.spec.whatwg.org/#readable-stream-default-controller-call-pull-if-needed>
+    #[allow(unrooted_must_root)]
+    fn call_pull_if_needed(&self) {
+        // Steps 1-2.
+        if !self.should_call_pull() {
+            return;
+        }
+
+        // Step 3.
+        if self.pulling.get() {
+            self.pull_again.set(true);
+            return;
+        }
+
+        // Steps 5-7.
+        let source = match self.source.borrow().clone() {
+            Some(source) => source,
+            None => return,
+        };
+        self.pulling.set(true);
+        let pull_promise = source.pull(
+            &self.global(),
+            ReadableStreamController::ReadableStreamDefaultController(DomRoot::from_ref(self)),
+        );
+        react_to_promise(
+            &pull_promise,
+            Dom::from_ref(self),
+            ReadableStreamDefaultController::pull_fulfilled,
+            ReadableStreamDefaultController::pull_rejected,
+        );
+    }
+
+    fn pull_fulfilled(controller: &Dom<Self>, _cx: JSContext, _v: HandleValue) {
+        controller.pulling.set(false);
+        if controller.pull_again.get() {
+            controller.pull_again.set(false);
+            controller.call_pull_if_needed();
+        }
+    }
+
+    fn pull_rejected(controller: &Dom<Self>, _cx: JSContext, error: HandleValue) {
+        controller.error(error);
+    }
+
+    /// <https://streams.spec.whatwg.org/#readable-stream-default-controller-should-call-pull>
+    fn should_call_pull(&self) -> bool {
+        // Steps 2-4.
+        if !self.can_close_or_enqueue() || !self.started.get() {
+            return false;
+        }
+
+        // Step 5.
+        if self.stream.is_locked() && self.stream.num_read_requests() > 0 {
+            return true;
+        }
+
+        // Steps 6-8.
+        self.desired_size().map_or(false, |size| size > 0.)
+    }
+
+    /// <https://streams.spec.whatwg.org/#rs-default-controller-has-backpressure>
+    pub fn has_backpressure(&self) -> bool {
+        !self.should_call_pull()
+    }
+
+    /// <https://streams.spec.whatwg.org/#readable-stream-default-controller-clear-algorithms>
+    fn clear_algorithms(&self) {
+        *self.source.borrow_mut() = None;
+        *self.strategy_size.borrow_mut() = None;
+    }
+
+    /// <https://streams.spec.whatwg.org/#readable-stream-default-controller-get-desired-size>
+    pub fn desired_size(&self) -> Option<f64> {
+        match self.stream.state() {
+            ReadableStreamState::Errored => None,
+            ReadableStreamState::Closed => Some(0.),
+            ReadableStreamState::Readable => {
+                Some(self.strategy_hwm - self.queue.borrow().total_size())
+            },
+        }
+    }
+
+    /// <https://streams.spec.whatwg.org/#readable-stream-default-controller-can-close-or-enqueue>
+    pub fn can_close_or_enqueue(&self) -> bool {
+        !self.close_requested.get() && self.stream.is_readable()
+    }
+
+    /// <https://streams.spec.whatwg.org/#readable-stream-default-controller-close>
+    pub fn close(&self) {
+        // Step 1.
+        if !self.can_close_or_enqueue() {
+            return;
+        }
+
+        // Steps 3-4.
+        self.close_requested.set(true);
+        if self.queue.borrow().is_empty() {
+            self.clear_algorithms();
+            self.stream.close();
+        }
+    }
+
+    /// <https://streams.spec.whatwg.org/#readable-stream-default-controller-enqueue>
+    #[allow(unsafe_code)]
+    pub fn enqueue(&self, cx: JSContext, chunk: HandleValue) -> ErrorResult {
+        // Step 1.
+        if !self.can_close_or_enqueue() {
+            return Ok(());
+        }
+
+        if self.stream.is_locked() && self.stream.num_read_requests() > 0 {
+            // Step 3.
+            if let Some(reader) = self.stream.default_reader() {
+                reader.fulfill_read_request(cx, chunk, false);
+            }
+        } else {
+            // Step 4.
+            let size = self.strategy_size.borrow().clone();
+            let result = chunk_size(size.as_ref(), chunk)
+                .and_then(|size| self.queue.borrow_mut().enqueue_value_with_size(chunk, size));
+            if let Err(error) = result {
+                let global = self.global();
+                rooted!(in(*cx) let mut error_value = UndefinedValue());
+                error_to_jsval(cx, &global, error, error_value.handle_mut());
+                self.error(error_value.handle());
+                unsafe { JS_SetPendingException(*cx, error_value.handle()) };
+                return Err(Error::JSFailed);
+            }
+        }
+
+        // Step 5.
+        self.call_pull_if_needed();
+        Ok(())
+    }
+
+    /// <https://streams.spec.whatwg.org/#readable-stream-default-controller-error>
+    pub fn error(&self, error: HandleValue) {
+        // Step 2.
+        if !self.stream.is_readable() {
+            return;
+        }
+
+        // Steps 3-5.
+        self.queue.borrow_mut().reset();
+        self.clear_algorithms();
+        self.stream.error(error);
+    }
+
+    /// <https://streams.spec.whatwg.org/#rs-default-controller-private-pull>
+    #[allow(unrooted_must_root)]
+    pub fn pull_steps(&self, promise: Rc<Promise>) {
+        let cx = self.global().get_cx();
+
+        // Step 2.
+        if !self.queue.borrow().is_empty() {
+            rooted!(in(*cx) let mut chunk = UndefinedValue());
+            self.queue.borrow_mut().dequeue_value(chunk.handle_mut());
+            if self.close_requested.get() && self.queue.borrow().is_empty() {
+                self.clear_algorithms();
+                self.stream.close();
+            } else {
+                self.call_pull_if_needed();
+            }
+            resolve_read_request(cx, &promise, chunk.handle(), false);
+            return;
+        }
+
+        // Step 3.
+        if let Some(reader) = self.stream.default_reader() {
+            reader.add_read_request(promise);
+        }
+        self.call_pull_if_needed();
+    }
+
+    /// <https://streams.spec.whatwg.org/#rs-default-controller-private-cancel>
+    #[allow(unrooted_must_root)]
+    pub fn cancel_steps(&self, reason: HandleValue) -> Rc<Promise> {
+        // Step 1.
+        self.queue.borrow_mut().reset();
+
+        // Steps 2-4.
+        let source = self.source.borrow().clone();
+        let result = match source {
+            Some(source) => source.cancel(&self.global(), reason),
+            None => resolved_promise(&self.global()),
+        };
+        self.clear_algorithms();
+        result
+    }
+}
+
+impl ReadableStreamDefaultControllerMethods for ReadableStreamDefaultController {
+    // https://streams.spec.whatwg.org/#rs-default-controller-desired-size
+    fn GetDesiredSize(&self) -> Option<f64> {
+        self.desired_size()
+    }
+
+    // https://streams.spec.whatwg.org/#rs-default-controller-close
+    fn Close(&self) -> ErrorResult {
+        // Step 1.
+        if !self.can_close_or_enqueue() {
+            return Err(Error::Type("The stream can't be closed".to_owned()));
+        }
+
+        // Step 2.
+        self.close();
+        Ok(())
+    }
+
+    // https://streams.spec.whatwg.org/#rs-default-controller-enqueue
+    fn Enqueue(&self, cx: JSContext, chunk: HandleValue) -> ErrorResult {
+        // Step 1.
+        if !self.can_close_or_enqueue() {
+            return Err(Error::Type(
+                "Chunks can't be enqueued into the stream".to_owned(),
+            ));
+        }
+
+        // Step 2.
+        self.enqueue(cx, chunk)
+    }
+
+    // https://streams.spec.whatwg.org/#rs-default-controller-error
+    fn Error(&self, _cx: JSContext, error: HandleValue) {
+        self.error(error);
+    }
+}
@@ -0,0 +1,240 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::dom::bindings::cell::DomRefCell;
+use crate::dom::bindings::codegen::Bindings::ReadableStreamDefaultReaderBinding;
+use crate::dom::bindings::codegen::Bindings::ReadableStreamDefaultReaderBinding::ReadableStreamDefaultReaderMethods;
+use crate::dom::bindings::error::{Error, Fallible};
+use crate::dom::bindings::reflector::{reflect_dom_object, DomObject, Reflector};
+use crate::dom::bindings::root::{DomRoot, MutNullableDom};
+use crate::dom::globalscope::GlobalScope;
+use crate::dom::promise::Promise;
+use crate::dom::readablestream::{ReadableStream, ReadableStreamState};
+use crate::realms::enter_realm;
+use crate::script_runtime::JSContext;
+use crate::streams::{create_read_result, mark_as_handled, rejected_promise};
+use crate::streams::{rejected_promise_with_error, resolved_promise};
+use dom_struct::dom_struct;
+use js::jsval::UndefinedValue;
+use js::rust::HandleValue;
+use std::collections::VecDeque;
+use std::mem;
+use std::rc::Rc;
+
+// https://streams.spec.whatwg.org/#default-reader-class
+#[dom_struct]
+pub struct ReadableStreamDefaultReader {
+    reflector_: Reflector,
+    stream: MutNullableDom<ReadableStream>,
+    #[ignore_malloc_size_of = "Rc"]
+    closed_promise: DomRefCell<Rc<Promise>>,
+    /// The promises of pending reads, which resolve with read results.
+    #[ignore_malloc_size_of = "Rc"]
+    read_requests: DomRefCell<VecDeque<Rc<Promise>>>,
+}
+
+impl ReadableStreamDefaultReader {
+    fn new_inherited(global: &GlobalScope) -> ReadableStreamDefaultReader {
+        ReadableStreamDefaultReader {
+            reflector_: Reflector::new(),
+            stream: Default::default(),
+            closed_promise: DomRefCell::new(Promise::new(global)),
+            read_requests: DomRefCell::new(VecDeque::new()),
+        }
+    }
+
+    /// <https://streams.spec.whatwg.org/#set-up-readable-stream-default-reader>
+    pub fn new(
+        global: &GlobalScope,
+        stream: &ReadableStream,
+    ) -> Fallible<DomRoot<ReadableStreamDefaultReader>> {
+        // Step 1.
+        if stream.is_locked() {
+            return Err(Error::Type("The stream is locked".to_owned()));
+        }
+
+        // Steps 2-3.
+        let reader = reflect_dom_object(
+            Box::new(ReadableStreamDefaultReader::new_inherited(global)),
+            global,
+            ReadableStreamDefaultReaderBinding::Wrap,
+        );
+        reader.stream.set(Some(stream));
+        stream.set_default_reader(Some(&reader));
+        *reader.closed_promise.borrow_mut() = initial_closed_promise(global, stream);
+        Ok(reader)
+    }
+
+    pub fn closed(&self) -> Rc<Promise> {
+        self.closed_promise.borrow().clone()
+    }
+
+    pub fn num_read_requests(&self) -> usize {
+        self.read_requests.borrow().len()
+    }
+
+    pub fn add_read_request(&self, promise: Rc<Promise>) {
+        self.read_requests.borrow_mut().push_back(promise);
+    }
+
+    /// <https://streams.spec.whatwg.org/#readable-stream-fulfill-read-request>
+    pub fn fulfill_read_request(&self, cx: JSContext, chunk: HandleValue, done: bool) {
+        let promise = self
+            .read_requests
+            .borrow_mut()
+            .pop_front()
+            .expect("Fulfilled a read request that doesn't exist");
+        resolve_read_request(cx, &promise, chunk, done);
+    }
+
+    /// <https://streams.spec.whatwg.org/#readable-stream-default-reader-read>
+    #[allow(unrooted_must_root)]
+    pub fn read(&self) -> Rc<Promise> {
+        let global = self.global();
+
+        // Step 1 of https://streams.spec.whatwg.org/#default-reader-read
+        let stream = match self.stream.get() {
+            Some(stream) => stream,
+            None => {
+                return rejected_promise_with_error(
+                    &global,
+                    Error::Type("The reader has been released".to_owned()),
+                )
+            },
+        };
+
+        // Step 3.
+        let promise = Promise::new(&global);
+        stream.set_disturbed();
+        let cx = global.get_cx();
+        match stream.state() {
+            // Step 4.
+            ReadableStreamState::Closed => {
+                rooted!(in(*cx) let undefined = UndefinedValue());
+                resolve_read_request(cx, &promise, undefined.handle(), true);
+            },
+            // Step 5.
+            ReadableStreamState::Errored => {
+                rooted!(in(*cx) let mut error = UndefinedValue());
+                stream.get_stored_error(error.handle_mut());
+                let _ac = enter_realm(&*promise);
+                promise.reject(cx, error.handle());
+            },
+            // Step 6.
+            ReadableStreamState::Readable => stream.pull_steps(promise.clone()),
+        }
+        promise
+    }
+
+    /// Resolve the pending reads and the closed promise of this reader once its stream closes.
+    pub fn close(&self) {
+        let cx = self.global().get_cx();
+        rooted!(in(*cx) let undefined = UndefinedValue());
+        let read_requests = mem::replace(&mut *self.read_requests.borrow_mut(), VecDeque::new());
+        for promise in read_requests {
+            resolve_read_request(cx, &promise, undefined.handle(), true);
+        }
+        self.closed_promise.borrow().resolve_native(&());
+    }
+
+    /// Reject the pending reads and the closed promise of this reader once its stream errors.
+    pub fn error(&self, error: HandleValue) {
+        let cx = self.global().get_cx();
+        let closed_promise = self.closed();
+        let _ac = enter_realm(&*closed_promise);
+        closed_promise.reject(cx, error);
+        mark_as_handled(&closed_promise);
+        let read_requests = mem::replace(&mut *self.read_requests.borrow_mut(), VecDeque::new());
+        for promise in read_requests {
+            promise.reject(cx, error);
+        }
+    }
+
+    /// <https://streams.spec.whatwg.org/#abstract-opdef-readablestreamdefaultreaderrelease>
+    pub fn release(&self) {
+        let stream = match self.stream.get() {
+            Some(stream) => stream,
+            None => return,
+        };
+        let global = self.global();
+
+        // https://streams.spec.whatwg.org/#readable-stream-reader-generic-release
+        let error = || Error::Type("The reader has been released".to_owned());
+        if stream.is_readable() {
+            self.closed_promise.borrow().reject_error(error());
+        } else {
+            *self.closed_promise.borrow_mut() = rejected_promise_with_error(&global, error());
+        }
+        mark_as_handled(&self.closed());
+        stream.set_default_reader(None);
+        self.stream.set(None);
+
+        // https://streams.spec.whatwg.org/#abstract-opdef-readablestreamdefaultreadererrorreadrequests
+        let read_requests = mem::replace(&mut *self.read_requests.borrow_mut(), VecDeque::new());
+        for promise in read_requests {
+            promise.reject_error(error());
+        }
+    }
+}
+
+impl ReadableStreamDefaultReaderMethods for ReadableStreamDefaultReader {
+    // https://streams.spec.whatwg.org/#default-reader-constructor
+    fn Constructor(
+        global: &GlobalScope,
+        stream: &ReadableStream,
+    ) -> Fallible<DomRoot<ReadableStreamDefaultReader>> {
+        ReadableStreamDefaultReader::new(global, stream)
+    }
+
+    // https://streams.spec.whatwg.org/#default-reader-read
+    fn Read(&self) -> Rc<Promise> {
+        self.read()
+    }
+
+    // https://streams.spec.whatwg.org/#default-reader-release-lock
+    fn ReleaseLock(&self) {
+        self.release();
+    }
+
+    // https://streams.spec.whatwg.org/#generic-reader-closed
+    fn Closed(&self) -> Rc<Promise> {
+        self.closed()
+    }
+
+    // https://streams.spec.whatwg.org/#generic-reader-cancel
+    fn Cancel(&self, _cx: JSContext, reason: HandleValue) -> Rc<Promise> {
+        match self.stream.get() {
+            Some(stream) => stream.cancel(reason),
+            None => rejected_promise_with_error(
+                &self.global(),
+                Error::Type("The reader has been released".to_owned()),
+            ),
+        }
+    }
+}
+
+/// The closed promise of a reader of `stream`, as steps 4-6 of
+/// <https://streams.spec.whatwg.org/#readable-stream-reader-generic-initialize> set it.
+pub fn initial_closed_promise(global: &GlobalScope, stream: &ReadableStream) -> Rc<Promise> {
+    match stream.state() {
+        ReadableStreamState::Readable => Promise::new(global),
+        ReadableStreamState::Closed => resolved_promise(global),
+        ReadableStreamState::Errored => {
+            let cx = global.get_cx();
+            rooted!(in(*cx) let mut error = UndefinedValue());
+            stream.get_stored_error(error.handle_mut());
+            let promise = rejected_promise(global, error.handle());
+            mark_as_handled(&promise);
+            promise
+        },
+    }
+}
+
+/// Resolve the promise of a read request with a read result.
+pub fn resolve_read_request(cx: JSContext, promise: &Promise, chunk: HandleValue, done: bool) {
+    let _ac = enter_realm(promise);
+    rooted!(in(*cx) let mut result = UndefinedValue());
+    create_read_result(cx, chunk, done, result.handle_mut());
+    promise.resolve(cx, result.handle());
+}
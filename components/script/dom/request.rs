@@ -3,6 +3,7 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 
 use crate::body::{consume_body, BodyOperations, BodyType};
+use crate::dom::abortsignal::AbortSignal;
 use crate::dom::bindings::cell::{DomRefCell, Ref};
 use crate::dom::bindings::codegen::Bindings::HeadersBinding::{HeadersInit, HeadersMethods};
 use crate::dom::bindings::codegen::Bindings::RequestBinding;
@@ -23,11 +24,13 @@ use crate::dom::bindings::trace::RootedTraceableBox;
 use crate::dom::globalscope::GlobalScope;
 use crate::dom::headers::{Guard, Headers};
 use crate::dom::promise::Promise;
-use crate::dom::xmlhttprequest::Extractable;
+use crate::dom::readablestream::ReadableStream;
+use crate::dom::xmlhttprequest::{extract_body, ExtractedBody};
 use dom_struct::dom_struct;
 use http::header::{HeaderName, HeaderValue};
 use http::method::InvalidMethod;
 use http::Method as HttpMethod;
+use net_traits::cache_storage_thread::CacheRequest;
 use net_traits::request::CacheMode as NetTraitsRequestCache;
 use net_traits::request::CredentialsMode as NetTraitsRequestCredentials;
 use net_traits::request::Destination as NetTraitsRequestDestination;
@@ -36,9 +39,9 @@ use net_traits::request::Referrer as NetTraitsRequestReferrer;
 use net_traits::request::Request as NetTraitsRequest;
 use net_traits::request::RequestMode as NetTraitsRequestMode;
 use net_traits::request::{Origin, Window};
+use net_traits::CustomResponseMediator;
 use net_traits::ReferrerPolicy as MsgReferrerPolicy;
 use servo_url::ServoUrl;
-use std::cell::Cell;
 use std::rc::Rc;
 use std::str::FromStr;
 
@@ -46,11 +49,11 @@ use std::str::FromStr;
 pub struct Request {
     reflector_: Reflector,
     request: DomRefCell<NetTraitsRequest>,
-    body_used: Cell<bool>,
     headers: MutNullableDom<Headers>,
+    signal: MutNullableDom<AbortSignal>,
     mime_type: DomRefCell<Vec<u8>>,
-    #[ignore_malloc_size_of = "Rc"]
-    body_promise: DomRefCell<Option<(Rc<Promise>, BodyType)>>,
+    /// The stream of the body, which is `None` for a null body.
+    body_stream: MutNullableDom<ReadableStream>,
 }
 
 impl Request {
@@ -58,10 +61,10 @@ impl Request {
         Request {
             reflector_: Reflector::new(),
             request: DomRefCell::new(net_request_from_global(global, url)),
-            body_used: Cell::new(false),
             headers: Default::default(),
+            signal: Default::default(),
             mime_type: DomRefCell::new("".to_string().into_bytes()),
-            body_promise: DomRefCell::new(None),
+            body_stream: Default::default(),
         }
     }
 
@@ -92,7 +95,8 @@ impl Request {
         // Step 4
         let base_url = global.api_base_url();
 
-        // Step 5 TODO: "Let signal be null."
+        // Step 5
+        let mut signal: Option<DomRoot<AbortSignal>> = None;
 
         match input {
             // Step 6
@@ -117,14 +121,10 @@ impl Request {
             },
             // Step 7
             RequestInfo::Request(ref input_request) => {
-                // This looks like Step 38
-                // TODO do this in the right place to not mask other errors
-                if request_is_disturbed(input_request) || request_is_locked(input_request) {
-                    return Err(Error::Type("Input is disturbed or locked".to_string()));
-                }
                 // Step 7.1
                 temporary_request = input_request.request.borrow().clone();
-                // Step 7.2 TODO: "Set signal to input's signal."
+                // Step 7.2
+                signal = Some(input_request.signal());
             },
         }
 
@@ -303,12 +303,18 @@ impl Request {
             request.method = method;
         }
 
-        // Step 28 TODO: "If init["signal"] exists..."
+        // Step 28
+        if let Some(init_signal) = init.signal.as_ref() {
+            signal = init_signal.clone();
+        }
 
         // Step 29
         let r = Request::from_net_request(global, request);
 
-        // Step 30 TODO: "If signal is not null..."
+        // Step 30
+        if let Some(signal) = signal {
+            r.signal().follow(&signal);
+        }
 
         // Step 31
         // "or_init" looks unclear here, but it always enters the block since r
@@ -413,13 +419,26 @@ impl Request {
         }
 
         // Step 35-36
+        let mut init_stream = None;
         if let Some(Some(ref init_body)) = init.body {
             // Step 36.2 TODO "If init["keepalive"] exists and is true..."
 
             // Step 36.3
-            let extracted_body_tmp = init_body.extract();
-            input_body = Some(extracted_body_tmp.0);
-            let content_type = extracted_body_tmp.1;
+            let content_type = match extract_body(init_body) {
+                ExtractedBody::Stream(stream) => {
+                    if stream.is_disturbed() || stream.is_locked() {
+                        return Err(Error::Type(
+                            "Body stream is disturbed or locked".to_string(),
+                        ));
+                    }
+                    init_stream = Some(stream);
+                    None
+                },
+                ExtractedBody::Bytes(extracted_body, content_type) => {
+                    input_body = Some(extracted_body);
+                    content_type
+                },
+            };
 
             // Step 36.4
             if let Some(contents) = content_type {
@@ -452,10 +471,33 @@ impl Request {
         // This looks like where we need to set the use-preflight flag
         // if the request has a body and nothing else has set the flag.
 
-        // Step 38 is done earlier
+        // Step 38
+        let init_body_is_null = match init.body {
+            Some(Some(_)) => false,
+            _ => true,
+        };
+        if let RequestInfo::Request(ref input_request) = input {
+            if init_body_is_null && (input_request.get_body_used() || input_request.is_locked()) {
+                return Err(Error::Type("Input is disturbed or locked".to_string()));
+            }
+        }
 
         // Step 39
-        // TODO: `ReadableStream` object is not implemented in Servo yet.
+        if let Some(ref stream) = init_stream {
+            r.body_stream.set(Some(stream));
+        } else if let Some(ref body) = input_body {
+            let stream = ReadableStream::new_from_bytes(global, body.clone());
+            r.body_stream.set(Some(&stream));
+
+            // The body of the input request is used by this one.
+            if init_body_is_null {
+                if let RequestInfo::Request(ref input_request) = input {
+                    if let Some(input_stream) = input_request.get_body() {
+                        input_stream.set_disturbed();
+                    }
+                }
+            }
+        }
 
         // Step 40
         r.request.borrow_mut().body = input_body;
@@ -467,13 +509,6 @@ impl Request {
         // Step 42
         Ok(r)
     }
-
-    // https://fetch.spec.whatwg.org/#concept-body-locked
-    fn locked(&self) -> bool {
-        // TODO: ReadableStream is unimplemented. Just return false
-        // for now.
-        false
-    }
 }
 
 impl Request {
@@ -486,7 +521,6 @@ impl Request {
     fn clone_from(r: &Request) -> Fallible<DomRoot<Request>> {
         let req = r.request.borrow();
         let url = req.url();
-        let body_used = r.body_used.get();
         let mime_type = r.mime_type.borrow().clone();
         let headers_guard = r.Headers().get_guard();
         let r_clone = Request::new(&r.global(), url);
@@ -496,18 +530,82 @@ impl Request {
             borrowed_r_request.origin = req.origin.clone();
         }
         *r_clone.request.borrow_mut() = req.clone();
-        r_clone.body_used.set(body_used);
+        // https://fetch.spec.whatwg.org/#concept-body-clone
+        if let Some(stream) = r.body_stream.get() {
+            let branches = stream.tee()?;
+            r.body_stream.set(Some(&branches[0]));
+            r_clone.body_stream.set(Some(&branches[1]));
+        }
         *r_clone.mime_type.borrow_mut() = mime_type;
         r_clone
             .Headers()
             .fill(Some(HeadersInit::Headers(r.Headers())))?;
         r_clone.Headers().set_guard(headers_guard);
+        r_clone.signal().follow(&r.signal());
         Ok(r_clone)
     }
 
     pub fn get_request(&self) -> NetTraitsRequest {
         self.request.borrow().clone()
     }
+
+    pub fn signal(&self) -> DomRoot<AbortSignal> {
+        self.signal.or_init(|| AbortSignal::new(&self.global()))
+    }
+
+    /// Set the bytes of the body of this request, once its stream was read.
+    pub fn set_body_bytes(&self, bytes: Vec<u8>) {
+        self.request.borrow_mut().body = Some(bytes);
+    }
+
+    /// Whether the body of this request is a stream given by script, whose
+    /// bytes are not known up front.
+    pub fn has_stream_body(&self) -> bool {
+        self.request.borrow().body.is_none() && self.body_stream.get().is_some()
+    }
+
+    /// What a cache keeps of this request.
+    pub fn cache_request(&self) -> CacheRequest {
+        let request = self.request.borrow();
+        CacheRequest {
+            method: request.method.clone(),
+            url: request.url(),
+            headers: self.Headers().get_headers_list(),
+        }
+    }
+
+    /// A request for an entry of a cache, whose headers can't be modified.
+    pub fn from_cache_request(global: &GlobalScope, cached: CacheRequest) -> DomRoot<Request> {
+        let r = Request::new(global, cached.url);
+        r.request.borrow_mut().method = cached.method;
+        r.Headers().set_headers(cached.headers);
+        r.Headers().set_guard(Guard::Immutable);
+        r
+    }
+
+    /// The request of a fetch that a service worker handles, whose headers can't
+    /// be modified.
+    pub fn from_mediator(
+        global: &GlobalScope,
+        mediator: &CustomResponseMediator,
+    ) -> DomRoot<Request> {
+        let r = Request::new(global, mediator.load_url.clone());
+        {
+            let mut request = r.request.borrow_mut();
+            request.method = mediator.method.clone();
+            request.mode = mediator.mode.clone();
+            request.destination = mediator.destination;
+            request.body = mediator.body.clone();
+        }
+        if let Some(ref body) = mediator.body {
+            r.body_stream
+                .set(Some(&ReadableStream::new_from_bytes(global, body.clone())));
+        }
+        r.Headers().set_headers(mediator.headers.clone());
+        r.Headers().set_guard(Guard::Immutable);
+        *r.mime_type.borrow_mut() = r.Headers().extract_mime_type();
+        r
+    }
 }
 
 fn net_request_from_global(global: &GlobalScope, url: ServoUrl) -> NetTraitsRequest {
@@ -556,18 +654,6 @@ fn includes_credentials(input: &ServoUrl) -> bool {
     !input.username().is_empty() || input.password().is_some()
 }
 
-// TODO: `Readable Stream` object is not implemented in Servo yet.
-// https://fetch.spec.whatwg.org/#concept-body-disturbed
-fn request_is_disturbed(_input: &Request) -> bool {
-    false
-}
-
-// TODO: `Readable Stream` object is not implemented in Servo yet.
-// https://fetch.spec.whatwg.org/#concept-body-locked
-fn request_is_locked(_input: &Request) -> bool {
-    false
-}
-
 impl RequestMethods for Request {
     // https://fetch.spec.whatwg.org/#dom-request-method
     fn Method(&self) -> ByteString {
@@ -642,18 +728,28 @@ impl RequestMethods for Request {
         DOMString::from_string(r.integrity_metadata.clone())
     }
 
+    // https://fetch.spec.whatwg.org/#dom-request-signal
+    fn Signal(&self) -> DomRoot<AbortSignal> {
+        self.signal()
+    }
+
     // https://fetch.spec.whatwg.org/#dom-body-bodyused
     fn BodyUsed(&self) -> bool {
-        self.body_used.get()
+        self.get_body_used()
+    }
+
+    // https://fetch.spec.whatwg.org/#dom-body-body
+    fn GetBody(&self) -> Option<DomRoot<ReadableStream>> {
+        self.body_stream.get()
     }
 
     // https://fetch.spec.whatwg.org/#dom-request-clone
     fn Clone(&self) -> Fallible<DomRoot<Request>> {
         // Step 1
-        if request_is_locked(self) {
+        if self.is_locked() {
             return Err(Error::Type("Request is locked".to_string()));
         }
-        if request_is_disturbed(self) {
+        if self.get_body_used() {
             return Err(Error::Type("Request is disturbed".to_string()));
         }
 
@@ -688,24 +784,8 @@ impl RequestMethods for Request {
 }
 
 impl BodyOperations for Request {
-    fn get_body_used(&self) -> bool {
-        self.BodyUsed()
-    }
-
-    fn set_body_promise(&self, p: &Rc<Promise>, body_type: BodyType) {
-        assert!(self.body_promise.borrow().is_none());
-        self.body_used.set(true);
-        *self.body_promise.borrow_mut() = Some((p.clone(), body_type));
-    }
-
-    fn is_locked(&self) -> bool {
-        self.locked()
-    }
-
-    fn take_body(&self) -> Option<Vec<u8>> {
-        let mut request = self.request.borrow_mut();
-        let body = request.body.take();
-        Some(body.unwrap_or(vec![]))
+    fn get_body(&self) -> Option<DomRoot<ReadableStream>> {
+        self.body_stream.get()
     }
 
     fn get_mime_type(&self) -> Ref<Vec<u8>> {
@@ -0,0 +1,305 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::dom::bindings::callback::ExceptionHandling;
+use crate::dom::bindings::cell::DomRefCell;
+use crate::dom::bindings::codegen::Bindings::ResizeObserverBinding::{
+    ResizeObserverBoxOptions, ResizeObserverCallback, ResizeObserverMethods, ResizeObserverOptions,
+    Wrap,
+};
+use crate::dom::bindings::codegen::Bindings::WindowBinding::WindowMethods;
+use crate::dom::bindings::error::Fallible;
+use crate::dom::bindings::inheritance::Castable;
+use crate::dom::bindings::reflector::{reflect_dom_object, Reflector};
+use crate::dom::bindings::root::{Dom, DomRoot};
+use crate::dom::domrectreadonly::DOMRectReadOnly;
+use crate::dom::element::Element;
+use crate::dom::node::{window_from_node, Node, NodeDamage, ShadowIncluding};
+use crate::dom::resizeobserverentry::ResizeObserverEntry;
+use crate::dom::resizeobserversize::{ResizeObserverSize, ResizeObserverSizeImpl};
+use crate::dom::window::Window;
+use app_units::Au;
+use dom_struct::dom_struct;
+use euclid::default::{Point2D, Rect, Size2D};
+use std::cell::Cell;
+use std::rc::Rc;
+use style::properties::{LonghandId, PropertyId};
+use style::values::computed::NonNegativeLengthPercentage;
+
+/// The boxes of an element that can be observed.
+struct ObservedBoxes {
+    border_box: ResizeObserverSizeImpl,
+    content_box: ResizeObserverSizeImpl,
+    device_pixel_content_box: ResizeObserverSizeImpl,
+    /// The content box, relative to the padding box.
+    content_rect: Rect<f64>,
+}
+
+impl ObservedBoxes {
+    fn size_of(&self, observed_box: ResizeObserverBoxOptions) -> ResizeObserverSizeImpl {
+        match observed_box {
+            ResizeObserverBoxOptions::Border_box => self.border_box,
+            ResizeObserverBoxOptions::Content_box => self.content_box,
+            ResizeObserverBoxOptions::Device_pixel_content_box => self.device_pixel_content_box,
+        }
+    }
+}
+
+/// The used padding of a side of an element, in CSS pixels.
+fn used_padding(target: &Element, padding: &NonNegativeLengthPercentage, side: LonghandId) -> f64 {
+    if let Some(padding) = padding.maybe_to_used_value(None) {
+        return padding.to_f64_px();
+    }
+    // Percentages are resolved against the containing block, which only layout knows about.
+    let resolved = window_from_node(target).resolved_style_query(
+        target.upcast::<Node>().to_trusted_node_address(),
+        None,
+        PropertyId::Longhand(side),
+    );
+    resolved.trim_end_matches("px").parse().unwrap_or(0.)
+}
+
+/// <https://drafts.csswg.org/resize-observer/#calculate-box-size>
+fn calculate_box_sizes(target: &Element) -> ObservedBoxes {
+    let zero = ObservedBoxes {
+        border_box: ResizeObserverSizeImpl::zero(),
+        content_box: ResizeObserverSizeImpl::zero(),
+        device_pixel_content_box: ResizeObserverSizeImpl::zero(),
+        content_rect: Rect::zero(),
+    };
+    let style = match target.style() {
+        Some(style) => style,
+        None => return zero,
+    };
+    if style.get_box().clone_display().is_none() {
+        return zero;
+    }
+    let border_box = match target.upcast::<Node>().bounding_content_box() {
+        Some(border_box) => border_box.size,
+        None => return zero,
+    };
+
+    let border = style.get_border();
+    let border_top = Au::from(border.border_top_width).to_f64_px();
+    let border_right = Au::from(border.border_right_width).to_f64_px();
+    let border_bottom = Au::from(border.border_bottom_width).to_f64_px();
+    let border_left = Au::from(border.border_left_width).to_f64_px();
+    let padding = style.get_padding();
+    let padding_top = used_padding(target, &padding.padding_top, LonghandId::PaddingTop);
+    let padding_right = used_padding(target, &padding.padding_right, LonghandId::PaddingRight);
+    let padding_bottom = used_padding(target, &padding.padding_bottom, LonghandId::PaddingBottom);
+    let padding_left = used_padding(target, &padding.padding_left, LonghandId::PaddingLeft);
+
+    let width = border_box.width.to_f64_px();
+    let height = border_box.height.to_f64_px();
+    let content_width = (width - border_left - border_right - padding_left - padding_right).max(0.);
+    let content_height =
+        (height - border_top - border_bottom - padding_top - padding_bottom).max(0.);
+    let device_pixel_ratio = window_from_node(target).device_pixel_ratio().get() as f64;
+    let device_pixel_width = (content_width * device_pixel_ratio).round();
+    let device_pixel_height = (content_height * device_pixel_ratio).round();
+
+    // Sizes are along the inline and block axes, which are swapped in vertical writing modes.
+    let logical_size = |width, height| {
+        if style.writing_mode.is_vertical() {
+            ResizeObserverSizeImpl::new(height, width)
+        } else {
+            ResizeObserverSizeImpl::new(width, height)
+        }
+    };
+    ObservedBoxes {
+        border_box: logical_size(width, height),
+        content_box: logical_size(content_width, content_height),
+        device_pixel_content_box: logical_size(device_pixel_width, device_pixel_height),
+        content_rect: Rect::new(
+            Point2D::new(padding_left, padding_top),
+            Size2D::new(content_width, content_height),
+        ),
+    }
+}
+
+/// <https://drafts.csswg.org/resize-observer/#calculate-depth-for-node>
+fn depth_of(target: &Element) -> usize {
+    target
+        .upcast::<Node>()
+        .inclusive_ancestors(ShadowIncluding::Yes)
+        .count()
+}
+
+/// <https://drafts.csswg.org/resize-observer/#resizeobservation>
+#[unrooted_must_root_lint::must_root]
+#[derive(JSTraceable, MallocSizeOf)]
+struct ResizeObservation {
+    target: Dom<Element>,
+    observed_box: ResizeObserverBoxOptions,
+    last_reported_size: Cell<ResizeObserverSizeImpl>,
+}
+
+impl ResizeObservation {
+    /// <https://drafts.csswg.org/resize-observer/#dom-resizeobservation-isactive>
+    fn is_active(&self) -> bool {
+        calculate_box_sizes(&self.target).size_of(self.observed_box) !=
+            self.last_reported_size.get()
+    }
+}
+
+// https://drafts.csswg.org/resize-observer/#resize-observer-interface
+#[dom_struct]
+pub struct ResizeObserver {
+    reflector_: Reflector,
+    #[ignore_malloc_size_of = "can't measure Rc values"]
+    callback: Rc<ResizeObserverCallback>,
+    /// <https://drafts.csswg.org/resize-observer/#dom-resizeobserver-observationtargets-slot>
+    observation_targets: DomRefCell<Vec<ResizeObservation>>,
+    /// <https://drafts.csswg.org/resize-observer/#dom-resizeobserver-activetargets-slot>
+    active_targets: DomRefCell<Vec<Dom<Element>>>,
+    /// <https://drafts.csswg.org/resize-observer/#dom-resizeobserver-skippedtargets-slot>
+    skipped_targets: DomRefCell<Vec<Dom<Element>>>,
+}
+
+impl ResizeObserver {
+    #[allow(unrooted_must_root)]
+    fn new_inherited(callback: Rc<ResizeObserverCallback>) -> ResizeObserver {
+        ResizeObserver {
+            reflector_: Reflector::new(),
+            callback,
+            observation_targets: DomRefCell::new(vec![]),
+            active_targets: DomRefCell::new(vec![]),
+            skipped_targets: DomRefCell::new(vec![]),
+        }
+    }
+
+    /// <https://drafts.csswg.org/resize-observer/#dom-resizeobserver-resizeobserver>
+    #[allow(non_snake_case)]
+    pub fn Constructor(
+        window: &Window,
+        callback: Rc<ResizeObserverCallback>,
+    ) -> Fallible<DomRoot<ResizeObserver>> {
+        let observer = reflect_dom_object(
+            Box::new(ResizeObserver::new_inherited(callback)),
+            window,
+            Wrap,
+        );
+        window.Document().add_resize_observer(&observer);
+        Ok(observer)
+    }
+
+    /// Steps 2.1-2.3 of
+    /// <https://drafts.csswg.org/resize-observer/#gather-active-observations-h>
+    pub fn gather_active_observations_at_depth(&self, depth: usize) {
+        let mut active_targets = self.active_targets.borrow_mut();
+        let mut skipped_targets = self.skipped_targets.borrow_mut();
+        active_targets.clear();
+        skipped_targets.clear();
+        for observation in self.observation_targets.borrow().iter() {
+            if !observation.is_active() {
+                continue;
+            }
+            if depth_of(&observation.target) > depth {
+                active_targets.push(Dom::from_ref(&*observation.target));
+            } else {
+                skipped_targets.push(Dom::from_ref(&*observation.target));
+            }
+        }
+    }
+
+    pub fn has_active_observations(&self) -> bool {
+        !self.active_targets.borrow().is_empty()
+    }
+
+    pub fn has_skipped_observations(&self) -> bool {
+        !self.skipped_targets.borrow().is_empty()
+    }
+
+    /// Step 2 of <https://drafts.csswg.org/resize-observer/#broadcast-active-observations>,
+    /// returning the depth of the shallowest target that was reported.
+    pub fn broadcast_active_observations(&self) -> usize {
+        let mut shallowest_target_depth = usize::max_value();
+        let active_targets: Vec<DomRoot<Element>> = self
+            .active_targets
+            .borrow_mut()
+            .drain(..)
+            .map(|target| DomRoot::from_ref(&*target))
+            .collect();
+        self.skipped_targets.borrow_mut().clear();
+        if active_targets.is_empty() {
+            return shallowest_target_depth;
+        }
+
+        // Steps 2.2-2.3.
+        let window = window_from_node(&*active_targets[0]);
+        let mut entries = vec![];
+        for target in active_targets {
+            let boxes = calculate_box_sizes(&target);
+            let size = |size| ResizeObserverSize::new(&window, size);
+            let border_box_size = size(boxes.border_box);
+            let content_box_size = size(boxes.content_box);
+            let device_pixel_content_box_size = size(boxes.device_pixel_content_box);
+            let content_rect = DOMRectReadOnly::new(
+                window.upcast(),
+                boxes.content_rect.origin.x,
+                boxes.content_rect.origin.y,
+                boxes.content_rect.size.width,
+                boxes.content_rect.size.height,
+            );
+            entries.push(ResizeObserverEntry::new(
+                &window,
+                &target,
+                &content_rect,
+                &[&border_box_size],
+                &[&content_box_size],
+                &[&device_pixel_content_box_size],
+            ));
+            if let Some(observation) = self
+                .observation_targets
+                .borrow()
+                .iter()
+                .find(|observation| *observation.target == *target)
+            {
+                observation
+                    .last_reported_size
+                    .set(boxes.size_of(observation.observed_box));
+            }
+            shallowest_target_depth = shallowest_target_depth.min(depth_of(&target));
+        }
+
+        // Step 2.5.
+        let _ = self
+            .callback
+            .Call_(self, entries, self, ExceptionHandling::Report);
+        shallowest_target_depth
+    }
+}
+
+impl ResizeObserverMethods for ResizeObserver {
+    // https://drafts.csswg.org/resize-observer/#dom-resizeobserver-observe
+    fn Observe(&self, target: &Element, options: &ResizeObserverOptions) {
+        // Steps 1-2.
+        self.Unobserve(target);
+        // Steps 3-4.
+        self.observation_targets
+            .borrow_mut()
+            .push(ResizeObservation {
+                target: Dom::from_ref(target),
+                observed_box: options.box_,
+                last_reported_size: Cell::new(ResizeObserverSizeImpl::zero()),
+            });
+        // Make sure the target is laid out, so that its first observation is gathered.
+        target.upcast::<Node>().dirty(NodeDamage::OtherNodeDamage);
+    }
+
+    // https://drafts.csswg.org/resize-observer/#dom-resizeobserver-unobserve
+    fn Unobserve(&self, target: &Element) {
+        self.observation_targets
+            .borrow_mut()
+            .retain(|observation| &*observation.target != target);
+    }
+
+    // https://drafts.csswg.org/resize-observer/#dom-resizeobserver-disconnect
+    fn Disconnect(&self) {
+        self.observation_targets.borrow_mut().clear();
+        self.active_targets.borrow_mut().clear();
+        self.skipped_targets.borrow_mut().clear();
+    }
+}
@@ -0,0 +1,133 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::dom::bindings::cell::DomRefCell;
+use crate::dom::bindings::codegen::Bindings::ResizeObserverEntryBinding::{
+    ResizeObserverEntryMethods, Wrap,
+};
+use crate::dom::bindings::reflector::{reflect_dom_object, Reflector};
+use crate::dom::bindings::root::{Dom, DomRoot};
+use crate::dom::bindings::utils::to_frozen_array;
+use crate::dom::domrectreadonly::DOMRectReadOnly;
+use crate::dom::element::Element;
+use crate::dom::resizeobserversize::ResizeObserverSize;
+use crate::dom::window::Window;
+use crate::script_runtime::JSContext;
+use dom_struct::dom_struct;
+use js::jsapi::Heap;
+use js::jsval::JSVal;
+
+// https://drafts.csswg.org/resize-observer/#resize-observer-entry-interface
+#[dom_struct]
+pub struct ResizeObserverEntry {
+    reflector_: Reflector,
+    target: Dom<Element>,
+    content_rect: Dom<DOMRectReadOnly>,
+    border_box_size: Vec<Dom<ResizeObserverSize>>,
+    content_box_size: Vec<Dom<ResizeObserverSize>>,
+    device_pixel_content_box_size: Vec<Dom<ResizeObserverSize>>,
+    #[ignore_malloc_size_of = "mozjs"]
+    frozen_border_box_size: DomRefCell<Option<Heap<JSVal>>>,
+    #[ignore_malloc_size_of = "mozjs"]
+    frozen_content_box_size: DomRefCell<Option<Heap<JSVal>>>,
+    #[ignore_malloc_size_of = "mozjs"]
+    frozen_device_pixel_content_box_size: DomRefCell<Option<Heap<JSVal>>>,
+}
+
+/// The sizes, frozen into an array the first time they are asked for.
+fn frozen_sizes(
+    cache: &DomRefCell<Option<Heap<JSVal>>>,
+    sizes: &[Dom<ResizeObserverSize>],
+    cx: JSContext,
+) -> JSVal {
+    if let Some(sizes) = &*cache.borrow() {
+        return sizes.get();
+    }
+    let sizes: Vec<DomRoot<ResizeObserverSize>> = sizes
+        .iter()
+        .map(|size| DomRoot::from_ref(&**size))
+        .collect();
+    let frozen_sizes = to_frozen_array(sizes.as_slice(), cx);
+    let heap_val = Heap::default();
+    heap_val.set(frozen_sizes);
+    *cache.borrow_mut() = Some(heap_val);
+    frozen_sizes
+}
+
+impl ResizeObserverEntry {
+    #[allow(unrooted_must_root)]
+    fn new_inherited(
+        target: &Element,
+        content_rect: &DOMRectReadOnly,
+        border_box_size: &[&ResizeObserverSize],
+        content_box_size: &[&ResizeObserverSize],
+        device_pixel_content_box_size: &[&ResizeObserverSize],
+    ) -> ResizeObserverEntry {
+        let sizes =
+            |sizes: &[&ResizeObserverSize]| sizes.iter().map(|size| Dom::from_ref(*size)).collect();
+        ResizeObserverEntry {
+            reflector_: Reflector::new(),
+            target: Dom::from_ref(target),
+            content_rect: Dom::from_ref(content_rect),
+            border_box_size: sizes(border_box_size),
+            content_box_size: sizes(content_box_size),
+            device_pixel_content_box_size: sizes(device_pixel_content_box_size),
+            frozen_border_box_size: DomRefCell::new(None),
+            frozen_content_box_size: DomRefCell::new(None),
+            frozen_device_pixel_content_box_size: DomRefCell::new(None),
+        }
+    }
+
+    pub fn new(
+        window: &Window,
+        target: &Element,
+        content_rect: &DOMRectReadOnly,
+        border_box_size: &[&ResizeObserverSize],
+        content_box_size: &[&ResizeObserverSize],
+        device_pixel_content_box_size: &[&ResizeObserverSize],
+    ) -> DomRoot<ResizeObserverEntry> {
+        reflect_dom_object(
+            Box::new(ResizeObserverEntry::new_inherited(
+                target,
+                content_rect,
+                border_box_size,
+                content_box_size,
+                device_pixel_content_box_size,
+            )),
+            window,
+            Wrap,
+        )
+    }
+}
+
+impl ResizeObserverEntryMethods for ResizeObserverEntry {
+    // https://drafts.csswg.org/resize-observer/#dom-resizeobserverentry-target
+    fn Target(&self) -> DomRoot<Element> {
+        DomRoot::from_ref(&*self.target)
+    }
+
+    // https://drafts.csswg.org/resize-observer/#dom-resizeobserverentry-contentrect
+    fn ContentRect(&self) -> DomRoot<DOMRectReadOnly> {
+        DomRoot::from_ref(&*self.content_rect)
+    }
+
+    // https://drafts.csswg.org/resize-observer/#dom-resizeobserverentry-borderboxsize
+    fn BorderBoxSize(&self, cx: JSContext) -> JSVal {
+        frozen_sizes(&self.frozen_border_box_size, &self.border_box_size, cx)
+    }
+
+    // https://drafts.csswg.org/resize-observer/#dom-resizeobserverentry-contentboxsize
+    fn ContentBoxSize(&self, cx: JSContext) -> JSVal {
+        frozen_sizes(&self.frozen_content_box_size, &self.content_box_size, cx)
+    }
+
+    // https://drafts.csswg.org/resize-observer/#dom-resizeobserverentry-devicepixelcontentboxsize
+    fn DevicePixelContentBoxSize(&self, cx: JSContext) -> JSVal {
+        frozen_sizes(
+            &self.frozen_device_pixel_content_box_size,
+            &self.device_pixel_content_box_size,
+            cx,
+        )
+    }
+}
@@ -2,14 +2,13 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 
-use crate::body::{consume_body, consume_body_with_promise, BodyOperations, BodyType};
+use crate::body::{consume_body, BodyOperations, BodyType};
 use crate::dom::bindings::cell::{DomRefCell, Ref};
 use crate::dom::bindings::codegen::Bindings::HeadersBinding::{HeadersInit, HeadersMethods};
 use crate::dom::bindings::codegen::Bindings::ResponseBinding;
 use crate::dom::bindings::codegen::Bindings::ResponseBinding::{
-    ResponseMethods, ResponseType as DOMResponseType,
+    BodyInit, ResponseMethods, ResponseType as DOMResponseType,
 };
-use crate::dom::bindings::codegen::Bindings::XMLHttpRequestBinding::BodyInit;
 use crate::dom::bindings::error::{Error, Fallible};
 use crate::dom::bindings::reflector::{reflect_dom_object, DomObject, Reflector};
 use crate::dom::bindings::root::{DomRoot, MutNullableDom};
@@ -18,16 +17,18 @@ use crate::dom::globalscope::GlobalScope;
 use crate::dom::headers::{is_obs_text, is_vchar};
 use crate::dom::headers::{Guard, Headers};
 use crate::dom::promise::Promise;
-use crate::dom::xmlhttprequest::Extractable;
+use crate::dom::readablestream::ReadableStream;
+use crate::dom::xmlhttprequest::{extract_body, ExtractedBody};
+use crate::realms::enter_realm;
 use crate::script_runtime::StreamConsumer;
 use dom_struct::dom_struct;
 use http::header::HeaderMap as HyperHeaders;
 use hyper::StatusCode;
 use hyper_serde::Serde;
-use net_traits::response::ResponseBody as NetTraitsResponseBody;
+use net_traits::cache_storage_thread::CachedResponse;
+use net_traits::response::ResponseType;
+use net_traits::NetworkError;
 use servo_url::ServoUrl;
-use std::cell::Cell;
-use std::mem;
 use std::rc::Rc;
 use std::str::FromStr;
 use url::Position;
@@ -37,7 +38,6 @@ pub struct Response {
     reflector_: Reflector,
     headers_reflector: MutNullableDom<Headers>,
     mime_type: DomRefCell<Vec<u8>>,
-    body_used: Cell<bool>,
     /// `None` can be considered a StatusCode of `0`.
     #[ignore_malloc_size_of = "Defined in hyper"]
     status: DomRefCell<Option<StatusCode>>,
@@ -45,10 +45,11 @@ pub struct Response {
     response_type: DomRefCell<DOMResponseType>,
     url: DomRefCell<Option<ServoUrl>>,
     url_list: DomRefCell<Vec<ServoUrl>>,
-    // For now use the existing NetTraitsResponseBody enum
-    body: DomRefCell<NetTraitsResponseBody>,
-    #[ignore_malloc_size_of = "Rc"]
-    body_promise: DomRefCell<Option<(Rc<Promise>, BodyType)>>,
+    /// The stream of the body, which is `None` for a null body.
+    body_stream: MutNullableDom<ReadableStream>,
+    /// The stream into which the chunks of the fetch of this response are
+    /// enqueued, which stays the same when `body_stream` is teed by a clone.
+    fetch_stream: MutNullableDom<ReadableStream>,
     #[ignore_malloc_size_of = "StreamConsumer"]
     stream_consumer: DomRefCell<Option<StreamConsumer>>,
 }
@@ -60,14 +61,13 @@ impl Response {
             reflector_: Reflector::new(),
             headers_reflector: Default::default(),
             mime_type: DomRefCell::new("".to_string().into_bytes()),
-            body_used: Cell::new(false),
             status: DomRefCell::new(Some(StatusCode::OK)),
             raw_status: DomRefCell::new(Some((200, b"".to_vec()))),
             response_type: DomRefCell::new(DOMResponseType::Default),
             url: DomRefCell::new(None),
             url_list: DomRefCell::new(vec![]),
-            body: DomRefCell::new(NetTraitsResponseBody::Empty),
-            body_promise: DomRefCell::new(None),
+            body_stream: Default::default(),
+            fetch_stream: Default::default(),
             stream_consumer: DomRefCell::new(None),
         }
     }
@@ -130,8 +130,24 @@ impl Response {
             };
 
             // Step 7.3
-            let (extracted_body, content_type) = body.extract();
-            *r.body.borrow_mut() = NetTraitsResponseBody::Done(extracted_body);
+            let content_type = match extract_body(body) {
+                ExtractedBody::Stream(stream) => {
+                    if stream.is_disturbed() || stream.is_locked() {
+                        return Err(Error::Type(
+                            "Body stream is disturbed or locked".to_string(),
+                        ));
+                    }
+                    r.body_stream.set(Some(&stream));
+                    None
+                },
+                ExtractedBody::Bytes(extracted_body, content_type) => {
+                    r.body_stream.set(Some(&ReadableStream::new_from_bytes(
+                        global,
+                        extracted_body,
+                    )));
+                    content_type
+                },
+            };
 
             // Step 7.4
             if let Some(content_type_contents) = content_type {
@@ -212,39 +228,11 @@ impl Response {
         // Step 7
         Ok(r)
     }
-
-    // https://fetch.spec.whatwg.org/#concept-body-locked
-    fn locked(&self) -> bool {
-        // TODO: ReadableStream is unimplemented. Just return false
-        // for now.
-        false
-    }
 }
 
 impl BodyOperations for Response {
-    fn get_body_used(&self) -> bool {
-        self.BodyUsed()
-    }
-
-    fn set_body_promise(&self, p: &Rc<Promise>, body_type: BodyType) {
-        assert!(self.body_promise.borrow().is_none());
-        self.body_used.set(true);
-        *self.body_promise.borrow_mut() = Some((p.clone(), body_type));
-    }
-
-    fn is_locked(&self) -> bool {
-        self.locked()
-    }
-
-    fn take_body(&self) -> Option<Vec<u8>> {
-        let body = mem::replace(&mut *self.body.borrow_mut(), NetTraitsResponseBody::Empty);
-        match body {
-            NetTraitsResponseBody::Done(bytes) => Some(bytes),
-            body => {
-                mem::replace(&mut *self.body.borrow_mut(), body);
-                None
-            },
-        }
+    fn get_body(&self) -> Option<DomRoot<ReadableStream>> {
+        self.body_stream.get()
     }
 
     fn get_mime_type(&self) -> Ref<Vec<u8>> {
@@ -331,7 +319,7 @@ impl ResponseMethods for Response {
     // https://fetch.spec.whatwg.org/#dom-response-clone
     fn Clone(&self) -> Fallible<DomRoot<Response>> {
         // Step 1
-        if self.is_locked() || self.body_used.get() {
+        if self.is_locked() || self.get_body_used() {
             return Err(Error::Type("cannot clone a disturbed response".to_string()));
         }
 
@@ -351,12 +339,14 @@ impl ResponseMethods for Response {
         *new_response.url.borrow_mut() = self.url.borrow().clone();
         *new_response.url_list.borrow_mut() = self.url_list.borrow().clone();
 
-        if *self.body.borrow() != NetTraitsResponseBody::Empty {
-            *new_response.body.borrow_mut() = self.body.borrow().clone();
-        }
+        *new_response.mime_type.borrow_mut() = self.mime_type.borrow().clone();
 
-        // Step 3
-        // TODO: This step relies on promises, which are still unimplemented.
+        // https://fetch.spec.whatwg.org/#concept-body-clone
+        if let Some(stream) = self.body_stream.get() {
+            let branches = stream.tee()?;
+            self.body_stream.set(Some(&branches[0]));
+            new_response.body_stream.set(Some(&branches[1]));
+        }
 
         // Step 4
         Ok(new_response)
@@ -364,7 +354,12 @@ impl ResponseMethods for Response {
 
     // https://fetch.spec.whatwg.org/#dom-body-bodyused
     fn BodyUsed(&self) -> bool {
-        self.body_used.get()
+        self.get_body_used()
+    }
+
+    // https://fetch.spec.whatwg.org/#dom-body-body
+    fn GetBody(&self) -> Option<DomRoot<ReadableStream>> {
+        self.body_stream.get()
     }
 
     // https://fetch.spec.whatwg.org/#dom-body-text
@@ -425,20 +420,20 @@ impl Response {
                 *self.status.borrow_mut() = None;
                 self.set_raw_status(None);
                 self.set_headers(None);
-                *self.body.borrow_mut() = NetTraitsResponseBody::Done(vec![]);
+                self.body_stream.set(None);
             },
             DOMResponseType::Opaque => {
                 *self.url_list.borrow_mut() = vec![];
                 *self.status.borrow_mut() = None;
                 self.set_raw_status(None);
                 self.set_headers(None);
-                *self.body.borrow_mut() = NetTraitsResponseBody::Done(vec![]);
+                self.body_stream.set(None);
             },
             DOMResponseType::Opaqueredirect => {
                 *self.status.borrow_mut() = None;
                 self.set_raw_status(None);
                 self.set_headers(None);
-                *self.body.borrow_mut() = NetTraitsResponseBody::Done(vec![]);
+                self.body_stream.set(None);
             },
             DOMResponseType::Default => {},
             DOMResponseType::Basic => {},
@@ -446,21 +441,102 @@ impl Response {
         }
     }
 
+    /// What a cache keeps of this response, given the bytes of its body.
+    pub fn cached_response(&self, body: Option<Vec<u8>>) -> CachedResponse {
+        let response_type = match *self.response_type.borrow() {
+            DOMResponseType::Basic => ResponseType::Basic,
+            DOMResponseType::Cors => ResponseType::Cors,
+            DOMResponseType::Default => ResponseType::Default,
+            DOMResponseType::Error => {
+                ResponseType::Error(NetworkError::Internal("Error response".to_owned()))
+            },
+            DOMResponseType::Opaque => ResponseType::Opaque,
+            DOMResponseType::Opaqueredirect => ResponseType::OpaqueRedirect,
+        };
+        let mut url_list = self.url_list.borrow().clone();
+        if url_list.is_empty() {
+            url_list.extend(self.url.borrow().clone());
+        }
+        CachedResponse {
+            response_type,
+            url_list,
+            raw_status: self.raw_status.borrow().clone(),
+            headers: self.Headers().get_headers_list(),
+            body,
+        }
+    }
+
+    /// A response for an entry of a cache, whose headers can't be modified.
+    pub fn from_cached_response(global: &GlobalScope, cached: CachedResponse) -> DomRoot<Response> {
+        let r = Response::new(global);
+        *r.response_type.borrow_mut() = match cached.response_type {
+            ResponseType::Basic => DOMResponseType::Basic,
+            ResponseType::Cors => DOMResponseType::Cors,
+            ResponseType::Default => DOMResponseType::Default,
+            ResponseType::Error(..) => DOMResponseType::Error,
+            ResponseType::Opaque => DOMResponseType::Opaque,
+            ResponseType::OpaqueRedirect => DOMResponseType::Opaqueredirect,
+        };
+        *r.status.borrow_mut() = cached
+            .raw_status
+            .as_ref()
+            .and_then(|&(code, _)| StatusCode::from_u16(code).ok());
+        r.set_raw_status(cached.raw_status);
+        r.set_headers(Some(Serde(cached.headers)));
+        r.Headers().set_guard(Guard::Immutable);
+        *r.url.borrow_mut() = cached.url_list.last().cloned();
+        *r.url_list.borrow_mut() = cached.url_list;
+        if let Some(body) = cached.body {
+            r.body_stream
+                .set(Some(&ReadableStream::new_from_bytes(global, body)));
+        }
+        r
+    }
+
     pub fn set_stream_consumer(&self, sc: Option<StreamConsumer>) {
         *self.stream_consumer.borrow_mut() = sc;
     }
 
-    pub fn stream_chunk(&self, stream: &[u8]) {
+    /// Give this response a body whose chunks the fetch enqueues as they arrive.
+    pub fn init_fetch_stream(&self) {
+        match *self.response_type.borrow() {
+            DOMResponseType::Error | DOMResponseType::Opaque | DOMResponseType::Opaqueredirect => {
+                return
+            },
+            DOMResponseType::Default | DOMResponseType::Basic | DOMResponseType::Cors => {},
+        }
+        let stream = ReadableStream::new_native(&self.global());
+        self.body_stream.set(Some(&stream));
+        self.fetch_stream.set(Some(&stream));
+    }
+
+    pub fn stream_chunk(&self, chunk: Vec<u8>) {
         if let Some(stream_consumer) = self.stream_consumer.borrow_mut().as_ref() {
-            stream_consumer.consume_chunk(stream);
+            stream_consumer.consume_chunk(&chunk);
+        }
+        if let Some(stream) = self.fetch_stream.get() {
+            stream.enqueue_native(chunk);
+        }
+    }
+
+    /// Error the body of this response once its fetch is aborted.
+    pub fn abort(&self) {
+        if let Some(stream) = self.fetch_stream.take() {
+            let _ac = enter_realm(&*stream);
+            stream.error_native(Error::Abort);
+        }
+    }
+
+    /// Error the body of this response once its fetch fails.
+    pub fn fail(&self) {
+        if let Some(stream) = self.fetch_stream.take() {
+            stream.error_native(Error::Type("Network error occurred".to_string()));
         }
     }
 
-    #[allow(unrooted_must_root)]
-    pub fn finish(&self, body: Vec<u8>) {
-        *self.body.borrow_mut() = NetTraitsResponseBody::Done(body);
-        if let Some((p, body_type)) = self.body_promise.borrow_mut().take() {
-            consume_body_with_promise(self, body_type, &p);
+    pub fn finish(&self) {
+        if let Some(stream) = self.fetch_stream.take() {
+            stream.close_native();
         }
         if let Some(stream_consumer) = self.stream_consumer.borrow_mut().take() {
             stream_consumer.stream_end();
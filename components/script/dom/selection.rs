@@ -0,0 +1,491 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::dom::bindings::cell::DomRefCell;
+use crate::dom::bindings::codegen::Bindings::RangeBinding::RangeMethods;
+use crate::dom::bindings::codegen::Bindings::SelectionBinding::{SelectionMethods, Wrap};
+use crate::dom::bindings::error::{Error, ErrorResult, Fallible};
+use crate::dom::bindings::inheritance::Castable;
+use crate::dom::bindings::reflector::{reflect_dom_object, Reflector};
+use crate::dom::bindings::root::{Dom, DomRoot, MutNullableDom};
+use crate::dom::bindings::str::DOMString;
+use crate::dom::document::Document;
+use crate::dom::node::{Node, ShadowIncluding};
+use crate::dom::range::{bp_position, Range};
+use crate::dom::text::Text;
+use dom_struct::dom_struct;
+use std::cell::Cell;
+use std::cmp::Ordering;
+
+/// <https://w3c.github.io/selection-api/#dfn-direction>
+#[derive(Clone, Copy, JSTraceable, MallocSizeOf, PartialEq)]
+enum Direction {
+    Forwards,
+    Backwards,
+    Directionless,
+}
+
+/// The root of a node, not crossing shadow boundaries.
+fn root_of(node: &Node) -> DomRoot<Node> {
+    node.inclusive_ancestors(ShadowIncluding::No)
+        .last()
+        .unwrap()
+}
+
+/// The text nodes in a range, with the part of each of them that the range covers.
+pub fn selected_text(range: &Range) -> Vec<(DomRoot<Text>, (u32, u32))> {
+    let start_node = range.StartContainer();
+    let start_offset = range.StartOffset();
+    let end_node = range.EndContainer();
+    let end_offset = range.EndOffset();
+
+    let mut selected = vec![];
+    if let Some(text) = start_node.downcast::<Text>() {
+        let end = if start_node == end_node {
+            end_offset
+        } else {
+            start_node.len()
+        };
+        selected.push((DomRoot::from_ref(text), (start_offset, end)));
+    }
+    if start_node != end_node || !start_node.is::<Text>() {
+        let ancestor = range.CommonAncestorContainer();
+        for node in start_node.following_nodes(&ancestor) {
+            if bp_position(&node, 0, &end_node, end_offset) != Some(Ordering::Less) {
+                break;
+            }
+            if bp_position(&node, 0, &start_node, start_offset) == Some(Ordering::Less) {
+                continue;
+            }
+            let end = if node == end_node {
+                end_offset
+            } else {
+                node.len()
+            };
+            if let Some(text) = DomRoot::downcast::<Text>(node) {
+                selected.push((text, (0, end)));
+            }
+        }
+    }
+    selected.retain(|&(_, (start, end))| start < end);
+    selected
+}
+
+// https://w3c.github.io/selection-api/#selection-interface
+#[dom_struct]
+pub struct Selection {
+    reflector_: Reflector,
+    document: Dom<Document>,
+    range: MutNullableDom<Range>,
+    direction: Cell<Direction>,
+    /// The text nodes that layout was last told to highlight.
+    highlighted_nodes: DomRefCell<Vec<Dom<Text>>>,
+}
+
+impl Selection {
+    fn new_inherited(document: &Document) -> Selection {
+        Selection {
+            reflector_: Reflector::new(),
+            document: Dom::from_ref(document),
+            range: MutNullableDom::new(None),
+            direction: Cell::new(Direction::Directionless),
+            highlighted_nodes: DomRefCell::new(vec![]),
+        }
+    }
+
+    pub fn new(document: &Document) -> DomRoot<Selection> {
+        reflect_dom_object(
+            Box::new(Selection::new_inherited(document)),
+            document.window(),
+            Wrap,
+        )
+    }
+
+    pub fn range(&self) -> Option<DomRoot<Range>> {
+        self.range.get()
+    }
+
+    fn set_range(&self, range: Option<&Range>, direction: Direction) {
+        if let Some(old_range) = self.range.get() {
+            old_range.set_associated_selection(None);
+        }
+        if let Some(range) = range {
+            range.set_associated_selection(Some(self));
+        }
+        self.range.set(range);
+        self.direction.set(direction);
+        self.range_changed();
+    }
+
+    /// Called when this selection is associated with another range, or when a boundary
+    /// point of its range moves.
+    pub fn range_changed(&self) {
+        self.document.selection_changed();
+    }
+
+    /// <https://w3c.github.io/selection-api/#dfn-anchor>
+    fn anchor(&self) -> Option<(DomRoot<Node>, u32)> {
+        let range = self.range.get()?;
+        Some(match self.direction.get() {
+            Direction::Backwards => (range.EndContainer(), range.EndOffset()),
+            Direction::Forwards | Direction::Directionless => {
+                (range.StartContainer(), range.StartOffset())
+            },
+        })
+    }
+
+    /// <https://w3c.github.io/selection-api/#dfn-focus>
+    fn focus(&self) -> Option<(DomRoot<Node>, u32)> {
+        let range = self.range.get()?;
+        Some(match self.direction.get() {
+            Direction::Backwards => (range.StartContainer(), range.StartOffset()),
+            Direction::Forwards | Direction::Directionless => {
+                (range.EndContainer(), range.EndOffset())
+            },
+        })
+    }
+
+    /// Whether the document associated with this selection is a shadow-including
+    /// inclusive ancestor of a node.
+    fn is_in_document(&self, node: &Node) -> bool {
+        self.document
+            .upcast::<Node>()
+            .is_shadow_including_inclusive_ancestor_of(node)
+    }
+
+    /// Tells layout which text to highlight, or to draw a caret in when the selection is
+    /// collapsed in editable text, clearing the highlight of the text that is no longer selected.
+    pub fn update_highlight(&self) {
+        let selected = match self.range.get() {
+            Some(ref range) if !range.Collapsed() => selected_text(range),
+            Some(ref range) => {
+                let node = range.StartContainer();
+                match DomRoot::downcast::<Text>(node) {
+                    Some(text) if text.upcast::<Node>().is_editable() => {
+                        let offset = range.StartOffset();
+                        vec![(text, (offset, offset))]
+                    },
+                    _ => vec![],
+                }
+            },
+            None => vec![],
+        };
+        let mut highlighted_nodes = self.highlighted_nodes.borrow_mut();
+        for text in highlighted_nodes.iter() {
+            if !selected.iter().any(|(node, _)| **node == **text) {
+                text.set_selected_range(None);
+            }
+        }
+        for (text, selected_range) in &selected {
+            text.set_selected_range(Some(*selected_range));
+        }
+        *highlighted_nodes = selected
+            .iter()
+            .map(|(text, _)| Dom::from_ref(&**text))
+            .collect();
+    }
+
+    pub fn has_highlight(&self) -> bool {
+        !self.highlighted_nodes.borrow().is_empty()
+    }
+}
+
+impl SelectionMethods for Selection {
+    // https://w3c.github.io/selection-api/#dom-selection-anchornode
+    fn GetAnchorNode(&self) -> Option<DomRoot<Node>> {
+        self.anchor().map(|(node, _)| node)
+    }
+
+    // https://w3c.github.io/selection-api/#dom-selection-anchoroffset
+    fn AnchorOffset(&self) -> u32 {
+        self.anchor().map_or(0, |(_, offset)| offset)
+    }
+
+    // https://w3c.github.io/selection-api/#dom-selection-focusnode
+    fn GetFocusNode(&self) -> Option<DomRoot<Node>> {
+        self.focus().map(|(node, _)| node)
+    }
+
+    // https://w3c.github.io/selection-api/#dom-selection-focusoffset
+    fn FocusOffset(&self) -> u32 {
+        self.focus().map_or(0, |(_, offset)| offset)
+    }
+
+    // https://w3c.github.io/selection-api/#dom-selection-iscollapsed
+    fn IsCollapsed(&self) -> bool {
+        self.range.get().map_or(true, |range| range.Collapsed())
+    }
+
+    // https://w3c.github.io/selection-api/#dom-selection-rangecount
+    fn RangeCount(&self) -> u32 {
+        if self.range.get().is_some() {
+            1
+        } else {
+            0
+        }
+    }
+
+    // https://w3c.github.io/selection-api/#dom-selection-type
+    fn Type(&self) -> DOMString {
+        match self.range.get() {
+            None => DOMString::from("None"),
+            Some(ref range) if range.Collapsed() => DOMString::from("Caret"),
+            Some(_) => DOMString::from("Range"),
+        }
+    }
+
+    // https://w3c.github.io/selection-api/#dom-selection-getrangeat
+    fn GetRangeAt(&self, index: u32) -> Fallible<DomRoot<Range>> {
+        if index != 0 {
+            return Err(Error::IndexSize);
+        }
+        self.range.get().ok_or(Error::IndexSize)
+    }
+
+    // https://w3c.github.io/selection-api/#dom-selection-addrange
+    fn AddRange(&self, range: &Range) {
+        // Step 1.
+        if !self.is_in_document(&range.StartContainer()) {
+            return;
+        }
+        // Step 2.
+        if self.range.get().is_some() {
+            return;
+        }
+        // Step 3.
+        self.set_range(Some(range), Direction::Forwards);
+    }
+
+    // https://w3c.github.io/selection-api/#dom-selection-removerange
+    fn RemoveRange(&self, range: &Range) -> ErrorResult {
+        if self.range.get().as_deref() != Some(range) {
+            return Err(Error::NotFound);
+        }
+        self.set_range(None, Direction::Directionless);
+        Ok(())
+    }
+
+    // https://w3c.github.io/selection-api/#dom-selection-removeallranges
+    fn RemoveAllRanges(&self) {
+        if self.range.get().is_some() {
+            self.set_range(None, Direction::Directionless);
+        }
+    }
+
+    // https://w3c.github.io/selection-api/#dom-selection-empty
+    fn Empty(&self) {
+        self.RemoveAllRanges();
+    }
+
+    // https://w3c.github.io/selection-api/#dom-selection-collapse
+    fn Collapse(&self, node: Option<&Node>, offset: u32) -> ErrorResult {
+        // Step 1.
+        let node = match node {
+            Some(node) => node,
+            None => {
+                self.RemoveAllRanges();
+                return Ok(());
+            },
+        };
+        // Step 2.
+        if node.is_doctype() {
+            return Err(Error::InvalidNodeType);
+        }
+        // Step 3.
+        if offset > node.len() {
+            return Err(Error::IndexSize);
+        }
+        // Step 4.
+        if !self.is_in_document(node) {
+            return Ok(());
+        }
+        // Steps 5-7.
+        let range = Range::new(&self.document, node, offset, node, offset);
+        self.set_range(Some(&range), Direction::Directionless);
+        Ok(())
+    }
+
+    // https://w3c.github.io/selection-api/#dom-selection-setposition
+    fn SetPosition(&self, node: Option<&Node>, offset: u32) -> ErrorResult {
+        self.Collapse(node, offset)
+    }
+
+    // https://w3c.github.io/selection-api/#dom-selection-collapsetostart
+    fn CollapseToStart(&self) -> ErrorResult {
+        // Step 1.
+        let range = self.range.get().ok_or(Error::InvalidState)?;
+        // Steps 2-3.
+        let start = range.StartContainer();
+        let new_range = Range::new(
+            &self.document,
+            &start,
+            range.StartOffset(),
+            &start,
+            range.StartOffset(),
+        );
+        self.set_range(Some(&new_range), Direction::Directionless);
+        Ok(())
+    }
+
+    // https://w3c.github.io/selection-api/#dom-selection-collapsetoend
+    fn CollapseToEnd(&self) -> ErrorResult {
+        // Step 1.
+        let range = self.range.get().ok_or(Error::InvalidState)?;
+        // Steps 2-3.
+        let end = range.EndContainer();
+        let new_range = Range::new(
+            &self.document,
+            &end,
+            range.EndOffset(),
+            &end,
+            range.EndOffset(),
+        );
+        self.set_range(Some(&new_range), Direction::Directionless);
+        Ok(())
+    }
+
+    // https://w3c.github.io/selection-api/#dom-selection-extend
+    fn Extend(&self, node: &Node, offset: u32) -> ErrorResult {
+        // Step 1.
+        if !self.is_in_document(node) {
+            return Ok(());
+        }
+        // Step 2.
+        let range = self.range.get().ok_or(Error::InvalidState)?;
+        if node.is_doctype() {
+            return Err(Error::InvalidNodeType);
+        }
+        if offset > node.len() {
+            return Err(Error::IndexSize);
+        }
+        // Steps 3-4.
+        let (anchor_node, anchor_offset) = self.anchor().unwrap();
+        // Steps 5-8.
+        let focus_is_before_anchor =
+            bp_position(node, offset, &anchor_node, anchor_offset) == Some(Ordering::Less);
+        let new_range = if root_of(node) != root_of(&range.StartContainer()) {
+            Range::new(&self.document, node, offset, node, offset)
+        } else if focus_is_before_anchor {
+            Range::new(&self.document, node, offset, &anchor_node, anchor_offset)
+        } else {
+            Range::new(&self.document, &anchor_node, anchor_offset, node, offset)
+        };
+        // Steps 9-10.
+        let direction = if focus_is_before_anchor {
+            Direction::Backwards
+        } else {
+            Direction::Forwards
+        };
+        self.set_range(Some(&new_range), direction);
+        Ok(())
+    }
+
+    // https://w3c.github.io/selection-api/#dom-selection-setbaseandextent
+    fn SetBaseAndExtent(
+        &self,
+        anchor_node: &Node,
+        anchor_offset: u32,
+        focus_node: &Node,
+        focus_offset: u32,
+    ) -> ErrorResult {
+        // Step 1.
+        if anchor_node.is_doctype() || focus_node.is_doctype() {
+            return Err(Error::InvalidNodeType);
+        }
+        if anchor_offset > anchor_node.len() || focus_offset > focus_node.len() {
+            return Err(Error::IndexSize);
+        }
+        // Step 2.
+        if !self.is_in_document(anchor_node) || !self.is_in_document(focus_node) {
+            return Ok(());
+        }
+        // Steps 3-6.
+        let focus_is_before_anchor =
+            bp_position(focus_node, focus_offset, anchor_node, anchor_offset) ==
+                Some(Ordering::Less);
+        let new_range = if focus_is_before_anchor {
+            Range::new(
+                &self.document,
+                focus_node,
+                focus_offset,
+                anchor_node,
+                anchor_offset,
+            )
+        } else {
+            Range::new(
+                &self.document,
+                anchor_node,
+                anchor_offset,
+                focus_node,
+                focus_offset,
+            )
+        };
+        // Steps 7-8.
+        let direction = if focus_is_before_anchor {
+            Direction::Backwards
+        } else {
+            Direction::Forwards
+        };
+        self.set_range(Some(&new_range), direction);
+        Ok(())
+    }
+
+    // https://w3c.github.io/selection-api/#dom-selection-selectallchildren
+    fn SelectAllChildren(&self, node: &Node) -> ErrorResult {
+        // Step 1.
+        if node.is_doctype() {
+            return Err(Error::InvalidNodeType);
+        }
+        // Step 2.
+        if !self.is_in_document(node) {
+            return Ok(());
+        }
+        // Steps 3-6.
+        let range = Range::new(&self.document, node, 0, node, node.children_count());
+        // Steps 7-8.
+        self.set_range(Some(&range), Direction::Forwards);
+        Ok(())
+    }
+
+    // https://w3c.github.io/selection-api/#dom-selection-deletefromdocument
+    fn DeleteFromDocument(&self) -> ErrorResult {
+        match self.range.get() {
+            Some(range) => range.DeleteContents(),
+            None => Ok(()),
+        }
+    }
+
+    // https://w3c.github.io/selection-api/#dom-selection-containsnode
+    fn ContainsNode(&self, node: &Node, allow_partial_containment: bool) -> bool {
+        // Step 1.
+        let range = match self.range.get() {
+            Some(range) => range,
+            None => return false,
+        };
+        if root_of(node) != root_of(&range.StartContainer()) {
+            return false;
+        }
+        // Steps 2-4.
+        let start_is_before = |offset| {
+            bp_position(&range.StartContainer(), range.StartOffset(), node, offset) !=
+                Some(Ordering::Greater)
+        };
+        let end_is_after = |offset| {
+            bp_position(&range.EndContainer(), range.EndOffset(), node, offset) !=
+                Some(Ordering::Less)
+        };
+        if allow_partial_containment {
+            start_is_before(node.len()) && end_is_after(0)
+        } else {
+            start_is_before(0) && end_is_after(node.len())
+        }
+    }
+
+    // https://w3c.github.io/selection-api/#dom-selection-stringifier
+    fn Stringifier(&self) -> DOMString {
+        self.range
+            .get()
+            .map_or_else(DOMString::new, |range| range.Stringifier())
+    }
+}
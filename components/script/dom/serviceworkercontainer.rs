@@ -2,11 +2,14 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 
+use crate::dom::bindings::cell::DomRefCell;
+use crate::dom::bindings::codegen::Bindings::ServiceWorkerBinding::ServiceWorkerState;
 use crate::dom::bindings::codegen::Bindings::ServiceWorkerContainerBinding::RegistrationOptions;
 use crate::dom::bindings::codegen::Bindings::ServiceWorkerContainerBinding::{
     ServiceWorkerContainerMethods, Wrap,
 };
 use crate::dom::bindings::error::Error;
+use crate::dom::bindings::inheritance::Castable;
 use crate::dom::bindings::reflector::{reflect_dom_object, DomObject};
 use crate::dom::bindings::root::{Dom, DomRoot, MutNullableDom};
 use crate::dom::bindings::str::USVString;
@@ -15,10 +18,12 @@ use crate::dom::eventtarget::EventTarget;
 use crate::dom::globalscope::GlobalScope;
 use crate::dom::promise::Promise;
 use crate::dom::serviceworker::ServiceWorker;
+use crate::dom::serviceworkerregistration::ServiceWorkerRegistration;
 use crate::realms::InRealm;
 use crate::script_thread::ScriptThread;
 use crate::serviceworkerjob::{Job, JobType};
 use dom_struct::dom_struct;
+use servo_url::ServoUrl;
 use std::default::Default;
 use std::rc::Rc;
 
@@ -27,6 +32,8 @@ pub struct ServiceWorkerContainer {
     eventtarget: EventTarget,
     controller: MutNullableDom<ServiceWorker>,
     client: Dom<Client>,
+    #[ignore_malloc_size_of = "Rc"]
+    ready: DomRefCell<Option<Rc<Promise>>>,
 }
 
 impl ServiceWorkerContainer {
@@ -35,6 +42,7 @@ impl ServiceWorkerContainer {
             eventtarget: EventTarget::new_inherited(),
             controller: Default::default(),
             client: Dom::from_ref(client),
+            ready: DomRefCell::new(None),
         }
     }
 
@@ -42,7 +50,47 @@ impl ServiceWorkerContainer {
     pub fn new(global: &GlobalScope) -> DomRoot<ServiceWorkerContainer> {
         let client = Client::new(&global.as_window());
         let container = ServiceWorkerContainer::new_inherited(&*client);
-        reflect_dom_object(Box::new(container), global, Wrap)
+        let container = reflect_dom_object(Box::new(container), global, Wrap);
+        // The window may have been controlled since it was created.
+        if let Some((scope, script_url)) =
+            ScriptThread::get_service_worker_controller(global.pipeline_id())
+        {
+            container.set_controller(scope, script_url, false);
+        }
+        container
+    }
+
+    /// Make the service worker of `scope` control the window, firing `controllerchange`
+    /// if the service worker claimed it.
+    pub fn set_controller(&self, scope: ServoUrl, script_url: ServoUrl, claimed: bool) {
+        let worker = ServiceWorker::install_serviceworker(&self.global(), script_url, scope, false);
+        worker.set_transition_state(ServiceWorkerState::Activated);
+        self.client.set_controller(&worker);
+        if claimed {
+            // https://w3c.github.io/ServiceWorker/#notify-controller-change-algorithm
+            self.upcast::<EventTarget>()
+                .fire_event(atom!("controllerchange"));
+        }
+    }
+
+    /// Resolve the `ready` promise once the registration of the window has an
+    /// active worker.
+    /// <https://w3c.github.io/ServiceWorker/#navigator-service-worker-ready>
+    pub fn resolve_ready(&self) {
+        let promise = match *self.ready.borrow() {
+            Some(ref promise) => promise.clone(),
+            None => return,
+        };
+        if promise.is_fulfilled() {
+            return;
+        }
+        if let Some(registration) =
+            ScriptThread::get_registration_for_url(&self.client.creation_url())
+        {
+            if registration.active().is_some() {
+                promise.resolve_native(&*registration);
+            }
+        }
     }
 }
 
@@ -52,6 +100,59 @@ impl ServiceWorkerContainerMethods for ServiceWorkerContainer {
         self.client.get_controller()
     }
 
+    // https://w3c.github.io/ServiceWorker/#navigator-service-worker-ready
+    fn Ready(&self, comp: InRealm) -> Rc<Promise> {
+        let promise = self
+            .ready
+            .borrow_mut()
+            .get_or_insert_with(|| Promise::new_in_current_realm(&self.global(), comp))
+            .clone();
+        self.resolve_ready();
+        promise
+    }
+
+    // https://w3c.github.io/ServiceWorker/#navigator-service-worker-getRegistration
+    fn GetRegistration(&self, client_url: USVString, comp: InRealm) -> Rc<Promise> {
+        // Step 1
+        let promise = Promise::new_in_current_realm(&*self.global(), comp);
+        // Steps 2-4
+        let client_url = match self.global().api_base_url().join(&client_url.0) {
+            Ok(url) => url,
+            Err(_) => {
+                promise.reject_error(Error::Type("Invalid client URL".to_owned()));
+                return promise;
+            },
+        };
+        // Step 5
+        if client_url.origin() != self.client.creation_url().origin() {
+            promise.reject_error(Error::Security);
+            return promise;
+        }
+        // Steps 6-7
+        match ScriptThread::get_registration_for_url(&client_url) {
+            Some(registration) => promise.resolve_native(&*registration),
+            None => promise.resolve_native(&()),
+        }
+        promise
+    }
+
+    // https://w3c.github.io/ServiceWorker/#navigator-service-worker-getRegistrations
+    fn GetRegistrations(&self, comp: InRealm) -> Rc<Promise> {
+        let promise = Promise::new_in_current_realm(&*self.global(), comp);
+        let origin = self.client.creation_url().origin();
+        let registrations: Vec<DomRoot<ServiceWorkerRegistration>> =
+            ScriptThread::get_registrations(&origin);
+        promise.resolve_native(&registrations);
+        promise
+    }
+
+    // https://w3c.github.io/ServiceWorker/#dom-serviceworkercontainer-oncontrollerchange
+    event_handler!(
+        controllerchange,
+        GetOncontrollerchange,
+        SetOncontrollerchange
+    );
+
     #[allow(unrooted_must_root)] // Job is unrooted
     /// https://w3c.github.io/ServiceWorker/#navigator-service-worker-register and - A
     /// https://w3c.github.io/ServiceWorker/#start-register-algorithm - B
@@ -9,22 +9,27 @@ use crate::dom::bindings::codegen::Bindings::ServiceWorkerGlobalScopeBinding;
 use crate::dom::bindings::codegen::Bindings::ServiceWorkerGlobalScopeBinding::ServiceWorkerGlobalScopeMethods;
 use crate::dom::bindings::codegen::Bindings::WorkerBinding::WorkerType;
 use crate::dom::bindings::inheritance::Castable;
-use crate::dom::bindings::root::{DomRoot, RootCollection, ThreadLocalStackRoots};
+use crate::dom::bindings::reflector::DomObject;
+use crate::dom::bindings::root::{DomRoot, MutNullableDom, RootCollection, ThreadLocalStackRoots};
 use crate::dom::bindings::str::DOMString;
 use crate::dom::bindings::structuredclone;
+use crate::dom::clients::Clients;
 use crate::dom::dedicatedworkerglobalscope::AutoWorkerReset;
 use crate::dom::event::Event;
-use crate::dom::eventtarget::EventTarget;
 use crate::dom::extendableevent::ExtendableEvent;
 use crate::dom::extendablemessageevent::ExtendableMessageEvent;
+use crate::dom::fetchevent::FetchEvent;
 use crate::dom::globalscope::GlobalScope;
 use crate::dom::messageevent::MessageEvent;
+use crate::dom::promise::Promise;
+use crate::dom::request::Request;
+use crate::dom::serviceworkerregistration::ServiceWorkerRegistration;
 use crate::dom::worker::TrustedWorkerAddress;
 use crate::dom::workerglobalscope::WorkerGlobalScope;
 use crate::fetch::load_whole_resource;
-use crate::realms::enter_realm;
+use crate::realms::{enter_realm, InRealm};
 use crate::script_runtime::{
-    new_rt_and_cx, CommonScriptMsg, JSContext as SafeJSContext, Runtime, ScriptChan,
+    new_rt_and_cx, CommonScriptMsg, JSContext as SafeJSContext, Runtime, ScriptChan, ScriptPort,
 };
 use crate::task_queue::{QueuedTask, QueuedTaskConversion, TaskQueue};
 use crate::task_source::TaskSourceName;
@@ -37,11 +42,16 @@ use js::jsapi::{JSContext, JS_AddInterruptCallback};
 use js::jsval::UndefinedValue;
 use msg::constellation_msg::PipelineId;
 use net_traits::request::{CredentialsMode, Destination, ParserMetadata, Referrer, RequestBuilder};
+use net_traits::request::{RequestMode, ServiceWorkersMode};
 use net_traits::{CustomResponseMediator, IpcSend};
-use script_traits::{ScopeThings, ServiceWorkerMsg, WorkerGlobalScopeInit, WorkerScriptLoadOrigin};
+use script_traits::{ScopeThings, ServiceWorkerLifecycleState, ServiceWorkerMsg};
+use script_traits::{WorkerGlobalScopeInit, WorkerScriptLoadOrigin};
+use servo_atoms::Atom;
 use servo_config::pref;
 use servo_rand::random;
 use servo_url::ServoUrl;
+use std::cell::Cell;
+use std::rc::Rc;
 use std::thread;
 use std::time::{Duration, Instant};
 use style::thread_state::{self, ThreadState};
@@ -52,6 +62,10 @@ pub enum ServiceWorkerScriptMsg {
     CommonWorker(WorkerScriptMsg),
     /// Message to request a custom response by the service worker
     Response(CustomResponseMediator),
+    /// Fire the install event
+    Install,
+    /// Fire the activate event
+    Activate,
     /// Wake-up call from the task queue.
     WakeUp,
 }
@@ -139,6 +153,18 @@ impl ScriptChan for ServiceWorkerChan {
     }
 }
 
+impl ScriptPort for Receiver<ServiceWorkerScriptMsg> {
+    fn recv(&self) -> Result<CommonScriptMsg, ()> {
+        match self.recv() {
+            Ok(ServiceWorkerScriptMsg::CommonWorker(WorkerScriptMsg::Common(script_msg))) => {
+                Ok(script_msg)
+            },
+            Err(_) => Err(()),
+            Ok(_) => panic!("unexpected service worker event message!"),
+        }
+    }
+}
+
 unsafe_no_jsmanaged_fields!(TaskQueue<ServiceWorkerScriptMsg>);
 
 #[dom_struct]
@@ -162,6 +188,18 @@ pub struct ServiceWorkerGlobalScope {
     swmanager_sender: IpcSender<ServiceWorkerMsg>,
 
     scope_url: ServoUrl,
+
+    /// The URL the service worker was registered with.
+    script_url: ServoUrl,
+
+    state: Cell<ServiceWorkerLifecycleState>,
+
+    registration: MutNullableDom<ServiceWorkerRegistration>,
+
+    clients: MutNullableDom<Clients>,
+
+    /// Whether the service worker called `skipWaiting`.
+    skip_waiting: Cell<bool>,
 }
 
 impl WorkerEventLoopMethods for ServiceWorkerGlobalScope {
@@ -200,6 +238,8 @@ impl ServiceWorkerGlobalScope {
         time_out_port: Receiver<Instant>,
         swmanager_sender: IpcSender<ServiceWorkerMsg>,
         scope_url: ServoUrl,
+        script_url: ServoUrl,
+        state: ServiceWorkerLifecycleState,
     ) -> ServiceWorkerGlobalScope {
         ServiceWorkerGlobalScope {
             workerglobalscope: WorkerGlobalScope::new_inherited(
@@ -216,6 +256,11 @@ impl ServiceWorkerGlobalScope {
             time_out_port,
             swmanager_sender: swmanager_sender,
             scope_url: scope_url,
+            script_url,
+            state: Cell::new(state),
+            registration: Default::default(),
+            clients: Default::default(),
+            skip_waiting: Cell::new(false),
         }
     }
 
@@ -230,6 +275,8 @@ impl ServiceWorkerGlobalScope {
         time_out_port: Receiver<Instant>,
         swmanager_sender: IpcSender<ServiceWorkerMsg>,
         scope_url: ServoUrl,
+        script_url: ServoUrl,
+        state: ServiceWorkerLifecycleState,
     ) -> DomRoot<ServiceWorkerGlobalScope> {
         let cx = runtime.cx();
         let scope = Box::new(ServiceWorkerGlobalScope::new_inherited(
@@ -242,6 +289,8 @@ impl ServiceWorkerGlobalScope {
             time_out_port,
             swmanager_sender,
             scope_url,
+            script_url,
+            state,
         ));
         unsafe { ServiceWorkerGlobalScopeBinding::Wrap(SafeJSContext::from_ptr(cx), scope) }
     }
@@ -255,6 +304,7 @@ impl ServiceWorkerGlobalScope {
         devtools_receiver: IpcReceiver<DevtoolScriptControlMsg>,
         swmanager_sender: IpcSender<ServiceWorkerMsg>,
         scope_url: ServoUrl,
+        state: ServiceWorkerLifecycleState,
     ) {
         let ScopeThings {
             script_url,
@@ -264,11 +314,7 @@ impl ServiceWorkerGlobalScope {
         } = scope_things;
 
         let serialized_worker_url = script_url.to_string();
-        let origin = GlobalScope::current()
-            .expect("No current global object")
-            .origin()
-            .immutable()
-            .clone();
+        let origin = script_url.origin();
         thread::Builder::new()
             .name(format!("ServiceWorker for {}", serialized_worker_url))
             .spawn(move || {
@@ -284,6 +330,7 @@ impl ServiceWorkerGlobalScope {
 
                 let referrer = referrer_url.map(|referrer_url| Referrer::ReferrerUrl(referrer_url));
 
+                // The script of a service worker never goes through a service worker.
                 let request = RequestBuilder::new(script_url.clone())
                     .destination(Destination::ServiceWorker)
                     .credentials_mode(CredentialsMode::Include)
@@ -292,22 +339,9 @@ impl ServiceWorkerGlobalScope {
                     .pipeline_id(Some(pipeline_id))
                     .referrer(referrer)
                     .referrer_policy(referrer_policy)
+                    .service_workers_mode(ServiceWorkersMode::None)
                     .origin(origin);
 
-                let (url, source) = match load_whole_resource(
-                    request,
-                    &init.resource_threads.sender(),
-                    &GlobalScope::current().expect("No current global object"),
-                ) {
-                    Err(_) => {
-                        println!("error loading script {}", serialized_worker_url);
-                        return;
-                    },
-                    Ok((metadata, bytes)) => {
-                        (metadata.final_url, String::from_utf8(bytes).unwrap())
-                    },
-                };
-
                 let runtime = new_rt_and_cx(None);
 
                 let (devtools_mpsc_chan, devtools_mpsc_port) = unbounded();
@@ -319,9 +353,10 @@ impl ServiceWorkerGlobalScope {
                 let sw_lifetime_timeout = pref!(dom.serviceworker.timeout_seconds) as u64;
                 let time_out_port = after(Duration::new(sw_lifetime_timeout, 0));
 
+                let resource_threads_sender = init.resource_threads.sender();
                 let global = ServiceWorkerGlobalScope::new(
                     init,
-                    url,
+                    script_url.clone(),
                     devtools_mpsc_port,
                     runtime,
                     own_sender,
@@ -329,9 +364,27 @@ impl ServiceWorkerGlobalScope {
                     time_out_port,
                     swmanager_sender,
                     scope_url,
+                    script_url,
+                    state,
                 );
                 let scope = global.upcast::<WorkerGlobalScope>();
 
+                let (url, source) =
+                    match load_whole_resource(request, &resource_threads_sender, global.upcast()) {
+                        Err(_) => {
+                            println!("error loading script {}", serialized_worker_url);
+                            // A worker whose script can't be fetched is never installed.
+                            if state == ServiceWorkerLifecycleState::Installing {
+                                global.lifecycle_event_finished(atom!("install"), false);
+                            }
+                            return;
+                        },
+                        Ok((metadata, bytes)) => {
+                            (metadata.final_url, String::from_utf8(bytes).unwrap())
+                        },
+                    };
+                scope.set_url(url);
+
                 unsafe {
                     // Handle interrupt requests
                     JS_AddInterruptCallback(*scope.get_cx(), Some(interrupt_callback));
@@ -339,7 +392,6 @@ impl ServiceWorkerGlobalScope {
 
                 scope.execute_script(DOMString::from(source));
 
-                global.dispatch_activate();
                 let reporter_name = format!("service-worker-reporter-{}", random::<u64>());
                 scope
                     .upcast::<GlobalScope>()
@@ -421,13 +473,15 @@ impl ServiceWorkerGlobalScope {
             CommonWorker(WorkerScriptMsg::Common(msg)) => {
                 self.upcast::<WorkerGlobalScope>().process_event(msg);
             },
-            Response(mediator) => {
-                // TODO XXXcreativcoder This will eventually use a FetchEvent interface to fire event
-                // when we have the Request and Response dom api's implemented
-                // https://w3c.github.io/ServiceWorker/#fetchevent-interface
-                self.upcast::<EventTarget>().fire_event(atom!("fetch"));
-                let _ = mediator.response_chan.send(None);
-            },
+            Response(mediator) => self.dispatch_fetch(mediator),
+            Install => self.dispatch_lifecycle_event(
+                atom!("install"),
+                ServiceWorkerLifecycleState::Installing,
+            ),
+            Activate => self.dispatch_lifecycle_event(
+                atom!("activate"),
+                ServiceWorkerLifecycleState::Activating,
+            ),
             WakeUp => {},
         }
     }
@@ -438,10 +492,85 @@ impl ServiceWorkerGlobalScope {
         })
     }
 
-    fn dispatch_activate(&self) {
-        let event = ExtendableEvent::new(self, atom!("activate"), false, false);
-        let event = (&*event).upcast::<Event>();
-        self.upcast::<EventTarget>().dispatch_event(event);
+    pub fn new_script_pair(&self) -> (Box<dyn ScriptChan + Send>, Box<dyn ScriptPort + Send>) {
+        let (tx, rx) = unbounded();
+        (Box::new(ServiceWorkerChan { sender: tx }), Box::new(rx))
+    }
+
+    pub fn swmanager_sender(&self) -> &IpcSender<ServiceWorkerMsg> {
+        &self.swmanager_sender
+    }
+
+    pub fn scope_url(&self) -> &ServoUrl {
+        &self.scope_url
+    }
+
+    fn set_state(&self, state: ServiceWorkerLifecycleState) {
+        self.state.set(state);
+        if let Some(registration) = self.registration.get() {
+            registration.update_state(state);
+        }
+    }
+
+    /// Fire the install or activate event, and wait for the promises passed to `waitUntil`.
+    /// <https://w3c.github.io/ServiceWorker/#install-algorithm>
+    /// <https://w3c.github.io/ServiceWorker/#activation-algorithm>
+    fn dispatch_lifecycle_event(&self, type_: Atom, state: ServiceWorkerLifecycleState) {
+        self.set_state(state);
+        let event = ExtendableEvent::new(self, type_, false, false);
+        event.upcast::<Event>().fire(self.upcast());
+        event.finish_dispatch();
+    }
+
+    /// Tell the service worker manager that the install or activate event, and the
+    /// promises passed to its `waitUntil`, settled.
+    pub fn lifecycle_event_finished(&self, type_: Atom, succeeded: bool) {
+        let msg = match type_ {
+            atom!("install") => {
+                if succeeded {
+                    self.set_state(ServiceWorkerLifecycleState::Installed);
+                } else {
+                    self.set_state(ServiceWorkerLifecycleState::Redundant);
+                }
+                ServiceWorkerMsg::InstallFinished(self.scope_url.clone(), succeeded)
+            },
+            atom!("activate") => {
+                self.set_state(ServiceWorkerLifecycleState::Activated);
+                ServiceWorkerMsg::ActivateFinished(self.scope_url.clone())
+            },
+            _ => return,
+        };
+        let _ = self.swmanager_sender.send(msg);
+    }
+
+    /// Fire a fetch event for a request of a client of this service worker.
+    /// <https://w3c.github.io/ServiceWorker/#handle-fetch>
+    fn dispatch_fetch(&self, mediator: CustomResponseMediator) {
+        let global = self.upcast::<GlobalScope>();
+        let _ac = enter_realm(global);
+        let request = Request::from_mediator(global, &mediator);
+        let client_id = match mediator.client {
+            Some(pipeline_id) => DOMString::from_string(pipeline_id.to_string()),
+            None => DOMString::new(),
+        };
+        // A navigation creates the client that the service worker responds for.
+        let (client_id, resulting_client_id) = if mediator.mode == RequestMode::Navigate {
+            (DOMString::new(), client_id)
+        } else {
+            (client_id, DOMString::new())
+        };
+        let event = FetchEvent::new(
+            global,
+            atom!("fetch"),
+            false,
+            true,
+            &request,
+            client_id,
+            resulting_client_id,
+        );
+        event.set_response_chan(mediator.response_chan);
+        event.upcast::<Event>().fire(self.upcast());
+        event.finish_dispatch();
     }
 }
 
@@ -461,4 +590,42 @@ impl ServiceWorkerGlobalScopeMethods for ServiceWorkerGlobalScope {
 
     // https://w3c.github.io/ServiceWorker/#dom-serviceworkerglobalscope-onmessageerror
     event_handler!(messageerror, GetOnmessageerror, SetOnmessageerror);
+
+    // https://w3c.github.io/ServiceWorker/#dom-serviceworkerglobalscope-oninstall
+    event_handler!(install, GetOninstall, SetOninstall);
+
+    // https://w3c.github.io/ServiceWorker/#dom-serviceworkerglobalscope-onactivate
+    event_handler!(activate, GetOnactivate, SetOnactivate);
+
+    // https://w3c.github.io/ServiceWorker/#dom-serviceworkerglobalscope-onfetch
+    event_handler!(fetch, GetOnfetch, SetOnfetch);
+
+    // https://w3c.github.io/ServiceWorker/#service-worker-global-scope-clients
+    fn Clients(&self) -> DomRoot<Clients> {
+        self.clients.or_init(|| Clients::new(self))
+    }
+
+    // https://w3c.github.io/ServiceWorker/#service-worker-global-scope-registration
+    fn Registration(&self) -> DomRoot<ServiceWorkerRegistration> {
+        self.registration.or_init(|| {
+            ServiceWorkerRegistration::new(
+                self.upcast(),
+                &self.script_url,
+                self.scope_url.clone(),
+                self.state.get(),
+            )
+        })
+    }
+
+    // https://w3c.github.io/ServiceWorker/#service-worker-global-scope-skipwaiting
+    fn SkipWaiting(&self, comp: InRealm) -> Rc<Promise> {
+        // Step 1
+        let promise = Promise::new_in_current_realm(&self.global(), comp);
+        // Step 2.1. The manager doesn't make installed workers wait for the
+        // active worker to be unused, so there is nothing else to do.
+        self.skip_waiting.set(true);
+        // Step 2.3
+        promise.resolve_native(&());
+        promise
+    }
 }
@@ -3,13 +3,14 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 
 use crate::dom::bindings::cell::DomRefCell;
+use crate::dom::bindings::codegen::Bindings::ServiceWorkerBinding::ServiceWorkerMethods;
 use crate::dom::bindings::codegen::Bindings::ServiceWorkerBinding::ServiceWorkerState;
 use crate::dom::bindings::codegen::Bindings::ServiceWorkerRegistrationBinding::ServiceWorkerUpdateViaCache;
 use crate::dom::bindings::codegen::Bindings::ServiceWorkerRegistrationBinding::{
     ServiceWorkerRegistrationMethods, Wrap,
 };
 use crate::dom::bindings::reflector::{reflect_dom_object, DomObject};
-use crate::dom::bindings::root::{Dom, DomRoot, MutNullableDom};
+use crate::dom::bindings::root::{DomRoot, MutNullableDom};
 use crate::dom::bindings::str::{ByteString, USVString};
 use crate::dom::eventtarget::EventTarget;
 use crate::dom::globalscope::GlobalScope;
@@ -18,7 +19,7 @@ use crate::dom::serviceworker::ServiceWorker;
 use crate::dom::workerglobalscope::prepare_workerscope_init;
 use devtools_traits::WorkerId;
 use dom_struct::dom_struct;
-use script_traits::{ScopeThings, WorkerScriptLoadOrigin};
+use script_traits::{ScopeThings, ServiceWorkerLifecycleState, WorkerScriptLoadOrigin};
 use servo_url::ServoUrl;
 use std::cell::Cell;
 use uuid::Uuid;
@@ -26,9 +27,9 @@ use uuid::Uuid;
 #[dom_struct]
 pub struct ServiceWorkerRegistration {
     eventtarget: EventTarget,
-    active: Option<Dom<ServiceWorker>>,
-    installing: Option<Dom<ServiceWorker>>,
-    waiting: Option<Dom<ServiceWorker>>,
+    active: MutNullableDom<ServiceWorker>,
+    installing: MutNullableDom<ServiceWorker>,
+    waiting: MutNullableDom<ServiceWorker>,
     navigation_preload: MutNullableDom<NavigationPreloadManager>,
     scope: ServoUrl,
     navigation_preload_enabled: Cell<bool>,
@@ -38,12 +39,12 @@ pub struct ServiceWorkerRegistration {
 }
 
 impl ServiceWorkerRegistration {
-    fn new_inherited(active_sw: &ServiceWorker, scope: ServoUrl) -> ServiceWorkerRegistration {
+    fn new_inherited(scope: ServoUrl) -> ServiceWorkerRegistration {
         ServiceWorkerRegistration {
             eventtarget: EventTarget::new_inherited(),
-            active: Some(Dom::from_ref(active_sw)),
-            installing: None,
-            waiting: None,
+            active: Default::default(),
+            installing: Default::default(),
+            waiting: Default::default(),
             navigation_preload: MutNullableDom::new(None),
             scope: scope,
             navigation_preload_enabled: Cell::new(false),
@@ -53,32 +54,62 @@ impl ServiceWorkerRegistration {
         }
     }
 
-    #[allow(unrooted_must_root)]
+    /// A registration whose worker, with the given script, is in `state`.
     pub fn new(
         global: &GlobalScope,
         script_url: &ServoUrl,
         scope: ServoUrl,
+        state: ServiceWorkerLifecycleState,
     ) -> DomRoot<ServiceWorkerRegistration> {
-        let active_worker =
+        let worker =
             ServiceWorker::install_serviceworker(global, script_url.clone(), scope.clone(), true);
-        active_worker.set_transition_state(ServiceWorkerState::Installed);
-
-        reflect_dom_object(
-            Box::new(ServiceWorkerRegistration::new_inherited(
-                &*active_worker,
-                scope,
-            )),
+        let registration = reflect_dom_object(
+            Box::new(ServiceWorkerRegistration::new_inherited(scope)),
             global,
             Wrap,
-        )
+        );
+        registration.installing.set(Some(&worker));
+        registration.update_state(state);
+        registration
     }
 
-    pub fn active(&self) -> Option<&ServiceWorker> {
-        self.active.as_ref().map(|sw| &**sw)
+    pub fn active(&self) -> Option<DomRoot<ServiceWorker>> {
+        self.active.get()
     }
 
-    pub fn get_installed(&self) -> &ServiceWorker {
-        self.active.as_ref().unwrap()
+    /// Move the worker to the slot for its new state.
+    /// <https://w3c.github.io/ServiceWorker/#update-registration-state-algorithm>
+    /// <https://w3c.github.io/ServiceWorker/#update-state-algorithm>
+    pub fn update_state(&self, state: ServiceWorkerLifecycleState) {
+        let worker = match self.get_newest_worker() {
+            Some(worker) => worker,
+            None => return,
+        };
+        self.installing.set(None);
+        self.waiting.set(None);
+        self.active.set(None);
+        let dom_state = match state {
+            ServiceWorkerLifecycleState::Installing => {
+                self.installing.set(Some(&worker));
+                ServiceWorkerState::Installing
+            },
+            ServiceWorkerLifecycleState::Installed => {
+                self.waiting.set(Some(&worker));
+                ServiceWorkerState::Installed
+            },
+            ServiceWorkerLifecycleState::Activating => {
+                self.active.set(Some(&worker));
+                ServiceWorkerState::Activating
+            },
+            ServiceWorkerLifecycleState::Activated => {
+                self.active.set(Some(&worker));
+                ServiceWorkerState::Activated
+            },
+            ServiceWorkerLifecycleState::Redundant => ServiceWorkerState::Redundant,
+        };
+        if worker.State() != dom_state {
+            worker.set_transition_state(dom_state);
+        }
     }
 
     pub fn get_navigation_preload_header_value(&self) -> Option<ByteString> {
@@ -127,13 +158,10 @@ impl ServiceWorkerRegistration {
 
     // https://w3c.github.io/ServiceWorker/#get-newest-worker-algorithm
     pub fn get_newest_worker(&self) -> Option<DomRoot<ServiceWorker>> {
-        if self.installing.as_ref().is_some() {
-            self.installing.as_ref().map(|sw| DomRoot::from_ref(&**sw))
-        } else if self.waiting.as_ref().is_some() {
-            self.waiting.as_ref().map(|sw| DomRoot::from_ref(&**sw))
-        } else {
-            self.active.as_ref().map(|sw| DomRoot::from_ref(&**sw))
-        }
+        self.installing
+            .get()
+            .or_else(|| self.waiting.get())
+            .or_else(|| self.active.get())
     }
 }
 
@@ -157,17 +185,17 @@ pub fn longest_prefix_match(stored_scope: &ServoUrl, potential_match: &ServoUrl)
 impl ServiceWorkerRegistrationMethods for ServiceWorkerRegistration {
     // https://w3c.github.io/ServiceWorker/#service-worker-registration-installing-attribute
     fn GetInstalling(&self) -> Option<DomRoot<ServiceWorker>> {
-        self.installing.as_ref().map(|sw| DomRoot::from_ref(&**sw))
+        self.installing.get()
     }
 
     // https://w3c.github.io/ServiceWorker/#service-worker-registration-active-attribute
     fn GetActive(&self) -> Option<DomRoot<ServiceWorker>> {
-        self.active.as_ref().map(|sw| DomRoot::from_ref(&**sw))
+        self.active.get()
     }
 
     // https://w3c.github.io/ServiceWorker/#service-worker-registration-waiting-attribute
     fn GetWaiting(&self) -> Option<DomRoot<ServiceWorker>> {
-        self.waiting.as_ref().map(|sw| DomRoot::from_ref(&**sw))
+        self.waiting.get()
     }
 
     // https://w3c.github.io/ServiceWorker/#service-worker-registration-scope-attribute
@@ -45,19 +45,22 @@ use html5ever::tendril::fmt::UTF8;
 use html5ever::tendril::{ByteTendril, StrTendril, TendrilSink};
 use html5ever::tree_builder::{ElementFlags, NextParserState, NodeOrText, QuirksMode, TreeSink};
 use html5ever::{Attribute, ExpandedName, LocalName, QualName};
+use http::header::HeaderMap;
 use hyper_serde::Serde;
 use mime::{self, Mime};
 use msg::constellation_msg::PipelineId;
-use net_traits::{FetchMetadata, FetchResponseListener, Metadata, NetworkError};
+use net_traits::{CrossOriginEmbedderPolicy, FetchMetadata, FetchResponseListener};
+use net_traits::{Metadata, NetworkError};
 use net_traits::{ResourceFetchTiming, ResourceTimingType};
 use profile_traits::time::{
     profile, ProfilerCategory, TimerMetadata, TimerMetadataFrameType, TimerMetadataReflowType,
 };
 use script_traits::DocumentActivity;
 use servo_config::pref;
-use servo_url::ServoUrl;
+use servo_url::{ImmutableOrigin, ServoUrl};
 use std::borrow::Cow;
 use std::cell::Cell;
+use std::collections::HashMap;
 use std::mem;
 use style::context::QuirksMode as ServoQuirksMode;
 use tendril::stream::LossyDecoder;
@@ -705,6 +708,8 @@ pub struct ParserContext {
     resource_timing: ResourceFetchTiming,
     /// pushed entry index
     pushed_entry_index: Option<usize>,
+    /// The origins of the documents of the ancestor browsing contexts, nearest first.
+    ancestor_origins: Vec<ImmutableOrigin>,
 }
 
 impl ParserContext {
@@ -716,8 +721,193 @@ impl ParserContext {
             url: url,
             resource_timing: ResourceFetchTiming::new(ResourceTimingType::Navigation),
             pushed_entry_index: None,
+            ancestor_origins: vec![],
         }
     }
+
+    pub fn set_ancestor_origins(&mut self, ancestor_origins: Vec<ImmutableOrigin>) {
+        self.ancestor_origins = ancestor_origins;
+    }
+}
+
+/// <https://www.w3.org/TR/CSP/#initialize-document-csp>, parsing the enforced and the
+/// report-only policies that the headers of the document's response deliver.
+fn parse_csp_list(headers: &HeaderMap) -> Option<CspList> {
+    let mut csp_list: Option<CspList> = None;
+    let policy_headers = [
+        ("content-security-policy", csp::PolicyDisposition::Enforce),
+        (
+            "content-security-policy-report-only",
+            csp::PolicyDisposition::Report,
+        ),
+    ];
+    for (name, disposition) in policy_headers.iter() {
+        for value in headers.get_all(*name).iter() {
+            // This silently ignores a policy that contains invalid Unicode.
+            // We should probably report an error somewhere.
+            let value = match value.to_str() {
+                Ok(value) => value,
+                Err(_) => continue,
+            };
+            let policies = CspList::parse(value, csp::PolicySource::Header, disposition.clone());
+            match csp_list {
+                Some(ref mut csp_list) => csp_list.append(policies),
+                None => csp_list = Some(policies),
+            }
+        }
+    }
+    csp_list
+}
+
+/// Parse the `Reporting-Endpoints` header, a dictionary of names and quoted URLs,
+/// <https://w3c.github.io/reporting/#header>.
+fn parse_reporting_endpoints(
+    headers: &HeaderMap,
+    base_url: &ServoUrl,
+) -> HashMap<String, ServoUrl> {
+    headers
+        .get_all("reporting-endpoints")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|member| {
+            let mut parts = member.splitn(2, '=');
+            let name = parts.next()?.trim();
+            let url = parts.next()?.trim();
+            if name.is_empty() || !url.starts_with('"') || !url.ends_with('"') || url.len() < 2 {
+                return None;
+            }
+            let url = base_url.join(&url[1..url.len() - 1]).ok()?;
+            // Reports may only be delivered to potentially trustworthy URLs.
+            let is_loopback = url.host_str() == Some("localhost") ||
+                url.host_str() == Some("127.0.0.1") ||
+                url.host_str() == Some("[::1]");
+            if !url.is_secure_scheme() && !is_loopback {
+                return None;
+            }
+            Some((name.to_owned(), url))
+        })
+        .collect()
+}
+
+/// Whether a source list of a `frame-ancestors` directive matches the origin of an
+/// ancestor of the document that `self_origin` is the origin of,
+/// <https://w3c.github.io/webappsec-csp/#match-url-to-source-list>.
+fn does_origin_match_source_list(
+    origin: &ImmutableOrigin,
+    self_origin: &ImmutableOrigin,
+    source_list: &[String],
+) -> bool {
+    let (scheme, host, port) = match *origin {
+        ImmutableOrigin::Tuple(ref scheme, ref host, port) => (scheme, host.to_string(), port),
+        ImmutableOrigin::Opaque(_) => return false,
+    };
+    source_list.iter().any(|source| {
+        let source = source.to_ascii_lowercase();
+        if source == "'none'" {
+            return false;
+        }
+        if source == "'self'" {
+            return origin == self_origin;
+        }
+        if source == "*" {
+            return scheme.starts_with("http") ||
+                scheme.starts_with("ws") ||
+                Some(scheme.as_str()) == self_origin.scheme();
+        }
+        if source.ends_with(':') {
+            return scheme_matches(&source[..source.len() - 1], scheme);
+        }
+
+        // A host-source, `[scheme "://"] host [":" port] [path]`.
+        let (source_scheme, rest) = match source.find("://") {
+            Some(index) => (Some(&source[..index]), &source[index + 3..]),
+            None => (None, &source[..]),
+        };
+        if let Some(source_scheme) = source_scheme {
+            if !scheme_matches(source_scheme, scheme) {
+                return false;
+            }
+        } else if !scheme_matches(self_origin.scheme().unwrap_or("https"), scheme) {
+            return false;
+        }
+        let host_and_port = rest.split('/').next().unwrap_or("");
+        let mut parts = host_and_port.rsplitn(2, ':');
+        let (source_host, source_port) = match (parts.next(), parts.next()) {
+            (Some(port), Some(host)) => (host, Some(port)),
+            (Some(host), None) => (host, None),
+            _ => return false,
+        };
+        let host_matches = if source_host.starts_with("*.") {
+            host.ends_with(&source_host[1..])
+        } else {
+            host == source_host
+        };
+        let port_matches = match source_port {
+            Some("*") => true,
+            Some(source_port) => source_port.parse::<u16>().ok() == Some(port),
+            None => default_port(scheme) == Some(port),
+        };
+        host_matches && port_matches
+    })
+}
+
+/// Whether the scheme of a source matches a scheme, upgrades to secure schemes included.
+fn scheme_matches(source_scheme: &str, scheme: &str) -> bool {
+    source_scheme == scheme ||
+        (source_scheme == "http" && scheme == "https") ||
+        (source_scheme == "ws" && (scheme == "wss" || scheme == "http" || scheme == "https")) ||
+        (source_scheme == "wss" && scheme == "https")
+}
+
+fn default_port(scheme: &str) -> Option<u16> {
+    match scheme {
+        "http" | "ws" => Some(80),
+        "https" | "wss" => Some(443),
+        "ftp" => Some(21),
+        _ => None,
+    }
+}
+
+/// <https://w3c.github.io/webappsec-csp/#frame-ancestors-navigation-response>,
+/// checking the ancestors of a nested browsing context against the `frame-ancestors`
+/// directives of the policies the response to its navigation delivers.
+fn should_navigation_response_be_blocked_by_frame_ancestors(
+    csp_list: &CspList,
+    self_origin: &ImmutableOrigin,
+    ancestor_origins: &[ImmutableOrigin],
+) -> (csp::CheckResult, Vec<csp::Violation>) {
+    let mut result = csp::CheckResult::Allowed;
+    let mut violations = vec![];
+    for policy in &csp_list.0 {
+        let directive = match policy
+            .directive_set
+            .iter()
+            .find(|directive| directive.name == "frame-ancestors")
+        {
+            Some(directive) => directive,
+            None => continue,
+        };
+        let blocked_ancestor = ancestor_origins
+            .iter()
+            .find(|origin| !does_origin_match_source_list(origin, self_origin, &directive.value));
+        if let Some(ancestor_origin) = blocked_ancestor {
+            let ancestor_url = ServoUrl::parse(&ancestor_origin.ascii_serialization())
+                .map(ServoUrl::into_url)
+                .ok();
+            if let Some(ancestor_url) = ancestor_url {
+                violations.push(csp::Violation {
+                    resource: csp::ViolationResource::Url(ancestor_url),
+                    directive: directive.clone(),
+                    policy: policy.clone(),
+                });
+            }
+            if let csp::PolicyDisposition::Enforce = policy.disposition {
+                result = csp::CheckResult::Blocked;
+            }
+        }
+    }
+    (result, violations)
 }
 
 impl FetchResponseListener for ParserContext {
@@ -757,27 +947,46 @@ impl FetchResponseListener for ParserContext {
 
         // https://www.w3.org/TR/CSP/#initialize-document-csp
         // TODO: Implement step 1 (local scheme special case)
-        let csp_list = metadata.as_ref().and_then(|m| {
-            let h = m.headers.as_ref()?;
-            let mut csp = h.get_all("content-security-policy").iter();
-            // This silently ignores the CSP if it contains invalid Unicode.
-            // We should probably report an error somewhere.
-            let c = csp.next().and_then(|c| c.to_str().ok())?;
-            let mut csp_list = CspList::parse(
-                c,
-                csp::PolicySource::Header,
-                csp::PolicyDisposition::Enforce,
-            );
-            for c in csp {
-                let c = c.to_str().ok()?;
-                csp_list.append(CspList::parse(
-                    c,
-                    csp::PolicySource::Header,
-                    csp::PolicyDisposition::Enforce,
-                ));
+        let headers = metadata
+            .as_ref()
+            .and_then(|m| m.headers.as_ref())
+            .map(|h| &**h);
+        let csp_list = headers.and_then(|h| parse_csp_list(h));
+        let reporting_endpoints = headers
+            .map(|h| parse_reporting_endpoints(h, &self.url))
+            .unwrap_or_default();
+        let embedder_policy = metadata
+            .as_ref()
+            .and_then(|m| {
+                m.headers
+                    .as_ref()
+                    .map(|h| CrossOriginEmbedderPolicy::from_response_headers(&m.final_url, h))
+            })
+            .unwrap_or_default();
+
+        // https://w3c.github.io/webappsec-csp/#should-block-navigation-response
+        let mut frame_ancestors_violations = vec![];
+        if let (Some(csp_list), Some(metadata)) = (csp_list.as_ref(), metadata.as_ref()) {
+            let (check_result, violations) =
+                should_navigation_response_be_blocked_by_frame_ancestors(
+                    csp_list,
+                    &metadata.final_url.origin(),
+                    &self.ancestor_origins,
+                );
+            frame_ancestors_violations = violations;
+            if check_result == csp::CheckResult::Blocked {
+                network_error =
+                    Some("Blocked by Content-Security-Policy frame-ancestors".to_owned());
             }
-            Some(csp_list)
-        });
+        }
+        let (metadata, content_type) = if network_error.is_some() && ssl_error.is_none() {
+            let mut meta = Metadata::default(self.url.clone());
+            let mime: Option<Mime> = "text/html".parse().ok();
+            meta.set_content_type(mime.as_ref());
+            (Some(meta), mime)
+        } else {
+            (metadata, content_type)
+        };
 
         let parser = match ScriptThread::page_headers_available(&self.id, metadata) {
             Some(parser) => parser,
@@ -788,6 +997,15 @@ impl FetchResponseListener for ParserContext {
         }
 
         parser.document.set_csp_list(csp_list);
+        parser.document.set_reporting_endpoints(reporting_endpoints);
+        parser.document.set_embedder_policy(embedder_policy);
+        if !frame_ancestors_violations.is_empty() {
+            parser
+                .document
+                .window()
+                .upcast::<GlobalScope>()
+                .report_csp_violations(frame_ancestors_violations, None);
+        }
 
         self.parser = Some(Trusted::new(&*parser));
 
@@ -912,6 +1130,15 @@ impl FetchResponseListener for ParserContext {
         }
     }
 
+    fn process_csp_violations(&mut self, violations: Vec<csp::Violation>) {
+        let parser = match self.parser.as_ref() {
+            Some(parser) => parser.root(),
+            None => return,
+        };
+        let global = parser.document.window().upcast::<GlobalScope>();
+        global.report_csp_violations(violations, None);
+    }
+
     fn resource_timing_mut(&mut self) -> &mut ResourceFetchTiming {
         &mut self.resource_timing
     }
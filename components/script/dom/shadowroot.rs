@@ -3,18 +3,21 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 
 use crate::dom::bindings::cell::DomRefCell;
+use crate::dom::bindings::codegen::Bindings::DocumentBinding::DocumentMethods;
 use crate::dom::bindings::codegen::Bindings::ShadowRootBinding::ShadowRootBinding::ShadowRootMethods;
 use crate::dom::bindings::codegen::Bindings::ShadowRootBinding::{self, ShadowRootMode};
 use crate::dom::bindings::inheritance::Castable;
 use crate::dom::bindings::num::Finite;
 use crate::dom::bindings::reflector::reflect_dom_object;
 use crate::dom::bindings::root::{Dom, DomRoot, LayoutDom, MutNullableDom};
+use crate::dom::caretposition::CaretPosition;
 use crate::dom::cssstylesheet::CSSStyleSheet;
 use crate::dom::document::Document;
 use crate::dom::documentfragment::DocumentFragment;
 use crate::dom::documentorshadowroot::{DocumentOrShadowRoot, StyleSheetInDocument};
 use crate::dom::element::Element;
 use crate::dom::node::{Node, NodeDamage, NodeFlags, ShadowIncluding, UnbindContext};
+use crate::dom::selection::Selection;
 use crate::dom::stylesheetlist::{StyleSheetList, StyleSheetListOwner};
 use crate::dom::window::Window;
 use crate::stylesheet_set::StylesheetSetRef;
@@ -217,6 +220,25 @@ impl ShadowRootMethods for ShadowRoot {
         elements
     }
 
+    // https://drafts.csswg.org/cssom-view/#dom-document-caretpositionfrompoint
+    fn CaretPositionFromPoint(
+        &self,
+        x: Finite<f64>,
+        y: Finite<f64>,
+    ) -> Option<DomRoot<CaretPosition>> {
+        self.document_or_shadow_root.caret_position_from_point(
+            x,
+            y,
+            self.document.has_browsing_context(),
+        )
+    }
+
+    // https://w3c.github.io/selection-api/#dom-document-getselection
+    fn GetSelection(&self) -> Option<DomRoot<Selection>> {
+        // Shadow roots share the selection of their document.
+        self.document.GetSelection()
+    }
+
     /// https://dom.spec.whatwg.org/#dom-shadowroot-mode
     fn Mode(&self) -> ShadowRootMode {
         ShadowRootMode::Closed
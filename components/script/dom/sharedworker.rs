@@ -0,0 +1,198 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::dom::abstractworker::SimpleWorkerErrorHandler;
+use crate::dom::bindings::codegen::Bindings::SharedWorkerBinding::{SharedWorkerMethods, Wrap};
+use crate::dom::bindings::codegen::Bindings::WorkerBinding::WorkerType;
+use crate::dom::bindings::codegen::UnionTypes::StringOrWorkerOptions;
+use crate::dom::bindings::error::{Error, Fallible};
+use crate::dom::bindings::inheritance::Castable;
+use crate::dom::bindings::refcounted::Trusted;
+use crate::dom::bindings::reflector::{reflect_dom_object, DomObject};
+use crate::dom::bindings::root::{Dom, DomRoot};
+use crate::dom::bindings::str::USVString;
+use crate::dom::bindings::structuredclone;
+use crate::dom::eventtarget::EventTarget;
+use crate::dom::globalscope::GlobalScope;
+use crate::dom::messageport::MessagePort;
+use crate::dom::sharedworkerglobalscope::SharedWorkerGlobalScope;
+use crate::dom::workerglobalscope::prepare_workerscope_init;
+use crate::realms::enter_realm;
+use crate::task::TaskOnce;
+use crate::task_source::dom_manipulation::DOMManipulationTaskSource;
+use crate::task_source::TaskSource;
+use devtools_traits::{DevtoolsPageInfo, ScriptToDevtoolsControlMsg, WorkerId};
+use dom_struct::dom_struct;
+use ipc_channel::ipc::{self, IpcSender};
+use ipc_channel::router::ROUTER;
+use js::jsval::ObjectValue;
+use js::rust::{CustomAutoRooter, CustomAutoRooterGuard};
+use script_traits::{ScriptMsg, SharedWorkerKey, WorkerScriptLoadOrigin};
+use uuid::Uuid;
+
+pub type TrustedSharedWorkerAddress = Trusted<SharedWorker>;
+
+// https://html.spec.whatwg.org/multipage/#sharedworker
+#[dom_struct]
+pub struct SharedWorker {
+    eventtarget: EventTarget,
+    /// The outside port, entangled with the one passed to the connect event of the worker.
+    port: Dom<MessagePort>,
+}
+
+impl SharedWorker {
+    fn new_inherited(port: &MessagePort) -> SharedWorker {
+        SharedWorker {
+            eventtarget: EventTarget::new_inherited(),
+            port: Dom::from_ref(port),
+        }
+    }
+
+    pub fn new(global: &GlobalScope, port: &MessagePort) -> DomRoot<SharedWorker> {
+        reflect_dom_object(Box::new(SharedWorker::new_inherited(port)), global, Wrap)
+    }
+
+    // https://html.spec.whatwg.org/multipage/#dom-sharedworker
+    #[allow(non_snake_case)]
+    pub fn Constructor(
+        global: &GlobalScope,
+        script_url: USVString,
+        options: StringOrWorkerOptions,
+    ) -> Fallible<DomRoot<SharedWorker>> {
+        // Steps 1-2.
+        let (name, worker_type) = match options {
+            StringOrWorkerOptions::String(name) => (name, WorkerType::Classic),
+            StringOrWorkerOptions::WorkerOptions(options) => (options.name, options.type_),
+        };
+        let name = String::from(name);
+
+        // Steps 5-7.
+        let worker_url = match global.api_base_url().join(&script_url) {
+            Ok(url) => url,
+            Err(_) => return Err(Error::Syntax),
+        };
+        if worker_url.origin() != *global.origin().immutable() {
+            return Err(Error::Security);
+        }
+
+        // Steps 8-11, the outside port, and the inside port that goes to the worker.
+        let outside_port = MessagePort::new(global);
+        let inside_port = MessagePort::new(global);
+        global.track_message_port(&*outside_port, None);
+        global.track_message_port(&*inside_port, None);
+        global.entangle_ports(
+            outside_port.message_port_id().clone(),
+            inside_port.message_port_id().clone(),
+        );
+        let worker = SharedWorker::new(global, &outside_port);
+
+        // Transfer the inside port, it is the data of the message,
+        // and the only item of its transfer list.
+        let cx = global.get_cx();
+        let port_data = {
+            let _ac = enter_realm(global);
+            let port_object = inside_port.reflector().get_jsobject().get();
+            rooted!(in(*cx) let port_value = ObjectValue(port_object));
+            let mut transfer = CustomAutoRooter::new(vec![port_object]);
+            let transfer = CustomAutoRooterGuard::new(*cx, &mut transfer);
+            structuredclone::write(cx, port_value.handle(), Some(transfer))?
+        };
+
+        // Step 12, the constellation looks for a worker with the same key,
+        // and connects the port to it, we only start one if there is none.
+        let key = SharedWorkerKey {
+            origin: global.origin().immutable().clone(),
+            url: worker_url.clone(),
+            name: name.clone(),
+        };
+        let (sender, receiver) = ipc::channel().expect("ipc channel failure");
+        let error_sender = worker.error_sender(global);
+        let msg = ScriptMsg::ConnectSharedWorker(key.clone(), port_data, sender, error_sender);
+        if global.script_to_constellation_chan().send(msg).is_err() {
+            return Err(Error::Operation);
+        }
+        let needs_start = receiver.recv().unwrap_or(false);
+        if !needs_start {
+            return Ok(worker);
+        }
+
+        let worker_load_origin = WorkerScriptLoadOrigin {
+            referrer_url: None,
+            referrer_policy: None,
+            pipeline_id: global.pipeline_id(),
+        };
+
+        let (devtools_sender, devtools_receiver) = ipc::channel().unwrap();
+        if let Some(ref chan) = global.devtools_chan() {
+            let worker_id = WorkerId(Uuid::new_v4());
+            let page_info = DevtoolsPageInfo {
+                title: format!("SharedWorker for {}", worker_url),
+                url: worker_url.clone(),
+            };
+            let _ = chan.send(ScriptToDevtoolsControlMsg::NewGlobal(
+                (global.pipeline_id(), Some(worker_id)),
+                devtools_sender.clone(),
+                page_info,
+            ));
+        }
+
+        let init = prepare_workerscope_init(global, Some(devtools_sender));
+
+        SharedWorkerGlobalScope::run_worker_scope(
+            init,
+            worker_url,
+            devtools_receiver,
+            key,
+            worker_load_origin,
+            name,
+            worker_type,
+            global.image_cache(),
+        );
+
+        Ok(worker)
+    }
+
+    /// A sender through which the constellation fires an error event at this worker,
+    /// if the shared worker it connects to fails to load its script.
+    fn error_sender(&self, global: &GlobalScope) -> IpcSender<()> {
+        let (sender, receiver) = ipc::channel().expect("ipc channel failure");
+        let mut address = Some(Trusted::new(self));
+        let task_source = global.dom_manipulation_task_source();
+        let canceller = global.task_canceller(DOMManipulationTaskSource::NAME);
+        ROUTER.add_route(
+            receiver.to_opaque(),
+            Box::new(move |_message| {
+                let address = match address.take() {
+                    Some(address) => address,
+                    None => return,
+                };
+                let _ = task_source
+                    .queue_with_canceller(SimpleWorkerErrorHandler::new(address), &canceller);
+            }),
+        );
+        sender
+    }
+
+    pub fn dispatch_simple_error(address: TrustedSharedWorkerAddress) {
+        let worker = address.root();
+        worker.upcast().fire_event(atom!("error"));
+    }
+}
+
+impl SharedWorkerMethods for SharedWorker {
+    // https://html.spec.whatwg.org/multipage/#dom-sharedworker-port
+    fn Port(&self) -> DomRoot<MessagePort> {
+        DomRoot::from_ref(&*self.port)
+    }
+
+    // https://html.spec.whatwg.org/multipage/#handler-abstractworker-onerror
+    event_handler!(error, GetOnerror, SetOnerror);
+}
+
+impl TaskOnce for SimpleWorkerErrorHandler<SharedWorker> {
+    #[allow(unrooted_must_root)]
+    fn run_once(self) {
+        SharedWorker::dispatch_simple_error(self.addr);
+    }
+}
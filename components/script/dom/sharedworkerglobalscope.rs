@@ -0,0 +1,499 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::devtools;
+use crate::dom::abstractworkerglobalscope::{run_worker_event_loop, WorkerEventLoopMethods};
+use crate::dom::bindings::codegen::Bindings::SharedWorkerGlobalScopeBinding;
+use crate::dom::bindings::codegen::Bindings::SharedWorkerGlobalScopeBinding::SharedWorkerGlobalScopeMethods;
+use crate::dom::bindings::codegen::Bindings::WorkerBinding::WorkerType;
+use crate::dom::bindings::codegen::UnionTypes::WindowProxyOrMessagePortOrServiceWorker;
+use crate::dom::bindings::conversions::ToJSValConvertible;
+use crate::dom::bindings::inheritance::Castable;
+use crate::dom::bindings::root::{DomRoot, RootCollection, ThreadLocalStackRoots};
+use crate::dom::bindings::str::DOMString;
+use crate::dom::bindings::structuredclone;
+use crate::dom::dedicatedworkerglobalscope::AutoWorkerReset;
+use crate::dom::event::Event;
+use crate::dom::globalscope::GlobalScope;
+use crate::dom::messageevent::MessageEvent;
+use crate::dom::worker::TrustedWorkerAddress;
+use crate::dom::workerglobalscope::WorkerGlobalScope;
+use crate::fetch::load_whole_resource;
+use crate::realms::enter_realm;
+use crate::script_runtime::{
+    new_rt_and_cx, CommonScriptMsg, JSContext as SafeJSContext, Runtime, ScriptChan, ScriptPort,
+};
+use crate::task_queue::{QueuedTask, QueuedTaskConversion, TaskQueue};
+use crate::task_source::TaskSourceName;
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use devtools_traits::DevtoolScriptControlMsg;
+use dom_struct::dom_struct;
+use ipc_channel::ipc::{self, IpcReceiver};
+use ipc_channel::router::ROUTER;
+use js::jsapi::{JSContext, JS_AddInterruptCallback};
+use js::jsval::UndefinedValue;
+use msg::constellation_msg::PipelineId;
+use net_traits::image_cache::ImageCache;
+use net_traits::request::{CredentialsMode, Destination, ParserMetadata};
+use net_traits::request::{Referrer, RequestBuilder, RequestMode};
+use net_traits::IpcSend;
+use script_traits::{ScriptMsg, SharedWorkerKey, SharedWorkerMsg, StructuredSerializedData};
+use script_traits::{WorkerGlobalScopeInit, WorkerScriptLoadOrigin};
+use servo_rand::random;
+use servo_url::ServoUrl;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use style::thread_state::{self, ThreadState};
+
+/// Messages used to control the shared worker event loop.
+pub enum SharedWorkerScriptMsg {
+    /// Standard message from a worker.
+    Common(CommonScriptMsg),
+    /// A client connected, with the port it communicates through.
+    Connect(StructuredSerializedData),
+    /// Wake-up call from the task queue, or from the constellation to close the worker.
+    WakeUp,
+}
+
+impl QueuedTaskConversion for SharedWorkerScriptMsg {
+    fn task_source_name(&self) -> Option<&TaskSourceName> {
+        match self {
+            SharedWorkerScriptMsg::Common(CommonScriptMsg::Task(
+                _category,
+                _boxed,
+                _pipeline_id,
+                task_source,
+            )) => Some(&task_source),
+            _ => None,
+        }
+    }
+
+    fn pipeline_id(&self) -> Option<PipelineId> {
+        // Workers always return None, since the pipeline_id is only used to check for document activity,
+        // and this check does not apply to worker event-loops.
+        None
+    }
+
+    fn into_queued_task(self) -> Option<QueuedTask> {
+        match self {
+            SharedWorkerScriptMsg::Common(CommonScriptMsg::Task(
+                category,
+                boxed,
+                pipeline_id,
+                task_source,
+            )) => Some((None, category, boxed, pipeline_id, task_source)),
+            _ => None,
+        }
+    }
+
+    fn from_queued_task(queued_task: QueuedTask) -> Self {
+        let (_worker, category, boxed, pipeline_id, task_source) = queued_task;
+        let script_msg = CommonScriptMsg::Task(category, boxed, pipeline_id, task_source);
+        SharedWorkerScriptMsg::Common(script_msg)
+    }
+
+    fn inactive_msg() -> Self {
+        // Inactive is only relevant in the context of a browsing-context event-loop.
+        panic!("Workers should never receive messages marked as inactive");
+    }
+
+    fn wake_up_msg() -> Self {
+        SharedWorkerScriptMsg::WakeUp
+    }
+
+    fn is_wake_up(&self) -> bool {
+        match self {
+            SharedWorkerScriptMsg::WakeUp => true,
+            _ => false,
+        }
+    }
+}
+
+pub enum MixedMessage {
+    FromSharedWorker(SharedWorkerScriptMsg),
+    FromDevtools(DevtoolScriptControlMsg),
+}
+
+#[derive(Clone, JSTraceable)]
+pub struct SharedWorkerChan {
+    pub sender: Sender<SharedWorkerScriptMsg>,
+}
+
+impl ScriptChan for SharedWorkerChan {
+    fn send(&self, msg: CommonScriptMsg) -> Result<(), ()> {
+        self.sender
+            .send(SharedWorkerScriptMsg::Common(msg))
+            .map_err(|_| ())
+    }
+
+    fn clone(&self) -> Box<dyn ScriptChan + Send> {
+        Box::new(SharedWorkerChan {
+            sender: self.sender.clone(),
+        })
+    }
+}
+
+impl ScriptPort for Receiver<SharedWorkerScriptMsg> {
+    fn recv(&self) -> Result<CommonScriptMsg, ()> {
+        match self.recv() {
+            Ok(SharedWorkerScriptMsg::Common(script_msg)) => Ok(script_msg),
+            Err(_) => Err(()),
+            Ok(_) => panic!("unexpected shared worker event message!"),
+        }
+    }
+}
+
+unsafe_no_jsmanaged_fields!(TaskQueue<SharedWorkerScriptMsg>);
+
+// https://html.spec.whatwg.org/multipage/#sharedworkerglobalscope
+#[dom_struct]
+pub struct SharedWorkerGlobalScope {
+    workerglobalscope: WorkerGlobalScope,
+
+    #[ignore_malloc_size_of = "Defined in std"]
+    task_queue: TaskQueue<SharedWorkerScriptMsg>,
+
+    #[ignore_malloc_size_of = "Defined in std"]
+    own_sender: Sender<SharedWorkerScriptMsg>,
+
+    #[ignore_malloc_size_of = "Arc"]
+    image_cache: Arc<dyn ImageCache>,
+}
+
+impl WorkerEventLoopMethods for SharedWorkerGlobalScope {
+    type WorkerMsg = SharedWorkerScriptMsg;
+    type Event = MixedMessage;
+
+    fn task_queue(&self) -> &TaskQueue<SharedWorkerScriptMsg> {
+        &self.task_queue
+    }
+
+    fn handle_event(&self, event: MixedMessage) {
+        self.handle_mixed_message(event);
+    }
+
+    fn handle_worker_post_event(&self, _worker: &TrustedWorkerAddress) -> Option<AutoWorkerReset> {
+        None
+    }
+
+    fn from_worker_msg(&self, msg: SharedWorkerScriptMsg) -> MixedMessage {
+        MixedMessage::FromSharedWorker(msg)
+    }
+
+    fn from_devtools_msg(&self, msg: DevtoolScriptControlMsg) -> MixedMessage {
+        MixedMessage::FromDevtools(msg)
+    }
+}
+
+impl SharedWorkerGlobalScope {
+    fn new_inherited(
+        init: WorkerGlobalScopeInit,
+        worker_name: DOMString,
+        worker_type: WorkerType,
+        worker_url: ServoUrl,
+        from_devtools_receiver: Receiver<DevtoolScriptControlMsg>,
+        runtime: Runtime,
+        own_sender: Sender<SharedWorkerScriptMsg>,
+        receiver: Receiver<SharedWorkerScriptMsg>,
+        closing: Arc<AtomicBool>,
+        image_cache: Arc<dyn ImageCache>,
+    ) -> SharedWorkerGlobalScope {
+        SharedWorkerGlobalScope {
+            workerglobalscope: WorkerGlobalScope::new_inherited(
+                init,
+                worker_name,
+                worker_type,
+                worker_url,
+                runtime,
+                from_devtools_receiver,
+                Some(closing),
+            ),
+            task_queue: TaskQueue::new(receiver, own_sender.clone()),
+            own_sender,
+            image_cache,
+        }
+    }
+
+    #[allow(unsafe_code)]
+    pub fn new(
+        init: WorkerGlobalScopeInit,
+        worker_name: DOMString,
+        worker_type: WorkerType,
+        worker_url: ServoUrl,
+        from_devtools_receiver: Receiver<DevtoolScriptControlMsg>,
+        runtime: Runtime,
+        own_sender: Sender<SharedWorkerScriptMsg>,
+        receiver: Receiver<SharedWorkerScriptMsg>,
+        closing: Arc<AtomicBool>,
+        image_cache: Arc<dyn ImageCache>,
+    ) -> DomRoot<SharedWorkerGlobalScope> {
+        let cx = runtime.cx();
+        let scope = Box::new(SharedWorkerGlobalScope::new_inherited(
+            init,
+            worker_name,
+            worker_type,
+            worker_url,
+            from_devtools_receiver,
+            runtime,
+            own_sender,
+            receiver,
+            closing,
+            image_cache,
+        ));
+        unsafe { SharedWorkerGlobalScopeBinding::Wrap(SafeJSContext::from_ptr(cx), scope) }
+    }
+
+    #[allow(unsafe_code)]
+    // https://html.spec.whatwg.org/multipage/#run-a-worker
+    pub fn run_worker_scope(
+        init: WorkerGlobalScopeInit,
+        worker_url: ServoUrl,
+        from_devtools_receiver: IpcReceiver<DevtoolScriptControlMsg>,
+        key: SharedWorkerKey,
+        worker_load_origin: WorkerScriptLoadOrigin,
+        worker_name: String,
+        worker_type: WorkerType,
+        image_cache: Arc<dyn ImageCache>,
+    ) {
+        let serialized_worker_url = worker_url.to_string();
+        let name = format!("SharedWorker for {}", serialized_worker_url);
+        let current_global = GlobalScope::current().expect("No current global object");
+        let origin = current_global.origin().immutable().clone();
+        let csp_list = current_global.get_csp_list();
+
+        thread::Builder::new()
+            .name(name)
+            .spawn(move || {
+                thread_state::initialize(ThreadState::SCRIPT | ThreadState::IN_WORKER);
+                let roots = RootCollection::new();
+                let _stack_roots = ThreadLocalStackRoots::new(&roots);
+
+                let WorkerScriptLoadOrigin {
+                    referrer_url,
+                    referrer_policy,
+                    pipeline_id,
+                } = worker_load_origin;
+
+                let referrer = referrer_url.map(|referrer_url| Referrer::ReferrerUrl(referrer_url));
+
+                let request = RequestBuilder::new(worker_url.clone())
+                    .destination(Destination::SharedWorker)
+                    .mode(RequestMode::SameOrigin)
+                    .credentials_mode(CredentialsMode::CredentialsSameOrigin)
+                    .parser_metadata(ParserMetadata::NotParserInserted)
+                    .use_url_credentials(true)
+                    .pipeline_id(Some(pipeline_id))
+                    .referrer(referrer)
+                    .referrer_policy(referrer_policy)
+                    .origin(origin)
+                    .csp_list(csp_list);
+
+                // A shared worker outlives the document that started it,
+                // so it doesn't share the runtime of its parent.
+                let runtime = new_rt_and_cx(None);
+
+                let (devtools_mpsc_chan, devtools_mpsc_port) = unbounded();
+                ROUTER.route_ipc_receiver_to_crossbeam_sender(
+                    from_devtools_receiver,
+                    devtools_mpsc_chan,
+                );
+
+                let (own_sender, receiver) = unbounded();
+                let closing = Arc::new(AtomicBool::new(false));
+                let global = SharedWorkerGlobalScope::new(
+                    init,
+                    DOMString::from_string(worker_name),
+                    worker_type,
+                    worker_url,
+                    devtools_mpsc_port,
+                    runtime,
+                    own_sender.clone(),
+                    receiver,
+                    closing.clone(),
+                    image_cache,
+                );
+                let scope = global.upcast::<WorkerGlobalScope>();
+                let global_scope = global.upcast::<GlobalScope>();
+                let worker_id = scope.get_worker_id();
+
+                // Let the constellation send the connections of the clients, and close
+                // the worker once they are all gone.
+                let (constellation_sender, constellation_receiver) =
+                    ipc::channel().expect("ipc channel failure");
+                ROUTER.add_route(
+                    constellation_receiver.to_opaque(),
+                    Box::new(move |message| match message.to() {
+                        Ok(SharedWorkerMsg::Connect(port)) => {
+                            let _ = own_sender.send(SharedWorkerScriptMsg::Connect(port));
+                        },
+                        Ok(SharedWorkerMsg::Terminate) => {
+                            closing.store(true, Ordering::SeqCst);
+                            let _ = own_sender.send(SharedWorkerScriptMsg::WakeUp);
+                        },
+                        Err(e) => warn!("Error receiving a SharedWorkerMsg: {:?}", e),
+                    }),
+                );
+                let _ = global_scope.script_to_constellation_chan().send(
+                    ScriptMsg::SharedWorkerStarted(key.clone(), worker_id, constellation_sender),
+                );
+
+                let (metadata, bytes) = match load_whole_resource(
+                    request,
+                    &global_scope.resource_threads().sender(),
+                    &global_scope,
+                ) {
+                    Err(e) => {
+                        warn!(
+                            "Failed to load the script of shared worker {} ({:?}).",
+                            serialized_worker_url, e
+                        );
+                        // The constellation fires an error event at the clients.
+                        let _ = global_scope
+                            .script_to_constellation_chan()
+                            .send(ScriptMsg::SharedWorkerClosed(key, worker_id));
+                        return;
+                    },
+                    Ok((metadata, bytes)) => (metadata, bytes),
+                };
+                let _ = global_scope
+                    .script_to_constellation_chan()
+                    .send(ScriptMsg::SharedWorkerLoaded(key.clone(), worker_id));
+                scope.set_url(metadata.final_url);
+                let source = String::from_utf8_lossy(&bytes);
+
+                unsafe {
+                    // Handle interrupt requests
+                    JS_AddInterruptCallback(*scope.get_cx(), Some(interrupt_callback));
+                }
+
+                if !scope.is_closing() {
+                    scope.execute_script(DOMString::from(source));
+                }
+
+                let reporter_name = format!("shared-worker-reporter-{}", random::<u64>());
+                scope
+                    .upcast::<GlobalScope>()
+                    .mem_profiler_chan()
+                    .run_with_memory_reporting(
+                        || {
+                            // Step 29, Run the responsible event loop specified
+                            // by inside settings until it is destroyed.
+                            // The worker processing model remains on this step
+                            // until the event loop is destroyed,
+                            // which happens after the closing flag is set to true,
+                            // by the worker itself or once its last client is gone.
+                            while !scope.is_closing() {
+                                run_worker_event_loop(&*global, None);
+                            }
+                            // Let other connections to the databases of the worker upgrade them.
+                            global_scope.close_indexeddb_connections();
+                            // Stop receiving messages on the ports and channels of the worker.
+                            global_scope.remove_web_messaging_infra();
+                            // The next client to connect starts a new worker.
+                            let _ = global_scope
+                                .script_to_constellation_chan()
+                                .send(ScriptMsg::SharedWorkerClosed(key, worker_id));
+                        },
+                        reporter_name,
+                        scope.script_chan(),
+                        CommonScriptMsg::CollectReports,
+                    );
+            })
+            .expect("Thread spawning failed");
+    }
+
+    pub fn image_cache(&self) -> Arc<dyn ImageCache> {
+        self.image_cache.clone()
+    }
+
+    pub fn script_chan(&self) -> Box<dyn ScriptChan + Send> {
+        Box::new(SharedWorkerChan {
+            sender: self.own_sender.clone(),
+        })
+    }
+
+    pub fn new_script_pair(&self) -> (Box<dyn ScriptChan + Send>, Box<dyn ScriptPort + Send>) {
+        let (tx, rx) = unbounded();
+        (Box::new(SharedWorkerChan { sender: tx }), Box::new(rx))
+    }
+
+    fn handle_mixed_message(&self, msg: MixedMessage) {
+        match msg {
+            MixedMessage::FromDevtools(msg) => match msg {
+                DevtoolScriptControlMsg::EvaluateJS(_pipe_id, string, sender) => {
+                    devtools::handle_evaluate_js(self.upcast(), string, sender)
+                },
+                DevtoolScriptControlMsg::WantsLiveNotifications(_pipe_id, bool_val) => {
+                    devtools::handle_wants_live_notifications(self.upcast(), bool_val)
+                },
+                _ => debug!("got an unusable devtools control message inside the worker!"),
+            },
+            MixedMessage::FromSharedWorker(SharedWorkerScriptMsg::Common(msg)) => {
+                self.upcast::<WorkerGlobalScope>().process_event(msg);
+            },
+            MixedMessage::FromSharedWorker(SharedWorkerScriptMsg::Connect(port)) => {
+                self.dispatch_connect(port);
+            },
+            MixedMessage::FromSharedWorker(SharedWorkerScriptMsg::WakeUp) => {},
+        }
+    }
+
+    /// Fire a connect event for the inside port of a new client.
+    /// <https://html.spec.whatwg.org/multipage/#dom-sharedworker>
+    #[allow(unsafe_code)]
+    fn dispatch_connect(&self, port: StructuredSerializedData) {
+        let global = self.upcast::<GlobalScope>();
+        let cx = global.get_cx();
+        let _ac = enter_realm(self);
+        rooted!(in(*cx) let mut port_value = UndefinedValue());
+        let ports = match structuredclone::read(global, port, port_value.handle_mut()) {
+            Ok(ports) => ports,
+            Err(()) => return warn!("Failed to receive the port of a shared worker client."),
+        };
+        let source = match ports.first() {
+            Some(port) => WindowProxyOrMessagePortOrServiceWorker::MessagePort(port.clone()),
+            None => return warn!("A shared worker client connected without a port."),
+        };
+        rooted!(in(*cx) let mut data = UndefinedValue());
+        unsafe { DOMString::new().to_jsval(*cx, data.handle_mut()) };
+        let event = MessageEvent::new(
+            global,
+            atom!("connect"),
+            false,
+            false,
+            data.handle(),
+            DOMString::new(),
+            Some(&source),
+            DOMString::new(),
+            ports,
+        );
+        event.upcast::<Event>().fire(self.upcast());
+    }
+}
+
+#[allow(unsafe_code)]
+unsafe extern "C" fn interrupt_callback(cx: *mut JSContext) -> bool {
+    let worker = DomRoot::downcast::<WorkerGlobalScope>(GlobalScope::from_context(cx))
+        .expect("global is not a worker scope");
+    assert!(worker.is::<SharedWorkerGlobalScope>());
+
+    // A false response causes the script to terminate
+    !worker.is_closing()
+}
+
+impl SharedWorkerGlobalScopeMethods for SharedWorkerGlobalScope {
+    // https://html.spec.whatwg.org/multipage/#dom-sharedworkerglobalscope-name
+    fn Name(&self) -> DOMString {
+        self.workerglobalscope.worker_name()
+    }
+
+    // https://html.spec.whatwg.org/multipage/#dom-sharedworkerglobalscope-close
+    fn Close(&self) {
+        self.workerglobalscope.close()
+    }
+
+    // https://html.spec.whatwg.org/multipage/#handler-sharedworkerglobalscope-onconnect
+    event_handler!(connect, GetOnconnect, SetOnconnect);
+}
@@ -0,0 +1,158 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::dom::bindings::codegen::Bindings::PermissionStatusBinding::{
+    PermissionName, PermissionState,
+};
+use crate::dom::bindings::codegen::Bindings::StorageManagerBinding::{
+    StorageEstimate, StorageManagerMethods, Wrap,
+};
+use crate::dom::bindings::error::Error;
+use crate::dom::bindings::refcounted::TrustedPromise;
+use crate::dom::bindings::reflector::{reflect_dom_object, DomObject, Reflector};
+use crate::dom::bindings::root::DomRoot;
+use crate::dom::globalscope::GlobalScope;
+use crate::dom::permissions::get_descriptor_permission_state;
+use crate::dom::promise::Promise;
+use crate::realms::{enter_realm, InRealm};
+use crate::task_source::dom_manipulation::DOMManipulationTaskSource;
+use crate::task_source::TaskSource;
+use dom_struct::dom_struct;
+use ipc_channel::ipc::{self, IpcSender};
+use ipc_channel::router::ROUTER;
+use net_traits::quota_thread::{self, QuotaThreadMsg};
+use net_traits::IpcSend;
+use serde::{Deserialize, Serialize};
+use servo_url::ImmutableOrigin;
+use std::rc::Rc;
+
+// https://storage.spec.whatwg.org/#storagemanager
+#[dom_struct]
+pub struct StorageManager {
+    reflector_: Reflector,
+}
+
+impl StorageManager {
+    fn new_inherited() -> StorageManager {
+        StorageManager {
+            reflector_: Reflector::new(),
+        }
+    }
+
+    pub fn new(global: &GlobalScope) -> DomRoot<StorageManager> {
+        reflect_dom_object(Box::new(StorageManager::new_inherited()), global, Wrap)
+    }
+
+    /// The origin whose local storage shelf this is, or failure if it has none.
+    /// <https://storage.spec.whatwg.org/#obtain-a-local-storage-shelf>
+    fn shelf_origin(&self, promise: &Promise) -> Option<ImmutableOrigin> {
+        let origin = self.global().origin().immutable().clone();
+        if !origin.is_tuple() {
+            promise.reject_error(Error::Type("Opaque origins have no storage".to_owned()));
+            return None;
+        }
+        Some(origin)
+    }
+
+    /// Ask the quota manager something, and settle `promise` with its reply.
+    fn ask_quota_manager<T>(
+        &self,
+        promise: &Rc<Promise>,
+        msg: impl FnOnce(IpcSender<T>) -> QuotaThreadMsg,
+        settle: fn(&Promise, T),
+    ) where
+        T: for<'de> Deserialize<'de> + Serialize + Send + 'static,
+    {
+        let global = self.global();
+        let (sender, receiver) = ipc::channel().expect("ipc channel failure");
+        let mut trusted_promise = Some(TrustedPromise::new(promise.clone()));
+        let task_source = global.dom_manipulation_task_source();
+        let canceller = global.task_canceller(DOMManipulationTaskSource::NAME);
+        ROUTER.add_route(
+            receiver.to_opaque(),
+            Box::new(move |message| {
+                let promise = match trusted_promise.take() {
+                    Some(promise) => promise,
+                    None => return,
+                };
+                let reply: T = match message.to() {
+                    Ok(reply) => reply,
+                    Err(e) => {
+                        return warn!("Error receiving a reply from the quota manager: {:?}", e)
+                    },
+                };
+                let _ = task_source.queue_with_canceller(
+                    task!(settle_storage_manager_promise: move || {
+                        let promise = promise.root();
+                        let _ac = enter_realm(&*promise);
+                        settle(&promise, reply);
+                    }),
+                    &canceller,
+                );
+            }),
+        );
+        if let Err(e) = global.resource_threads().send(msg(sender)) {
+            warn!("Quota thread unavailable ({})", e);
+        }
+    }
+}
+
+fn resolve_with_bool(promise: &Promise, value: bool) {
+    promise.resolve_native(&value);
+}
+
+impl StorageManagerMethods for StorageManager {
+    // https://storage.spec.whatwg.org/#dom-storagemanager-persisted
+    fn Persisted(&self, comp: InRealm) -> Rc<Promise> {
+        let promise = Promise::new_in_current_realm(&self.global(), comp);
+        if let Some(origin) = self.shelf_origin(&promise) {
+            self.ask_quota_manager(
+                &promise,
+                |sender| QuotaThreadMsg::Persisted(origin, sender),
+                resolve_with_bool,
+            );
+        }
+        promise
+    }
+
+    // https://storage.spec.whatwg.org/#dom-storagemanager-persist
+    fn Persist(&self, comp: InRealm) -> Rc<Promise> {
+        let global = self.global();
+        let promise = Promise::new_in_current_realm(&global, comp);
+        let origin = match self.shelf_origin(&promise) {
+            Some(origin) => origin,
+            None => return promise,
+        };
+        // Without the permission, the bucket keeps its mode, which is what is reported.
+        let permission =
+            get_descriptor_permission_state(PermissionName::Persistent_storage, Some(&global));
+        self.ask_quota_manager(
+            &promise,
+            |sender| match permission {
+                PermissionState::Granted => QuotaThreadMsg::Persist(origin, sender),
+                _ => QuotaThreadMsg::Persisted(origin, sender),
+            },
+            resolve_with_bool,
+        );
+        promise
+    }
+
+    // https://storage.spec.whatwg.org/#dom-storagemanager-estimate
+    fn Estimate(&self, comp: InRealm) -> Rc<Promise> {
+        let promise = Promise::new_in_current_realm(&self.global(), comp);
+        if let Some(origin) = self.shelf_origin(&promise) {
+            self.ask_quota_manager(
+                &promise,
+                |sender| QuotaThreadMsg::Estimate(origin, sender),
+                |promise, estimate: quota_thread::StorageEstimate| {
+                    promise.resolve_native(&StorageEstimate {
+                        usage: Some(estimate.usage),
+                        quota: Some(estimate.quota),
+                    });
+                },
+            );
+        }
+        promise
+    }
+}
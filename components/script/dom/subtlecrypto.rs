@@ -0,0 +1,2266 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::dom::bindings::codegen::Bindings::CryptoKeyBinding::{CryptoKeyPair, KeyType, KeyUsage};
+use crate::dom::bindings::codegen::Bindings::SubtleCryptoBinding;
+use crate::dom::bindings::codegen::Bindings::SubtleCryptoBinding::SubtleCryptoMethods;
+use crate::dom::bindings::codegen::Bindings::SubtleCryptoBinding::{
+    AesCbcParams, AesCtrParams, AesDerivedKeyParams, AesGcmParams, AesKeyGenParams, Algorithm,
+    EcKeyGenParams, EcKeyImportParams, EcdhKeyDeriveParams, EcdsaParams, HkdfParams,
+    HmacImportParams, HmacKeyGenParams, JsonWebKey, KeyFormat, Pbkdf2Params, RsaHashedImportParams,
+    RsaHashedKeyGenParams, RsaOaepParams, RsaPssParams,
+};
+use crate::dom::bindings::codegen::UnionTypes::{
+    ArrayBufferViewOrArrayBuffer, ArrayBufferViewOrArrayBufferOrJsonWebKey, ObjectOrString,
+};
+use crate::dom::bindings::error::{Error, ErrorResult, Fallible};
+use crate::dom::bindings::refcounted::TrustedPromise;
+use crate::dom::bindings::reflector::{reflect_dom_object, DomObject, Reflector};
+use crate::dom::bindings::root::DomRoot;
+use crate::dom::bindings::str::DOMString;
+use crate::dom::bindings::trace::RootedTraceableBox;
+use crate::dom::cryptokey::{set_property, CryptoKey, KeyAlgorithm, KeyHandle};
+use crate::dom::globalscope::GlobalScope;
+use crate::dom::promise::Promise;
+use crate::realms::{enter_realm, InRealm};
+use crate::script_runtime::JSContext;
+use crate::task_source::dom_manipulation::DOMManipulationTaskSource;
+use crate::task_source::TaskSource;
+use crossbeam_channel::{bounded, Sender};
+use dom_struct::dom_struct;
+use js::conversions::{ConversionResult, FromJSValConvertible};
+use js::jsapi::{JSObject, JS_NewPlainObject};
+use js::jsval::{ObjectValue, UndefinedValue};
+use js::rust::HandleValue;
+use js::typedarray::{ArrayBuffer, CreateWith};
+use openssl::bn::{BigNum, BigNumContext, BigNumRef};
+use openssl::derive::Deriver;
+use openssl::ec::{EcGroup, EcKey, EcKeyRef, EcPoint, PointConversionForm};
+use openssl::ecdsa::EcdsaSig;
+use openssl::encrypt::{Decrypter, Encrypter};
+use openssl::error::ErrorStack;
+use openssl::hash::{hash, MessageDigest};
+use openssl::memcmp;
+use openssl::nid::Nid;
+use openssl::pkcs5::pbkdf2_hmac;
+use openssl::pkey::{HasPublic, Id, PKey, PKeyRef, Private, Public};
+use openssl::rand::rand_bytes;
+use openssl::rsa::{Padding, Rsa};
+use openssl::sign::{RsaPssSaltlen, Signer, Verifier};
+use openssl::symm::{self, Cipher};
+use std::ptr;
+use std::rc::Rc;
+use std::thread;
+
+/// The number of threads running the operations of every `SubtleCrypto` of the process.
+const CRYPTO_THREADS: usize = 4;
+
+/// The number of operations that can wait for a thread, before new ones are rejected.
+const CRYPTO_QUEUE_CAPACITY: usize = 256;
+
+type CryptoJob = Box<dyn FnOnce() + Send>;
+
+lazy_static! {
+    /// The queue of the WebCrypto threads, `None` if none of them could be spawned.
+    static ref CRYPTO_QUEUE: Option<Sender<CryptoJob>> = spawn_crypto_threads();
+}
+
+fn spawn_crypto_threads() -> Option<Sender<CryptoJob>> {
+    let (sender, receiver) = bounded::<CryptoJob>(CRYPTO_QUEUE_CAPACITY);
+    let mut spawned = 0;
+    for index in 0..CRYPTO_THREADS {
+        let receiver = receiver.clone();
+        let result = thread::Builder::new()
+            .name(format!("WebCrypto#{}", index))
+            .spawn(move || {
+                while let Ok(job) = receiver.recv() {
+                    job();
+                }
+            });
+        match result {
+            Ok(_) => spawned += 1,
+            Err(e) => warn!("Failed to spawn a WebCrypto thread ({:?}).", e),
+        }
+    }
+    if spawned == 0 {
+        return None;
+    }
+    Some(sender)
+}
+
+/// The names of the algorithms that are supported.
+/// <https://w3c.github.io/webcrypto/#algorithm-overview>
+#[derive(Clone, Copy, Debug, Eq, JSTraceable, MallocSizeOf, PartialEq)]
+pub enum AlgorithmName {
+    Sha1,
+    Sha256,
+    Sha384,
+    Sha512,
+    Hmac,
+    AesCbc,
+    AesCtr,
+    AesGcm,
+    Ecdsa,
+    Ecdh,
+    RsaOaep,
+    RsaPss,
+    Pbkdf2,
+    Hkdf,
+}
+
+const ALGORITHM_NAMES: [AlgorithmName; 14] = [
+    AlgorithmName::Sha1,
+    AlgorithmName::Sha256,
+    AlgorithmName::Sha384,
+    AlgorithmName::Sha512,
+    AlgorithmName::Hmac,
+    AlgorithmName::AesCbc,
+    AlgorithmName::AesCtr,
+    AlgorithmName::AesGcm,
+    AlgorithmName::Ecdsa,
+    AlgorithmName::Ecdh,
+    AlgorithmName::RsaOaep,
+    AlgorithmName::RsaPss,
+    AlgorithmName::Pbkdf2,
+    AlgorithmName::Hkdf,
+];
+
+impl AlgorithmName {
+    /// The algorithm registered under `name`, which is matched ASCII case-insensitively.
+    fn from_name(name: &str) -> Option<AlgorithmName> {
+        ALGORITHM_NAMES
+            .iter()
+            .find(|algorithm| algorithm.as_str().eq_ignore_ascii_case(name))
+            .cloned()
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            AlgorithmName::Sha1 => "SHA-1",
+            AlgorithmName::Sha256 => "SHA-256",
+            AlgorithmName::Sha384 => "SHA-384",
+            AlgorithmName::Sha512 => "SHA-512",
+            AlgorithmName::Hmac => "HMAC",
+            AlgorithmName::AesCbc => "AES-CBC",
+            AlgorithmName::AesCtr => "AES-CTR",
+            AlgorithmName::AesGcm => "AES-GCM",
+            AlgorithmName::Ecdsa => "ECDSA",
+            AlgorithmName::Ecdh => "ECDH",
+            AlgorithmName::RsaOaep => "RSA-OAEP",
+            AlgorithmName::RsaPss => "RSA-PSS",
+            AlgorithmName::Pbkdf2 => "PBKDF2",
+            AlgorithmName::Hkdf => "HKDF",
+        }
+    }
+
+    /// The digest of a hash algorithm.
+    fn message_digest(&self) -> MessageDigest {
+        match *self {
+            AlgorithmName::Sha1 => MessageDigest::sha1(),
+            AlgorithmName::Sha256 => MessageDigest::sha256(),
+            AlgorithmName::Sha384 => MessageDigest::sha384(),
+            AlgorithmName::Sha512 => MessageDigest::sha512(),
+            _ => unreachable!("{} is not a hash algorithm", self.as_str()),
+        }
+    }
+
+    /// The block size of a hash algorithm in bits, which is the default
+    /// length of HMAC keys.
+    fn block_size(&self) -> u32 {
+        match *self {
+            AlgorithmName::Sha1 | AlgorithmName::Sha256 => 512,
+            _ => 1024,
+        }
+    }
+
+    /// The suffix of the JWK `alg` values of keys used with a hash algorithm.
+    fn jwk_suffix(&self) -> &'static str {
+        match *self {
+            AlgorithmName::Sha1 => "1",
+            AlgorithmName::Sha256 => "256",
+            AlgorithmName::Sha384 => "384",
+            _ => "512",
+        }
+    }
+}
+
+/// The elliptic curves that are supported.
+/// <https://w3c.github.io/webcrypto/#dfn-NamedCurve>
+#[derive(Clone, Copy, Debug, Eq, JSTraceable, MallocSizeOf, PartialEq)]
+pub enum NamedCurve {
+    P256,
+    P384,
+}
+
+impl NamedCurve {
+    fn from_name(name: &str) -> Option<NamedCurve> {
+        match name {
+            "P-256" => Some(NamedCurve::P256),
+            "P-384" => Some(NamedCurve::P384),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            NamedCurve::P256 => "P-256",
+            NamedCurve::P384 => "P-384",
+        }
+    }
+
+    fn group(&self) -> Fallible<EcGroup> {
+        let nid = match *self {
+            NamedCurve::P256 => Nid::X9_62_PRIME256V1,
+            NamedCurve::P384 => Nid::SECP384R1,
+        };
+        EcGroup::from_curve_name(nid).map_err(|_| Error::Operation)
+    }
+
+    fn from_group_nid(nid: Option<Nid>) -> Option<NamedCurve> {
+        match nid {
+            Some(Nid::X9_62_PRIME256V1) => Some(NamedCurve::P256),
+            Some(Nid::SECP384R1) => Some(NamedCurve::P384),
+            _ => None,
+        }
+    }
+
+    /// The length in bytes of the coordinates and private keys on the curve.
+    fn field_size(&self) -> usize {
+        match *self {
+            NamedCurve::P256 => 32,
+            NamedCurve::P384 => 48,
+        }
+    }
+
+    /// The suffix of the JWK `alg` values of ECDSA keys on the curve.
+    fn jwk_suffix(&self) -> &'static str {
+        match *self {
+            NamedCurve::P256 => "256",
+            NamedCurve::P384 => "384",
+        }
+    }
+}
+
+/// The operations for which algorithms are normalized.
+/// <https://w3c.github.io/webcrypto/#supported-operation>
+#[derive(Clone, Copy, PartialEq)]
+enum Operation {
+    Encrypt,
+    Decrypt,
+    Sign,
+    Verify,
+    Digest,
+    GenerateKey,
+    DeriveBits,
+    ImportKey,
+    GetKeyLength,
+}
+
+/// An algorithm together with the parameters it was given.
+/// <https://w3c.github.io/webcrypto/#algorithm-normalization-normalize-an-algorithm>
+enum NormalizedAlgorithm {
+    Plain(AlgorithmName),
+    AesCbc {
+        iv: Vec<u8>,
+    },
+    AesCtr {
+        counter: Vec<u8>,
+        length: u8,
+    },
+    AesGcm {
+        iv: Vec<u8>,
+        additional_data: Vec<u8>,
+        tag_length: Option<u8>,
+    },
+    AesKey {
+        name: AlgorithmName,
+        length: u16,
+    },
+    Hmac {
+        hash: AlgorithmName,
+        length: Option<u32>,
+    },
+    RsaKeyGen {
+        name: AlgorithmName,
+        modulus_length: u32,
+        public_exponent: Vec<u8>,
+        hash: AlgorithmName,
+    },
+    RsaImport {
+        name: AlgorithmName,
+        hash: AlgorithmName,
+    },
+    RsaOaep {
+        label: Vec<u8>,
+    },
+    RsaPss {
+        salt_length: u32,
+    },
+    Ecdsa {
+        hash: AlgorithmName,
+    },
+    EcKey {
+        name: AlgorithmName,
+        named_curve: String,
+    },
+    Ecdh {
+        public: KeyParts,
+    },
+    Hkdf {
+        hash: AlgorithmName,
+        salt: Vec<u8>,
+        info: Vec<u8>,
+    },
+    Pbkdf2 {
+        hash: AlgorithmName,
+        salt: Vec<u8>,
+        iterations: u32,
+    },
+}
+
+impl NormalizedAlgorithm {
+    fn name(&self) -> AlgorithmName {
+        match *self {
+            NormalizedAlgorithm::Plain(name) |
+            NormalizedAlgorithm::AesKey { name, .. } |
+            NormalizedAlgorithm::RsaKeyGen { name, .. } |
+            NormalizedAlgorithm::RsaImport { name, .. } |
+            NormalizedAlgorithm::EcKey { name, .. } => name,
+            NormalizedAlgorithm::AesCbc { .. } => AlgorithmName::AesCbc,
+            NormalizedAlgorithm::AesCtr { .. } => AlgorithmName::AesCtr,
+            NormalizedAlgorithm::AesGcm { .. } => AlgorithmName::AesGcm,
+            NormalizedAlgorithm::Hmac { .. } => AlgorithmName::Hmac,
+            NormalizedAlgorithm::RsaOaep { .. } => AlgorithmName::RsaOaep,
+            NormalizedAlgorithm::RsaPss { .. } => AlgorithmName::RsaPss,
+            NormalizedAlgorithm::Ecdsa { .. } => AlgorithmName::Ecdsa,
+            NormalizedAlgorithm::Ecdh { .. } => AlgorithmName::Ecdh,
+            NormalizedAlgorithm::Hkdf { .. } => AlgorithmName::Hkdf,
+            NormalizedAlgorithm::Pbkdf2 { .. } => AlgorithmName::Pbkdf2,
+        }
+    }
+}
+
+/// What operations need of a `CryptoKey`, which unlike the key itself can be
+/// sent to the thread that the operations run on.
+#[derive(Clone)]
+struct KeyParts {
+    extractable: bool,
+    algorithm: KeyAlgorithm,
+    usages: Vec<KeyUsage>,
+    handle: KeyHandle,
+}
+
+impl KeyParts {
+    fn of(key: &CryptoKey) -> KeyParts {
+        KeyParts {
+            extractable: key.is_extractable(),
+            algorithm: key.algorithm().clone(),
+            usages: key.usages().to_vec(),
+            handle: key.handle().clone(),
+        }
+    }
+
+    fn key_type(&self) -> KeyType {
+        match self.handle {
+            KeyHandle::Secret(_) => KeyType::Secret,
+            KeyHandle::Private(_) => KeyType::Private,
+            KeyHandle::Public(_) => KeyType::Public,
+        }
+    }
+
+    fn has_usage(&self, usage: KeyUsage) -> bool {
+        self.usages.contains(&usage)
+    }
+
+    fn into_key(self, global: &GlobalScope) -> DomRoot<CryptoKey> {
+        CryptoKey::new(
+            global,
+            self.extractable,
+            self.algorithm,
+            self.usages,
+            self.handle,
+        )
+    }
+}
+
+/// The members of a `JsonWebKey` dictionary, which unlike the dictionary
+/// can be sent to the thread that the operations run on.
+#[derive(Default)]
+struct Jwk {
+    kty: Option<String>,
+    use_: Option<String>,
+    key_ops: Option<Vec<String>>,
+    alg: Option<String>,
+    ext: Option<bool>,
+    crv: Option<String>,
+    x: Option<String>,
+    y: Option<String>,
+    d: Option<String>,
+    n: Option<String>,
+    e: Option<String>,
+    p: Option<String>,
+    q: Option<String>,
+    dp: Option<String>,
+    dq: Option<String>,
+    qi: Option<String>,
+    /// Whether the `oth` member is present, since keys with more than two
+    /// primes aren't supported.
+    oth: bool,
+    k: Option<String>,
+}
+
+impl Jwk {
+    fn from_dictionary(jwk: &JsonWebKey) -> Jwk {
+        let string = |member: &Option<DOMString>| member.as_ref().map(|string| string.to_string());
+        Jwk {
+            kty: string(&jwk.kty),
+            use_: string(&jwk.use_),
+            key_ops: jwk
+                .key_ops
+                .as_ref()
+                .map(|key_ops| key_ops.iter().map(|op| op.to_string()).collect()),
+            alg: string(&jwk.alg),
+            ext: jwk.ext,
+            crv: string(&jwk.crv),
+            x: string(&jwk.x),
+            y: string(&jwk.y),
+            d: string(&jwk.d),
+            n: string(&jwk.n),
+            e: string(&jwk.e),
+            p: string(&jwk.p),
+            q: string(&jwk.q),
+            dp: string(&jwk.dp),
+            dq: string(&jwk.dq),
+            qi: string(&jwk.qi),
+            oth: jwk.oth.is_some(),
+            k: string(&jwk.k),
+        }
+    }
+
+    fn into_dictionary(self) -> JsonWebKey {
+        let mut jwk = JsonWebKey::empty();
+        jwk.kty = self.kty.map(DOMString::from);
+        jwk.use_ = self.use_.map(DOMString::from);
+        jwk.key_ops = self
+            .key_ops
+            .map(|key_ops| key_ops.into_iter().map(DOMString::from).collect());
+        jwk.alg = self.alg.map(DOMString::from);
+        jwk.ext = self.ext;
+        jwk.crv = self.crv.map(DOMString::from);
+        jwk.x = self.x.map(DOMString::from);
+        jwk.y = self.y.map(DOMString::from);
+        jwk.d = self.d.map(DOMString::from);
+        jwk.n = self.n.map(DOMString::from);
+        jwk.e = self.e.map(DOMString::from);
+        jwk.p = self.p.map(DOMString::from);
+        jwk.q = self.q.map(DOMString::from);
+        jwk.dp = self.dp.map(DOMString::from);
+        jwk.dq = self.dq.map(DOMString::from);
+        jwk.qi = self.qi.map(DOMString::from);
+        jwk.k = self.k.map(DOMString::from);
+        jwk
+    }
+}
+
+/// The key material passed to `importKey`.
+enum KeyData {
+    Bytes(Vec<u8>),
+    Jwk(Jwk),
+}
+
+/// The result of `generateKey`.
+enum GeneratedKey {
+    Key(KeyParts),
+    Pair { public: KeyParts, private: KeyParts },
+}
+
+/// The result of `exportKey`.
+enum ExportedKey {
+    Bytes(Vec<u8>),
+    Jwk(Jwk),
+}
+
+// https://w3c.github.io/webcrypto/#subtlecrypto-interface
+#[dom_struct]
+pub struct SubtleCrypto {
+    reflector_: Reflector,
+}
+
+impl SubtleCrypto {
+    fn new_inherited() -> SubtleCrypto {
+        SubtleCrypto {
+            reflector_: Reflector::new(),
+        }
+    }
+
+    pub fn new(global: &GlobalScope) -> DomRoot<SubtleCrypto> {
+        reflect_dom_object(
+            Box::new(SubtleCrypto::new_inherited()),
+            global,
+            SubtleCryptoBinding::Wrap,
+        )
+    }
+
+    /// Run `operation` in parallel on the WebCrypto threads, then settle `promise`
+    /// with its result from a task queued on the DOM manipulation task source.
+    /// `promise` is rejected with an `OperationError` if the operation can't be queued.
+    fn perform<T, F>(&self, promise: &Rc<Promise>, operation: F, settle: fn(&Promise, T))
+    where
+        T: Send + 'static,
+        F: FnOnce() -> Fallible<T> + Send + 'static,
+    {
+        let queue = match *CRYPTO_QUEUE {
+            Some(ref queue) => queue,
+            None => return promise.reject_error(Error::Operation),
+        };
+        let global = self.global();
+        let trusted_promise = TrustedPromise::new(promise.clone());
+        let task_source = global.dom_manipulation_task_source();
+        let canceller = global.task_canceller(DOMManipulationTaskSource::NAME);
+        let job = Box::new(move || {
+            let result = operation();
+            let _ = task_source.queue_with_canceller(
+                task!(settle_crypto_promise: move || {
+                    let promise = trusted_promise.root();
+                    let _ac = enter_realm(&*promise);
+                    match result {
+                        Ok(value) => settle(&promise, value),
+                        Err(error) => promise.reject_error(error),
+                    }
+                }),
+                &canceller,
+            );
+        });
+        if let Err(e) = queue.try_send(job) {
+            warn!("Failed to queue a WebCrypto operation ({:?}).", e);
+            promise.reject_error(Error::Operation);
+        }
+    }
+}
+
+impl SubtleCryptoMethods for SubtleCrypto {
+    // https://w3c.github.io/webcrypto/#dfn-SubtleCrypto-method-encrypt
+    fn Encrypt(
+        &self,
+        cx: JSContext,
+        algorithm: ObjectOrString,
+        key: &CryptoKey,
+        data: ArrayBufferViewOrArrayBuffer,
+        comp: InRealm,
+    ) -> Rc<Promise> {
+        let promise = Promise::new_in_current_realm(&self.global(), comp);
+        let normalized = match normalize_algorithm(cx, &algorithm, Operation::Encrypt) {
+            Ok(normalized) => normalized,
+            Err(error) => {
+                promise.reject_error(error);
+                return promise;
+            },
+        };
+        let key = KeyParts::of(key);
+        let data = buffer_source_bytes(&data);
+        self.perform(
+            &promise,
+            move || {
+                check_key(&key, &normalized, KeyUsage::Encrypt)?;
+                encrypt(&normalized, &key, &data)
+            },
+            resolve_with_array_buffer,
+        );
+        promise
+    }
+
+    // https://w3c.github.io/webcrypto/#dfn-SubtleCrypto-method-decrypt
+    fn Decrypt(
+        &self,
+        cx: JSContext,
+        algorithm: ObjectOrString,
+        key: &CryptoKey,
+        data: ArrayBufferViewOrArrayBuffer,
+        comp: InRealm,
+    ) -> Rc<Promise> {
+        let promise = Promise::new_in_current_realm(&self.global(), comp);
+        let normalized = match normalize_algorithm(cx, &algorithm, Operation::Decrypt) {
+            Ok(normalized) => normalized,
+            Err(error) => {
+                promise.reject_error(error);
+                return promise;
+            },
+        };
+        let key = KeyParts::of(key);
+        let data = buffer_source_bytes(&data);
+        self.perform(
+            &promise,
+            move || {
+                check_key(&key, &normalized, KeyUsage::Decrypt)?;
+                decrypt(&normalized, &key, &data)
+            },
+            resolve_with_array_buffer,
+        );
+        promise
+    }
+
+    // https://w3c.github.io/webcrypto/#dfn-SubtleCrypto-method-sign
+    fn Sign(
+        &self,
+        cx: JSContext,
+        algorithm: ObjectOrString,
+        key: &CryptoKey,
+        data: ArrayBufferViewOrArrayBuffer,
+        comp: InRealm,
+    ) -> Rc<Promise> {
+        let promise = Promise::new_in_current_realm(&self.global(), comp);
+        let normalized = match normalize_algorithm(cx, &algorithm, Operation::Sign) {
+            Ok(normalized) => normalized,
+            Err(error) => {
+                promise.reject_error(error);
+                return promise;
+            },
+        };
+        let key = KeyParts::of(key);
+        let data = buffer_source_bytes(&data);
+        self.perform(
+            &promise,
+            move || {
+                check_key(&key, &normalized, KeyUsage::Sign)?;
+                sign(&normalized, &key, &data)
+            },
+            resolve_with_array_buffer,
+        );
+        promise
+    }
+
+    // https://w3c.github.io/webcrypto/#dfn-SubtleCrypto-method-verify
+    fn Verify(
+        &self,
+        cx: JSContext,
+        algorithm: ObjectOrString,
+        key: &CryptoKey,
+        signature: ArrayBufferViewOrArrayBuffer,
+        data: ArrayBufferViewOrArrayBuffer,
+        comp: InRealm,
+    ) -> Rc<Promise> {
+        let promise = Promise::new_in_current_realm(&self.global(), comp);
+        let normalized = match normalize_algorithm(cx, &algorithm, Operation::Verify) {
+            Ok(normalized) => normalized,
+            Err(error) => {
+                promise.reject_error(error);
+                return promise;
+            },
+        };
+        let key = KeyParts::of(key);
+        let signature = buffer_source_bytes(&signature);
+        let data = buffer_source_bytes(&data);
+        self.perform(
+            &promise,
+            move || {
+                check_key(&key, &normalized, KeyUsage::Verify)?;
+                verify(&normalized, &key, &signature, &data)
+            },
+            |promise, valid| promise.resolve_native(&valid),
+        );
+        promise
+    }
+
+    // https://w3c.github.io/webcrypto/#dfn-SubtleCrypto-method-digest
+    fn Digest(
+        &self,
+        cx: JSContext,
+        algorithm: ObjectOrString,
+        data: ArrayBufferViewOrArrayBuffer,
+        comp: InRealm,
+    ) -> Rc<Promise> {
+        let promise = Promise::new_in_current_realm(&self.global(), comp);
+        let name = match normalize_algorithm(cx, &algorithm, Operation::Digest) {
+            Ok(normalized) => normalized.name(),
+            Err(error) => {
+                promise.reject_error(error);
+                return promise;
+            },
+        };
+        let data = buffer_source_bytes(&data);
+        self.perform(
+            &promise,
+            move || digest(name, &data),
+            resolve_with_array_buffer,
+        );
+        promise
+    }
+
+    // https://w3c.github.io/webcrypto/#dfn-SubtleCrypto-method-generateKey
+    fn GenerateKey(
+        &self,
+        cx: JSContext,
+        algorithm: ObjectOrString,
+        extractable: bool,
+        key_usages: Vec<KeyUsage>,
+        comp: InRealm,
+    ) -> Rc<Promise> {
+        let promise = Promise::new_in_current_realm(&self.global(), comp);
+        let normalized = match normalize_algorithm(cx, &algorithm, Operation::GenerateKey) {
+            Ok(normalized) => normalized,
+            Err(error) => {
+                promise.reject_error(error);
+                return promise;
+            },
+        };
+        self.perform(
+            &promise,
+            move || generate_key(&normalized, extractable, &key_usages),
+            |promise, generated| {
+                let global = promise.global();
+                match generated {
+                    GeneratedKey::Key(key) => promise.resolve_native(&key.into_key(&global)),
+                    GeneratedKey::Pair { public, private } => {
+                        promise.resolve_native(&CryptoKeyPair {
+                            publicKey: Some(public.into_key(&global)),
+                            privateKey: Some(private.into_key(&global)),
+                        })
+                    },
+                }
+            },
+        );
+        promise
+    }
+
+    // https://w3c.github.io/webcrypto/#dfn-SubtleCrypto-method-deriveKey
+    fn DeriveKey(
+        &self,
+        cx: JSContext,
+        algorithm: ObjectOrString,
+        base_key: &CryptoKey,
+        derived_key_type: ObjectOrString,
+        extractable: bool,
+        key_usages: Vec<KeyUsage>,
+        comp: InRealm,
+    ) -> Rc<Promise> {
+        let promise = Promise::new_in_current_realm(&self.global(), comp);
+
+        // Steps 2-7.
+        let normalized =
+            normalize_algorithm(cx, &algorithm, Operation::DeriveBits).and_then(|normalized| {
+                let import_params =
+                    normalize_algorithm(cx, &derived_key_type, Operation::ImportKey)?;
+                let length_params =
+                    normalize_algorithm(cx, &derived_key_type, Operation::GetKeyLength)?;
+                Ok((normalized, import_params, length_params))
+            });
+        let (normalized, import_params, length_params) = match normalized {
+            Ok(normalized) => normalized,
+            Err(error) => {
+                promise.reject_error(error);
+                return promise;
+            },
+        };
+        let base_key = KeyParts::of(base_key);
+        self.perform(
+            &promise,
+            move || {
+                derive_key(
+                    &normalized,
+                    &import_params,
+                    &length_params,
+                    &base_key,
+                    extractable,
+                    &key_usages,
+                )
+            },
+            resolve_with_key,
+        );
+        promise
+    }
+
+    // https://w3c.github.io/webcrypto/#dfn-SubtleCrypto-method-deriveBits
+    fn DeriveBits(
+        &self,
+        cx: JSContext,
+        algorithm: ObjectOrString,
+        base_key: &CryptoKey,
+        length: u32,
+        comp: InRealm,
+    ) -> Rc<Promise> {
+        let promise = Promise::new_in_current_realm(&self.global(), comp);
+        let normalized = match normalize_algorithm(cx, &algorithm, Operation::DeriveBits) {
+            Ok(normalized) => normalized,
+            Err(error) => {
+                promise.reject_error(error);
+                return promise;
+            },
+        };
+        let base_key = KeyParts::of(base_key);
+        self.perform(
+            &promise,
+            move || {
+                check_key(&base_key, &normalized, KeyUsage::DeriveBits)?;
+                derive_bits(&normalized, &base_key, Some(length))
+            },
+            resolve_with_array_buffer,
+        );
+        promise
+    }
+
+    // https://w3c.github.io/webcrypto/#dfn-SubtleCrypto-method-importKey
+    fn ImportKey(
+        &self,
+        cx: JSContext,
+        format: KeyFormat,
+        key_data: ArrayBufferViewOrArrayBufferOrJsonWebKey,
+        algorithm: ObjectOrString,
+        extractable: bool,
+        key_usages: Vec<KeyUsage>,
+        comp: InRealm,
+    ) -> Rc<Promise> {
+        let promise = Promise::new_in_current_realm(&self.global(), comp);
+
+        // Step 2.
+        let key_data = match (format, key_data) {
+            (KeyFormat::Jwk, ArrayBufferViewOrArrayBufferOrJsonWebKey::JsonWebKey(jwk)) => {
+                KeyData::Jwk(Jwk::from_dictionary(&jwk))
+            },
+            (KeyFormat::Jwk, _) | (_, ArrayBufferViewOrArrayBufferOrJsonWebKey::JsonWebKey(_)) => {
+                promise.reject_error(Error::Type(
+                    "The key data doesn't match its format".to_owned(),
+                ));
+                return promise;
+            },
+            (_, ArrayBufferViewOrArrayBufferOrJsonWebKey::ArrayBufferView(view)) => {
+                KeyData::Bytes(view.to_vec())
+            },
+            (_, ArrayBufferViewOrArrayBufferOrJsonWebKey::ArrayBuffer(buffer)) => {
+                KeyData::Bytes(buffer.to_vec())
+            },
+        };
+
+        // Steps 3-4.
+        let normalized = match normalize_algorithm(cx, &algorithm, Operation::ImportKey) {
+            Ok(normalized) => normalized,
+            Err(error) => {
+                promise.reject_error(error);
+                return promise;
+            },
+        };
+
+        // Steps 5-10.
+        self.perform(
+            &promise,
+            move || {
+                let key = import_key(format, key_data, &normalized, extractable, &key_usages)?;
+                if key.key_type() != KeyType::Public && key.usages.is_empty() {
+                    return Err(Error::Syntax);
+                }
+                Ok(key)
+            },
+            resolve_with_key,
+        );
+        promise
+    }
+
+    // https://w3c.github.io/webcrypto/#dfn-SubtleCrypto-method-exportKey
+    fn ExportKey(&self, format: KeyFormat, key: &CryptoKey, comp: InRealm) -> Rc<Promise> {
+        let promise = Promise::new_in_current_realm(&self.global(), comp);
+        let key = KeyParts::of(key);
+        self.perform(
+            &promise,
+            move || export_key(format, &key),
+            |promise, exported| match exported {
+                ExportedKey::Bytes(bytes) => resolve_with_array_buffer(promise, bytes),
+                ExportedKey::Jwk(jwk) => promise.resolve_native(&jwk.into_dictionary()),
+            },
+        );
+        promise
+    }
+}
+
+fn buffer_source_bytes(source: &ArrayBufferViewOrArrayBuffer) -> Vec<u8> {
+    match *source {
+        ArrayBufferViewOrArrayBuffer::ArrayBufferView(ref view) => view.to_vec(),
+        ArrayBufferViewOrArrayBuffer::ArrayBuffer(ref buffer) => buffer.to_vec(),
+    }
+}
+
+/// Resolve `promise` with a new `ArrayBuffer` holding `bytes`.
+#[allow(unsafe_code)]
+fn resolve_with_array_buffer(promise: &Promise, bytes: Vec<u8>) {
+    let cx = promise.global().get_cx();
+    rooted!(in(*cx) let mut array_buffer = ptr::null_mut::<JSObject>());
+    let created =
+        unsafe { ArrayBuffer::create(*cx, CreateWith::Slice(&bytes), array_buffer.handle_mut()) };
+    if created.is_err() {
+        return promise.reject_error(Error::JSFailed);
+    }
+    rooted!(in(*cx) let value = ObjectValue(array_buffer.get()));
+    promise.resolve(cx, value.handle());
+}
+
+/// Resolve `promise` with a new `CryptoKey` made of `key`.
+fn resolve_with_key(promise: &Promise, key: KeyParts) {
+    promise.resolve_native(&key.into_key(&promise.global()));
+}
+
+/// Convert `value` to the dictionary `T`.
+#[allow(unsafe_code)]
+fn convert_params<T: FromJSValConvertible<Config = ()>>(
+    cx: JSContext,
+    value: HandleValue,
+) -> Fallible<T> {
+    match unsafe { T::from_jsval(*cx, value, ()) } {
+        Ok(ConversionResult::Success(params)) => Ok(params),
+        Ok(ConversionResult::Failure(error)) => Err(Error::Type(error.into_owned())),
+        Err(()) => Err(Error::JSFailed),
+    }
+}
+
+/// <https://w3c.github.io/webcrypto/#algorithm-normalization-normalize-an-algorithm>
+#[allow(unsafe_code)]
+fn normalize_algorithm(
+    cx: JSContext,
+    algorithm: &ObjectOrString,
+    operation: Operation,
+) -> Fallible<NormalizedAlgorithm> {
+    // A string is normalized as if it were the dictionary `{ name: algorithm }`.
+    rooted!(in(*cx) let mut value = UndefinedValue());
+    match *algorithm {
+        ObjectOrString::Object(ref object) => value.set(ObjectValue(object.get())),
+        ObjectOrString::String(ref name) => {
+            rooted!(in(*cx) let object = unsafe { JS_NewPlainObject(*cx) });
+            assert!(!object.is_null());
+            set_property(cx, object.handle(), "name", name);
+            value.set(ObjectValue(object.get()));
+        },
+    }
+
+    let initial: Algorithm = convert_params(cx, value.handle())?;
+    let name = AlgorithmName::from_name(&initial.name).ok_or(Error::NotSupported)?;
+    let value = value.handle();
+    let normalized = match (operation, name) {
+        (Operation::Encrypt, AlgorithmName::RsaOaep) |
+        (Operation::Decrypt, AlgorithmName::RsaOaep) => {
+            let params: RootedTraceableBox<RsaOaepParams> = convert_params(cx, value)?;
+            NormalizedAlgorithm::RsaOaep {
+                label: params
+                    .label
+                    .as_ref()
+                    .map(buffer_source_bytes)
+                    .unwrap_or_default(),
+            }
+        },
+        (Operation::Encrypt, AlgorithmName::AesCbc) |
+        (Operation::Decrypt, AlgorithmName::AesCbc) => {
+            let params: RootedTraceableBox<AesCbcParams> = convert_params(cx, value)?;
+            NormalizedAlgorithm::AesCbc {
+                iv: buffer_source_bytes(&params.iv),
+            }
+        },
+        (Operation::Encrypt, AlgorithmName::AesCtr) |
+        (Operation::Decrypt, AlgorithmName::AesCtr) => {
+            let params: RootedTraceableBox<AesCtrParams> = convert_params(cx, value)?;
+            NormalizedAlgorithm::AesCtr {
+                counter: buffer_source_bytes(&params.counter),
+                length: params.length,
+            }
+        },
+        (Operation::Encrypt, AlgorithmName::AesGcm) |
+        (Operation::Decrypt, AlgorithmName::AesGcm) => {
+            let params: RootedTraceableBox<AesGcmParams> = convert_params(cx, value)?;
+            NormalizedAlgorithm::AesGcm {
+                iv: buffer_source_bytes(&params.iv),
+                additional_data: params
+                    .additionalData
+                    .as_ref()
+                    .map(buffer_source_bytes)
+                    .unwrap_or_default(),
+                tag_length: params.tagLength,
+            }
+        },
+        (Operation::Sign, AlgorithmName::RsaPss) | (Operation::Verify, AlgorithmName::RsaPss) => {
+            let params: RsaPssParams = convert_params(cx, value)?;
+            NormalizedAlgorithm::RsaPss {
+                salt_length: params.saltLength,
+            }
+        },
+        (Operation::Sign, AlgorithmName::Ecdsa) | (Operation::Verify, AlgorithmName::Ecdsa) => {
+            let params: RootedTraceableBox<EcdsaParams> = convert_params(cx, value)?;
+            NormalizedAlgorithm::Ecdsa {
+                hash: normalize_hash(cx, &params.hash)?,
+            }
+        },
+        (Operation::Sign, AlgorithmName::Hmac) | (Operation::Verify, AlgorithmName::Hmac) => {
+            NormalizedAlgorithm::Plain(name)
+        },
+        (Operation::Digest, AlgorithmName::Sha1) |
+        (Operation::Digest, AlgorithmName::Sha256) |
+        (Operation::Digest, AlgorithmName::Sha384) |
+        (Operation::Digest, AlgorithmName::Sha512) => NormalizedAlgorithm::Plain(name),
+        (Operation::GenerateKey, AlgorithmName::RsaOaep) |
+        (Operation::GenerateKey, AlgorithmName::RsaPss) => {
+            let params: RootedTraceableBox<RsaHashedKeyGenParams> = convert_params(cx, value)?;
+            NormalizedAlgorithm::RsaKeyGen {
+                name,
+                modulus_length: params.parent.modulusLength,
+                public_exponent: buffer_source_bytes(&params.parent.publicExponent),
+                hash: normalize_hash(cx, &params.hash)?,
+            }
+        },
+        (Operation::GenerateKey, AlgorithmName::Ecdsa) |
+        (Operation::GenerateKey, AlgorithmName::Ecdh) => {
+            let params: EcKeyGenParams = convert_params(cx, value)?;
+            NormalizedAlgorithm::EcKey {
+                name,
+                named_curve: String::from(params.namedCurve),
+            }
+        },
+        (Operation::GenerateKey, AlgorithmName::AesCbc) |
+        (Operation::GenerateKey, AlgorithmName::AesCtr) |
+        (Operation::GenerateKey, AlgorithmName::AesGcm) => {
+            let params: AesKeyGenParams = convert_params(cx, value)?;
+            NormalizedAlgorithm::AesKey {
+                name,
+                length: params.length,
+            }
+        },
+        (Operation::GenerateKey, AlgorithmName::Hmac) => {
+            let params: RootedTraceableBox<HmacKeyGenParams> = convert_params(cx, value)?;
+            NormalizedAlgorithm::Hmac {
+                hash: normalize_hash(cx, &params.hash)?,
+                length: params.length,
+            }
+        },
+        (Operation::DeriveBits, AlgorithmName::Ecdh) => {
+            let params: EcdhKeyDeriveParams = convert_params(cx, value)?;
+            NormalizedAlgorithm::Ecdh {
+                public: KeyParts::of(&params.public),
+            }
+        },
+        (Operation::DeriveBits, AlgorithmName::Hkdf) => {
+            let params: RootedTraceableBox<HkdfParams> = convert_params(cx, value)?;
+            NormalizedAlgorithm::Hkdf {
+                hash: normalize_hash(cx, &params.hash)?,
+                salt: buffer_source_bytes(&params.salt),
+                info: buffer_source_bytes(&params.info),
+            }
+        },
+        (Operation::DeriveBits, AlgorithmName::Pbkdf2) => {
+            let params: RootedTraceableBox<Pbkdf2Params> = convert_params(cx, value)?;
+            NormalizedAlgorithm::Pbkdf2 {
+                hash: normalize_hash(cx, &params.hash)?,
+                salt: buffer_source_bytes(&params.salt),
+                iterations: params.iterations,
+            }
+        },
+        (Operation::ImportKey, AlgorithmName::RsaOaep) |
+        (Operation::ImportKey, AlgorithmName::RsaPss) => {
+            let params: RootedTraceableBox<RsaHashedImportParams> = convert_params(cx, value)?;
+            NormalizedAlgorithm::RsaImport {
+                name,
+                hash: normalize_hash(cx, &params.hash)?,
+            }
+        },
+        (Operation::ImportKey, AlgorithmName::Ecdsa) |
+        (Operation::ImportKey, AlgorithmName::Ecdh) => {
+            let params: EcKeyImportParams = convert_params(cx, value)?;
+            NormalizedAlgorithm::EcKey {
+                name,
+                named_curve: String::from(params.namedCurve),
+            }
+        },
+        (Operation::ImportKey, AlgorithmName::Hmac) |
+        (Operation::GetKeyLength, AlgorithmName::Hmac) => {
+            let params: RootedTraceableBox<HmacImportParams> = convert_params(cx, value)?;
+            NormalizedAlgorithm::Hmac {
+                hash: normalize_hash(cx, &params.hash)?,
+                length: params.length,
+            }
+        },
+        (Operation::ImportKey, AlgorithmName::AesCbc) |
+        (Operation::ImportKey, AlgorithmName::AesCtr) |
+        (Operation::ImportKey, AlgorithmName::AesGcm) |
+        (Operation::ImportKey, AlgorithmName::Hkdf) |
+        (Operation::ImportKey, AlgorithmName::Pbkdf2) |
+        (Operation::GetKeyLength, AlgorithmName::Hkdf) |
+        (Operation::GetKeyLength, AlgorithmName::Pbkdf2) => NormalizedAlgorithm::Plain(name),
+        (Operation::GetKeyLength, AlgorithmName::AesCbc) |
+        (Operation::GetKeyLength, AlgorithmName::AesCtr) |
+        (Operation::GetKeyLength, AlgorithmName::AesGcm) => {
+            let params: AesDerivedKeyParams = convert_params(cx, value)?;
+            NormalizedAlgorithm::AesKey {
+                name,
+                length: params.length,
+            }
+        },
+        _ => return Err(Error::NotSupported),
+    };
+    Ok(normalized)
+}
+
+/// Normalize the hash member of an algorithm, which must name a digest algorithm.
+fn normalize_hash(cx: JSContext, hash: &ObjectOrString) -> Fallible<AlgorithmName> {
+    normalize_algorithm(cx, hash, Operation::Digest).map(|normalized| normalized.name())
+}
+
+/// Check that `key` can be used with `algorithm` for `usage`.
+fn check_key(key: &KeyParts, algorithm: &NormalizedAlgorithm, usage: KeyUsage) -> ErrorResult {
+    if key.algorithm.name() != algorithm.name() || !key.has_usage(usage) {
+        return Err(Error::InvalidAccess);
+    }
+    Ok(())
+}
+
+/// The usages that keys of the given algorithm and type support.
+fn supported_usages(name: AlgorithmName, key_type: KeyType) -> &'static [KeyUsage] {
+    match (name, key_type) {
+        (AlgorithmName::RsaOaep, KeyType::Public) => &[KeyUsage::Encrypt, KeyUsage::WrapKey],
+        (AlgorithmName::RsaOaep, _) => &[KeyUsage::Decrypt, KeyUsage::UnwrapKey],
+        (AlgorithmName::RsaPss, KeyType::Public) | (AlgorithmName::Ecdsa, KeyType::Public) => {
+            &[KeyUsage::Verify]
+        },
+        (AlgorithmName::RsaPss, _) | (AlgorithmName::Ecdsa, _) => &[KeyUsage::Sign],
+        (AlgorithmName::Ecdh, KeyType::Public) => &[],
+        (AlgorithmName::Ecdh, _) | (AlgorithmName::Hkdf, _) | (AlgorithmName::Pbkdf2, _) => {
+            &[KeyUsage::DeriveKey, KeyUsage::DeriveBits]
+        },
+        (AlgorithmName::AesCbc, _) | (AlgorithmName::AesCtr, _) | (AlgorithmName::AesGcm, _) => &[
+            KeyUsage::Encrypt,
+            KeyUsage::Decrypt,
+            KeyUsage::WrapKey,
+            KeyUsage::UnwrapKey,
+        ],
+        (AlgorithmName::Hmac, _) => &[KeyUsage::Sign, KeyUsage::Verify],
+        _ => &[],
+    }
+}
+
+/// The usages out of `usages` that a key of the given algorithm and type
+/// supports, without duplicates.
+fn usages_for(name: AlgorithmName, key_type: KeyType, usages: &[KeyUsage]) -> Vec<KeyUsage> {
+    let supported = supported_usages(name, key_type);
+    let mut result: Vec<KeyUsage> = vec![];
+    for usage in usages {
+        if supported.contains(usage) && !result.contains(usage) {
+            result.push(*usage);
+        }
+    }
+    result
+}
+
+/// Throw a `SyntaxError` if a key of the given algorithm and type doesn't support all of `usages`.
+fn check_usages(name: AlgorithmName, key_type: KeyType, usages: &[KeyUsage]) -> ErrorResult {
+    let supported = supported_usages(name, key_type);
+    if usages.iter().all(|usage| supported.contains(usage)) {
+        Ok(())
+    } else {
+        Err(Error::Syntax)
+    }
+}
+
+fn random_bytes(length: usize) -> Fallible<Vec<u8>> {
+    let mut bytes = vec![0; length];
+    rand_bytes(&mut bytes).map_err(|_| Error::Operation)?;
+    Ok(bytes)
+}
+
+fn secret_bytes(key: &KeyParts) -> Fallible<&[u8]> {
+    match key.handle {
+        KeyHandle::Secret(ref bytes) => Ok(bytes),
+        _ => Err(Error::InvalidAccess),
+    }
+}
+
+fn private_key(key: &KeyParts) -> Fallible<&PKey<Private>> {
+    match key.handle {
+        KeyHandle::Private(ref pkey) => Ok(pkey),
+        _ => Err(Error::InvalidAccess),
+    }
+}
+
+fn public_key(key: &KeyParts) -> Fallible<&PKey<Public>> {
+    match key.handle {
+        KeyHandle::Public(ref pkey) => Ok(pkey),
+        _ => Err(Error::InvalidAccess),
+    }
+}
+
+/// The AES cipher for the given mode and key.
+fn aes_cipher(name: AlgorithmName, key: &[u8]) -> Fallible<Cipher> {
+    let cipher = match (name, key.len()) {
+        (AlgorithmName::AesCbc, 16) => Cipher::aes_128_cbc(),
+        (AlgorithmName::AesCbc, 24) => aes_192_cipher(Nid::AES_192_CBC)?,
+        (AlgorithmName::AesCbc, 32) => Cipher::aes_256_cbc(),
+        (AlgorithmName::AesCtr, 16) => Cipher::aes_128_ctr(),
+        (AlgorithmName::AesCtr, 24) => aes_192_cipher(Nid::AES_192_CTR)?,
+        (AlgorithmName::AesCtr, 32) => Cipher::aes_256_ctr(),
+        (AlgorithmName::AesGcm, 16) => Cipher::aes_128_gcm(),
+        (AlgorithmName::AesGcm, 24) => aes_192_cipher(Nid::AES_192_GCM)?,
+        (AlgorithmName::AesGcm, 32) => Cipher::aes_256_gcm(),
+        _ => return Err(Error::NotSupported),
+    };
+    Ok(cipher)
+}
+
+/// The openssl bindings have no constructors for the 192 bit AES ciphers,
+/// so those are looked up by their identifiers instead.
+fn aes_192_cipher(nid: Nid) -> Fallible<Cipher> {
+    Cipher::from_nid(nid).ok_or(Error::Operation)
+}
+
+/// Check the tag length of AES-GCM, returning it in bytes.
+fn gcm_tag_length(tag_length: Option<u8>) -> Fallible<usize> {
+    match tag_length.unwrap_or(128) {
+        length @ 32 |
+        length @ 64 |
+        length @ 96 |
+        length @ 104 |
+        length @ 112 |
+        length @ 120 |
+        length @ 128 => Ok(length as usize / 8),
+        _ => Err(Error::Operation),
+    }
+}
+
+/// Run AES-CTR, for which only the rightmost `length` bits of the counter
+/// block are incremented, wrapping around instead of carrying into the nonce.
+/// <https://w3c.github.io/webcrypto/#aes-ctr-operations>
+fn aes_ctr(
+    cipher: Cipher,
+    key: &[u8],
+    counter: &[u8],
+    length: u8,
+    data: &[u8],
+) -> Fallible<Vec<u8>> {
+    if counter.len() != 16 || length == 0 || length > 128 {
+        return Err(Error::Operation);
+    }
+    let run = |counter: &[u8], data: &[u8]| {
+        symm::encrypt(cipher, key, Some(counter), data).map_err(|_| Error::Operation)
+    };
+    if length == 128 {
+        return run(counter, data);
+    }
+
+    let mut block = [0; 16];
+    block.copy_from_slice(counter);
+    let initial = u128::from_be_bytes(block);
+    let mask = (1u128 << length) - 1;
+    let blocks = (data.len() as u128 + 15) / 16;
+    if blocks > 1u128 << length {
+        return Err(Error::Operation);
+    }
+    let blocks_until_wrap = (1u128 << length) - (initial & mask);
+    if blocks <= blocks_until_wrap {
+        return run(counter, data);
+    }
+    let split = (blocks_until_wrap * 16) as usize;
+    let mut result = run(counter, &data[..split])?;
+    result.extend(run(&(initial & !mask).to_be_bytes(), &data[split..])?);
+    Ok(result)
+}
+
+/// <https://w3c.github.io/webcrypto/#dfn-SubtleCrypto-method-encrypt>
+fn encrypt(algorithm: &NormalizedAlgorithm, key: &KeyParts, data: &[u8]) -> Fallible<Vec<u8>> {
+    match *algorithm {
+        NormalizedAlgorithm::AesCbc { ref iv } => {
+            let secret = secret_bytes(key)?;
+            if iv.len() != 16 {
+                return Err(Error::Operation);
+            }
+            symm::encrypt(
+                aes_cipher(AlgorithmName::AesCbc, secret)?,
+                secret,
+                Some(&iv[..]),
+                data,
+            )
+            .map_err(|_| Error::Operation)
+        },
+        NormalizedAlgorithm::AesCtr {
+            ref counter,
+            length,
+        } => {
+            let secret = secret_bytes(key)?;
+            let cipher = aes_cipher(AlgorithmName::AesCtr, secret)?;
+            aes_ctr(cipher, secret, counter, length, data)
+        },
+        NormalizedAlgorithm::AesGcm {
+            ref iv,
+            ref additional_data,
+            tag_length,
+        } => {
+            let secret = secret_bytes(key)?;
+            if iv.is_empty() {
+                return Err(Error::Operation);
+            }
+            let mut tag = vec![0; gcm_tag_length(tag_length)?];
+            let cipher = aes_cipher(AlgorithmName::AesGcm, secret)?;
+            let mut result = symm::encrypt_aead(
+                cipher,
+                secret,
+                Some(&iv[..]),
+                additional_data,
+                data,
+                &mut tag,
+            )
+            .map_err(|_| Error::Operation)?;
+            result.extend(tag);
+            Ok(result)
+        },
+        NormalizedAlgorithm::RsaOaep { ref label } => {
+            let pkey = public_key(key)?;
+            let digest = key
+                .algorithm
+                .hash()
+                .ok_or(Error::InvalidAccess)?
+                .message_digest();
+            let result: Result<_, ErrorStack> = (|| {
+                let mut encrypter = Encrypter::new(pkey)?;
+                encrypter.set_rsa_padding(Padding::PKCS1_OAEP)?;
+                encrypter.set_rsa_oaep_md(digest)?;
+                encrypter.set_rsa_mgf1_md(digest)?;
+                if !label.is_empty() {
+                    encrypter.set_rsa_oaep_label(label)?;
+                }
+                let mut buffer = vec![0; encrypter.encrypt_len(data)?];
+                let length = encrypter.encrypt(data, &mut buffer)?;
+                buffer.truncate(length);
+                Ok(buffer)
+            })();
+            result.map_err(|_| Error::Operation)
+        },
+        _ => Err(Error::NotSupported),
+    }
+}
+
+/// <https://w3c.github.io/webcrypto/#dfn-SubtleCrypto-method-decrypt>
+fn decrypt(algorithm: &NormalizedAlgorithm, key: &KeyParts, data: &[u8]) -> Fallible<Vec<u8>> {
+    match *algorithm {
+        NormalizedAlgorithm::AesCbc { ref iv } => {
+            let secret = secret_bytes(key)?;
+            if iv.len() != 16 {
+                return Err(Error::Operation);
+            }
+            symm::decrypt(
+                aes_cipher(AlgorithmName::AesCbc, secret)?,
+                secret,
+                Some(&iv[..]),
+                data,
+            )
+            .map_err(|_| Error::Operation)
+        },
+        // Decryption in counter mode is the same as encryption.
+        NormalizedAlgorithm::AesCtr { .. } => encrypt(algorithm, key, data),
+        NormalizedAlgorithm::AesGcm {
+            ref iv,
+            ref additional_data,
+            tag_length,
+        } => {
+            let secret = secret_bytes(key)?;
+            let tag_length = gcm_tag_length(tag_length)?;
+            if iv.is_empty() || data.len() < tag_length {
+                return Err(Error::Operation);
+            }
+            let (ciphertext, tag) = data.split_at(data.len() - tag_length);
+            let cipher = aes_cipher(AlgorithmName::AesGcm, secret)?;
+            symm::decrypt_aead(
+                cipher,
+                secret,
+                Some(&iv[..]),
+                additional_data,
+                ciphertext,
+                tag,
+            )
+            .map_err(|_| Error::Operation)
+        },
+        NormalizedAlgorithm::RsaOaep { ref label } => {
+            let pkey = private_key(key)?;
+            let digest = key
+                .algorithm
+                .hash()
+                .ok_or(Error::InvalidAccess)?
+                .message_digest();
+            let result: Result<_, ErrorStack> = (|| {
+                let mut decrypter = Decrypter::new(pkey)?;
+                decrypter.set_rsa_padding(Padding::PKCS1_OAEP)?;
+                decrypter.set_rsa_oaep_md(digest)?;
+                decrypter.set_rsa_mgf1_md(digest)?;
+                if !label.is_empty() {
+                    decrypter.set_rsa_oaep_label(label)?;
+                }
+                let mut buffer = vec![0; decrypter.decrypt_len(data)?];
+                let length = decrypter.decrypt(data, &mut buffer)?;
+                buffer.truncate(length);
+                Ok(buffer)
+            })();
+            result.map_err(|_| Error::Operation)
+        },
+        _ => Err(Error::NotSupported),
+    }
+}
+
+fn digest(algorithm: AlgorithmName, data: &[u8]) -> Fallible<Vec<u8>> {
+    hash(algorithm.message_digest(), data)
+        .map(|digest| digest.to_vec())
+        .map_err(|_| Error::Operation)
+}
+
+fn hmac(hash: AlgorithmName, key: &[u8], data: &[u8]) -> Fallible<Vec<u8>> {
+    let pkey = PKey::hmac(key).map_err(|_| Error::Operation)?;
+    let mut signer = Signer::new(hash.message_digest(), &pkey).map_err(|_| Error::Operation)?;
+    signer.update(data).map_err(|_| Error::Operation)?;
+    signer.sign_to_vec().map_err(|_| Error::Operation)
+}
+
+/// Left-pad `number` with zeros to `length` bytes.
+fn padded_bytes(number: &BigNumRef, length: usize) -> Vec<u8> {
+    let bytes = number.to_vec();
+    let mut result = vec![0; length.saturating_sub(bytes.len())];
+    result.extend(bytes);
+    result
+}
+
+/// <https://w3c.github.io/webcrypto/#dfn-SubtleCrypto-method-sign>
+fn sign(algorithm: &NormalizedAlgorithm, key: &KeyParts, data: &[u8]) -> Fallible<Vec<u8>> {
+    match *algorithm {
+        NormalizedAlgorithm::Plain(AlgorithmName::Hmac) => {
+            let hash = key.algorithm.hash().ok_or(Error::InvalidAccess)?;
+            hmac(hash, secret_bytes(key)?, data)
+        },
+        NormalizedAlgorithm::RsaPss { salt_length } => {
+            let pkey = private_key(key)?;
+            let digest = key
+                .algorithm
+                .hash()
+                .ok_or(Error::InvalidAccess)?
+                .message_digest();
+            let result = (|| {
+                let mut signer = Signer::new(digest, pkey)?;
+                signer.set_rsa_padding(Padding::PKCS1_PSS)?;
+                signer.set_rsa_pss_saltlen(RsaPssSaltlen::custom(salt_length as i32))?;
+                signer.set_rsa_mgf1_md(digest)?;
+                signer.update(data)?;
+                signer.sign_to_vec()
+            })();
+            result.map_err(|_| Error::Operation)
+        },
+        NormalizedAlgorithm::Ecdsa { hash } => {
+            let ec_key = private_key(key)?.ec_key().map_err(|_| Error::Operation)?;
+            let curve = key.algorithm.named_curve().ok_or(Error::InvalidAccess)?;
+            let hashed = digest(hash, data)?;
+            let signature = EcdsaSig::sign(&hashed, &ec_key).map_err(|_| Error::Operation)?;
+            let mut result = padded_bytes(signature.r(), curve.field_size());
+            result.extend(padded_bytes(signature.s(), curve.field_size()));
+            Ok(result)
+        },
+        _ => Err(Error::NotSupported),
+    }
+}
+
+/// <https://w3c.github.io/webcrypto/#dfn-SubtleCrypto-method-verify>
+fn verify(
+    algorithm: &NormalizedAlgorithm,
+    key: &KeyParts,
+    signature: &[u8],
+    data: &[u8],
+) -> Fallible<bool> {
+    match *algorithm {
+        NormalizedAlgorithm::Plain(AlgorithmName::Hmac) => {
+            let hash = key.algorithm.hash().ok_or(Error::InvalidAccess)?;
+            let expected = hmac(hash, secret_bytes(key)?, data)?;
+            Ok(expected.len() == signature.len() && memcmp::eq(&expected, signature))
+        },
+        NormalizedAlgorithm::RsaPss { salt_length } => {
+            let pkey = public_key(key)?;
+            let digest = key
+                .algorithm
+                .hash()
+                .ok_or(Error::InvalidAccess)?
+                .message_digest();
+            let result = (|| {
+                let mut verifier = Verifier::new(digest, pkey)?;
+                verifier.set_rsa_padding(Padding::PKCS1_PSS)?;
+                verifier.set_rsa_pss_saltlen(RsaPssSaltlen::custom(salt_length as i32))?;
+                verifier.set_rsa_mgf1_md(digest)?;
+                verifier.update(data)?;
+                verifier.verify(signature)
+            })();
+            // A malformed signature is reported as invalid rather than as an error.
+            Ok(result.unwrap_or(false))
+        },
+        NormalizedAlgorithm::Ecdsa { hash } => {
+            let ec_key = public_key(key)?.ec_key().map_err(|_| Error::Operation)?;
+            let curve = key.algorithm.named_curve().ok_or(Error::InvalidAccess)?;
+            if signature.len() != 2 * curve.field_size() {
+                return Ok(false);
+            }
+            let (r, s) = signature.split_at(curve.field_size());
+            let hashed = digest(hash, data)?;
+            let result = (|| {
+                let signature = EcdsaSig::from_private_components(
+                    BigNum::from_slice(r)?,
+                    BigNum::from_slice(s)?,
+                )?;
+                signature.verify(&hashed, &ec_key)
+            })();
+            Ok(result.unwrap_or(false))
+        },
+        _ => Err(Error::NotSupported),
+    }
+}
+
+/// <https://w3c.github.io/webcrypto/#dfn-SubtleCrypto-method-generateKey>
+fn generate_key(
+    algorithm: &NormalizedAlgorithm,
+    extractable: bool,
+    usages: &[KeyUsage],
+) -> Fallible<GeneratedKey> {
+    let name = algorithm.name();
+    let supported = |usage: &KeyUsage| {
+        supported_usages(name, KeyType::Public).contains(usage) ||
+            supported_usages(name, KeyType::Private).contains(usage)
+    };
+    if !usages.iter().all(supported) {
+        return Err(Error::Syntax);
+    }
+
+    let (key_algorithm, secret) = match *algorithm {
+        NormalizedAlgorithm::AesKey { name, length } => {
+            let secret = match length {
+                128 | 192 | 256 => random_bytes(length as usize / 8)?,
+                _ => return Err(Error::Operation),
+            };
+            (KeyAlgorithm::Aes { name, length }, secret)
+        },
+        NormalizedAlgorithm::Hmac { hash, length } => {
+            let length = length.unwrap_or_else(|| hash.block_size());
+            if length == 0 || length % 8 != 0 {
+                return Err(Error::Operation);
+            }
+            let secret = random_bytes(length as usize / 8)?;
+            (KeyAlgorithm::Hmac { hash, length }, secret)
+        },
+        NormalizedAlgorithm::RsaKeyGen {
+            name,
+            modulus_length,
+            ref public_exponent,
+            hash,
+        } => {
+            let exponent = BigNum::from_slice(public_exponent).map_err(|_| Error::Operation)?;
+            // OpenSSL accepts exponents that no key can be made with, and
+            // looks for primes forever with even ones.
+            if !exponent.is_bit_set(0) || exponent.num_bits() < 2 {
+                return Err(Error::Operation);
+            }
+            let rsa =
+                Rsa::generate_with_e(modulus_length, &exponent).map_err(|_| Error::Operation)?;
+            let key_algorithm = KeyAlgorithm::RsaHashed {
+                name,
+                modulus_length,
+                public_exponent: public_exponent.clone(),
+                hash,
+            };
+            let public = Rsa::from_public_components(
+                rsa.n().to_owned().map_err(|_| Error::Operation)?,
+                rsa.e().to_owned().map_err(|_| Error::Operation)?,
+            )
+            .and_then(PKey::from_rsa)
+            .map_err(|_| Error::Operation)?;
+            let private = PKey::from_rsa(rsa).map_err(|_| Error::Operation)?;
+            return create_key_pair(key_algorithm, public, private, extractable, usages);
+        },
+        NormalizedAlgorithm::EcKey {
+            name,
+            ref named_curve,
+        } => {
+            let curve = NamedCurve::from_name(named_curve).ok_or(Error::NotSupported)?;
+            let group = curve.group()?;
+            let private = EcKey::generate(&group).map_err(|_| Error::Operation)?;
+            let public = EcKey::from_public_key(&group, private.public_key())
+                .and_then(PKey::from_ec_key)
+                .map_err(|_| Error::Operation)?;
+            let private = PKey::from_ec_key(private).map_err(|_| Error::Operation)?;
+            let key_algorithm = KeyAlgorithm::Ec {
+                name,
+                named_curve: curve,
+            };
+            return create_key_pair(key_algorithm, public, private, extractable, usages);
+        },
+        _ => return Err(Error::NotSupported),
+    };
+
+    let usages = usages_for(name, KeyType::Secret, usages);
+    if usages.is_empty() {
+        return Err(Error::Syntax);
+    }
+    Ok(GeneratedKey::Key(KeyParts {
+        extractable,
+        algorithm: key_algorithm,
+        usages,
+        handle: KeyHandle::Secret(secret),
+    }))
+}
+
+fn create_key_pair(
+    algorithm: KeyAlgorithm,
+    public: PKey<Public>,
+    private: PKey<Private>,
+    extractable: bool,
+    usages: &[KeyUsage],
+) -> Fallible<GeneratedKey> {
+    let name = algorithm.name();
+    let private_usages = usages_for(name, KeyType::Private, usages);
+    if private_usages.is_empty() {
+        return Err(Error::Syntax);
+    }
+    let public = KeyParts {
+        extractable: true,
+        algorithm: algorithm.clone(),
+        usages: usages_for(name, KeyType::Public, usages),
+        handle: KeyHandle::Public(public),
+    };
+    let private = KeyParts {
+        extractable,
+        algorithm,
+        usages: private_usages,
+        handle: KeyHandle::Private(private),
+    };
+    Ok(GeneratedKey::Pair { public, private })
+}
+
+/// The length of the keys described by `algorithm`, which is `None` for
+/// algorithms whose keys have no fixed length.
+/// <https://w3c.github.io/webcrypto/#dfn-get-key-length>
+fn key_length(algorithm: &NormalizedAlgorithm) -> Fallible<Option<u32>> {
+    match *algorithm {
+        NormalizedAlgorithm::AesKey { length, .. } => match length {
+            128 | 192 | 256 => Ok(Some(length as u32)),
+            _ => Err(Error::Operation),
+        },
+        NormalizedAlgorithm::Hmac { hash, length } => match length {
+            Some(0) => Err(Error::Type("The HMAC key length can't be zero".to_owned())),
+            Some(length) => Ok(Some(length)),
+            None => Ok(Some(hash.block_size())),
+        },
+        _ => Ok(None),
+    }
+}
+
+/// <https://w3c.github.io/webcrypto/#dfn-SubtleCrypto-method-deriveKey>
+fn derive_key(
+    normalized: &NormalizedAlgorithm,
+    import_params: &NormalizedAlgorithm,
+    length_params: &NormalizedAlgorithm,
+    base_key: &KeyParts,
+    extractable: bool,
+    usages: &[KeyUsage],
+) -> Fallible<KeyParts> {
+    // Steps 11-12.
+    check_key(base_key, normalized, KeyUsage::DeriveKey)?;
+
+    // Steps 13-15.
+    let length = key_length(length_params)?;
+    let secret = derive_bits(normalized, base_key, length)?;
+    let key = import_key(
+        KeyFormat::Raw,
+        KeyData::Bytes(secret),
+        import_params,
+        extractable,
+        usages,
+    )?;
+
+    // Step 16.
+    if key.usages.is_empty() {
+        return Err(Error::Syntax);
+    }
+    Ok(key)
+}
+
+/// <https://w3c.github.io/webcrypto/#dfn-SubtleCrypto-method-deriveBits>
+fn derive_bits(
+    algorithm: &NormalizedAlgorithm,
+    key: &KeyParts,
+    length: Option<u32>,
+) -> Fallible<Vec<u8>> {
+    match *algorithm {
+        NormalizedAlgorithm::Ecdh { ref public } => {
+            // https://w3c.github.io/webcrypto/#ecdh-operations
+            if public.key_type() != KeyType::Public ||
+                public.algorithm.name() != AlgorithmName::Ecdh ||
+                public.algorithm.named_curve() != key.algorithm.named_curve()
+            {
+                return Err(Error::InvalidAccess);
+            }
+            let mut secret = (|| {
+                let mut deriver = Deriver::new(private_key(key).ok()?).ok()?;
+                deriver.set_peer(public_key(public).ok()?).ok()?;
+                deriver.derive_to_vec().ok()
+            })()
+            .ok_or(Error::Operation)?;
+            if let Some(length) = length {
+                let bytes = (length as usize + 7) / 8;
+                if bytes > secret.len() {
+                    return Err(Error::Operation);
+                }
+                secret.truncate(bytes);
+                if length % 8 != 0 {
+                    secret[bytes - 1] &= 0xFF << (8 - length % 8);
+                }
+            }
+            Ok(secret)
+        },
+        NormalizedAlgorithm::Hkdf {
+            hash,
+            ref salt,
+            ref info,
+        } => {
+            // https://w3c.github.io/webcrypto/#hkdf-operations
+            let length = match length {
+                Some(length) if length != 0 && length % 8 == 0 => length as usize / 8,
+                _ => return Err(Error::Operation),
+            };
+            hkdf(hash, secret_bytes(key)?, salt, info, length)
+        },
+        NormalizedAlgorithm::Pbkdf2 {
+            hash,
+            ref salt,
+            iterations,
+        } => {
+            // https://w3c.github.io/webcrypto/#pbkdf2-operations
+            let length = match length {
+                Some(length) if length != 0 && length % 8 == 0 => length as usize / 8,
+                _ => return Err(Error::Operation),
+            };
+            if iterations == 0 {
+                return Err(Error::Operation);
+            }
+            let mut result = vec![0; length];
+            pbkdf2_hmac(
+                secret_bytes(key)?,
+                salt,
+                iterations as usize,
+                hash.message_digest(),
+                &mut result,
+            )
+            .map_err(|_| Error::Operation)?;
+            Ok(result)
+        },
+        _ => Err(Error::NotSupported),
+    }
+}
+
+/// <https://tools.ietf.org/html/rfc5869>
+fn hkdf(
+    hash: AlgorithmName,
+    key: &[u8],
+    salt: &[u8],
+    info: &[u8],
+    length: usize,
+) -> Fallible<Vec<u8>> {
+    let hash_length = hash.message_digest().size();
+    if length > 255 * hash_length {
+        return Err(Error::Operation);
+    }
+
+    // An empty salt is the same as a salt of zeros.
+    let zeros;
+    let salt: &[u8] = if salt.is_empty() {
+        zeros = vec![0; hash_length];
+        &zeros
+    } else {
+        salt
+    };
+    let pseudorandom_key = hmac(hash, salt, key)?;
+
+    let mut result = Vec::with_capacity(length);
+    let mut block = vec![];
+    let mut counter = 1u8;
+    while result.len() < length {
+        let mut input = block;
+        input.extend_from_slice(info);
+        input.push(counter);
+        block = hmac(hash, &pseudorandom_key, &input)?;
+        result.extend_from_slice(&block);
+        counter = counter.wrapping_add(1);
+    }
+    result.truncate(length);
+    Ok(result)
+}
+
+fn base64url_encode(bytes: &[u8]) -> String {
+    base64::encode_config(bytes, base64::URL_SAFE_NO_PAD)
+}
+
+fn base64url_decode(string: Option<&String>) -> Fallible<Vec<u8>> {
+    let string = string.ok_or(Error::Data)?;
+    base64::decode_config(&**string, base64::URL_SAFE_NO_PAD).map_err(|_| Error::Data)
+}
+
+fn jwk_big_num(string: Option<&String>) -> Fallible<BigNum> {
+    BigNum::from_slice(&base64url_decode(string)?).map_err(|_| Error::Data)
+}
+
+/// Run the checks of a JSON Web Key that are common to all algorithms.
+fn check_jwk(
+    jwk: &Jwk,
+    kty: &str,
+    use_: &str,
+    alg: Option<&str>,
+    extractable: bool,
+    usages: &[KeyUsage],
+) -> ErrorResult {
+    if jwk.kty.as_ref().map(|kty| &**kty) != Some(kty) {
+        return Err(Error::Data);
+    }
+    if let (Some(expected), Some(actual)) = (alg, jwk.alg.as_ref()) {
+        if &**actual != expected {
+            return Err(Error::Data);
+        }
+    }
+    if !usages.is_empty() && jwk.use_.as_ref().map_or(false, |actual| &**actual != use_) {
+        return Err(Error::Data);
+    }
+    if let Some(ref key_ops) = jwk.key_ops {
+        if !usages
+            .iter()
+            .all(|usage| key_ops.iter().any(|op| &**op == usage.as_str()))
+        {
+            return Err(Error::Data);
+        }
+    }
+    if jwk.ext == Some(false) && extractable {
+        return Err(Error::Data);
+    }
+    Ok(())
+}
+
+/// The JWK `alg` value of an RSA key.
+fn rsa_jwk_alg(name: AlgorithmName, hash: AlgorithmName) -> String {
+    match (name, hash) {
+        (AlgorithmName::RsaOaep, AlgorithmName::Sha1) => "RSA-OAEP".to_owned(),
+        (AlgorithmName::RsaOaep, hash) => format!("RSA-OAEP-{}", hash.jwk_suffix()),
+        (_, hash) => format!("PS{}", hash.jwk_suffix()),
+    }
+}
+
+/// The JWK `alg` value of an AES key.
+fn aes_jwk_alg(name: AlgorithmName, length: u16) -> String {
+    let mode = match name {
+        AlgorithmName::AesCbc => "CBC",
+        AlgorithmName::AesCtr => "CTR",
+        _ => "GCM",
+    };
+    format!("A{}{}", length, mode)
+}
+
+/// The length in bits of the modulus of an RSA key, and its public exponent.
+fn rsa_parameters<T: HasPublic>(pkey: &PKeyRef<T>) -> Fallible<(u32, Vec<u8>)> {
+    let rsa = pkey.rsa().map_err(|_| Error::Data)?;
+    Ok((rsa.n().num_bits() as u32, rsa.e().to_vec()))
+}
+
+/// Check that `pkey` is an elliptic curve key on `curve`.
+fn check_ec_curve<T>(pkey: &PKeyRef<T>, curve: NamedCurve) -> ErrorResult {
+    if pkey.id() != Id::EC {
+        return Err(Error::Data);
+    }
+    let ec_key = pkey.ec_key().map_err(|_| Error::Data)?;
+    if NamedCurve::from_group_nid(ec_key.group().curve_name()) != Some(curve) {
+        return Err(Error::Data);
+    }
+    Ok(())
+}
+
+/// <https://w3c.github.io/webcrypto/#dfn-SubtleCrypto-method-importKey>
+fn import_key(
+    format: KeyFormat,
+    key_data: KeyData,
+    algorithm: &NormalizedAlgorithm,
+    extractable: bool,
+    usages: &[KeyUsage],
+) -> Fallible<KeyParts> {
+    let name = algorithm.name();
+    let (key_algorithm, handle) = match *algorithm {
+        // https://w3c.github.io/webcrypto/#aes-operations
+        NormalizedAlgorithm::Plain(AlgorithmName::AesCbc) |
+        NormalizedAlgorithm::Plain(AlgorithmName::AesCtr) |
+        NormalizedAlgorithm::Plain(AlgorithmName::AesGcm) => {
+            check_usages(name, KeyType::Secret, usages)?;
+            let (secret, jwk) = match (format, key_data) {
+                (KeyFormat::Raw, KeyData::Bytes(bytes)) => (bytes, None),
+                (KeyFormat::Jwk, KeyData::Jwk(jwk)) => {
+                    (base64url_decode(jwk.k.as_ref())?, Some(jwk))
+                },
+                _ => return Err(Error::NotSupported),
+            };
+            let length = match secret.len() {
+                16 | 24 | 32 => secret.len() as u16 * 8,
+                _ => return Err(Error::Data),
+            };
+            if let Some(jwk) = jwk {
+                let alg = aes_jwk_alg(name, length);
+                check_jwk(&jwk, "oct", "enc", Some(alg.as_str()), extractable, usages)?;
+            }
+            (
+                KeyAlgorithm::Aes { name, length },
+                KeyHandle::Secret(secret),
+            )
+        },
+        // https://w3c.github.io/webcrypto/#hmac-operations
+        NormalizedAlgorithm::Hmac { hash, length } => {
+            check_usages(name, KeyType::Secret, usages)?;
+            let secret = match (format, key_data) {
+                (KeyFormat::Raw, KeyData::Bytes(bytes)) => bytes,
+                (KeyFormat::Jwk, KeyData::Jwk(jwk)) => {
+                    let alg = format!("HS{}", hash.jwk_suffix());
+                    check_jwk(&jwk, "oct", "sig", Some(alg.as_str()), extractable, usages)?;
+                    base64url_decode(jwk.k.as_ref())?
+                },
+                _ => return Err(Error::NotSupported),
+            };
+            let data_length = secret.len() as u32 * 8;
+            if data_length == 0 {
+                return Err(Error::Data);
+            }
+            let length = match length {
+                Some(length) if length > data_length || length <= data_length - 8 => {
+                    return Err(Error::Data)
+                },
+                Some(length) => length,
+                None => data_length,
+            };
+            (
+                KeyAlgorithm::Hmac { hash, length },
+                KeyHandle::Secret(secret),
+            )
+        },
+        // https://w3c.github.io/webcrypto/#hkdf-operations
+        // https://w3c.github.io/webcrypto/#pbkdf2-operations
+        NormalizedAlgorithm::Plain(AlgorithmName::Hkdf) |
+        NormalizedAlgorithm::Plain(AlgorithmName::Pbkdf2) => {
+            check_usages(name, KeyType::Secret, usages)?;
+            let secret = match (format, key_data) {
+                (KeyFormat::Raw, KeyData::Bytes(bytes)) => bytes,
+                _ => return Err(Error::NotSupported),
+            };
+            if extractable {
+                return Err(Error::Syntax);
+            }
+            (KeyAlgorithm::Kdf { name }, KeyHandle::Secret(secret))
+        },
+        // https://w3c.github.io/webcrypto/#rsa-oaep-operations
+        // https://w3c.github.io/webcrypto/#rsa-pss-operations
+        NormalizedAlgorithm::RsaImport { name, hash } => {
+            let handle = match (format, key_data) {
+                (KeyFormat::Spki, KeyData::Bytes(bytes)) => {
+                    check_usages(name, KeyType::Public, usages)?;
+                    let pkey = PKey::public_key_from_der(&bytes).map_err(|_| Error::Data)?;
+                    if pkey.id() != Id::RSA {
+                        return Err(Error::Data);
+                    }
+                    KeyHandle::Public(pkey)
+                },
+                (KeyFormat::Pkcs8, KeyData::Bytes(bytes)) => {
+                    check_usages(name, KeyType::Private, usages)?;
+                    let pkey = PKey::private_key_from_der(&bytes).map_err(|_| Error::Data)?;
+                    if pkey.id() != Id::RSA {
+                        return Err(Error::Data);
+                    }
+                    KeyHandle::Private(pkey)
+                },
+                (KeyFormat::Jwk, KeyData::Jwk(jwk)) => {
+                    import_rsa_jwk(&jwk, name, hash, extractable, usages)?
+                },
+                _ => return Err(Error::NotSupported),
+            };
+            let (modulus_length, public_exponent) = match handle {
+                KeyHandle::Public(ref pkey) => rsa_parameters(pkey)?,
+                KeyHandle::Private(ref pkey) => rsa_parameters(pkey)?,
+                KeyHandle::Secret(_) => unreachable!(),
+            };
+            let key_algorithm = KeyAlgorithm::RsaHashed {
+                name,
+                modulus_length,
+                public_exponent,
+                hash,
+            };
+            (key_algorithm, handle)
+        },
+        // https://w3c.github.io/webcrypto/#ecdsa-operations
+        // https://w3c.github.io/webcrypto/#ecdh-operations
+        NormalizedAlgorithm::EcKey {
+            name,
+            ref named_curve,
+        } => {
+            let curve = NamedCurve::from_name(named_curve).ok_or(Error::NotSupported)?;
+            let handle = match (format, key_data) {
+                (KeyFormat::Raw, KeyData::Bytes(bytes)) => {
+                    check_usages(name, KeyType::Public, usages)?;
+                    let group = curve.group()?;
+                    let pkey: Result<_, ErrorStack> = (|| {
+                        let mut context = BigNumContext::new()?;
+                        let point = EcPoint::from_bytes(&group, &bytes, &mut context)?;
+                        let ec_key = EcKey::from_public_key(&group, &point)?;
+                        ec_key.check_key()?;
+                        PKey::from_ec_key(ec_key)
+                    })();
+                    KeyHandle::Public(pkey.map_err(|_| Error::Data)?)
+                },
+                (KeyFormat::Spki, KeyData::Bytes(bytes)) => {
+                    check_usages(name, KeyType::Public, usages)?;
+                    let pkey = PKey::public_key_from_der(&bytes).map_err(|_| Error::Data)?;
+                    check_ec_curve(&pkey, curve)?;
+                    KeyHandle::Public(pkey)
+                },
+                (KeyFormat::Pkcs8, KeyData::Bytes(bytes)) => {
+                    check_usages(name, KeyType::Private, usages)?;
+                    let pkey = PKey::private_key_from_der(&bytes).map_err(|_| Error::Data)?;
+                    check_ec_curve(&pkey, curve)?;
+                    KeyHandle::Private(pkey)
+                },
+                (KeyFormat::Jwk, KeyData::Jwk(jwk)) => {
+                    import_ec_jwk(&jwk, name, curve, extractable, usages)?
+                },
+                _ => return Err(Error::NotSupported),
+            };
+            let key_algorithm = KeyAlgorithm::Ec {
+                name,
+                named_curve: curve,
+            };
+            (key_algorithm, handle)
+        },
+        _ => return Err(Error::NotSupported),
+    };
+
+    let key_type = match handle {
+        KeyHandle::Secret(_) => KeyType::Secret,
+        KeyHandle::Private(_) => KeyType::Private,
+        KeyHandle::Public(_) => KeyType::Public,
+    };
+    Ok(KeyParts {
+        extractable,
+        algorithm: key_algorithm,
+        usages: usages_for(name, key_type, usages),
+        handle,
+    })
+}
+
+fn import_rsa_jwk(
+    jwk: &Jwk,
+    name: AlgorithmName,
+    hash: AlgorithmName,
+    extractable: bool,
+    usages: &[KeyUsage],
+) -> Fallible<KeyHandle> {
+    let use_ = if name == AlgorithmName::RsaOaep {
+        "enc"
+    } else {
+        "sig"
+    };
+    let alg = rsa_jwk_alg(name, hash);
+    check_jwk(jwk, "RSA", use_, Some(alg.as_str()), extractable, usages)?;
+    let n = jwk_big_num(jwk.n.as_ref())?;
+    let e = jwk_big_num(jwk.e.as_ref())?;
+
+    if jwk.d.is_none() {
+        check_usages(name, KeyType::Public, usages)?;
+        return Rsa::from_public_components(n, e)
+            .and_then(PKey::from_rsa)
+            .map(KeyHandle::Public)
+            .map_err(|_| Error::Data);
+    }
+
+    check_usages(name, KeyType::Private, usages)?;
+    if jwk.oth {
+        return Err(Error::NotSupported);
+    }
+    let rsa = Rsa::from_private_components(
+        n,
+        e,
+        jwk_big_num(jwk.d.as_ref())?,
+        jwk_big_num(jwk.p.as_ref())?,
+        jwk_big_num(jwk.q.as_ref())?,
+        jwk_big_num(jwk.dp.as_ref())?,
+        jwk_big_num(jwk.dq.as_ref())?,
+        jwk_big_num(jwk.qi.as_ref())?,
+    )
+    .map_err(|_| Error::Data)?;
+    if !rsa.check_key().unwrap_or(false) {
+        return Err(Error::Data);
+    }
+    PKey::from_rsa(rsa)
+        .map(KeyHandle::Private)
+        .map_err(|_| Error::Data)
+}
+
+fn import_ec_jwk(
+    jwk: &Jwk,
+    name: AlgorithmName,
+    curve: NamedCurve,
+    extractable: bool,
+    usages: &[KeyUsage],
+) -> Fallible<KeyHandle> {
+    if name == AlgorithmName::Ecdsa {
+        let alg = format!("ES{}", curve.jwk_suffix());
+        check_jwk(jwk, "EC", "sig", Some(alg.as_str()), extractable, usages)?;
+    } else {
+        check_jwk(jwk, "EC", "enc", None, extractable, usages)?;
+    }
+    if jwk.crv.as_ref().map(|crv| &**crv) != Some(curve.as_str()) {
+        return Err(Error::Data);
+    }
+
+    let group = curve.group()?;
+    let x = jwk_big_num(jwk.x.as_ref())?;
+    let y = jwk_big_num(jwk.y.as_ref())?;
+    let public =
+        EcKey::from_public_key_affine_coordinates(&group, &x, &y).map_err(|_| Error::Data)?;
+    if jwk.d.is_none() {
+        check_usages(name, KeyType::Public, usages)?;
+        return PKey::from_ec_key(public)
+            .map(KeyHandle::Public)
+            .map_err(|_| Error::Data);
+    }
+
+    check_usages(name, KeyType::Private, usages)?;
+    let d = jwk_big_num(jwk.d.as_ref())?;
+    let private: Result<_, ErrorStack> = (|| {
+        let private = EcKey::from_private_components(&group, &d, public.public_key())?;
+        private.check_key()?;
+        PKey::from_ec_key(private)
+    })();
+    private.map(KeyHandle::Private).map_err(|_| Error::Data)
+}
+
+/// The uncompressed encoding of the public point of an elliptic curve key.
+fn ec_point_bytes<T: HasPublic>(pkey: &PKeyRef<T>) -> Result<Vec<u8>, ErrorStack> {
+    let ec_key = pkey.ec_key()?;
+    let mut context = BigNumContext::new()?;
+    ec_key.public_key().to_bytes(
+        ec_key.group(),
+        PointConversionForm::UNCOMPRESSED,
+        &mut context,
+    )
+}
+
+/// Set the coordinates of the public point of an elliptic curve key in `jwk`.
+fn set_jwk_ec_coordinates<T: HasPublic>(
+    jwk: &mut Jwk,
+    ec_key: &EcKeyRef<T>,
+    curve: NamedCurve,
+) -> Result<(), ErrorStack> {
+    let mut context = BigNumContext::new()?;
+    let mut x = BigNum::new()?;
+    let mut y = BigNum::new()?;
+    ec_key
+        .public_key()
+        .affine_coordinates_gfp(ec_key.group(), &mut x, &mut y, &mut context)?;
+    jwk.x = Some(base64url_encode(&padded_bytes(&x, curve.field_size())));
+    jwk.y = Some(base64url_encode(&padded_bytes(&y, curve.field_size())));
+    Ok(())
+}
+
+/// The DER encoding of the PKCS #8 structure of a private key.
+fn pkcs8_bytes(pkey: &PKey<Private>) -> Fallible<Vec<u8>> {
+    let pem = pkey
+        .private_key_to_pem_pkcs8()
+        .map_err(|_| Error::Operation)?;
+    let pem = String::from_utf8(pem).map_err(|_| Error::Operation)?;
+    let encoded: String = pem
+        .lines()
+        .filter(|line| !line.starts_with("-----"))
+        .collect();
+    base64::decode(&encoded).map_err(|_| Error::Operation)
+}
+
+/// <https://w3c.github.io/webcrypto/#dfn-SubtleCrypto-method-exportKey>
+fn export_key(format: KeyFormat, key: &KeyParts) -> Fallible<ExportedKey> {
+    // Step 5.
+    if !key.extractable {
+        return Err(Error::InvalidAccess);
+    }
+
+    let is_ec = key.algorithm.named_curve().is_some();
+    match (format, &key.handle) {
+        (KeyFormat::Raw, &KeyHandle::Secret(ref bytes)) => Ok(ExportedKey::Bytes(bytes.clone())),
+        (KeyFormat::Raw, &KeyHandle::Public(ref pkey)) if is_ec => ec_point_bytes(pkey)
+            .map(ExportedKey::Bytes)
+            .map_err(|_| Error::Operation),
+        (KeyFormat::Spki, &KeyHandle::Public(ref pkey)) => pkey
+            .public_key_to_der()
+            .map(ExportedKey::Bytes)
+            .map_err(|_| Error::Operation),
+        (KeyFormat::Pkcs8, &KeyHandle::Private(ref pkey)) => {
+            pkcs8_bytes(pkey).map(ExportedKey::Bytes)
+        },
+        (KeyFormat::Jwk, _) => export_jwk(key).map(ExportedKey::Jwk),
+        (KeyFormat::Spki, &KeyHandle::Private(_)) | (KeyFormat::Pkcs8, &KeyHandle::Public(_)) => {
+            Err(Error::InvalidAccess)
+        },
+        _ => Err(Error::NotSupported),
+    }
+}
+
+fn export_jwk(key: &KeyParts) -> Fallible<Jwk> {
+    let mut jwk = Jwk::default();
+    match (&key.algorithm, &key.handle) {
+        (&KeyAlgorithm::Aes { name, length }, &KeyHandle::Secret(ref bytes)) => {
+            jwk.kty = Some("oct".to_owned());
+            jwk.k = Some(base64url_encode(bytes));
+            jwk.alg = Some(aes_jwk_alg(name, length));
+        },
+        (&KeyAlgorithm::Hmac { hash, .. }, &KeyHandle::Secret(ref bytes)) => {
+            jwk.kty = Some("oct".to_owned());
+            jwk.k = Some(base64url_encode(bytes));
+            jwk.alg = Some(format!("HS{}", hash.jwk_suffix()));
+        },
+        (&KeyAlgorithm::RsaHashed { name, hash, .. }, handle) => {
+            jwk.kty = Some("RSA".to_owned());
+            jwk.alg = Some(rsa_jwk_alg(name, hash));
+            match *handle {
+                KeyHandle::Public(ref pkey) => {
+                    let rsa = pkey.rsa().map_err(|_| Error::Operation)?;
+                    jwk.n = Some(base64url_encode(&rsa.n().to_vec()));
+                    jwk.e = Some(base64url_encode(&rsa.e().to_vec()));
+                },
+                KeyHandle::Private(ref pkey) => {
+                    let rsa = pkey.rsa().map_err(|_| Error::Operation)?;
+                    let encode = |number: Option<&BigNumRef>| -> Fallible<String> {
+                        number
+                            .map(|number| base64url_encode(&number.to_vec()))
+                            .ok_or(Error::Operation)
+                    };
+                    jwk.n = Some(encode(Some(rsa.n()))?);
+                    jwk.e = Some(encode(Some(rsa.e()))?);
+                    jwk.d = Some(encode(Some(rsa.d()))?);
+                    jwk.p = Some(encode(rsa.p())?);
+                    jwk.q = Some(encode(rsa.q())?);
+                    jwk.dp = Some(encode(rsa.dmp1())?);
+                    jwk.dq = Some(encode(rsa.dmq1())?);
+                    jwk.qi = Some(encode(rsa.iqmp())?);
+                },
+                KeyHandle::Secret(_) => return Err(Error::Operation),
+            }
+        },
+        (&KeyAlgorithm::Ec { named_curve, .. }, handle) => {
+            jwk.kty = Some("EC".to_owned());
+            jwk.crv = Some(named_curve.as_str().to_owned());
+            let result: Result<(), ErrorStack> = match *handle {
+                KeyHandle::Public(ref pkey) => pkey
+                    .ec_key()
+                    .and_then(|ec_key| set_jwk_ec_coordinates(&mut jwk, &ec_key, named_curve)),
+                KeyHandle::Private(ref pkey) => pkey.ec_key().and_then(|ec_key| {
+                    set_jwk_ec_coordinates(&mut jwk, &ec_key, named_curve)?;
+                    let d = padded_bytes(ec_key.private_key(), named_curve.field_size());
+                    jwk.d = Some(base64url_encode(&d));
+                    Ok(())
+                }),
+                KeyHandle::Secret(_) => return Err(Error::Operation),
+            };
+            result.map_err(|_| Error::Operation)?;
+        },
+        _ => return Err(Error::NotSupported),
+    }
+    jwk.key_ops = Some(
+        key.usages
+            .iter()
+            .map(|usage| usage.as_str().to_owned())
+            .collect(),
+    );
+    jwk.ext = Some(key.extractable);
+    Ok(jwk)
+}
@@ -9,24 +9,29 @@ use crate::dom::bindings::codegen::Bindings::TextBinding::{self, TextMethods};
 use crate::dom::bindings::codegen::Bindings::WindowBinding::WindowMethods;
 use crate::dom::bindings::error::{Error, Fallible};
 use crate::dom::bindings::inheritance::Castable;
-use crate::dom::bindings::root::DomRoot;
+use crate::dom::bindings::root::{DomRoot, LayoutDom};
 use crate::dom::bindings::str::DOMString;
-use crate::dom::characterdata::CharacterData;
+use crate::dom::characterdata::{CharacterData, LayoutCharacterDataHelpers};
 use crate::dom::document::Document;
-use crate::dom::node::Node;
+use crate::dom::node::{Node, NodeDamage};
 use crate::dom::window::Window;
 use dom_struct::dom_struct;
+use std::cell::Cell;
+use std::ops::Range;
 
 /// An HTML text node.
 #[dom_struct]
 pub struct Text {
     characterdata: CharacterData,
+    /// The part of this node's data highlighted as selected, in UTF-16 code units.
+    selected_range: Cell<Option<(u32, u32)>>,
 }
 
 impl Text {
     pub fn new_inherited(text: DOMString, document: &Document) -> Text {
         Text {
             characterdata: CharacterData::new_inherited(text, document),
+            selected_range: Cell::new(None),
         }
     }
 
@@ -43,6 +48,46 @@ impl Text {
         let document = window.Document();
         Ok(Text::new(text, &document))
     }
+
+    pub fn selected_range(&self) -> Option<(u32, u32)> {
+        self.selected_range.get()
+    }
+
+    /// Sets the part of this node's data that layout highlights as selected.
+    pub fn set_selected_range(&self, selected_range: Option<(u32, u32)>) {
+        if self.selected_range.get() == selected_range {
+            return;
+        }
+        self.selected_range.set(selected_range);
+        self.upcast::<Node>().dirty(NodeDamage::OtherNodeDamage);
+    }
+}
+
+pub trait LayoutTextHelpers {
+    unsafe fn selection_for_layout(&self) -> Option<Range<usize>>;
+}
+
+#[allow(unsafe_code)]
+impl LayoutTextHelpers for LayoutDom<Text> {
+    unsafe fn selection_for_layout(&self) -> Option<Range<usize>> {
+        let (start, end) = (*self.unsafe_get()).selected_range.get()?;
+        let data = self.upcast::<CharacterData>().data_for_layout();
+        // Convert the UTF-16 offsets into the byte offsets layout works with.
+        let mut utf16_offset = 0;
+        let mut byte_start = data.len();
+        let mut byte_end = data.len();
+        for (byte_offset, c) in data.char_indices() {
+            if utf16_offset == start {
+                byte_start = byte_offset;
+            }
+            if utf16_offset == end {
+                byte_end = byte_offset;
+                break;
+            }
+            utf16_offset += c.len_utf16() as u32;
+        }
+        Some(byte_start..byte_end)
+    }
 }
 
 impl TextMethods for Text {
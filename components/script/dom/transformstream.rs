@@ -0,0 +1,429 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::dom::bindings::callback::ExceptionHandling;
+use crate::dom::bindings::cell::DomRefCell;
+use crate::dom::bindings::codegen::Bindings::QueuingStrategyBinding::QueuingStrategy;
+use crate::dom::bindings::codegen::Bindings::TransformStreamBinding;
+use crate::dom::bindings::codegen::Bindings::TransformStreamBinding::TransformStreamMethods;
+use crate::dom::bindings::codegen::Bindings::TransformerBinding::{
+    Transformer as TransformerDict, TransformerFlushCallback, TransformerStartCallback,
+    TransformerTransformCallback,
+};
+use crate::dom::bindings::conversions::ConversionResult;
+use crate::dom::bindings::error::{Error, Fallible};
+use crate::dom::bindings::reflector::{reflect_dom_object, DomObject, Reflector};
+use crate::dom::bindings::root::{Dom, DomRoot, MutNullableDom};
+use crate::dom::globalscope::GlobalScope;
+use crate::dom::promise::Promise;
+use crate::dom::readablestream::{ReadableStream, UnderlyingSource};
+use crate::dom::transformstreamdefaultcontroller::TransformStreamDefaultController;
+use crate::dom::writablestream::{UnderlyingSink, WritableStream, WritableStreamState};
+use crate::realms::enter_realm;
+use crate::script_runtime::JSContext;
+use crate::streams::{extract_high_water_mark, promise_from_call_result};
+use crate::streams::{react_to_promise, resolve_with_promise, resolved_promise};
+use dom_struct::dom_struct;
+use js::jsapi::{Heap, JSObject};
+use js::jsval::{JSVal, ObjectValue, UndefinedValue};
+use js::rust::{HandleObject, HandleValue};
+use std::cell::Cell;
+use std::ptr;
+use std::rc::Rc;
+
+/// A transformer given to the `TransformStream` constructor by script, whose
+/// missing callbacks stand for the algorithms of an identity transform.
+#[derive(JSTraceable)]
+pub struct JsTransformer {
+    /// The `this` object of the callbacks.
+    object: Heap<*mut JSObject>,
+    start: Option<Rc<TransformerStartCallback>>,
+    transform: Option<Rc<TransformerTransformCallback>>,
+    flush: Option<Rc<TransformerFlushCallback>>,
+}
+
+impl JsTransformer {
+    fn new(object: HandleObject, dict: &TransformerDict) -> Rc<JsTransformer> {
+        let transformer = Rc::new(JsTransformer {
+            object: Heap::default(),
+            start: dict.start.clone(),
+            transform: dict.transform.clone(),
+            flush: dict.flush.clone(),
+        });
+        transformer.object.set(object.get());
+        transformer
+    }
+
+    /// The transform algorithm.
+    #[allow(unrooted_must_root)]
+    pub fn transform(
+        &self,
+        global: &GlobalScope,
+        chunk: HandleValue,
+        controller: &TransformStreamDefaultController,
+    ) -> Rc<Promise> {
+        match self.transform {
+            Some(ref transform) => {
+                let cx = global.get_cx();
+                rooted!(in(*cx) let this = self.object.get());
+                let result = transform.Call_(
+                    &this.handle(),
+                    chunk,
+                    controller,
+                    ExceptionHandling::Rethrow,
+                );
+                promise_from_call_result(global, result)
+            },
+            None => {
+                let result = controller.enqueue(global.get_cx(), chunk);
+                promise_from_call_result(global, result.map(|()| UndefinedValue()))
+            },
+        }
+    }
+
+    /// The flush algorithm.
+    #[allow(unrooted_must_root)]
+    pub fn flush(
+        &self,
+        global: &GlobalScope,
+        controller: &TransformStreamDefaultController,
+    ) -> Rc<Promise> {
+        match self.flush {
+            Some(ref flush) => {
+                let cx = global.get_cx();
+                rooted!(in(*cx) let this = self.object.get());
+                let result = flush.Call_(&this.handle(), controller, ExceptionHandling::Rethrow);
+                promise_from_call_result(global, result)
+            },
+            None => resolved_promise(global),
+        }
+    }
+}
+
+/// A transform stream and the promise of the close algorithm of its writable side.
+#[unrooted_must_root_lint::must_root]
+#[derive(Clone, JSTraceable, MallocSizeOf)]
+struct FlushReaction {
+    stream: Dom<TransformStream>,
+    #[ignore_malloc_size_of = "Rc"]
+    promise: Rc<Promise>,
+}
+
+/// A chunk written to a transform stream while it applied backpressure.
+#[unrooted_must_root_lint::must_root]
+#[derive(JSTraceable, MallocSizeOf)]
+struct PendingWrite {
+    stream: Dom<TransformStream>,
+    #[ignore_malloc_size_of = "Defined in rust-mozjs"]
+    chunk: Heap<JSVal>,
+    #[ignore_malloc_size_of = "Rc"]
+    promise: Rc<Promise>,
+}
+
+// https://streams.spec.whatwg.org/#ts-class
+#[dom_struct]
+pub struct TransformStream {
+    reflector_: Reflector,
+    readable: MutNullableDom<ReadableStream>,
+    writable: MutNullableDom<WritableStream>,
+    controller: MutNullableDom<TransformStreamDefaultController>,
+    backpressure: Cell<bool>,
+    #[ignore_malloc_size_of = "Rc"]
+    backpressure_change_promise: DomRefCell<Option<Rc<Promise>>>,
+    /// The promise that the start algorithms of both sides return.
+    #[ignore_malloc_size_of = "Rc"]
+    start_promise: Rc<Promise>,
+}
+
+impl TransformStream {
+    fn new_inherited(global: &GlobalScope) -> TransformStream {
+        TransformStream {
+            reflector_: Reflector::new(),
+            readable: Default::default(),
+            writable: Default::default(),
+            controller: Default::default(),
+            backpressure: Cell::new(false),
+            backpressure_change_promise: DomRefCell::new(None),
+            start_promise: Promise::new(global),
+        }
+    }
+
+    pub fn readable(&self) -> DomRoot<ReadableStream> {
+        self.readable
+            .get()
+            .expect("Transform stream without a readable side")
+    }
+
+    pub fn writable(&self) -> DomRoot<WritableStream> {
+        self.writable
+            .get()
+            .expect("Transform stream without a writable side")
+    }
+
+    pub fn controller(&self) -> DomRoot<TransformStreamDefaultController> {
+        self.controller
+            .get()
+            .expect("Transform stream without a controller")
+    }
+
+    pub fn backpressure(&self) -> bool {
+        self.backpressure.get()
+    }
+
+    pub fn start_promise(&self) -> Rc<Promise> {
+        self.start_promise.clone()
+    }
+
+    /// <https://streams.spec.whatwg.org/#initialize-transform-stream>
+    #[allow(unrooted_must_root)]
+    fn initialize(
+        &self,
+        writable_strategy: &QueuingStrategy,
+        readable_strategy: &QueuingStrategy,
+    ) -> Fallible<()> {
+        let global = self.global();
+
+        // Steps 1-5.
+        let writable_hwm = extract_high_water_mark(writable_strategy, 1.)?;
+        let writable = WritableStream::new_with_sink(
+            &global,
+            UnderlyingSink::Transform(Dom::from_ref(self)),
+            writable_hwm,
+            writable_strategy.size.clone(),
+        )?;
+        self.writable.set(Some(&writable));
+
+        // Steps 6-10.
+        let readable_hwm = extract_high_water_mark(readable_strategy, 0.)?;
+        let readable = ReadableStream::new_with_source(
+            &global,
+            UnderlyingSource::Transform(Dom::from_ref(self)),
+            readable_hwm,
+            readable_strategy.size.clone(),
+        );
+        self.readable.set(Some(&readable));
+
+        // Steps 11-13.
+        self.set_backpressure(true);
+        Ok(())
+    }
+
+    /// <https://streams.spec.whatwg.org/#transform-stream-error>
+    pub fn error(&self, error: HandleValue) {
+        if let Some(controller) = self.readable().default_controller() {
+            controller.error(error);
+        }
+        self.error_writable_and_unblock_write(error);
+    }
+
+    /// <https://streams.spec.whatwg.org/#transform-stream-error-writable-and-unblock-write>
+    pub fn error_writable_and_unblock_write(&self, error: HandleValue) {
+        // Step 1.
+        self.controller().clear_algorithms();
+
+        // Step 2.
+        if let Some(controller) = self.writable().controller() {
+            controller.error_if_needed(error);
+        }
+
+        // Step 3, https://streams.spec.whatwg.org/#transform-stream-unblock-write
+        if self.backpressure.get() {
+            self.set_backpressure(false);
+        }
+    }
+
+    /// <https://streams.spec.whatwg.org/#transform-stream-set-backpressure>
+    pub fn set_backpressure(&self, backpressure: bool) {
+        // Step 2.
+        if let Some(promise) = self.backpressure_change_promise.borrow_mut().take() {
+            promise.resolve_native(&());
+        }
+
+        // Steps 3-4.
+        *self.backpressure_change_promise.borrow_mut() = Some(Promise::new(&self.global()));
+        self.backpressure.set(backpressure);
+    }
+
+    fn backpressure_change_promise(&self) -> Rc<Promise> {
+        self.backpressure_change_promise
+            .borrow()
+            .clone()
+            .expect("Transform stream without a backpressure change promise")
+    }
+
+    /// <https://streams.spec.whatwg.org/#transform-stream-default-sink-write-algorithm>
+    #[allow(unrooted_must_root)]
+    pub fn sink_write(&self, chunk: HandleValue) -> Rc<Promise> {
+        // Step 3.
+        if self.backpressure.get() {
+            let pending = Rc::new(PendingWrite {
+                stream: Dom::from_ref(self),
+                chunk: Heap::default(),
+                promise: Promise::new(&self.global()),
+            });
+            pending.chunk.set(chunk.get());
+            react_to_promise(
+                &self.backpressure_change_promise(),
+                pending.clone(),
+                TransformStream::backpressure_relieved,
+                TransformStream::backpressure_change_rejected,
+            );
+            return pending.promise.clone();
+        }
+
+        // Step 4.
+        self.controller().perform_transform(chunk)
+    }
+
+    #[allow(unrooted_must_root)]
+    fn backpressure_relieved(pending: &Rc<PendingWrite>, cx: JSContext, _v: HandleValue) {
+        let writable = pending.stream.writable();
+        if writable.state() == WritableStreamState::Erroring {
+            rooted!(in(*cx) let mut error = UndefinedValue());
+            writable.get_stored_error(error.handle_mut());
+            let _ac = enter_realm(&*pending.promise);
+            return pending.promise.reject(cx, error.handle());
+        }
+        rooted!(in(*cx) let chunk = pending.chunk.get());
+        let transform_promise = pending
+            .stream
+            .controller()
+            .perform_transform(chunk.handle());
+        resolve_with_promise(&pending.promise, &transform_promise);
+    }
+
+    fn backpressure_change_rejected(
+        pending: &Rc<PendingWrite>,
+        cx: JSContext,
+        reason: HandleValue,
+    ) {
+        let _ac = enter_realm(&*pending.promise);
+        pending.promise.reject(cx, reason);
+    }
+
+    /// <https://streams.spec.whatwg.org/#transform-stream-default-sink-abort-algorithm>
+    pub fn sink_abort(&self, reason: HandleValue) -> Rc<Promise> {
+        self.error(reason);
+        resolved_promise(&self.global())
+    }
+
+    /// <https://streams.spec.whatwg.org/#transform-stream-default-sink-close-algorithm>
+    #[allow(unrooted_must_root)]
+    pub fn sink_close(&self) -> Rc<Promise> {
+        // Steps 1-4.
+        let controller = self.controller();
+        let flush_promise = controller.flush();
+        controller.clear_algorithms();
+
+        // Step 5.
+        let reaction = FlushReaction {
+            stream: Dom::from_ref(self),
+            promise: Promise::new(&self.global()),
+        };
+        react_to_promise(
+            &flush_promise,
+            reaction.clone(),
+            TransformStream::flush_fulfilled,
+            TransformStream::flush_rejected,
+        );
+        reaction.promise
+    }
+
+    fn flush_fulfilled(reaction: &FlushReaction, cx: JSContext, _v: HandleValue) {
+        let readable = reaction.stream.readable();
+        if readable.is_errored() {
+            rooted!(in(*cx) let mut error = UndefinedValue());
+            readable.get_stored_error(error.handle_mut());
+            let _ac = enter_realm(&*reaction.promise);
+            return reaction.promise.reject(cx, error.handle());
+        }
+        if let Some(controller) = readable.default_controller() {
+            controller.close();
+        }
+        reaction.promise.resolve_native(&());
+    }
+
+    fn flush_rejected(reaction: &FlushReaction, cx: JSContext, reason: HandleValue) {
+        reaction.stream.error(reason);
+        rooted!(in(*cx) let mut error = UndefinedValue());
+        reaction
+            .stream
+            .readable()
+            .get_stored_error(error.handle_mut());
+        let _ac = enter_realm(&*reaction.promise);
+        reaction.promise.reject(cx, error.handle());
+    }
+
+    /// <https://streams.spec.whatwg.org/#transform-stream-default-source-pull>
+    pub fn source_pull(&self) -> Rc<Promise> {
+        self.set_backpressure(false);
+        self.backpressure_change_promise()
+    }
+
+    /// <https://streams.spec.whatwg.org/#transform-stream-default-source-cancel>
+    pub fn source_cancel(&self, reason: HandleValue) -> Rc<Promise> {
+        self.error_writable_and_unblock_write(reason);
+        resolved_promise(&self.global())
+    }
+}
+
+impl TransformStreamMethods for TransformStream {
+    // https://streams.spec.whatwg.org/#ts-constructor
+    #[allow(unrooted_must_root)]
+    fn Constructor(
+        cx: JSContext,
+        global: &GlobalScope,
+        transformer: Option<*mut JSObject>,
+        writable_strategy: &QueuingStrategy,
+        readable_strategy: &QueuingStrategy,
+    ) -> Fallible<DomRoot<TransformStream>> {
+        // Steps 1-2.
+        rooted!(in(*cx) let object = transformer.unwrap_or(ptr::null_mut()));
+        rooted!(in(*cx) let mut value = UndefinedValue());
+        if !object.is_null() {
+            value.set(ObjectValue(object.get()));
+        }
+        let dict = match TransformerDict::new(cx, value.handle()) {
+            Ok(ConversionResult::Success(dict)) => dict,
+            Ok(ConversionResult::Failure(error)) => return Err(Error::Type(error.into_owned())),
+            Err(()) => return Err(Error::JSFailed),
+        };
+        let transformer = JsTransformer::new(object.handle(), &dict);
+
+        // Steps 4-9.
+        let stream = reflect_dom_object(
+            Box::new(TransformStream::new_inherited(global)),
+            global,
+            TransformStreamBinding::Wrap,
+        );
+        let controller = TransformStreamDefaultController::new(&stream, transformer.clone());
+        stream.controller.set(Some(&controller));
+        stream.initialize(writable_strategy, readable_strategy)?;
+
+        // Steps 10-11.
+        match transformer.start {
+            Some(ref start) => {
+                rooted!(in(*cx) let this = transformer.object.get());
+                let result =
+                    start.Call_(&this.handle(), &*controller, ExceptionHandling::Rethrow)?;
+                rooted!(in(*cx) let result = result);
+                let _ac = enter_realm(&*stream.start_promise);
+                stream.start_promise.resolve(cx, result.handle());
+            },
+            None => stream.start_promise.resolve_native(&()),
+        }
+
+        Ok(stream)
+    }
+
+    // https://streams.spec.whatwg.org/#ts-readable
+    fn Readable(&self) -> DomRoot<ReadableStream> {
+        self.readable()
+    }
+
+    // https://streams.spec.whatwg.org/#ts-writable
+    fn Writable(&self) -> DomRoot<WritableStream> {
+        self.writable()
+    }
+}
@@ -0,0 +1,197 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::dom::bindings::cell::DomRefCell;
+use crate::dom::bindings::codegen::Bindings::TransformStreamDefaultControllerBinding;
+use crate::dom::bindings::codegen::Bindings::TransformStreamDefaultControllerBinding::TransformStreamDefaultControllerMethods;
+use crate::dom::bindings::error::{Error, ErrorResult};
+use crate::dom::bindings::reflector::{reflect_dom_object, DomObject, Reflector};
+use crate::dom::bindings::root::{Dom, DomRoot};
+use crate::dom::promise::Promise;
+use crate::dom::transformstream::{JsTransformer, TransformStream};
+use crate::realms::enter_realm;
+use crate::script_runtime::JSContext;
+use crate::streams::{error_to_jsval, react_to_promise, resolved_promise};
+use dom_struct::dom_struct;
+use js::jsval::UndefinedValue;
+use js::rust::wrappers::JS_SetPendingException;
+use js::rust::HandleValue;
+use std::rc::Rc;
+
+/// A transform whose result a write to the writable side waits for.
+#[unrooted_must_root_lint::must_root]
+#[derive(Clone, JSTraceable, MallocSizeOf)]
+struct TransformReaction {
+    controller: Dom<TransformStreamDefaultController>,
+    #[ignore_malloc_size_of = "Rc"]
+    promise: Rc<Promise>,
+}
+
+// https://streams.spec.whatwg.org/#ts-default-controller-class
+#[dom_struct]
+pub struct TransformStreamDefaultController {
+    reflector_: Reflector,
+    stream: Dom<TransformStream>,
+    /// The transformer, which is cleared once no more chunks can be transformed.
+    #[ignore_malloc_size_of = "Rc"]
+    transformer: DomRefCell<Option<Rc<JsTransformer>>>,
+}
+
+impl TransformStreamDefaultController {
+    fn new_inherited(
+        stream: &TransformStream,
+        transformer: Rc<JsTransformer>,
+    ) -> TransformStreamDefaultController {
+        TransformStreamDefaultController {
+            reflector_: Reflector::new(),
+            stream: Dom::from_ref(stream),
+            transformer: DomRefCell::new(Some(transformer)),
+        }
+    }
+
+    /// <https://streams.spec.whatwg.org/#set-up-transform-stream-default-controller>
+    pub fn new(
+        stream: &TransformStream,
+        transformer: Rc<JsTransformer>,
+    ) -> DomRoot<TransformStreamDefaultController> {
+        reflect_dom_object(
+            Box::new(TransformStreamDefaultController::new_inherited(
+                stream,
+                transformer,
+            )),
+            &*stream.global(),
+            TransformStreamDefaultControllerBinding::Wrap,
+        )
+    }
+
+    /// <https://streams.spec.whatwg.org/#transform-stream-default-controller-clear-algorithms>
+    pub fn clear_algorithms(&self) {
+        *self.transformer.borrow_mut() = None;
+    }
+
+    /// <https://streams.spec.whatwg.org/#transform-stream-default-controller-enqueue>
+    #[allow(unsafe_code)]
+    pub fn enqueue(&self, cx: JSContext, chunk: HandleValue) -> ErrorResult {
+        // Steps 1-3.
+        let readable = self.stream.readable();
+        let controller = match readable.default_controller() {
+            Some(controller) if controller.can_close_or_enqueue() => controller,
+            _ => {
+                return Err(Error::Type(
+                    "Chunks can't be enqueued into the readable side".to_owned(),
+                ))
+            },
+        };
+
+        // Step 4.
+        if let Err(error) = controller.enqueue(cx, chunk) {
+            let global = self.global();
+            rooted!(in(*cx) let mut error_value = UndefinedValue());
+            error_to_jsval(cx, &global, error, error_value.handle_mut());
+            self.stream
+                .error_writable_and_unblock_write(error_value.handle());
+            rooted!(in(*cx) let mut stored_error = UndefinedValue());
+            readable.get_stored_error(stored_error.handle_mut());
+            unsafe { JS_SetPendingException(*cx, stored_error.handle()) };
+            return Err(Error::JSFailed);
+        }
+
+        // Steps 5-6.
+        if controller.has_backpressure() && !self.stream.backpressure() {
+            self.stream.set_backpressure(true);
+        }
+        Ok(())
+    }
+
+    /// <https://streams.spec.whatwg.org/#transform-stream-default-controller-perform-transform>
+    #[allow(unrooted_must_root)]
+    pub fn perform_transform(&self, chunk: HandleValue) -> Rc<Promise> {
+        let global = self.global();
+
+        // Step 1.
+        let transformer = self.transformer.borrow().clone();
+        let transform_promise = match transformer {
+            Some(transformer) => transformer.transform(&global, chunk, self),
+            None => resolved_promise(&global),
+        };
+
+        // Step 2.
+        let reaction = TransformReaction {
+            controller: Dom::from_ref(self),
+            promise: Promise::new(&global),
+        };
+        react_to_promise(
+            &transform_promise,
+            reaction.clone(),
+            TransformStreamDefaultController::transform_fulfilled,
+            TransformStreamDefaultController::transform_rejected,
+        );
+        reaction.promise
+    }
+
+    fn transform_fulfilled(reaction: &TransformReaction, _cx: JSContext, _v: HandleValue) {
+        reaction.promise.resolve_native(&());
+    }
+
+    fn transform_rejected(reaction: &TransformReaction, cx: JSContext, reason: HandleValue) {
+        reaction.controller.stream.error(reason);
+        let _ac = enter_realm(&*reaction.promise);
+        reaction.promise.reject(cx, reason);
+    }
+
+    /// The flush algorithm of the transformer.
+    #[allow(unrooted_must_root)]
+    pub fn flush(&self) -> Rc<Promise> {
+        let transformer = self.transformer.borrow().clone();
+        match transformer {
+            Some(transformer) => transformer.flush(&self.global(), self),
+            None => resolved_promise(&self.global()),
+        }
+    }
+
+    /// <https://streams.spec.whatwg.org/#transform-stream-default-controller-terminate>
+    fn terminate(&self) {
+        // Steps 2-3.
+        if let Some(controller) = self.stream.readable().default_controller() {
+            controller.close();
+        }
+
+        // Steps 4-5.
+        let global = self.global();
+        let cx = global.get_cx();
+        rooted!(in(*cx) let mut error = UndefinedValue());
+        error_to_jsval(
+            cx,
+            &global,
+            Error::Type("The transform stream has been terminated".to_owned()),
+            error.handle_mut(),
+        );
+        self.stream.error_writable_and_unblock_write(error.handle());
+    }
+}
+
+impl TransformStreamDefaultControllerMethods for TransformStreamDefaultController {
+    // https://streams.spec.whatwg.org/#ts-default-controller-desired-size
+    fn GetDesiredSize(&self) -> Option<f64> {
+        self.stream
+            .readable()
+            .default_controller()
+            .and_then(|controller| controller.desired_size())
+    }
+
+    // https://streams.spec.whatwg.org/#ts-default-controller-enqueue
+    fn Enqueue(&self, cx: JSContext, chunk: HandleValue) -> ErrorResult {
+        self.enqueue(cx, chunk)
+    }
+
+    // https://streams.spec.whatwg.org/#ts-default-controller-error
+    fn Error(&self, _cx: JSContext, reason: HandleValue) {
+        self.stream.error(reason);
+    }
+
+    // https://streams.spec.whatwg.org/#ts-default-controller-terminate
+    fn Terminate(&self) {
+        self.terminate();
+    }
+}
@@ -113,7 +113,8 @@ pub struct WebSocket {
     #[ignore_malloc_size_of = "Defined in std"]
     sender: IpcSender<WebSocketDomAction>,
     binary_type: Cell<BinaryType>,
-    protocol: DomRefCell<String>, //Subprotocol selected by server
+    protocol: DomRefCell<String>,   //Subprotocol selected by server
+    extensions: DomRefCell<String>, //Extensions accepted by server
 }
 
 impl WebSocket {
@@ -127,6 +128,7 @@ impl WebSocket {
             sender: sender,
             binary_type: Cell::new(BinaryType::Blob),
             protocol: DomRefCell::new("".to_owned()),
+            extensions: DomRefCell::new("".to_owned()),
         }
     }
 
@@ -206,7 +208,8 @@ impl WebSocket {
         let request = RequestBuilder::new(url_record)
             .origin(global.origin().immutable().clone())
             .mode(RequestMode::WebSocket { protocols })
-            .referrer(Some(Referrer::NoReferrer));
+            .referrer(Some(Referrer::NoReferrer))
+            .csp_list(global.get_csp_list());
 
         let channels = FetchChannels::WebSocket {
             event_sender: resource_event_sender,
@@ -221,10 +224,14 @@ impl WebSocket {
         ROUTER.add_route(
             dom_event_receiver.to_opaque(),
             Box::new(move |message| match message.to().unwrap() {
-                WebSocketNetworkEvent::ConnectionEstablished { protocol_in_use } => {
+                WebSocketNetworkEvent::ConnectionEstablished {
+                    protocol_in_use,
+                    extensions_in_use,
+                } => {
                     let open_thread = ConnectionEstablishedTask {
                         address: address.clone(),
                         protocol_in_use,
+                        extensions_in_use,
                     };
                     task_source
                         .queue_with_canceller(open_thread, &canceller)
@@ -239,6 +246,16 @@ impl WebSocket {
                         .queue_with_canceller(message_thread, &canceller)
                         .unwrap();
                 },
+                WebSocketNetworkEvent::ReportCspViolations(violations) => {
+                    let address = address.clone();
+                    let _ = task_source.queue_with_canceller(
+                        task!(report_csp_violations: move || {
+                            let ws = address.root();
+                            ws.global().report_csp_violations(violations, None);
+                        }),
+                        &canceller,
+                    );
+                },
                 WebSocketNetworkEvent::Fail => {
                     fail_the_websocket_connection(address.clone(), &task_source, &canceller);
                 },
@@ -343,6 +360,11 @@ impl WebSocketMethods for WebSocket {
         self.binary_type.set(btype)
     }
 
+    // https://html.spec.whatwg.org/multipage/#dom-websocket-extensions
+    fn Extensions(&self) -> DOMString {
+        DOMString::from(self.extensions.borrow().clone())
+    }
+
     // https://html.spec.whatwg.org/multipage/#dom-websocket-protocol
     fn Protocol(&self) -> DOMString {
         DOMString::from(self.protocol.borrow().clone())
@@ -461,6 +483,7 @@ impl WebSocketMethods for WebSocket {
 struct ConnectionEstablishedTask {
     address: Trusted<WebSocket>,
     protocol_in_use: Option<String>,
+    extensions_in_use: Option<String>,
 }
 
 impl TaskOnce for ConnectionEstablishedTask {
@@ -472,7 +495,9 @@ impl TaskOnce for ConnectionEstablishedTask {
         ws.ready_state.set(WebSocketRequestState::Open);
 
         // Step 2: Extensions.
-        // TODO: Set extensions to extensions in use.
+        if let Some(extensions) = self.extensions_in_use {
+            *ws.extensions.borrow_mut() = extensions;
+        }
 
         // Step 3.
         if let Some(protocol_name) = self.protocol_in_use {
@@ -28,6 +28,7 @@ use crate::dom::bindings::trace::RootedTraceableBox;
 use crate::dom::bindings::utils::{GlobalStaticData, WindowProxyHandler};
 use crate::dom::bindings::weakref::DOMTracker;
 use crate::dom::bluetooth::BluetoothExtraPermissionData;
+use crate::dom::cachestorage::CacheStorage;
 use crate::dom::crypto::Crypto;
 use crate::dom::cssstyledeclaration::{CSSModificationAccess, CSSStyleDeclaration, CSSStyleOwner};
 use crate::dom::customelementregistry::CustomElementRegistry;
@@ -38,6 +39,7 @@ use crate::dom::eventtarget::EventTarget;
 use crate::dom::globalscope::GlobalScope;
 use crate::dom::hashchangeevent::HashChangeEvent;
 use crate::dom::history::History;
+use crate::dom::idbfactory::IDBFactory;
 use crate::dom::location::Location;
 use crate::dom::mediaquerylist::{MediaQueryList, MediaQueryListMatchState};
 use crate::dom::mediaquerylistevent::MediaQueryListEvent;
@@ -47,6 +49,8 @@ use crate::dom::node::{document_from_node, from_untrusted_node_address, Node, No
 use crate::dom::performance::Performance;
 use crate::dom::promise::Promise;
 use crate::dom::screen::Screen;
+use crate::dom::selection::Selection;
+use crate::dom::serviceworkercontainer::ServiceWorkerContainer;
 use crate::dom::storage::Storage;
 use crate::dom::testrunner::TestRunner;
 use crate::dom::webglrenderingcontext::WebGLCommandSender;
@@ -65,6 +69,7 @@ use crate::script_thread::{ScriptThread, SendableMainThreadScriptChan};
 use crate::task_manager::TaskManager;
 use crate::task_source::{TaskSource, TaskSourceName};
 use crate::timers::{IsInterval, TimerCallback};
+use crate::user_activation::UserActivation;
 use crate::webdriver_handlers::jsval_to_webdriver;
 use app_units::Au;
 use base64;
@@ -126,6 +131,7 @@ use std::mem;
 use std::rc::Rc;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
+use std::time::Instant;
 use style::dom::OpaqueNode;
 use style::error_reporting::{ContextualParseError, ParseErrorReporter};
 use style::media_queries;
@@ -170,6 +176,7 @@ pub enum ReflowReason {
     IFrameLoadEvent,
     MissingExplicitReflow,
     ElementStateChanged,
+    ResizeObserver,
 }
 
 #[dom_struct]
@@ -241,6 +248,10 @@ pub struct Window {
     /// suppress others like MissingExplicitReflow.
     suppress_reflow: Cell<bool>,
 
+    /// Whether resize observations are being delivered, during which the reflows
+    /// they cause don't deliver them again.
+    delivering_resize_observations: Cell<bool>,
+
     /// A counter of the number of pending reflows for this window.
     pending_reflow_count: Cell<u32>,
 
@@ -335,6 +346,9 @@ pub struct Window {
     /// A mechanism to force the compositor to process events.
     #[ignore_malloc_size_of = "traits are cumbersome"]
     event_loop_waker: Option<Box<dyn EventLoopWaker>>,
+
+    /// Whether the user recently interacted with the window.
+    user_activation: UserActivation,
 }
 
 impl Window {
@@ -804,6 +818,16 @@ impl WindowMethods for Window {
         self.upcast::<GlobalScope>().crypto()
     }
 
+    // https://w3c.github.io/IndexedDB/#dom-windoworworkerglobalscope-indexeddb
+    fn IndexedDB(&self) -> DomRoot<IDBFactory> {
+        self.upcast::<GlobalScope>().indexed_db()
+    }
+
+    // https://w3c.github.io/ServiceWorker/#dom-windoworworkerglobalscope-caches
+    fn Caches(&self) -> DomRoot<CacheStorage> {
+        self.upcast::<GlobalScope>().caches()
+    }
+
     // https://html.spec.whatwg.org/multipage/#dom-frameelement
     fn GetFrameElement(&self) -> Option<DomRoot<Element>> {
         // Steps 1-3.
@@ -1065,6 +1089,11 @@ impl WindowMethods for Window {
         }
     }
 
+    // https://w3c.github.io/selection-api/#dom-window-getselection
+    fn GetSelection(&self) -> Option<DomRoot<Selection>> {
+        self.Document().GetSelection()
+    }
+
     // https://drafts.csswg.org/cssom/#dom-window-getcomputedstyle
     fn GetComputedStyle(
         &self,
@@ -1391,8 +1420,9 @@ impl Window {
         // thread, informing it that it can safely free the memory.
         self.Document().upcast::<Node>().teardown();
 
-        // Tell the constellation to drop the sender to our message-port router, if there is any.
-        self.upcast::<GlobalScope>().remove_message_ports_router();
+        // Tell the constellation to drop the sender to our message-port
+        // and broadcast-channel routers, if there are any.
+        self.upcast::<GlobalScope>().remove_web_messaging_infra();
 
         // Clean up any active promises
         // https://github.com/servo/servo/issues/15318
@@ -1590,6 +1620,9 @@ impl Window {
 
         let document = self.Document();
 
+        // Make sure layout highlights the text that is selected now.
+        document.update_selection_highlight();
+
         let stylesheets_changed = document.flush_stylesheets_for_reflow();
 
         // If this reflow is for display, ensure webgl canvases are composited with
@@ -1717,6 +1750,14 @@ impl Window {
             );
         }
 
+        // Resize observations are gathered once the layout to be displayed is done,
+        // and can only change if layout did anything.
+        if for_display && issued_reflow && !self.delivering_resize_observations.get() {
+            self.delivering_resize_observations.set(true);
+            self.deliver_resize_observations();
+            self.delivering_resize_observations.set(false);
+        }
+
         // If writing a screenshot, check if the script has reached a state
         // where it's safe to write the image. This means that:
         // 1) The reflow is for display (otherwise it could be a query)
@@ -1750,6 +1791,20 @@ impl Window {
         issued_reflow
     }
 
+    /// <https://drafts.csswg.org/resize-observer/#html-event-loop>
+    fn deliver_resize_observations(&self) {
+        let document = self.Document();
+        document.gather_active_resize_observations_at_depth(0);
+        while document.has_active_resize_observations() {
+            let depth = document.broadcast_active_resize_observations();
+            self.reflow(ReflowGoal::Full, ReflowReason::ResizeObserver);
+            document.gather_active_resize_observations_at_depth(depth);
+        }
+        if document.has_skipped_resize_observations() {
+            document.deliver_resize_loop_error_notification();
+        }
+    }
+
     pub fn layout_reflow(&self, query_msg: QueryMsg) -> bool {
         if self.layout_is_busy.load(Ordering::Relaxed) {
             let url = self.get_url().into_string();
@@ -1930,7 +1985,7 @@ impl Window {
         &self,
         replace: HistoryEntryReplacement,
         force_reload: bool,
-        load_data: LoadData,
+        mut load_data: LoadData,
     ) {
         let doc = self.Document();
         // TODO: Important re security. See https://github.com/servo/servo/issues/23373
@@ -1997,6 +2052,9 @@ impl Window {
                 self.window_proxy().start_delaying_load_events_mode();
             }
             // TODO: step 11, navigationType.
+            // The new document replaces this one in the same browsing context,
+            // so it has the same ancestors.
+            load_data.ancestor_origins = doc.ancestor_origins();
             // Step 12, 13
             ScriptThread::navigate(pipeline_id, load_data, replace);
         };
@@ -2019,6 +2077,13 @@ impl Window {
         self.Document().url()
     }
 
+    /// The service worker container of the navigator, if script has used it.
+    pub fn service_worker_container(&self) -> Option<DomRoot<ServiceWorkerContainer>> {
+        self.navigator
+            .get()
+            .and_then(|navigator| navigator.service_worker_container())
+    }
+
     pub fn layout_chan(&self) -> &Sender<Msg> {
         &self.layout_chan
     }
@@ -2147,6 +2212,18 @@ impl Window {
         self.parent_info.is_none()
     }
 
+    /// Record that the user interacted with this window through an activation
+    /// triggering input event.
+    /// <https://html.spec.whatwg.org/multipage/#activation-notification>
+    pub fn notify_activation(&self) {
+        self.user_activation.notify(Instant::now());
+    }
+
+    /// <https://html.spec.whatwg.org/multipage/#transient-activation>
+    pub fn has_transient_activation(&self) -> bool {
+        self.user_activation.is_transient_at(Instant::now())
+    }
+
     /// Evaluate media query lists and report changes
     /// <https://drafts.csswg.org/cssom-view/#evaluate-media-queries-and-report-changes>
     pub fn evaluate_media_queries_and_report_changes(&self) {
@@ -2300,6 +2377,7 @@ impl Window {
             window_size: Cell::new(window_size),
             current_viewport: Cell::new(Rect::zero()),
             suppress_reflow: Cell::new(true),
+            delivering_resize_observations: Cell::new(false),
             pending_reflow_count: Default::default(),
             current_state: Cell::new(WindowState::Alive),
             devtools_marker_sender: Default::default(),
@@ -2329,6 +2407,7 @@ impl Window {
             replace_surrogates,
             player_context,
             event_loop_waker,
+            user_activation: UserActivation::new(),
         });
 
         unsafe { WindowBinding::Wrap(JSContext::from_ptr(runtime.cx()), win) }
@@ -2405,6 +2484,7 @@ fn debug_reflow_events(id: PipelineId, reflow_goal: &ReflowGoal, reason: &Reflow
         ReflowReason::IFrameLoadEvent => "\tIFrameLoadEvent",
         ReflowReason::MissingExplicitReflow => "\tMissingExplicitReflow",
         ReflowReason::ElementStateChanged => "\tElementStateChanged",
+        ReflowReason::ResizeObserver => "\tResizeObserver",
     });
 
     println!("{}", debug_msg);
@@ -15,12 +15,15 @@ use crate::dom::bindings::root::{DomRoot, MutNullableDom};
 use crate::dom::bindings::settings_stack::AutoEntryScript;
 use crate::dom::bindings::str::{DOMString, USVString};
 use crate::dom::bindings::trace::RootedTraceableBox;
+use crate::dom::cachestorage::CacheStorage;
 use crate::dom::crypto::Crypto;
 use crate::dom::dedicatedworkerglobalscope::DedicatedWorkerGlobalScope;
 use crate::dom::globalscope::GlobalScope;
+use crate::dom::idbfactory::IDBFactory;
 use crate::dom::performance::Performance;
 use crate::dom::promise::Promise;
 use crate::dom::serviceworkerglobalscope::ServiceWorkerGlobalScope;
+use crate::dom::sharedworkerglobalscope::SharedWorkerGlobalScope;
 use crate::dom::window::{base64_atob, base64_btoa};
 use crate::dom::workerlocation::WorkerLocation;
 use crate::dom::workernavigator::WorkerNavigator;
@@ -29,6 +32,7 @@ use crate::realms::InRealm;
 use crate::script_runtime::JSContext;
 use crate::script_runtime::{get_reports, CommonScriptMsg, Runtime, ScriptChan, ScriptPort};
 use crate::task::TaskCanceller;
+use crate::task_source::database_access::DatabaseAccessTaskSource;
 use crate::task_source::dom_manipulation::DOMManipulationTaskSource;
 use crate::task_source::file_reading::FileReadingTaskSource;
 use crate::task_source::networking::NetworkingTaskSource;
@@ -187,6 +191,10 @@ impl WorkerGlobalScope {
         *self.worker_url.borrow_mut() = url;
     }
 
+    pub fn worker_name(&self) -> DOMString {
+        self.worker_name.clone()
+    }
+
     pub fn get_worker_id(&self) -> WorkerId {
         self.worker_id.clone()
     }
@@ -281,6 +289,16 @@ impl WorkerGlobalScopeMethods for WorkerGlobalScope {
         self.upcast::<GlobalScope>().crypto()
     }
 
+    // https://w3c.github.io/IndexedDB/#dom-windoworworkerglobalscope-indexeddb
+    fn IndexedDB(&self) -> DomRoot<IDBFactory> {
+        self.upcast::<GlobalScope>().indexed_db()
+    }
+
+    // https://w3c.github.io/ServiceWorker/#dom-windoworworkerglobalscope-caches
+    fn Caches(&self) -> DomRoot<CacheStorage> {
+        self.upcast::<GlobalScope>().caches()
+    }
+
     // https://html.spec.whatwg.org/multipage/#dom-windowbase64-btoa
     fn Btoa(&self, btoa: DOMString) -> Fallible<DOMString> {
         base64_btoa(btoa)
@@ -413,15 +431,22 @@ impl WorkerGlobalScope {
     pub fn script_chan(&self) -> Box<dyn ScriptChan + Send> {
         let dedicated = self.downcast::<DedicatedWorkerGlobalScope>();
         let service_worker = self.downcast::<ServiceWorkerGlobalScope>();
+        let shared_worker = self.downcast::<SharedWorkerGlobalScope>();
         if let Some(dedicated) = dedicated {
             return dedicated.script_chan();
         } else if let Some(service_worker) = service_worker {
             return service_worker.script_chan();
+        } else if let Some(shared_worker) = shared_worker {
+            return shared_worker.script_chan();
         } else {
-            panic!("need to implement a sender for SharedWorker")
+            unreachable!()
         }
     }
 
+    pub fn database_access_task_source(&self) -> DatabaseAccessTaskSource {
+        DatabaseAccessTaskSource(self.script_chan(), self.pipeline_id())
+    }
+
     pub fn dom_manipulation_task_source(&self) -> DOMManipulationTaskSource {
         DOMManipulationTaskSource(self.script_chan(), self.pipeline_id())
     }
@@ -456,10 +481,16 @@ impl WorkerGlobalScope {
 
     pub fn new_script_pair(&self) -> (Box<dyn ScriptChan + Send>, Box<dyn ScriptPort + Send>) {
         let dedicated = self.downcast::<DedicatedWorkerGlobalScope>();
+        let service_worker = self.downcast::<ServiceWorkerGlobalScope>();
+        let shared_worker = self.downcast::<SharedWorkerGlobalScope>();
         if let Some(dedicated) = dedicated {
             return dedicated.new_script_pair();
+        } else if let Some(service_worker) = service_worker {
+            return service_worker.new_script_pair();
+        } else if let Some(shared_worker) = shared_worker {
+            return shared_worker.new_script_pair();
         } else {
-            panic!("need to implement a sender for SharedWorker/ServiceWorker")
+            unreachable!()
         }
     }
 
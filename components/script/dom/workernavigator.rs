@@ -8,8 +8,10 @@ use crate::dom::bindings::reflector::{reflect_dom_object, DomObject, Reflector};
 use crate::dom::bindings::root::{DomRoot, MutNullableDom};
 use crate::dom::bindings::str::DOMString;
 use crate::dom::gpu::GPU;
+use crate::dom::lockmanager::LockManager;
 use crate::dom::navigatorinfo;
 use crate::dom::permissions::Permissions;
+use crate::dom::storagemanager::StorageManager;
 use crate::dom::workerglobalscope::WorkerGlobalScope;
 use dom_struct::dom_struct;
 
@@ -19,6 +21,8 @@ pub struct WorkerNavigator {
     reflector_: Reflector,
     permissions: MutNullableDom<Permissions>,
     gpu: MutNullableDom<GPU>,
+    locks: MutNullableDom<LockManager>,
+    storage: MutNullableDom<StorageManager>,
 }
 
 impl WorkerNavigator {
@@ -27,6 +31,8 @@ impl WorkerNavigator {
             reflector_: Reflector::new(),
             permissions: Default::default(),
             gpu: Default::default(),
+            locks: Default::default(),
+            storage: Default::default(),
         }
     }
 
@@ -105,4 +111,14 @@ impl WorkerNavigatorMethods for WorkerNavigator {
     fn Gpu(&self) -> DomRoot<GPU> {
         self.gpu.or_init(|| GPU::new(&self.global()))
     }
+
+    // https://wicg.github.io/web-locks/#dom-navigatorlocks-locks
+    fn Locks(&self) -> DomRoot<LockManager> {
+        self.locks.or_init(|| LockManager::new(&self.global()))
+    }
+
+    // https://storage.spec.whatwg.org/#dom-navigatorstorage-storage
+    fn Storage(&self) -> DomRoot<StorageManager> {
+        self.storage.or_init(|| StorageManager::new(&self.global()))
+    }
 }
@@ -0,0 +1,668 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::dom::bindings::callback::ExceptionHandling;
+use crate::dom::bindings::cell::DomRefCell;
+use crate::dom::bindings::codegen::Bindings::QueuingStrategyBinding::{
+    QueuingStrategy, QueuingStrategySize,
+};
+use crate::dom::bindings::codegen::Bindings::UnderlyingSinkBinding::{
+    UnderlyingSink as UnderlyingSinkDict, UnderlyingSinkAbortCallback, UnderlyingSinkCloseCallback,
+    UnderlyingSinkStartCallback, UnderlyingSinkWriteCallback,
+};
+use crate::dom::bindings::codegen::Bindings::WritableStreamBinding;
+use crate::dom::bindings::codegen::Bindings::WritableStreamBinding::WritableStreamMethods;
+use crate::dom::bindings::conversions::ConversionResult;
+use crate::dom::bindings::error::{Error, Fallible};
+use crate::dom::bindings::reflector::{reflect_dom_object, DomObject, Reflector};
+use crate::dom::bindings::root::{Dom, DomRoot, MutNullableDom};
+use crate::dom::globalscope::GlobalScope;
+use crate::dom::promise::Promise;
+use crate::dom::transformstream::TransformStream;
+use crate::dom::writablestreamdefaultcontroller::WritableStreamDefaultController;
+use crate::dom::writablestreamdefaultwriter::WritableStreamDefaultWriter;
+use crate::realms::enter_realm;
+use crate::script_runtime::JSContext;
+use crate::streams::{extract_high_water_mark, promise_from_call_result, react_to_promise};
+use crate::streams::{rejected_promise, rejected_promise_with_error, resolved_promise};
+use dom_struct::dom_struct;
+use js::jsapi::{Heap, JSObject};
+use js::jsval::{JSVal, ObjectValue, UndefinedValue};
+use js::rust::{HandleObject, HandleValue, MutableHandleValue};
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::mem;
+use std::ptr;
+use std::rc::Rc;
+
+/// <https://streams.spec.whatwg.org/#writablestream-state>
+#[derive(Clone, Copy, Debug, JSTraceable, MallocSizeOf, PartialEq)]
+pub enum WritableStreamState {
+    Writable,
+    Closed,
+    Erroring,
+    Errored,
+}
+
+/// An underlying sink given to the `WritableStream` constructor by script.
+#[derive(JSTraceable)]
+pub struct JsUnderlyingSink {
+    /// The `this` object of the callbacks.
+    object: Heap<*mut JSObject>,
+    start: Option<Rc<UnderlyingSinkStartCallback>>,
+    write: Option<Rc<UnderlyingSinkWriteCallback>>,
+    close: Option<Rc<UnderlyingSinkCloseCallback>>,
+    abort: Option<Rc<UnderlyingSinkAbortCallback>>,
+}
+
+impl JsUnderlyingSink {
+    fn new(object: HandleObject, dict: &UnderlyingSinkDict) -> Rc<JsUnderlyingSink> {
+        let sink = Rc::new(JsUnderlyingSink {
+            object: Heap::default(),
+            start: dict.start.clone(),
+            write: dict.write.clone(),
+            close: dict.close.clone(),
+            abort: dict.abort.clone(),
+        });
+        sink.object.set(object.get());
+        sink
+    }
+}
+
+/// The algorithms with which a controller starts, writes to, closes and
+/// aborts the destination of the chunks of its stream.
+/// <https://streams.spec.whatwg.org/#underlying-sink-api>
+#[unrooted_must_root_lint::must_root]
+#[derive(Clone, JSTraceable, MallocSizeOf)]
+pub enum UnderlyingSink {
+    Js(#[ignore_malloc_size_of = "Rc"] Rc<JsUnderlyingSink>),
+    /// The writable side of a transform stream.
+    Transform(Dom<TransformStream>),
+}
+
+impl UnderlyingSink {
+    /// The start algorithm, which may throw if the sink was given by script.
+    #[allow(unrooted_must_root)]
+    pub fn start(
+        &self,
+        global: &GlobalScope,
+        controller: &WritableStreamDefaultController,
+    ) -> Fallible<Rc<Promise>> {
+        match *self {
+            UnderlyingSink::Js(ref sink) => match sink.start {
+                Some(ref start) => {
+                    let cx = global.get_cx();
+                    rooted!(in(*cx) let this = sink.object.get());
+                    let result =
+                        start.Call_(&this.handle(), controller, ExceptionHandling::Rethrow)?;
+                    Ok(promise_from_call_result(global, Ok(result)))
+                },
+                None => Ok(resolved_promise(global)),
+            },
+            UnderlyingSink::Transform(ref stream) => Ok(stream.start_promise()),
+        }
+    }
+
+    /// The write algorithm.
+    #[allow(unrooted_must_root)]
+    pub fn write(
+        &self,
+        global: &GlobalScope,
+        chunk: HandleValue,
+        controller: &WritableStreamDefaultController,
+    ) -> Rc<Promise> {
+        match *self {
+            UnderlyingSink::Js(ref sink) => match sink.write {
+                Some(ref write) => {
+                    let cx = global.get_cx();
+                    rooted!(in(*cx) let this = sink.object.get());
+                    let result = write.Call_(
+                        &this.handle(),
+                        chunk,
+                        controller,
+                        ExceptionHandling::Rethrow,
+                    );
+                    promise_from_call_result(global, result)
+                },
+                None => resolved_promise(global),
+            },
+            UnderlyingSink::Transform(ref stream) => stream.sink_write(chunk),
+        }
+    }
+
+    /// The close algorithm.
+    #[allow(unrooted_must_root)]
+    pub fn close(&self, global: &GlobalScope) -> Rc<Promise> {
+        match *self {
+            UnderlyingSink::Js(ref sink) => match sink.close {
+                Some(ref close) => {
+                    let cx = global.get_cx();
+                    rooted!(in(*cx) let this = sink.object.get());
+                    let result = close.Call_(&this.handle(), ExceptionHandling::Rethrow);
+                    promise_from_call_result(global, result)
+                },
+                None => resolved_promise(global),
+            },
+            UnderlyingSink::Transform(ref stream) => stream.sink_close(),
+        }
+    }
+
+    /// The abort algorithm.
+    #[allow(unrooted_must_root)]
+    pub fn abort(&self, global: &GlobalScope, reason: HandleValue) -> Rc<Promise> {
+        match *self {
+            UnderlyingSink::Js(ref sink) => match sink.abort {
+                Some(ref abort) => {
+                    let cx = global.get_cx();
+                    rooted!(in(*cx) let this = sink.object.get());
+                    let result = abort.Call_(&this.handle(), reason, ExceptionHandling::Rethrow);
+                    promise_from_call_result(global, result)
+                },
+                None => resolved_promise(global),
+            },
+            UnderlyingSink::Transform(ref stream) => stream.sink_abort(reason),
+        }
+    }
+}
+
+/// <https://streams.spec.whatwg.org/#pending-abort-request>
+#[derive(JSTraceable, MallocSizeOf)]
+struct PendingAbortRequest {
+    #[ignore_malloc_size_of = "Rc"]
+    promise: Rc<Promise>,
+    #[ignore_malloc_size_of = "Defined in rust-mozjs"]
+    reason: Box<Heap<JSVal>>,
+    was_already_erroring: bool,
+}
+
+/// The stream and the promise of an abort request that waits for the abort
+/// algorithm of the sink.
+#[unrooted_must_root_lint::must_root]
+#[derive(Clone, JSTraceable, MallocSizeOf)]
+struct AbortReaction {
+    stream: Dom<WritableStream>,
+    #[ignore_malloc_size_of = "Rc"]
+    promise: Rc<Promise>,
+}
+
+// https://streams.spec.whatwg.org/#ws-class
+#[dom_struct]
+pub struct WritableStream {
+    reflector_: Reflector,
+    state: Cell<WritableStreamState>,
+    #[ignore_malloc_size_of = "Defined in rust-mozjs"]
+    stored_error: Heap<JSVal>,
+    backpressure: Cell<bool>,
+    controller: MutNullableDom<WritableStreamDefaultController>,
+    writer: MutNullableDom<WritableStreamDefaultWriter>,
+    #[ignore_malloc_size_of = "Rc"]
+    write_requests: DomRefCell<VecDeque<Rc<Promise>>>,
+    #[ignore_malloc_size_of = "Rc"]
+    in_flight_write_request: DomRefCell<Option<Rc<Promise>>>,
+    #[ignore_malloc_size_of = "Rc"]
+    close_request: DomRefCell<Option<Rc<Promise>>>,
+    #[ignore_malloc_size_of = "Rc"]
+    in_flight_close_request: DomRefCell<Option<Rc<Promise>>>,
+    pending_abort_request: DomRefCell<Option<PendingAbortRequest>>,
+}
+
+impl WritableStream {
+    fn new_inherited() -> WritableStream {
+        WritableStream {
+            reflector_: Reflector::new(),
+            state: Cell::new(WritableStreamState::Writable),
+            stored_error: Heap::default(),
+            backpressure: Cell::new(false),
+            controller: Default::default(),
+            writer: Default::default(),
+            write_requests: DomRefCell::new(VecDeque::new()),
+            in_flight_write_request: DomRefCell::new(None),
+            close_request: DomRefCell::new(None),
+            in_flight_close_request: DomRefCell::new(None),
+            pending_abort_request: DomRefCell::new(None),
+        }
+    }
+
+    fn new(global: &GlobalScope) -> DomRoot<WritableStream> {
+        reflect_dom_object(
+            Box::new(WritableStream::new_inherited()),
+            global,
+            WritableStreamBinding::Wrap,
+        )
+    }
+
+    /// <https://streams.spec.whatwg.org/#create-writable-stream>
+    #[allow(unrooted_must_root)]
+    pub fn new_with_sink(
+        global: &GlobalScope,
+        sink: UnderlyingSink,
+        high_water_mark: f64,
+        size: Option<Rc<QueuingStrategySize>>,
+    ) -> Fallible<DomRoot<WritableStream>> {
+        let stream = WritableStream::new(global);
+        WritableStreamDefaultController::setup(&stream, sink, high_water_mark, size)?;
+        Ok(stream)
+    }
+
+    pub fn state(&self) -> WritableStreamState {
+        self.state.get()
+    }
+
+    pub fn is_writable(&self) -> bool {
+        self.state.get() == WritableStreamState::Writable
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.state.get() == WritableStreamState::Closed
+    }
+
+    /// <https://streams.spec.whatwg.org/#is-writable-stream-locked>
+    pub fn is_locked(&self) -> bool {
+        self.writer.get().is_some()
+    }
+
+    pub fn backpressure(&self) -> bool {
+        self.backpressure.get()
+    }
+
+    pub fn get_stored_error(&self, mut rval: MutableHandleValue) {
+        rval.set(self.stored_error.get());
+    }
+
+    pub fn controller(&self) -> Option<DomRoot<WritableStreamDefaultController>> {
+        self.controller.get()
+    }
+
+    pub fn set_controller(&self, controller: &WritableStreamDefaultController) {
+        self.controller.set(Some(controller));
+    }
+
+    pub fn writer(&self) -> Option<DomRoot<WritableStreamDefaultWriter>> {
+        self.writer.get()
+    }
+
+    pub fn set_writer(&self, writer: Option<&WritableStreamDefaultWriter>) {
+        self.writer.set(writer);
+    }
+
+    /// <https://streams.spec.whatwg.org/#writable-stream-close-queued-or-in-flight>
+    pub fn close_queued_or_in_flight(&self) -> bool {
+        self.close_request.borrow().is_some() || self.in_flight_close_request.borrow().is_some()
+    }
+
+    /// <https://streams.spec.whatwg.org/#writable-stream-has-operation-marked-in-flight>
+    fn has_operation_marked_in_flight(&self) -> bool {
+        self.in_flight_write_request.borrow().is_some() ||
+            self.in_flight_close_request.borrow().is_some()
+    }
+
+    pub fn has_in_flight_write_request(&self) -> bool {
+        self.in_flight_write_request.borrow().is_some()
+    }
+
+    /// <https://streams.spec.whatwg.org/#writable-stream-abort>
+    #[allow(unrooted_must_root)]
+    pub fn abort(&self, reason: HandleValue) -> Rc<Promise> {
+        let global = self.global();
+
+        // Steps 1-4.
+        if self.is_closed() || self.state.get() == WritableStreamState::Errored {
+            return resolved_promise(&global);
+        }
+
+        // Step 5.
+        if let Some(ref request) = *self.pending_abort_request.borrow() {
+            return request.promise.clone();
+        }
+
+        // Steps 6-8.
+        let was_already_erroring = self.state.get() == WritableStreamState::Erroring;
+        let cx = global.get_cx();
+        rooted!(in(*cx) let mut reason = reason.get());
+        if was_already_erroring {
+            reason.set(UndefinedValue());
+        }
+
+        // Steps 9-10.
+        let promise = Promise::new(&global);
+        *self.pending_abort_request.borrow_mut() = Some(PendingAbortRequest {
+            promise: promise.clone(),
+            reason: Heap::boxed(reason.get()),
+            was_already_erroring,
+        });
+
+        // Step 11.
+        if !was_already_erroring {
+            self.start_erroring(reason.handle());
+        }
+
+        // Step 12.
+        promise
+    }
+
+    /// <https://streams.spec.whatwg.org/#writable-stream-close>
+    #[allow(unrooted_must_root)]
+    pub fn close(&self) -> Rc<Promise> {
+        let global = self.global();
+
+        // Step 2.
+        if self.is_closed() || self.state.get() == WritableStreamState::Errored {
+            return rejected_promise_with_error(
+                &global,
+                Error::Type("The stream is closed or errored".to_owned()),
+            );
+        }
+
+        // Steps 4-5.
+        let promise = Promise::new(&global);
+        *self.close_request.borrow_mut() = Some(promise.clone());
+
+        // Step 7.
+        if let Some(writer) = self.writer.get() {
+            if self.backpressure.get() && self.is_writable() {
+                writer.resolve_ready_promise();
+            }
+        }
+
+        // Step 8.
+        if let Some(controller) = self.controller.get() {
+            controller.close();
+        }
+
+        // Step 9.
+        promise
+    }
+
+    /// <https://streams.spec.whatwg.org/#writable-stream-add-write-request>
+    #[allow(unrooted_must_root)]
+    pub fn add_write_request(&self) -> Rc<Promise> {
+        let promise = Promise::new(&self.global());
+        self.write_requests.borrow_mut().push_back(promise.clone());
+        promise
+    }
+
+    /// <https://streams.spec.whatwg.org/#writable-stream-deal-with-rejection>
+    pub fn deal_with_rejection(&self, error: HandleValue) {
+        if self.is_writable() {
+            self.start_erroring(error);
+        } else {
+            self.finish_erroring();
+        }
+    }
+
+    /// <https://streams.spec.whatwg.org/#writable-stream-start-erroring>
+    pub fn start_erroring(&self, reason: HandleValue) {
+        // Steps 3-5.
+        self.state.set(WritableStreamState::Erroring);
+        self.stored_error.set(reason.get());
+
+        // Step 6.
+        if let Some(writer) = self.writer.get() {
+            writer.ensure_ready_promise_rejected(reason);
+        }
+
+        // Step 7.
+        let started = self.controller.get().map_or(false, |c| c.started());
+        if !self.has_operation_marked_in_flight() && started {
+            self.finish_erroring();
+        }
+    }
+
+    /// <https://streams.spec.whatwg.org/#writable-stream-finish-erroring>
+    #[allow(unrooted_must_root)]
+    pub fn finish_erroring(&self) {
+        let global = self.global();
+        let cx = global.get_cx();
+
+        // Step 4.
+        self.state.set(WritableStreamState::Errored);
+
+        // Step 5.
+        if let Some(controller) = self.controller.get() {
+            controller.error_steps();
+        }
+
+        // Steps 6-8.
+        rooted!(in(*cx) let stored_error = self.stored_error.get());
+        let write_requests = mem::replace(&mut *self.write_requests.borrow_mut(), VecDeque::new());
+        for promise in write_requests {
+            let _ac = enter_realm(&*promise);
+            promise.reject(cx, stored_error.handle());
+        }
+
+        // Step 9.
+        let request = self.pending_abort_request.borrow_mut().take();
+        let request = match request {
+            Some(request) => request,
+            None => return self.reject_close_and_closed_promise_if_needed(),
+        };
+
+        // Step 12.
+        if request.was_already_erroring {
+            let _ac = enter_realm(&*request.promise);
+            request.promise.reject(cx, stored_error.handle());
+            return self.reject_close_and_closed_promise_if_needed();
+        }
+
+        // Steps 13-15.
+        rooted!(in(*cx) let reason = request.reason.get());
+        let promise = match self.controller.get() {
+            Some(controller) => controller.abort_steps(reason.handle()),
+            None => resolved_promise(&global),
+        };
+        react_to_promise(
+            &promise,
+            AbortReaction {
+                stream: Dom::from_ref(self),
+                promise: request.promise,
+            },
+            WritableStream::abort_fulfilled,
+            WritableStream::abort_rejected,
+        );
+    }
+
+    fn abort_fulfilled(reaction: &AbortReaction, _cx: JSContext, _v: HandleValue) {
+        reaction.promise.resolve_native(&());
+        reaction.stream.reject_close_and_closed_promise_if_needed();
+    }
+
+    fn abort_rejected(reaction: &AbortReaction, cx: JSContext, reason: HandleValue) {
+        {
+            let _ac = enter_realm(&*reaction.promise);
+            reaction.promise.reject(cx, reason);
+        }
+        reaction.stream.reject_close_and_closed_promise_if_needed();
+    }
+
+    /// <https://streams.spec.whatwg.org/#writable-stream-finish-in-flight-write>
+    pub fn finish_in_flight_write(&self) {
+        if let Some(promise) = self.in_flight_write_request.borrow_mut().take() {
+            promise.resolve_native(&());
+        }
+    }
+
+    /// <https://streams.spec.whatwg.org/#writable-stream-finish-in-flight-write-with-error>
+    pub fn finish_in_flight_write_with_error(&self, error: HandleValue) {
+        let promise = self.in_flight_write_request.borrow_mut().take();
+        if let Some(promise) = promise {
+            let _ac = enter_realm(&*promise);
+            promise.reject(self.global().get_cx(), error);
+        }
+        self.deal_with_rejection(error);
+    }
+
+    /// <https://streams.spec.whatwg.org/#writable-stream-finish-in-flight-close>
+    pub fn finish_in_flight_close(&self) {
+        // Steps 2-3.
+        if let Some(promise) = self.in_flight_close_request.borrow_mut().take() {
+            promise.resolve_native(&());
+        }
+
+        // Step 5.
+        if self.state.get() == WritableStreamState::Erroring {
+            self.stored_error.set(UndefinedValue());
+            if let Some(request) = self.pending_abort_request.borrow_mut().take() {
+                request.promise.resolve_native(&());
+            }
+        }
+
+        // Steps 6-8.
+        self.state.set(WritableStreamState::Closed);
+        if let Some(writer) = self.writer.get() {
+            writer.closed().resolve_native(&());
+        }
+    }
+
+    /// <https://streams.spec.whatwg.org/#writable-stream-finish-in-flight-close-with-error>
+    pub fn finish_in_flight_close_with_error(&self, error: HandleValue) {
+        let cx = self.global().get_cx();
+
+        // Steps 2-3.
+        let promise = self.in_flight_close_request.borrow_mut().take();
+        if let Some(promise) = promise {
+            let _ac = enter_realm(&*promise);
+            promise.reject(cx, error);
+        }
+
+        // Step 6.
+        let request = self.pending_abort_request.borrow_mut().take();
+        if let Some(request) = request {
+            let _ac = enter_realm(&*request.promise);
+            request.promise.reject(cx, error);
+        }
+
+        // Step 7.
+        self.deal_with_rejection(error);
+    }
+
+    /// <https://streams.spec.whatwg.org/#writable-stream-mark-close-request-in-flight>
+    pub fn mark_close_request_in_flight(&self) {
+        let request = self.close_request.borrow_mut().take();
+        *self.in_flight_close_request.borrow_mut() = request;
+    }
+
+    /// <https://streams.spec.whatwg.org/#writable-stream-mark-first-write-request-in-flight>
+    pub fn mark_first_write_request_in_flight(&self) {
+        let request = self.write_requests.borrow_mut().pop_front();
+        *self.in_flight_write_request.borrow_mut() = request;
+    }
+
+    /// <https://streams.spec.whatwg.org/#writable-stream-reject-close-and-closed-promise-if-needed>
+    fn reject_close_and_closed_promise_if_needed(&self) {
+        let cx = self.global().get_cx();
+        rooted!(in(*cx) let stored_error = self.stored_error.get());
+
+        // Step 2.
+        let close_request = self.close_request.borrow_mut().take();
+        if let Some(promise) = close_request {
+            let _ac = enter_realm(&*promise);
+            promise.reject(cx, stored_error.handle());
+        }
+
+        // Step 4.
+        if let Some(writer) = self.writer.get() {
+            writer.ensure_closed_promise_rejected(stored_error.handle());
+        }
+    }
+
+    /// <https://streams.spec.whatwg.org/#writable-stream-update-backpressure>
+    pub fn update_backpressure(&self, backpressure: bool) {
+        // Step 3.
+        if let Some(writer) = self.writer.get() {
+            if backpressure != self.backpressure.get() {
+                if backpressure {
+                    writer.reset_ready_promise();
+                } else {
+                    writer.resolve_ready_promise();
+                }
+            }
+        }
+
+        // Step 4.
+        self.backpressure.set(backpressure);
+    }
+}
+
+impl WritableStreamMethods for WritableStream {
+    // https://streams.spec.whatwg.org/#ws-constructor
+    #[allow(unrooted_must_root)]
+    fn Constructor(
+        cx: JSContext,
+        global: &GlobalScope,
+        underlying_sink: Option<*mut JSObject>,
+        strategy: &QueuingStrategy,
+    ) -> Fallible<DomRoot<WritableStream>> {
+        // Steps 1-2.
+        rooted!(in(*cx) let object = underlying_sink.unwrap_or(ptr::null_mut()));
+        rooted!(in(*cx) let mut value = UndefinedValue());
+        if !object.is_null() {
+            value.set(ObjectValue(object.get()));
+        }
+        let dict = match UnderlyingSinkDict::new(cx, value.handle()) {
+            Ok(ConversionResult::Success(dict)) => dict,
+            Ok(ConversionResult::Failure(error)) => return Err(Error::Type(error.into_owned())),
+            Err(()) => return Err(Error::JSFailed),
+        };
+
+        // Steps 4-7.
+        let high_water_mark = extract_high_water_mark(strategy, 1.)?;
+        let sink = UnderlyingSink::Js(JsUnderlyingSink::new(object.handle(), &dict));
+        WritableStream::new_with_sink(global, sink, high_water_mark, strategy.size.clone())
+    }
+
+    // https://streams.spec.whatwg.org/#ws-locked
+    fn Locked(&self) -> bool {
+        self.is_locked()
+    }
+
+    // https://streams.spec.whatwg.org/#ws-abort
+    fn Abort(&self, _cx: JSContext, reason: HandleValue) -> Rc<Promise> {
+        // Step 1.
+        if self.is_locked() {
+            return rejected_promise_with_error(
+                &self.global(),
+                Error::Type("The stream is locked".to_owned()),
+            );
+        }
+
+        // Step 2.
+        self.abort(reason)
+    }
+
+    // https://streams.spec.whatwg.org/#ws-close
+    fn Close(&self) -> Rc<Promise> {
+        // Step 1.
+        if self.is_locked() {
+            return rejected_promise_with_error(
+                &self.global(),
+                Error::Type("The stream is locked".to_owned()),
+            );
+        }
+
+        // Step 2.
+        if self.close_queued_or_in_flight() {
+            return rejected_promise_with_error(
+                &self.global(),
+                Error::Type("The stream is already closing".to_owned()),
+            );
+        }
+
+        // Step 3.
+        self.close()
+    }
+
+    // https://streams.spec.whatwg.org/#ws-get-writer
+    fn GetWriter(&self) -> Fallible<DomRoot<WritableStreamDefaultWriter>> {
+        WritableStreamDefaultWriter::new(&self.global(), self)
+    }
+}
+
+/// A promise rejected with the stored error of `stream`.
+pub fn rejected_with_stored_error(stream: &WritableStream) -> Rc<Promise> {
+    let global = stream.global();
+    let cx = global.get_cx();
+    rooted!(in(*cx) let mut error = UndefinedValue());
+    stream.get_stored_error(error.handle_mut());
+    rejected_promise(&global, error.handle())
+}
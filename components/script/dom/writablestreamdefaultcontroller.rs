@@ -0,0 +1,311 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::dom::bindings::cell::DomRefCell;
+use crate::dom::bindings::codegen::Bindings::QueuingStrategyBinding::QueuingStrategySize;
+use crate::dom::bindings::codegen::Bindings::WritableStreamDefaultControllerBinding;
+use crate::dom::bindings::codegen::Bindings::WritableStreamDefaultControllerBinding::WritableStreamDefaultControllerMethods;
+use crate::dom::bindings::error::Fallible;
+use crate::dom::bindings::reflector::{reflect_dom_object, DomObject, Reflector};
+use crate::dom::bindings::root::{Dom, DomRoot};
+use crate::dom::promise::Promise;
+use crate::dom::writablestream::{UnderlyingSink, WritableStream, WritableStreamState};
+use crate::script_runtime::JSContext;
+use crate::streams::QueueWithSizes;
+use crate::streams::{chunk_size, error_to_jsval, react_to_promise, resolved_promise};
+use dom_struct::dom_struct;
+use js::jsval::UndefinedValue;
+use js::rust::HandleValue;
+use std::cell::Cell;
+use std::rc::Rc;
+
+// https://streams.spec.whatwg.org/#ws-default-controller-class
+#[dom_struct]
+pub struct WritableStreamDefaultController {
+    reflector_: Reflector,
+    stream: Dom<WritableStream>,
+    queue: DomRefCell<QueueWithSizes>,
+    started: Cell<bool>,
+    strategy_hwm: f64,
+    #[ignore_malloc_size_of = "Rc"]
+    strategy_size: DomRefCell<Option<Rc<QueuingStrategySize>>>,
+    /// The sink of the chunks, which is cleared once no more chunks can be written.
+    sink: DomRefCell<Option<UnderlyingSink>>,
+}
+
+impl WritableStreamDefaultController {
+    #[allow(unrooted_must_root)]
+    fn new_inherited(
+        stream: &WritableStream,
+        sink: UnderlyingSink,
+        strategy_hwm: f64,
+        strategy_size: Option<Rc<QueuingStrategySize>>,
+    ) -> WritableStreamDefaultController {
+        WritableStreamDefaultController {
+            reflector_: Reflector::new(),
+            stream: Dom::from_ref(stream),
+            queue: Default::default(),
+            started: Cell::new(false),
+            strategy_hwm,
+            strategy_size: DomRefCell::new(strategy_size),
+            sink: DomRefCell::new(Some(sink)),
+        }
+    }
+
+    /// <https://streams.spec.whatwg.org/#set-up-writable-stream-default-controller>
+    #[allow(unrooted_must_root)]
+    pub fn setup(
+        stream: &WritableStream,
+        sink: UnderlyingSink,
+        strategy_hwm: f64,
+        strategy_size: Option<Rc<QueuingStrategySize>>,
+    ) -> Fallible<DomRoot<WritableStreamDefaultController>> {
+        let global = stream.global();
+
+        // Steps 1-14.
+        let controller = reflect_dom_object(
+            Box::new(WritableStreamDefaultController::new_inherited(
+                stream,
+                sink.clone(),
+                strategy_hwm,
+                strategy_size,
+            )),
+            &*global,
+            WritableStreamDefaultControllerBinding::Wrap,
+        );
+        stream.set_controller(&controller);
+        stream.update_backpressure(controller.get_backpressure());
+
+        // Steps 15-18.
+        let start_promise = sink.start(&global, &controller)?;
+        react_to_promise(
+            &start_promise,
+            Dom::from_ref(&*controller),
+            WritableStreamDefaultController::start_fulfilled,
+            WritableStreamDefaultController::start_rejected,
+        );
+        Ok(controller)
+    }
+
+    fn start_fulfilled(controller: &Dom<Self>, _cx: JSContext, _v: HandleValue) {
+        controller.started.set(true);
+        controller.advance_queue_if_needed();
+    }
+
+    fn start_rejected(controller: &Dom<Self>, _cx: JSContext, error: HandleValue) {
+        controller.started.set(true);
+        controller.stream.deal_with_rejection(error);
+    }
+
+    pub fn started(&self) -> bool {
+        self.started.get()
+    }
+
+    /// <https://streams.spec.whatwg.org/#writable-stream-default-controller-clear-algorithms>
+    fn clear_algorithms(&self) {
+        *self.sink.borrow_mut() = None;
+        *self.strategy_size.borrow_mut() = None;
+    }
+
+    /// <https://streams.spec.whatwg.org/#writable-stream-default-controller-get-desired-size>
+    pub fn desired_size(&self) -> f64 {
+        self.strategy_hwm - self.queue.borrow().total_size()
+    }
+
+    /// <https://streams.spec.whatwg.org/#writable-stream-default-controller-get-backpressure>
+    fn get_backpressure(&self) -> bool {
+        self.desired_size() <= 0.
+    }
+
+    /// <https://streams.spec.whatwg.org/#writable-stream-default-controller-get-chunk-size>
+    pub fn get_chunk_size(&self, cx: JSContext, chunk: HandleValue) -> f64 {
+        let size = self.strategy_size.borrow().clone();
+        match chunk_size(size.as_ref(), chunk) {
+            Ok(size) => size,
+            Err(error) => {
+                rooted!(in(*cx) let mut error_value = UndefinedValue());
+                error_to_jsval(cx, &self.global(), error, error_value.handle_mut());
+                self.error_if_needed(error_value.handle());
+                1.
+            },
+        }
+    }
+
+    /// <https://streams.spec.whatwg.org/#writable-stream-default-controller-write>
+    pub fn write(&self, cx: JSContext, chunk: HandleValue, chunk_size: f64) {
+        // Steps 1-2.
+        let result = self
+            .queue
+            .borrow_mut()
+            .enqueue_value_with_size(chunk, chunk_size);
+        if let Err(error) = result {
+            rooted!(in(*cx) let mut error_value = UndefinedValue());
+            error_to_jsval(cx, &self.global(), error, error_value.handle_mut());
+            return self.error_if_needed(error_value.handle());
+        }
+
+        // Step 4.
+        if !self.stream.close_queued_or_in_flight() && self.stream.is_writable() {
+            self.stream.update_backpressure(self.get_backpressure());
+        }
+
+        // Step 5.
+        self.advance_queue_if_needed();
+    }
+
+    /// <https://streams.spec.whatwg.org/#writable-stream-default-controller-close>
+    pub fn close(&self) {
+        self.queue.borrow_mut().enqueue_close_sentinel();
+        self.advance_queue_if_needed();
+    }
+
+    /// <https://streams.spec.whatwg.org/#writable-stream-default-controller-advance-queue-if-needed>
+    fn advance_queue_if_needed(&self) {
+        // Steps 2-3.
+        if !self.started.get() || self.stream.has_in_flight_write_request() {
+            return;
+        }
+
+        // Steps 5-6.
+        if self.stream.state() == WritableStreamState::Erroring {
+            return self.stream.finish_erroring();
+        }
+
+        // Step 7.
+        if self.queue.borrow().is_empty() {
+            return;
+        }
+
+        // Steps 8-9.
+        if self.queue.borrow().peek_is_close_sentinel() {
+            self.process_close();
+        } else {
+            let cx = self.global().get_cx();
+            rooted!(in(*cx) let mut chunk = UndefinedValue());
+            self.queue.borrow().peek_queue_value(chunk.handle_mut());
+            self.process_write(chunk.handle());
+        }
+    }
+
+    /// <https://streams.spec.whatwg.org/#writable-stream-default-controller-error-if-needed>
+    pub fn error_if_needed(&self, error: HandleValue) {
+        if self.stream.is_writable() {
+            self.error(error);
+        }
+    }
+
+    /// <https://streams.spec.whatwg.org/#writable-stream-default-controller-error>
+    fn error(&self, error: HandleValue) {
+        self.clear_algorithms();
+        self.stream.start_erroring(error);
+    }
+
+    /// <https://streams.spec.whatwg.org/#writable-stream-default-controller-process-close>
+    #[allow(unrooted_must_root)]
+    fn process_close(&self) {
+        // Step 2.
+        self.stream.mark_close_request_in_flight();
+
+        // Steps 3-4.
+        let cx = self.global().get_cx();
+        rooted!(in(*cx) let mut sentinel = UndefinedValue());
+        self.queue.borrow_mut().dequeue_value(sentinel.handle_mut());
+
+        // Steps 5-7.
+        let sink = self.sink.borrow().clone();
+        let close_promise = match sink {
+            Some(sink) => sink.close(&self.global()),
+            None => resolved_promise(&self.global()),
+        };
+        self.clear_algorithms();
+        react_to_promise(
+            &close_promise,
+            Dom::from_ref(self),
+            WritableStreamDefaultController::close_fulfilled,
+            WritableStreamDefaultController::close_rejected,
+        );
+    }
+
+    fn close_fulfilled(controller: &Dom<Self>, _cx: JSContext, _v: HandleValue) {
+        controller.stream.finish_in_flight_close();
+    }
+
+    fn close_rejected(controller: &Dom<Self>, _cx: JSContext, error: HandleValue) {
+        controller.stream.finish_in_flight_close_with_error(error);
+    }
+
+    /// <https://streams.spec.whatwg.org/#writable-stream-default-controller-process-write>
+    #[allow(unrooted_must_root)]
+    fn process_write(&self, chunk: HandleValue) {
+        // Step 2.
+        self.stream.mark_first_write_request_in_flight();
+
+        // Steps 3-4.
+        let sink = self.sink.borrow().clone();
+        let write_promise = match sink {
+            Some(sink) => sink.write(&self.global(), chunk, self),
+            None => resolved_promise(&self.global()),
+        };
+        react_to_promise(
+            &write_promise,
+            Dom::from_ref(self),
+            WritableStreamDefaultController::write_fulfilled,
+            WritableStreamDefaultController::write_rejected,
+        );
+    }
+
+    fn write_fulfilled(controller: &Dom<Self>, cx: JSContext, _v: HandleValue) {
+        // Steps 1-5.
+        controller.stream.finish_in_flight_write();
+        rooted!(in(*cx) let mut chunk = UndefinedValue());
+        controller
+            .queue
+            .borrow_mut()
+            .dequeue_value(chunk.handle_mut());
+        let stream = &controller.stream;
+        if !stream.close_queued_or_in_flight() && stream.is_writable() {
+            stream.update_backpressure(controller.get_backpressure());
+        }
+
+        // Step 6.
+        controller.advance_queue_if_needed();
+    }
+
+    fn write_rejected(controller: &Dom<Self>, _cx: JSContext, error: HandleValue) {
+        if controller.stream.is_writable() {
+            controller.clear_algorithms();
+        }
+        controller.stream.finish_in_flight_write_with_error(error);
+    }
+
+    /// <https://streams.spec.whatwg.org/#ws-default-controller-private-abort>
+    #[allow(unrooted_must_root)]
+    pub fn abort_steps(&self, reason: HandleValue) -> Rc<Promise> {
+        let sink = self.sink.borrow().clone();
+        let result = match sink {
+            Some(sink) => sink.abort(&self.global(), reason),
+            None => resolved_promise(&self.global()),
+        };
+        self.clear_algorithms();
+        result
+    }
+
+    /// <https://streams.spec.whatwg.org/#ws-default-controller-private-error>
+    pub fn error_steps(&self) {
+        self.queue.borrow_mut().reset();
+    }
+}
+
+impl WritableStreamDefaultControllerMethods for WritableStreamDefaultController {
+    // https://streams.spec.whatwg.org/#ws-default-controller-error
+    fn Error(&self, _cx: JSContext, error: HandleValue) {
+        // Steps 1-2.
+        if !self.stream.is_writable() {
+            return;
+        }
+
+        // Step 3.
+        self.error(error);
+    }
+}
@@ -0,0 +1,313 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::dom::bindings::cell::DomRefCell;
+use crate::dom::bindings::codegen::Bindings::WritableStreamDefaultWriterBinding;
+use crate::dom::bindings::codegen::Bindings::WritableStreamDefaultWriterBinding::WritableStreamDefaultWriterMethods;
+use crate::dom::bindings::error::{Error, Fallible};
+use crate::dom::bindings::reflector::{reflect_dom_object, DomObject, Reflector};
+use crate::dom::bindings::root::{DomRoot, MutNullableDom};
+use crate::dom::globalscope::GlobalScope;
+use crate::dom::promise::Promise;
+use crate::dom::writablestream::{rejected_with_stored_error, WritableStream, WritableStreamState};
+use crate::realms::enter_realm;
+use crate::script_runtime::JSContext;
+use crate::streams::{error_to_jsval, resolved_promise};
+use crate::streams::{mark_as_handled, rejected_promise, rejected_promise_with_error};
+use dom_struct::dom_struct;
+use js::jsval::UndefinedValue;
+use js::rust::HandleValue;
+use std::rc::Rc;
+
+// https://streams.spec.whatwg.org/#default-writer-class
+#[dom_struct]
+pub struct WritableStreamDefaultWriter {
+    reflector_: Reflector,
+    stream: MutNullableDom<WritableStream>,
+    #[ignore_malloc_size_of = "Rc"]
+    closed_promise: DomRefCell<Rc<Promise>>,
+    #[ignore_malloc_size_of = "Rc"]
+    ready_promise: DomRefCell<Rc<Promise>>,
+}
+
+impl WritableStreamDefaultWriter {
+    fn new_inherited(global: &GlobalScope) -> WritableStreamDefaultWriter {
+        WritableStreamDefaultWriter {
+            reflector_: Reflector::new(),
+            stream: Default::default(),
+            closed_promise: DomRefCell::new(Promise::new(global)),
+            ready_promise: DomRefCell::new(Promise::new(global)),
+        }
+    }
+
+    /// <https://streams.spec.whatwg.org/#set-up-writable-stream-default-writer>
+    pub fn new(
+        global: &GlobalScope,
+        stream: &WritableStream,
+    ) -> Fallible<DomRoot<WritableStreamDefaultWriter>> {
+        // Step 1.
+        if stream.is_locked() {
+            return Err(Error::Type("The stream is locked".to_owned()));
+        }
+
+        // Steps 2-3.
+        let writer = reflect_dom_object(
+            Box::new(WritableStreamDefaultWriter::new_inherited(global)),
+            global,
+            WritableStreamDefaultWriterBinding::Wrap,
+        );
+        writer.stream.set(Some(stream));
+        stream.set_writer(Some(&writer));
+
+        // Steps 4-8.
+        let (ready_promise, closed_promise) = match stream.state() {
+            WritableStreamState::Writable => {
+                let ready_promise = if !stream.close_queued_or_in_flight() && stream.backpressure()
+                {
+                    Promise::new(global)
+                } else {
+                    resolved_promise(global)
+                };
+                (ready_promise, Promise::new(global))
+            },
+            WritableStreamState::Erroring => {
+                let ready_promise = rejected_with_stored_error(stream);
+                mark_as_handled(&ready_promise);
+                (ready_promise, Promise::new(global))
+            },
+            WritableStreamState::Closed => (resolved_promise(global), resolved_promise(global)),
+            WritableStreamState::Errored => {
+                let ready_promise = rejected_with_stored_error(stream);
+                mark_as_handled(&ready_promise);
+                let closed_promise = rejected_with_stored_error(stream);
+                mark_as_handled(&closed_promise);
+                (ready_promise, closed_promise)
+            },
+        };
+        *writer.ready_promise.borrow_mut() = ready_promise;
+        *writer.closed_promise.borrow_mut() = closed_promise;
+        Ok(writer)
+    }
+
+    pub fn closed(&self) -> Rc<Promise> {
+        self.closed_promise.borrow().clone()
+    }
+
+    pub fn ready(&self) -> Rc<Promise> {
+        self.ready_promise.borrow().clone()
+    }
+
+    pub fn resolve_ready_promise(&self) {
+        self.ready_promise.borrow().resolve_native(&());
+    }
+
+    /// Replace the ready promise with a pending one, while the stream applies backpressure.
+    pub fn reset_ready_promise(&self) {
+        *self.ready_promise.borrow_mut() = Promise::new(&self.global());
+    }
+
+    /// <https://streams.spec.whatwg.org/#writable-stream-default-writer-ensure-ready-promise-rejected>
+    pub fn ensure_ready_promise_rejected(&self, error: HandleValue) {
+        let ready_promise = self.ready();
+        if ready_promise.is_fulfilled() {
+            *self.ready_promise.borrow_mut() = rejected_promise(&self.global(), error);
+        } else {
+            let _ac = enter_realm(&*ready_promise);
+            ready_promise.reject(self.global().get_cx(), error);
+        }
+        mark_as_handled(&self.ready());
+    }
+
+    /// <https://streams.spec.whatwg.org/#writable-stream-default-writer-ensure-closed-promise-rejected>
+    pub fn ensure_closed_promise_rejected(&self, error: HandleValue) {
+        let closed_promise = self.closed();
+        if closed_promise.is_fulfilled() {
+            *self.closed_promise.borrow_mut() = rejected_promise(&self.global(), error);
+        } else {
+            let _ac = enter_realm(&*closed_promise);
+            closed_promise.reject(self.global().get_cx(), error);
+        }
+        mark_as_handled(&self.closed());
+    }
+
+    /// <https://streams.spec.whatwg.org/#writable-stream-default-writer-get-desired-size>
+    fn desired_size(&self, stream: &WritableStream) -> Option<f64> {
+        match stream.state() {
+            WritableStreamState::Errored | WritableStreamState::Erroring => None,
+            WritableStreamState::Closed => Some(0.),
+            WritableStreamState::Writable => stream.controller().map(|c| c.desired_size()),
+        }
+    }
+
+    /// <https://streams.spec.whatwg.org/#writable-stream-default-writer-write>
+    pub fn write(&self, cx: JSContext, chunk: HandleValue) -> Rc<Promise> {
+        let global = self.global();
+        let stream = match self.stream.get() {
+            Some(stream) => stream,
+            None => {
+                return rejected_promise_with_error(
+                    &global,
+                    Error::Type("The writer has been released".to_owned()),
+                )
+            },
+        };
+        let controller = match stream.controller() {
+            Some(controller) => controller,
+            None => return resolved_promise(&global),
+        };
+
+        // Step 3.
+        let chunk_size = controller.get_chunk_size(cx, chunk);
+
+        // Step 4.
+        if self.stream.get().map_or(true, |s| s != stream) {
+            return rejected_promise_with_error(
+                &global,
+                Error::Type("The writer has been released".to_owned()),
+            );
+        }
+
+        match stream.state() {
+            // Step 6.
+            WritableStreamState::Errored | WritableStreamState::Erroring => {
+                return rejected_with_stored_error(&stream)
+            },
+            // Step 7.
+            WritableStreamState::Closed => {
+                return rejected_promise_with_error(
+                    &global,
+                    Error::Type("The stream is closed".to_owned()),
+                )
+            },
+            WritableStreamState::Writable if stream.close_queued_or_in_flight() => {
+                return rejected_promise_with_error(
+                    &global,
+                    Error::Type("The stream is closing".to_owned()),
+                )
+            },
+            WritableStreamState::Writable => {},
+        }
+
+        // Steps 10-12.
+        let promise = stream.add_write_request();
+        controller.write(cx, chunk, chunk_size);
+        promise
+    }
+
+    /// <https://streams.spec.whatwg.org/#writable-stream-default-writer-close-with-error-propagation>
+    pub fn close_with_error_propagation(&self) -> Rc<Promise> {
+        let global = self.global();
+        let stream = match self.stream.get() {
+            Some(stream) => stream,
+            None => return resolved_promise(&global),
+        };
+
+        // Step 4.
+        if stream.close_queued_or_in_flight() || stream.is_closed() {
+            return resolved_promise(&global);
+        }
+
+        // Step 5.
+        if stream.state() == WritableStreamState::Errored {
+            return rejected_with_stored_error(&stream);
+        }
+
+        // Step 7.
+        stream.close()
+    }
+
+    /// <https://streams.spec.whatwg.org/#writable-stream-default-writer-release>
+    pub fn release(&self) {
+        let stream = match self.stream.get() {
+            Some(stream) => stream,
+            None => return,
+        };
+
+        // Steps 3-5.
+        let global = self.global();
+        let cx = global.get_cx();
+        rooted!(in(*cx) let mut error = UndefinedValue());
+        error_to_jsval(
+            cx,
+            &global,
+            Error::Type("The writer has been released".to_owned()),
+            error.handle_mut(),
+        );
+        self.ensure_ready_promise_rejected(error.handle());
+        self.ensure_closed_promise_rejected(error.handle());
+
+        // Steps 6-7.
+        stream.set_writer(None);
+        self.stream.set(None);
+    }
+}
+
+impl WritableStreamDefaultWriterMethods for WritableStreamDefaultWriter {
+    // https://streams.spec.whatwg.org/#default-writer-constructor
+    fn Constructor(
+        global: &GlobalScope,
+        stream: &WritableStream,
+    ) -> Fallible<DomRoot<WritableStreamDefaultWriter>> {
+        WritableStreamDefaultWriter::new(global, stream)
+    }
+
+    // https://streams.spec.whatwg.org/#default-writer-closed
+    fn Closed(&self) -> Rc<Promise> {
+        self.closed()
+    }
+
+    // https://streams.spec.whatwg.org/#default-writer-desired-size
+    fn GetDesiredSize(&self) -> Fallible<Option<f64>> {
+        match self.stream.get() {
+            Some(stream) => Ok(self.desired_size(&stream)),
+            None => Err(Error::Type("The writer has been released".to_owned())),
+        }
+    }
+
+    // https://streams.spec.whatwg.org/#default-writer-ready
+    fn Ready(&self) -> Rc<Promise> {
+        self.ready()
+    }
+
+    // https://streams.spec.whatwg.org/#default-writer-abort
+    fn Abort(&self, _cx: JSContext, reason: HandleValue) -> Rc<Promise> {
+        match self.stream.get() {
+            Some(stream) => stream.abort(reason),
+            None => rejected_promise_with_error(
+                &self.global(),
+                Error::Type("The writer has been released".to_owned()),
+            ),
+        }
+    }
+
+    // https://streams.spec.whatwg.org/#default-writer-close
+    fn Close(&self) -> Rc<Promise> {
+        let stream = match self.stream.get() {
+            Some(stream) => stream,
+            None => {
+                return rejected_promise_with_error(
+                    &self.global(),
+                    Error::Type("The writer has been released".to_owned()),
+                )
+            },
+        };
+        if stream.close_queued_or_in_flight() {
+            return rejected_promise_with_error(
+                &self.global(),
+                Error::Type("The stream is already closing".to_owned()),
+            );
+        }
+        stream.close()
+    }
+
+    // https://streams.spec.whatwg.org/#default-writer-release-lock
+    fn ReleaseLock(&self) {
+        self.release();
+    }
+
+    // https://streams.spec.whatwg.org/#default-writer-write
+    fn Write(&self, cx: JSContext, chunk: HandleValue) -> Rc<Promise> {
+        self.write(cx, chunk)
+    }
+}
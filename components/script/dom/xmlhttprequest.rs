@@ -5,12 +5,12 @@
 use crate::document_loader::DocumentLoader;
 use crate::dom::bindings::cell::DomRefCell;
 use crate::dom::bindings::codegen::Bindings::BlobBinding::BlobBinding::BlobMethods;
+use crate::dom::bindings::codegen::Bindings::ResponseBinding::BodyInit;
 use crate::dom::bindings::codegen::Bindings::WindowBinding::WindowMethods;
 use crate::dom::bindings::codegen::Bindings::XMLHttpRequestBinding;
-use crate::dom::bindings::codegen::Bindings::XMLHttpRequestBinding::BodyInit;
 use crate::dom::bindings::codegen::Bindings::XMLHttpRequestBinding::XMLHttpRequestMethods;
 use crate::dom::bindings::codegen::Bindings::XMLHttpRequestBinding::XMLHttpRequestResponseType;
-use crate::dom::bindings::codegen::UnionTypes::DocumentOrBodyInit;
+use crate::dom::bindings::codegen::UnionTypes::DocumentOrXMLHttpRequestBodyInit;
 use crate::dom::bindings::conversions::ToJSValConvertible;
 use crate::dom::bindings::error::{Error, ErrorResult, Fallible};
 use crate::dom::bindings::inheritance::Castable;
@@ -30,6 +30,7 @@ use crate::dom::htmlformelement::{encode_multipart_form_data, generate_boundary}
 use crate::dom::node::Node;
 use crate::dom::performanceresourcetiming::InitiatorType;
 use crate::dom::progressevent::ProgressEvent;
+use crate::dom::readablestream::ReadableStream;
 use crate::dom::servoparser::ServoParser;
 use crate::dom::urlsearchparams::URLSearchParams;
 use crate::dom::window::Window;
@@ -42,6 +43,7 @@ use crate::script_runtime::JSContext;
 use crate::task_source::networking::NetworkingTaskSource;
 use crate::task_source::TaskSourceName;
 use crate::timers::{OneshotTimerCallback, OneshotTimerHandle};
+use content_security_policy as csp;
 use dom_struct::dom_struct;
 use encoding_rs::{Encoding, UTF_8};
 use euclid::Length;
@@ -283,6 +285,11 @@ impl XMLHttpRequest {
             fn submit_resource_timing(&mut self) {
                 network_listener::submit_timing(self)
             }
+
+            fn process_csp_violations(&mut self, violations: Vec<csp::Violation>) {
+                let global = &self.resource_timing_global();
+                global.report_csp_violations(violations, None);
+            }
         }
 
         impl ResourceTimingListener for XHRContext {
@@ -555,7 +562,7 @@ impl XMLHttpRequestMethods for XMLHttpRequest {
     }
 
     // https://xhr.spec.whatwg.org/#the-send()-method
-    fn Send(&self, data: Option<DocumentOrBodyInit>) -> ErrorResult {
+    fn Send(&self, data: Option<DocumentOrXMLHttpRequestBodyInit>) -> ErrorResult {
         // Step 1, 2
         if self.ready_state.get() != XMLHttpRequestState::Opened || self.send_flag.get() {
             return Err(Error::InvalidState);
@@ -568,7 +575,7 @@ impl XMLHttpRequestMethods for XMLHttpRequest {
         };
         // Step 4 (first half)
         let extracted_or_serialized = match data {
-            Some(DocumentOrBodyInit::Document(ref doc)) => {
+            Some(DocumentOrXMLHttpRequestBodyInit::Document(ref doc)) => {
                 let data = Vec::from(serialize_document(&doc)?.as_ref());
                 let content_type = if doc.is_html_document() {
                     "text/html;charset=UTF-8"
@@ -577,14 +584,18 @@ impl XMLHttpRequestMethods for XMLHttpRequest {
                 };
                 Some((data, Some(DOMString::from(content_type))))
             },
-            Some(DocumentOrBodyInit::Blob(ref b)) => Some(b.extract()),
-            Some(DocumentOrBodyInit::FormData(ref formdata)) => Some(formdata.extract()),
-            Some(DocumentOrBodyInit::String(ref str)) => Some(str.extract()),
-            Some(DocumentOrBodyInit::URLSearchParams(ref urlsp)) => Some(urlsp.extract()),
-            Some(DocumentOrBodyInit::ArrayBuffer(ref typedarray)) => {
+            Some(DocumentOrXMLHttpRequestBodyInit::Blob(ref b)) => Some(b.extract()),
+            Some(DocumentOrXMLHttpRequestBodyInit::FormData(ref formdata)) => {
+                Some(formdata.extract())
+            },
+            Some(DocumentOrXMLHttpRequestBodyInit::String(ref str)) => Some(str.extract()),
+            Some(DocumentOrXMLHttpRequestBodyInit::URLSearchParams(ref urlsp)) => {
+                Some(urlsp.extract())
+            },
+            Some(DocumentOrXMLHttpRequestBodyInit::ArrayBuffer(ref typedarray)) => {
                 Some((typedarray.to_vec(), None))
             },
-            Some(DocumentOrBodyInit::ArrayBufferView(ref typedarray)) => {
+            Some(DocumentOrXMLHttpRequestBodyInit::ArrayBufferView(ref typedarray)) => {
                 Some((typedarray.to_vec(), None))
             },
             None => None,
@@ -660,13 +671,15 @@ impl XMLHttpRequestMethods for XMLHttpRequest {
                     .map(|referrer_url| Referrer::ReferrerUrl(referrer_url)),
             )
             .referrer_policy(self.referrer_policy.clone())
-            .pipeline_id(Some(self.global().pipeline_id()));
+            .pipeline_id(Some(self.global().pipeline_id()))
+            .csp_list(self.global().get_csp_list());
 
         // step 4 (second half)
         match extracted_or_serialized {
             Some((_, ref content_type)) => {
                 let encoding = match data {
-                    Some(DocumentOrBodyInit::String(_)) | Some(DocumentOrBodyInit::Document(_)) =>
+                    Some(DocumentOrXMLHttpRequestBodyInit::String(_)) |
+                    Some(DocumentOrXMLHttpRequestBodyInit::Document(_)) =>
                     // XHR spec differs from http, and says UTF-8 should be in capitals,
                     // instead of "utf-8", which is what Hyper defaults to. So not
                     // using content types provided by Hyper.
@@ -1618,18 +1631,26 @@ fn serialize_document(doc: &Document) -> Fallible<DOMString> {
     }
 }
 
-impl Extractable for BodyInit {
-    // https://fetch.spec.whatwg.org/#concept-bodyinit-extract
-    fn extract(&self) -> (Vec<u8>, Option<DOMString>) {
-        match *self {
-            BodyInit::String(ref s) => s.extract(),
-            BodyInit::URLSearchParams(ref usp) => usp.extract(),
-            BodyInit::Blob(ref b) => b.extract(),
-            BodyInit::FormData(ref formdata) => formdata.extract(),
-            BodyInit::ArrayBuffer(ref typedarray) => ((typedarray.to_vec(), None)),
-            BodyInit::ArrayBufferView(ref typedarray) => ((typedarray.to_vec(), None)),
-        }
-    }
+/// A body given by script, with its bytes extracted unless it is a stream.
+pub enum ExtractedBody {
+    /// A stream, whose bytes are only known once it is read.
+    Stream(DomRoot<ReadableStream>),
+    /// The bytes of any other body, and its content type.
+    Bytes(Vec<u8>, Option<DOMString>),
+}
+
+// https://fetch.spec.whatwg.org/#concept-bodyinit-extract
+pub fn extract_body(body: &BodyInit) -> ExtractedBody {
+    let (bytes, content_type) = match *body {
+        BodyInit::String(ref s) => s.extract(),
+        BodyInit::URLSearchParams(ref usp) => usp.extract(),
+        BodyInit::Blob(ref b) => b.extract(),
+        BodyInit::FormData(ref formdata) => formdata.extract(),
+        BodyInit::ArrayBuffer(ref typedarray) => (typedarray.to_vec(), None),
+        BodyInit::ArrayBufferView(ref typedarray) => (typedarray.to_vec(), None),
+        BodyInit::ReadableStream(ref stream) => return ExtractedBody::Stream(stream.clone()),
+    };
+    ExtractedBody::Bytes(bytes, content_type)
 }
 
 /// Returns whether `bs` is a `field-value`, as defined by
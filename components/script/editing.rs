@@ -0,0 +1,1646 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Editing of the content of editing hosts, through the keyboard and through the commands of
+//! `document.execCommand`.
+//!
+//! <https://w3c.github.io/editing/docs/execCommand/>
+
+use crate::dom::bindings::codegen::Bindings::CharacterDataBinding::CharacterDataMethods;
+use crate::dom::bindings::codegen::Bindings::DocumentBinding::DocumentMethods;
+use crate::dom::bindings::codegen::Bindings::NodeBinding::NodeMethods;
+use crate::dom::bindings::codegen::Bindings::RangeBinding::RangeMethods;
+use crate::dom::bindings::codegen::Bindings::SelectionBinding::SelectionMethods;
+use crate::dom::bindings::codegen::Bindings::TextBinding::TextMethods;
+use crate::dom::bindings::inheritance::Castable;
+use crate::dom::bindings::root::{Dom, DomRoot};
+use crate::dom::bindings::str::DOMString;
+use crate::dom::characterdata::CharacterData;
+use crate::dom::document::Document;
+use crate::dom::element::Element;
+use crate::dom::event::{Event, EventStatus};
+use crate::dom::htmlanchorelement::HTMLAnchorElement;
+use crate::dom::htmlbrelement::HTMLBRElement;
+use crate::dom::htmlelement::HTMLElement;
+use crate::dom::htmlimageelement::HTMLImageElement;
+use crate::dom::htmlspanelement::HTMLSpanElement;
+use crate::dom::inputevent::InputEvent;
+use crate::dom::mutationobserver::Mutation;
+use crate::dom::node::{window_from_node, CloneChildrenFlag, Node, ShadowIncluding};
+use crate::dom::range::Range;
+use crate::dom::selection::{selected_text, Selection};
+use crate::dom::text::Text;
+use crate::textinput::CMD_OR_CONTROL;
+use html5ever::{LocalName, Namespace};
+use keyboard_types::{Key, KeyState, KeyboardEvent, Modifiers, ShortcutMatcher};
+use std::iter;
+use style::values::generics::font::FontStyle;
+use style::values::specified::box_::DisplayOutside;
+
+/// The number of edits that can be undone.
+const MAX_UNDO_TRANSACTIONS: usize = 100;
+
+/// The commands of `document.execCommand` that are supported.
+/// <https://w3c.github.io/editing/docs/execCommand/#supported>
+#[derive(Clone, Copy, PartialEq)]
+pub enum EditCommand {
+    Bold,
+    CreateLink,
+    Delete,
+    ForwardDelete,
+    InsertLineBreak,
+    InsertParagraph,
+    InsertText,
+    Italic,
+    Redo,
+    SelectAll,
+    Undo,
+}
+
+impl EditCommand {
+    pub fn from_name(name: &str) -> Option<EditCommand> {
+        Some(match &*name.to_ascii_lowercase() {
+            "bold" => EditCommand::Bold,
+            "createlink" => EditCommand::CreateLink,
+            "delete" => EditCommand::Delete,
+            "forwarddelete" => EditCommand::ForwardDelete,
+            "insertlinebreak" => EditCommand::InsertLineBreak,
+            "insertparagraph" => EditCommand::InsertParagraph,
+            "inserttext" => EditCommand::InsertText,
+            "italic" => EditCommand::Italic,
+            "redo" => EditCommand::Redo,
+            "selectall" => EditCommand::SelectAll,
+            "undo" => EditCommand::Undo,
+            _ => return None,
+        })
+    }
+
+    /// The `inputType` of the input events of this command, if it changes the content.
+    /// <https://w3c.github.io/input-events/#interface-InputEvent-Attributes>
+    fn input_type(self) -> Option<&'static str> {
+        Some(match self {
+            EditCommand::Bold => "formatBold",
+            EditCommand::CreateLink => "insertLink",
+            EditCommand::Delete => "deleteContentBackward",
+            EditCommand::ForwardDelete => "deleteContentForward",
+            EditCommand::InsertLineBreak => "insertLineBreak",
+            EditCommand::InsertParagraph => "insertParagraph",
+            EditCommand::InsertText => "insertText",
+            EditCommand::Italic => "formatItalic",
+            EditCommand::Redo => "historyRedo",
+            EditCommand::Undo => "historyUndo",
+            EditCommand::SelectAll => return None,
+        })
+    }
+
+    fn inline_style(self) -> Option<InlineStyle> {
+        match self {
+            EditCommand::Bold => Some(InlineStyle::Bold),
+            EditCommand::Italic => Some(InlineStyle::Italic),
+            _ => None,
+        }
+    }
+}
+
+/// The styles of text that can be toggled.
+#[derive(Clone, Copy)]
+enum InlineStyle {
+    Bold,
+    Italic,
+}
+
+impl InlineStyle {
+    /// The element that is wrapped around text to apply this style.
+    fn local_name(self) -> LocalName {
+        match self {
+            InlineStyle::Bold => local_name!("b"),
+            InlineStyle::Italic => local_name!("i"),
+        }
+    }
+
+    /// Whether an element applies this style to its content by virtue of being itself.
+    fn is_applied_by(self, element: &Element) -> bool {
+        if *element.namespace() != ns!(html) {
+            return false;
+        }
+        match self {
+            InlineStyle::Bold => {
+                *element.local_name() == local_name!("b") ||
+                    *element.local_name() == local_name!("strong")
+            },
+            InlineStyle::Italic => {
+                *element.local_name() == local_name!("i") ||
+                    *element.local_name() == local_name!("em")
+            },
+        }
+    }
+
+    /// Whether the text of a node is rendered in this style.
+    fn is_applied_to(self, node: &Node) -> bool {
+        let element = match node.downcast::<Element>() {
+            Some(element) => DomRoot::from_ref(element),
+            None => match node.GetParentElement() {
+                Some(element) => element,
+                None => return false,
+            },
+        };
+        let style = match element.style() {
+            Some(style) => style,
+            None => return false,
+        };
+        match self {
+            InlineStyle::Bold => style.get_font().clone_font_weight().is_bold(),
+            InlineStyle::Italic => match style.get_font().clone_font_style() {
+                FontStyle::Normal => false,
+                _ => true,
+            },
+        }
+    }
+
+    /// The declaration that removes this style from text that inherits it.
+    fn removal_declaration(self) -> &'static str {
+        match self {
+            InlineStyle::Bold => "font-weight: normal",
+            InlineStyle::Italic => "font-style: normal",
+        }
+    }
+}
+
+/// The selection in an editing host, at some point in time.
+#[unrooted_must_root_lint::must_root]
+#[derive(JSTraceable, MallocSizeOf, PartialEq)]
+struct EditSelection {
+    start_node: Dom<Node>,
+    start_offset: u32,
+    end_node: Dom<Node>,
+    end_offset: u32,
+}
+
+impl EditSelection {
+    /// The selection of a document, if it is in an editing host.
+    #[allow(unrooted_must_root)]
+    fn new(host: &Element, selection: &Selection) -> Option<EditSelection> {
+        let host = host.upcast::<Node>();
+        let range = selection.range()?;
+        let start_node = range.StartContainer();
+        let end_node = range.EndContainer();
+        if !host.is_inclusive_ancestor_of(&start_node) || !host.is_inclusive_ancestor_of(&end_node)
+        {
+            return None;
+        }
+        Some(EditSelection {
+            start_node: Dom::from_ref(&*start_node),
+            start_offset: range.StartOffset(),
+            end_node: Dom::from_ref(&*end_node),
+            end_offset: range.EndOffset(),
+        })
+    }
+
+    fn select(&self, selection: &Selection) {
+        let _ = selection.SetBaseAndExtent(
+            &self.start_node,
+            self.start_offset,
+            &self.end_node,
+            self.end_offset,
+        );
+    }
+}
+
+/// A change made to the nodes of a document while editing, which can be reverted.
+#[unrooted_must_root_lint::must_root]
+#[derive(Clone, JSTraceable, MallocSizeOf)]
+enum EditOperation {
+    /// The code units of the data of a node from an offset were replaced.
+    Splice {
+        node: Dom<CharacterData>,
+        offset: u32,
+        removed: DOMString,
+        inserted_length: u32,
+    },
+    /// Nodes were inserted into and removed from the children of a node, before a child.
+    Children {
+        parent: Dom<Node>,
+        added: Vec<Dom<Node>>,
+        removed: Vec<Dom<Node>>,
+        next: Option<Dom<Node>>,
+    },
+    /// An attribute of an element was set or removed.
+    Attribute {
+        element: Dom<Element>,
+        name: LocalName,
+        namespace: Namespace,
+        old_value: Option<DOMString>,
+    },
+}
+
+impl EditOperation {
+    /// The splice that changed the data of a node from `old_data` to its current data.
+    #[allow(unrooted_must_root)]
+    fn splice(node: &CharacterData, old_data: &DOMString) -> Option<EditOperation> {
+        let is_high_surrogate = |unit: u16| unit >= 0xD800 && unit < 0xDC00;
+        let is_low_surrogate = |unit: u16| unit >= 0xDC00 && unit < 0xE000;
+        let old: Vec<u16> = old_data.encode_utf16().collect();
+        let new: Vec<u16> = node.data().encode_utf16().collect();
+        if old == new {
+            return None;
+        }
+        // Keep surrogate pairs whole, so that the removed code units are a string.
+        let mut prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+        if prefix > 0 && is_high_surrogate(old[prefix - 1]) {
+            prefix -= 1;
+        }
+        let mut suffix = old[prefix..]
+            .iter()
+            .rev()
+            .zip(new[prefix..].iter().rev())
+            .take_while(|(a, b)| a == b)
+            .count();
+        if suffix > 0 && is_low_surrogate(old[old.len() - suffix]) {
+            suffix -= 1;
+        }
+        Some(EditOperation::Splice {
+            node: Dom::from_ref(node),
+            offset: prefix as u32,
+            removed: DOMString::from(String::from_utf16_lossy(&old[prefix..old.len() - suffix])),
+            inserted_length: (new.len() - prefix - suffix) as u32,
+        })
+    }
+
+    /// Applies the inverse of this operation to the nodes it changed.
+    fn revert(&self) {
+        match *self {
+            EditOperation::Splice {
+                ref node,
+                offset,
+                ref removed,
+                inserted_length,
+            } => {
+                let _ = node.ReplaceData(offset, inserted_length, removed.clone());
+            },
+            EditOperation::Children {
+                ref parent,
+                ref added,
+                ref removed,
+                ref next,
+            } => {
+                for node in added {
+                    let _ = parent.RemoveChild(node);
+                }
+                for node in removed {
+                    let _ = parent.InsertBefore(node, next.as_deref());
+                }
+            },
+            EditOperation::Attribute {
+                ref element,
+                ref name,
+                ref namespace,
+                ref old_value,
+            } => match *old_value {
+                Some(ref old_value) => {
+                    let value = element.parse_attribute(namespace, name, old_value.clone());
+                    match element.get_attribute(namespace, name) {
+                        Some(attr) => attr.set_value(value, element),
+                        None => element.push_new_attribute(
+                            name.clone(),
+                            value,
+                            name.clone(),
+                            namespace.clone(),
+                            None,
+                        ),
+                    }
+                },
+                None => {
+                    element.remove_attribute(namespace, name);
+                },
+            },
+        }
+    }
+}
+
+/// An edit of the content of an editing host.
+#[unrooted_must_root_lint::must_root]
+#[derive(JSTraceable, MallocSizeOf)]
+struct EditTransaction {
+    host: Dom<Element>,
+    /// The changes the edit made, in the order it made them.
+    operations: Vec<EditOperation>,
+    selection_before: Option<EditSelection>,
+    selection_after: Option<EditSelection>,
+    /// Whether this edit is text being typed, which the text typed right after it is merged into.
+    typing: bool,
+}
+
+/// The edits of the content of the editing hosts of a document that can be undone and redone.
+#[unrooted_must_root_lint::must_root]
+#[derive(Default, JSTraceable, MallocSizeOf)]
+pub struct EditHistory {
+    undo_stack: Vec<EditTransaction>,
+    redo_stack: Vec<EditTransaction>,
+    /// The changes made so far by the edit being made, undone or redone.
+    recording: Option<Vec<EditOperation>>,
+    /// A node whose data is being changed by that edit, with its data before the change,
+    /// which is recorded as a splice once the change is made.
+    pending_data_change: Option<(Dom<CharacterData>, DOMString)>,
+}
+
+impl EditHistory {
+    /// Records a change of the nodes of the document, which is about to be made for character
+    /// data and attributes, and was just made for children. Changes made to an editing host by
+    /// anything but an edit clear the history, which couldn't be applied to it any more.
+    #[allow(unrooted_must_root)]
+    pub fn note_mutation(&mut self, target: &Node, mutation: &Mutation) {
+        if self.recording.is_none() {
+            let edited = self
+                .undo_stack
+                .iter()
+                .chain(&self.redo_stack)
+                .any(|transaction| {
+                    transaction
+                        .host
+                        .upcast::<Node>()
+                        .is_shadow_including_inclusive_ancestor_of(target)
+                });
+            if edited {
+                self.undo_stack.clear();
+                self.redo_stack.clear();
+            }
+            return;
+        }
+        self.record_pending_data_change();
+        let operation = match *mutation {
+            Mutation::CharacterData { ref old_value } => {
+                self.pending_data_change = target
+                    .downcast::<CharacterData>()
+                    .map(|node| (Dom::from_ref(node), old_value.clone()));
+                return;
+            },
+            Mutation::ChildList {
+                added,
+                removed,
+                next,
+                ..
+            } => {
+                let nodes = |nodes: Option<&[&Node]>| -> Vec<Dom<Node>> {
+                    nodes
+                        .unwrap_or(&[])
+                        .iter()
+                        .map(|node| Dom::from_ref(*node))
+                        .collect()
+                };
+                EditOperation::Children {
+                    parent: Dom::from_ref(target),
+                    added: nodes(added),
+                    removed: nodes(removed),
+                    next: next.map(Dom::from_ref),
+                }
+            },
+            Mutation::Attribute {
+                ref name,
+                ref namespace,
+                ref old_value,
+            } => match target.downcast::<Element>() {
+                Some(element) => EditOperation::Attribute {
+                    element: Dom::from_ref(element),
+                    name: name.clone(),
+                    namespace: namespace.clone(),
+                    old_value: old_value.clone(),
+                },
+                None => return,
+            },
+        };
+        if let Some(ref mut operations) = self.recording {
+            operations.push(operation);
+        }
+    }
+
+    #[allow(unrooted_must_root)]
+    fn record_pending_data_change(&mut self) {
+        let (node, old_data) = match self.pending_data_change.take() {
+            Some(change) => change,
+            None => return,
+        };
+        if let (Some(operation), Some(operations)) = (
+            EditOperation::splice(&node, &old_data),
+            self.recording.as_mut(),
+        ) {
+            operations.push(operation);
+        }
+    }
+
+    fn start_recording(&mut self) {
+        self.recording = Some(vec![]);
+    }
+
+    /// Stops recording the changes of an edit, and adds them to the history.
+    #[allow(unrooted_must_root)]
+    fn finish_edit(
+        &mut self,
+        host: &Element,
+        selection_before: Option<EditSelection>,
+        selection_after: Option<EditSelection>,
+        typing: bool,
+    ) {
+        self.record_pending_data_change();
+        let operations = match self.recording.take() {
+            Some(operations) => operations,
+            None => return,
+        };
+        if operations.is_empty() {
+            return;
+        }
+        self.redo_stack.clear();
+        if typing {
+            if let Some(last) = self.undo_stack.last_mut() {
+                // Merge text typed in a row, unless the caret was moved in between.
+                if last.typing && *last.host == *host && last.selection_after == selection_before {
+                    last.operations.extend(operations);
+                    last.selection_after = selection_after;
+                    return;
+                }
+            }
+        }
+        if self.undo_stack.len() == MAX_UNDO_TRANSACTIONS {
+            self.undo_stack.remove(0);
+        }
+        self.undo_stack.push(EditTransaction {
+            host: Dom::from_ref(host),
+            operations,
+            selection_before,
+            selection_after,
+            typing,
+        });
+    }
+
+    /// Stops recording the changes that reverted the last edit of the undo stack, or of the
+    /// redo stack, and moves it to the other stack as those changes, which revert it back.
+    fn finish_revert(&mut self, undo: bool) {
+        self.record_pending_data_change();
+        let operations = self.recording.take().unwrap_or_default();
+        let (from, to) = if undo {
+            (&mut self.undo_stack, &mut self.redo_stack)
+        } else {
+            (&mut self.redo_stack, &mut self.undo_stack)
+        };
+        if let Some(transaction) = from.pop() {
+            to.push(EditTransaction {
+                host: transaction.host,
+                operations,
+                selection_before: transaction.selection_after,
+                selection_after: transaction.selection_before,
+                typing: false,
+            });
+        }
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo_stack.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo_stack.is_empty()
+    }
+}
+
+/// The editing host the selection of a document is in, if the selection is editable.
+pub fn active_editing_host(document: &Document) -> Option<DomRoot<Element>> {
+    let range = document.selection().range()?;
+    let host = range.StartContainer().editing_host()?;
+    if range.EndContainer().editing_host().as_deref() != Some(&*host) {
+        return None;
+    }
+    Some(host)
+}
+
+/// <https://w3c.github.io/editing/docs/execCommand/#enabled>
+pub fn query_command_enabled(document: &Document, command: EditCommand) -> bool {
+    match command {
+        EditCommand::Undo => document.edit_history().borrow().can_undo(),
+        EditCommand::Redo => document.edit_history().borrow().can_redo(),
+        EditCommand::SelectAll => true,
+        _ => active_editing_host(document).is_some(),
+    }
+}
+
+/// Whether the inline style of a command is applied to all and to any of the selected text.
+fn inline_style_state(document: &Document, style: InlineStyle) -> (bool, bool) {
+    let range = match document.selection().range() {
+        Some(range) => range,
+        None => return (false, false),
+    };
+    if range.Collapsed() {
+        let applied = style.is_applied_to(&range.StartContainer());
+        return (applied, applied);
+    }
+    let texts = formattable_text(&range);
+    let all = texts.iter().all(|text| style.is_applied_to(text.upcast()));
+    let any = texts.iter().any(|text| style.is_applied_to(text.upcast()));
+    (all && !texts.is_empty(), any)
+}
+
+/// <https://w3c.github.io/editing/docs/execCommand/#state>
+pub fn query_command_state(document: &Document, command: EditCommand) -> bool {
+    match command.inline_style() {
+        Some(style) => inline_style_state(document, style).0,
+        None => false,
+    }
+}
+
+/// <https://w3c.github.io/editing/docs/execCommand/#indeterminate>
+pub fn query_command_indeterm(document: &Document, command: EditCommand) -> bool {
+    match command.inline_style() {
+        Some(style) => {
+            let (all, any) = inline_style_state(document, style);
+            any && !all
+        },
+        None => false,
+    }
+}
+
+/// <https://w3c.github.io/editing/docs/execCommand/#execcommand()>
+pub fn exec_command(document: &Document, command: EditCommand, value: DOMString) -> bool {
+    if !query_command_enabled(document, command) {
+        return false;
+    }
+    execute(document, command, value, false)
+}
+
+/// Runs a command, firing `input` at the editing host whose content it changed.
+fn execute(document: &Document, command: EditCommand, value: DOMString, typing: bool) -> bool {
+    let selection = document.selection();
+    let host = match command {
+        EditCommand::SelectAll => {
+            select_all(document, &selection);
+            return true;
+        },
+        EditCommand::Undo | EditCommand::Redo => {
+            match step_through_history(document, command == EditCommand::Undo) {
+                Some(host) => host,
+                None => return false,
+            }
+        },
+        _ => {
+            let host = match active_editing_host(document) {
+                Some(host) => host,
+                None => return false,
+            };
+            let edited = record_edit(document, &host, typing, |selection| match command {
+                EditCommand::Bold | EditCommand::Italic => {
+                    toggle_inline_style(selection, &host, command.inline_style().unwrap())
+                },
+                EditCommand::CreateLink => create_link(selection, &host, &value),
+                EditCommand::Delete => delete_backward(selection, &host),
+                EditCommand::ForwardDelete => delete_forward(selection, &host),
+                EditCommand::InsertLineBreak => insert_line_break(selection),
+                EditCommand::InsertParagraph => insert_paragraph(selection, &host),
+                EditCommand::InsertText => insert_text(selection, &value),
+                EditCommand::Redo | EditCommand::SelectAll | EditCommand::Undo => false,
+            });
+            if !edited {
+                return false;
+            }
+            host
+        },
+    };
+    let data = match command {
+        EditCommand::InsertText => Some(value),
+        _ => None,
+    };
+    if let Some(input_type) = command.input_type() {
+        fire_input_event(&host, "input", input_type, data, false);
+    }
+    true
+}
+
+/// Edits the content of an editing host, recording the edit in the history of the document.
+#[allow(unrooted_must_root)]
+fn record_edit<F>(document: &Document, host: &Element, typing: bool, edit: F) -> bool
+where
+    F: FnOnce(&Selection) -> bool,
+{
+    let selection = document.selection();
+    let selection_before = EditSelection::new(host, &selection);
+    document.edit_history().borrow_mut().start_recording();
+    let edited = edit(&selection);
+    let selection_after = EditSelection::new(host, &selection);
+    // What was changed is recorded even if the edit gave up, so that the history still
+    // applies to the content of the editing host.
+    document.edit_history().borrow_mut().finish_edit(
+        host,
+        selection_before,
+        selection_after,
+        typing,
+    );
+    edited
+}
+
+/// Undoes or redoes the last edit, returning the editing host whose content changed.
+#[allow(unrooted_must_root)]
+fn step_through_history(document: &Document, undo: bool) -> Option<DomRoot<Element>> {
+    // The transaction stays in the history while it is reverted, which keeps the nodes
+    // it refers to alive.
+    let (host, operations) = {
+        let history = document.edit_history().borrow();
+        let transaction = if undo {
+            history.undo_stack.last()?
+        } else {
+            history.redo_stack.last()?
+        };
+        (
+            DomRoot::from_ref(&*transaction.host),
+            transaction.operations.clone(),
+        )
+    };
+    document.edit_history().borrow_mut().start_recording();
+    for operation in operations.iter().rev() {
+        operation.revert();
+    }
+    document.edit_history().borrow_mut().finish_revert(undo);
+
+    let history = document.edit_history().borrow();
+    let transaction = if undo {
+        history.redo_stack.last()
+    } else {
+        history.undo_stack.last()
+    };
+    if let Some(selection) =
+        transaction.and_then(|transaction| transaction.selection_after.as_ref())
+    {
+        selection.select(&document.selection());
+    }
+    Some(host)
+}
+
+/// Fires an input event at an editing host, returning whether it was not canceled.
+fn fire_input_event(
+    host: &Element,
+    type_: &str,
+    input_type: &str,
+    data: Option<DOMString>,
+    cancelable: bool,
+) -> bool {
+    let window = window_from_node(host);
+    let event = InputEvent::new(
+        &window,
+        DOMString::from(type_),
+        true,
+        cancelable,
+        Some(&window),
+        0,
+        data,
+        false,
+        DOMString::from(input_type),
+    );
+    event.upcast::<Event>().fire(host.upcast()) == EventStatus::NotCanceled
+}
+
+/// Edits the content of the editing host the selection is in, in response to a key being
+/// pressed, and returns whether the key was handled.
+pub fn handle_key_event(document: &Document, event: &KeyboardEvent) -> bool {
+    if event.state != KeyState::Down || event.is_composing {
+        return false;
+    }
+    let host = match active_editing_host(document) {
+        Some(host) => host,
+        None => return false,
+    };
+    let extend = event.modifiers.contains(Modifiers::SHIFT);
+    let mut modifiers = event.modifiers;
+    modifiers.remove(Modifiers::SHIFT);
+
+    // https://w3c.github.io/input-events/#event-order-when-text-is-inserted
+    let user_edit = |command: EditCommand, value: DOMString| {
+        let data = match command {
+            EditCommand::InsertText => Some(value.clone()),
+            _ => None,
+        };
+        let input_type = command.input_type().unwrap();
+        if fire_input_event(&host, "beforeinput", input_type, data, true) {
+            execute(document, command, value, command == EditCommand::InsertText);
+        }
+        true
+    };
+    let move_caret = |direction: Direction| {
+        let selection = document.selection();
+        move_selection_focus(&selection, &host, direction, extend)
+    };
+    ShortcutMatcher::new(KeyState::Down, event.key.clone(), modifiers)
+        .shortcut(CMD_OR_CONTROL, 'B', || {
+            user_edit(EditCommand::Bold, DOMString::new())
+        })
+        .shortcut(CMD_OR_CONTROL, 'I', || {
+            user_edit(EditCommand::Italic, DOMString::new())
+        })
+        .shortcut(CMD_OR_CONTROL, 'Z', || {
+            let command = if extend {
+                EditCommand::Redo
+            } else {
+                EditCommand::Undo
+            };
+            query_command_enabled(document, command) && user_edit(command, DOMString::new())
+        })
+        .shortcut(CMD_OR_CONTROL, 'Y', || {
+            query_command_enabled(document, EditCommand::Redo) &&
+                user_edit(EditCommand::Redo, DOMString::new())
+        })
+        .shortcut(CMD_OR_CONTROL, 'A', || {
+            select_all(document, &document.selection());
+            true
+        })
+        .shortcut(Modifiers::empty(), Key::Backspace, || {
+            user_edit(EditCommand::Delete, DOMString::new())
+        })
+        .shortcut(Modifiers::empty(), Key::Delete, || {
+            user_edit(EditCommand::ForwardDelete, DOMString::new())
+        })
+        .shortcut(Modifiers::empty(), Key::Enter, || {
+            let command = if extend {
+                EditCommand::InsertLineBreak
+            } else {
+                EditCommand::InsertParagraph
+            };
+            user_edit(command, DOMString::new())
+        })
+        .shortcut(Modifiers::empty(), Key::ArrowLeft, || {
+            move_caret(Direction::Backward)
+        })
+        .shortcut(Modifiers::empty(), Key::ArrowRight, || {
+            move_caret(Direction::Forward)
+        })
+        .shortcut(Modifiers::empty(), Key::Home, || {
+            move_caret(Direction::ToStart)
+        })
+        .shortcut(Modifiers::empty(), Key::End, || {
+            move_caret(Direction::ToEnd)
+        })
+        .otherwise(|| match event.key {
+            Key::Character(ref c)
+                if !modifiers.intersects(Modifiers::CONTROL | Modifiers::META) =>
+            {
+                user_edit(EditCommand::InsertText, DOMString::from(c.as_str()))
+            },
+            _ => false,
+        })
+        .unwrap_or(false)
+}
+
+/// Deletes what is selected in the editing host the selection is in, once it has been cut to
+/// the clipboard, and returns whether anything was deleted.
+pub fn delete_by_cut(document: &Document) -> bool {
+    edit_with_clipboard(document, "deleteByCut", delete_selected_content)
+}
+
+/// Inserts text pasted from the clipboard at the caret of the editing host the selection is
+/// in, and returns whether it was inserted.
+pub fn insert_from_paste(document: &Document, text: &str) -> bool {
+    edit_with_clipboard(document, "insertFromPaste", |selection| {
+        insert_lines(selection, text)
+    })
+}
+
+/// Edits the content of the active editing host for a clipboard action, firing `beforeinput`
+/// and `input` around the edit.
+fn edit_with_clipboard<F>(document: &Document, input_type: &str, edit: F) -> bool
+where
+    F: FnOnce(&Selection) -> bool,
+{
+    let host = match active_editing_host(document) {
+        Some(host) => host,
+        None => return false,
+    };
+    if !fire_input_event(&host, "beforeinput", input_type, None, true) {
+        return false;
+    }
+    if !record_edit(document, &host, false, edit) {
+        return false;
+    }
+    fire_input_event(&host, "input", input_type, None, false);
+    true
+}
+
+/// Inserts text at the caret, with a line break for each of its newlines.
+fn insert_lines(selection: &Selection, text: &str) -> bool {
+    delete_selected_content(selection);
+    let mut inserted = false;
+    for (index, line) in text.split('\n').enumerate() {
+        if index > 0 {
+            if !insert_line_break(selection) {
+                break;
+            }
+            inserted = true;
+        }
+        let line = line.trim_end_matches('\r');
+        if !line.is_empty() {
+            if !insert_text(selection, &DOMString::from(line)) {
+                break;
+            }
+            inserted = true;
+        }
+    }
+    inserted
+}
+
+/// Selects the content of the editing host the selection is in, or else of the document.
+fn select_all(document: &Document, selection: &Selection) {
+    let root = match active_editing_host(document) {
+        Some(host) => DomRoot::upcast::<Node>(host),
+        None => match document.GetBody().or_else(|| document.GetDocumentElement()) {
+            Some(root) => DomRoot::upcast(root),
+            None => return,
+        },
+    };
+    let _ = selection.SelectAllChildren(&root);
+}
+
+/// Whether a node is an element that is laid out as a block.
+fn is_block(node: &Node) -> bool {
+    node.downcast::<Element>()
+        .and_then(|element| element.style())
+        .map_or(false, |style| {
+            style.get_box().clone_display().outside() == DisplayOutside::Block
+        })
+}
+
+/// Whether a node is an element without content that takes up room in a line.
+fn is_void_content(node: &Node) -> bool {
+    node.is::<HTMLBRElement>() || node.is::<HTMLImageElement>()
+}
+
+fn is_whitespace(unit: u16) -> bool {
+    unit == ' ' as u16 || unit == '\t' as u16 || unit == '\n' as u16 || unit == 0xa0
+}
+
+fn utf16_units(text: &Text) -> Vec<u16> {
+    text.upcast::<CharacterData>()
+        .data()
+        .encode_utf16()
+        .collect()
+}
+
+/// The offset of the start of the character before an offset in a text node.
+fn previous_character(text: &Text, offset: u32) -> u32 {
+    let units = utf16_units(text);
+    let mut previous = offset.saturating_sub(1);
+    if previous > 0 &&
+        (0xdc00..0xe000).contains(&units[previous as usize]) &&
+        (0xd800..0xdc00).contains(&units[previous as usize - 1])
+    {
+        previous -= 1;
+    }
+    previous
+}
+
+/// The offset of the end of the character after an offset in a text node.
+fn next_character(text: &Text, offset: u32) -> u32 {
+    let units = utf16_units(text);
+    let mut next = offset + 1;
+    if (next as usize) < units.len() &&
+        (0xd800..0xdc00).contains(&units[offset as usize]) &&
+        (0xdc00..0xe000).contains(&units[next as usize])
+    {
+        next += 1;
+    }
+    next.min(units.len() as u32)
+}
+
+/// The text of a range that editing commands format: its text nodes, split so that they are
+/// wholly selected, and without those that are only whitespace.
+fn formattable_text(range: &Range) -> Vec<DomRoot<Text>> {
+    selected_text(range)
+        .into_iter()
+        .map(|(text, _)| text)
+        .filter(|text| {
+            !utf16_units(text)
+                .into_iter()
+                .all(|unit| is_whitespace(unit))
+        })
+        .collect()
+}
+
+/// Splits the text nodes at the boundary points of the selection, so that the text nodes in it
+/// are wholly selected.
+fn split_selected_text(range: &Range) {
+    let end_node = range.EndContainer();
+    let end_offset = range.EndOffset();
+    if let Some(text) = end_node.downcast::<Text>() {
+        if end_offset > 0 && end_offset < end_node.len() {
+            let _ = text.SplitText(end_offset);
+        }
+    }
+    let start_node = range.StartContainer();
+    let start_offset = range.StartOffset();
+    if let Some(text) = start_node.downcast::<Text>() {
+        if start_offset > 0 && start_offset < start_node.len() {
+            if let Ok(new_text) = text.SplitText(start_offset) {
+                let _ = range.SetStart(new_text.upcast(), 0);
+            }
+        }
+    }
+}
+
+/// Selects a run of text nodes, from the start of the first to the end of the last.
+fn select_text(selection: &Selection, texts: &[DomRoot<Text>]) {
+    if let (Some(first), Some(last)) = (texts.first(), texts.last()) {
+        let last = last.upcast::<Node>();
+        let _ = selection.SetBaseAndExtent(first.upcast(), 0, last, last.len());
+    }
+}
+
+/// Puts a node in a new element, or in the element before it when that one can be reused.
+fn wrap<F>(node: &Node, wrapper: DomRoot<Element>, can_merge_with: F)
+where
+    F: Fn(&Element) -> bool,
+{
+    if let Some(previous) = node
+        .GetPreviousSibling()
+        .and_then(DomRoot::downcast::<Element>)
+    {
+        if can_merge_with(&previous) {
+            let _ = previous.upcast::<Node>().AppendChild(node);
+            return;
+        }
+    }
+    let parent = match node.GetParentNode() {
+        Some(parent) => parent,
+        None => return,
+    };
+    let _ = parent.InsertBefore(wrapper.upcast(), Some(node));
+    let _ = wrapper.upcast::<Node>().AppendChild(node);
+}
+
+/// Replaces an element with its children.
+fn unwrap(element: &Node) {
+    let parent = match element.GetParentNode() {
+        Some(parent) => parent,
+        None => return,
+    };
+    for child in element.children().collect::<Vec<_>>() {
+        let _ = parent.InsertBefore(&child, Some(element));
+    }
+    element.remove_self();
+}
+
+/// <https://w3c.github.io/editing/docs/execCommand/#the-bold-command>
+/// <https://w3c.github.io/editing/docs/execCommand/#the-italic-command>
+fn toggle_inline_style(selection: &Selection, host: &Element, style: InlineStyle) -> bool {
+    let range = match selection.range() {
+        Some(range) => range,
+        None => return false,
+    };
+    if range.Collapsed() {
+        return false;
+    }
+    split_selected_text(&range);
+    let texts = formattable_text(&range);
+    if texts.is_empty() {
+        return false;
+    }
+    let document = host.upcast::<Node>().owner_doc();
+    let host = host.upcast::<Node>();
+    let applied = texts.iter().all(|text| style.is_applied_to(text.upcast()));
+
+    for text in &texts {
+        let node = text.upcast::<Node>();
+        if !applied {
+            if style.is_applied_to(node) {
+                continue;
+            }
+            let wrapper = HTMLElement::new(style.local_name(), None, &document);
+            wrap(node, DomRoot::upcast(wrapper), |element| {
+                *element.local_name() == style.local_name() && element.attrs().is_empty()
+            });
+            continue;
+        }
+
+        // Unwrap the element that applies the style when all of its text is selected, and
+        // otherwise override the style for the selected text.
+        let applying_element = node
+            .ancestors()
+            .take_while(|ancestor| !ancestor.is_inclusive_ancestor_of(host))
+            .find(|ancestor| {
+                ancestor
+                    .downcast::<Element>()
+                    .map_or(false, |element| style.is_applied_by(element))
+            });
+        if let Some(element) = applying_element {
+            let wholly_selected = element
+                .traverse_preorder(ShadowIncluding::No)
+                .filter_map(DomRoot::downcast::<Text>)
+                .all(|descendant| {
+                    texts.iter().any(|text| *text == descendant) ||
+                        utf16_units(&descendant)
+                            .into_iter()
+                            .all(|unit| is_whitespace(unit))
+                });
+            if wholly_selected {
+                unwrap(&element);
+                continue;
+            }
+        }
+        let span = HTMLSpanElement::new(local_name!("span"), None, &document);
+        span.upcast::<Element>().set_string_attribute(
+            &local_name!("style"),
+            DOMString::from(style.removal_declaration()),
+        );
+        wrap(node, DomRoot::upcast(span), |_| false);
+    }
+    select_text(selection, &texts);
+    true
+}
+
+/// <https://w3c.github.io/editing/docs/execCommand/#the-createlink-command>
+fn create_link(selection: &Selection, host: &Element, url: &DOMString) -> bool {
+    if url.is_empty() {
+        return false;
+    }
+    let range = match selection.range() {
+        Some(range) => range,
+        None => return false,
+    };
+    let document = range.StartContainer().owner_doc();
+    let new_link = || {
+        let link = HTMLAnchorElement::new(local_name!("a"), None, &document);
+        link.upcast::<Element>()
+            .set_string_attribute(&local_name!("href"), url.clone());
+        link
+    };
+
+    // Without a selection to make a link of, insert the URL as a link.
+    if range.Collapsed() {
+        let link = new_link();
+        let text = Text::new(url.clone(), &document);
+        let _ = link.upcast::<Node>().AppendChild(text.upcast());
+        if !insert_node(selection, link.upcast()) {
+            return false;
+        }
+        select_text(selection, &[text]);
+        return true;
+    }
+
+    split_selected_text(&range);
+    let texts = formattable_text(&range);
+    if texts.is_empty() {
+        return false;
+    }
+    for text in &texts {
+        let node = text.upcast::<Node>();
+        let existing_link = node
+            .ancestors()
+            .take_while(|ancestor| !ancestor.is_inclusive_ancestor_of(host.upcast()))
+            .find_map(DomRoot::downcast::<HTMLAnchorElement>);
+        if let Some(link) = existing_link {
+            link.upcast::<Element>()
+                .set_string_attribute(&local_name!("href"), url.clone());
+            continue;
+        }
+        wrap(node, DomRoot::upcast(new_link()), |element| {
+            element.is::<HTMLAnchorElement>() &&
+                element.get_string_attribute(&local_name!("href")) == *url
+        });
+    }
+    select_text(selection, &texts);
+    true
+}
+
+/// Deletes the content of the selection, if it is not collapsed.
+fn delete_selected_content(selection: &Selection) -> bool {
+    match selection.range() {
+        Some(ref range) if !range.Collapsed() => range.DeleteContents().is_ok(),
+        _ => false,
+    }
+}
+
+/// The boundary point a caret at a boundary point is drawn at: in the text next to it, if any.
+fn caret_position(node: &Node, offset: u32) -> (DomRoot<Node>, u32) {
+    if node.is::<Text>() {
+        return (DomRoot::from_ref(node), offset);
+    }
+    if offset > 0 {
+        if let Some(text) = node
+            .children()
+            .nth(offset as usize - 1)
+            .filter(|child| child.is::<Text>())
+        {
+            let length = text.len();
+            return (text, length);
+        }
+    }
+    if let Some(text) = node
+        .children()
+        .nth(offset as usize)
+        .filter(|child| child.is::<Text>())
+    {
+        return (text, 0);
+    }
+    (DomRoot::from_ref(node), offset)
+}
+
+fn collapse_to(selection: &Selection, node: &Node, offset: u32) {
+    let (node, offset) = caret_position(node, offset);
+    let _ = selection.Collapse(Some(&node), offset);
+}
+
+/// Inserts a node at the caret, splitting the text the caret is in, and moves the caret after it.
+fn insert_node(selection: &Selection, new_node: &Node) -> bool {
+    delete_selected_content(selection);
+    let range = match selection.range() {
+        Some(range) => range,
+        None => return false,
+    };
+    let (node, offset) = (range.StartContainer(), range.StartOffset());
+    let (parent, reference) = match node.downcast::<Text>() {
+        Some(text) => {
+            let parent = match node.GetParentNode() {
+                Some(parent) => parent,
+                None => return false,
+            };
+            if offset == 0 {
+                (parent, Some(DomRoot::from_ref(&*node)))
+            } else if offset == node.len() {
+                (parent, node.GetNextSibling())
+            } else {
+                match text.SplitText(offset) {
+                    Ok(new_text) => (parent, Some(DomRoot::upcast(new_text))),
+                    Err(_) => return false,
+                }
+            }
+        },
+        None => {
+            let reference = node.children().nth(offset as usize);
+            (DomRoot::from_ref(&*node), reference)
+        },
+    };
+    if parent.InsertBefore(new_node, reference.as_deref()).is_err() {
+        return false;
+    }
+    collapse_to(selection, &parent, new_node.index() + 1);
+    true
+}
+
+/// Inserts text at the caret, in place of what is selected.
+/// <https://w3c.github.io/editing/docs/execCommand/#the-inserttext-command>
+fn insert_text(selection: &Selection, text: &DOMString) -> bool {
+    if text.is_empty() {
+        return delete_selected_content(selection);
+    }
+    delete_selected_content(selection);
+    let range = match selection.range() {
+        Some(range) => range,
+        None => return false,
+    };
+    let (node, offset) = caret_position(&range.StartContainer(), range.StartOffset());
+    let (text_node, offset) = match DomRoot::downcast::<Text>(node.clone()) {
+        Some(text_node) => (text_node, offset),
+        None => {
+            if !node.is::<Element>() {
+                return false;
+            }
+            let document = node.owner_doc();
+            let text_node = Text::new(DOMString::new(), &document);
+            let reference = node.children().nth(offset as usize);
+            if node
+                .InsertBefore(text_node.upcast(), reference.as_deref())
+                .is_err()
+            {
+                return false;
+            }
+            (text_node, 0)
+        },
+    };
+    let offset = insert_into_text(&text_node, offset, text);
+    let _ = selection.Collapse(Some(text_node.upcast()), offset);
+    true
+}
+
+/// Inserts text in a text node, using no-break spaces where ordinary spaces would collapse
+/// away, and returns the offset after what was inserted.
+fn insert_into_text(text: &Text, offset: u32, data: &str) -> u32 {
+    let units = utf16_units(text);
+    let at_end = offset as usize == units.len();
+    let mut previous_is_whitespace = offset == 0 || is_whitespace(units[offset as usize - 1]);
+    let characters: Vec<char> = data.chars().collect();
+    let mut inserted = String::new();
+    for (index, &character) in characters.iter().enumerate() {
+        let is_last = index + 1 == characters.len();
+        let character = if character == ' ' && (previous_is_whitespace || (is_last && at_end)) {
+            '\u{a0}'
+        } else {
+            character
+        };
+        previous_is_whitespace = character == ' ' || character == '\u{a0}';
+        inserted.push(character);
+    }
+
+    // A no-break space right before the insertion point can be an ordinary space again, now
+    // that it is followed by other text.
+    let (mut start, mut count) = (offset, 0);
+    if offset > 1 &&
+        units[offset as usize - 1] == 0xa0 &&
+        !is_whitespace(units[offset as usize - 2]) &&
+        !inserted.starts_with('\u{a0}')
+    {
+        start -= 1;
+        count = 1;
+        inserted.insert(0, ' ');
+    }
+    let inserted = DOMString::from(inserted);
+    let length = inserted.encode_utf16().count() as u32;
+    let _ = text
+        .upcast::<CharacterData>()
+        .ReplaceData(start, count, inserted);
+    start + length
+}
+
+/// Removes the characters between two offsets of a text node, removing the text node when no
+/// characters are left, and collapses the selection where they were.
+fn delete_characters(selection: &Selection, text: &Text, start: u32, end: u32) {
+    let node = text.upcast::<Node>();
+    let _ = text
+        .upcast::<CharacterData>()
+        .DeleteData(start, end - start);
+    if node.len() > 0 {
+        let _ = selection.Collapse(Some(node), start);
+        return;
+    }
+    let parent = match node.GetParentNode() {
+        Some(parent) => parent,
+        None => return,
+    };
+    let index = node.index();
+    node.remove_self();
+    collapse_to(selection, &parent, index);
+}
+
+/// Removes a line break or an image, and collapses the selection where it was.
+fn delete_node(selection: &Selection, node: &Node) {
+    let parent = match node.GetParentNode() {
+        Some(parent) => parent,
+        None => return,
+    };
+    let index = node.index();
+    node.remove_self();
+    collapse_to(selection, &parent, index);
+}
+
+/// Merges a block with what precedes it, making its first line part of the line before it.
+fn merge_with_previous_block(selection: &Selection, block: &Node) -> bool {
+    let previous = block.preceding_siblings().find(|sibling| {
+        sibling.downcast::<Text>().map_or(true, |text| {
+            !utf16_units(text)
+                .into_iter()
+                .all(|unit| is_whitespace(unit))
+        })
+    });
+    let previous = match previous {
+        Some(previous) => previous,
+        None => return false,
+    };
+    if !is_block(&previous) {
+        // The block follows inline content, which its content joins.
+        let parent = match block.GetParentNode() {
+            Some(parent) => parent,
+            None => return false,
+        };
+        let index = block.index();
+        unwrap(block);
+        collapse_to(selection, &parent, index);
+        return true;
+    }
+
+    // Drop the line break that kept an empty block from collapsing.
+    if let Some(last_child) = previous.GetLastChild() {
+        if last_child.is::<HTMLBRElement>() {
+            last_child.remove_self();
+        }
+    }
+    let offset = previous.children_count();
+    for child in block.children().collect::<Vec<_>>() {
+        let _ = previous.AppendChild(&child);
+    }
+    block.remove_self();
+    collapse_to(selection, &previous, offset);
+    true
+}
+
+/// The nodes before a boundary point inside an editing host, in reverse tree order.
+fn nodes_before(host: &Node, node: &Node, offset: u32) -> Box<dyn Iterator<Item = DomRoot<Node>>> {
+    let child_before = if offset > 0 && !node.is::<Text>() {
+        node.children().nth(offset as usize - 1)
+    } else {
+        None
+    };
+    match child_before {
+        Some(child) => {
+            // Start from the last of the descendants of the child before the boundary point.
+            let last = child.descending_last_children().last().unwrap_or(child);
+            Box::new(iter::once(last.clone()).chain(last.preceding_nodes(host)))
+        },
+        None => Box::new(node.preceding_nodes(host)),
+    }
+}
+
+/// The nodes after a boundary point inside an editing host, in tree order.
+fn nodes_after(host: &Node, node: &Node, offset: u32) -> Box<dyn Iterator<Item = DomRoot<Node>>> {
+    let child_after = if !node.is::<Text>() {
+        node.children().nth(offset as usize)
+    } else {
+        None
+    };
+    match child_after {
+        Some(child) => Box::new(iter::once(child.clone()).chain(child.following_nodes(host))),
+        None => {
+            // Skip the descendants of an element the boundary point is at the end of.
+            let last = node
+                .descending_last_children()
+                .last()
+                .unwrap_or(DomRoot::from_ref(node));
+            Box::new(last.following_nodes(host))
+        },
+    }
+}
+
+/// <https://w3c.github.io/editing/docs/execCommand/#the-delete-command>
+fn delete_backward(selection: &Selection, host: &Element) -> bool {
+    if delete_selected_content(selection) {
+        return true;
+    }
+    let range = match selection.range() {
+        Some(range) => range,
+        None => return false,
+    };
+    let (node, offset) = (range.StartContainer(), range.StartOffset());
+    if let Some(text) = node.downcast::<Text>() {
+        if offset > 0 {
+            delete_characters(selection, text, previous_character(text, offset), offset);
+            return true;
+        }
+    }
+    let host = host.upcast::<Node>();
+    for candidate in nodes_before(host, &node, offset) {
+        if *candidate == *host {
+            break;
+        }
+        if candidate.is_inclusive_ancestor_of(&node) {
+            // The caret is at the start of this element.
+            if is_block(&candidate) {
+                return merge_with_previous_block(selection, &candidate);
+            }
+            continue;
+        }
+        if let Some(text) = candidate.downcast::<Text>() {
+            let length = candidate.len();
+            if length > 0 {
+                delete_characters(selection, text, previous_character(text, length), length);
+                return true;
+            }
+        } else if is_void_content(&candidate) {
+            delete_node(selection, &candidate);
+            return true;
+        }
+    }
+    false
+}
+
+/// <https://w3c.github.io/editing/docs/execCommand/#the-forwarddelete-command>
+fn delete_forward(selection: &Selection, host: &Element) -> bool {
+    if delete_selected_content(selection) {
+        return true;
+    }
+    let range = match selection.range() {
+        Some(range) => range,
+        None => return false,
+    };
+    let (node, offset) = (range.StartContainer(), range.StartOffset());
+    if let Some(text) = node.downcast::<Text>() {
+        if offset < node.len() {
+            delete_characters(selection, text, offset, next_character(text, offset));
+            return true;
+        }
+    }
+    for candidate in nodes_after(host.upcast(), &node, offset) {
+        if is_block(&candidate) {
+            // The caret is at the end of the block before this one.
+            return merge_with_previous_block(selection, &candidate);
+        }
+        if let Some(text) = candidate.downcast::<Text>() {
+            if candidate.len() > 0 {
+                delete_characters(selection, text, 0, next_character(text, 0));
+                return true;
+            }
+        } else if is_void_content(&candidate) {
+            delete_node(selection, &candidate);
+            return true;
+        }
+    }
+    false
+}
+
+/// <https://w3c.github.io/editing/docs/execCommand/#the-insertlinebreak-command>
+fn insert_line_break(selection: &Selection) -> bool {
+    let document = match selection.range() {
+        Some(range) => range.StartContainer().owner_doc(),
+        None => return false,
+    };
+    let line_break = HTMLBRElement::new(local_name!("br"), None, &document);
+    if !insert_node(selection, line_break.upcast()) {
+        return false;
+    }
+    // A line break at the end of a block does not start a line on its own.
+    let line_break = line_break.upcast::<Node>();
+    if line_break.GetNextSibling().is_none() {
+        let extra_line_break = HTMLBRElement::new(local_name!("br"), None, &document);
+        if let Some(parent) = line_break.GetParentNode() {
+            let _ = parent.AppendChild(extra_line_break.upcast());
+        }
+    }
+    true
+}
+
+/// <https://w3c.github.io/editing/docs/execCommand/#the-insertparagraph-command>
+fn insert_paragraph(selection: &Selection, host: &Element) -> bool {
+    delete_selected_content(selection);
+    let range = match selection.range() {
+        Some(range) => range,
+        None => return false,
+    };
+    let (node, offset) = (range.StartContainer(), range.StartOffset());
+    let host = host.upcast::<Node>();
+    let block = node
+        .inclusive_ancestors(ShadowIncluding::No)
+        .take_while(|ancestor| *ancestor != *host)
+        .find(|ancestor| is_block(ancestor));
+    let block = match block {
+        Some(block) => block,
+        // Without a block to split, the best that can be done is a new line.
+        None => return insert_line_break(selection),
+    };
+    let parent = match block.GetParentNode() {
+        Some(parent) => parent,
+        None => return false,
+    };
+
+    // Move what follows the caret in the block to a copy of the block after it.
+    let document = block.owner_doc();
+    let rest = Range::new(&document, &node, offset, &block, block.children_count());
+    let content = match rest.ExtractContents() {
+        Ok(content) => content,
+        Err(_) => return false,
+    };
+    let new_block = Node::clone(&block, None, CloneChildrenFlag::DoNotCloneChildren);
+    let _ = new_block.AppendChild(content.upcast());
+    let next_sibling = block.GetNextSibling();
+    if parent
+        .InsertBefore(&new_block, next_sibling.as_deref())
+        .is_err()
+    {
+        return false;
+    }
+
+    // Keep empty blocks from collapsing.
+    for block in &[&block, &new_block] {
+        let is_empty = !block
+            .traverse_preorder(ShadowIncluding::No)
+            .any(|descendant| {
+                (descendant.is::<Text>() && descendant.len() > 0) || is_void_content(&descendant)
+            });
+        if is_empty {
+            let line_break = HTMLBRElement::new(local_name!("br"), None, &document);
+            let _ = block.AppendChild(line_break.upcast());
+        }
+    }
+    collapse_to(selection, &new_block, 0);
+    true
+}
+
+#[derive(Clone, Copy)]
+enum Direction {
+    Backward,
+    Forward,
+    ToStart,
+    ToEnd,
+}
+
+/// The boundary point one character before or after a boundary point in an editing host.
+fn adjacent_position(
+    host: &Node,
+    node: &Node,
+    offset: u32,
+    forward: bool,
+) -> Option<(DomRoot<Node>, u32)> {
+    if let Some(text) = node.downcast::<Text>() {
+        if !forward && offset > 0 {
+            return Some((DomRoot::from_ref(node), previous_character(text, offset)));
+        }
+        if forward && offset < node.len() {
+            return Some((DomRoot::from_ref(node), next_character(text, offset)));
+        }
+    }
+
+    // Moving to another line, to the start or end of it, is moving by one character.
+    let mut changed_line = false;
+    let candidates = if forward {
+        nodes_after(host, node, offset)
+    } else {
+        nodes_before(host, node, offset)
+    };
+    for candidate in candidates {
+        if *candidate == *host {
+            break;
+        }
+        if let Some(text) = candidate.downcast::<Text>() {
+            let length = candidate.len();
+            if length == 0 {
+                continue;
+            }
+            let offset = match (forward, changed_line) {
+                (true, true) => 0,
+                (true, false) => next_character(text, 0),
+                (false, true) => length,
+                (false, false) => previous_character(text, length),
+            };
+            return Some((candidate, offset));
+        }
+        if is_void_content(&candidate) {
+            let parent = candidate.GetParentNode()?;
+            let index = candidate.index();
+            return Some((parent, if forward { index + 1 } else { index }));
+        }
+        if is_block(&candidate) {
+            changed_line = true;
+        }
+    }
+    None
+}
+
+/// Moves the focus of the selection, collapsing the selection unless it is being extended.
+fn move_selection_focus(
+    selection: &Selection,
+    host: &Element,
+    direction: Direction,
+    extend: bool,
+) -> bool {
+    let range = match selection.range() {
+        Some(range) => range,
+        None => return false,
+    };
+    let host = host.upcast::<Node>();
+
+    // Without extending it, moving by a character collapses a selection to one of its ends.
+    if !extend && !range.Collapsed() {
+        match direction {
+            Direction::Backward => {
+                collapse_to(selection, &range.StartContainer(), range.StartOffset());
+                return true;
+            },
+            Direction::Forward => {
+                collapse_to(selection, &range.EndContainer(), range.EndOffset());
+                return true;
+            },
+            Direction::ToStart | Direction::ToEnd => {},
+        }
+    }
+
+    let (node, offset) = match (selection.GetFocusNode(), selection.FocusOffset()) {
+        (Some(node), offset) => (node, offset),
+        (None, _) => return false,
+    };
+    let (node, offset) = match direction {
+        Direction::Backward | Direction::Forward => {
+            let forward = match direction {
+                Direction::Forward => true,
+                _ => false,
+            };
+            match adjacent_position(host, &node, offset, forward) {
+                Some(position) => position,
+                None => return true,
+            }
+        },
+        Direction::ToStart => host
+            .traverse_preorder(ShadowIncluding::No)
+            .find(|node| node.is::<Text>() && node.len() > 0)
+            .map_or((DomRoot::from_ref(host), 0), |text| (text, 0)),
+        Direction::ToEnd => host
+            .traverse_preorder(ShadowIncluding::No)
+            .filter(|node| node.is::<Text>() && node.len() > 0)
+            .last()
+            .map_or((DomRoot::from_ref(host), host.children_count()), |text| {
+                let length = text.len();
+                (text, length)
+            }),
+    };
+    if extend {
+        let _ = selection.Extend(&node, offset);
+    } else {
+        collapse_to(selection, &node, offset);
+    }
+    true
+}
+
+/// Puts the caret at the start of an editing host that got focused, unless the selection is
+/// in it already.
+pub fn editing_host_focused(document: &Document, host: &Element) {
+    if active_editing_host(document).as_deref() == Some(host) {
+        return;
+    }
+    collapse_to(&document.selection(), host.upcast(), 0);
+}
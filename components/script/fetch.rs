@@ -2,6 +2,8 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 
+use crate::body::{read_body_then_fetch, BodyOperations};
+use crate::dom::abortsignal::AbortSignal;
 use crate::dom::bindings::codegen::Bindings::RequestBinding::RequestInfo;
 use crate::dom::bindings::codegen::Bindings::RequestBinding::RequestInit;
 use crate::dom::bindings::codegen::Bindings::ResponseBinding::ResponseBinding::ResponseMethods;
@@ -24,6 +26,7 @@ use crate::network_listener::{
 };
 use crate::realms::{enter_realm, InRealm};
 use crate::task_source::TaskSourceName;
+use content_security_policy as csp;
 use ipc_channel::ipc;
 use ipc_channel::router::ROUTER;
 use net_traits::request::{
@@ -36,15 +39,18 @@ use net_traits::{FetchChannels, FetchResponseListener, NetworkError};
 use net_traits::{FetchMetadata, FilteredMetadata, Metadata};
 use net_traits::{ResourceFetchTiming, ResourceTimingType};
 use servo_url::ServoUrl;
-use std::mem;
 use std::rc::Rc;
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, Weak};
 
-struct FetchContext {
+pub struct FetchContext {
     fetch_promise: Option<TrustedPromise>,
     response_object: Trusted<Response>,
-    body: Vec<u8>,
     resource_timing: ResourceFetchTiming,
+    canceller: FetchCanceller,
+    /// Whether the request's signal aborted this fetch.
+    locally_aborted: bool,
+    /// The request's signal, and this context as it registered itself with the signal.
+    signal: Option<(Trusted<AbortSignal>, Weak<Mutex<FetchContext>>)>,
 }
 
 /// RAII fetch canceller object. By default initialized to not having a canceller
@@ -124,10 +130,13 @@ fn request_init_from_request(request: NetTraitsRequest) -> RequestBuilder {
         pipeline_id: request.pipeline_id,
         redirect_mode: request.redirect_mode,
         integrity_metadata: request.integrity_metadata.clone(),
+        cryptographic_nonce_metadata: request.cryptographic_nonce_metadata.clone(),
         url_list: vec![],
         parser_metadata: request.parser_metadata,
         initiator: request.initiator,
         csp_list: None,
+        embedder_policy: request.embedder_policy,
+        top_level_navigation: request.top_level_navigation,
     }
 }
 
@@ -139,24 +148,47 @@ pub fn Fetch(
     init: RootedTraceableBox<RequestInit>,
     comp: InRealm,
 ) -> Rc<Promise> {
-    let core_resource_thread = global.core_resource_thread();
-
     // Step 1
     let promise = Promise::new_in_current_realm(global, comp);
-    let response = Response::new(global);
 
     // Step 2
-    let request = match Request::Constructor(global, input, init) {
+    let request_object = match Request::Constructor(global, input, init) {
         Err(e) => {
             promise.reject_error(e);
             return promise;
         },
-        Ok(r) => r.get_request(),
+        Ok(r) => r,
     };
+    // A stream body isn't uploaded as it is read, the request is sent once
+    // all of its bytes are known.
+    match request_object.get_body() {
+        Some(ref stream) if request_object.has_stream_body() => {
+            read_body_then_fetch(&request_object, stream, &promise)
+        },
+        _ => fetch_request(global, &request_object, &promise),
+    }
+    promise
+}
+
+/// The steps of <https://fetch.spec.whatwg.org/#fetch-method> after the request
+/// is constructed, once the bytes of its body are known.
+pub fn fetch_request(global: &GlobalScope, request_object: &Request, promise: &Rc<Promise>) {
+    let core_resource_thread = global.core_resource_thread();
+    let response = Response::new(global);
+    let request = request_object.get_request();
+    let signal = request_object.signal();
+
+    // https://fetch.spec.whatwg.org/#abort-fetch if the signal already aborted
+    if signal.aborted() {
+        promise.reject_error(Error::Abort);
+        return;
+    }
+
     let timing_type = request.timing_type();
 
     let mut request_init = request_init_from_request(request);
     request_init.csp_list = global.get_csp_list().clone();
+    request_init.embedder_policy = global.embedder_policy();
 
     // Step 3
     if global.downcast::<ServiceWorkerGlobalScope>().is_some() {
@@ -168,12 +200,22 @@ pub fn Fetch(
 
     // Step 5
     let (action_sender, action_receiver) = ipc::channel().unwrap();
+    let mut canceller = FetchCanceller::new();
+    let cancel_receiver = canceller.initialize();
     let fetch_context = Arc::new(Mutex::new(FetchContext {
         fetch_promise: Some(TrustedPromise::new(promise.clone())),
         response_object: Trusted::new(&*response),
-        body: vec![],
         resource_timing: ResourceFetchTiming::new(timing_type),
+        canceller,
+        locally_aborted: false,
+        signal: None,
     }));
+
+    // Aborting the signal aborts the fetch while it is ongoing.
+    let weak_context = Arc::downgrade(&fetch_context);
+    signal.add_fetch(weak_context.clone());
+    fetch_context.lock().unwrap().signal = Some((Trusted::new(&*signal), weak_context));
+
     let listener = NetworkListener {
         context: fetch_context,
         task_source: global.networking_task_source(),
@@ -189,14 +231,44 @@ pub fn Fetch(
     core_resource_thread
         .send(NetTraitsFetch(
             request_init,
-            FetchChannels::ResponseMsg(action_sender, None),
+            FetchChannels::ResponseMsg(action_sender, Some(cancel_receiver)),
         ))
         .unwrap();
+}
 
-    promise
+impl FetchContext {
+    /// Steps 9.1-9.3 of <https://fetch.spec.whatwg.org/#dom-global-fetch>
+    pub fn abort(&mut self) {
+        // Step 9.1
+        self.locally_aborted = true;
+
+        // Step 9.2, https://fetch.spec.whatwg.org/#abort-fetch
+        if let Some(promise) = self.fetch_promise.take() {
+            let promise = promise.root();
+            if !promise.is_fulfilled() {
+                let _ac = enter_realm(&*promise);
+                promise.reject_error(Error::Abort);
+            }
+        }
+        self.response_object.root().abort();
+
+        // Step 9.3
+        self.canceller.cancel();
+    }
+
+    /// Stop following the request's signal once the fetch is over.
+    fn remove_from_signal(&mut self) {
+        if let Some((signal, context)) = self.signal.take() {
+            signal.root().remove_fetch(&context);
+        }
+    }
 }
 
-impl PreInvoke for FetchContext {}
+impl PreInvoke for FetchContext {
+    fn should_invoke(&self) -> bool {
+        !self.locally_aborted
+    }
+}
 
 impl FetchResponseListener for FetchContext {
     fn process_request_body(&mut self) {
@@ -219,6 +291,7 @@ impl FetchResponseListener for FetchContext {
         match fetch_metadata {
             // Step 4.1
             Err(_) => {
+                self.remove_from_signal();
                 promise.reject_error(Error::Type("Network error occurred".to_string()));
                 self.fetch_promise = Some(TrustedPromise::new(promise));
                 self.response_object.root().set_type(DOMResponseType::Error);
@@ -255,19 +328,26 @@ impl FetchResponseListener for FetchContext {
             },
         }
         // Step 4.3
+        self.response_object.root().init_fetch_stream();
         promise.resolve_native(&self.response_object.root());
         self.fetch_promise = Some(TrustedPromise::new(promise));
     }
 
-    fn process_response_chunk(&mut self, mut chunk: Vec<u8>) {
-        self.response_object.root().stream_chunk(chunk.as_slice());
-        self.body.append(&mut chunk);
-    }
-
-    fn process_response_eof(&mut self, _response: Result<ResourceFetchTiming, NetworkError>) {
+    fn process_response_chunk(&mut self, chunk: Vec<u8>) {
         let response = self.response_object.root();
         let _ac = enter_realm(&*response);
-        response.finish(mem::replace(&mut self.body, vec![]));
+        response.stream_chunk(chunk);
+    }
+
+    fn process_response_eof(&mut self, response: Result<ResourceFetchTiming, NetworkError>) {
+        self.canceller.ignore();
+        self.remove_from_signal();
+        let response_object = self.response_object.root();
+        let _ac = enter_realm(&*response_object);
+        if response.is_err() {
+            response_object.fail();
+        }
+        response_object.finish();
         // TODO
         // ... trailerObject is not supported in Servo yet.
     }
@@ -287,6 +367,11 @@ impl FetchResponseListener for FetchContext {
             _ => {},
         };
     }
+
+    fn process_csp_violations(&mut self, violations: Vec<csp::Violation>) {
+        let global = &self.resource_timing_global();
+        global.report_csp_violations(violations, None);
+    }
 }
 
 impl ResourceTimingListener for FetchContext {
@@ -328,6 +413,9 @@ pub fn load_whole_resource(
     loop {
         match action_receiver.recv().unwrap() {
             FetchResponseMsg::ProcessRequestBody | FetchResponseMsg::ProcessRequestEOF => (),
+            FetchResponseMsg::ProcessCspViolations(violations) => {
+                global.report_csp_violations(violations, None)
+            },
             FetchResponseMsg::ProcessResponse(Ok(m)) => {
                 metadata = Some(match m {
                     FetchMetadata::Unfiltered(m) => m,
@@ -0,0 +1,485 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Conversions between JavaScript values and the keys and values of IndexedDB.
+
+use crate::dom::bindings::codegen::Bindings::IDBCursorBinding::IDBCursorDirection;
+use crate::dom::bindings::codegen::UnionTypes::StringOrStringSequence;
+use crate::dom::bindings::conversions::ToJSValConvertible;
+use crate::dom::bindings::conversions::{jsstring_to_str, root_from_handlevalue};
+use crate::dom::bindings::error::{Error, Fallible};
+use crate::dom::bindings::str::DOMString;
+use crate::dom::bindings::structuredclone;
+use crate::dom::bindings::utils::{get_dictionary_property, set_dictionary_property};
+use crate::dom::domexception::DOMErrorName;
+use crate::dom::globalscope::GlobalScope;
+use crate::dom::idbkeyrange::IDBKeyRange;
+use crate::script_runtime::JSContext;
+use js::jsapi::{ClippedTime, DateGetMsecSinceEpoch, Handle, HandleValueArray, JSObject};
+use js::jsapi::{JS_NewArrayObject, JS_NewPlainObject, NewDateObject, ObjectIsDate};
+use js::jsval::{NullValue, ObjectValue, UndefinedValue};
+use js::rust::jsapi_wrapped::JS_GetArrayLength;
+use js::rust::wrappers::JS_IsArrayObject;
+use js::rust::{HandleValue, MutableHandleValue};
+use js::typedarray::{ArrayBuffer, ArrayBufferView, CreateWith};
+use net_traits::indexeddb_thread::ObjectStoreMetadata;
+use net_traits::indexeddb_thread::{
+    BackendError, CursorDirection, IndexMetadata, IndexedDBKeyRange,
+};
+use net_traits::indexeddb_thread::{IndexedDBKeyType, IndexedDBRecord, KeyPath};
+use script_traits::StructuredSerializedData;
+use std::ptr;
+
+/// The outcome of extracting a key from a value using a key path.
+///
+/// <https://w3c.github.io/IndexedDB/#extract-a-key-from-a-value-using-a-key-path>
+pub enum KeyExtraction {
+    Key(IndexedDBKeyType),
+    /// The key path led to a value that is not a valid key.
+    Invalid,
+    /// The key path led to nothing.
+    Failure,
+}
+
+/// <https://w3c.github.io/IndexedDB/#convert-a-value-to-a-key>
+#[allow(unsafe_code)]
+pub fn convert_value_to_key(
+    cx: JSContext,
+    input: HandleValue,
+    seen: &mut Vec<*mut JSObject>,
+) -> Fallible<IndexedDBKeyType> {
+    if input.is_number() {
+        let number = input.to_number();
+        if number.is_nan() {
+            return Err(Error::Data);
+        }
+        return Ok(IndexedDBKeyType::Number(number));
+    }
+
+    if input.is_string() {
+        let string = unsafe { jsstring_to_str(*cx, input.to_string()) };
+        return Ok(IndexedDBKeyType::String(string.into()));
+    }
+
+    if !input.is_object() {
+        return Err(Error::Data);
+    }
+
+    rooted!(in(*cx) let object = input.to_object());
+    unsafe {
+        let mut is_date = false;
+        if !ObjectIsDate(*cx, Handle::from(object.handle()), &mut is_date) {
+            return Err(Error::JSFailed);
+        }
+        if is_date {
+            let mut time = 0.;
+            if !DateGetMsecSinceEpoch(*cx, Handle::from(object.handle()), &mut time) {
+                return Err(Error::JSFailed);
+            }
+            if time.is_nan() {
+                return Err(Error::Data);
+            }
+            return Ok(IndexedDBKeyType::Date(time));
+        }
+
+        typedarray!(in(*cx) let buffer: ArrayBuffer = object.get());
+        if let Ok(buffer) = buffer {
+            return Ok(IndexedDBKeyType::Binary(buffer.to_vec()));
+        }
+        typedarray!(in(*cx) let view: ArrayBufferView = object.get());
+        if let Ok(view) = view {
+            return Ok(IndexedDBKeyType::Binary(view.to_vec()));
+        }
+
+        let mut is_array = false;
+        if !JS_IsArrayObject(*cx, input, &mut is_array) {
+            return Err(Error::JSFailed);
+        }
+        if !is_array {
+            return Err(Error::Data);
+        }
+        if seen.contains(&object.get()) {
+            return Err(Error::Data);
+        }
+        let mut length = 0;
+        if !JS_GetArrayLength(*cx, object.handle(), &mut length) {
+            return Err(Error::JSFailed);
+        }
+        seen.push(object.get());
+        let mut keys = Vec::with_capacity(length as usize);
+        for index in 0..length {
+            rooted!(in(*cx) let mut entry = UndefinedValue());
+            match get_dictionary_property(
+                *cx,
+                object.handle(),
+                &index.to_string(),
+                entry.handle_mut(),
+            ) {
+                Ok(true) => {},
+                Ok(false) => return Err(Error::Data),
+                Err(()) => return Err(Error::JSFailed),
+            }
+            keys.push(convert_value_to_key(cx, entry.handle(), seen)?);
+        }
+        seen.pop();
+        Ok(IndexedDBKeyType::Array(keys))
+    }
+}
+
+/// <https://w3c.github.io/IndexedDB/#convert-a-value-to-a-multientry-key>
+#[allow(unsafe_code)]
+fn convert_value_to_multi_entry_key(
+    cx: JSContext,
+    input: HandleValue,
+) -> Fallible<IndexedDBKeyType> {
+    let mut is_array = false;
+    if input.is_object() && !unsafe { JS_IsArrayObject(*cx, input, &mut is_array) } {
+        return Err(Error::JSFailed);
+    }
+    if !is_array {
+        return convert_value_to_key(cx, input, &mut vec![]);
+    }
+
+    rooted!(in(*cx) let object = input.to_object());
+    let mut length = 0;
+    if !unsafe { JS_GetArrayLength(*cx, object.handle(), &mut length) } {
+        return Err(Error::JSFailed);
+    }
+    let mut seen = vec![object.get()];
+    let mut keys = vec![];
+    for index in 0..length {
+        rooted!(in(*cx) let mut entry = UndefinedValue());
+        match get_dictionary_property(*cx, object.handle(), &index.to_string(), entry.handle_mut())
+        {
+            Ok(true) => {},
+            Ok(false) => continue,
+            Err(()) => return Err(Error::JSFailed),
+        }
+        // Invalid entries are left out of the index.
+        match convert_value_to_key(cx, entry.handle(), &mut seen) {
+            Ok(key) => {
+                if !keys.contains(&key) {
+                    keys.push(key);
+                }
+            },
+            Err(Error::Data) => {},
+            Err(error) => return Err(error),
+        }
+    }
+    Ok(IndexedDBKeyType::Array(keys))
+}
+
+/// <https://w3c.github.io/IndexedDB/#convert-a-key-to-a-value>
+#[allow(unsafe_code)]
+pub fn key_type_to_jsval(cx: JSContext, key: &IndexedDBKeyType, mut rval: MutableHandleValue) {
+    unsafe {
+        match *key {
+            IndexedDBKeyType::Number(number) => number.to_jsval(*cx, rval),
+            IndexedDBKeyType::Date(time) => {
+                let date = NewDateObject(*cx, ClippedTime { t: time });
+                rval.set(ObjectValue(date));
+            },
+            IndexedDBKeyType::String(ref string) => {
+                DOMString::from(string.clone()).to_jsval(*cx, rval)
+            },
+            IndexedDBKeyType::Binary(ref bytes) => {
+                rooted!(in(*cx) let mut buffer = ptr::null_mut::<JSObject>());
+                assert!(
+                    ArrayBuffer::create(*cx, CreateWith::Slice(bytes), buffer.handle_mut()).is_ok()
+                );
+                rval.set(ObjectValue(buffer.get()));
+            },
+            IndexedDBKeyType::Array(ref keys) => {
+                rooted!(in(*cx) let array = JS_NewArrayObject(*cx, &HandleValueArray::new()));
+                for (index, key) in keys.iter().enumerate() {
+                    rooted!(in(*cx) let mut entry = UndefinedValue());
+                    key_type_to_jsval(cx, key, entry.handle_mut());
+                    let _ = set_dictionary_property(
+                        *cx,
+                        array.handle(),
+                        &index.to_string(),
+                        entry.handle(),
+                    );
+                }
+                rval.set(ObjectValue(array.get()));
+            },
+        }
+    }
+}
+
+/// <https://w3c.github.io/IndexedDB/#convert-a-value-to-a-key-range>
+pub fn convert_value_to_key_range(
+    cx: JSContext,
+    input: HandleValue,
+    null_disallowed: bool,
+) -> Fallible<IndexedDBKeyRange> {
+    if let Ok(range) = root_from_handlevalue::<IDBKeyRange>(input, *cx) {
+        return Ok(range.inner().clone());
+    }
+    if input.is_undefined() || input.is_null() {
+        if null_disallowed {
+            return Err(Error::Data);
+        }
+        return Ok(IndexedDBKeyRange::unbounded());
+    }
+    let key = convert_value_to_key(cx, input, &mut vec![])?;
+    Ok(IndexedDBKeyRange::only(key))
+}
+
+/// Whether `identifier` is an ECMAScript identifier, restricted to ASCII.
+fn is_identifier(identifier: &str) -> bool {
+    let mut chars = identifier.chars();
+    let is_start = |c: char| c.is_alphabetic() || c == '$' || c == '_';
+    match chars.next() {
+        Some(first) if is_start(first) => chars.all(|c| is_start(c) || c.is_numeric()),
+        _ => false,
+    }
+}
+
+/// <https://w3c.github.io/IndexedDB/#valid-key-path>
+pub fn is_valid_key_path(key_path: &KeyPath) -> bool {
+    let is_valid_string = |path: &str| path.is_empty() || path.split('.').all(is_identifier);
+    match *key_path {
+        KeyPath::String(ref path) => is_valid_string(path),
+        KeyPath::Sequence(ref paths) => {
+            !paths.is_empty() && paths.iter().all(|path| is_valid_string(path))
+        },
+    }
+}
+
+/// <https://w3c.github.io/IndexedDB/#evaluate-a-key-path-on-a-value>
+///
+/// Returns `Ok(false)` if the key path leads to nothing.
+#[allow(unsafe_code)]
+fn evaluate_key_path_on_value(
+    cx: JSContext,
+    value: HandleValue,
+    key_path: &str,
+    mut rval: MutableHandleValue,
+) -> Fallible<bool> {
+    rooted!(in(*cx) let mut current = value.get());
+    if !key_path.is_empty() {
+        for identifier in key_path.split('.') {
+            if current.is_string() && identifier == "length" {
+                let string = unsafe { jsstring_to_str(*cx, current.to_string()) };
+                let length = string.encode_utf16().count() as f64;
+                unsafe { length.to_jsval(*cx, current.handle_mut()) };
+                continue;
+            }
+            if !current.is_object() {
+                return Ok(false);
+            }
+            rooted!(in(*cx) let object = current.to_object());
+            match get_dictionary_property(*cx, object.handle(), identifier, current.handle_mut()) {
+                Ok(true) => {},
+                Ok(false) => return Ok(false),
+                Err(()) => return Err(Error::JSFailed),
+            }
+        }
+    }
+    rval.set(current.get());
+    Ok(true)
+}
+
+/// <https://w3c.github.io/IndexedDB/#extract-a-key-from-a-value-using-a-key-path>
+pub fn extract_key(
+    cx: JSContext,
+    value: HandleValue,
+    key_path: &KeyPath,
+    multi_entry: bool,
+) -> Fallible<KeyExtraction> {
+    let paths = match *key_path {
+        KeyPath::String(ref path) => vec![path],
+        KeyPath::Sequence(ref paths) => paths.iter().collect(),
+    };
+    let mut keys = vec![];
+    for path in paths {
+        rooted!(in(*cx) let mut result = UndefinedValue());
+        if !evaluate_key_path_on_value(cx, value, path, result.handle_mut())? {
+            return Ok(KeyExtraction::Failure);
+        }
+        let key = if multi_entry {
+            convert_value_to_multi_entry_key(cx, result.handle())
+        } else {
+            convert_value_to_key(cx, result.handle(), &mut vec![])
+        };
+        match key {
+            Ok(key) => keys.push(key),
+            Err(Error::Data) => return Ok(KeyExtraction::Invalid),
+            Err(error) => return Err(error),
+        }
+    }
+    match *key_path {
+        KeyPath::String(_) => Ok(KeyExtraction::Key(keys.remove(0))),
+        KeyPath::Sequence(_) => Ok(KeyExtraction::Key(IndexedDBKeyType::Array(keys))),
+    }
+}
+
+/// The keys that a value has in an index, which are none if the key path of
+/// the index does not lead to a valid key.
+///
+/// <https://w3c.github.io/IndexedDB/#store-a-record-into-an-object-store>
+pub fn index_keys(
+    cx: JSContext,
+    value: HandleValue,
+    index: &IndexMetadata,
+) -> Fallible<Vec<IndexedDBKeyType>> {
+    let multi_entry = index.multi_entry;
+    match extract_key(cx, value, &index.key_path, multi_entry)? {
+        KeyExtraction::Key(IndexedDBKeyType::Array(keys)) if multi_entry => Ok(keys),
+        KeyExtraction::Key(key) => Ok(vec![key]),
+        KeyExtraction::Invalid | KeyExtraction::Failure => Ok(vec![]),
+    }
+}
+
+/// <https://w3c.github.io/IndexedDB/#check-that-a-key-could-be-injected-into-a-value>
+#[allow(unsafe_code)]
+pub fn can_inject_key(cx: JSContext, value: HandleValue, key_path: &str) -> Fallible<bool> {
+    let mut identifiers: Vec<&str> = key_path.split('.').collect();
+    identifiers.pop();
+    rooted!(in(*cx) let mut current = value.get());
+    for identifier in identifiers {
+        if !current.is_object() {
+            return Ok(false);
+        }
+        rooted!(in(*cx) let object = current.to_object());
+        match get_dictionary_property(*cx, object.handle(), identifier, current.handle_mut()) {
+            Ok(true) => {},
+            Ok(false) => return Ok(true),
+            Err(()) => return Err(Error::JSFailed),
+        }
+    }
+    Ok(current.is_object())
+}
+
+/// <https://w3c.github.io/IndexedDB/#inject-a-key-into-a-value-using-a-key-path>
+#[allow(unsafe_code)]
+pub fn inject_key(
+    cx: JSContext,
+    value: HandleValue,
+    key: &IndexedDBKeyType,
+    key_path: &str,
+) -> Fallible<()> {
+    let mut identifiers: Vec<&str> = key_path.split('.').collect();
+    let last = identifiers.pop().unwrap_or("");
+    rooted!(in(*cx) let mut current = value.get());
+    for identifier in identifiers {
+        if !current.is_object() {
+            return Err(Error::Data);
+        }
+        rooted!(in(*cx) let object = current.to_object());
+        match get_dictionary_property(*cx, object.handle(), identifier, current.handle_mut()) {
+            Ok(true) => {},
+            Ok(false) => {
+                rooted!(in(*cx) let created = unsafe { JS_NewPlainObject(*cx) });
+                current.set(ObjectValue(created.get()));
+                if set_dictionary_property(*cx, object.handle(), identifier, current.handle())
+                    .is_err()
+                {
+                    return Err(Error::JSFailed);
+                }
+            },
+            Err(()) => return Err(Error::JSFailed),
+        }
+    }
+    if !current.is_object() {
+        return Err(Error::Data);
+    }
+    rooted!(in(*cx) let object = current.to_object());
+    rooted!(in(*cx) let mut key_value = UndefinedValue());
+    key_type_to_jsval(cx, key, key_value.handle_mut());
+    set_dictionary_property(*cx, object.handle(), last, key_value.handle())
+        .map_err(|()| Error::JSFailed)
+}
+
+/// Serialize a value with the structured clone algorithm, to store it in a database.
+pub fn serialize_value(cx: JSContext, value: HandleValue) -> Fallible<Vec<u8>> {
+    let data = structuredclone::write(cx, value, None)?;
+    bincode::serialize(&data).map_err(|_| Error::DataClone)
+}
+
+/// Deserialize a value that `serialize_value` serialized, in the realm of `global`.
+pub fn deserialize_value(
+    global: &GlobalScope,
+    bytes: &[u8],
+    rval: MutableHandleValue,
+) -> Fallible<()> {
+    let data: StructuredSerializedData =
+        bincode::deserialize(bytes).map_err(|_| Error::DataClone)?;
+    structuredclone::read(global, data, rval).map_err(|()| Error::DataClone)?;
+    Ok(())
+}
+
+/// The value of a record read from the backend, with its primary key injected at
+/// `inject_path` when the object store generated it. Key-only records have no value.
+pub fn record_value(
+    global: &GlobalScope,
+    record: &IndexedDBRecord,
+    inject_path: Option<&str>,
+    mut rval: MutableHandleValue,
+) -> Fallible<()> {
+    let bytes = match record.value {
+        Some(ref bytes) => bytes,
+        None => {
+            rval.set(UndefinedValue());
+            return Ok(());
+        },
+    };
+    deserialize_value(global, bytes, rval)?;
+    match inject_path {
+        Some(path) => inject_key(global.get_cx(), rval.handle(), &record.primary_key, path),
+        None => Ok(()),
+    }
+}
+
+/// The key path at which the keys an object store generates are injected into its values.
+pub fn key_injection_path(metadata: &ObjectStoreMetadata) -> Option<String> {
+    match metadata.key_path {
+        Some(KeyPath::String(ref path)) if metadata.auto_increment => Some(path.clone()),
+        _ => None,
+    }
+}
+
+/// The exception that script sees for an error of the backend.
+pub fn backend_error_name(error: &BackendError) -> DOMErrorName {
+    match *error {
+        BackendError::Constraint => DOMErrorName::ConstraintError,
+        BackendError::Version => DOMErrorName::VersionError,
+        BackendError::Abort => DOMErrorName::AbortError,
+    }
+}
+
+pub fn cursor_direction(direction: IDBCursorDirection) -> CursorDirection {
+    match direction {
+        IDBCursorDirection::Next => CursorDirection::Next,
+        IDBCursorDirection::Nextunique => CursorDirection::NextUnique,
+        IDBCursorDirection::Prev => CursorDirection::Prev,
+        IDBCursorDirection::Prevunique => CursorDirection::PrevUnique,
+    }
+}
+
+pub fn key_path_from_idl(key_path: &StringOrStringSequence) -> KeyPath {
+    match *key_path {
+        StringOrStringSequence::String(ref path) => KeyPath::String(path.to_string()),
+        StringOrStringSequence::StringSequence(ref paths) => {
+            KeyPath::Sequence(paths.iter().map(|path| path.to_string()).collect())
+        },
+    }
+}
+
+/// The value of the `keyPath` attribute of object stores and indexes.
+#[allow(unsafe_code)]
+pub fn key_path_to_jsval(cx: JSContext, key_path: Option<&KeyPath>, mut rval: MutableHandleValue) {
+    unsafe {
+        match key_path {
+            Some(KeyPath::String(path)) => DOMString::from(path.clone()).to_jsval(*cx, rval),
+            Some(KeyPath::Sequence(paths)) => paths
+                .iter()
+                .map(|path| DOMString::from(path.clone()))
+                .collect::<Vec<_>>()
+                .to_jsval(*cx, rval),
+            None => rval.set(NullValue()),
+        }
+    }
+}
@@ -64,12 +64,16 @@ pub mod document_loader;
 mod dom;
 #[warn(deprecated)]
 mod canvas_state;
+#[warn(deprecated)]
+mod editing;
 mod euclidext;
 #[warn(deprecated)]
 pub mod fetch;
 #[warn(deprecated)]
 mod image_listener;
 #[warn(deprecated)]
+mod indexed_db;
+#[warn(deprecated)]
 mod init;
 #[warn(deprecated)]
 mod layout_image;
@@ -89,10 +93,14 @@ pub mod script_runtime;
 #[allow(unsafe_code)]
 pub mod script_thread;
 #[warn(deprecated)]
+mod security_manager;
+#[warn(deprecated)]
 mod serviceworker_manager;
 #[warn(deprecated)]
 mod serviceworkerjob;
 #[warn(deprecated)]
+mod streams;
+#[warn(deprecated)]
 mod stylesheet_loader;
 #[warn(deprecated)]
 mod stylesheet_set;
@@ -111,6 +119,8 @@ mod timers;
 #[warn(deprecated)]
 mod unpremultiplytable;
 #[warn(deprecated)]
+pub mod user_activation;
+#[warn(deprecated)]
 mod webdriver_handlers;
 
 pub use init::{init, init_service_workers};
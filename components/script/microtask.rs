@@ -130,11 +130,14 @@ impl MicrotaskQueue {
         }
 
         // Step 3
-        for global in globalscopes.into_iter() {
-            notify_about_rejected_promises(&global);
+        for global in globalscopes.iter() {
+            notify_about_rejected_promises(global);
         }
 
-        // TODO: Step 4 - Cleanup Indexed Database transactions.
+        // Step 4
+        for global in globalscopes.iter() {
+            global.cleanup_indexeddb_transactions();
+        }
 
         // Step 5
         self.performing_a_microtask_checkpoint.set(false);
@@ -34,6 +34,7 @@ use crate::network_listener::{PreInvoke, ResourceTimingListener};
 use crate::realms::{enter_realm, AlreadyInRealm, InRealm};
 use crate::task::TaskBox;
 use crate::task_source::TaskSourceName;
+use content_security_policy as csp;
 use encoding_rs::UTF_8;
 use hyper_serde::Serde;
 use ipc_channel::ipc;
@@ -1036,6 +1037,11 @@ impl FetchResponseListener for ModuleContext {
     fn submit_resource_timing(&mut self) {
         network_listener::submit_timing(self)
     }
+
+    fn process_csp_violations(&mut self, violations: Vec<csp::Violation>) {
+        let global = &self.resource_timing_global();
+        global.report_csp_violations(violations, None);
+    }
 }
 
 impl ResourceTimingListener for ModuleContext {
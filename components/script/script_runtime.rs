@@ -154,6 +154,7 @@ pub enum ScriptThreadEventCategory {
     ExitFullscreen,
     WebVREvent,
     PerformanceTimelineTask,
+    DatabaseAccessEvent,
 }
 
 /// An interface for receiving ScriptMsg values in an event loop. Used for synchronous DOM
@@ -57,7 +57,7 @@ use crate::dom::node::{
 use crate::dom::performanceentry::PerformanceEntry;
 use crate::dom::performancepainttiming::PerformancePaintTiming;
 use crate::dom::serviceworker::TrustedServiceWorkerAddress;
-use crate::dom::serviceworkerregistration::ServiceWorkerRegistration;
+use crate::dom::serviceworkerregistration::{longest_prefix_match, ServiceWorkerRegistration};
 use crate::dom::servoparser::{ParserContext, ServoParser};
 use crate::dom::transitionevent::TransitionEvent;
 use crate::dom::uievent::UIEvent;
@@ -74,6 +74,7 @@ use crate::script_runtime::{CommonScriptMsg, ScriptChan, ScriptThreadEventCatego
 use crate::serviceworkerjob::{Job, JobQueue};
 use crate::task_manager::TaskManager;
 use crate::task_queue::{QueuedTask, QueuedTaskConversion, TaskQueue};
+use crate::task_source::database_access::DatabaseAccessTaskSource;
 use crate::task_source::dom_manipulation::DOMManipulationTaskSource;
 use crate::task_source::file_reading::FileReadingTaskSource;
 use crate::task_source::history_traversal::HistoryTraversalTaskSource;
@@ -90,6 +91,7 @@ use crate::task_source::TaskSourceName;
 use crate::webdriver_handlers;
 use bluetooth_traits::BluetoothRequest;
 use canvas_traits::webgl::WebGLPipeline;
+use content_security_policy as csp;
 use crossbeam_channel::{unbounded, Receiver, Sender};
 use devtools_traits::CSSError;
 use devtools_traits::{DevtoolScriptControlMsg, DevtoolsPageInfo};
@@ -117,7 +119,8 @@ use msg::constellation_msg::{BrowsingContextId, HistoryStateId, PipelineId};
 use msg::constellation_msg::{HangAnnotation, MonitoredComponentId, MonitoredComponentType};
 use msg::constellation_msg::{PipelineNamespace, TopLevelBrowsingContextId};
 use net_traits::image_cache::{ImageCache, PendingImageResponse};
-use net_traits::request::{CredentialsMode, Destination, RedirectMode, RequestBuilder};
+use net_traits::request::{CredentialsMode, Destination, RedirectMode};
+use net_traits::request::{RequestBuilder, RequestMode};
 use net_traits::storage_thread::StorageType;
 use net_traits::{FetchMetadata, FetchResponseListener, FetchResponseMsg};
 use net_traits::{
@@ -141,7 +144,7 @@ use script_traits::{
 use script_traits::{InitialScriptState, JsEvalResult, LayoutMsg, LoadData, LoadOrigin};
 use script_traits::{MediaSessionActionType, MouseButton, MouseEventType, NewLayoutInfo};
 use script_traits::{Painter, ProgressiveWebMetricType, ScriptMsg, ScriptThreadFactory};
-use script_traits::{ScriptToConstellationChan, TimerSchedulerMsg};
+use script_traits::{ScriptToConstellationChan, ServiceWorkerLifecycleState, TimerSchedulerMsg};
 use script_traits::{TouchEventType, TouchId, UntrustedNodeAddress, WheelDelta};
 use script_traits::{UpdatePipelineIdReason, WebrenderIpcSender, WindowSizeData, WindowSizeType};
 use servo_atoms::Atom;
@@ -209,6 +212,8 @@ struct InProgressLoad {
     url: ServoUrl,
     /// The origin for the document
     origin: MutableOrigin,
+    /// The origins of the documents of the ancestor browsing contexts, nearest first.
+    ancestor_origins: Vec<ImmutableOrigin>,
     /// Timestamp reporting the time when the browser started this load.
     navigation_start: u64,
     /// High res timestamp reporting the time when the browser started this load.
@@ -231,6 +236,7 @@ impl InProgressLoad {
         window_size: WindowSizeData,
         url: ServoUrl,
         origin: MutableOrigin,
+        ancestor_origins: Vec<ImmutableOrigin>,
         layout_is_busy: Arc<AtomicBool>,
     ) -> InProgressLoad {
         let current_time = get_time();
@@ -250,6 +256,7 @@ impl InProgressLoad {
             is_visible: true,
             url: url,
             origin: origin,
+            ancestor_origins,
             navigation_start: (current_time.sec * 1000 + current_time.nsec as i64 / 1000000) as u64,
             navigation_start_precise: navigation_start_precise,
             canceller: Default::default(),
@@ -531,6 +538,8 @@ pub struct ScriptThread {
     incomplete_parser_contexts: RefCell<IncompleteParserContexts>,
     /// A map to store service worker registrations for a given origin
     registration_map: DomRefCell<HashMap<ServoUrl, Dom<ServiceWorkerRegistration>>>,
+    /// The scope and script URL of the service worker controlling each window
+    service_worker_controllers: DomRefCell<HashMap<PipelineId, (ServoUrl, ServoUrl)>>,
     /// A job queue for Service Workers keyed by their scope url
     job_queue_map: Rc<JobQueue>,
     /// Image cache for this script thread.
@@ -796,6 +805,7 @@ impl ScriptThreadFactory for ScriptThread {
                     window_size,
                     load_data.url.clone(),
                     origin,
+                    load_data.ancestor_origins.clone(),
                     layout_is_busy,
                 );
                 script_thread.pre_page_load(new_load, load_data);
@@ -900,6 +910,47 @@ impl ScriptThread {
         })
     }
 
+    /// The registration whose scope is the longest prefix of `url`.
+    /// <https://w3c.github.io/ServiceWorker/#scope-match-algorithm>
+    pub fn get_registration_for_url(url: &ServoUrl) -> Option<DomRoot<ServiceWorkerRegistration>> {
+        SCRIPT_THREAD_ROOT.with(|root| {
+            let script_thread = unsafe { &*root.get().unwrap() };
+            script_thread
+                .registration_map
+                .borrow()
+                .iter()
+                .filter(|(scope, _)| longest_prefix_match(scope, url))
+                .max_by_key(|(scope, _)| scope.as_str().len())
+                .map(|(_, registration)| DomRoot::from_ref(&**registration))
+        })
+    }
+
+    /// The registrations of an origin.
+    pub fn get_registrations(origin: &ImmutableOrigin) -> Vec<DomRoot<ServiceWorkerRegistration>> {
+        SCRIPT_THREAD_ROOT.with(|root| {
+            let script_thread = unsafe { &*root.get().unwrap() };
+            script_thread
+                .registration_map
+                .borrow()
+                .iter()
+                .filter(|(scope, _)| scope.origin() == *origin)
+                .map(|(_, registration)| DomRoot::from_ref(&**registration))
+                .collect()
+        })
+    }
+
+    /// The scope and script URL of the service worker controlling a window.
+    pub fn get_service_worker_controller(pipeline_id: PipelineId) -> Option<(ServoUrl, ServoUrl)> {
+        SCRIPT_THREAD_ROOT.with(|root| {
+            let script_thread = unsafe { &*root.get().unwrap() };
+            script_thread
+                .service_worker_controllers
+                .borrow()
+                .get(&pipeline_id)
+                .cloned()
+        })
+    }
+
     #[allow(unrooted_must_root)]
     pub fn schedule_job(job: Job) {
         SCRIPT_THREAD_ROOT.with(|root| {
@@ -1281,6 +1332,7 @@ impl ScriptThread {
             incomplete_loads: DomRefCell::new(vec![]),
             incomplete_parser_contexts: RefCell::new(vec![]),
             registration_map: DomRefCell::new(HashMap::new()),
+            service_worker_controllers: DomRefCell::new(HashMap::new()),
             job_queue_map: Rc::new(JobQueue::new()),
 
             image_cache: state.image_cache.clone(),
@@ -1601,6 +1653,19 @@ impl ScriptThread {
             }
         }
 
+        // Step 14, once every document is laid out, since the geometry of the documents
+        // of ancestor browsing contexts is used for targets in nested ones.
+        let documents: Vec<DomRoot<Document>> = self
+            .documents
+            .borrow()
+            .iter()
+            .map(|(_, document)| document)
+            .filter(|document| document.is_fully_active())
+            .collect();
+        for document in documents {
+            document.update_intersection_observations();
+        }
+
         true
     }
 
@@ -1627,6 +1692,9 @@ impl ScriptThread {
         let hang_annotation = match category {
             ScriptThreadEventCategory::AttachLayout => ScriptHangAnnotation::AttachLayout,
             ScriptThreadEventCategory::ConstellationMsg => ScriptHangAnnotation::ConstellationMsg,
+            ScriptThreadEventCategory::DatabaseAccessEvent => {
+                ScriptHangAnnotation::DatabaseAccessEvent
+            },
             ScriptThreadEventCategory::DevtoolsMsg => ScriptHangAnnotation::DevtoolsMsg,
             ScriptThreadEventCategory::DocumentEvent => ScriptHangAnnotation::DocumentEvent,
             ScriptThreadEventCategory::DomEvent => ScriptHangAnnotation::DomEvent,
@@ -1672,6 +1740,7 @@ impl ScriptThread {
                 match *inner_msg {
                     StopDelayingLoadEventsMode(id) => Some(id),
                     NavigationResponse(id, _) => Some(id),
+                    SeverOpener(id) => Some(id),
                     AttachLayout(ref new_layout_info) => Some(new_layout_info.new_pipeline_id),
                     Resize(id, ..) => Some(id),
                     ResizeInactive(id, ..) => Some(id),
@@ -1708,6 +1777,8 @@ impl ScriptThread {
                     PaintMetric(..) => None,
                     ExitFullScreen(id, ..) => Some(id),
                     MediaSessionAction(..) => None,
+                    UpdateServiceWorkerState(..) => None,
+                    SetServiceWorkerController(..) => None,
                 }
             },
             MixedMessage::FromDevtools(_) => None,
@@ -1743,6 +1814,9 @@ impl ScriptThread {
                 ScriptThreadEventCategory::ConstellationMsg => {
                     ProfilerCategory::ScriptConstellationMsg
                 },
+                ScriptThreadEventCategory::DatabaseAccessEvent => {
+                    ProfilerCategory::ScriptDatabaseAccessEvent
+                },
                 ScriptThreadEventCategory::DevtoolsMsg => ProfilerCategory::ScriptDevtoolsMsg,
                 ScriptThreadEventCategory::DocumentEvent => ProfilerCategory::ScriptDocumentEvent,
                 ScriptThreadEventCategory::DomEvent => ProfilerCategory::ScriptDomEvent,
@@ -1816,9 +1890,15 @@ impl ScriptThread {
                         self.handle_fetch_chunk(id, chunk)
                     },
                     FetchResponseMsg::ProcessResponseEOF(eof) => self.handle_fetch_eof(id, eof),
+                    FetchResponseMsg::ProcessCspViolations(violations) => {
+                        self.handle_fetch_csp_violations(id, violations)
+                    },
                     _ => unreachable!(),
                 };
             },
+            ConstellationControlMsg::SeverOpener(pipeline_id) => {
+                self.handle_sever_opener(pipeline_id)
+            },
             ConstellationControlMsg::NavigateIframe(
                 parent_pipeline_id,
                 browsing_context_id,
@@ -1933,6 +2013,15 @@ impl ScriptThread {
             ConstellationControlMsg::MediaSessionAction(pipeline_id, action) => {
                 self.handle_media_session_action(pipeline_id, action)
             },
+            ConstellationControlMsg::UpdateServiceWorkerState(scope, state) => {
+                self.handle_update_service_worker_state(scope, state)
+            },
+            ConstellationControlMsg::SetServiceWorkerController(
+                pipeline_id,
+                scope,
+                script_url,
+                claimed,
+            ) => self.handle_set_service_worker_controller(pipeline_id, scope, script_url, claimed),
             msg @ ConstellationControlMsg::AttachLayout(..) |
             msg @ ConstellationControlMsg::Viewport(..) |
             msg @ ConstellationControlMsg::SetScrollState(..) |
@@ -2384,6 +2473,7 @@ impl ScriptThread {
             window_size,
             load_data.url.clone(),
             origin,
+            load_data.ancestor_origins.clone(),
             layout_is_busy.clone(),
         );
         if load_data.url.as_str() == "about:blank" {
@@ -2535,6 +2625,25 @@ impl ScriptThread {
         }
     }
 
+    fn handle_sever_opener(&self, pipeline_id: PipelineId) {
+        let browsing_context_id = {
+            let mut incomplete_loads = self.incomplete_loads.borrow_mut();
+            match incomplete_loads
+                .iter_mut()
+                .find(|load| load.pipeline_id == pipeline_id)
+            {
+                Some(load) => {
+                    load.opener = None;
+                    load.browsing_context_id
+                },
+                None => return warn!("Severing the opener of {} after it loaded.", pipeline_id),
+            }
+        };
+        if let Some(window_proxy) = self.window_proxies.borrow().get(&browsing_context_id) {
+            window_proxy.disown();
+        }
+    }
+
     fn handle_unload_document(&self, pipeline_id: PipelineId) {
         let document = self.documents.borrow().find_document(pipeline_id);
         if let Some(document) = document {
@@ -2706,7 +2815,10 @@ impl ScriptThread {
             None => return warn!("Registration failed for {}", scope),
         };
 
-        let script_url = maybe_registration.get_installed().get_script_url();
+        let script_url = match maybe_registration.get_newest_worker() {
+            Some(worker) => worker.get_script_url(),
+            None => return,
+        };
         let scope_things =
             ServiceWorkerRegistration::create_scope_things(window.upcast(), script_url);
         let _ = self.script_sender.send((
@@ -2715,6 +2827,52 @@ impl ScriptThread {
         ));
     }
 
+    /// <https://w3c.github.io/ServiceWorker/#update-registration-state-algorithm>
+    fn handle_update_service_worker_state(
+        &self,
+        scope: ServoUrl,
+        state: ServiceWorkerLifecycleState,
+    ) {
+        let registration = match self.handle_get_registration(&scope) {
+            Some(registration) => registration,
+            None => return,
+        };
+        registration.update_state(state);
+        match state {
+            // The registration has an active worker now.
+            ServiceWorkerLifecycleState::Activating => {
+                for (_, document) in self.documents.borrow().iter() {
+                    if let Some(container) = document.window().service_worker_container() {
+                        container.resolve_ready();
+                    }
+                }
+            },
+            ServiceWorkerLifecycleState::Redundant => {
+                self.registration_map.borrow_mut().remove(&scope);
+            },
+            _ => {},
+        }
+    }
+
+    fn handle_set_service_worker_controller(
+        &self,
+        pipeline_id: PipelineId,
+        scope: ServoUrl,
+        script_url: ServoUrl,
+        claimed: bool,
+    ) {
+        self.service_worker_controllers
+            .borrow_mut()
+            .insert(pipeline_id, (scope.clone(), script_url.clone()));
+        let window = match self.documents.borrow().find_window(pipeline_id) {
+            Some(window) => window,
+            None => return,
+        };
+        if let Some(container) = window.service_worker_container() {
+            container.set_controller(scope, script_url, claimed);
+        }
+    }
+
     pub fn schedule_job_queue(&self, scope_url: ServoUrl) {
         let _ = self
             .chan
@@ -2722,6 +2880,10 @@ impl ScriptThread {
             .send(MainThreadScriptMsg::DispatchJobQueue { scope_url });
     }
 
+    pub fn database_access_task_source(&self, pipeline_id: PipelineId) -> DatabaseAccessTaskSource {
+        DatabaseAccessTaskSource(self.remote_event_task_sender.clone(), pipeline_id)
+    }
+
     pub fn dom_manipulation_task_source(
         &self,
         pipeline_id: PipelineId,
@@ -2792,6 +2954,7 @@ impl ScriptThread {
         debug!("Exiting pipeline {}.", id);
 
         self.closed_pipelines.borrow_mut().insert(id);
+        self.service_worker_controllers.borrow_mut().remove(&id);
 
         // Check if the exit message is for an in progress load.
         let idx = self
@@ -3160,6 +3323,7 @@ impl ScriptThread {
         };
 
         let task_manager = TaskManager::new(
+            self.database_access_task_source(incomplete.pipeline_id),
             self.dom_manipulation_task_source(incomplete.pipeline_id),
             self.file_reading_task_source(incomplete.pipeline_id),
             self.history_traversal_task_source(incomplete.pipeline_id),
@@ -3287,6 +3451,7 @@ impl ScriptThread {
             referrer_policy,
             incomplete.canceller,
         );
+        document.set_ancestor_origins(incomplete.ancestor_origins);
         document.set_ready_state(DocumentReadyState::Loading);
 
         self.documents
@@ -3703,9 +3868,22 @@ impl ScriptThread {
     /// argument until a notification is received that the fetch is complete.
     fn pre_page_load(&self, mut incomplete: InProgressLoad, load_data: LoadData) {
         let id = incomplete.pipeline_id.clone();
+        // The origin of the navigation request is the origin that initiated it,
+        // so that the network stack can tell same-site and cross-site navigations apart
+        // when it selects SameSite cookies. That origin can differ from the one of the
+        // URL being loaded, so the request has to be in navigate mode for main fetch
+        // not to treat the response as an opaque no-cors one.
+        let origin = match load_data.load_origin {
+            LoadOrigin::Script(ref origin) => origin.clone(),
+            LoadOrigin::Constellation | LoadOrigin::WebDriver => {
+                incomplete.origin.immutable().clone()
+            },
+        };
         let req_init = RequestBuilder::new(load_data.url.clone())
             .method(load_data.method)
             .destination(Destination::Document)
+            .mode(RequestMode::Navigate)
+            .top_level_navigation(incomplete.parent_info.is_none())
             .credentials_mode(CredentialsMode::Include)
             .use_url_credentials(true)
             .pipeline_id(Some(id))
@@ -3714,9 +3892,10 @@ impl ScriptThread {
             .headers(load_data.headers)
             .body(load_data.data)
             .redirect_mode(RedirectMode::Manual)
-            .origin(incomplete.origin.immutable().clone());
+            .origin(origin);
 
-        let context = ParserContext::new(id, load_data.url);
+        let mut context = ParserContext::new(id, load_data.url);
+        context.set_ancestor_origins(incomplete.ancestor_origins.clone());
         self.incomplete_parser_contexts
             .borrow_mut()
             .push((id, context));
@@ -3776,6 +3955,16 @@ impl ScriptThread {
         }
     }
 
+    fn handle_fetch_csp_violations(&self, id: PipelineId, violations: Vec<csp::Violation>) {
+        let mut incomplete_parser_contexts = self.incomplete_parser_contexts.borrow_mut();
+        let parser = incomplete_parser_contexts
+            .iter_mut()
+            .find(|&&mut (pipeline_id, _)| pipeline_id == id);
+        if let Some(&mut (_, ref mut ctxt)) = parser {
+            ctxt.process_csp_violations(violations);
+        }
+    }
+
     /// Synchronously fetch `about:blank`. Stores the `InProgressLoad`
     /// argument until a notification is received that the fetch is complete.
     fn start_page_load_about_blank(
@@ -0,0 +1,317 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Reporting of content security policy violations, as `securitypolicyviolation`
+//! events and as reports sent to the endpoints the policy names,
+//! <https://w3c.github.io/webappsec-csp/#reporting>.
+
+use crate::dom::bindings::codegen::Bindings::DocumentBinding::DocumentMethods;
+use crate::dom::bindings::codegen::Bindings::EventBinding::EventInit;
+use crate::dom::bindings::codegen::Bindings::SecurityPolicyViolationEventBinding::{
+    SecurityPolicyViolationEventDisposition, SecurityPolicyViolationEventInit,
+};
+use crate::dom::bindings::inheritance::Castable;
+use crate::dom::bindings::refcounted::Trusted;
+use crate::dom::bindings::reflector::DomObject;
+use crate::dom::bindings::root::DomRoot;
+use crate::dom::bindings::str::{DOMString, USVString};
+use crate::dom::element::Element;
+use crate::dom::event::{Event, EventBubbles, EventCancelable};
+use crate::dom::eventtarget::EventTarget;
+use crate::dom::globalscope::GlobalScope;
+use crate::dom::node::Node;
+use crate::dom::securitypolicyviolationevent::SecurityPolicyViolationEvent;
+use crate::dom::window::Window;
+use crate::task_source::TaskSource;
+use content_security_policy::{PolicyDisposition, Violation, ViolationResource};
+use headers::{ContentType, HeaderMapExt};
+use http::{HeaderMap, Method};
+use net_traits::request::{CredentialsMode, Destination, RequestBuilder, RequestMode};
+use net_traits::{CoreResourceMsg, FetchChannels};
+use servo_url::ServoUrl;
+
+/// The longest sample of an inline script or style that a report includes,
+/// <https://w3c.github.io/webappsec-csp/#create-violation-for-global>.
+const MAX_SAMPLE_LENGTH: usize = 40;
+
+/// The details of a violation that are exposed to content, in the shape of the body
+/// of a `report-uri` report, <https://w3c.github.io/webappsec-csp/#deprecated-serialize-violation>.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct CSPViolationReport {
+    document_uri: String,
+    referrer: String,
+    blocked_uri: String,
+    effective_directive: String,
+    violated_directive: String,
+    original_policy: String,
+    disposition: String,
+    status_code: u16,
+    #[serde(rename = "script-sample")]
+    sample: String,
+    source_file: String,
+    line_number: u32,
+    column_number: u32,
+}
+
+/// The body of a `report-to` report,
+/// <https://w3c.github.io/webappsec-csp/#reporting>.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CSPViolationReportBody<'a> {
+    #[serde(rename = "documentURL")]
+    document_url: &'a str,
+    referrer: &'a str,
+    #[serde(rename = "blockedURL")]
+    blocked_url: &'a str,
+    effective_directive: &'a str,
+    original_policy: &'a str,
+    source_file: &'a str,
+    sample: &'a str,
+    disposition: &'a str,
+    status_code: u16,
+    line_number: u32,
+    column_number: u32,
+}
+
+/// The body of a `report-uri` report.
+#[derive(Debug, Serialize)]
+struct CSPReport<'a> {
+    #[serde(rename = "csp-report")]
+    csp_report: &'a CSPViolationReport,
+}
+
+/// A report as queued by the Reporting API, <https://w3c.github.io/reporting/#serialize-reports>.
+#[derive(Debug, Serialize)]
+struct Report<'a> {
+    age: u64,
+    #[serde(rename = "type")]
+    type_: &'static str,
+    url: &'a str,
+    user_agent: &'a str,
+    body: CSPViolationReportBody<'a>,
+}
+
+/// <https://w3c.github.io/webappsec-csp/#strip-url-for-use-in-reports>
+fn strip_url_for_use_in_reports(mut url: ServoUrl) -> String {
+    // Step 1.
+    if url.scheme() != "http" && url.scheme() != "https" {
+        return url.scheme().to_owned();
+    }
+    // Steps 2-4.
+    url.set_fragment(None);
+    let _ = url.set_username("");
+    let _ = url.set_password(None);
+    url.into_string()
+}
+
+impl CSPViolationReport {
+    /// <https://w3c.github.io/webappsec-csp/#create-violation-for-global>
+    fn new(global: &GlobalScope, violation: &Violation) -> CSPViolationReport {
+        let (blocked_uri, sample) = match violation.resource {
+            ViolationResource::Url(ref url) => (
+                strip_url_for_use_in_reports(ServoUrl::from_url(url.clone())),
+                None,
+            ),
+            ViolationResource::Inline { ref sample, .. } => ("inline".to_owned(), sample.clone()),
+        };
+        let sample = sample
+            .map(|sample| sample.chars().take(MAX_SAMPLE_LENGTH).collect())
+            .unwrap_or_default();
+        let referrer = global
+            .downcast::<Window>()
+            .map(|window| window.Document().Referrer().into())
+            .unwrap_or_default();
+        let original_policy = violation
+            .policy
+            .directive_set
+            .iter()
+            .map(|directive| {
+                let mut serialized = directive.name.clone();
+                for value in &directive.value {
+                    serialized.push(' ');
+                    serialized.push_str(value);
+                }
+                serialized
+            })
+            .collect::<Vec<_>>()
+            .join("; ");
+        let disposition = match violation.policy.disposition {
+            PolicyDisposition::Enforce => "enforce",
+            PolicyDisposition::Report => "report",
+        };
+        let document_uri = strip_url_for_use_in_reports(global.get_url());
+        CSPViolationReport {
+            source_file: match violation.resource {
+                ViolationResource::Inline { .. } => document_uri.clone(),
+                ViolationResource::Url(_) => String::new(),
+            },
+            document_uri,
+            referrer,
+            blocked_uri,
+            effective_directive: violation.directive.name.clone(),
+            violated_directive: violation.directive.name.clone(),
+            original_policy,
+            disposition: disposition.to_owned(),
+            status_code: 0,
+            sample,
+            line_number: 0,
+            column_number: 0,
+        }
+    }
+
+    fn event_init(&self) -> SecurityPolicyViolationEventInit {
+        SecurityPolicyViolationEventInit {
+            parent: EventInit {
+                bubbles: true,
+                cancelable: false,
+            },
+            documentURI: USVString(self.document_uri.clone()),
+            referrer: USVString(self.referrer.clone()),
+            blockedURI: USVString(self.blocked_uri.clone()),
+            effectiveDirective: DOMString::from(self.effective_directive.clone()),
+            violatedDirective: DOMString::from(self.violated_directive.clone()),
+            originalPolicy: DOMString::from(self.original_policy.clone()),
+            sourceFile: USVString(self.source_file.clone()),
+            sample: DOMString::from(self.sample.clone()),
+            disposition: if self.disposition == "report" {
+                SecurityPolicyViolationEventDisposition::Report
+            } else {
+                SecurityPolicyViolationEventDisposition::Enforce
+            },
+            statusCode: self.status_code,
+            lineNumber: self.line_number,
+            columnNumber: self.column_number,
+        }
+    }
+
+    fn report_to_body(&self, user_agent: &str) -> Vec<u8> {
+        let report = Report {
+            age: 0,
+            type_: "csp-violation",
+            url: &self.document_uri,
+            user_agent,
+            body: CSPViolationReportBody {
+                document_url: &self.document_uri,
+                referrer: &self.referrer,
+                blocked_url: &self.blocked_uri,
+                effective_directive: &self.effective_directive,
+                original_policy: &self.original_policy,
+                source_file: &self.source_file,
+                sample: &self.sample,
+                disposition: &self.disposition,
+                status_code: self.status_code,
+                line_number: self.line_number,
+                column_number: self.column_number,
+            },
+        };
+        serde_json::to_vec(&[report]).unwrap_or_default()
+    }
+
+    fn report_uri_body(&self) -> Vec<u8> {
+        serde_json::to_vec(&CSPReport { csp_report: self }).unwrap_or_default()
+    }
+}
+
+/// <https://w3c.github.io/webappsec-csp/#report-violation>
+pub fn report_violations(
+    global: &GlobalScope,
+    violations: Vec<Violation>,
+    element: Option<&Element>,
+) {
+    for violation in violations {
+        let report = CSPViolationReport::new(global, &violation);
+
+        // Steps 1-3.
+        let target = match element {
+            Some(element) if element.upcast::<Node>().is_connected() => {
+                DomRoot::from_ref(element.upcast::<EventTarget>())
+            },
+            _ => match global.downcast::<Window>() {
+                Some(window) => DomRoot::from_ref(window.Document().upcast::<EventTarget>()),
+                None => DomRoot::from_ref(global.upcast::<EventTarget>()),
+            },
+        };
+        fire_violation_event(global, &target, report.clone());
+
+        // Step 4.
+        send_violation_reports(global, &violation, &report);
+    }
+}
+
+/// <https://w3c.github.io/webappsec-csp/#report-violation>, step 3.2.
+fn fire_violation_event(global: &GlobalScope, target: &EventTarget, report: CSPViolationReport) {
+    let target = Trusted::new(target);
+    let _ = global.dom_manipulation_task_source().queue(
+        task!(fire_security_policy_violation_event: move || {
+            let target = target.root();
+            let event = SecurityPolicyViolationEvent::new(
+                &target.global(),
+                atom!("securitypolicyviolation"),
+                EventBubbles::Bubbles,
+                EventCancelable::NotCancelable,
+                &report.event_init(),
+            );
+            event.upcast::<Event>().fire(&target);
+        }),
+        global.upcast(),
+    );
+}
+
+/// <https://w3c.github.io/webappsec-csp/#report-violation>, step 3.4.
+fn send_violation_reports(
+    global: &GlobalScope,
+    violation: &Violation,
+    report: &CSPViolationReport,
+) {
+    let directive_values = |name: &str| {
+        violation
+            .policy
+            .directive_set
+            .iter()
+            .find(|directive| directive.name == name)
+            .map(|directive| directive.value.clone())
+    };
+
+    // `report-to` takes precedence over the deprecated `report-uri`.
+    if let Some(group) = directive_values("report-to").and_then(|values| values.first().cloned()) {
+        if let Some(endpoint) = global.reporting_endpoint(&group) {
+            let body = report.report_to_body(&global.get_user_agent());
+            send_report(global, endpoint, "application/reports+json", body);
+        }
+        return;
+    }
+
+    if let Some(urls) = directive_values("report-uri") {
+        let body = report.report_uri_body();
+        let base_url = global.api_base_url();
+        for url in urls {
+            match base_url.join(&url) {
+                Ok(endpoint) => {
+                    send_report(global, endpoint, "application/csp-report", body.clone())
+                },
+                Err(_) => warn!("Invalid report-uri {}", url),
+            }
+        }
+    }
+}
+
+fn send_report(global: &GlobalScope, url: ServoUrl, content_type: &str, body: Vec<u8>) {
+    let mut headers = HeaderMap::new();
+    headers.typed_insert(ContentType::from(
+        content_type.parse::<mime::Mime>().unwrap(),
+    ));
+    let request = RequestBuilder::new(url)
+        .method(Method::POST)
+        .headers(headers)
+        .body(Some(body))
+        .destination(Destination::Report)
+        .mode(RequestMode::NoCors)
+        .credentials_mode(CredentialsMode::CredentialsSameOrigin)
+        .origin(global.origin().immutable().clone())
+        .pipeline_id(Some(global.pipeline_id()));
+    let _ = global
+        .core_resource_thread()
+        .send(CoreResourceMsg::Fetch(request, FetchChannels::Prefetch));
+}
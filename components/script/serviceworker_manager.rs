@@ -6,6 +6,9 @@
 //! It also stores an active workers map, which holds descriptors of running service workers.
 //! If an active service worker timeouts, then it removes the descriptor entry from its
 //! active_workers map
+//!
+//! It drives each registration through its lifecycle, and hands the fetches of the
+//! clients that a service worker controls to that worker.
 
 use crate::dom::abstractworker::WorkerScriptMsg;
 use crate::dom::serviceworkerglobalscope::{ServiceWorkerGlobalScope, ServiceWorkerScriptMsg};
@@ -14,8 +17,11 @@ use crossbeam_channel::{unbounded, Receiver, RecvError, Sender};
 use devtools_traits::{DevtoolsPageInfo, ScriptToDevtoolsControlMsg};
 use ipc_channel::ipc::{self, IpcSender};
 use ipc_channel::router::ROUTER;
+use msg::constellation_msg::PipelineId;
+use net_traits::request::RequestMode;
 use net_traits::{CoreResourceMsg, CustomResponseMediator};
-use script_traits::{DOMMessage, SWManagerMsg, SWManagerSenders, ScopeThings, ServiceWorkerMsg};
+use script_traits::{DOMMessage, SWManagerMsg, SWManagerSenders, ScopeThings};
+use script_traits::{ServiceWorkerClientInfo, ServiceWorkerLifecycleState, ServiceWorkerMsg};
 use servo_config::pref;
 use servo_url::ServoUrl;
 use std::collections::HashMap;
@@ -26,17 +32,27 @@ enum Message {
     FromConstellation(ServiceWorkerMsg),
 }
 
+/// A registered service worker, and where it is in its lifecycle.
+struct Registration {
+    scope_things: ScopeThings,
+    state: ServiceWorkerLifecycleState,
+}
+
 pub struct ServiceWorkerManager {
     // map of registered service worker descriptors
-    registered_workers: HashMap<ServoUrl, ScopeThings>,
+    registered_workers: HashMap<ServoUrl, Registration>,
     // map of active service worker descriptors
     active_workers: HashMap<ServoUrl, Sender<ServiceWorkerScriptMsg>>,
+    // the scope of the service worker controlling each client
+    controlled_clients: HashMap<PipelineId, ServoUrl>,
     // own sender to send messages here
     own_sender: IpcSender<ServiceWorkerMsg>,
     // receiver to receive messages from constellation
     own_port: Receiver<ServiceWorkerMsg>,
     // to receive resource messages
     resource_receiver: Receiver<CustomResponseMediator>,
+    // to send messages to the constellation
+    constellation_sender: IpcSender<SWManagerMsg>,
 }
 
 impl ServiceWorkerManager {
@@ -44,13 +60,16 @@ impl ServiceWorkerManager {
         own_sender: IpcSender<ServiceWorkerMsg>,
         from_constellation_receiver: Receiver<ServiceWorkerMsg>,
         resource_port: Receiver<CustomResponseMediator>,
+        constellation_sender: IpcSender<SWManagerMsg>,
     ) -> ServiceWorkerManager {
         ServiceWorkerManager {
             registered_workers: HashMap::new(),
             active_workers: HashMap::new(),
+            controlled_clients: HashMap::new(),
             own_sender: own_sender,
             own_port: from_constellation_receiver,
             resource_receiver: resource_port,
+            constellation_sender,
         }
     }
 
@@ -66,30 +85,44 @@ impl ServiceWorkerManager {
         let _ = sw_senders
             .swmanager_sender
             .send(SWManagerMsg::OwnSender(own_sender.clone()));
+        let constellation_sender = sw_senders.swmanager_sender;
         thread::Builder::new()
             .name("ServiceWorkerManager".to_owned())
             .spawn(move || {
-                ServiceWorkerManager::new(own_sender, from_constellation, resource_port)
-                    .handle_message();
+                ServiceWorkerManager::new(
+                    own_sender,
+                    from_constellation,
+                    resource_port,
+                    constellation_sender,
+                )
+                .handle_message();
             })
             .expect("Thread spawning failed");
     }
 
+    /// The longest scope of an active service worker that `load_url` is in.
+    /// <https://w3c.github.io/ServiceWorker/#scope-match-algorithm>
     pub fn get_matching_scope(&self, load_url: &ServoUrl) -> Option<ServoUrl> {
-        for scope in self.registered_workers.keys() {
-            if longest_prefix_match(&scope, load_url) {
-                return Some(scope.clone());
-            }
-        }
-        None
+        self.registered_workers
+            .iter()
+            .filter(|&(scope, registration)| {
+                registration.state == ServiceWorkerLifecycleState::Activated &&
+                    longest_prefix_match(scope, load_url)
+            })
+            .max_by_key(|&(scope, _)| scope.as_str().len())
+            .map(|(scope, _)| scope.clone())
     }
 
     pub fn wakeup_serviceworker(
         &mut self,
         scope_url: ServoUrl,
     ) -> Option<Sender<ServiceWorkerScriptMsg>> {
-        let scope_things = self.registered_workers.get(&scope_url);
-        if let Some(scope_things) = scope_things {
+        let registration = self.registered_workers.get(&scope_url);
+        if let Some(&Registration {
+            ref scope_things,
+            state,
+        }) = registration
+        {
             let (sender, receiver) = unbounded();
             let (devtools_sender, devtools_receiver) = ipc::channel().unwrap();
             if let Some(ref chan) = scope_things.devtools_chan {
@@ -111,6 +144,7 @@ impl ServiceWorkerManager {
                 devtools_receiver,
                 self.own_sender.clone(),
                 scope_url.clone(),
+                state,
             );
             // We store the activated worker
             self.active_workers.insert(scope_url, sender.clone());
@@ -133,6 +167,52 @@ impl ServiceWorkerManager {
         }
     }
 
+    /// Send a message to the service worker of a scope, waking it up if needed.
+    fn send_to_worker(&mut self, scope_url: ServoUrl, msg: ServiceWorkerScriptMsg) {
+        let sender = match self.active_workers.get(&scope_url) {
+            Some(sender) => Some(sender.clone()),
+            None => self.wakeup_serviceworker(scope_url),
+        };
+        if let Some(sender) = sender {
+            let _ = sender.send(msg);
+        }
+    }
+
+    /// Record the new state of the service worker of a scope, and tell the documents.
+    fn update_state(&mut self, scope_url: &ServoUrl, state: ServiceWorkerLifecycleState) {
+        if let Some(registration) = self.registered_workers.get_mut(scope_url) {
+            registration.state = state;
+        }
+        let _ = self
+            .constellation_sender
+            .send(SWManagerMsg::UpdateState(scope_url.clone(), state));
+    }
+
+    /// Make the service worker of a scope control a client.
+    fn set_controller(&mut self, client: PipelineId, scope_url: ServoUrl, claimed: bool) {
+        let script_url = match self.registered_workers.get(&scope_url) {
+            Some(registration) => registration.scope_things.script_url.clone(),
+            None => return,
+        };
+        self.controlled_clients.insert(client, scope_url.clone());
+        let _ = self.constellation_sender.send(SWManagerMsg::SetController(
+            client, scope_url, script_url, claimed,
+        ));
+    }
+
+    /// The windows in a scope, according to the constellation.
+    fn clients_in_scope(&self, scope_url: &ServoUrl) -> Vec<ServiceWorkerClientInfo> {
+        let (sender, receiver) = match ipc::channel() {
+            Ok(channel) => channel,
+            Err(_) => return vec![],
+        };
+        let msg = SWManagerMsg::MatchClients(scope_url.clone(), sender);
+        if self.constellation_sender.send(msg).is_err() {
+            return vec![];
+        }
+        receiver.recv().unwrap_or_default()
+    }
+
     fn forward_message(&self, msg: DOMMessage, sender: &Sender<ServiceWorkerScriptMsg>) {
         let DOMMessage { origin, data } = msg;
         let _ = sender.send(ServiceWorkerScriptMsg::CommonWorker(
@@ -146,10 +226,68 @@ impl ServiceWorkerManager {
                 if self.registered_workers.contains_key(&scope) {
                     warn!("ScopeThings for {:?} already stored in SW-Manager", scope);
                 } else {
-                    self.registered_workers.insert(scope, scope_things);
+                    // https://w3c.github.io/ServiceWorker/#install-algorithm
+                    let registration = Registration {
+                        scope_things,
+                        state: ServiceWorkerLifecycleState::Installing,
+                    };
+                    self.registered_workers.insert(scope.clone(), registration);
+                    self.update_state(&scope, ServiceWorkerLifecycleState::Installing);
+                    self.send_to_worker(scope, ServiceWorkerScriptMsg::Install);
                 }
                 true
             },
+            ServiceWorkerMsg::InstallFinished(scope, installed) => {
+                if !installed {
+                    self.update_state(&scope, ServiceWorkerLifecycleState::Redundant);
+                    self.registered_workers.remove(&scope);
+                    self.active_workers.remove(&scope);
+                    return true;
+                }
+                self.update_state(&scope, ServiceWorkerLifecycleState::Installed);
+                // A registration only ever has one worker here, so there is no
+                // active worker for this one to wait for.
+                // https://w3c.github.io/ServiceWorker/#activation-algorithm
+                self.update_state(&scope, ServiceWorkerLifecycleState::Activating);
+                self.send_to_worker(scope, ServiceWorkerScriptMsg::Activate);
+                true
+            },
+            ServiceWorkerMsg::ActivateFinished(scope) => {
+                self.update_state(&scope, ServiceWorkerLifecycleState::Activated);
+                true
+            },
+            ServiceWorkerMsg::MatchClients(scope, include_uncontrolled, sender) => {
+                let clients = self
+                    .clients_in_scope(&scope)
+                    .into_iter()
+                    .filter(|client| {
+                        include_uncontrolled ||
+                            self.controlled_clients.get(&client.pipeline_id) == Some(&scope)
+                    })
+                    .collect();
+                let _ = sender.send(clients);
+                true
+            },
+            ServiceWorkerMsg::Claim(scope, sender) => {
+                // https://w3c.github.io/ServiceWorker/#clients-claim
+                let active = match self.registered_workers.get(&scope) {
+                    Some(registration) => match registration.state {
+                        ServiceWorkerLifecycleState::Activating |
+                        ServiceWorkerLifecycleState::Activated => true,
+                        _ => false,
+                    },
+                    None => false,
+                };
+                if active {
+                    for client in self.clients_in_scope(&scope) {
+                        if self.controlled_clients.get(&client.pipeline_id) != Some(&scope) {
+                            self.set_controller(client.pipeline_id, scope.clone(), true);
+                        }
+                    }
+                }
+                let _ = sender.send(active);
+                true
+            },
             ServiceWorkerMsg::Timeout(scope) => {
                 if self.active_workers.contains_key(&scope) {
                     let _ = self.active_workers.remove(&scope);
@@ -174,23 +312,35 @@ impl ServiceWorkerManager {
         }
     }
 
+    /// <https://w3c.github.io/ServiceWorker/#on-fetch-request-algorithm>
     fn handle_message_from_resource(&mut self, mediator: CustomResponseMediator) -> bool {
-        if serviceworker_enabled() {
-            if let Some(scope) = self.get_matching_scope(&mediator.load_url) {
-                if self.active_workers.contains_key(&scope) {
-                    if let Some(sender) = self.active_workers.get(&scope) {
-                        let _ = sender.send(ServiceWorkerScriptMsg::Response(mediator));
-                    }
-                } else {
-                    if let Some(sender) = self.wakeup_serviceworker(scope) {
-                        let _ = sender.send(ServiceWorkerScriptMsg::Response(mediator));
-                    }
+        if !serviceworker_enabled() {
+            let _ = mediator.response_chan.send(Ok(None));
+            return true;
+        }
+        let scope = if mediator.mode == RequestMode::Navigate {
+            // The document that the navigation creates is controlled by the
+            // service worker whose scope it is in, if any.
+            let scope = self.get_matching_scope(&mediator.load_url);
+            if let Some(client) = mediator.client {
+                match scope {
+                    Some(ref scope) => self.set_controller(client, scope.clone(), false),
+                    None => {
+                        self.controlled_clients.remove(&client);
+                    },
                 }
-            } else {
-                let _ = mediator.response_chan.send(None);
             }
+            scope
         } else {
-            let _ = mediator.response_chan.send(None);
+            mediator
+                .client
+                .and_then(|client| self.controlled_clients.get(&client).cloned())
+        };
+        match scope {
+            Some(scope) => self.send_to_worker(scope, ServiceWorkerScriptMsg::Response(mediator)),
+            None => {
+                let _ = mediator.response_chan.send(Ok(None));
+            },
         }
         true
     }
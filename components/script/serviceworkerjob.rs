@@ -20,6 +20,7 @@ use crate::dom::urlhelper::UrlHelper;
 use crate::script_thread::ScriptThread;
 use crate::task_source::dom_manipulation::DOMManipulationTaskSource;
 use crate::task_source::TaskSource;
+use script_traits::ServiceWorkerLifecycleState;
 use servo_url::ServoUrl;
 use std::cmp::PartialEq;
 use std::collections::HashMap;
@@ -205,7 +206,12 @@ impl JobQueue {
             }
         } else {
             // Step 6.1
-            let new_reg = ServiceWorkerRegistration::new(&*global, &job.script_url, scope_url);
+            let new_reg = ServiceWorkerRegistration::new(
+                &*global,
+                &job.script_url,
+                scope_url,
+                ServiceWorkerLifecycleState::Installing,
+            );
             script_thread.handle_serviceworker_registration(&job.scope_url, &*new_reg, pipeline_id);
         }
         // Step 7
@@ -280,8 +286,7 @@ impl JobQueue {
             return;
         }
         // Step 8
-        if let Some(newest_worker) = newest_worker {
-            job.client.set_controller(&*newest_worker);
+        if newest_worker.is_some() {
             // Step 8.1
             resolve_job_promise(
                 job,
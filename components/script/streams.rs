@@ -0,0 +1,520 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Abstract operations shared by the interfaces of the Streams standard.
+//! <https://streams.spec.whatwg.org/>
+
+use crate::dom::bindings::callback::ExceptionHandling;
+use crate::dom::bindings::codegen::Bindings::QueuingStrategyBinding::{
+    QueuingStrategy, QueuingStrategySize,
+};
+use crate::dom::bindings::error::{Error, Fallible};
+use crate::dom::bindings::reflector::DomObject;
+use crate::dom::bindings::trace::JSTraceable;
+use crate::dom::bindings::utils::{get_dictionary_property, set_dictionary_property};
+use crate::dom::globalscope::GlobalScope;
+use crate::dom::promise::Promise;
+use crate::dom::promisenativehandler::{Callback, PromiseNativeHandler};
+use crate::realms::enter_realm;
+use crate::script_runtime::JSContext as SafeJSContext;
+use js::jsapi::{Heap, JSContext, JSObject, JSTracer, JS_ClearPendingException};
+use js::jsapi::{JS_GetArrayBufferViewBuffer, JS_GetArrayBufferViewByteLength};
+use js::jsapi::{JS_GetArrayBufferViewByteOffset, JS_GetArrayBufferViewType};
+use js::jsapi::{JS_NewDataView, JS_NewFloat32ArrayWithBuffer, JS_NewFloat64ArrayWithBuffer};
+use js::jsapi::{JS_NewInt16ArrayWithBuffer, JS_NewInt32ArrayWithBuffer};
+use js::jsapi::{JS_NewInt8ArrayWithBuffer, JS_NewPlainObject};
+use js::jsapi::{JS_NewUint16ArrayWithBuffer, JS_NewUint32ArrayWithBuffer};
+use js::jsapi::{JS_NewUint8ArrayWithBuffer, JS_NewUint8ClampedArrayWithBuffer, Type};
+use js::jsval::{BooleanValue, JSVal, ObjectValue, UndefinedValue};
+use js::rust::wrappers::JS_GetPendingException;
+use js::rust::{HandleObject, HandleValue, MutableHandleValue};
+use js::typedarray::{ArrayBufferView, CreateWith, Uint8Array};
+use malloc_size_of::{MallocSizeOf, MallocSizeOfOps};
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::ptr;
+use std::rc::Rc;
+
+/// <https://streams.spec.whatwg.org/#value-with-size>
+#[derive(JSTraceable, MallocSizeOf)]
+struct ValueWithSize {
+    /// `None` for the close sentinel of a writable stream.
+    #[ignore_malloc_size_of = "Defined in rust-mozjs"]
+    value: Option<Box<Heap<JSVal>>>,
+    size: f64,
+}
+
+/// The queue of chunks of a stream controller, along with their total size.
+/// <https://streams.spec.whatwg.org/#queue-with-sizes>
+#[derive(Default, JSTraceable, MallocSizeOf)]
+pub struct QueueWithSizes {
+    queue: VecDeque<ValueWithSize>,
+    total_size: f64,
+}
+
+impl QueueWithSizes {
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    pub fn total_size(&self) -> f64 {
+        self.total_size
+    }
+
+    /// <https://streams.spec.whatwg.org/#enqueue-value-with-size>
+    pub fn enqueue_value_with_size(&mut self, value: HandleValue, size: f64) -> Fallible<()> {
+        // Step 3.
+        if !size.is_finite() || size < 0. {
+            return Err(Error::Range(
+                "The size of a chunk must be a finite, non-negative number".to_owned(),
+            ));
+        }
+
+        // Steps 4-5.
+        self.queue.push_back(ValueWithSize {
+            value: Some(Heap::boxed(value.get())),
+            size,
+        });
+        self.total_size += size;
+        Ok(())
+    }
+
+    /// Enqueue the close sentinel of a writable stream, whose size is 0.
+    pub fn enqueue_close_sentinel(&mut self) {
+        self.queue.push_back(ValueWithSize {
+            value: None,
+            size: 0.,
+        });
+    }
+
+    /// <https://streams.spec.whatwg.org/#dequeue-value>
+    pub fn dequeue_value(&mut self, mut rval: MutableHandleValue) {
+        // Steps 3-4.
+        let pair = self
+            .queue
+            .pop_front()
+            .expect("Dequeued from an empty queue");
+
+        // Steps 5-6.
+        self.total_size -= pair.size;
+        if self.total_size < 0. {
+            self.total_size = 0.;
+        }
+
+        // Step 7.
+        rval.set(pair.value.map_or(UndefinedValue(), |value| value.get()));
+    }
+
+    /// Whether the value at the front of the queue is the close sentinel.
+    pub fn peek_is_close_sentinel(&self) -> bool {
+        self.queue
+            .front()
+            .map_or(false, |pair| pair.value.is_none())
+    }
+
+    /// <https://streams.spec.whatwg.org/#peek-queue-value>
+    pub fn peek_queue_value(&self, mut rval: MutableHandleValue) {
+        let pair = self.queue.front().expect("Peeked at an empty queue");
+        rval.set(
+            pair.value
+                .as_ref()
+                .map_or(UndefinedValue(), |value| value.get()),
+        );
+    }
+
+    /// <https://streams.spec.whatwg.org/#reset-queue>
+    pub fn reset(&mut self) {
+        self.queue.clear();
+        self.total_size = 0.;
+    }
+}
+
+/// <https://streams.spec.whatwg.org/#validate-and-normalize-high-water-mark>
+pub fn extract_high_water_mark(strategy: &QueuingStrategy, default_hwm: f64) -> Fallible<f64> {
+    // Steps 1-2.
+    let high_water_mark = match strategy.highWaterMark {
+        Some(high_water_mark) => high_water_mark,
+        None => return Ok(default_hwm),
+    };
+
+    // Step 4.
+    if high_water_mark.is_nan() || high_water_mark < 0. {
+        return Err(Error::Range(
+            "The high water mark must be a non-negative number".to_owned(),
+        ));
+    }
+
+    // Step 5.
+    Ok(high_water_mark)
+}
+
+/// <https://streams.spec.whatwg.org/#make-size-algorithm-from-size-function>,
+/// where a missing size function stands for an algorithm that returns 1.
+/// An exception thrown by the size function is left pending on the context.
+pub fn chunk_size(size: Option<&Rc<QueuingStrategySize>>, chunk: HandleValue) -> Fallible<f64> {
+    match size {
+        Some(size) => size.Call__(chunk, ExceptionHandling::Rethrow),
+        None => Ok(1.),
+    }
+}
+
+/// Store the JS value of `error` in `rval`, taking the pending exception of `cx`
+/// for `Error::JSFailed`.
+#[allow(unsafe_code)]
+pub fn error_to_jsval(
+    cx: SafeJSContext,
+    global: &GlobalScope,
+    error: Error,
+    mut rval: MutableHandleValue,
+) {
+    match error {
+        Error::JSFailed => unsafe {
+            rval.set(UndefinedValue());
+            JS_GetPendingException(*cx, rval);
+            JS_ClearPendingException(*cx);
+        },
+        error => unsafe { error.to_jsval(*cx, global, rval) },
+    }
+}
+
+/// A promise resolved with undefined.
+pub fn resolved_promise(global: &GlobalScope) -> Rc<Promise> {
+    let promise = Promise::new(global);
+    promise.resolve_native(&());
+    promise
+}
+
+/// A promise rejected with `reason`.
+pub fn rejected_promise(global: &GlobalScope, reason: HandleValue) -> Rc<Promise> {
+    let promise = Promise::new(global);
+    let _ac = enter_realm(global);
+    promise.reject(global.get_cx(), reason);
+    promise
+}
+
+/// A promise rejected with `error`.
+pub fn rejected_promise_with_error(global: &GlobalScope, error: Error) -> Rc<Promise> {
+    let promise = Promise::new(global);
+    promise.reject_error(error);
+    promise
+}
+
+/// A promise resolved with the result of calling an algorithm given by script,
+/// which is rejected with the exception if the call throws.
+#[allow(unrooted_must_root)]
+pub fn promise_from_call_result(global: &GlobalScope, result: Fallible<JSVal>) -> Rc<Promise> {
+    let cx = global.get_cx();
+    match result {
+        Ok(value) => {
+            rooted!(in(*cx) let value = value);
+            Promise::new_resolved(global, cx, value.handle())
+                .expect("Failed to create a resolved promise")
+        },
+        Err(error) => {
+            rooted!(in(*cx) let mut reason = UndefinedValue());
+            error_to_jsval(cx, global, error, reason.handle_mut());
+            rejected_promise(global, reason.handle())
+        },
+    }
+}
+
+/// Resolve `promise` with `other`, so that it settles the same way `other` does.
+#[allow(unrooted_must_root)]
+pub fn resolve_with_promise(promise: &Promise, other: &Promise) {
+    let cx = promise.global().get_cx();
+    let _ac = enter_realm(promise);
+    rooted!(in(*cx) let value = ObjectValue(other.promise_obj().get()));
+    promise.resolve(cx, value.handle());
+}
+
+/// Create the object with which a read from a stream resolves.
+/// <https://streams.spec.whatwg.org/#readablestreamreadresult>
+#[allow(unsafe_code)]
+pub fn create_read_result(
+    cx: SafeJSContext,
+    value: HandleValue,
+    done: bool,
+    mut rval: MutableHandleValue,
+) {
+    rooted!(in(*cx) let object = unsafe { JS_NewPlainObject(*cx) });
+    assert!(!object.is_null());
+    rooted!(in(*cx) let done = BooleanValue(done));
+    set_dictionary_property(*cx, object.handle(), "value", value)
+        .expect("Failed to set the value of a read result");
+    set_dictionary_property(*cx, object.handle(), "done", done.handle())
+        .expect("Failed to set the done flag of a read result");
+    rval.set(ObjectValue(object.get()));
+}
+
+/// Split an object created by `create_read_result` into its value, stored in
+/// `rval`, and whether the stream is done.
+pub fn read_result_parts(cx: SafeJSContext, result: HandleValue, rval: MutableHandleValue) -> bool {
+    if !result.is_object() {
+        return true;
+    }
+    rooted!(in(*cx) let object = result.to_object());
+    rooted!(in(*cx) let mut done = UndefinedValue());
+    let _ = get_dictionary_property(*cx, object.handle(), "value", rval);
+    let _ = get_dictionary_property(*cx, object.handle(), "done", done.handle_mut());
+    done.is_boolean() && done.to_boolean()
+}
+
+/// Store a new `Uint8Array` holding a copy of `bytes` in `rval`.
+#[allow(unsafe_code)]
+pub fn create_uint8_array(
+    cx: SafeJSContext,
+    bytes: &[u8],
+    mut rval: MutableHandleValue,
+) -> Fallible<()> {
+    rooted!(in(*cx) let mut array = ptr::null_mut::<JSObject>());
+    unsafe { Uint8Array::create(*cx, CreateWith::Slice(bytes), array.handle_mut()) }
+        .map_err(|_| Error::JSFailed)?;
+    rval.set(ObjectValue(array.get()));
+    Ok(())
+}
+
+/// A copy of the bytes of `value`, if it is a `Uint8Array`.
+#[allow(unsafe_code)]
+pub fn uint8_array_bytes(cx: SafeJSContext, value: HandleValue) -> Option<Vec<u8>> {
+    if !value.is_object() {
+        return None;
+    }
+    unsafe {
+        typedarray!(in(*cx) let array: Uint8Array = value.to_object());
+        array.ok().map(|array| array.to_vec())
+    }
+}
+
+/// A copy of the bytes that `view` looks at.
+#[allow(unsafe_code)]
+pub fn view_bytes(cx: SafeJSContext, view: HandleObject) -> Vec<u8> {
+    unsafe {
+        typedarray!(in(*cx) let view: ArrayBufferView = view.get());
+        view.map(|view| view.to_vec()).unwrap_or_default()
+    }
+}
+
+/// Copy `bytes` into `view`, starting `offset` bytes into the bytes it looks at.
+#[allow(unsafe_code)]
+pub fn write_into_view(cx: SafeJSContext, view: HandleObject, offset: usize, bytes: &[u8]) {
+    unsafe {
+        typedarray!(in(*cx) let view: ArrayBufferView = view.get());
+        if let Ok(mut view) = view {
+            view.as_mut_slice()[offset..offset + bytes.len()].copy_from_slice(bytes);
+        }
+    }
+}
+
+/// The size of an element of a view of type `view_type`, which is 1 for a `DataView`.
+pub fn element_size(view_type: Type) -> usize {
+    match view_type {
+        Type::Int16 | Type::Uint16 => 2,
+        Type::Int32 | Type::Uint32 | Type::Float32 => 4,
+        Type::Int64 | Type::Float64 => 8,
+        _ => 1,
+    }
+}
+
+/// The type of the elements of `view`.
+#[allow(unsafe_code)]
+pub fn view_type(view: HandleObject) -> Type {
+    unsafe { JS_GetArrayBufferViewType(view.get()) }
+}
+
+/// The offset in bytes of `view` into its buffer.
+#[allow(unsafe_code)]
+pub fn view_byte_offset(view: HandleObject) -> usize {
+    unsafe { JS_GetArrayBufferViewByteOffset(view.get()) as usize }
+}
+
+/// The number of bytes that `view` looks at.
+#[allow(unsafe_code)]
+pub fn view_byte_length(view: HandleObject) -> usize {
+    unsafe { JS_GetArrayBufferViewByteLength(view.get()) as usize }
+}
+
+/// Whether `view` and `other` are views on the same buffer.
+#[allow(unsafe_code)]
+pub fn same_buffer(cx: SafeJSContext, view: HandleObject, other: HandleObject) -> bool {
+    let mut is_shared = false;
+    rooted!(in(*cx) let buffer = unsafe {
+        JS_GetArrayBufferViewBuffer(*cx, view.into(), &mut is_shared)
+    });
+    rooted!(in(*cx) let other_buffer = unsafe {
+        JS_GetArrayBufferViewBuffer(*cx, other.into(), &mut is_shared)
+    });
+    !buffer.is_null() && buffer.get() == other_buffer.get()
+}
+
+/// Store in `rval` a view of type `view_type` on the buffer of `view`, which
+/// looks at `byte_length` bytes from `byte_offset` bytes into that buffer.
+#[allow(unsafe_code)]
+pub fn create_view(
+    cx: SafeJSContext,
+    view: HandleObject,
+    view_type: Type,
+    byte_offset: usize,
+    byte_length: usize,
+    mut rval: MutableHandleValue,
+) -> Fallible<()> {
+    let mut is_shared = false;
+    rooted!(in(*cx) let buffer = unsafe {
+        JS_GetArrayBufferViewBuffer(*cx, view.into(), &mut is_shared)
+    });
+    if buffer.is_null() {
+        return Err(Error::JSFailed);
+    }
+    let offset = byte_offset as u32;
+    let length = (byte_length / element_size(view_type)) as i32;
+    let buffer_handle = buffer.handle().into();
+    let object = unsafe {
+        match view_type {
+            Type::Int8 => JS_NewInt8ArrayWithBuffer(*cx, buffer_handle, offset, length),
+            Type::Uint8 => JS_NewUint8ArrayWithBuffer(*cx, buffer_handle, offset, length),
+            Type::Uint8Clamped => {
+                JS_NewUint8ClampedArrayWithBuffer(*cx, buffer_handle, offset, length)
+            },
+            Type::Int16 => JS_NewInt16ArrayWithBuffer(*cx, buffer_handle, offset, length),
+            Type::Uint16 => JS_NewUint16ArrayWithBuffer(*cx, buffer_handle, offset, length),
+            Type::Int32 => JS_NewInt32ArrayWithBuffer(*cx, buffer_handle, offset, length),
+            Type::Uint32 => JS_NewUint32ArrayWithBuffer(*cx, buffer_handle, offset, length),
+            Type::Float32 => JS_NewFloat32ArrayWithBuffer(*cx, buffer_handle, offset, length),
+            Type::Float64 => JS_NewFloat64ArrayWithBuffer(*cx, buffer_handle, offset, length),
+            _ => JS_NewDataView(*cx, buffer_handle, offset, byte_length as i32),
+        }
+    };
+    if object.is_null() {
+        return Err(Error::JSFailed);
+    }
+    rval.set(ObjectValue(object));
+    Ok(())
+}
+
+/// Steps that run once a promise settles, along with the object they act on.
+pub struct PromiseReaction<H: JSTraceable + 'static> {
+    holder: H,
+    steps: fn(&H, SafeJSContext, HandleValue),
+}
+
+#[allow(unsafe_code)]
+unsafe impl<H: JSTraceable + 'static> JSTraceable for PromiseReaction<H> {
+    unsafe fn trace(&self, trc: *mut JSTracer) {
+        self.holder.trace(trc);
+    }
+}
+
+impl<H: JSTraceable + 'static> MallocSizeOf for PromiseReaction<H> {
+    fn size_of(&self, _ops: &mut MallocSizeOfOps) -> usize {
+        0
+    }
+}
+
+impl<H: JSTraceable + 'static> Callback for PromiseReaction<H> {
+    #[allow(unsafe_code)]
+    fn callback(&self, cx: *mut JSContext, v: HandleValue) {
+        (self.steps)(&self.holder, unsafe { SafeJSContext::from_ptr(cx) }, v)
+    }
+}
+
+/// Run `on_fulfilled` or `on_rejected` with `holder` once `promise` settles.
+/// <https://webidl.spec.whatwg.org/#dfn-perform-steps-once-promise-is-settled>
+pub fn react_to_promise<H: Clone + JSTraceable + 'static>(
+    promise: &Promise,
+    holder: H,
+    on_fulfilled: fn(&H, SafeJSContext, HandleValue),
+    on_rejected: fn(&H, SafeJSContext, HandleValue),
+) {
+    let global = promise.global();
+    let handler = PromiseNativeHandler::new(
+        &global,
+        Some(Box::new(PromiseReaction {
+            holder: holder.clone(),
+            steps: on_fulfilled,
+        })),
+        Some(Box::new(PromiseReaction {
+            holder,
+            steps: on_rejected,
+        })),
+    );
+    let _ac = enter_realm(promise);
+    promise.append_native_handler(&handler);
+}
+
+/// Steps for `react_to_promise` that do nothing, which also mark a
+/// rejected promise as handled.
+pub fn ignore<T: ?Sized>(_: &T, _: SafeJSContext, _: HandleValue) {}
+
+fn resolve_with_undefined(promise: &Rc<Promise>, _cx: SafeJSContext, _v: HandleValue) {
+    promise.resolve_native(&());
+}
+
+fn reject_with_reason(promise: &Rc<Promise>, cx: SafeJSContext, reason: HandleValue) {
+    let _ac = enter_realm(&**promise);
+    promise.reject(cx, reason);
+}
+
+/// A promise that is fulfilled with undefined once `promise` is fulfilled, and
+/// rejected with the same reason as `promise` otherwise.
+#[allow(unrooted_must_root)]
+pub fn transform_to_undefined(promise: &Promise) -> Rc<Promise> {
+    let result = Promise::new(&promise.global());
+    react_to_promise(
+        promise,
+        result.clone(),
+        resolve_with_undefined,
+        reject_with_reason,
+    );
+    result
+}
+
+/// Stop the rejection of `promise` from being reported as unhandled.
+pub fn mark_as_handled(promise: &Promise) {
+    let handler = PromiseNativeHandler::new(&promise.global(), None, None);
+    let _ac = enter_realm(promise);
+    promise.append_native_handler(&handler);
+}
+
+/// The state of a promise that waits for a number of other promises.
+#[derive(JSTraceable, MallocSizeOf)]
+struct WaitForAll {
+    remaining: Cell<usize>,
+    #[ignore_malloc_size_of = "Rc"]
+    promise: Rc<Promise>,
+}
+
+impl WaitForAll {
+    fn fulfilled(state: &Rc<WaitForAll>, _cx: SafeJSContext, _v: HandleValue) {
+        state.remaining.set(state.remaining.get() - 1);
+        if state.remaining.get() == 0 {
+            state.promise.resolve_native(&());
+        }
+    }
+
+    fn rejected(state: &Rc<WaitForAll>, cx: SafeJSContext, reason: HandleValue) {
+        reject_with_reason(&state.promise, cx, reason);
+    }
+}
+
+/// A promise that is fulfilled once all of `promises` are fulfilled, and
+/// rejected with the reason of the first of them to be rejected otherwise.
+/// <https://webidl.spec.whatwg.org/#wait-for-all>
+#[allow(unrooted_must_root)]
+pub fn wait_for_all(global: &GlobalScope, promises: Vec<Rc<Promise>>) -> Rc<Promise> {
+    if promises.is_empty() {
+        return resolved_promise(global);
+    }
+    let state = Rc::new(WaitForAll {
+        remaining: Cell::new(promises.len()),
+        promise: Promise::new(global),
+    });
+    for promise in &promises {
+        react_to_promise(
+            promise,
+            state.clone(),
+            WaitForAll::fulfilled,
+            WaitForAll::rejected,
+        );
+    }
+    state.promise.clone()
+}
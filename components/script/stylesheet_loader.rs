@@ -18,6 +18,7 @@ use crate::dom::performanceresourcetiming::InitiatorType;
 use crate::dom::shadowroot::ShadowRoot;
 use crate::fetch::create_a_potential_cors_request;
 use crate::network_listener::{self, NetworkListener, PreInvoke, ResourceTimingListener};
+use content_security_policy as csp;
 use cssparser::SourceLocation;
 use encoding_rs::UTF_8;
 use ipc_channel::ipc;
@@ -233,6 +234,12 @@ impl FetchResponseListener for StylesheetContext {
     fn submit_resource_timing(&mut self) {
         network_listener::submit_timing(self)
     }
+
+    fn process_csp_violations(&mut self, violations: Vec<csp::Violation>) {
+        let global = &self.resource_timing_global();
+        let elem = self.elem.root();
+        global.report_csp_violations(violations, Some(elem.upcast()));
+    }
 }
 
 impl ResourceTimingListener for StylesheetContext {
@@ -327,7 +334,12 @@ impl<'a> StylesheetLoader<'a> {
             Referrer::ReferrerUrl(document.url()),
             referrer_policy,
             integrity_metadata,
-        );
+        )
+        .cryptographic_nonce_metadata(String::from(
+            self.elem
+                .upcast::<Element>()
+                .get_string_attribute(&local_name!("nonce")),
+        ));
 
         document.fetch_async(LoadType::Stylesheet(url), request, action_sender);
     }
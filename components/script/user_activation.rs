@@ -0,0 +1,49 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Tracking whether the user recently interacted with a window, which gates
+//! APIs such as clipboard writes.
+//! <https://html.spec.whatwg.org/multipage/#tracking-user-activation>
+
+use keyboard_types::{Key, KeyState, KeyboardEvent};
+use std::cell::Cell;
+use std::time::{Duration, Instant};
+
+/// How long a window keeps transient activation after the user interacted with it.
+/// <https://html.spec.whatwg.org/multipage/#transient-activation-duration>
+pub const TRANSIENT_ACTIVATION_DURATION: Duration = Duration::from_secs(5);
+
+/// The user activation of a window.
+#[derive(Default, JSTraceable, MallocSizeOf)]
+pub struct UserActivation {
+    /// <https://html.spec.whatwg.org/multipage/#last-activation-timestamp>
+    last_activation_timestamp: Cell<Option<Instant>>,
+}
+
+impl UserActivation {
+    pub fn new() -> UserActivation {
+        UserActivation::default()
+    }
+
+    /// Record that an activation triggering input event was fired at `timestamp`.
+    /// <https://html.spec.whatwg.org/multipage/#activation-notification>
+    pub fn notify(&self, timestamp: Instant) {
+        self.last_activation_timestamp.set(Some(timestamp));
+    }
+
+    /// <https://html.spec.whatwg.org/multipage/#transient-activation>
+    pub fn is_transient_at(&self, now: Instant) -> bool {
+        self.last_activation_timestamp
+            .get()
+            .and_then(|timestamp| now.checked_duration_since(timestamp))
+            .map_or(false, |elapsed| elapsed < TRANSIENT_ACTIVATION_DURATION)
+    }
+}
+
+/// Whether a key event is an activation triggering input event, which is the case
+/// of every keydown event but the ones of the Escape key.
+/// <https://html.spec.whatwg.org/multipage/#activation-triggering-input-event>
+pub fn is_activation_triggering_key_event(event: &KeyboardEvent) -> bool {
+    event.state == KeyState::Down && event.key != Key::Escape
+}
@@ -12,6 +12,7 @@ use ipc_channel::ipc::{IpcReceiver, IpcSender};
 use metrics::PaintTimeMetrics;
 use msg::constellation_msg::{BackgroundHangMonitorRegister, BrowsingContextId, PipelineId};
 use net_traits::image_cache::ImageCache;
+use net_traits::request::CspContext;
 use profile_traits::mem::ReportsChan;
 use script_traits::Painter;
 use script_traits::{ConstellationControlMsg, LayoutControlMsg, LayoutMsg as ConstellationMsg};
@@ -88,6 +89,9 @@ pub enum Msg {
     /// Set the final Url.
     SetFinalUrl(ServoUrl),
 
+    /// Set the content security policies that web fonts are fetched under.
+    SetCspContext(CspContext),
+
     /// Tells layout about the new scrolling offsets of each scrollable stacking context.
     SetScrollStates(Vec<ScrollState>),
 
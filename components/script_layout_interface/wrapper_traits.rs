@@ -191,6 +191,10 @@ pub trait ThreadSafeLayoutNode:
     /// the parent until all the children have been processed.
     fn parent_style(&self) -> Arc<ComputedValues>;
 
+    /// Returns the `::selection` style of the parent, which is what selected text is
+    /// highlighted with.
+    fn parent_selected_style(&self) -> Arc<ComputedValues>;
+
     fn get_before_pseudo(&self) -> Option<Self> {
         self.as_element()
             .and_then(|el| el.get_before_pseudo())
@@ -247,8 +251,7 @@ pub trait ThreadSafeLayoutNode:
             el.selected_style()
         } else {
             debug_assert!(self.is_text_node());
-            // TODO(stshine): What should the selected style be for text?
-            self.parent_style()
+            self.parent_selected_style()
         }
     }
 
@@ -21,7 +21,7 @@ pub mod serializable;
 pub mod transferable;
 pub mod webdriver_msg;
 
-use crate::serializable::BlobImpl;
+use crate::serializable::{BlobData, BlobImpl};
 use crate::transferable::MessagePortImpl;
 use crate::webdriver_msg::{LoadStatus, WebDriverScriptCommand};
 use bluetooth_traits::BluetoothRequest;
@@ -73,11 +73,15 @@ use webvr_traits::{WebVREvent, WebVRMsg};
 
 pub use crate::script_msg::{
     DOMMessage, HistoryEntryReplacement, SWManagerMsg, SWManagerSenders, ScopeThings,
-    ServiceWorkerMsg,
+    ServiceWorkerClientInfo, ServiceWorkerLifecycleState, ServiceWorkerMsg, SharedWorkerKey,
+    SharedWorkerMsg,
 };
 pub use crate::script_msg::{
     EventResult, IFrameSize, IFrameSizeMsg, LayoutMsg, LogEntry, ScriptMsg,
 };
+pub use crate::script_msg::{
+    WebLockEvent, WebLockId, WebLockInfo, WebLockMode, WebLockRequest, WebLocksSnapshot,
+};
 
 /// The address of a node. Layout sends these back. They must be validated via
 /// `from_untrusted_node_address` before they can be used, because we do not trust layout.
@@ -179,6 +183,9 @@ pub struct LoadData {
 
     /// The source to use instead of a network response for a srcdoc document.
     pub srcdoc: String,
+    /// The origins of the documents of the ancestors of the browsing context being loaded
+    /// into, nearest first, which `frame-ancestors` directives are checked against.
+    pub ancestor_origins: Vec<ImmutableOrigin>,
 }
 
 /// The result of evaluating a javascript scheme url.
@@ -211,6 +218,7 @@ impl LoadData {
             referrer: referrer,
             referrer_policy: referrer_policy,
             srcdoc: "".to_string(),
+            ancestor_origins: vec![],
         }
     }
 }
@@ -293,6 +301,10 @@ pub enum ConstellationControlMsg {
     /// Sends the final response to script thread for fetching after all redirections
     /// have been resolved
     NavigationResponse(PipelineId, FetchResponseMsg),
+    /// The document being loaded in the pipeline moved to a new browsing context group
+    /// because of its cross-origin opener policy, which severs it from its opener.
+    /// https://html.spec.whatwg.org/multipage/#browsing-context-group-switches-due-to-cross-origin-opener-policy
+    SeverOpener(PipelineId),
     /// Gives a channel and ID to a layout thread, as well as the ID of that layout's parent
     AttachLayout(NewLayoutInfo),
     /// Window resized.  Sends a DOM event eventually, but first we combine events.
@@ -403,6 +415,11 @@ pub enum ConstellationControlMsg {
     PaintMetric(PipelineId, ProgressiveWebMetricType, u64),
     /// Notifies the media session about a user requested media session action.
     MediaSessionAction(PipelineId, MediaSessionActionType),
+    /// Update the state of the service worker of a scope.
+    UpdateServiceWorkerState(ServoUrl, ServiceWorkerLifecycleState),
+    /// Set the controller of a window to the service worker of a scope, with the given
+    /// script URL, firing `controllerchange` if the service worker claimed it.
+    SetServiceWorkerController(PipelineId, ServoUrl, ServoUrl, bool),
 }
 
 impl fmt::Debug for ConstellationControlMsg {
@@ -411,6 +428,7 @@ impl fmt::Debug for ConstellationControlMsg {
         let variant = match *self {
             StopDelayingLoadEventsMode(..) => "StopDelayingLoadsEventMode",
             NavigationResponse(..) => "NavigationResponse",
+            SeverOpener(..) => "SeverOpener",
             AttachLayout(..) => "AttachLayout",
             Resize(..) => "Resize",
             ResizeInactive(..) => "ResizeInactive",
@@ -442,6 +460,8 @@ impl fmt::Debug for ConstellationControlMsg {
             PaintMetric(..) => "PaintMetric",
             ExitFullScreen(..) => "ExitFullScreen",
             MediaSessionAction(..) => "MediaSessionAction",
+            UpdateServiceWorkerState(..) => "UpdateServiceWorkerState",
+            SetServiceWorkerController(..) => "SetServiceWorkerController",
         };
         write!(formatter, "ConstellationControlMsg::{}", variant)
     }
@@ -955,6 +975,58 @@ pub struct StructuredSerializedData {
     pub ports: Option<HashMap<MessagePortId, MessagePortImpl>>,
 }
 
+impl StructuredSerializedData {
+    /// Clone the serialized data, for delivery to more than one broadcast-channel.
+    /// Only data without transferred objects can be cloned.
+    pub fn clone_for_broadcast(&self) -> StructuredSerializedData {
+        let blobs = self.blobs.as_ref().map(|blobs| {
+            blobs
+                .iter()
+                .filter_map(|(blob_id, blob)| match blob.blob_data() {
+                    // Keep the original id as key, it is the one found in `serialized`,
+                    // while the clone gets an id of its own.
+                    BlobData::Memory(bytes) => Some((
+                        blob_id.clone(),
+                        BlobImpl::new_from_bytes(bytes.clone(), blob.type_string()),
+                    )),
+                    _ => {
+                        warn!("Serialized blob not backed by memory, not broadcasting it.");
+                        None
+                    },
+                })
+                .collect()
+        });
+        debug_assert!(self.ports.is_none());
+        StructuredSerializedData {
+            serialized: self.serialized.clone(),
+            blobs,
+            ports: None,
+        }
+    }
+}
+
+/// A message sent on a broadcast-channel.
+/// <https://html.spec.whatwg.org/multipage/#broadcasting-to-other-browsing-contexts>
+#[derive(Debug, Deserialize, MallocSizeOf, Serialize)]
+pub struct BroadcastMsg {
+    /// The origin of the channel, and of the sender.
+    pub origin: ImmutableOrigin,
+    /// The name of the channel.
+    pub channel_name: String,
+    /// The serialized message.
+    pub data: StructuredSerializedData,
+}
+
+impl Clone for BroadcastMsg {
+    fn clone(&self) -> BroadcastMsg {
+        BroadcastMsg {
+            origin: self.origin.clone(),
+            channel_name: self.channel_name.clone(),
+            data: self.data.clone_for_broadcast(),
+        }
+    }
+}
+
 /// A task on the https://html.spec.whatwg.org/multipage/#port-message-queue
 #[derive(Debug, Deserialize, MallocSizeOf, Serialize)]
 pub struct PortMessageTask {
@@ -4,6 +4,7 @@
 
 use crate::AnimationState;
 use crate::AuxiliaryBrowsingContextLoadInfo;
+use crate::BroadcastMsg;
 use crate::DocumentState;
 use crate::IFrameLoadInfoWithData;
 use crate::LayoutControlMsg;
@@ -22,7 +23,8 @@ use euclid::Size2D;
 use gfx_traits::Epoch;
 use ipc_channel::ipc::{IpcReceiver, IpcSender};
 use msg::constellation_msg::{
-    BrowsingContextId, MessagePortId, MessagePortRouterId, PipelineId, TopLevelBrowsingContextId,
+    BroadcastChannelRouterId, BrowsingContextId, MessagePortId, MessagePortRouterId, PipelineId,
+    TopLevelBrowsingContextId,
 };
 use msg::constellation_msg::{HistoryStateId, TraversalDirection};
 use net_traits::request::RequestBuilder;
@@ -35,6 +37,7 @@ use std::collections::{HashMap, VecDeque};
 use std::fmt;
 use style_traits::viewport::ViewportConstraints;
 use style_traits::CSSPixel;
+use uuid::Uuid;
 use webgpu::{wgpu, WebGPUResponseResult};
 use webrender_api::units::{DeviceIntPoint, DeviceIntSize};
 
@@ -142,6 +145,20 @@ pub enum ScriptMsg {
     RemoveMessagePort(MessagePortId),
     /// Entangle two message-ports.
     EntanglePorts(MessagePortId, MessagePortId),
+    /// A global has started managing broadcast-channels.
+    NewBroadcastChannelRouter(
+        BroadcastChannelRouterId,
+        IpcSender<BroadcastMsg>,
+        ImmutableOrigin,
+    ),
+    /// A global has stopped managing broadcast-channels.
+    RemoveBroadcastChannelRouter(BroadcastChannelRouterId, ImmutableOrigin),
+    /// A global started listening on a broadcast-channel name.
+    NewBroadcastChannelNameInRouter(BroadcastChannelRouterId, String, ImmutableOrigin),
+    /// A global stopped listening on a broadcast-channel name.
+    RemoveBroadcastChannelNameInRouter(BroadcastChannelRouterId, String, ImmutableOrigin),
+    /// Broadcast a message to all other same-origin globals listening on the channel.
+    ScheduleBroadcast(BroadcastChannelRouterId, BroadcastMsg),
     /// Forward a message to the embedder.
     ForwardToEmbedder(EmbedderMsg),
     /// Requests are sent to constellation and fetches are checked manually
@@ -250,6 +267,27 @@ pub enum ScriptMsg {
     ForwardDOMMessage(DOMMessage, ServoUrl),
     /// Store the data required to activate a service worker for the given scope
     RegisterServiceWorker(ScopeThings, ServoUrl),
+    /// Connect the transferred port to the shared worker with the given key,
+    /// replying whether the sender has to start the shared worker.
+    /// The last sender is notified if the worker fails to load its script.
+    ConnectSharedWorker(
+        SharedWorkerKey,
+        StructuredSerializedData,
+        IpcSender<bool>,
+        IpcSender<()>,
+    ),
+    /// A shared worker started, and can be sent connections.
+    SharedWorkerStarted(SharedWorkerKey, WorkerId, IpcSender<SharedWorkerMsg>),
+    /// A shared worker loaded its script.
+    SharedWorkerLoaded(SharedWorkerKey, WorkerId),
+    /// A shared worker closed, or failed to load its script.
+    SharedWorkerClosed(SharedWorkerKey, WorkerId),
+    /// Request a lock from the lock manager of an origin.
+    RequestWebLock(WebLockRequest),
+    /// Release a held lock, or abort a pending request for one.
+    ReleaseWebLock(ImmutableOrigin, WebLockId),
+    /// Get the held locks and the pending requests of an origin.
+    QueryWebLocks(ImmutableOrigin, IpcSender<WebLocksSnapshot>),
     /// Get Window Informations size and position
     GetClientWindow(IpcSender<(DeviceIntSize, DeviceIntPoint)>),
     /// Get the screen size (pixel)
@@ -279,6 +317,11 @@ impl fmt::Debug for ScriptMsg {
             RerouteMessagePort(..) => "RerouteMessagePort",
             RemoveMessagePort(..) => "RemoveMessagePort",
             MessagePortShipped(..) => "MessagePortShipped",
+            NewBroadcastChannelRouter(..) => "NewBroadcastChannelRouter",
+            RemoveBroadcastChannelRouter(..) => "RemoveBroadcastChannelRouter",
+            NewBroadcastChannelNameInRouter(..) => "NewBroadcastChannelNameInRouter",
+            RemoveBroadcastChannelNameInRouter(..) => "RemoveBroadcastChannelNameInRouter",
+            ScheduleBroadcast(..) => "ScheduleBroadcast",
             EntanglePorts(..) => "EntanglePorts",
             ForwardToEmbedder(..) => "ForwardToEmbedder",
             InitiateNavigateRequest(..) => "InitiateNavigateRequest",
@@ -313,6 +356,13 @@ impl fmt::Debug for ScriptMsg {
             PipelineExited => "PipelineExited",
             ForwardDOMMessage(..) => "ForwardDOMMessage",
             RegisterServiceWorker(..) => "RegisterServiceWorker",
+            ConnectSharedWorker(..) => "ConnectSharedWorker",
+            SharedWorkerStarted(..) => "SharedWorkerStarted",
+            SharedWorkerLoaded(..) => "SharedWorkerLoaded",
+            SharedWorkerClosed(..) => "SharedWorkerClosed",
+            RequestWebLock(..) => "RequestWebLock",
+            ReleaseWebLock(..) => "ReleaseWebLock",
+            QueryWebLocks(..) => "QueryWebLocks",
             GetClientWindow(..) => "GetClientWindow",
             GetScreenSize(..) => "GetScreenSize",
             GetScreenAvailSize(..) => "GetScreenAvailSize",
@@ -364,6 +414,18 @@ pub enum ServiceWorkerMsg {
     Timeout(ServoUrl),
     /// Message sent by constellation to forward to a running service worker
     ForwardDOMMessage(DOMMessage, ServoUrl),
+    /// Sent by a service worker once its install event and the promises passed to
+    /// `waitUntil` have settled, with whether it was installed
+    InstallFinished(ServoUrl, bool),
+    /// Sent by a service worker once its activate event and the promises passed to
+    /// `waitUntil` have settled
+    ActivateFinished(ServoUrl),
+    /// Get the clients in the scope of a service worker, including those it doesn't
+    /// control if the flag is set
+    MatchClients(ServoUrl, bool, IpcSender<Vec<ServiceWorkerClientInfo>>),
+    /// Make the service worker of a scope control every client in the scope,
+    /// replying whether it is active
+    Claim(ServoUrl, IpcSender<bool>),
     /// Exit the service worker manager
     Exit,
 }
@@ -373,4 +435,116 @@ pub enum ServiceWorkerMsg {
 pub enum SWManagerMsg {
     /// Provide the constellation with a means of communicating with the Service Worker Manager
     OwnSender(IpcSender<ServiceWorkerMsg>),
+    /// Tell every script thread about the new state of the service worker of a scope
+    UpdateState(ServoUrl, ServiceWorkerLifecycleState),
+    /// Get the windows in a scope
+    MatchClients(ServoUrl, IpcSender<Vec<ServiceWorkerClientInfo>>),
+    /// Tell a window that the service worker of a scope, with the given script URL, controls it.
+    /// The flag is set if the service worker claimed the window after it was created.
+    SetController(PipelineId, ServoUrl, ServoUrl, bool),
+}
+
+/// <https://w3c.github.io/ServiceWorker/#dfn-state>
+#[derive(Clone, Copy, Debug, Deserialize, Eq, MallocSizeOf, PartialEq, Serialize)]
+pub enum ServiceWorkerLifecycleState {
+    Installing,
+    Installed,
+    Activating,
+    Activated,
+    Redundant,
+}
+
+/// A window that a service worker sees as a client
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ServiceWorkerClientInfo {
+    pub pipeline_id: PipelineId,
+    pub url: ServoUrl,
+    /// Whether the window is a top-level browsing context rather than a nested one
+    pub top_level: bool,
+}
+
+/// What identifies a shared worker, which every same-origin global
+/// constructing a `SharedWorker` with the same URL and name connects to.
+/// <https://html.spec.whatwg.org/multipage/#dom-sharedworker>
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct SharedWorkerKey {
+    /// The origin of the globals connecting to the shared worker.
+    pub origin: ImmutableOrigin,
+    /// The URL of the script of the shared worker.
+    pub url: ServoUrl,
+    /// The name of the shared worker.
+    pub name: String,
+}
+
+/// Messages sent by the constellation to a running shared worker.
+#[derive(Debug, Deserialize, Serialize)]
+pub enum SharedWorkerMsg {
+    /// A new client connected, with the transferred inside port.
+    Connect(StructuredSerializedData),
+    /// The last client went away, terminate the worker.
+    Terminate,
+}
+
+/// <https://wicg.github.io/web-locks/#enumdef-lockmode>
+#[derive(Clone, Copy, Debug, Deserialize, Eq, MallocSizeOf, PartialEq, Serialize)]
+pub enum WebLockMode {
+    Shared,
+    Exclusive,
+}
+
+/// Identifies a request for a lock, and the lock once it is granted.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, MallocSizeOf, PartialEq, Serialize)]
+pub struct WebLockId(pub Uuid);
+
+impl WebLockId {
+    pub fn new() -> WebLockId {
+        WebLockId(Uuid::new_v4())
+    }
+}
+
+/// A request for a lock, made through `navigator.locks.request`.
+/// <https://wicg.github.io/web-locks/#lock-request>
+#[derive(Debug, Deserialize, Serialize)]
+pub struct WebLockRequest {
+    pub id: WebLockId,
+    /// The origin whose lock manager handles the request.
+    pub origin: ImmutableOrigin,
+    pub name: String,
+    pub mode: WebLockMode,
+    /// The id of the client that made the request, as reported by `query`.
+    pub client_id: String,
+    /// Whether to give up, rather than wait, if the lock can't be granted right away.
+    pub if_available: bool,
+    /// Whether to release the held locks with the same name and be granted first.
+    pub steal: bool,
+    /// Where the lock manager tells the client what happened to the request.
+    pub sender: IpcSender<WebLockEvent>,
+}
+
+/// What happened to a request for a lock.
+#[derive(Debug, Deserialize, Serialize)]
+pub enum WebLockEvent {
+    /// The lock was granted.
+    Granted,
+    /// The request asked for the lock only if available, and it wasn't.
+    NotAvailable,
+    /// The lock was granted, and then released by a request that stole it.
+    Stolen,
+}
+
+/// A held lock or a pending request, as reported by `query`.
+/// <https://wicg.github.io/web-locks/#dictdef-lockinfo>
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct WebLockInfo {
+    pub name: String,
+    pub mode: WebLockMode,
+    pub client_id: String,
+}
+
+/// The state of the lock manager of an origin.
+/// <https://wicg.github.io/web-locks/#dictdef-lockmanagersnapshot>
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct WebLocksSnapshot {
+    pub held: Vec<WebLockInfo>,
+    pub pending: Vec<WebLockInfo>,
 }
@@ -735,6 +735,36 @@ where
                     );
                 }
             },
+
+            WindowEvent::CancelDownload(id) => {
+                let msg = ConstellationMsg::CancelDownload(id);
+                if let Err(e) = self.constellation_chan.send(msg) {
+                    warn!(
+                        "Sending CancelDownload message to constellation failed ({:?}).",
+                        e
+                    );
+                }
+            },
+
+            WindowEvent::ResumeDownload(id) => {
+                let msg = ConstellationMsg::ResumeDownload(id);
+                if let Err(e) = self.constellation_chan.send(msg) {
+                    warn!(
+                        "Sending ResumeDownload message to constellation failed ({:?}).",
+                        e
+                    );
+                }
+            },
+
+            WindowEvent::ClearOriginData(origin) => {
+                let msg = ConstellationMsg::ClearOriginData(origin);
+                if let Err(e) = self.constellation_chan.send(msg) {
+                    warn!(
+                        "Sending ClearOriginData message to constellation failed ({:?}).",
+                        e
+                    );
+                }
+            },
         }
     }
 
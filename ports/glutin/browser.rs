@@ -7,7 +7,9 @@ use crate::window_trait::{WindowPortsMethods, LINE_HEIGHT};
 use euclid::{Point2D, Vector2D};
 use keyboard_types::{Key, KeyboardEvent, Modifiers, ShortcutMatcher};
 use servo::compositing::windowing::{WebRenderDebugOption, WindowEvent};
-use servo::embedder_traits::{EmbedderMsg, FilterPattern, PromptDefinition, PromptOrigin, PromptResult};
+use servo::embedder_traits::{AuthenticationChallenge, AuthenticationScheme, ClipboardContents};
+use servo::embedder_traits::{EmbedderMsg, FilterPattern};
+use servo::embedder_traits::{DownloadStatus, PromptCredentials, PromptDefinition, PromptOrigin, PromptResult};
 use servo::msg::constellation_msg::TopLevelBrowsingContextId as BrowserId;
 use servo::msg::constellation_msg::TraversalDirection;
 use servo::net_traits::pub_domains::is_reg_domain;
@@ -21,6 +23,7 @@ use std::env;
 use std::fs::File;
 use std::io::Write;
 use std::mem;
+use std::path::PathBuf;
 use std::rc::Rc;
 use std::thread;
 use std::time::Duration;
@@ -314,6 +317,9 @@ where
                             PromptDefinition::Input(_message, default, sender) => {
                                 sender.send(Some(default.to_owned()))
                             }
+                            PromptDefinition::Credentials(_challenge, sender) => {
+                                sender.send(None)
+                            }
                         }
                     } else {
                         thread::Builder::new()
@@ -363,6 +369,16 @@ where
                                         let result = tinyfiledialogs::input_box("", &message, &default);
                                         sender.send(result)
                                     }
+                                    PromptDefinition::Credentials(challenge, sender) => {
+                                        let message = tiny_dialog_escape(&credentials_prompt_message(&challenge));
+                                        let title = "Authentication Required";
+                                        let credentials = tinyfiledialogs::input_box(title, &message, "")
+                                            .and_then(|user_name| {
+                                                tinyfiledialogs::password_box(title, &message)
+                                                    .map(|password| PromptCredentials { user_name, password })
+                                            });
+                                        sender.send(credentials)
+                                    }
                                 }
                             })
                             .unwrap()
@@ -411,24 +427,25 @@ where
                     self.handle_key_from_servo(browser_id, key_event);
                 },
                 EmbedderMsg::GetClipboardContents(sender) => {
+                    // Only text can be exchanged with the system clipboard.
                     let contents = match self.clipboard_ctx {
                         Some(ref mut ctx) => {
                             match ctx.get_contents() {
-                                Ok(c) => c,
+                                Ok(c) => ClipboardContents::from_text(c),
                                 Err(e) => {
                                     warn!("Error getting clipboard contents ({}), defaulting to empty string", e);
-                                    "".to_owned()
+                                    ClipboardContents::from_text("".to_owned())
                                 },
                             }
                         },
-                        None => "".to_owned(),
+                        None => ClipboardContents::from_text("".to_owned()),
                     };
                     if let Err(e) = sender.send(contents) {
                         warn!("Failed to send clipboard ({})", e);
                     }
                 }
-                EmbedderMsg::SetClipboardContents(text) => {
-                    if let Some(ref mut ctx) = self.clipboard_ctx {
+                EmbedderMsg::SetClipboardContents(contents) => {
+                    if let (Some(ctx), Some(text)) = (self.clipboard_ctx.as_mut(), contents.text()) {
                         if let Err(e) = ctx.set_contents(text) {
                             warn!("Error setting clipboard contents ({})", e);
                         }
@@ -508,6 +525,33 @@ where
                     debug!("MediaSessionEvent received");
                     // TODO(ferjm): MediaSession support for Glutin based browsers.
                 },
+                EmbedderMsg::DownloadStarted(id, url, filename, sender) => {
+                    let default_path = default_download_path(&filename);
+                    let path = if opts::get().headless {
+                        Some(default_path)
+                    } else {
+                        get_download_path(default_path)
+                    };
+                    match path {
+                        Some(ref path) => info!("Download {:?}: saving {} to {}.", id, url, path),
+                        None => info!("Download {:?}: {} declined.", id, url),
+                    }
+                    if let Err(e) = sender.send(path) {
+                        let reason = format!("Failed to send DownloadStarted response: {}", e);
+                        self.event_queue.push(WindowEvent::SendError(None, reason));
+                    }
+                },
+                EmbedderMsg::DownloadProgress(id, received, total) => match total {
+                    Some(total) => debug!("Download {:?}: {} of {} bytes", id, received, total),
+                    None => debug!("Download {:?}: {} bytes", id, received),
+                },
+                EmbedderMsg::DownloadFinished(id, status) => match status {
+                    DownloadStatus::Complete => info!("Download {:?} complete.", id),
+                    DownloadStatus::Cancelled => info!("Download {:?} cancelled.", id),
+                    DownloadStatus::Failed(reason) => {
+                        warn!("Download {:?} failed: {}", id, reason)
+                    },
+                },
             }
         }
     }
@@ -579,6 +623,29 @@ fn get_selected_files(patterns: Vec<FilterPattern>, multiple_files: bool) -> Opt
         .expect("Thread spawning failed")
 }
 
+/// The file a download is saved to unless the user picks another one: the
+/// user's download directory, falling back to their home and the current directory.
+fn default_download_path(filename: &str) -> String {
+    dirs::download_dir()
+        .or_else(dirs::home_dir)
+        .or_else(|| env::current_dir().ok())
+        .unwrap_or_else(PathBuf::new)
+        .join(filename)
+        .to_string_lossy()
+        .into_owned()
+}
+
+fn get_download_path(default_path: String) -> Option<String> {
+    thread::Builder::new()
+        .name("Save file".to_owned())
+        .spawn(move || {
+            tinyfiledialogs::save_file_dialog("Save file", &tiny_dialog_escape(&default_path))
+        })
+        .unwrap()
+        .join()
+        .expect("Thread spawning failed")
+}
+
 fn sanitize_url(request: &str) -> Option<ServoUrl> {
     let request = request.trim();
     ServoUrl::parse(&request)
@@ -596,6 +663,22 @@ fn sanitize_url(request: &str) -> Option<ServoUrl> {
         })
 }
 
+fn credentials_prompt_message(challenge: &AuthenticationChallenge) -> String {
+    let host = challenge.url.host_str().unwrap_or("");
+    let mut message = if challenge.is_proxy {
+        format!("The proxy for {} requires a user name and password.", host)
+    } else {
+        format!("{} requires a user name and password.", host)
+    };
+    if let Some(ref realm) = challenge.realm {
+        message.push_str(&format!(" The site says: \"{}\"", realm));
+    }
+    if challenge.scheme == AuthenticationScheme::Basic && challenge.url.scheme() != "https" {
+        message.push_str(" Your password will be sent unencrypted.");
+    }
+    message
+}
+
 // This is a mitigation for #25498, not a verified solution.
 // There may be codepaths in tinyfiledialog.c that this is
 // inadquate against, as it passes the string via shell to
@@ -16,7 +16,9 @@ use servo::compositing::windowing::{
     WindowMethods,
 };
 use servo::embedder_traits::resources::{self, Resource, ResourceReaderMethods};
-use servo::embedder_traits::{EmbedderMsg, MediaSessionEvent, PromptDefinition, PromptOrigin};
+use servo::embedder_traits::{
+    ClipboardContents, EmbedderMsg, MediaSessionEvent, PromptDefinition, PromptOrigin,
+};
 use servo::euclid::{Point2D, Rect, Scale, Size2D, Vector2D};
 use servo::keyboard_types::{Key, KeyState, KeyboardEvent};
 use servo::msg::constellation_msg::TraversalDirection;
@@ -562,6 +564,9 @@ impl ServoGlue {
                         PromptDefinition::Input(message, default, sender) => {
                             sender.send(cb.prompt_input(message, default, trusted))
                         },
+                        // TODO: let the host prompt for credentials. Until then,
+                        // behave as if the user declined to enter any.
+                        PromptDefinition::Credentials(_, sender) => sender.send(None),
                     };
                     if let Err(e) = res {
                         let reason = format!("Failed to send Prompt response: {}", e);
@@ -585,10 +590,13 @@ impl ServoGlue {
                 },
                 EmbedderMsg::GetClipboardContents(sender) => {
                     let contents = self.callbacks.host_callbacks.get_clipboard_contents();
-                    let _ = sender.send(contents.unwrap_or("".to_owned()));
+                    let contents = ClipboardContents::from_text(contents.unwrap_or("".to_owned()));
+                    let _ = sender.send(contents);
                 },
-                EmbedderMsg::SetClipboardContents(text) => {
-                    self.callbacks.host_callbacks.set_clipboard_contents(text);
+                EmbedderMsg::SetClipboardContents(contents) => {
+                    if let Some(text) = contents.text() {
+                        self.callbacks.host_callbacks.set_clipboard_contents(text);
+                    }
                 },
                 EmbedderMsg::CloseBrowser => {
                     // TODO: close the appropriate "tab".
@@ -643,7 +651,10 @@ impl ServoGlue {
                 EmbedderMsg::HeadParsed |
                 EmbedderMsg::SetFullscreenState(..) |
                 EmbedderMsg::Panic(..) |
-                EmbedderMsg::ReportProfile(..) => {},
+                EmbedderMsg::ReportProfile(..) |
+                EmbedderMsg::DownloadStarted(..) |
+                EmbedderMsg::DownloadProgress(..) |
+                EmbedderMsg::DownloadFinished(..) => {},
             }
         }
         Ok(())
@@ -0,0 +1,66 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use keyboard_types::{Key, KeyState, KeyboardEvent};
+use script::user_activation::{
+    is_activation_triggering_key_event, UserActivation, TRANSIENT_ACTIVATION_DURATION,
+};
+use std::time::{Duration, Instant};
+
+#[test]
+fn test_no_activation_is_not_transient() {
+    let activation = UserActivation::new();
+    assert!(!activation.is_transient_at(Instant::now()));
+}
+
+#[test]
+fn test_activation_is_transient_for_the_activation_duration() {
+    let activation = UserActivation::new();
+    let timestamp = Instant::now();
+    activation.notify(timestamp);
+
+    assert!(activation.is_transient_at(timestamp));
+    assert!(activation.is_transient_at(timestamp + TRANSIENT_ACTIVATION_DURATION / 2));
+    assert!(!activation.is_transient_at(timestamp + TRANSIENT_ACTIVATION_DURATION));
+    assert!(!activation
+        .is_transient_at(timestamp + TRANSIENT_ACTIVATION_DURATION + Duration::from_secs(1)));
+}
+
+#[test]
+fn test_activation_is_not_transient_before_it_happened() {
+    let now = Instant::now();
+    let activation = UserActivation::new();
+    activation.notify(now + Duration::from_secs(1));
+    assert!(!activation.is_transient_at(now));
+}
+
+#[test]
+fn test_later_activation_extends_transient_activation() {
+    let activation = UserActivation::new();
+    let timestamp = Instant::now();
+    activation.notify(timestamp);
+    activation.notify(timestamp + TRANSIENT_ACTIVATION_DURATION);
+    assert!(activation.is_transient_at(timestamp + TRANSIENT_ACTIVATION_DURATION));
+}
+
+#[test]
+fn test_activation_triggering_key_events() {
+    let keydown = |key| KeyboardEvent {
+        state: KeyState::Down,
+        key,
+        ..Default::default()
+    };
+    assert!(is_activation_triggering_key_event(&keydown(
+        Key::Character("a".to_owned())
+    )));
+    assert!(is_activation_triggering_key_event(&keydown(Key::Enter)));
+    assert!(!is_activation_triggering_key_event(&keydown(Key::Escape)));
+
+    let keyup = KeyboardEvent {
+        state: KeyState::Up,
+        key: Key::Character("a".to_owned()),
+        ..Default::default()
+    };
+    assert!(!is_activation_triggering_key_event(&keyup));
+}
@@ -7,7 +7,10 @@ importScripts("interfaces.js");
 
 // IMPORTANT: Do not change the list below without review from a DOM peer!
 test_interfaces([
+  "AbortController",
+  "AbortSignal",
   "Blob",
+  "BroadcastChannel",
   "CanvasGradient",
   "CanvasPattern",
   "CloseEvent",
@@ -19,6 +22,7 @@ test_interfaces([
   "DOMRect",
   "DOMRectReadOnly",
   "DOMStringList",
+  "CryptoKey",
   "CustomEvent",
   "DedicatedWorkerGlobalScope",
   "DOMException",
@@ -34,6 +38,8 @@ test_interfaces([
   "Headers",
   "History",
   "ImageData",
+  "Lock",
+  "LockManager",
   "MessageChannel",
   "MessageEvent",
   "MessagePort",
@@ -47,10 +53,21 @@ test_interfaces([
   "PerformanceResourceTiming",
   "ProgressEvent",
   "PromiseRejectionEvent",
+  "ReadableByteStreamController",
+  "ReadableStream",
+  "ReadableStreamBYOBReader",
+  "ReadableStreamBYOBRequest",
+  "ReadableStreamDefaultController",
+  "ReadableStreamDefaultReader",
   "Request",
   "Response",
+  "SecurityPolicyViolationEvent",
+  "StorageManager",
+  "SubtleCrypto",
   "TextDecoder",
   "TextEncoder",
+  "TransformStream",
+  "TransformStreamDefaultController",
   "URL",
   "URLSearchParams",
   "WebSocket",
@@ -58,6 +75,9 @@ test_interfaces([
   "WorkerGlobalScope",
   "WorkerLocation",
   "WorkerNavigator",
+  "WritableStream",
+  "WritableStreamDefaultController",
+  "WritableStreamDefaultWriter",
   "XMLHttpRequest",
   "XMLHttpRequestEventTarget",
   "XMLHttpRequestUpload",